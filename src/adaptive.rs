@@ -0,0 +1,293 @@
+#[cfg(feature = "network")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "network")]
+use anyhow::Error;
+#[cfg(feature = "network")]
+use gst::prelude::*;
+
+#[cfg(feature = "network")]
+const MANIFEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// One quality level of an HLS or DASH stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variant {
+    /// Bits per second as advertised by the manifest
+    pub bandwidth: u64,
+    pub height: Option<u32>,
+}
+
+impl Variant {
+    pub fn label(&self) -> String {
+        let bitrate = format!("{:.1} Mbps", self.bandwidth as f64 / 1_000_000.0);
+        match self.height {
+            Some(height) => format!("{}p ({})", height, bitrate),
+            None => bitrate,
+        }
+    }
+}
+
+/// Whether the uri points to an HLS playlist or a DASH manifest
+#[cfg(feature = "network")]
+pub fn is_adaptive_uri(uri: &str) -> bool {
+    let path = uri.split(['?', '#']).next().unwrap_or(uri).to_lowercase();
+    path.ends_with(".m3u8") || path.ends_with(".mpd")
+}
+
+/// Downloads the manifest and lists its video variants, highest bandwidth first. `configure`
+/// gets the source element before it connects, for the headers and login of the stream.
+#[cfg(feature = "network")]
+pub fn fetch_variants(
+    uri: &str,
+    configure: impl FnOnce(&gst::Element),
+) -> Result<Vec<Variant>, Error> {
+    let manifest = fetch_manifest(uri, configure)?;
+    Ok(parse_variants(&manifest))
+}
+
+#[cfg(feature = "network")]
+fn parse_variants(manifest: &str) -> Vec<Variant> {
+    let mut variants = if manifest.trim_start().starts_with("#EXTM3U") {
+        parse_hls(manifest)
+    } else {
+        parse_dash(manifest)
+    };
+    variants.sort_by_key(|variant| std::cmp::Reverse(variant.bandwidth));
+    variants.dedup();
+    variants
+}
+
+/// The variant that is playing, by the height of the decoded video. Variants of the same height
+/// are told apart by the bitrate of the stream, None when that isn't known yet.
+pub fn active_variant(variants: &[Variant], height: u32, bitrate: Option<u32>) -> Option<usize> {
+    let mut candidates = variants
+        .iter()
+        .enumerate()
+        .filter(|(_, variant)| variant.height == Some(height))
+        .peekable();
+    let (first, _) = candidates.next()?;
+    if candidates.peek().is_none() {
+        return Some(first);
+    }
+    // the advertised bandwidth is a peak, the stream usually stays a bit below it
+    let bitrate = u64::from(bitrate?);
+    std::iter::once((first, &variants[first]))
+        .chain(candidates)
+        .min_by_key(|(_, variant)| variant.bandwidth.abs_diff(bitrate))
+        .map(|(index, _)| index)
+}
+
+/// Reads the whole file with whatever source element handles the uri scheme
+#[cfg(feature = "network")]
+fn fetch_manifest(uri: &str, configure: impl FnOnce(&gst::Element)) -> Result<String, Error> {
+    let source = gst::Element::make_from_uri(gst::URIType::Src, uri, None)?;
+    configure(&source);
+    let sink = gst_app::AppSink::builder().sync(false).build();
+
+    let pipeline = gst::Pipeline::new(None);
+    pipeline.add_many(&[&source, sink.upcast_ref()])?;
+    source.link(&sink)?;
+    pipeline.set_state(gst::State::Playing)?;
+
+    let mut manifest = Vec::new();
+    let deadline = Instant::now() + MANIFEST_TIMEOUT;
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+        let timeout = gst::ClockTime::from_nseconds(remaining.as_nanos() as u64);
+        let Some(sample) = sink.try_pull_sample(timeout) else {
+            break;
+        };
+        if let Some(buffer) = sample.buffer() {
+            manifest.extend_from_slice(buffer.map_readable()?.as_slice());
+        }
+    }
+
+    pipeline.set_state(gst::State::Null)?;
+
+    Ok(String::from_utf8_lossy(&manifest).into_owned())
+}
+
+/// Variants of an HLS master playlist, a media playlist has none
+#[cfg(feature = "network")]
+fn parse_hls(playlist: &str) -> Vec<Variant> {
+    playlist
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("#EXT-X-STREAM-INF:"))
+        .filter_map(|attributes| {
+            let mut variant = Variant {
+                bandwidth: 0,
+                height: None,
+            };
+            for (name, value) in split_hls_attributes(attributes) {
+                match name {
+                    "BANDWIDTH" => variant.bandwidth = value.parse().ok()?,
+                    "RESOLUTION" => {
+                        let (_, height) = value.split_once('x')?;
+                        variant.height = height.parse().ok();
+                    }
+                    _ => {}
+                }
+            }
+            (variant.bandwidth > 0).then_some(variant)
+        })
+        .collect()
+}
+
+/// Splits `NAME=value,NAME="quoted, value"` into its pairs
+#[cfg(feature = "network")]
+fn split_hls_attributes(attributes: &str) -> Vec<(&str, &str)> {
+    let mut pairs = Vec::new();
+    let mut rest = attributes;
+
+    while let Some((name, value)) = rest.split_once('=') {
+        let (value, next) = if let Some(quoted) = value.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            let next = quoted[end..].trim_start_matches('"');
+            (&quoted[..end], next)
+        } else {
+            let end = value.find(',').unwrap_or(value.len());
+            (&value[..end], &value[end..])
+        };
+
+        pairs.push((name.trim(), value));
+        rest = next.trim_start_matches(',');
+    }
+
+    pairs
+}
+
+/// Video representations of a DASH manifest, found without a full xml parser
+#[cfg(feature = "network")]
+fn parse_dash(manifest: &str) -> Vec<Variant> {
+    manifest
+        .split("<Representation")
+        .skip(1)
+        .filter_map(|representation| {
+            let tag = &representation[..representation.find('>')?];
+            let attribute = |name: &str| {
+                let pattern = format!("{}=\"", name);
+                let (index, _) = tag
+                    .match_indices(&pattern)
+                    .find(|(index, _)| tag[..*index].ends_with(char::is_whitespace))?;
+                let start = index + pattern.len();
+                let end = start + tag[start..].find('"')?;
+                Some(&tag[start..end])
+            };
+
+            // audio representations have no dimensions
+            let height = attribute("height")?.parse().ok()?;
+            Some(Variant {
+                bandwidth: attribute("bandwidth")?.parse().ok()?,
+                height: Some(height),
+            })
+        })
+        .collect()
+}
+
+#[cfg(all(test, feature = "network"))]
+mod tests {
+    use super::*;
+
+    fn variant(bandwidth: u64, height: Option<u32>) -> Variant {
+        Variant { bandwidth, height }
+    }
+
+    #[test]
+    fn recognizes_adaptive_uris() {
+        assert!(is_adaptive_uri("https://host/live/master.m3u8"));
+        assert!(is_adaptive_uri("https://host/Manifest.MPD?token=a.mp4"));
+        assert!(is_adaptive_uri("https://host/index.m3u8#t=10"));
+        assert!(!is_adaptive_uri("https://host/video.mp4?list=a.m3u8"));
+    }
+
+    #[test]
+    fn parses_hls_master_playlists() {
+        let playlist = "#EXTM3U\n\
+                        #EXT-X-STREAM-INF:BANDWIDTH=800000,RESOLUTION=640x360,CODECS=\"avc1.4d401e,mp4a.40.2\"\n\
+                        360p.m3u8\n\
+                        #EXT-X-STREAM-INF:CODECS=\"avc1.640028, mp4a.40.2\",RESOLUTION=1920x1080,BANDWIDTH=5000000\n\
+                        1080p.m3u8\n\
+                        #EXT-X-STREAM-INF:BANDWIDTH=64000,CODECS=\"mp4a.40.2\"\n\
+                        audio.m3u8\n\
+                        #EXT-X-STREAM-INF:BANDWIDTH=abc,RESOLUTION=1280x720\n\
+                        broken.m3u8\n\
+                        #EXT-X-STREAM-INF:RESOLUTION=1280x720\n\
+                        no-bandwidth.m3u8\n";
+        assert_eq!(
+            parse_hls(playlist),
+            vec![
+                variant(800_000, Some(360)),
+                variant(5_000_000, Some(1080)),
+                variant(64_000, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn media_playlists_have_no_variants() {
+        let playlist = "#EXTM3U\n#EXT-X-TARGETDURATION:10\n#EXTINF:10,\nsegment0.ts\n";
+        assert!(parse_hls(playlist).is_empty());
+    }
+
+    #[test]
+    fn splits_quoted_attributes() {
+        assert_eq!(
+            split_hls_attributes("A=1,B=\"x, y\",C=z"),
+            vec![("A", "1"), ("B", "x, y"), ("C", "z")]
+        );
+        assert_eq!(
+            split_hls_attributes("A=\"unterminated"),
+            vec![("A", "unterminated")]
+        );
+        assert!(split_hls_attributes("").is_empty());
+    }
+
+    #[test]
+    fn parses_dash_manifests() {
+        let manifest = r#"<MPD><Period>
+            <AdaptationSet mimeType="video/mp4">
+              <Representation id="1" bandwidth="1000000" width="1280" height="720"/>
+              <Representation id="2" maxheight="1" height="1080" bandwidth="4000000">
+              </Representation>
+              <Representation id="3" bandwidth="x" height="480"/>
+            </AdaptationSet>
+            <AdaptationSet mimeType="audio/mp4">
+              <Representation id="4" bandwidth="128000"/>
+            </AdaptationSet>
+            <Representation id="5" bandwidth="1"
+        </Period></MPD>"#;
+        assert_eq!(
+            parse_dash(manifest),
+            vec![
+                variant(1_000_000, Some(720)),
+                variant(4_000_000, Some(1080))
+            ]
+        );
+    }
+
+    #[test]
+    fn sorts_and_dedups_variants() {
+        let playlist = "#EXTM3U\n\
+                        #EXT-X-STREAM-INF:BANDWIDTH=800000,RESOLUTION=640x360\n360p.m3u8\n\
+                        #EXT-X-STREAM-INF:BANDWIDTH=5000000,RESOLUTION=1920x1080\n1080p.m3u8\n\
+                        #EXT-X-STREAM-INF:BANDWIDTH=800000,RESOLUTION=640x360\n360p-backup.m3u8\n";
+        assert_eq!(
+            parse_variants(playlist),
+            vec![variant(5_000_000, Some(1080)), variant(800_000, Some(360))]
+        );
+    }
+
+    #[test]
+    fn finds_the_active_variant() {
+        let variants = [
+            variant(6_000_000, Some(1080)),
+            variant(3_000_000, Some(1080)),
+            variant(1_500_000, Some(720)),
+        ];
+        assert_eq!(active_variant(&variants, 720, None), Some(2));
+        assert_eq!(active_variant(&variants, 480, Some(1_000_000)), None);
+        // the height alone doesn't tell the two 1080p variants apart
+        assert_eq!(active_variant(&variants, 1080, None), None);
+        assert_eq!(active_variant(&variants, 1080, Some(2_700_000)), Some(1));
+        assert_eq!(active_variant(&variants, 1080, Some(5_500_000)), Some(0));
+    }
+}
@@ -0,0 +1,252 @@
+use std::{
+    net::UdpSocket,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{Context, Error};
+
+use crate::config::AmbilightSettings;
+
+/// Upper bound of `AmbilightSettings::horizontal_zones` and `vertical_zones`
+pub const MAX_ZONES_PER_EDGE: u32 = 64;
+const MAX_ZONES: u64 = 4 * MAX_ZONES_PER_EDGE as u64;
+const ZONE_SIZE: u64 = std::mem::size_of::<[f32; 4]>() as u64;
+
+/// Has to match Params in ambilight.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct AmbilightParams {
+    horizontal_zones: u32,
+    vertical_zones: u32,
+    depth: f32,
+    decode_source: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Readback {
+    Idle,
+    /// The copy of this many zones is recorded, the buffer can be mapped once it is submitted
+    Recorded(u32),
+    Mapping(u32),
+    Mapped(u32),
+}
+
+/// Averages zones along the edges of every frame on the GPU, for LED strips behind the screen
+/// or theming. The colors arrive a frame or two late, they are read back without stalling.
+///
+/// Zones go clockwise from the top left corner: the top edge left to right, the right edge top
+/// to bottom, the bottom edge right to left and the left edge bottom to top.
+pub struct Ambilight {
+    settings: AmbilightSettings,
+    pipeline: wgpu::ComputePipeline,
+    params_buffer: wgpu::Buffer,
+    zones_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    /// Shared with the map_async callback
+    readback: Arc<Mutex<Readback>>,
+}
+
+impl Ambilight {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Ambilight Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("ambilight.wgsl").into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Ambilight Pipeline"),
+            layout: None,
+            module: &shader,
+            entry_point: "average_zones",
+        });
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Ambilight Params Buffer"),
+            size: std::mem::size_of::<AmbilightParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        // sized for the most zones, so changing the settings never has to wait for a readback
+        let zones_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Ambilight Zones Buffer"),
+            size: MAX_ZONES * ZONE_SIZE,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Ambilight Readback Buffer"),
+            size: MAX_ZONES * ZONE_SIZE,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            settings: AmbilightSettings::default(),
+            pipeline,
+            params_buffer,
+            zones_buffer,
+            readback_buffer,
+            readback: Arc::new(Mutex::new(Readback::Idle)),
+        }
+    }
+
+    pub fn set_settings(&mut self, settings: &AmbilightSettings) {
+        if settings != &self.settings {
+            self.settings = settings.clone();
+        }
+    }
+
+    fn zones_per_edge(&self) -> (u32, u32) {
+        (
+            self.settings.horizontal_zones.clamp(1, MAX_ZONES_PER_EDGE),
+            self.settings.vertical_zones.clamp(1, MAX_ZONES_PER_EDGE),
+        )
+    }
+
+    /// Records the averaging of `source`, skipped while the previous colors are still being read
+    /// back. `decode_source` is set when `source` holds encoded values textureLoad doesn't decode.
+    pub fn extract(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        source: &wgpu::TextureView,
+        decode_source: bool,
+    ) {
+        if !self.settings.enabled || *self.readback.lock().unwrap() != Readback::Idle {
+            return;
+        }
+
+        let (horizontal_zones, vertical_zones) = self.zones_per_edge();
+        queue.write_buffer(
+            &self.params_buffer,
+            0,
+            bytemuck::cast_slice(&[AmbilightParams {
+                horizontal_zones,
+                vertical_zones,
+                depth: self.settings.depth.clamp(0.01, 0.5),
+                decode_source: decode_source as u32,
+            }]),
+        );
+        // the source changes with the interpolation and postprocess settings
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Ambilight Bind Group"),
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(source),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.zones_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let zones = 2 * (horizontal_zones + vertical_zones);
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Ambilight Pass"),
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(zones, 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(
+            &self.zones_buffer,
+            0,
+            &self.readback_buffer,
+            0,
+            zones as u64 * ZONE_SIZE,
+        );
+        *self.readback.lock().unwrap() = Readback::Recorded(zones);
+    }
+
+    /// Starts reading back the zones recorded by `extract`, call after submitting the encoder
+    pub fn submitted(&self) {
+        let zones = {
+            let mut readback = self.readback.lock().unwrap();
+            let Readback::Recorded(zones) = *readback else {
+                return;
+            };
+            *readback = Readback::Mapping(zones);
+            zones
+        };
+
+        // the callback can run right away, so the lock has to be released first
+        let state = self.readback.clone();
+        self.readback_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let mut state = state.lock().unwrap();
+                *state = match result {
+                    Ok(()) => Readback::Mapped(zones),
+                    Err(err) => {
+                        tracing::warn!("Failed to read back the ambilight colors: {}", err);
+                        Readback::Idle
+                    }
+                };
+            });
+    }
+
+    /// The srgb colors of the last frame whose readback finished, None while it is in flight
+    pub fn take_colors(&self, device: &wgpu::Device) -> Option<Vec<[u8; 3]>> {
+        device.poll(wgpu::Maintain::Poll);
+        let mut readback = self.readback.lock().unwrap();
+        let Readback::Mapped(zones) = *readback else {
+            return None;
+        };
+
+        let colors = {
+            let data = self
+                .readback_buffer
+                .slice(..zones as u64 * ZONE_SIZE)
+                .get_mapped_range();
+            bytemuck::cast_slice::<u8, [f32; 4]>(&data)
+                .iter()
+                .map(|&[r, g, b, _]| {
+                    [r, g, b].map(|value| (value.clamp(0.0, 1.0) * 255.0).round() as u8)
+                })
+                .collect()
+        };
+        self.readback_buffer.unmap();
+        *readback = Readback::Idle;
+        Some(colors)
+    }
+}
+
+/// Sends the zones to e.g. a WLED controller with its realtime UDP protocol, as DRGB packets
+pub struct UdpOutput {
+    socket: UdpSocket,
+}
+
+impl UdpOutput {
+    /// Byte 0 of a packet, DRGB: plain RGB triplets from the first LED
+    const PROTOCOL_DRGB: u8 = 2;
+    /// Seconds the controller waits after the last packet before it goes back to its own effect
+    const TIMEOUT_SECS: u8 = 2;
+    /// DRGB packets can address this many LEDs
+    const MAX_LEDS: usize = 490;
+
+    pub fn new(target: &str) -> Result<Self, Error> {
+        let socket = UdpSocket::bind("0.0.0.0:0").context("Failed to open a UDP socket")?;
+        socket
+            .connect(target)
+            .with_context(|| format!("Failed to resolve {}", target))?;
+        // a frame that can't be sent right away is replaced by the next one anyway
+        socket.set_nonblocking(true)?;
+        Ok(Self { socket })
+    }
+
+    pub fn send(&self, colors: &[[u8; 3]]) {
+        let mut packet = Vec::with_capacity(2 + 3 * colors.len().min(Self::MAX_LEDS));
+        packet.extend([Self::PROTOCOL_DRGB, Self::TIMEOUT_SECS]);
+        packet.extend(colors.iter().take(Self::MAX_LEDS).flatten());
+        if let Err(err) = self.socket.send(&packet) {
+            tracing::debug!("Failed to send the ambilight colors: {}", err);
+        }
+    }
+}
@@ -0,0 +1,96 @@
+// Averages the zones along the edges of the frame, see ambilight.rs
+
+struct Params {
+    horizontal_zones: u32,
+    vertical_zones: u32,
+    // fraction of the frame the zones reach into it
+    depth: f32,
+    // the video texture holds encoded values that textureLoad doesn't decode
+    decode_source: u32,
+}
+
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+@group(0) @binding(1)
+var<uniform> params: Params;
+// srgb encoded, one per zone
+@group(0) @binding(2)
+var<storage, read_write> zones: array<vec4<f32>>;
+
+var<workgroup> partial_sums: array<vec3<f32>, 64>;
+
+fn srgb_encode(linear: vec3<f32>) -> vec3<f32> {
+    let low = linear * 12.92;
+    let high = 1.055 * pow(linear, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, linear <= vec3<f32>(0.0031308));
+}
+
+fn srgb_decode(encoded: vec3<f32>) -> vec3<f32> {
+    let low = encoded / 12.92;
+    let high = pow((encoded + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, encoded <= vec3<f32>(0.04045));
+}
+
+// The area of a zone as min and max uv. Zones go clockwise from the top left corner, like the
+// LEDs of a strip glued around a screen: the top edge left to right, the right edge top to
+// bottom, the bottom edge right to left and the left edge bottom to top.
+fn zone_rect(zone: u32) -> vec4<f32> {
+    let width = 1.0 / f32(params.horizontal_zones);
+    let height = 1.0 / f32(params.vertical_zones);
+    let depth = params.depth;
+    var index = zone;
+    if index < params.horizontal_zones {
+        let x = f32(index) * width;
+        return vec4<f32>(x, 0.0, x + width, depth);
+    }
+    index -= params.horizontal_zones;
+    if index < params.vertical_zones {
+        let y = f32(index) * height;
+        return vec4<f32>(1.0 - depth, y, 1.0, y + height);
+    }
+    index -= params.vertical_zones;
+    if index < params.horizontal_zones {
+        let x = 1.0 - f32(index + 1u) * width;
+        return vec4<f32>(x, 1.0 - depth, x + width, 1.0);
+    }
+    index -= params.horizontal_zones;
+    let y = 1.0 - f32(index + 1u) * height;
+    return vec4<f32>(0.0, y, depth, y + height);
+}
+
+// One workgroup per zone, every invocation averages a 4x4 grid of texels so the zone is
+// sampled on a 32x32 grid, which is then summed up in shared memory
+@compute @workgroup_size(8, 8)
+fn average_zones(
+    @builtin(workgroup_id) group: vec3<u32>,
+    @builtin(local_invocation_id) local: vec3<u32>,
+    @builtin(local_invocation_index) index: u32,
+) {
+    let rect = zone_rect(group.x);
+    let size = vec2<u32>(textureDimensions(source));
+    var sum = vec3<f32>(0.0);
+    for (var y = 0u; y < 4u; y += 1u) {
+        for (var x = 0u; x < 4u; x += 1u) {
+            let cell = (vec2<f32>(local.xy * 4u + vec2<u32>(x, y)) + 0.5) / 32.0;
+            let uv = mix(rect.xy, rect.zw, cell);
+            let texel = min(vec2<u32>(uv * vec2<f32>(size)), size - vec2<u32>(1u));
+            var color = textureLoad(source, texel, 0).rgb;
+            if params.decode_source == 1u {
+                color = srgb_decode(color);
+            }
+            sum += color;
+        }
+    }
+    partial_sums[index] = sum / 16.0;
+    workgroupBarrier();
+
+    for (var stride = 32u; stride > 0u; stride >>= 1u) {
+        if index < stride {
+            partial_sums[index] += partial_sums[index + stride];
+        }
+        workgroupBarrier();
+    }
+    if index == 0u {
+        zones[group.x] = vec4<f32>(srgb_encode(partial_sums[0] / 64.0), 1.0);
+    }
+}
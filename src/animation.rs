@@ -0,0 +1,175 @@
+use std::{
+    fs::File,
+    io::{BufReader, Read},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Context, Error};
+use image::{
+    codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder},
+    AnimationDecoder, Frames, RgbaImage,
+};
+
+/// Browsers show frames with a shorter delay for 100ms, GIFs made for them rely on that
+const MIN_FRAME_DELAY: Duration = Duration::from_millis(20);
+const SHORT_FRAME_DELAY: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Gif,
+    Png,
+    WebP,
+}
+
+fn format(path: &Path) -> Option<Format> {
+    match path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_lowercase)
+        .as_deref()
+    {
+        Some("gif") => Some(Format::Gif),
+        Some("png" | "apng") => Some(Format::Png),
+        Some("webp") => Some(Format::WebP),
+        _ => None,
+    }
+}
+
+/// Whether the file is a GIF, an APNG or an animated WebP, which are played like a video
+pub fn is_animated(path: &Path) -> bool {
+    let Ok(file) = File::open(path) else {
+        return false;
+    };
+    match format(path) {
+        // single frame GIFs are rare enough to not look at the frames up front
+        Some(Format::Gif) => true,
+        Some(Format::Png) => {
+            PngDecoder::new(BufReader::new(file)).is_ok_and(|decoder| decoder.is_apng())
+        }
+        Some(Format::WebP) => {
+            // the animation flag of the extended header, "RIFF" size "WEBP" "VP8X" size flags
+            let mut header = [0; 21];
+            BufReader::new(file).read_exact(&mut header).is_ok()
+                && &header[12..16] == b"VP8X"
+                && header[20] & 0x02 != 0
+        }
+        None => false,
+    }
+}
+
+/// The frames of an animated image, decoded one at a time and starting over after the last
+pub struct Animation {
+    path: PathBuf,
+    frames: Frames<'static>,
+    /// Where the frame that was returned last starts
+    position: Duration,
+    /// Where the next frame starts
+    next_position: Duration,
+    /// Known once all frames were decoded
+    duration: Option<Duration>,
+    frames_this_pass: usize,
+    /// Only had one frame, which stays up
+    still: bool,
+}
+
+impl Animation {
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            frames: open_frames(path)?,
+            position: Duration::ZERO,
+            next_position: Duration::ZERO,
+            duration: None,
+            frames_this_pass: 0,
+            still: false,
+        })
+    }
+
+    pub fn position(&self) -> Duration {
+        self.position
+    }
+
+    pub fn duration(&self) -> Option<Duration> {
+        self.duration
+    }
+
+    /// The next frame and how long it stays up, None once there is nothing left to show
+    pub fn next_frame(&mut self) -> Result<Option<(RgbaImage, Duration)>, Error> {
+        if self.still {
+            return Ok(None);
+        }
+        let frame = match self.frames.next() {
+            Some(frame) => frame,
+            None => {
+                self.duration = Some(self.next_position);
+                if self.frames_this_pass <= 1 {
+                    self.still = true;
+                    return Ok(None);
+                }
+                self.rewind()?;
+                match self.frames.next() {
+                    Some(frame) => frame,
+                    None => return Ok(None),
+                }
+            }
+        }
+        .with_context(|| format!("Failed to decode {}", self.path.display()))?;
+
+        let delay = match Duration::from(frame.delay()) {
+            delay if delay < MIN_FRAME_DELAY => SHORT_FRAME_DELAY,
+            delay => delay,
+        };
+        self.frames_this_pass += 1;
+        self.position = self.next_position;
+        self.next_position += delay;
+        Ok(Some((frame.into_buffer(), delay)))
+    }
+
+    /// Decodes up to the frame that is up at `target`, the formats have no index to jump with
+    pub fn seek(&mut self, target: Duration) -> Result<Option<(RgbaImage, Duration)>, Error> {
+        let target = match self.duration {
+            Some(duration) if !duration.is_zero() => {
+                Duration::from_nanos((target.as_nanos() % duration.as_nanos()) as u64)
+            }
+            _ => target,
+        };
+        if self.still {
+            return Ok(None);
+        }
+        self.rewind()?;
+        let mut previous = None;
+        loop {
+            let Some(frame) = self.next_frame()? else {
+                return Ok(None);
+            };
+            // the target was past the end, which wasn't known yet
+            let wrapped = previous.is_some_and(|previous| self.position < previous);
+            if wrapped || self.next_position > target {
+                return Ok(Some(frame));
+            }
+            previous = Some(self.position);
+        }
+    }
+
+    fn rewind(&mut self) -> Result<(), Error> {
+        self.frames = open_frames(&self.path)?;
+        self.position = Duration::ZERO;
+        self.next_position = Duration::ZERO;
+        self.frames_this_pass = 0;
+        Ok(())
+    }
+}
+
+fn open_frames(path: &Path) -> Result<Frames<'static>, Error> {
+    let reader = BufReader::new(
+        File::open(path).with_context(|| format!("Failed to open {}", path.display()))?,
+    );
+    let frames = match format(path) {
+        Some(Format::Gif) => GifDecoder::new(reader)?.into_frames(),
+        Some(Format::Png) => PngDecoder::new(reader)?.apng().into_frames(),
+        Some(Format::WebP) => WebPDecoder::new(reader)?.into_frames(),
+        None => anyhow::bail!("{} isn't a .gif, .png or .webp", path.display()),
+    };
+    Ok(frames)
+}
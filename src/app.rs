@@ -1,5 +1,110 @@
+use std::{
+    cell::{Ref, RefCell},
+    collections::HashMap,
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "ui")]
 use clipboard::{ClipboardContext, ClipboardProvider};
-use winit::event::{VirtualKeyCode, WindowEvent};
+#[cfg(feature = "ui")]
+use crossbeam_channel::{unbounded, Receiver};
+use encoding_rs::Encoding;
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{ElementState, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent},
+};
+
+#[cfg(all(feature = "ui", feature = "network"))]
+use crate::dlna_browser::MediaServerBrowser;
+#[cfg(feature = "network")]
+use crate::dlna_renderer::{RendererCommand, RendererState};
+#[cfg(feature = "library")]
+use crate::library::Library;
+#[cfg(feature = "ndi")]
+use crate::ndi::{self, NdiSources};
+use crate::{
+    adaptive::Variant,
+    ambilight::UdpOutput,
+    audio_extract, clip_export,
+    command_palette::CommandPalette,
+    commands::{Action, KeyBinding},
+    compare::{self, Compare},
+    config::{
+        self, AmbilightSettings, AspectRatio, AudioSettings, CaptionSettings, Config, Credentials,
+        Dither, GpuSettings, HttpOptions, LetterboxSettings, MediaProfile, PostprocessSettings,
+        Projection, ProxySettings, Session, StereoLayout, StereoOutput, SurfaceFormat,
+        UserShaderSettings, WindowGeometry,
+    },
+    controls::{self, ControlBar},
+    cover_art::{self, CoverArt},
+    cover_flow::CoverFlow,
+    cue,
+    custom_pipeline::PipelineDescription,
+    disc::{self, DiscMenu, DiscNavigation, DiscPosition},
+    error::PlayerError,
+    frame_export,
+    jobs::{unique_path, JobState, Jobs},
+    lut::CubeLut,
+    media_decoder::{DecoderCommand, PlayerEvent, SharedClock, STDIN_URI},
+    media_keys::MediaKey,
+    playlist::{natural_cmp, Playlist, PlaylistItem},
+    playlist_file::{self, PlaylistEntry},
+    renderer::{ScalingMode, SphereView},
+    spherical::VideoLayout,
+    stats::StatsOverlay,
+    subtitles::{self, SubtitleFile},
+    toasts::Toasts,
+    transcode,
+};
+#[cfg(feature = "ui")]
+use crate::{
+    audio_filters::{EQUALIZER_FREQUENCIES, EQUALIZER_MAX_DB, EQUALIZER_MIN_DB, EQUALIZER_PRESETS},
+    compare::CompareMode,
+    config::{
+        CaptionChannel, FrameFormat, GpuBackend, LanguagePreferences, LetterboxMode, Normalization,
+        ProxyMode, SeekMode, SubtitleStyle, TestPattern, TranscodePreset, Visualizer,
+        TEST_FRAME_RATES,
+    },
+    custom_pipeline,
+    gamepad::GamepadButton,
+    languages,
+    logging::{LogBuffer, LogConsole},
+    media_decoder::{CACHE_FILE_PREFIX, SHORT_SEEK},
+    renderer::HDR_SURFACE_FORMAT,
+    subtitles::SecondSubtitle,
+    thumbnailer::{Thumbnail, Thumbnailer},
+};
+
+/// Going to the previous item restarts the current one when it has played longer than this
+const SMART_PREVIOUS_THRESHOLD: Duration = Duration::from_secs(3);
+/// Step of the audio delay hotkeys
+const AUDIO_DELAY_STEP_MS: i64 = 10;
+/// Step of the subtitle delay hotkeys and buttons
+const SUBTITLE_DELAY_STEP_MS: i64 = 100;
+/// Step of the volume hotkeys and of a notch of the scroll wheel
+const VOLUME_STEP: f64 = 0.05;
+/// Seeked by a notch of the scroll wheel while shift is held
+const WHEEL_SEEK_STEP: Duration = Duration::from_secs(5);
+/// Pixels of a touchpad scroll that count as a notch of the scroll wheel
+const PIXELS_PER_WHEEL_STEP: f64 = 50.0;
+/// Two clicks at most this far apart are a double click
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+/// Pixels the cursor can move before a click becomes a drag
+const DRAG_THRESHOLD: f64 = 8.0;
+/// Dragging over the whole width of the window scrubs this far
+const DRAG_SCRUB_RANGE: Duration = Duration::from_secs(120);
+/// Degrees of field of view per wheel step, in 360° video
+const FOV_STEP: f32 = 5.0;
+/// Factor of one step of the mouse wheel while ctrl is held
+const ZOOM_STEP: f32 = 1.1;
+const MIN_ZOOM: f32 = 0.25;
+const MAX_ZOOM: f32 = 4.0;
+/// Width of a thumbnail in the library grid
+#[cfg(feature = "library")]
+const LIBRARY_TILE_WIDTH: f32 = 192.0;
 
 #[derive(Default)]
 pub struct Modifiers {
@@ -10,70 +115,4733 @@ pub struct Modifiers {
     pub command: bool,
 }
 
+/// Left mouse button held down on the video
+struct PointerDrag {
+    start_x: f64,
+    start_y: f64,
+    start_position: Duration,
+    /// Where a 360° video looked when the drag started, it turns the view instead of scrubbing
+    start_view: Option<SphereView>,
+    /// Set once the cursor moved past `DRAG_THRESHOLD`
+    scrubbing: bool,
+}
+
+/// Part of a file that is exported, see `App::clip_range`
+struct ClipRange {
+    uri: String,
+    title: String,
+    start: Duration,
+    end: Duration,
+}
+
+impl ClipRange {
+    fn label(&self) -> String {
+        format!(
+            "{} {} - {}",
+            self.title,
+            controls::format_time(self.start),
+            controls::format_time(self.end)
+        )
+    }
+}
+
+/// A server asked for a login while playing an item, shown until it is sent or cancelled
+#[cfg(feature = "ui")]
+struct LoginPrompt {
+    index: usize,
+    origin: String,
+    credentials: Credentials,
+    remember: bool,
+    /// Where playback resumes once signed in
+    position: Duration,
+    /// A login was sent and the server refused it
+    refused: bool,
+}
+
 #[derive(Default)]
 pub struct Input {
     pub modifiers: Modifiers,
+    cursor: Option<PhysicalPosition<f64>>,
+    window_size: PhysicalSize<u32>,
+    /// The cursor is over an egui window or panel, which handles the mouse itself
+    pointer_over_ui: bool,
+    last_click: Option<(Instant, PhysicalPosition<f64>)>,
+    drag: Option<PointerDrag>,
 }
 
 pub struct App {
     input: Input,
-    on_load_file_request: Option<Box<dyn FnOnce(String)>>,
+    on_command: Option<Box<dyn FnMut(DecoderCommand)>>,
+    #[cfg(feature = "ui")]
     clipboard: ClipboardContext,
+    audio_only: bool,
+    /// Playing with the window hidden, see `set_background`
+    background: bool,
+    paused: bool,
+    /// Playing backwards, reset for every item
+    reverse: bool,
+    scaling_mode: ScalingMode,
+    frame_interpolation: bool,
+    fullscreen: bool,
+    /// Monitor fullscreen goes to, None for the one showing the window. Only kept for the
+    /// session, monitors come and go.
+    fullscreen_monitor: Option<String>,
+    /// Names of the connected monitors
+    monitors: Vec<String>,
+    /// The monitor showing the window
+    current_monitor: Option<String>,
+    /// Uris to play in a new player window, which may be empty, taken by the event loop
+    new_window: Option<Vec<String>>,
+    /// Requested multiple of the video size for the window, applied by the event loop
+    window_scale: Option<f64>,
+    /// Whether the video is also shown fullscreen on a second monitor
+    projector: bool,
+    /// Whether the video plays in a small window on top of the others instead of the main one
+    picture_in_picture: bool,
+    /// Advances through the images of the playlist, see `SlideshowSettings`
+    slideshow: bool,
+    /// When the current item was loaded, while it is a still image
+    image_shown_at: Option<Instant>,
+    /// The file the current one is compared with, played by a second decoder of the event loop
+    compare: Option<Compare>,
+    /// Path or url typed into the compare settings
+    #[cfg(feature = "ui")]
+    compare_uri: String,
+    /// Title and chapter while a DVD or Blu-ray plays
+    disc: Option<DiscPosition>,
+    /// The position got into the CUE track that was selected last, earlier positions are left
+    /// over from the previous track
+    track_reached: bool,
+    /// Typed into the custom pipeline section, see `CustomPipeline`
+    #[cfg(feature = "ui")]
+    pipeline_description: String,
+    /// Playing instead of a playlist item, see `play_pipeline`
+    pipeline: Option<PipelineDescription>,
+    /// Sends to the decoder of the compared file, set while it is running
+    on_compare_command: Option<Box<dyn FnMut(DecoderCommand)>>,
+    /// Clock the current file plays on, the compared file follows it
+    clock: Option<SharedClock>,
+    loop_file: bool,
+    /// Marked A-B loop points, the loop is active once both are set
+    loop_a: Option<Duration>,
+    loop_b: Option<Duration>,
+    /// The http options of the current item being edited, the headers are in
+    /// `http_headers_text`
+    http_options: HttpOptions,
+    /// The request headers of the current item being edited, one `Name: value` per line
+    http_headers_text: String,
+    /// The cookie file of the current item being edited
+    cookie_file_text: String,
+    /// The proxy of `--proxy`, used instead of the one in the settings until the player is
+    /// closed
+    session_proxy: Option<ProxySettings>,
+    /// Logins entered without remembering them, by `config::origin`
+    session_credentials: HashMap<String, Credentials>,
+    #[cfg(feature = "ui")]
+    login_prompt: Option<LoginPrompt>,
+    /// The file the current item is being recorded to and when the recording started
+    recording: Option<(PathBuf, Instant)>,
+    /// Where the clip to export starts and ends, the item's start and end when not set
+    clip_in: Option<Duration>,
+    clip_out: Option<Duration>,
+    /// Index of the audio stream that is playing, see `PlayerEvent::AudioStream`
+    audio_stream: Option<usize>,
+    /// How many audio streams the current item has
+    audio_streams: usize,
+    /// How many video streams the current item has, None until it plays
+    video_streams: Option<usize>,
+    /// Read from the metadata of the current item, see `PlayerEvent::Layout`
+    detected_layout: VideoLayout,
+    /// Where the camera looks while a 360° video is shown
+    sphere_view: SphereView,
+    jobs: Jobs,
+    /// Name the current equalizer gains are saved under
+    #[cfg(feature = "ui")]
+    equalizer_preset_name: String,
+    /// Files of a drag and drop that is still arriving, see `finish_drop`
+    dropped_files: Vec<PathBuf>,
+    /// Path of a user shader typed into the postprocessing settings
+    #[cfg(feature = "ui")]
+    user_shader_path: String,
+    /// The LUT of `Config::color_lut`
+    lut: Option<Arc<CubeLut>>,
+    /// Turned off to compare with the ungraded video
+    lut_enabled: bool,
+    /// Path of a LUT typed into the color grading settings
+    #[cfg(feature = "ui")]
+    lut_path: String,
+    /// The image of `LetterboxSettings::image`
+    letterbox_image: Option<Arc<CoverArt>>,
+    /// Path of an image typed into the letterbox settings
+    #[cfg(feature = "ui")]
+    letterbox_image_path: String,
+    /// Edge colors of a recent frame, empty while the ambilight is off
+    ambilight_colors: Vec<[u8; 3]>,
+    /// Where the ambilight colors are sent, see `AmbilightSettings::udp_target`
+    ambilight_output: Option<UdpOutput>,
+    /// host:port typed into the ambilight settings
+    #[cfg(feature = "ui")]
+    ambilight_target: String,
+    present_mode: wgpu::PresentMode,
+    supported_present_modes: Vec<wgpu::PresentMode>,
+    /// The format the surface was created with, `Config::surface_format` applies after a restart
+    surface_format: Option<wgpu::TextureFormat>,
+    /// GPUs `Config::gpu` can pick from
+    adapter_names: Vec<String>,
+    /// The GPU rendering right now
+    current_adapter: String,
+    /// Changed GPU settings the windows move to, taken by the event loop
+    new_gpu: Option<GpuSettings>,
+    playlist: Playlist,
+    cover_flow: CoverFlow,
+    #[cfg(all(feature = "ui", feature = "network"))]
+    media_servers: MediaServerBrowser,
+    #[cfg(feature = "ndi")]
+    ndi_sources: NdiSources,
+    #[cfg(feature = "ui")]
+    thumbnails: HashMap<String, egui::TextureHandle>,
+    #[cfg(feature = "ui")]
+    thumbnailer: Thumbnailer,
+    #[cfg(feature = "ui")]
+    thumbnail_receiver: Receiver<Thumbnail>,
+    /// Shared by the windows like the config, None when the database couldn't be opened
+    #[cfg(feature = "library")]
+    library: Option<Rc<RefCell<Library>>>,
+    /// Textures of the library grid, loaded once their tile is visible
+    #[cfg(all(feature = "ui", feature = "library"))]
+    library_thumbnails: HashMap<String, Option<egui::TextureHandle>>,
+    /// `Library::generation` the thumbnails were loaded at
+    #[cfg(all(feature = "ui", feature = "library"))]
+    library_generation: u64,
+    /// Path of a folder typed into the library settings
+    #[cfg(feature = "library")]
+    library_folder_path: String,
+    subtitle: Option<SubtitleFile>,
+    /// Drawn above `subtitle` with its own delay, e.g. the original language over a translation
+    #[cfg(feature = "ui")]
+    second_subtitle: Option<SecondSubtitle>,
+    #[cfg(feature = "ui")]
+    second_subtitle_path: String,
+    /// The language lists of the settings as they are typed, see `LanguagePreferences`
+    #[cfg(feature = "ui")]
+    audio_languages_text: String,
+    #[cfg(feature = "ui")]
+    subtitle_languages_text: String,
+    stats: StatsOverlay,
+    #[cfg(feature = "ui")]
+    log_console: LogConsole,
+    controls: ControlBar,
+    toasts: Toasts,
+    command_palette: CommandPalette,
+    /// Shared by the windows, what one of them changes is saved for all
+    config: Rc<RefCell<Config>>,
+    /// Quality levels of the current HLS/DASH stream
+    variants: Vec<Variant>,
+    active_variant: Option<usize>,
+    /// The variant picked in the quality menu, None for automatic selection
+    selected_variant: Option<usize>,
+    /// How many uris were loaded into the decoder, see `PlayerEvent::Position`
+    load_generation: u64,
 }
 
 impl App {
-    pub fn new() -> Self {
+    pub fn new(
+        #[cfg(feature = "ui")] log_buffer: LogBuffer,
+        shared_config: Rc<RefCell<Config>>,
+        #[cfg(feature = "library")] library: Option<Rc<RefCell<Library>>>,
+    ) -> Self {
+        #[cfg(feature = "ui")]
+        let (thumbnail_sender, thumbnail_receiver) = unbounded();
+        let config = shared_config.borrow();
+        let lut = config.color_lut.as_deref().and_then(|path| {
+            CubeLut::load(path)
+                .map_err(|err| tracing::warn!("{:#}", err))
+                .ok()
+        });
+        let ambilight_output = config.ambilight.udp_target.as_deref().and_then(|target| {
+            UdpOutput::new(target)
+                .map_err(|err| tracing::warn!("{:#}", err))
+                .ok()
+        });
+        let letterbox_image = config.letterbox.image.as_deref().and_then(|path| {
+            cover_art::load(path)
+                .map_err(|err| tracing::warn!("{:#}", err))
+                .ok()
+        });
         Self {
             input: Input::default(),
-            on_load_file_request: None,
+            on_command: None,
+            #[cfg(feature = "ui")]
             clipboard: ClipboardProvider::new().unwrap(),
+            audio_only: false,
+            background: false,
+            paused: false,
+            reverse: false,
+            scaling_mode: ScalingMode::default(),
+            frame_interpolation: false,
+            fullscreen: false,
+            fullscreen_monitor: None,
+            monitors: Vec::new(),
+            current_monitor: None,
+            window_scale: None,
+            new_window: None,
+            projector: false,
+            picture_in_picture: false,
+            slideshow: false,
+            image_shown_at: None,
+            compare: None,
+            #[cfg(feature = "ui")]
+            compare_uri: String::new(),
+            disc: None,
+            track_reached: true,
+            #[cfg(feature = "ui")]
+            pipeline_description: String::new(),
+            pipeline: None,
+            on_compare_command: None,
+            clock: None,
+            loop_file: false,
+            loop_a: None,
+            loop_b: None,
+            http_options: HttpOptions::default(),
+            http_headers_text: String::new(),
+            cookie_file_text: String::new(),
+            session_proxy: None,
+            session_credentials: HashMap::new(),
+            #[cfg(feature = "ui")]
+            login_prompt: None,
+            recording: None,
+            clip_in: None,
+            clip_out: None,
+            audio_stream: None,
+            audio_streams: 0,
+            video_streams: None,
+            detected_layout: VideoLayout::default(),
+            sphere_view: SphereView::default(),
+            jobs: Jobs::default(),
+            #[cfg(feature = "ui")]
+            equalizer_preset_name: String::new(),
+            dropped_files: Vec::new(),
+            #[cfg(feature = "ui")]
+            user_shader_path: String::new(),
+            lut: lut.map(Arc::new),
+            lut_enabled: true,
+            #[cfg(feature = "ui")]
+            lut_path: String::new(),
+            letterbox_image: letterbox_image.map(Arc::new),
+            #[cfg(feature = "ui")]
+            letterbox_image_path: String::new(),
+            ambilight_colors: Vec::new(),
+            ambilight_output,
+            #[cfg(feature = "ui")]
+            ambilight_target: config.ambilight.udp_target.clone().unwrap_or_default(),
+            present_mode: wgpu::PresentMode::Fifo,
+            supported_present_modes: vec![wgpu::PresentMode::Fifo],
+            surface_format: None,
+            adapter_names: Vec::new(),
+            current_adapter: String::new(),
+            new_gpu: None,
+            playlist: Playlist::default(),
+            cover_flow: CoverFlow::default(),
+            #[cfg(all(feature = "ui", feature = "network"))]
+            media_servers: MediaServerBrowser::default(),
+            #[cfg(feature = "ndi")]
+            ndi_sources: NdiSources::default(),
+            #[cfg(feature = "ui")]
+            thumbnails: HashMap::new(),
+            #[cfg(feature = "ui")]
+            thumbnailer: Thumbnailer::new(thumbnail_sender),
+            #[cfg(feature = "ui")]
+            thumbnail_receiver,
+            #[cfg(feature = "library")]
+            library,
+            #[cfg(all(feature = "ui", feature = "library"))]
+            library_thumbnails: HashMap::new(),
+            #[cfg(all(feature = "ui", feature = "library"))]
+            library_generation: 0,
+            #[cfg(feature = "library")]
+            library_folder_path: String::new(),
+            subtitle: None,
+            #[cfg(feature = "ui")]
+            second_subtitle: None,
+            #[cfg(feature = "ui")]
+            second_subtitle_path: String::new(),
+            #[cfg(feature = "ui")]
+            audio_languages_text: config.languages.audio.join(", "),
+            #[cfg(feature = "ui")]
+            subtitle_languages_text: config.languages.subtitles.join(", "),
+            stats: StatsOverlay::default(),
+            #[cfg(feature = "ui")]
+            log_console: LogConsole::new(log_buffer),
+            controls: ControlBar::default(),
+            toasts: Toasts::default(),
+            command_palette: CommandPalette::default(),
+            config: shared_config.clone(),
+            variants: Vec::new(),
+            active_variant: None,
+            selected_variant: None,
+            load_generation: 0,
         }
     }
 
-    pub fn set_on_load_file_request<F: FnOnce(String) + Send + 'static>(&mut self, func: F) {
-        self.on_load_file_request = Some(Box::new(func));
+    pub fn audio_only(&self) -> bool {
+        self.audio_only
     }
 
-    pub fn handle_window_event(&mut self, event: &WindowEvent) {
-        fn format_url(url: &str) -> String {
-            if url.starts_with("http") {
-                url.to_string()
-            } else if cfg!(target_os = "windows") {
-                format!("file:///{}", url.replace('\\', "/"))
-            } else {
-                format!("file://{}", url)
+    #[cfg(any(feature = "ui", windows))]
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    #[cfg(feature = "ui")]
+    pub fn muted(&self) -> bool {
+        self.config.borrow().audio.muted
+    }
+
+    #[cfg(feature = "ui")]
+    pub fn tray_icon(&self) -> bool {
+        self.config.borrow().tray_icon
+    }
+
+    pub fn minimize_to_tray(&self) -> bool {
+        self.config.borrow().tray_icon && self.config.borrow().minimize_to_tray
+    }
+
+    pub fn close_to_tray(&self) -> bool {
+        self.config.borrow().tray_icon && self.config.borrow().close_to_tray
+    }
+
+    /// While the window is hidden only the audio is decoded, the video comes back with it
+    pub fn set_background(&mut self, background: bool) {
+        if background == self.background {
+            return;
+        }
+        self.background = background;
+        if background {
+            self.projector = false;
+            self.picture_in_picture = false;
+        }
+        self.send_command(DecoderCommand::SetAudioOnly(self.audio_only || background));
+    }
+
+    /// Title of the playlist item or the pipeline that is playing
+    pub fn current_title(&self) -> Option<&str> {
+        self.playlist
+            .current()
+            .map(|item| item.title.as_str())
+            .or(self.pipeline.as_ref().map(PipelineDescription::as_str))
+    }
+
+    /// The title and artist to show outside the window, the current song of internet radio
+    /// streams as it changes
+    pub fn now_playing(&self) -> Option<(&str, Option<&str>)> {
+        self.current_title()?;
+        let tags = self.controls.tags();
+        let title = tags.title.as_deref().or(self.current_title())?;
+        Some((title, tags.artist.as_deref().or(tags.station.as_deref())))
+    }
+
+    pub fn window_title(&self) -> String {
+        match self.now_playing() {
+            Some((title, Some(artist))) => format!("{} - {} - wgpu-media-player", artist, title),
+            Some((title, None)) => format!("{} - wgpu-media-player", title),
+            None => "wgpu-media-player".to_string(),
+        }
+    }
+
+    /// Records the current item into the output folder, or saves the recording that is running
+    fn toggle_recording(&mut self) {
+        if self.recording.is_some() {
+            self.send_command(DecoderCommand::StopRecording);
+            return;
+        }
+        let Some(item) = self.playlist.current() else {
+            return;
+        };
+        let directory = self.config.borrow().output_directory();
+        if let Err(err) = std::fs::create_dir_all(&directory) {
+            self.toasts
+                .push(format!("Can't record to {}: {}", directory.display(), err));
+            return;
+        }
+        let path = unique_path(&directory, &item.title, "mkv");
+        self.send_command(DecoderCommand::StartRecording(path));
+    }
+
+    /// The file of the current item and the part of it between the in and out points, shows
+    /// a toast when there is nothing to export
+    fn clip_range(&mut self) -> Option<ClipRange> {
+        let item = self.playlist.current()?;
+        let (file_uri, track) = cue::split_track_uri(&item.uri);
+        let start = self
+            .clip_in
+            .or_else(|| track.map(|track| track.start))
+            .unwrap_or_default();
+        let Some(end) = self
+            .clip_out
+            .or_else(|| track.and_then(|track| track.end))
+            .or_else(|| self.controls.duration())
+        else {
+            self.toasts
+                .push("Set the end of the clip, the length isn't known".to_string());
+            return None;
+        };
+        if start >= end {
+            self.toasts
+                .push("The clip has to end after it starts".to_string());
+            return None;
+        }
+        Some(ClipRange {
+            uri: file_uri.to_string(),
+            title: item.title.clone(),
+            start,
+            end,
+        })
+    }
+
+    /// The output folder, created when it doesn't exist yet
+    fn export_directory(&mut self) -> Option<PathBuf> {
+        let directory = self.config.borrow().output_directory();
+        if let Err(err) = std::fs::create_dir_all(&directory) {
+            self.toasts
+                .push(format!("Can't export to {}: {}", directory.display(), err));
+            return None;
+        }
+        Some(directory)
+    }
+
+    /// Exports the part of the current item between the in and out points to the output folder
+    fn export_clip(&mut self) {
+        let Some(clip) = self.clip_range() else {
+            return;
+        };
+        let Some(directory) = self.export_directory() else {
+            return;
+        };
+        let path = self
+            .jobs
+            .unused_path(&directory, &format!("{} clip", clip.title), "mkv");
+        let job_path = path.clone();
+        self.jobs.spawn(clip.label(), path, move |context| {
+            clip_export::export(&clip.uri, clip.start, clip.end, &job_path, context)
+        });
+    }
+
+    /// Writes every frame between the in and out points to a folder of images
+    fn export_frames(&mut self) {
+        let Some(clip) = self.clip_range() else {
+            return;
+        };
+        let Some(directory) = self.export_directory() else {
+            return;
+        };
+        let path = self
+            .jobs
+            .unused_path(&directory, &format!("{} frames", clip.title), "");
+        let (job_path, settings) = (path.clone(), self.config.borrow().frame_export);
+        self.jobs.spawn(
+            format!("Frames of {}", clip.label()),
+            path,
+            move |context| {
+                frame_export::export(
+                    &clip.uri, clip.start, clip.end, &job_path, settings, context,
+                )
+            },
+        );
+    }
+
+    /// Copies the audio stream that is playing to a file of its own in the output folder
+    fn extract_audio(&mut self) {
+        let Some(item) = self.playlist.current() else {
+            return;
+        };
+        let (uri, name) = (
+            cue::split_track_uri(&item.uri).0.to_string(),
+            format!("{} audio", item.title),
+        );
+        let label = format!("Audio of {}", item.title);
+        let index = self.audio_stream.unwrap_or(0);
+        let Some(directory) = self.export_directory() else {
+            return;
+        };
+        // changed to .m4a by the job when the stream is AAC or ALAC
+        let path = self.jobs.unused_path(&directory, &name, "flac");
+        let job_path = path.clone();
+        self.jobs.spawn(label, path, move |context| {
+            audio_extract::extract(&uri, index, &job_path, context)
+        });
+    }
+
+    /// Queues the conversion of the playlist items to the preset in the settings, the items of
+    /// a CUE sheet share a file which is converted once
+    fn transcode(&mut self, indices: impl IntoIterator<Item = usize>) {
+        let Some(directory) = self.export_directory() else {
+            return;
+        };
+        let preset = self.config.borrow().transcode_preset;
+        let mut uris: Vec<(String, String)> = Vec::new();
+        for index in indices {
+            let Some(item) = self.playlist.items().get(index) else {
+                continue;
+            };
+            let uri = cue::split_track_uri(&item.uri).0;
+            if !uris.iter().any(|(known, _)| known == uri) {
+                uris.push((uri.to_string(), item.title.clone()));
+            }
+        }
+        for (uri, title) in uris {
+            let path = self
+                .jobs
+                .unused_path(&directory, &title, preset.extension());
+            let job_path = path.clone();
+            self.jobs.enqueue(
+                format!("{} to {}", title, preset.extension().to_uppercase()),
+                path,
+                move |context| transcode::transcode(&uri, preset, &job_path, context),
+            );
+        }
+    }
+
+    /// Shows a toast for the jobs that ended
+    pub fn poll_jobs(&mut self) {
+        for job in self.jobs.poll() {
+            match &job.state {
+                JobState::Done => self.toasts.push(format!("Saved {}", job.path.display())),
+                JobState::Failed(err) => self
+                    .toasts
+                    .push(format!("Failed to export {}: {}", job.label, err)),
+                JobState::Queued | JobState::Running(_) | JobState::Cancelled => {}
+            }
+        }
+    }
+
+    /// The login entered this session for the server of `uri`, or the saved one
+    fn credentials(&self, uri: &str) -> Option<Credentials> {
+        let origin = config::origin(uri)?;
+        self.session_credentials
+            .get(&origin)
+            .cloned()
+            .or_else(|| self.config.borrow().credentials.get(&origin).cloned())
+    }
+
+    /// Asks for a login for the current item, the previous one is filled in when it was refused
+    #[cfg(feature = "ui")]
+    fn prompt_login(&mut self, message: String) {
+        let Some(index) = self.playlist.current_index() else {
+            return;
+        };
+        let uri = &self.playlist.items()[index].uri;
+        let Some(origin) = config::origin(uri) else {
+            self.toasts.push(format!("Not authorized: {}", message));
+            return;
+        };
+        tracing::info!("{} asked for a login: {}", origin, message);
+        let sent = self.credentials(uri);
+        self.login_prompt = Some(LoginPrompt {
+            index,
+            remember: self.config.borrow().credentials.contains_key(&origin),
+            refused: sent.is_some(),
+            credentials: sent.unwrap_or_default(),
+            position: self.controls.position(),
+            origin,
+        });
+    }
+
+    /// Without the ui there is nothing to type a login into, the refusal is only reported
+    #[cfg(not(feature = "ui"))]
+    fn prompt_login(&mut self, message: String) {
+        self.toasts.push(format!("Not authorized: {}", message));
+    }
+
+    /// Keeps the login of the prompt and plays its item again from where it stopped
+    #[cfg(feature = "ui")]
+    fn sign_in(&mut self, prompt: LoginPrompt) {
+        if prompt.remember {
+            self.config
+                .borrow_mut()
+                .credentials
+                .insert(prompt.origin.clone(), prompt.credentials.clone());
+        } else {
+            self.config.borrow_mut().credentials.remove(&prompt.origin);
+        }
+        self.config.borrow().save();
+        self.session_credentials
+            .insert(prompt.origin, prompt.credentials);
+
+        if prompt.index >= self.playlist.len() {
+            return;
+        }
+        let start = (!prompt.position.is_zero()).then_some(prompt.position);
+        self.play_at(prompt.index, start);
+    }
+
+    pub fn set_session_proxy(&mut self, proxy: ProxySettings) {
+        self.session_proxy = Some(proxy);
+    }
+
+    /// The name and uuid the DLNA renderer is announced with, None when it is turned off. The
+    /// uuid is made and saved the first time.
+    #[cfg(feature = "network")]
+    pub fn dlna_renderer_identity(&mut self) -> Option<(String, String)> {
+        if !self.config.borrow().dlna.renderer {
+            return None;
+        }
+        let uuid = self.config.borrow().dlna.uuid.clone();
+        let uuid = match uuid {
+            Some(uuid) => uuid,
+            None => {
+                let uuid = crate::dlna::new_uuid();
+                self.config.borrow_mut().dlna.uuid = Some(uuid.clone());
+                self.config.borrow().save();
+                uuid
+            }
+        };
+        let name = match self.config.borrow().dlna.name.trim() {
+            "" => format!(
+                "wgpu-media-player on {}",
+                gethostname::gethostname().to_string_lossy()
+            ),
+            name => name.to_string(),
+        };
+        Some((name, uuid))
+    }
+
+    #[cfg(feature = "network")]
+    pub fn renderer_state(&self) -> RendererState {
+        RendererState {
+            uri: self.playlist.current().map(|item| item.uri.clone()),
+            title: self.now_playing().map(|(title, _)| title.to_string()),
+            paused: self.paused,
+            position: self.controls.position(),
+            duration: self.controls.duration(),
+            volume: self.config.borrow().audio.volume,
+            muted: self.config.borrow().audio.muted,
+        }
+    }
+
+    /// How far the playing item got from 0 to 1, None for nothing or a live stream
+    #[cfg(windows)]
+    pub fn progress(&self) -> Option<f64> {
+        self.playlist.current()?;
+        let duration = self
+            .controls
+            .duration()
+            .filter(|duration| !duration.is_zero())?;
+        Some((self.controls.position().as_secs_f64() / duration.as_secs_f64()).clamp(0.0, 1.0))
+    }
+
+    /// Whether the display has to stay awake, while video is playing in a visible window.
+    /// Music doesn't keep it awake, not even with the visualizer or the cover art on screen.
+    #[cfg(feature = "ui")]
+    pub fn inhibit_sleep(&self) -> bool {
+        !self.config.borrow().allow_sleep
+            && !self.paused
+            && !self.audio_only
+            && !self.background
+            && self.video_streams != Some(0)
+            && self.current_title().is_some()
+    }
+
+    pub fn scaling_mode(&self) -> ScalingMode {
+        self.scaling_mode
+    }
+
+    pub fn frame_interpolation(&self) -> bool {
+        self.frame_interpolation
+    }
+
+    pub fn postprocess(&self) -> Ref<'_, PostprocessSettings> {
+        Ref::map(self.config.borrow(), |config| &config.postprocess)
+    }
+
+    /// Runs the shader after the built in filters, see `UserShader`
+    pub fn add_user_shader(&mut self, path: PathBuf) {
+        let mut config = self.config.borrow_mut();
+        let shaders = &mut config.postprocess.user_shaders;
+        if !shaders.iter().any(|shader| shader.path == path) {
+            shaders.push(UserShaderSettings {
+                path,
+                enabled: true,
+            });
+            config.save();
+        }
+    }
+
+    /// The saved surface format and whether HDR output is wanted, see `select_surface_format`
+    pub fn surface_settings(&self) -> (SurfaceFormat, bool) {
+        let config = self.config.borrow();
+        (config.surface_format, config.hdr_output)
+    }
+
+    pub fn dither(&self) -> Dither {
+        self.config.borrow().dither
+    }
+
+    /// Where the camera looks, None unless the current item is 360° video
+    pub fn sphere_view(&self) -> Option<SphereView> {
+        (self.projection() == Projection::Equirectangular).then_some(self.sphere_view)
+    }
+
+    /// The projection chosen for the current item, or the one its metadata declares
+    fn projection(&self) -> Projection {
+        self.playlist
+            .current()
+            .and_then(|item| self.config.borrow().media_profile(&item.uri).projection)
+            .unwrap_or(self.detected_layout.projection)
+    }
+
+    /// The stereo layout chosen for the current item, or the one its metadata declares
+    pub fn stereo_layout(&self) -> StereoLayout {
+        self.playlist
+            .current()
+            .and_then(|item| self.config.borrow().media_profile(&item.uri).stereo_layout)
+            .unwrap_or(self.detected_layout.stereo)
+    }
+
+    pub fn stereo_output(&self) -> StereoOutput {
+        self.config.borrow().stereo_output
+    }
+
+    /// Overrides the stereo layout of the current item and remembers it, None goes back to the
+    /// detected one
+    #[cfg(feature = "ui")]
+    fn set_stereo_layout(&mut self, layout: Option<StereoLayout>) {
+        let Some(item) = self.playlist.current() else {
+            return;
+        };
+        let uri = item.uri.clone();
+
+        let mut profile = self.config.borrow().media_profile(&uri);
+        profile.stereo_layout = layout;
+        self.config.borrow_mut().set_media_profile(&uri, profile);
+        self.config.borrow().save();
+    }
+
+    /// Overrides the projection of the current item and remembers it, None goes back to the
+    /// detected one
+    #[cfg(feature = "ui")]
+    fn set_projection(&mut self, projection: Option<Projection>) {
+        let Some(item) = self.playlist.current() else {
+            return;
+        };
+        let uri = item.uri.clone();
+
+        let mut profile = self.config.borrow().media_profile(&uri);
+        profile.projection = projection;
+        self.config.borrow_mut().set_media_profile(&uri, profile);
+        self.config.borrow().save();
+    }
+
+    /// None while no LUT is loaded or it is toggled off
+    pub fn lut(&self) -> Option<&Arc<CubeLut>> {
+        self.lut.as_ref().filter(|_| self.lut_enabled)
+    }
+
+    fn load_lut(&mut self, path: PathBuf) {
+        match CubeLut::load(&path) {
+            Ok(lut) => {
+                self.lut = Some(Arc::new(lut));
+                self.lut_enabled = true;
+                self.config.borrow_mut().color_lut = Some(path);
+                self.config.borrow().save();
+            }
+            Err(err) => self.toasts.push(format!("{:#}", err)),
+        }
+    }
+
+    #[cfg(feature = "ui")]
+    fn clear_lut(&mut self) {
+        self.lut = None;
+        self.config.borrow_mut().color_lut = None;
+        self.config.borrow().save();
+    }
+
+    pub fn letterbox(&self) -> Ref<'_, LetterboxSettings> {
+        Ref::map(self.config.borrow(), |config| &config.letterbox)
+    }
+
+    pub fn letterbox_image(&self) -> Option<&Arc<CoverArt>> {
+        self.letterbox_image.as_ref()
+    }
+
+    #[cfg(feature = "ui")]
+    fn load_letterbox_image(&mut self, path: PathBuf) {
+        match cover_art::load(&path) {
+            Ok(image) => {
+                self.letterbox_image = Some(Arc::new(image));
+                let mut config = self.config.borrow_mut();
+                config.letterbox.image = Some(path);
+                config.letterbox.mode = LetterboxMode::Image;
+                config.save();
+            }
+            Err(err) => self.toasts.push(format!("{:#}", err)),
+        }
+    }
+
+    #[cfg(feature = "ui")]
+    fn clear_letterbox_image(&mut self) {
+        self.letterbox_image = None;
+        self.config.borrow_mut().letterbox.image = None;
+        self.config.borrow().save();
+    }
+
+    pub fn ambilight(&self) -> Ref<'_, AmbilightSettings> {
+        Ref::map(self.config.borrow(), |config| &config.ambilight)
+    }
+
+    /// Passes the edge colors of a frame on to the UDP target and the preview
+    pub fn set_ambilight_colors(&mut self, colors: Vec<[u8; 3]>) {
+        if !self.config.borrow().ambilight.enabled {
+            return;
+        }
+        if let Some(output) = &self.ambilight_output {
+            output.send(&colors);
+        }
+        self.ambilight_colors = colors;
+    }
+
+    /// Sends the colors to `target` from now on, nowhere when it is empty
+    #[cfg(feature = "ui")]
+    fn set_ambilight_target(&mut self, target: String) {
+        if target.is_empty() {
+            self.ambilight_output = None;
+            self.config.borrow_mut().ambilight.udp_target = None;
+            self.config.borrow().save();
+            return;
+        }
+        match UdpOutput::new(&target) {
+            Ok(output) => {
+                self.ambilight_output = Some(output);
+                self.config.borrow_mut().ambilight.udp_target = Some(target);
+                self.config.borrow().save();
             }
+            Err(err) => self.toasts.push(format!("{:#}", err)),
+        }
+    }
+
+    pub fn fullscreen(&self) -> bool {
+        self.fullscreen
+    }
+
+    pub fn set_fullscreen(&mut self, fullscreen: bool) {
+        self.fullscreen = fullscreen;
+    }
+
+    pub fn fullscreen_monitor(&self) -> Option<&str> {
+        self.fullscreen_monitor.as_deref()
+    }
+
+    pub fn set_monitors(&mut self, monitors: Vec<String>, current: Option<String>) {
+        self.monitors = monitors;
+        self.current_monitor = current;
+    }
+
+    /// What to play in a new player window, once per request
+    pub fn take_new_window(&mut self) -> Option<Vec<String>> {
+        self.new_window.take()
+    }
+
+    /// The GPU and graphics API every window should be moved to, once
+    pub fn take_gpu_settings(&mut self) -> Option<GpuSettings> {
+        self.new_gpu.take()
+    }
+
+    /// The multiple of the video size the window should be resized to, once
+    pub fn take_window_scale(&mut self) -> Option<f64> {
+        self.window_scale.take()
+    }
+
+    pub fn set_window_size(&mut self, size: PhysicalSize<u32>) {
+        self.input.window_size = size;
+    }
+
+    pub fn projector(&self) -> bool {
+        self.projector
+    }
+
+    pub fn set_projector(&mut self, projector: bool) {
+        self.projector = projector;
+    }
+
+    pub fn picture_in_picture(&self) -> bool {
+        self.picture_in_picture
+    }
+
+    /// Detaching the video opens the playlist in the main window
+    pub fn set_picture_in_picture(&mut self, picture_in_picture: bool) {
+        self.picture_in_picture = picture_in_picture;
+        if picture_in_picture && !self.cover_flow.is_open() {
+            self.cover_flow.toggle(&self.playlist);
+        }
+    }
+
+    /// How long an image fades into the next one
+    pub fn crossfade(&self) -> Duration {
+        Duration::from_millis(self.config.borrow().slideshow.crossfade_ms as u64)
+    }
+
+    fn toggle_slideshow(&mut self) {
+        self.slideshow = !self.slideshow;
+        // the current image gets its full interval
+        if let Some(shown_at) = self.image_shown_at.as_mut() {
+            *shown_at = Instant::now();
+        }
+    }
+
+    /// Moves on to the next item once the image has been up long enough, called once per event
+    /// loop iteration
+    pub fn advance_slideshow(&mut self) {
+        let Some(shown_at) = self.image_shown_at.filter(|_| self.slideshow) else {
+            return;
+        };
+        // pausing holds the current image
+        if self.paused {
+            self.image_shown_at = Some(Instant::now());
+            return;
+        }
+        let interval =
+            Duration::from_secs_f32(self.config.borrow().slideshow.interval_secs.max(0.5));
+        if shown_at.elapsed() < interval || self.playlist.len() < 2 {
+            return;
+        }
+        if let Some(index) = self.playlist.current_index() {
+            // starts over after the last one
+            self.play((index + 1) % self.playlist.len());
+        }
+    }
+
+    pub fn compare(&self) -> Option<&Compare> {
+        self.compare.as_ref()
+    }
+
+    /// Plays `uri` next to the current file, replacing the file it is compared with
+    #[cfg(feature = "ui")]
+    fn start_compare(&mut self, uri: String) {
+        match self.compare.as_mut() {
+            Some(compare) => compare.uri = uri,
+            None => self.compare = Some(Compare::new(uri)),
         }
+        // otherwise it is loaded once the event loop started its decoder
+        if self.on_compare_command.is_some() {
+            self.load_compare();
+        }
+    }
+
+    /// The event loop shuts the decoder of the compared file down once this is None
+    fn stop_compare(&mut self) {
+        self.compare = None;
+        self.on_compare_command = None;
+    }
+
+    /// Loads the compared file at the position of the current one. Both are seeked so they
+    /// start over at the same running time, after which the compared file follows the clock.
+    fn load_compare(&mut self) {
+        let Some(uri) = self.compare.as_ref().map(|compare| compare.uri.clone()) else {
+            return;
+        };
+        // only the current file is heard
+        let audio = AudioSettings {
+            muted: true,
+            ..self.config.borrow().audio
+        };
+        self.send_compare_command(DecoderCommand::SetAudioSettings(audio));
+        let seek_mode = self.config.borrow().seek_mode;
+        self.send_compare_command(DecoderCommand::SetSeekMode(seek_mode));
+        self.send_compare_command(DecoderCommand::SetLoopFile(self.loop_file));
+        self.send_compare_command(DecoderCommand::FollowClock(self.clock.clone()));
+        self.send_compare_command(DecoderCommand::Load(uri));
+        self.send_compare_command(DecoderCommand::SetPaused(self.paused));
+        self.send_command(DecoderCommand::Seek(self.controls.position()));
+    }
+
+    /// Called once the decoder of the compared file is running
+    pub fn set_on_compare_command<F: FnMut(DecoderCommand) + Send + 'static>(&mut self, func: F) {
+        self.on_compare_command = Some(Box::new(func));
+        self.load_compare();
+    }
 
+    fn send_compare_command(&mut self, command: DecoderCommand) {
+        if let Some(on_compare_command) = self.on_compare_command.as_mut() {
+            on_compare_command(command);
+        }
+    }
+
+    /// Events of the decoder of the compared file, only its errors are of interest
+    pub fn handle_compare_event(&mut self, event: PlayerEvent) {
         match event {
-            WindowEvent::ModifiersChanged(state) => {
-                self.input.modifiers.alt = state.alt();
-                self.input.modifiers.ctrl = state.ctrl();
-                self.input.modifiers.shift = state.shift();
-                self.input.modifiers.mac_cmd = cfg!(target_os = "macos") && state.logo();
-                self.input.modifiers.command = if cfg!(target_os = "macos") {
-                    state.logo()
-                } else {
-                    state.ctrl()
-                };
+            PlayerEvent::Error(error) => self.toasts.push(format!("Compared file: {}", error)),
+            PlayerEvent::ReconnectFailed => self
+                .toasts
+                .push("Lost the connection to the compared stream".to_string()),
+            _ => {}
+        }
+    }
+
+    pub fn present_mode(&self) -> wgpu::PresentMode {
+        self.present_mode
+    }
+
+    pub fn set_supported_present_modes(&mut self, present_modes: Vec<wgpu::PresentMode>) {
+        self.supported_present_modes = present_modes;
+    }
+
+    pub fn set_surface_format(&mut self, format: wgpu::TextureFormat) {
+        self.surface_format = Some(format);
+    }
+
+    pub fn set_adapters(&mut self, adapter_names: Vec<String>, current_adapter: String) {
+        self.adapter_names = adapter_names;
+        self.current_adapter = current_adapter;
+    }
+
+    /// The textures of the ui were on the previous GPU, the thumbnails are made again
+    #[cfg(feature = "ui")]
+    pub fn forget_textures(&mut self) {
+        self.thumbnails.clear();
+        #[cfg(feature = "library")]
+        self.library_thumbnails.clear();
+        for item in self.playlist.items() {
+            self.thumbnailer.request(&item.uri);
+        }
+    }
+
+    pub fn set_on_command<F: FnMut(DecoderCommand) + Send + 'static>(&mut self, func: F) {
+        self.on_command = Some(Box::new(func));
+    }
+
+    fn send_command(&mut self, command: DecoderCommand) {
+        // seeks and the like move the compared file along
+        if let Some(mirrored) = compare::mirror(&command) {
+            self.send_compare_command(mirrored);
+        }
+        if let Some(on_command) = self.on_command.as_mut() {
+            on_command(command);
+        }
+    }
+
+    pub fn handle_player_event(&mut self, event: PlayerEvent) {
+        match event {
+            PlayerEvent::Stats(stats) => self.stats.set_decoder_stats(stats),
+            PlayerEvent::Buffering(percent) => self.controls.set_buffering(percent),
+            // still queued from the previous item, it would get its resume position
+            PlayerEvent::Position { generation, .. } if generation != self.load_generation => {}
+            PlayerEvent::Position {
+                position, duration, ..
+            } => {
+                self.controls.set_position(position, duration);
+                self.follow_cue_track(position);
+                // the tracks of a CUE sheet start at their own position
+                if let Some(item) = self
+                    .playlist
+                    .current()
+                    .filter(|item| cue::split_track_uri(&item.uri).1.is_none())
+                {
+                    self.config
+                        .borrow_mut()
+                        .set_recent_position(&item.uri, position, duration);
+                }
+                #[cfg(feature = "library")]
+                if let (Some(library), Some(item)) = (&self.library, self.playlist.current()) {
+                    library
+                        .borrow_mut()
+                        .update_position(&item.uri, position, duration);
+                }
             }
-            WindowEvent::KeyboardInput { input, .. } => {
-                if let Some(keycode) = input.virtual_keycode {
-                    if self.input.modifiers.command && keycode == VirtualKeyCode::V {
-                        if let Ok(path_or_url) = self.clipboard.get_contents() {
-                            if let Some(on_load_file_request) = self.on_load_file_request.take() {
-                                on_load_file_request(format_url(&path_or_url));
-                            }
-                        }
+            PlayerEvent::BufferedRanges(ranges) => self.controls.set_buffered_ranges(ranges),
+            PlayerEvent::Variants(variants) => self.variants = variants,
+            PlayerEvent::ActiveVariant(index) => self.active_variant = index,
+            PlayerEvent::AudioStream(index) => self.audio_stream = index,
+            PlayerEvent::AudioStreams(count) => self.audio_streams = count,
+            PlayerEvent::VideoStreams(count) => self.video_streams = Some(count),
+            PlayerEvent::Layout(layout) => self.detected_layout = layout,
+            PlayerEvent::LoopCleared => {
+                self.loop_file = false;
+                self.loop_a = None;
+                self.loop_b = None;
+                self.controls.set_loop_points(None, None);
+            }
+            PlayerEvent::Reconnecting {
+                attempt,
+                max_attempts,
+            } => self
+                .controls
+                .set_reconnecting(Some((attempt, max_attempts))),
+            PlayerEvent::ReconnectFailed => {
+                self.controls.set_reconnecting(None);
+                self.toasts
+                    .push("Lost the connection to the stream, gave up reconnecting".to_string());
+            }
+            PlayerEvent::Error(PlayerError::Unauthorized(message)) => self.prompt_login(message),
+            PlayerEvent::Error(error) => self.toasts.push(error.to_string()),
+            PlayerEvent::Tags(tags) => {
+                // the embedded title is nicer than the file name, the station stays the same
+                // while the title of internet radio follows the song
+                let title = tags.station.as_ref().or(tags.title.as_ref());
+                if let (Some(title), Some(item)) = (title, self.playlist.current()) {
+                    let is_new = self
+                        .config
+                        .borrow()
+                        .recent
+                        .first()
+                        .is_none_or(|recent| recent.uri != item.uri || recent.title != *title);
+                    if is_new {
+                        let uri = item.uri.clone();
+                        self.config.borrow_mut().add_recent(&uri, Some(title));
+                        self.config.borrow().save();
                     }
                 }
+                self.controls.set_tags(tags);
+            }
+            // uploaded straight to the renderer
+            PlayerEvent::CoverArt(_) | PlayerEvent::Image(_) => {}
+            PlayerEvent::Clock(clock) => {
+                self.clock = Some(clock.clone());
+                self.send_compare_command(DecoderCommand::FollowClock(Some(clock)));
             }
-            WindowEvent::DroppedFile(path) => {
-                if let Some(on_load_file_request) = self.on_load_file_request.take() {
-                    on_load_file_request(format_url(&path.to_string_lossy()));
+            PlayerEvent::Disc(position) => self.disc = Some(position),
+            PlayerEvent::Recording(Some(path)) => self.recording = Some((path, Instant::now())),
+            PlayerEvent::Recording(None) => {
+                if let Some((path, _)) = self.recording.take() {
+                    self.toasts
+                        .push(format!("Saved the recording to {}", path.display()));
                 }
             }
-            _ => {}
         }
     }
+
+    pub fn frame_received(&mut self) {
+        self.stats.frame_received();
+    }
+
+    pub fn frame_rendered(&mut self) {
+        self.stats.frame_rendered();
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        self.send_command(DecoderCommand::SetPaused(paused));
+        // keeps the position to continue at in case the player doesn't get to close cleanly
+        if paused {
+            self.config.borrow().save();
+        }
+    }
+
+    fn set_reverse(&mut self, reverse: bool) {
+        self.reverse = reverse;
+        self.send_command(DecoderCommand::SetRate(if reverse { -1.0 } else { 1.0 }));
+    }
+
+    /// Pauses and moves a single frame, stepping changes the direction playback resumes in
+    fn step_frame(&mut self, backward: bool) {
+        self.paused = true;
+        self.reverse = backward;
+        self.send_command(DecoderCommand::StepFrame { backward });
+    }
+
+    /// Adds the uri to the playlist and starts playing it
+    #[cfg(feature = "ui")]
+    fn enqueue_and_play(&mut self, uri: String) {
+        let index = self.enqueue(uri);
+        self.play(index);
+    }
+
+    /// Opens files from the command line or a later launch. They are played right away
+    /// unless `enqueue` is set, or enqueueing is configured and something is playing already.
+    pub fn open(&mut self, uris: Vec<String>, enqueue: bool) {
+        let enqueue = (enqueue || self.config.borrow().enqueue_opened_files)
+            && self.playlist.current().is_some();
+        let mut first = None;
+        for uri in uris {
+            if let Some(index) = self.enqueue_expanded(uri) {
+                first.get_or_insert(index);
+            }
+        }
+        if let (Some(index), false) = (first, enqueue) {
+            self.play(index);
+        }
+    }
+
+    /// Reopens the playlist of a previous session where it was left
+    pub fn restore_session(&mut self, session: Session) {
+        for uri in session.uris {
+            self.enqueue(uri);
+        }
+        let Some(index) = session.current.filter(|&index| index < self.playlist.len()) else {
+            return;
+        };
+        let start = (session.position_ms > 0).then(|| Duration::from_millis(session.position_ms));
+        self.play_at(index, start);
+        if session.paused {
+            self.set_paused(true);
+        }
+    }
+
+    /// Remembers the playlist and the window for the next start when sessions are restored.
+    /// Without a `window` the geometry of the previous session is kept.
+    pub fn save_session(&mut self, window: Option<WindowGeometry>) {
+        // the position in the recent items is only saved along with other changes
+        if !self.config.borrow().restore_session {
+            self.config.borrow().save();
+            return;
+        }
+        let previous_window = self
+            .config
+            .borrow()
+            .session
+            .as_ref()
+            .and_then(|session| session.window);
+        // the standard input can't be read again
+        let items = self.playlist.items();
+        let is_stdin = |item: &PlaylistItem| item.uri == STDIN_URI;
+        let current = self
+            .playlist
+            .current_index()
+            .filter(|&index| !is_stdin(&items[index]))
+            .map(|index| index - items[..index].iter().filter(|item| is_stdin(item)).count());
+        self.config.borrow_mut().session = Some(Session {
+            uris: items
+                .iter()
+                .filter(|item| !is_stdin(item))
+                .map(|item| item.uri.clone())
+                .collect(),
+            current,
+            position_ms: self.controls.position().as_millis() as u64,
+            paused: self.paused,
+            window: window.or(previous_window),
+        });
+        self.config.borrow().save();
+    }
+
+    /// Adds the uri to the end of the playlist and returns its index
+    fn enqueue(&mut self, uri: String) -> usize {
+        #[cfg(feature = "ui")]
+        self.thumbnailer.request(&uri);
+        let mut item = PlaylistItem::new(uri);
+        item.av_offset_ms = self.config.borrow().media_profile(&item.uri).av_offset_ms;
+        self.playlist.push(item)
+    }
+
+    /// Selects the next track of a CUE sheet when the file plays into it, and stops at the end of
+    /// the track otherwise
+    fn follow_cue_track(&mut self, position: Duration) {
+        let Some(index) = self.playlist.current_index() else {
+            return;
+        };
+        let items = self.playlist.items();
+        let (file_uri, Some(track)) = cue::split_track_uri(&items[index].uri) else {
+            return;
+        };
+        if !self.track_reached {
+            self.track_reached = track.contains(position);
+            return;
+        }
+        if position < track.end.unwrap_or(Duration::MAX) {
+            return;
+        }
+
+        let next_track =
+            items
+                .get(index + 1)
+                .and_then(|item| match cue::split_track_uri(&item.uri) {
+                    (next_file_uri, Some(next_track)) if next_file_uri == file_uri => {
+                        Some(next_track)
+                    }
+                    _ => None,
+                });
+        if next_track.is_some_and(|next_track| next_track.contains(position)) {
+            if let Some(item) = self.playlist.select(index + 1) {
+                let (uri, title) = (item.uri.clone(), item.title.clone());
+                self.config.borrow_mut().add_recent(&uri, Some(&title));
+                self.config.borrow().save();
+            }
+        } else if !self.paused {
+            self.set_paused(true);
+        }
+    }
+
+    /// Enqueues the uri, or the entries of the M3U, PLS or XSPF file it points to. Returns the
+    /// index of the first item that was added.
+    fn enqueue_expanded(&mut self, uri: String) -> Option<usize> {
+        let entries = match playlist_file::read_uri(&uri) {
+            Ok(Some(entries)) => entries,
+            Ok(None) => return Some(self.enqueue(uri)),
+            Err(err) => {
+                tracing::warn!("Failed to read the playlist {}: {:#}", uri, err);
+                self.toasts
+                    .push(format!("Failed to read the playlist {}", uri));
+                return None;
+            }
+        };
+        self.enqueue_entries(entries)
+    }
+
+    /// Adds entries of a playlist file or a media server with their titles, returns the index
+    /// of the first one
+    fn enqueue_entries(&mut self, entries: Vec<PlaylistEntry>) -> Option<usize> {
+        let mut first = None;
+        for entry in entries {
+            let index = self.enqueue(entry.uri);
+            if let (Some(title), Some(item)) = (entry.title, self.playlist.get_mut(index)) {
+                item.title = title;
+            }
+            first.get_or_insert(index);
+        }
+        first
+    }
+
+    /// Enqueues the files dropped on the window in natural order and plays the first one.
+    /// winit sends a `DroppedFile` per file, so this runs once all of them have arrived.
+    pub fn finish_drop(&mut self) {
+        if self.dropped_files.is_empty() {
+            return;
+        }
+
+        let (shaders, dropped): (Vec<_>, Vec<_>) = self.dropped_files.drain(..).partition(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "wgsl")
+        });
+        for path in shaders {
+            self.add_user_shader(path);
+        }
+        let (luts, dropped): (Vec<_>, Vec<_>) = dropped.into_iter().partition(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "cube")
+        });
+        if let Some(path) = luts.into_iter().last() {
+            self.load_lut(path);
+        }
+
+        // DVD and Blu-ray folders play from their menu
+        let discs: Vec<_> = dropped
+            .iter()
+            .filter_map(|path| disc::folder_uri(path))
+            .collect();
+        let dropped: Vec<_> = dropped
+            .into_iter()
+            .filter(|path| disc::folder_uri(path).is_none())
+            .collect();
+        let mut first = None;
+        for uri in discs {
+            let index = self.enqueue(uri);
+            first.get_or_insert(index);
+        }
+
+        // the images of dropped folders are shown as a slideshow
+        let (folders, dropped): (Vec<_>, Vec<_>) =
+            dropped.into_iter().partition(|path| path.is_dir());
+        if !folders.is_empty() {
+            self.slideshow = true;
+        }
+
+        let (subtitles, mut media): (Vec<_>, Vec<_>) = dropped
+            .into_iter()
+            .partition(|path| subtitles::is_subtitle_file(path));
+        media.extend(folders.iter().flat_map(|folder| folder_images(folder)));
+        media.sort_by(|a, b| natural_cmp(&a.to_string_lossy(), &b.to_string_lossy()));
+
+        for path in media {
+            if let Some(index) = self.enqueue_expanded(format_url(&path.to_string_lossy())) {
+                first.get_or_insert(index);
+            }
+        }
+        if let Some(index) = first {
+            self.play(index);
+        }
+
+        // after playing, which clears the subtitles of the previous item
+        if let Some(path) = subtitles.first() {
+            self.load_subtitle(path, None);
+        }
+        #[cfg(feature = "ui")]
+        if let Some(path) = subtitles.get(1) {
+            self.load_second_subtitle(path, None);
+        }
+    }
+
+    fn play(&mut self, index: usize) {
+        self.play_at(index, None);
+    }
+
+    /// Plays the item from `start`, without one it continues where it was left the last time
+    /// Plays a pipeline of the user outside the playlist, see `CustomPipeline`
+    pub fn play_pipeline(&mut self, pipeline: PipelineDescription) {
+        self.playlist.deselect();
+        self.image_shown_at = None;
+        self.disc = None;
+        self.reset_playback(None);
+        self.track_reached = true;
+        self.load_generation += 1;
+        self.send_command(DecoderCommand::PlayPipeline(pipeline.clone()));
+        self.pipeline = Some(pipeline);
+    }
+
+    /// Forgets the state of the previous item and sends the settings before the next one loads
+    fn reset_playback(&mut self, credentials: Option<Credentials>) {
+        self.subtitle = None;
+        #[cfg(feature = "ui")]
+        {
+            self.second_subtitle = None;
+        }
+        self.paused = false;
+        self.reverse = false;
+        // the comparison is between two encodes of the same video
+        self.stop_compare();
+        self.clock = None;
+        self.controls.reset();
+        self.loop_a = None;
+        self.loop_b = None;
+        self.clip_in = None;
+        self.clip_out = None;
+        self.audio_stream = None;
+        self.audio_streams = 0;
+        self.video_streams = None;
+        self.detected_layout = VideoLayout::default();
+        self.sphere_view = SphereView::default();
+        self.variants.clear();
+        self.active_variant = None;
+        self.selected_variant = None;
+        let settings = {
+            let config = self.config.borrow();
+            [
+                DecoderCommand::SetRtspSettings(config.rtsp),
+                DecoderCommand::SetIngestSettings(config.ingest.clone()),
+                DecoderCommand::SetCredentials(credentials),
+                DecoderCommand::SetProxySettings(
+                    self.session_proxy
+                        .clone()
+                        .unwrap_or_else(|| config.proxy.clone()),
+                ),
+                DecoderCommand::SetAudioSettings(config.audio),
+                DecoderCommand::SetVisualizer(config.visualizer),
+                DecoderCommand::SetCaptions(config.captions),
+                DecoderCommand::SetSubtitleStyle(config.subtitle_style.clone()),
+                DecoderCommand::SetLanguagePreferences(config.languages.clone()),
+                DecoderCommand::SetSeekMode(config.seek_mode),
+                DecoderCommand::SetCacheSettings(config.cache.clone()),
+            ]
+        };
+        for command in settings {
+            self.send_command(command);
+        }
+    }
+
+    fn play_at(&mut self, index: usize, start: Option<Duration>) {
+        if let Some(item) = self.playlist.select(index) {
+            let uri = item.uri.clone();
+            let title = item.title.clone();
+            // the profiles of local files follow their content, so they survive renames
+            self.config.borrow_mut().identify_media(&uri);
+            let profile = self.config.borrow().media_profile(&uri);
+            let av_offset_ms = profile.av_offset_ms;
+            if let Some(item) = self.playlist.current_mut() {
+                item.av_offset_ms = av_offset_ms;
+            }
+            self.config.borrow_mut().add_recent(&uri, Some(&title));
+            self.config.borrow().save();
+            let http_options = profile.http.clone();
+            self.image_shown_at = cover_art::is_image_uri(&uri).then(Instant::now);
+            self.disc = None;
+            self.http_headers_text = http_options
+                .headers
+                .iter()
+                .map(|(name, value)| format!("{}: {}\n", name, value))
+                .collect();
+            self.cookie_file_text = http_options
+                .cookie_file
+                .as_ref()
+                .map(|path| path.display().to_string())
+                .unwrap_or_default();
+            self.http_options = http_options.clone();
+            self.pipeline = None;
+            self.reset_playback(self.credentials(&uri));
+            self.send_command(DecoderCommand::SetHttpOptions(http_options));
+            // the tracks of a CUE sheet are seeks within the file that is already loaded
+            let (file_uri, track) = cue::split_track_uri(&uri);
+            let start = match (track, start) {
+                (Some(track), _) => Some(track.start),
+                (None, Some(start)) => Some(start),
+                (None, None) => {
+                    let resume_position = self.config.borrow().resume_position(&uri);
+                    if let Some(position) = resume_position {
+                        self.toasts
+                            .push(format!("Continuing at {}", controls::format_time(position)));
+                    }
+                    resume_position
+                }
+            };
+            if let Some(start) = start {
+                self.send_command(DecoderCommand::SetStartPosition(start));
+            }
+            self.track_reached = track.is_none();
+            self.load_generation += 1;
+            self.send_command(DecoderCommand::Load(file_uri.to_string()));
+            self.send_command(DecoderCommand::SetAvOffset(av_offset_ms));
+            self.send_command(DecoderCommand::SetSubtitleDelay(profile.subtitle_delay_ms));
+            self.restore_choices(&profile);
+        }
+    }
+
+    /// Picks the audio stream and loads the subtitle files that were picked the last time the
+    /// item played
+    fn restore_choices(&mut self, profile: &MediaProfile) {
+        if let Some(index) = profile.audio_stream {
+            self.send_command(DecoderCommand::SetAudioStream(index));
+        }
+        if let Some(path) = profile.subtitle.as_ref().filter(|path| path.exists()) {
+            let encoding = profile
+                .subtitle_encoding
+                .as_ref()
+                .and_then(|label| Encoding::for_label(label.as_bytes()));
+            self.load_subtitle(path, encoding);
+        }
+        #[cfg(feature = "ui")]
+        if let Some(path) = profile
+            .second_subtitle
+            .as_ref()
+            .filter(|path| path.exists())
+        {
+            self.load_second_subtitle(path, None);
+        }
+    }
+
+    /// Changes the profile of the current item and saves it, for the next time it is played
+    fn update_media_profile(&mut self, change: impl FnOnce(&mut MediaProfile)) {
+        let Some(item) = self.playlist.current() else {
+            return;
+        };
+        let uri = item.uri.clone();
+
+        let mut profile = self.config.borrow().media_profile(&uri);
+        change(&mut profile);
+        self.config.borrow_mut().set_media_profile(&uri, profile);
+        self.config.borrow().save();
+    }
+
+    fn set_audio_stream(&mut self, index: usize) {
+        self.send_command(DecoderCommand::SetAudioStream(index));
+        self.update_media_profile(|profile| profile.audio_stream = Some(index));
+    }
+
+    /// The aspect ratio chosen for the current item, None shows it at its own
+    pub fn aspect_ratio(&self) -> Option<f32> {
+        self.playlist
+            .current()
+            .and_then(|item| self.config.borrow().media_profile(&item.uri).aspect_ratio)
+            .map(|aspect_ratio| aspect_ratio.value())
+    }
+
+    /// How much the current item is zoomed in, 1 fits it to the window
+    pub fn zoom(&self) -> f32 {
+        self.playlist
+            .current()
+            .and_then(|item| self.config.borrow().media_profile(&item.uri).zoom)
+            .unwrap_or(1.0)
+    }
+
+    fn set_zoom(&mut self, zoom: f32) {
+        let zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+        // keep the default out of the config
+        let zoom = ((zoom - 1.0).abs() > 0.01).then_some(zoom);
+        self.update_media_profile(|profile| profile.zoom = zoom);
+    }
+
+    /// Applies the offset to the current item and remembers it for the next time it is played
+    #[cfg(feature = "ui")]
+    fn set_av_offset(&mut self, av_offset_ms: i64, save: bool) {
+        let Some(item) = self.playlist.current_mut() else {
+            return;
+        };
+
+        item.av_offset_ms = av_offset_ms;
+        let uri = item.uri.clone();
+
+        let mut profile = self.config.borrow().media_profile(&uri);
+        profile.av_offset_ms = av_offset_ms;
+        self.config.borrow_mut().set_media_profile(&uri, profile);
+        if save {
+            self.config.borrow().save();
+        }
+
+        self.send_command(DecoderCommand::SetAvOffset(av_offset_ms));
+    }
+
+    fn subtitle_delay_ms(&self) -> i64 {
+        self.playlist.current().map_or(0, |item| {
+            self.config
+                .borrow()
+                .media_profile(&item.uri)
+                .subtitle_delay_ms
+        })
+    }
+
+    /// Shifts the subtitles of the current item and remembers it for the next time it is played
+    fn set_subtitle_delay(&mut self, delay_ms: i64, save: bool) {
+        let Some(item) = self.playlist.current() else {
+            return;
+        };
+        let uri = item.uri.clone();
+
+        let mut profile = self.config.borrow().media_profile(&uri);
+        profile.subtitle_delay_ms = delay_ms;
+        self.config.borrow_mut().set_media_profile(&uri, profile);
+        if save {
+            self.config.borrow().save();
+        }
+
+        self.send_command(DecoderCommand::SetSubtitleDelay(delay_ms));
+    }
+
+    #[cfg(feature = "ui")]
+    fn second_subtitle_delay_ms(&self) -> i64 {
+        self.playlist.current().map_or(0, |item| {
+            self.config
+                .borrow()
+                .media_profile(&item.uri)
+                .second_subtitle_delay_ms
+        })
+    }
+
+    /// Like `set_subtitle_delay`, the second subtitle is timed by the player itself
+    #[cfg(feature = "ui")]
+    fn set_second_subtitle_delay(&mut self, delay_ms: i64, save: bool) {
+        let Some(item) = self.playlist.current() else {
+            return;
+        };
+        let uri = item.uri.clone();
+
+        let mut profile = self.config.borrow().media_profile(&uri);
+        profile.second_subtitle_delay_ms = delay_ms;
+        self.config.borrow_mut().set_media_profile(&uri, profile);
+        if save {
+            self.config.borrow().save();
+        }
+    }
+
+    /// Restarts the current item when it has been playing for a bit, like music players do
+    fn play_previous(&mut self) {
+        let Some(index) = self.playlist.current_index() else {
+            return;
+        };
+
+        if self.controls.position() > SMART_PREVIOUS_THRESHOLD || index == 0 {
+            let command = self.controls.seek(Duration::ZERO);
+            self.send_command(command);
+        } else {
+            self.play(index - 1);
+        }
+    }
+
+    fn play_next(&mut self) {
+        if let Some(index) = self.playlist.current_index() {
+            if index + 1 < self.playlist.len() {
+                self.play(index + 1);
+            }
+        }
+    }
+
+    /// Turning them on or off reloads the pipeline at the current position
+    fn set_captions(&mut self, captions: CaptionSettings) {
+        self.config.borrow_mut().captions = captions;
+        self.config.borrow().save();
+        self.send_command(DecoderCommand::SetCaptions(captions));
+    }
+
+    #[cfg(feature = "ui")]
+    fn set_subtitle_style(&mut self, style: SubtitleStyle) {
+        self.config.borrow_mut().subtitle_style = style.clone();
+        self.config.borrow().save();
+        self.send_command(DecoderCommand::SetSubtitleStyle(style));
+    }
+
+    fn volume(&self) -> f64 {
+        self.config.borrow().audio.volume
+    }
+
+    fn set_volume(&mut self, volume: f64) {
+        let audio = {
+            let mut config = self.config.borrow_mut();
+            config.audio.volume = volume.clamp(0.0, 1.0);
+            config.audio.muted = false;
+            config.save();
+            config.audio
+        };
+        self.send_command(DecoderCommand::SetAudioSettings(audio));
+        self.controls.show_volume(audio.volume, false);
+    }
+
+    fn toggle_mute(&mut self) {
+        let audio = {
+            let mut config = self.config.borrow_mut();
+            config.audio.muted = !config.audio.muted;
+            config.save();
+            config.audio
+        };
+        self.send_command(DecoderCommand::SetAudioSettings(audio));
+        self.controls.show_volume(audio.volume, audio.muted);
+    }
+
+    fn adjust_audio_delay(&mut self, delta_ms: i64) {
+        let audio = {
+            let mut config = self.config.borrow_mut();
+            config.audio.output_delay_ms += delta_ms;
+            config.save();
+            config.audio
+        };
+        self.send_command(DecoderCommand::SetAudioSettings(audio));
+    }
+
+    /// Stores the edited http options for the current item and reloads it, they are only used
+    /// when the sources are created
+    #[cfg(feature = "ui")]
+    fn apply_http_options(&mut self) {
+        let Some(index) = self.playlist.current_index() else {
+            return;
+        };
+        let uri = self.playlist.items()[index].uri.clone();
+
+        let mut profile = self.config.borrow().media_profile(&uri);
+        profile.http = HttpOptions {
+            headers: self
+                .http_headers_text
+                .lines()
+                .filter_map(|line| line.split_once(':'))
+                .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+                .filter(|(name, _)| !name.is_empty())
+                .collect(),
+            user_agent: self.http_options.user_agent.trim().to_string(),
+            referer: self.http_options.referer.trim().to_string(),
+            cookie_file: Some(self.cookie_file_text.trim())
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from),
+        };
+        self.config.borrow_mut().set_media_profile(&uri, profile);
+        self.config.borrow().save();
+
+        self.play(index);
+    }
+
+    /// Marks A, then B, and clears the loop the third time
+    fn mark_ab_loop_point(&mut self) {
+        let position = self.controls.position();
+        match (self.loop_a, self.loop_b) {
+            (Some(a), None) if position > a => {
+                self.loop_b = Some(position);
+                self.send_command(DecoderCommand::SetAbLoop(Some((a, position))));
+            }
+            (None, _) | (Some(_), None) => self.loop_a = Some(position),
+            (Some(_), Some(_)) => {
+                self.loop_a = None;
+                self.loop_b = None;
+                self.send_command(DecoderCommand::SetAbLoop(None));
+            }
+        }
+        self.controls.set_loop_points(self.loop_a, self.loop_b);
+    }
+
+    /// Converts the subtitle file to UTF-8 and hands it to the decoder
+    fn load_subtitle(&mut self, path: &Path, encoding: Option<&'static Encoding>) {
+        match subtitles::load(path, encoding) {
+            Ok(subtitle) => {
+                let uri = format_url(&subtitle.converted_path.to_string_lossy());
+                self.send_command(DecoderCommand::SetSubtitle(uri));
+                let path = subtitle.path.clone();
+                let encoding = subtitle
+                    .encoding_overridden
+                    .then(|| subtitle.encoding.name().to_string());
+                self.update_media_profile(|profile| {
+                    profile.subtitle = Some(path);
+                    profile.subtitle_encoding = encoding;
+                });
+                self.subtitle = Some(subtitle);
+            }
+            Err(err) => {
+                tracing::warn!("Failed to load subtitle {:?}: {}", path, err);
+                self.toasts.push(format!(
+                    "Failed to load subtitle {}: {}",
+                    path.display(),
+                    err
+                ));
+            }
+        }
+    }
+
+    #[cfg(feature = "ui")]
+    fn load_second_subtitle(&mut self, path: &Path, encoding: Option<&'static Encoding>) {
+        match subtitles::load_second(path, encoding) {
+            Ok(subtitle) => {
+                let path = subtitle.file.path.clone();
+                self.update_media_profile(|profile| profile.second_subtitle = Some(path));
+                self.second_subtitle = Some(subtitle);
+            }
+            Err(err) => {
+                tracing::warn!("Failed to load subtitle {:?}: {}", path, err);
+                self.toasts.push(format!(
+                    "Failed to load subtitle {}: {}",
+                    path.display(),
+                    err
+                ));
+            }
+        }
+    }
+
+    fn snap_window(&mut self, scale: f64) {
+        self.fullscreen = false;
+        self.window_scale = Some(scale);
+    }
+
+    /// Goes fullscreen, on the monitor after the one it is on
+    fn fullscreen_on_next_monitor(&mut self) {
+        let from = self
+            .fullscreen_monitor
+            .as_ref()
+            .or(self.current_monitor.as_ref());
+        let index = from
+            .and_then(|from| self.monitors.iter().position(|name| name == from))
+            .map_or(0, |index| (index + 1) % self.monitors.len());
+        if let Some(name) = self.monitors.get(index) {
+            self.fullscreen_monitor = Some(name.clone());
+        }
+        self.fullscreen = true;
+    }
+
+    pub fn handle_media_key(&mut self, key: MediaKey) {
+        match key {
+            MediaKey::Play => self.set_paused(false),
+            MediaKey::Pause | MediaKey::Stop => self.set_paused(true),
+            MediaKey::PlayPause => self.execute(Action::TogglePause),
+            MediaKey::Next => self.execute(Action::NextItem),
+            MediaKey::Previous => self.execute(Action::PreviousItem),
+            // showing the window is up to the event loop
+            MediaKey::Raise => {}
+        }
+    }
+
+    /// The cover flow overlay is navigated with the d-pad or the stick, otherwise the buttons
+    /// control the playback
+    #[cfg(feature = "ui")]
+    pub fn handle_gamepad_button(&mut self, button: GamepadButton) {
+        if self.cover_flow.is_open() {
+            match button {
+                GamepadButton::Left => self.cover_flow.select_previous(),
+                GamepadButton::Right => self.cover_flow.select_next(&self.playlist),
+                GamepadButton::Confirm => {
+                    self.play(self.cover_flow.selected());
+                    self.cover_flow.close();
+                }
+                GamepadButton::Back | GamepadButton::Menu => self.cover_flow.close(),
+            }
+            return;
+        }
+        match button {
+            GamepadButton::Left => self.execute(Action::PreviousItem),
+            GamepadButton::Right => self.execute(Action::NextItem),
+            GamepadButton::Confirm => self.execute(Action::TogglePause),
+            GamepadButton::Menu => self.execute(Action::TogglePlaylistOverlay),
+            GamepadButton::Back => {}
+        }
+    }
+
+    #[cfg(feature = "network")]
+    pub fn handle_renderer_command(&mut self, command: RendererCommand) {
+        match command {
+            RendererCommand::Open { uri, title } => {
+                // control points send the uri again before playing it from the start
+                let index = match self.playlist.current_index() {
+                    Some(index) if self.playlist.items()[index].uri == uri => Some(index),
+                    _ => self.enqueue_entries(vec![PlaylistEntry { uri, title }]),
+                };
+                if let Some(index) = index {
+                    self.play(index);
+                    self.set_paused(true);
+                }
+            }
+            RendererCommand::Play => self.set_paused(false),
+            RendererCommand::Pause => self.set_paused(true),
+            RendererCommand::Stop => {
+                self.set_paused(true);
+                let command = self.controls.seek(Duration::ZERO);
+                self.send_command(command);
+            }
+            RendererCommand::Seek(position) => {
+                let command = self.controls.seek(position);
+                self.send_command(command);
+            }
+            RendererCommand::Next => self.play_next(),
+            RendererCommand::Previous => self.play_previous(),
+            RendererCommand::SetVolume(volume) => self.set_volume(volume),
+            RendererCommand::SetMuted(muted) => {
+                if muted != self.config.borrow().audio.muted {
+                    self.toggle_mute();
+                }
+            }
+        }
+    }
+
+    pub fn execute(&mut self, action: Action) {
+        match action {
+            Action::OpenCommandPalette => self.command_palette.open(),
+            Action::TogglePause => self.set_paused(!self.paused),
+            Action::StepFrameForward => self.step_frame(false),
+            Action::StepFrameBackward => self.step_frame(true),
+            Action::ToggleReversePlayback => self.set_reverse(!self.reverse),
+            #[cfg(feature = "ui")]
+            Action::PasteFromClipboard => {
+                if let Ok(path_or_url) = self.clipboard.get_contents() {
+                    self.enqueue_and_play(format_url(&path_or_url));
+                }
+            }
+            Action::TogglePlaylistOverlay => self.cover_flow.toggle(&self.playlist),
+            Action::ToggleStatistics => self.stats.toggle(),
+            #[cfg(feature = "ui")]
+            Action::ToggleLogConsole => self.log_console.toggle(),
+            #[cfg(not(feature = "ui"))]
+            Action::PasteFromClipboard | Action::ToggleLogConsole => {}
+            Action::ToggleTimeOsd => {
+                let mut config = self.config.borrow_mut();
+                config.time_display.osd = !config.time_display.osd;
+                config.save();
+            }
+            Action::ToggleColorLut => {
+                if self.lut.is_some() {
+                    self.lut_enabled = !self.lut_enabled;
+                }
+            }
+            Action::ToggleDeband => {
+                let mut config = self.config.borrow_mut();
+                config.postprocess.deband = !config.postprocess.deband;
+                config.save();
+            }
+            Action::ToggleAudioOnly => {
+                self.audio_only = !self.audio_only;
+                self.send_command(DecoderCommand::SetAudioOnly(
+                    self.audio_only || self.background,
+                ));
+            }
+            Action::ToggleProjector => self.projector = !self.projector,
+            Action::TogglePictureInPicture => self.set_picture_in_picture(!self.picture_in_picture),
+            Action::ToggleLoopFile => {
+                self.loop_file = !self.loop_file;
+                self.send_command(DecoderCommand::SetLoopFile(self.loop_file));
+            }
+            Action::MarkAbLoopPoint => self.mark_ab_loop_point(),
+            Action::PreviousItem => self.play_previous(),
+            Action::NextItem => self.play_next(),
+            Action::IncreaseAudioDelay => self.adjust_audio_delay(AUDIO_DELAY_STEP_MS),
+            Action::DecreaseAudioDelay => self.adjust_audio_delay(-AUDIO_DELAY_STEP_MS),
+            Action::IncreaseSubtitleDelay => {
+                self.set_subtitle_delay(self.subtitle_delay_ms() + SUBTITLE_DELAY_STEP_MS, true)
+            }
+            Action::DecreaseSubtitleDelay => {
+                self.set_subtitle_delay(self.subtitle_delay_ms() - SUBTITLE_DELAY_STEP_MS, true)
+            }
+            Action::ToggleFullscreen => self.fullscreen = !self.fullscreen,
+            Action::IncreaseVolume => self.set_volume(self.volume() + VOLUME_STEP),
+            Action::DecreaseVolume => self.set_volume(self.volume() - VOLUME_STEP),
+            Action::ToggleMute => self.toggle_mute(),
+            Action::SnapWindowHalfSize => self.snap_window(0.5),
+            Action::SnapWindowVideoSize => self.snap_window(1.0),
+            Action::SnapWindowDoubleSize => self.snap_window(2.0),
+            Action::FullscreenOnNextMonitor => self.fullscreen_on_next_monitor(),
+            Action::NewWindow => self.new_window = Some(Vec::new()),
+            Action::ToggleSlideshow => self.toggle_slideshow(),
+            Action::OpenDiscMenu => self.send_command(DecoderCommand::Navigate(
+                DiscNavigation::Menu(DiscMenu::Root),
+            )),
+            Action::ToggleRecording => self.toggle_recording(),
+            Action::SetClipIn => self.clip_in = Some(self.controls.position()),
+            Action::SetClipOut => self.clip_out = Some(self.controls.position()),
+            Action::ExportClip => self.export_clip(),
+            Action::ExportFrames => self.export_frames(),
+            Action::ExtractAudio => self.extract_audio(),
+            Action::TranscodeItem => self.transcode(self.playlist.current_index()),
+            Action::ToggleCaptions => {
+                let captions = self.config.borrow().captions;
+                let captions = CaptionSettings {
+                    enabled: !captions.enabled,
+                    ..captions
+                };
+                self.set_captions(captions);
+                self.toasts.push(if captions.enabled {
+                    format!("Closed captions {}", captions.channel.label())
+                } else {
+                    "Closed captions off".to_string()
+                });
+            }
+            Action::CycleAspectRatio => {
+                let current = self
+                    .playlist
+                    .current()
+                    .and_then(|item| self.config.borrow().media_profile(&item.uri).aspect_ratio);
+                // the video's own comes after the last one
+                let next = match current {
+                    None => Some(AspectRatio::ALL[0]),
+                    Some(aspect_ratio) => AspectRatio::ALL
+                        .iter()
+                        .position(|option| *option == aspect_ratio)
+                        .and_then(|index| AspectRatio::ALL.get(index + 1))
+                        .copied(),
+                };
+                self.update_media_profile(|profile| profile.aspect_ratio = next);
+                self.toasts.push(format!(
+                    "Aspect ratio {}",
+                    next.map_or("of the video", |aspect_ratio| aspect_ratio.label())
+                ));
+            }
+            Action::CycleAudioStream => {
+                if self.audio_streams > 1 {
+                    let index = self.audio_stream.map_or(0, |index| index + 1) % self.audio_streams;
+                    self.set_audio_stream(index);
+                    self.toasts.push(format!(
+                        "Audio track {} of {}",
+                        index + 1,
+                        self.audio_streams
+                    ));
+                }
+            }
+            Action::PlayTestSource => {
+                let source = PipelineDescription::test_source(&self.config.borrow().test_source);
+                self.play_pipeline(source);
+            }
+            Action::CycleScalingMode => {
+                let index = ScalingMode::ALL
+                    .iter()
+                    .position(|&mode| mode == self.scaling_mode)
+                    .unwrap_or(0);
+                self.scaling_mode = ScalingMode::ALL[(index + 1) % ScalingMode::ALL.len()];
+            }
+        }
+    }
+
+    /// Lists the recent files, returns the uri that was clicked
+    #[cfg(feature = "ui")]
+    fn recent_ui(&mut self, ui: &mut egui::Ui) -> Option<String> {
+        let mut clicked = None;
+        let mut new_window = None;
+        let mut compare = None;
+        let playing = self.playlist.current().is_some();
+        for item in &self.config.borrow().recent {
+            let response = ui
+                .selectable_label(false, &item.title)
+                .on_hover_text(&item.uri);
+            if response.clicked() {
+                clicked = Some(item.uri.clone());
+            }
+            response.context_menu(|ui| {
+                if ui.button("Open in new window").clicked() {
+                    new_window = Some(vec![item.uri.clone()]);
+                    ui.close_menu();
+                }
+                if playing && ui.button("Compare with the current file").clicked() {
+                    compare = Some(item.uri.clone());
+                    ui.close_menu();
+                }
+            });
+        }
+        if new_window.is_some() {
+            self.new_window = new_window;
+        }
+        if let Some(uri) = compare {
+            self.start_compare(uri);
+        }
+        if ui.small_button("Clear").clicked() {
+            self.config.borrow_mut().recent.clear();
+            self.config.borrow().save();
+        }
+        clicked
+    }
+
+    /// The recent items that were left before their end, with how far they played
+    #[cfg(feature = "ui")]
+    fn continue_watching_ui(&mut self, ui: &mut egui::Ui) -> Option<String> {
+        let mut clicked = None;
+        let mut forgotten = None;
+        egui::Grid::new("continue_watching")
+            .num_columns(2)
+            .show(ui, |ui| {
+                for item in self.config.borrow().continue_watching() {
+                    let response = ui
+                        .selectable_label(false, &item.title)
+                        .on_hover_text(&item.uri);
+                    if response.clicked() {
+                        clicked = Some(item.uri.clone());
+                    }
+                    response.context_menu(|ui| {
+                        if ui.button("Remove").clicked() {
+                            forgotten = Some(item.uri.clone());
+                            ui.close_menu();
+                        }
+                    });
+                    let remaining = Duration::from_millis(
+                        item.duration_ms
+                            .unwrap_or_default()
+                            .saturating_sub(item.position_ms),
+                    );
+                    ui.add(
+                        egui::ProgressBar::new(item.progress())
+                            .desired_width(120.0)
+                            .text(format!("{} left", controls::format_time(remaining))),
+                    );
+                    ui.end_row();
+                }
+            });
+        // it starts from the beginning the next time
+        if let Some(uri) = forgotten {
+            self.config
+                .borrow_mut()
+                .set_recent_position(&uri, Duration::ZERO, None);
+            self.config.borrow().save();
+        }
+        clicked
+    }
+
+    /// Shown in the middle of the window until something is opened, the library grid takes its
+    /// place once there is something in it
+    #[cfg(feature = "ui")]
+    fn splash_ui(&mut self, ctx: &egui::Context) {
+        #[cfg(feature = "library")]
+        if self
+            .library
+            .as_ref()
+            .is_some_and(|library| !library.borrow().items().is_empty())
+        {
+            return self.library_ui(ctx);
+        }
+
+        let mut clicked = None;
+        egui::Area::new("splash")
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.set_max_width(420.0);
+                    ui.heading("Drop a file or paste a url");
+                    let paste = Action::PasteFromClipboard
+                        .key_binding()
+                        .map(|binding| binding.to_string())
+                        .unwrap_or_default();
+                    ui.weak(format!("{} plays the path or url on the clipboard", paste));
+                    if self.config.borrow().continue_watching().next().is_some() {
+                        ui.separator();
+                        ui.label("Continue watching");
+                        clicked = self.continue_watching_ui(ui);
+                    }
+                    if !self.config.borrow().recent.is_empty() {
+                        ui.separator();
+                        ui.label("Recent");
+                        let recent = self.recent_ui(ui);
+                        clicked = clicked.take().or(recent);
+                    }
+                });
+            });
+
+        if let Some(uri) = clicked {
+            self.enqueue_and_play(uri);
+        }
+    }
+
+    /// The start screen with a library, its items as a grid of thumbnails
+    #[cfg(all(feature = "ui", feature = "library"))]
+    fn library_ui(&mut self, ctx: &egui::Context) {
+        let scanning = self
+            .library
+            .as_ref()
+            .is_some_and(|library| library.borrow().is_scanning());
+        let mut clicked = None;
+        let mut toggled = None;
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.heading("Library");
+                if scanning {
+                    ui.spinner();
+                    ui.weak("Scanning");
+                }
+            });
+            let paste = Action::PasteFromClipboard
+                .key_binding()
+                .map(|binding| binding.to_string())
+                .unwrap_or_default();
+            ui.weak(format!(
+                "Drop a file or press {} to play the path or url on the clipboard",
+                paste
+            ));
+            if self.config.borrow().continue_watching().next().is_some() {
+                ui.separator();
+                ui.label("Continue watching");
+                clicked = self.continue_watching_ui(ui);
+            }
+            ui.separator();
+
+            let Some(library) = self.library.clone() else {
+                return;
+            };
+            let library = library.borrow();
+
+            let image_size = egui::vec2(LIBRARY_TILE_WIDTH, LIBRARY_TILE_WIDTH * 9.0 / 16.0);
+            let line_height = ui.text_style_height(&egui::TextStyle::Body);
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                ui.horizontal_wrapped(|ui| {
+                    for item in library.items() {
+                        let (rect, response) = ui.allocate_exact_size(
+                            image_size + egui::vec2(0.0, line_height * 2.0 + 4.0),
+                            egui::Sense::click(),
+                        );
+                        if ui.is_rect_visible(rect) {
+                            let painter = ui.painter().with_clip_rect(rect);
+                            let image_rect = egui::Rect::from_min_size(rect.min, image_size);
+                            painter.rect_filled(image_rect, 2.0, ui.visuals().extreme_bg_color);
+                            let texture = self
+                                .library_thumbnails
+                                .entry(item.uri.clone())
+                                .or_insert_with(|| {
+                                    library.thumbnail(&item.uri).map(|thumbnail| {
+                                        let image = egui::ColorImage::from_rgba_unmultiplied(
+                                            [thumbnail.width as usize, thumbnail.height as usize],
+                                            &thumbnail.data,
+                                        );
+                                        ctx.load_texture(
+                                            &thumbnail.uri,
+                                            image,
+                                            egui::TextureOptions::LINEAR,
+                                        )
+                                    })
+                                });
+                            match texture {
+                                Some(texture) => {
+                                    let size = texture.size_vec2();
+                                    let scale = (image_size.x / size.x).min(image_size.y / size.y);
+                                    painter.image(
+                                        texture.id(),
+                                        egui::Rect::from_center_size(
+                                            image_rect.center(),
+                                            size * scale,
+                                        ),
+                                        egui::Rect::from_min_max(
+                                            egui::pos2(0.0, 0.0),
+                                            egui::pos2(1.0, 1.0),
+                                        ),
+                                        egui::Color32::WHITE,
+                                    );
+                                }
+                                None => {
+                                    painter.text(
+                                        image_rect.center(),
+                                        egui::Align2::CENTER_CENTER,
+                                        "♫",
+                                        egui::FontId::proportional(32.0),
+                                        ui.visuals().weak_text_color(),
+                                    );
+                                }
+                            }
+                            if item.watched {
+                                painter.text(
+                                    image_rect.right_top() + egui::vec2(-6.0, 4.0),
+                                    egui::Align2::RIGHT_TOP,
+                                    "✔",
+                                    egui::FontId::proportional(18.0),
+                                    egui::Color32::WHITE,
+                                );
+                            }
+                            if response.hovered() {
+                                painter.rect_stroke(image_rect, 2.0, ui.visuals().selection.stroke);
+                            }
+                            painter.text(
+                                image_rect.left_bottom() + egui::vec2(0.0, 2.0),
+                                egui::Align2::LEFT_TOP,
+                                &item.title,
+                                egui::TextStyle::Body.resolve(ui.style()),
+                                ui.visuals().text_color(),
+                            );
+                            if let Some(duration) = item.duration {
+                                painter.text(
+                                    image_rect.left_bottom() + egui::vec2(0.0, line_height + 2.0),
+                                    egui::Align2::LEFT_TOP,
+                                    controls::format_time(duration),
+                                    egui::TextStyle::Small.resolve(ui.style()),
+                                    ui.visuals().weak_text_color(),
+                                );
+                            }
+                        }
+
+                        let response = response.on_hover_text(&item.title);
+                        if response.clicked() {
+                            clicked = Some(item.uri.clone());
+                        }
+                        response.context_menu(|ui| {
+                            let label = if item.watched {
+                                "Mark as unwatched"
+                            } else {
+                                "Mark as watched"
+                            };
+                            if ui.button(label).clicked() {
+                                toggled = Some((item.uri.clone(), !item.watched));
+                                ui.close_menu();
+                            }
+                        });
+                    }
+                });
+            });
+        });
+
+        if let (Some((uri, watched)), Some(library)) = (toggled, &self.library) {
+            library.borrow_mut().set_watched(&uri, watched);
+        }
+        if let Some(uri) = clicked {
+            self.enqueue_and_play(uri);
+        }
+    }
+
+    #[cfg(all(feature = "ui", feature = "library"))]
+    fn library_settings_ui(&mut self, ui: &mut egui::Ui) {
+        let mut changed = false;
+        let mut removed = None;
+        for (index, folder) in self.config.borrow().library_folders.iter().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(folder.display().to_string());
+                if ui.small_button("Remove").clicked() {
+                    removed = Some(index);
+                }
+            });
+        }
+        if let Some(index) = removed {
+            self.config.borrow_mut().library_folders.remove(index);
+            changed = true;
+        }
+
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.library_folder_path).hint_text("Folder"));
+            let path = PathBuf::from(self.library_folder_path.trim());
+            if ui
+                .add_enabled(
+                    !self.library_folder_path.trim().is_empty(),
+                    egui::Button::new("Add"),
+                )
+                .clicked()
+            {
+                if !path.is_dir() {
+                    self.toasts
+                        .push(format!("{} isn't a folder", path.display()));
+                } else if !self.config.borrow().library_folders.contains(&path) {
+                    self.config.borrow_mut().library_folders.push(path);
+                    self.library_folder_path.clear();
+                    changed = true;
+                }
+            }
+        });
+
+        let Some(library) = self.library.clone() else {
+            ui.weak("The library couldn't be opened, the log has the details");
+            return;
+        };
+        let mut library = library.borrow_mut();
+        ui.horizontal(|ui| {
+            if ui.button("Scan now").clicked() {
+                changed = true;
+            }
+            if library.is_scanning() {
+                ui.spinner();
+                ui.weak("Scanning");
+            } else {
+                ui.weak(format!("{} items", library.items().len()));
+            }
+        });
+        if changed {
+            self.config.borrow().save();
+            library.scan(self.config.borrow().library_folders.clone());
+        }
+    }
+
+    #[cfg(feature = "ui")]
+    fn recording_settings_ui(&mut self, ui: &mut egui::Ui) {
+        let label = if self.recording.is_some() {
+            "Stop recording"
+        } else {
+            "Record"
+        };
+        let binding = Action::ToggleRecording
+            .key_binding()
+            .map(|binding| binding.to_string())
+            .unwrap_or_default();
+        if ui.button(label).on_hover_text(binding).clicked() {
+            self.toggle_recording();
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Folder");
+            let mut folder = self
+                .config
+                .borrow()
+                .output_folder
+                .as_ref()
+                .map(|folder| folder.display().to_string())
+                .unwrap_or_default();
+            let default = self
+                .config
+                .borrow()
+                .output_directory()
+                .to_string_lossy()
+                .into_owned();
+            if ui
+                .add(egui::TextEdit::singleline(&mut folder).hint_text(default))
+                .changed()
+            {
+                self.config.borrow_mut().output_folder =
+                    (!folder.trim().is_empty()).then(|| PathBuf::from(folder.trim()));
+                self.config.borrow().save();
+            }
+        });
+        ui.weak(
+            "The streams are saved as they are, without encoding them again, starting at the \
+             next keyframe",
+        );
+    }
+
+    #[cfg(feature = "ui")]
+    fn clip_ui(&mut self, ui: &mut egui::Ui) {
+        let point =
+            |point: Option<Duration>| point.map_or_else(|| "-".to_string(), controls::format_time);
+        egui::Grid::new("clip").num_columns(3).show(ui, |ui| {
+            ui.label("In");
+            ui.label(point(self.clip_in));
+            ui.horizontal(|ui| {
+                let binding = Action::SetClipIn
+                    .key_binding()
+                    .map(|binding| binding.to_string())
+                    .unwrap_or_default();
+                if ui.button("Set").on_hover_text(binding).clicked() {
+                    self.execute(Action::SetClipIn);
+                }
+                if ui
+                    .add_enabled(self.clip_in.is_some(), egui::Button::new("Clear"))
+                    .clicked()
+                {
+                    self.clip_in = None;
+                }
+            });
+            ui.end_row();
+
+            ui.label("Out");
+            ui.label(point(self.clip_out));
+            ui.horizontal(|ui| {
+                let binding = Action::SetClipOut
+                    .key_binding()
+                    .map(|binding| binding.to_string())
+                    .unwrap_or_default();
+                if ui.button("Set").on_hover_text(binding).clicked() {
+                    self.execute(Action::SetClipOut);
+                }
+                if ui
+                    .add_enabled(self.clip_out.is_some(), egui::Button::new("Clear"))
+                    .clicked()
+                {
+                    self.clip_out = None;
+                }
+            });
+            ui.end_row();
+        });
+
+        let binding = Action::ExportClip
+            .key_binding()
+            .map(|binding| binding.to_string())
+            .unwrap_or_default();
+        if ui.button("Export").on_hover_text(binding).clicked() {
+            self.export_clip();
+        }
+        ui.weak(
+            "The streams are copied when the file format allows it, the clip then starts at the \
+             keyframe before the in point. Otherwise they're encoded again, which takes longer.",
+        );
+
+        ui.separator();
+        let mut settings = self.config.borrow().frame_export;
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_label("Frames as")
+                .selected_text(settings.format.label())
+                .show_ui(ui, |ui| {
+                    for format in FrameFormat::ALL {
+                        ui.selectable_value(&mut settings.format, format, format.label());
+                    }
+                });
+            if settings.format == FrameFormat::Jpeg {
+                ui.add(egui::Slider::new(&mut settings.jpeg_quality, 1..=100).text("Quality"));
+            }
+        });
+        if settings != self.config.borrow().frame_export {
+            self.config.borrow_mut().frame_export = settings;
+            self.config.borrow().save();
+        }
+        let binding = Action::ExportFrames
+            .key_binding()
+            .map(|binding| binding.to_string())
+            .unwrap_or_default();
+        if ui.button("Export frames").on_hover_text(binding).clicked() {
+            self.export_frames();
+        }
+        ui.weak("Every frame of the clip at full size, numbered in a folder of their own");
+    }
+
+    #[cfg(feature = "ui")]
+    fn transcode_ui(&mut self, ui: &mut egui::Ui) {
+        let mut preset = self.config.borrow().transcode_preset;
+        egui::ComboBox::from_label("Preset")
+            .selected_text(preset.label())
+            .show_ui(ui, |ui| {
+                for option in TranscodePreset::ALL {
+                    ui.selectable_value(&mut preset, option, option.label());
+                }
+            });
+        if preset != self.config.borrow().transcode_preset {
+            self.config.borrow_mut().transcode_preset = preset;
+            self.config.borrow().save();
+        }
+
+        ui.horizontal(|ui| {
+            let binding = Action::TranscodeItem
+                .key_binding()
+                .map(|binding| binding.to_string())
+                .unwrap_or_default();
+            if ui
+                .add_enabled(
+                    self.playlist.current().is_some(),
+                    egui::Button::new("Convert this item"),
+                )
+                .on_hover_text(binding)
+                .clicked()
+            {
+                self.execute(Action::TranscodeItem);
+            }
+            if ui
+                .button(format!("Convert all {} items", self.playlist.len()))
+                .clicked()
+            {
+                self.transcode(0..self.playlist.len());
+            }
+        });
+        ui.weak("The files are converted one after the other, into the output folder");
+    }
+
+    /// Shown in the corner while recording, clicking it saves the recording
+    #[cfg(feature = "ui")]
+    fn recording_ui(&mut self, ctx: &egui::Context) {
+        let Some((path, started)) = &self.recording else {
+            return;
+        };
+        let mut stop = false;
+        egui::Area::new("recording")
+            .anchor(egui::Align2::RIGHT_TOP, [-12.0, 12.0])
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    let text = egui::RichText::new(format!(
+                        "⏺ REC {}",
+                        controls::format_time(started.elapsed())
+                    ))
+                    .color(egui::Color32::RED);
+                    stop = ui
+                        .add(egui::Label::new(text).sense(egui::Sense::click()))
+                        .on_hover_text(format!("{}\nClick to stop", path.display()))
+                        .clicked();
+                });
+            });
+        // the elapsed time keeps counting
+        ctx.request_repaint_after(Duration::from_secs(1));
+
+        if stop {
+            self.toggle_recording();
+        }
+    }
+
+    /// The cues of the second subtitle at the top of the window, in the subtitle style
+    #[cfg(feature = "ui")]
+    fn second_subtitle_ui(&self, ctx: &egui::Context) {
+        let Some(subtitle) = &self.second_subtitle else {
+            return;
+        };
+        let delay = Duration::from_millis(self.second_subtitle_delay_ms().unsigned_abs());
+        let position = self.controls.position();
+        let position = if self.second_subtitle_delay_ms() >= 0 {
+            position.saturating_sub(delay)
+        } else {
+            position + delay
+        };
+        let text = subtitles::text_at(&subtitle.cues, position);
+        if text.is_empty() {
+            return;
+        }
+
+        let config = self.config.borrow();
+        let style = &config.subtitle_style;
+        let screen = ctx.screen_rect();
+        // like textoverlay, the size is in points on a 640 pixel wide video
+        let font_size = style.font_size as f32 * screen.width() / 640.0;
+        let color = |[r, g, b]: [u8; 3]| egui::Color32::from_rgb(r, g, b);
+        let mut job = egui::text::LayoutJob::simple(
+            text,
+            egui::FontId::proportional(font_size),
+            color(style.text_color),
+            screen.width() * 0.9,
+        );
+        job.halign = egui::Align::Center;
+        let galley = ctx.fonts(|fonts| fonts.layout_job(job));
+
+        egui::Area::new("second_subtitle")
+            .anchor(
+                egui::Align2::CENTER_TOP,
+                [0.0, screen.height() * style.position],
+            )
+            .interactable(false)
+            .show(ctx, |ui| {
+                let (rect, _) = ui.allocate_exact_size(
+                    galley.size() + egui::vec2(16.0, 8.0),
+                    egui::Sense::hover(),
+                );
+                let painter = ui.painter();
+                if style.background {
+                    painter.rect_filled(rect, 4.0, egui::Color32::from_black_alpha(128));
+                }
+                let origin = rect.center_top() + egui::vec2(0.0, 4.0);
+                if style.outline {
+                    let outline = color(style.outline_color);
+                    for offset in [[-1.0, -1.0], [1.0, -1.0], [-1.0, 1.0], [1.0, 1.0]] {
+                        painter.galley_with_color(
+                            origin + egui::Vec2::from(offset) * (font_size / 16.0).max(1.0),
+                            galley.clone(),
+                            outline,
+                        );
+                    }
+                }
+                painter.galley(origin, galley);
+            });
+    }
+
+    /// Centered over the video, like the splash
+    #[cfg(feature = "ui")]
+    fn login_ui(&mut self, ctx: &egui::Context) {
+        let Some(prompt) = self.login_prompt.as_mut() else {
+            return;
+        };
+        let mut sign_in = false;
+        let mut cancel = false;
+        egui::Area::new("login")
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.set_max_width(360.0);
+                    ui.heading(format!("Sign in to {}", prompt.origin));
+                    if prompt.refused {
+                        ui.colored_label(ui.visuals().warn_fg_color, "The login was refused");
+                    }
+                    egui::Grid::new("login_fields")
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            ui.label("Username");
+                            ui.text_edit_singleline(&mut prompt.credentials.username);
+                            ui.end_row();
+                            ui.label("Password");
+                            let password = ui.add(
+                                egui::TextEdit::singleline(&mut prompt.credentials.password)
+                                    .password(true),
+                            );
+                            sign_in |= password.lost_focus()
+                                && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                            ui.end_row();
+                            ui.label("Token");
+                            ui.add(
+                                egui::TextEdit::singleline(&mut prompt.credentials.token)
+                                    .password(true)
+                                    .hint_text("Instead of a username"),
+                            );
+                            ui.end_row();
+                        });
+                    ui.checkbox(&mut prompt.remember, "Remember")
+                        .on_hover_text("Saved in the settings file without encryption");
+                    ui.horizontal(|ui| {
+                        sign_in |= ui.button("Sign in").clicked();
+                        cancel = ui.button("Cancel").clicked();
+                    });
+                });
+            });
+
+        if sign_in {
+            if let Some(prompt) = self.login_prompt.take() {
+                self.sign_in(prompt);
+            }
+        } else if cancel {
+            self.login_prompt = None;
+        }
+    }
+
+    /// Projection of the current item and the view into 360° video
+    #[cfg(feature = "ui")]
+    fn sphere_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(uri) = self.playlist.current().map(|item| item.uri.clone()) else {
+            return;
+        };
+        let current = self.config.borrow().media_profile(&uri).projection;
+        let mut projection = current;
+        let detected = format!("Detected ({})", self.detected_layout.projection.label());
+        egui::ComboBox::from_label("Projection")
+            .selected_text(projection.map_or(detected.as_str(), |projection| projection.label()))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut projection, None, detected.as_str());
+                for option in Projection::ALL {
+                    ui.selectable_value(&mut projection, Some(option), option.label());
+                }
+            })
+            .response
+            .on_hover_text("Remembered for this file");
+        if projection != current {
+            self.set_projection(projection);
+        }
+
+        if self.sphere_view().is_none() {
+            return;
+        }
+        ui.add(
+            egui::Slider::new(
+                &mut self.sphere_view.fov,
+                SphereView::MIN_FOV..=SphereView::MAX_FOV,
+            )
+            .text("Field of view")
+            .suffix("°"),
+        );
+        ui.label(format!(
+            "Looking {:.0}° right, {:.0}° up",
+            self.sphere_view.yaw, self.sphere_view.pitch
+        ));
+        if ui.button("Reset view").clicked() {
+            self.sphere_view = SphereView::default();
+        }
+        ui.label("Drag the video to look around, ctrl and the mouse wheel zoom");
+    }
+
+    /// Where the eyes of the current item are and what is shown of them
+    #[cfg(feature = "ui")]
+    fn stereo_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(uri) = self.playlist.current().map(|item| item.uri.clone()) else {
+            return;
+        };
+        let current = self.config.borrow().media_profile(&uri).stereo_layout;
+        let mut layout = current;
+        let detected = format!("Detected ({})", self.detected_layout.stereo.label());
+        egui::ComboBox::from_label("Layout")
+            .selected_text(layout.map_or(detected.as_str(), |layout| layout.label()))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut layout, None, detected.as_str());
+                for option in StereoLayout::ALL {
+                    ui.selectable_value(&mut layout, Some(option), option.label());
+                }
+            })
+            .response
+            .on_hover_text("Remembered for this file");
+        if layout != current {
+            self.set_stereo_layout(layout);
+        }
+
+        let mut output = self.config.borrow().stereo_output;
+        egui::ComboBox::from_label("Show")
+            .selected_text(output.label())
+            .show_ui(ui, |ui| {
+                for option in StereoOutput::ALL {
+                    ui.selectable_value(&mut output, option, option.label());
+                }
+            });
+        if output != self.config.borrow().stereo_output {
+            self.config.borrow_mut().stereo_output = output;
+            self.config.borrow().save();
+        }
+    }
+
+    /// Plays a second file in sync with the current one, e.g. another encode of it
+    #[cfg(feature = "ui")]
+    fn compare_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("B");
+            ui.text_edit_singleline(&mut self.compare_uri)
+                .on_hover_text("Path or url of the file shown next to the current one");
+            let uri = self.compare_uri.trim();
+            if ui
+                .add_enabled(!uri.is_empty(), egui::Button::new("Compare"))
+                .clicked()
+            {
+                let uri = format_url(uri);
+                self.start_compare(uri);
+            }
+        });
+
+        let Some(compare) = self.compare.as_mut() else {
+            return;
+        };
+        ui.label(format!("Comparing with {}", compare.uri));
+        ui.horizontal(|ui| {
+            for mode in CompareMode::ALL {
+                ui.radio_value(&mut compare.mode, mode, mode.label());
+            }
+        });
+        if compare.mode == CompareMode::Wipe {
+            ui.add(
+                egui::Slider::new(&mut compare.divider, 0.0..=1.0)
+                    .text("Divider")
+                    .show_value(false),
+            );
+        }
+        if ui.button("Stop comparing").clicked() {
+            self.stop_compare();
+        }
+    }
+
+    /// Menus, titles and chapters of the DVD or Blu-ray that is playing
+    #[cfg(feature = "ui")]
+    fn disc_ui(&mut self, ui: &mut egui::Ui, position: DiscPosition) {
+        ui.horizontal_wrapped(|ui| {
+            for menu in DiscMenu::ALL {
+                if ui.button(menu.label()).clicked() {
+                    self.send_command(DecoderCommand::Navigate(DiscNavigation::Menu(menu)));
+                }
+            }
+        });
+        egui::Grid::new("disc_navigation").show(ui, |ui| {
+            let buttons = [
+                [None, Some(("⏶", DiscNavigation::Up)), None],
+                [
+                    Some(("⏴", DiscNavigation::Left)),
+                    Some(("OK", DiscNavigation::Activate)),
+                    Some(("⏵", DiscNavigation::Right)),
+                ],
+                [None, Some(("⏷", DiscNavigation::Down)), None],
+            ];
+            for row in buttons {
+                for button in row {
+                    match button {
+                        Some((text, navigation)) => {
+                            if ui.button(text).clicked() {
+                                self.send_command(DecoderCommand::Navigate(navigation));
+                            }
+                        }
+                        None => {
+                            ui.label("");
+                        }
+                    }
+                }
+                ui.end_row();
+            }
+        });
+        ui.horizontal(|ui| {
+            if ui.button("Previous angle").clicked() {
+                self.send_command(DecoderCommand::Navigate(DiscNavigation::PreviousAngle));
+            }
+            if ui.button("Next angle").clicked() {
+                self.send_command(DecoderCommand::Navigate(DiscNavigation::NextAngle));
+            }
+        });
+
+        if let (Some(titles), Some(title)) = (position.titles, position.title) {
+            let mut selected = title;
+            egui::ComboBox::from_label("Title")
+                .selected_text(format!("{} / {}", title + 1, titles))
+                .show_ui(ui, |ui| {
+                    for index in 0..titles {
+                        ui.selectable_value(&mut selected, index, format!("Title {}", index + 1));
+                    }
+                });
+            if selected != title {
+                self.send_command(DecoderCommand::SelectTitle(selected));
+            }
+        }
+        if let (Some(chapters), Some(chapter)) = (position.chapters, position.chapter) {
+            let mut selected = chapter;
+            egui::ComboBox::from_label("Chapter")
+                .selected_text(format!("{} / {}", chapter + 1, chapters))
+                .show_ui(ui, |ui| {
+                    for index in 0..chapters {
+                        ui.selectable_value(&mut selected, index, format!("Chapter {}", index + 1));
+                    }
+                });
+            if selected != chapter {
+                self.send_command(DecoderCommand::SelectChapter(selected));
+            }
+        }
+    }
+
+    /// Shown in place of the video while it plays in the picture-in-picture window
+    #[cfg(feature = "ui")]
+    fn detached_ui(&mut self, ctx: &egui::Context) {
+        egui::Area::new("detached")
+            .anchor(egui::Align2::CENTER_BOTTOM, [0.0, -96.0])
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Playing in picture-in-picture");
+                        if ui.button("Re-attach").clicked() {
+                            self.set_picture_in_picture(false);
+                        }
+                    });
+                });
+            });
+    }
+
+    #[cfg(feature = "ui")]
+    pub fn ui(&mut self, ctx: &egui::Context) {
+        self.input.pointer_over_ui = ctx.wants_pointer_input() || ctx.is_pointer_over_area();
+
+        for thumbnail in self.thumbnail_receiver.try_iter() {
+            let image = egui::ColorImage::from_rgba_unmultiplied(
+                [thumbnail.width as usize, thumbnail.height as usize],
+                &thumbnail.data,
+            );
+            let texture = ctx.load_texture(&thumbnail.uri, image, egui::TextureOptions::LINEAR);
+            self.thumbnails.insert(thumbnail.uri, texture);
+        }
+        // a rescanned file may have a new thumbnail, the windows share the library so another
+        // one may have taken in the change
+        #[cfg(feature = "library")]
+        if let Some(library) = &self.library {
+            let mut library = library.borrow_mut();
+            library.poll();
+            if library.generation() != self.library_generation {
+                self.library_generation = library.generation();
+                self.library_thumbnails.clear();
+            }
+        }
+
+        self.cover_flow.ui(ctx, &self.playlist, &self.thumbnails);
+        self.stats.ui(ctx);
+        self.log_console.ui(ctx);
+        self.toasts.ui(ctx);
+
+        if let Some(action) = self.command_palette.ui(ctx) {
+            self.execute(action);
+        }
+
+        let mut time_display = self.config.borrow().time_display;
+        if let Some(command) = self.controls.ui(ctx, &mut time_display) {
+            self.send_command(command);
+        }
+        if self.config.borrow().time_display != time_display {
+            let mut config = self.config.borrow_mut();
+            config.time_display = time_display;
+            config.save();
+        }
+
+        self.second_subtitle_ui(ctx);
+        self.login_ui(ctx);
+        self.recording_ui(ctx);
+        if self.playlist.is_empty() {
+            self.splash_ui(ctx);
+        } else if self.picture_in_picture {
+            self.detached_ui(ctx);
+        } else if let Some(compare) = self.compare.as_mut() {
+            compare.divider_ui(ctx);
+        }
+
+        egui::Window::new("Playback").show(ctx, |ui| {
+            if let Some(index) = self.playlist.current_index() {
+                ui.horizontal(|ui| {
+                    if ui.button("⏮").on_hover_text("Previous").clicked() {
+                        self.play_previous();
+                    }
+                    ui.label(format!("{} / {}", index + 1, self.playlist.len()));
+                    if ui.button("⏭").on_hover_text("Next").clicked() {
+                        self.play_next();
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    if ui.button("⏴").on_hover_text("Previous frame").clicked() {
+                        self.step_frame(true);
+                    }
+                    let (icon, hover) = if self.paused {
+                        ("▶", "Play")
+                    } else {
+                        ("⏸", "Pause")
+                    };
+                    if ui.button(icon).on_hover_text(hover).clicked() {
+                        self.set_paused(!self.paused);
+                    }
+                    if ui.button("⏵").on_hover_text("Next frame").clicked() {
+                        self.step_frame(false);
+                    }
+                    let mut reverse = self.reverse;
+                    if ui.checkbox(&mut reverse, "Backwards").changed() {
+                        self.set_reverse(reverse);
+                    }
+                });
+            }
+
+            if !self.config.borrow().recent.is_empty() {
+                ui.collapsing("Recent", |ui| {
+                    if let Some(uri) = self.recent_ui(ui) {
+                        self.enqueue_and_play(uri);
+                    }
+                });
+            }
+
+            #[cfg(feature = "library")]
+            ui.collapsing("Library", |ui| self.library_settings_ui(ui));
+
+            #[cfg(feature = "network")]
+            ui.collapsing("Media servers", |ui| {
+                if let Some(entries) = self.media_servers.ui(ui) {
+                    if let Some(index) = self.enqueue_entries(entries) {
+                        self.play(index);
+                    }
+                }
+            });
+
+            // the sources are only looked for once the section is opened
+            #[cfg(feature = "ndi")]
+            ui.collapsing("NDI sources", |ui| {
+                if let Err(err) = self.ndi_sources.start() {
+                    ui.colored_label(ui.visuals().error_fg_color, err);
+                    return;
+                }
+                let names = self.ndi_sources.names();
+                if names.is_empty() {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Looking for sources");
+                    });
+                }
+                let current = self.playlist.current().map(|item| item.uri.as_str());
+                let mut selected = None;
+                for name in names {
+                    let uri = ndi::uri(&name);
+                    if ui
+                        .selectable_label(current == Some(uri.as_str()), &name)
+                        .clicked()
+                    {
+                        selected = Some(uri);
+                    }
+                }
+                if let Some(uri) = selected {
+                    self.enqueue_and_play(uri);
+                }
+            });
+
+            if self.playlist.current().is_some() {
+                ui.collapsing("A/B compare", |ui| self.compare_ui(ui));
+                ui.collapsing("Recording", |ui| self.recording_settings_ui(ui));
+                ui.collapsing("Clip", |ui| self.clip_ui(ui));
+            }
+
+            if !self.playlist.is_empty() {
+                ui.collapsing("Convert", |ui| self.transcode_ui(ui));
+            }
+
+            ui.collapsing("Exports", |ui| {
+                if self.playlist.current().is_some() {
+                    let binding = Action::ExtractAudio
+                        .key_binding()
+                        .map(|binding| binding.to_string())
+                        .unwrap_or_default();
+                    if ui
+                        .button("Extract the audio")
+                        .on_hover_text(binding)
+                        .clicked()
+                    {
+                        self.extract_audio();
+                    }
+                    ui.separator();
+                }
+                self.jobs.ui(ui);
+            });
+
+            if let Some(position) = self.disc {
+                ui.collapsing("Disc", |ui| self.disc_ui(ui, position));
+            }
+
+            ui.collapsing("Custom pipeline", |ui| {
+                ui.label(format!(
+                    "A gst-launch description, frames are taken from the appsink named {} and \
+                     sound from the one named {}",
+                    custom_pipeline::VIDEO_SINK,
+                    custom_pipeline::AUDIO_SINK
+                ));
+                ui.add(
+                    egui::TextEdit::multiline(&mut self.pipeline_description)
+                        .hint_text(custom_pipeline::EXAMPLE)
+                        .code_editor(),
+                );
+                let description = self.pipeline_description.trim();
+                if ui
+                    .add_enabled(!description.is_empty(), egui::Button::new("Play"))
+                    .clicked()
+                {
+                    let pipeline = PipelineDescription::new(description);
+                    self.play_pipeline(pipeline);
+                }
+            });
+
+            ui.collapsing("Test source", |ui| {
+                let mut settings = self.config.borrow().test_source;
+                egui::ComboBox::from_label("Pattern")
+                    .selected_text(settings.pattern.label())
+                    .show_ui(ui, |ui| {
+                        for pattern in TestPattern::ALL {
+                            ui.selectable_value(&mut settings.pattern, pattern, pattern.label());
+                        }
+                    });
+                let frame_rate_label = |(numerator, denominator): (u32, u32)| {
+                    format!("{:.3}", numerator as f64 / denominator as f64)
+                        .trim_end_matches('0')
+                        .trim_end_matches('.')
+                        .to_string()
+                };
+                egui::ComboBox::from_label("Frame rate")
+                    .selected_text(frame_rate_label(settings.frame_rate))
+                    .show_ui(ui, |ui| {
+                        for frame_rate in TEST_FRAME_RATES {
+                            ui.selectable_value(
+                                &mut settings.frame_rate,
+                                frame_rate,
+                                frame_rate_label(frame_rate),
+                            );
+                        }
+                    });
+                if settings != self.config.borrow().test_source {
+                    self.config.borrow_mut().test_source = settings;
+                    self.config.borrow().save();
+                }
+                if ui
+                    .button("Play")
+                    .on_hover_text(
+                        "Checks the rendering, the frame pacing and the sync without media files",
+                    )
+                    .clicked()
+                {
+                    self.execute(Action::PlayTestSource);
+                }
+            });
+
+            if ui
+                .checkbox(&mut self.audio_only, "Audio only (low power)")
+                .changed()
+            {
+                self.send_command(DecoderCommand::SetAudioOnly(
+                    self.audio_only || self.background,
+                ));
+            }
+
+            let mut visualizer = self.config.borrow().visualizer;
+            egui::ComboBox::from_label("Visualizer")
+                .selected_text(visualizer.label())
+                .show_ui(ui, |ui| {
+                    for option in Visualizer::ALL {
+                        ui.selectable_value(&mut visualizer, option, option.label());
+                    }
+                })
+                .response
+                .on_hover_text("Shown for files without video");
+            if visualizer != self.config.borrow().visualizer {
+                self.config.borrow_mut().visualizer = visualizer;
+                self.config.borrow().save();
+                self.send_command(DecoderCommand::SetVisualizer(visualizer));
+            }
+
+            let mut seek_mode = self.config.borrow().seek_mode;
+            egui::ComboBox::from_label("Seeking")
+                .selected_text(seek_mode.label())
+                .show_ui(ui, |ui| {
+                    for option in SeekMode::ALL {
+                        ui.selectable_value(&mut seek_mode, option, option.label());
+                    }
+                })
+                .response
+                .on_hover_text(format!(
+                    "Jumps shorter than {} seconds are always frame exact",
+                    SHORT_SEEK.as_secs()
+                ));
+            if seek_mode != self.config.borrow().seek_mode {
+                self.config.borrow_mut().seek_mode = seek_mode;
+                self.config.borrow().save();
+                self.send_command(DecoderCommand::SetSeekMode(seek_mode));
+            }
+
+            egui::ComboBox::from_label("Scaling")
+                .selected_text(self.scaling_mode.label())
+                .show_ui(ui, |ui| {
+                    for mode in ScalingMode::ALL {
+                        ui.selectable_value(&mut self.scaling_mode, mode, mode.label());
+                    }
+                });
+
+            if let Some(item) = self.playlist.current() {
+                let profile = self.config.borrow().media_profile(&item.uri);
+                let mut aspect_ratio = profile.aspect_ratio;
+                let binding = Action::CycleAspectRatio
+                    .key_binding()
+                    .map(|binding| binding.to_string())
+                    .unwrap_or_default();
+                egui::ComboBox::from_label("Aspect ratio")
+                    .selected_text(
+                        aspect_ratio.map_or("Video", |aspect_ratio| aspect_ratio.label()),
+                    )
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut aspect_ratio, None, "Video");
+                        for option in AspectRatio::ALL {
+                            ui.selectable_value(&mut aspect_ratio, Some(option), option.label());
+                        }
+                    })
+                    .response
+                    .on_hover_text(binding);
+                if aspect_ratio != profile.aspect_ratio {
+                    self.update_media_profile(|profile| profile.aspect_ratio = aspect_ratio);
+                }
+
+                let mut zoom = profile.zoom.unwrap_or(1.0);
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::Slider::new(&mut zoom, MIN_ZOOM..=MAX_ZOOM)
+                            .logarithmic(true)
+                            .text("Zoom"),
+                    )
+                    .on_hover_text("Ctrl and the mouse wheel zoom as well");
+                    if ui.button("Reset").clicked() {
+                        zoom = 1.0;
+                    }
+                });
+                if zoom != profile.zoom.unwrap_or(1.0) {
+                    self.set_zoom(zoom);
+                }
+                ui.weak("Remembered for this file, along with the tracks and delays");
+            }
+
+            let mut dither = self.config.borrow().dither;
+            egui::ComboBox::from_label("Dithering")
+                .selected_text(dither.label())
+                .show_ui(ui, |ui| {
+                    for option in Dither::ALL {
+                        ui.selectable_value(&mut dither, option, option.label());
+                    }
+                })
+                .response
+                .on_hover_text("Hides banding when 10 bit video is shown on an 8 bit display");
+            if dither != self.config.borrow().dither {
+                self.config.borrow_mut().dither = dither;
+                self.config.borrow().save();
+            }
+
+            ui.checkbox(
+                &mut self.frame_interpolation,
+                "Frame interpolation (experimental, GPU heavy)",
+            );
+
+            if self.playlist.current().is_some() {
+                ui.collapsing("360° video", |ui| self.sphere_ui(ui));
+                ui.collapsing("3D", |ui| self.stereo_ui(ui));
+            }
+
+            ui.collapsing("Postprocessing", |ui| {
+                let mut config = self.config.borrow_mut();
+                let postprocess = &mut config.postprocess;
+                let mut changed = ui
+                    .checkbox(&mut postprocess.denoise, "Denoise")
+                    .on_hover_text("Smooths grain and compression noise")
+                    .changed();
+                changed |= ui
+                    .checkbox(&mut postprocess.deband, "Deband")
+                    .on_hover_text("Hides the steps in gradients of 8 bit video")
+                    .changed();
+                let mut deband_dragged = false;
+                ui.add_enabled_ui(postprocess.deband, |ui| {
+                    ui.indent("deband", |ui| {
+                        let sliders = [
+                            egui::Slider::new(&mut postprocess.deband_iterations, 1..=4)
+                                .text("Iterations"),
+                            egui::Slider::new(&mut postprocess.deband_threshold, 0.5..=16.0)
+                                .text("Threshold"),
+                            egui::Slider::new(&mut postprocess.deband_range, 4.0..=64.0)
+                                .text("Range"),
+                            egui::Slider::new(&mut postprocess.deband_grain, 0.0..=8.0)
+                                .text("Grain"),
+                        ];
+                        for slider in sliders {
+                            let response = ui.add(slider);
+                            changed |= response.changed() || response.drag_released();
+                            deband_dragged |= response.dragged();
+                        }
+                    });
+                });
+                changed |= ui
+                    .checkbox(&mut postprocess.upscale, "Upscale")
+                    .on_hover_text("Edge adaptive upscaling when the video is shown larger")
+                    .changed();
+                changed |= ui.checkbox(&mut postprocess.sharpen, "Sharpen").changed();
+                let response = ui.add_enabled(
+                    postprocess.sharpen,
+                    egui::Slider::new(&mut postprocess.sharpen_strength, 0.0..=1.0)
+                        .text("Strength"),
+                );
+                changed |= response.changed() || response.drag_released();
+
+                ui.separator();
+                ui.label("User shaders").on_hover_text(
+                    "Compute shaders in .wgsl files, reloaded when they change. \
+                     Drop them on the window to add them.",
+                );
+                let mut remove = None;
+                for (index, shader) in postprocess.user_shaders.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        let name = shader.path.file_name().map_or_else(
+                            || shader.path.display().to_string(),
+                            |name| name.to_string_lossy().to_string(),
+                        );
+                        changed |= ui
+                            .checkbox(&mut shader.enabled, name)
+                            .on_hover_text(shader.path.display().to_string())
+                            .changed();
+                        if ui.small_button("Remove").clicked() {
+                            remove = Some(index);
+                        }
+                    });
+                }
+                if let Some(index) = remove {
+                    postprocess.user_shaders.remove(index);
+                    changed = true;
+                }
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.user_shader_path);
+                    let path = self.user_shader_path.trim();
+                    if ui
+                        .add_enabled(!path.is_empty(), egui::Button::new("Add"))
+                        .clicked()
+                    {
+                        postprocess.user_shaders.push(UserShaderSettings {
+                            path: PathBuf::from(path),
+                            enabled: true,
+                        });
+                        self.user_shader_path.clear();
+                        changed = true;
+                    }
+                });
+
+                // only write the config once the user is done dragging
+                if changed && !response.dragged() && !deband_dragged {
+                    config.save();
+                }
+            });
+
+            ui.collapsing("Color grading", |ui| {
+                let mut load = None;
+                let mut clear = false;
+                match (&self.lut, &self.config.borrow().color_lut) {
+                    (Some(lut), Some(path)) => {
+                        let name = lut
+                            .title
+                            .clone()
+                            .unwrap_or_else(|| path.display().to_string());
+                        ui.checkbox(&mut self.lut_enabled, format!("Apply {}", name))
+                            .on_hover_text(format!("{}, {}³ entries", path.display(), lut.size));
+                        clear = ui.button("Remove LUT").clicked();
+                    }
+                    _ => {
+                        ui.weak("Load a .cube LUT, or drop it on the window");
+                    }
+                }
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.lut_path);
+                    let path = self.lut_path.trim();
+                    if ui
+                        .add_enabled(!path.is_empty(), egui::Button::new("Load"))
+                        .clicked()
+                    {
+                        load = Some(PathBuf::from(path));
+                    }
+                });
+
+                if clear {
+                    self.clear_lut();
+                }
+                if let Some(path) = load {
+                    self.lut_path.clear();
+                    self.load_lut(path);
+                }
+            });
+
+            ui.collapsing("Letterbox", |ui| {
+                let mut changed = false;
+                let mut mode = self.config.borrow().letterbox.mode;
+                egui::ComboBox::from_label("Background")
+                    .selected_text(mode.label())
+                    .show_ui(ui, |ui| {
+                        for option in LetterboxMode::ALL {
+                            ui.selectable_value(&mut mode, option, option.label());
+                        }
+                    });
+                if mode != self.config.borrow().letterbox.mode {
+                    self.config.borrow_mut().letterbox.mode = mode;
+                    changed = true;
+                }
+
+                let mut dragged = false;
+                match mode {
+                    LetterboxMode::Black => {}
+                    LetterboxMode::Color => {
+                        ui.horizontal(|ui| {
+                            changed |= ui
+                                .color_edit_button_srgb(
+                                    &mut self.config.borrow_mut().letterbox.color,
+                                )
+                                .changed();
+                            ui.label("Color");
+                        });
+                    }
+                    LetterboxMode::Ambient => {
+                        let response = ui.add(
+                            egui::Slider::new(
+                                &mut self.config.borrow_mut().letterbox.ambient_dim,
+                                0.0..=1.0,
+                            )
+                            .text("Dim"),
+                        );
+                        changed |= response.changed() || response.drag_released();
+                        dragged = response.dragged();
+                    }
+                    LetterboxMode::Image => {
+                        let mut load = None;
+                        let mut clear = false;
+                        match &self.config.borrow().letterbox.image {
+                            Some(path) if self.letterbox_image.is_some() => {
+                                ui.label(path.display().to_string());
+                                clear = ui.button("Remove image").clicked();
+                            }
+                            _ => {
+                                ui.weak("Load a .png or .jpg image");
+                            }
+                        }
+                        ui.horizontal(|ui| {
+                            ui.text_edit_singleline(&mut self.letterbox_image_path);
+                            let path = self.letterbox_image_path.trim();
+                            if ui
+                                .add_enabled(!path.is_empty(), egui::Button::new("Load"))
+                                .clicked()
+                            {
+                                load = Some(PathBuf::from(path));
+                            }
+                        });
+
+                        if clear {
+                            self.clear_letterbox_image();
+                        }
+                        if let Some(path) = load {
+                            self.letterbox_image_path.clear();
+                            self.load_letterbox_image(path);
+                        }
+                    }
+                }
+
+                // only write the config once the user is done dragging
+                if changed && !dragged {
+                    self.config.borrow().save();
+                }
+            });
+
+            ui.collapsing("Ambilight", |ui| {
+                let mut config = self.config.borrow_mut();
+                let ambilight = &mut config.ambilight;
+                let mut changed = ui
+                    .checkbox(&mut ambilight.enabled, "Extract edge colors")
+                    .on_hover_text(
+                        "Averages zones along the edges of the video, e.g. for LED strips",
+                    )
+                    .changed();
+                let mut dragged = false;
+                ui.add_enabled_ui(ambilight.enabled, |ui| {
+                    let max_zones = crate::ambilight::MAX_ZONES_PER_EDGE;
+                    let sliders = [
+                        egui::Slider::new(&mut ambilight.horizontal_zones, 1..=max_zones)
+                            .text("Zones at the top and bottom"),
+                        egui::Slider::new(&mut ambilight.vertical_zones, 1..=max_zones)
+                            .text("Zones at the sides"),
+                        egui::Slider::new(&mut ambilight.depth, 0.01..=0.5).text("Depth"),
+                    ];
+                    for slider in sliders {
+                        let response = ui.add(slider);
+                        changed |= response.changed() || response.drag_released();
+                        dragged |= response.dragged();
+                    }
+                });
+                if !ambilight.enabled {
+                    self.ambilight_colors.clear();
+                }
+                ambilight_preview_ui(ui, ambilight, &self.ambilight_colors);
+                let connected = ambilight.udp_target.clone().unwrap_or_default();
+                if changed && !dragged {
+                    config.save();
+                }
+                drop(config);
+
+                let mut target = None;
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.ambilight_target)
+                        .on_hover_text(
+                            "host:port of a WLED controller, left empty to send nothing",
+                        );
+                    if ui
+                        .add_enabled(
+                            self.ambilight_target.trim() != connected,
+                            egui::Button::new("Send via UDP"),
+                        )
+                        .clicked()
+                    {
+                        target = Some(self.ambilight_target.trim().to_string());
+                    }
+                });
+                if let Some(target) = target {
+                    self.set_ambilight_target(target);
+                }
+            });
+
+            ui.checkbox(&mut self.projector, "Projector (video on second monitor)");
+
+            let mut picture_in_picture = self.picture_in_picture;
+            if ui
+                .checkbox(&mut picture_in_picture, "Picture-in-picture")
+                .changed()
+            {
+                self.set_picture_in_picture(picture_in_picture);
+            }
+
+            egui::ComboBox::from_label("Fullscreen monitor")
+                .selected_text(
+                    self.fullscreen_monitor
+                        .as_deref()
+                        .unwrap_or("The one showing the window"),
+                )
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(
+                        &mut self.fullscreen_monitor,
+                        None,
+                        "The one showing the window",
+                    );
+                    for name in &self.monitors {
+                        ui.selectable_value(&mut self.fullscreen_monitor, Some(name.clone()), name);
+                    }
+                });
+
+            if ui
+                .checkbox(
+                    &mut self.config.borrow_mut().allow_sleep,
+                    "Allow the screensaver during playback",
+                )
+                .changed()
+            {
+                self.config.borrow().save();
+            }
+
+            egui::ComboBox::from_label("Present mode")
+                .selected_text(present_mode_label(self.present_mode))
+                .show_ui(ui, |ui| {
+                    for &mode in &self.supported_present_modes {
+                        ui.selectable_value(&mut self.present_mode, mode, present_mode_label(mode));
+                    }
+                });
+
+            let mut surface_format = self.config.borrow().surface_format;
+            let response = egui::ComboBox::from_label("Surface format")
+                .selected_text(surface_format.label())
+                .show_ui(ui, |ui| {
+                    for option in SurfaceFormat::ALL {
+                        ui.selectable_value(&mut surface_format, option, option.label());
+                    }
+                })
+                .response;
+            if let Some(format) = self.surface_format {
+                response
+                    .on_hover_text(format!("Using {:?}, changes apply after a restart", format));
+            }
+            if surface_format != self.config.borrow().surface_format {
+                self.config.borrow_mut().surface_format = surface_format;
+                self.config.borrow().save();
+            }
+            let hdr_active = self.surface_format == Some(HDR_SURFACE_FORMAT);
+            let hdr_hint = format!(
+                "Needs an HDR display and applies after a restart, falls back to tone mapping \
+                 when unsupported. Currently {}.",
+                if hdr_active { "HDR" } else { "SDR" }
+            );
+            if ui
+                .checkbox(&mut self.config.borrow_mut().hdr_output, "HDR output")
+                .on_hover_text(hdr_hint)
+                .changed()
+            {
+                self.config.borrow().save();
+            }
+
+            let mut gpu = self.config.borrow().gpu.clone();
+            let gpu_hint = format!("Currently {}", self.current_adapter);
+            egui::ComboBox::from_label("GPU")
+                .selected_text(gpu.adapter.as_deref().unwrap_or("High performance"))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut gpu.adapter, None, "High performance");
+                    for name in &self.adapter_names {
+                        ui.selectable_value(&mut gpu.adapter, Some(name.clone()), name);
+                    }
+                })
+                .response
+                .on_hover_text(&gpu_hint);
+            egui::ComboBox::from_label("Graphics API")
+                .selected_text(gpu.backend.label())
+                .show_ui(ui, |ui| {
+                    for backend in GpuBackend::ALL {
+                        ui.selectable_value(&mut gpu.backend, backend, backend.label());
+                    }
+                })
+                .response
+                .on_hover_text(&gpu_hint);
+            if gpu != self.config.borrow().gpu {
+                self.config.borrow_mut().gpu = gpu.clone();
+                self.config.borrow().save();
+                self.new_gpu = Some(gpu);
+            }
+
+            if ui.checkbox(&mut self.loop_file, "Loop file").changed() {
+                self.send_command(DecoderCommand::SetLoopFile(self.loop_file));
+            }
+
+            ui.horizontal(|ui| {
+                let point =
+                    |point: Option<Duration>| point.map_or("-".to_string(), controls::format_time);
+                ui.label(format!(
+                    "A-B loop: {} to {}",
+                    point(self.loop_a),
+                    point(self.loop_b)
+                ));
+                let label = match (self.loop_a, self.loop_b) {
+                    (None, _) => "Set A",
+                    (Some(_), None) => "Set B",
+                    (Some(_), Some(_)) => "Clear",
+                };
+                if ui.button(label).clicked() {
+                    self.mark_ab_loop_point();
+                }
+            });
+
+            if let Some(item) = self.playlist.current() {
+                let mut av_offset_ms = item.av_offset_ms;
+                let response = ui.add(
+                    egui::DragValue::new(&mut av_offset_ms)
+                        .speed(5.0)
+                        .suffix(" ms")
+                        .prefix("A/V offset: "),
+                );
+                if response.changed() || response.drag_released() {
+                    // only write the config once the user is done dragging
+                    self.set_av_offset(av_offset_ms, !response.dragged());
+                }
+            }
+
+            if self.audio_streams > 1 {
+                let mut audio_stream = self.audio_stream;
+                let binding = Action::CycleAudioStream
+                    .key_binding()
+                    .map(|binding| binding.to_string())
+                    .unwrap_or_default();
+                egui::ComboBox::from_label("Audio track")
+                    .selected_text(
+                        audio_stream
+                            .map_or("-".to_string(), |index| format!("Track {}", index + 1)),
+                    )
+                    .show_ui(ui, |ui| {
+                        for index in 0..self.audio_streams {
+                            ui.selectable_value(
+                                &mut audio_stream,
+                                Some(index),
+                                format!("Track {}", index + 1),
+                            );
+                        }
+                    })
+                    .response
+                    .on_hover_text(binding);
+                if let Some(index) = audio_stream.filter(|_| audio_stream != self.audio_stream) {
+                    self.set_audio_stream(index);
+                }
+            }
+
+            if !self.variants.is_empty() {
+                let active = self
+                    .active_variant
+                    .and_then(|index| self.variants.get(index))
+                    .map(Variant::label);
+                let selected_text = match self.selected_variant {
+                    Some(index) => self.variants[index].label(),
+                    None => match &active {
+                        Some(active) => format!("Auto ({})", active),
+                        None => "Auto".to_string(),
+                    },
+                };
+
+                let previous = self.selected_variant;
+                egui::ComboBox::from_label("Quality")
+                    .selected_text(selected_text)
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.selected_variant, None, "Auto");
+                        for (index, variant) in self.variants.iter().enumerate() {
+                            ui.selectable_value(
+                                &mut self.selected_variant,
+                                Some(index),
+                                variant.label(),
+                            );
+                        }
+                    });
+                if self.selected_variant != previous {
+                    let bitrate = self
+                        .selected_variant
+                        .map(|index| self.variants[index].bandwidth);
+                    self.send_command(DecoderCommand::SetMaxBitrate(bitrate));
+                }
+
+                if let Some(active) = active {
+                    ui.weak(format!("Playing {}", active));
+                }
+            }
+
+            ui.collapsing("Audio", |ui| {
+                let mut audio = self.config.borrow().audio;
+                let mut changed = ui
+                    .checkbox(&mut audio.night_mode, "Night mode")
+                    .on_hover_text("Tames loud scenes and boosts quiet dialogue")
+                    .changed();
+                let response = ui.add_enabled(
+                    audio.night_mode,
+                    egui::Slider::new(&mut audio.night_mode_strength, 0.0..=1.0).text("Strength"),
+                );
+                changed |= response.changed() || response.drag_released();
+                egui::ComboBox::from_label("Loudness normalization")
+                    .selected_text(audio.normalization.label())
+                    .show_ui(ui, |ui| {
+                        for normalization in Normalization::ALL {
+                            changed |= ui
+                                .selectable_value(
+                                    &mut audio.normalization,
+                                    normalization,
+                                    normalization.label(),
+                                )
+                                .changed();
+                        }
+                    })
+                    .response
+                    .on_hover_text("Plays quiet films and loud ads at a consistent level");
+                let target_response = ui.add_enabled(
+                    audio.normalization != Normalization::Off,
+                    egui::DragValue::new(&mut audio.normalization_target_lufs)
+                        .clamp_range(-36.0..=-10.0)
+                        .speed(0.5)
+                        .suffix(" LUFS")
+                        .prefix("Target: "),
+                );
+                changed |= target_response.changed() || target_response.drag_released();
+                changed |= ui
+                    .checkbox(&mut audio.dialogue_boost, "Dialogue enhancement")
+                    .on_hover_text("Emphasizes the frequencies of speech")
+                    .changed();
+                let delay_response = ui
+                    .add(
+                        egui::DragValue::new(&mut audio.output_delay_ms)
+                            .speed(5.0)
+                            .suffix(" ms")
+                            .prefix("Output delay: "),
+                    )
+                    .on_hover_text("Compensates the latency of bluetooth headphones and receivers");
+                changed |= delay_response.changed() || delay_response.drag_released();
+
+                let mut equalizer_dragged = false;
+                let mut save_preset = false;
+                ui.collapsing("Equalizer", |ui| {
+                    changed |= ui.checkbox(&mut audio.equalizer, "Enabled").changed();
+                    ui.add_enabled_ui(audio.equalizer, |ui| {
+                        ui.horizontal(|ui| {
+                            for (gain, frequency) in audio
+                                .equalizer_gains_db
+                                .iter_mut()
+                                .zip(EQUALIZER_FREQUENCIES)
+                            {
+                                ui.vertical(|ui| {
+                                    let response = ui
+                                        .add(
+                                            egui::Slider::new(
+                                                gain,
+                                                EQUALIZER_MIN_DB..=EQUALIZER_MAX_DB,
+                                            )
+                                            .vertical()
+                                            .step_by(0.5)
+                                            .show_value(false),
+                                        )
+                                        .on_hover_text(format!("{:+.1} dB", gain));
+                                    changed |= response.changed() || response.drag_released();
+                                    equalizer_dragged |= response.dragged();
+                                    ui.small(frequency);
+                                });
+                            }
+                        });
+
+                        egui::ComboBox::from_label("Preset")
+                            .selected_text("Load preset")
+                            .show_ui(ui, |ui| {
+                                let config = self.config.borrow();
+                                let presets = EQUALIZER_PRESETS
+                                    .iter()
+                                    .map(|&(name, gains)| (name, gains))
+                                    .chain(
+                                        config
+                                            .equalizer_presets
+                                            .iter()
+                                            .map(|(name, &gains)| (name.as_str(), gains)),
+                                    );
+                                for (name, gains) in presets {
+                                    if ui.selectable_label(false, name).clicked() {
+                                        audio.equalizer_gains_db = gains;
+                                        self.equalizer_preset_name = name.to_string();
+                                        changed = true;
+                                    }
+                                }
+                            });
+
+                        ui.horizontal(|ui| {
+                            ui.text_edit_singleline(&mut self.equalizer_preset_name);
+                            save_preset = ui
+                                .add_enabled(
+                                    !self.equalizer_preset_name.trim().is_empty(),
+                                    egui::Button::new("Save preset"),
+                                )
+                                .clicked();
+                        });
+                    });
+                });
+
+                if changed {
+                    self.config.borrow_mut().audio = audio;
+                    self.send_command(DecoderCommand::SetAudioSettings(audio));
+                    // only write the config once the user is done dragging
+                    if !response.dragged()
+                        && !delay_response.dragged()
+                        && !target_response.dragged()
+                        && !equalizer_dragged
+                    {
+                        self.config.borrow().save();
+                    }
+                }
+
+                if save_preset {
+                    let mut config = self.config.borrow_mut();
+                    let gains = config.audio.equalizer_gains_db;
+                    config
+                        .equalizer_presets
+                        .insert(self.equalizer_preset_name.trim().to_string(), gains);
+                    config.save();
+                }
+            });
+
+            ui.collapsing("Slideshow", |ui| {
+                ui.checkbox(&mut self.slideshow, "Advance through the images")
+                    .on_hover_text("Starts by itself for dropped folders");
+                let mut config = self.config.borrow_mut();
+                let slideshow = &mut config.slideshow;
+                let mut changed = ui
+                    .add(
+                        egui::Slider::new(&mut slideshow.interval_secs, 1.0..=60.0)
+                            .text("Seconds per image"),
+                    )
+                    .changed();
+                changed |= ui
+                    .add(
+                        egui::Slider::new(&mut slideshow.crossfade_ms, 0..=2000)
+                            .text("Crossfade (ms)"),
+                    )
+                    .changed();
+
+                if changed {
+                    config.save();
+                }
+            });
+
+            ui.collapsing("Opening files", |ui| {
+                let mut config = self.config.borrow_mut();
+                let mut changed = ui
+                    .checkbox(
+                        &mut config.multiple_instances,
+                        "Open a new window for every launch",
+                    )
+                    .changed();
+                changed |= ui
+                    .add_enabled(
+                        !config.multiple_instances,
+                        egui::Checkbox::new(
+                            &mut config.enqueue_opened_files,
+                            "Add files opened later to the playlist",
+                        ),
+                    )
+                    .changed();
+                ui.weak("Or pass --enqueue when launching");
+
+                if ui
+                    .checkbox(
+                        &mut config.restore_session,
+                        "Restore the last session when started without files",
+                    )
+                    .on_hover_text("The playlist, position and window size")
+                    .changed()
+                {
+                    // a session saved before is stale by the time this is turned on again
+                    config.session = None;
+                    changed = true;
+                }
+
+                if changed {
+                    config.save();
+                }
+            });
+
+            ui.collapsing("Tray icon", |ui| {
+                let mut config = self.config.borrow_mut();
+                let mut changed = ui
+                    .checkbox(&mut config.tray_icon, "Show an icon in the system tray")
+                    .on_hover_text("Applies after a restart")
+                    .changed();
+                changed |= ui
+                    .add_enabled(
+                        config.tray_icon,
+                        egui::Checkbox::new(
+                            &mut config.minimize_to_tray,
+                            "Minimize to the tray, audio keeps playing",
+                        ),
+                    )
+                    .changed();
+                changed |= ui
+                    .add_enabled(
+                        config.tray_icon,
+                        egui::Checkbox::new(
+                            &mut config.close_to_tray,
+                            "Keep playing audio when the window is closed",
+                        ),
+                    )
+                    .on_hover_text("Quit from the tray icon")
+                    .changed();
+
+                if changed {
+                    config.save();
+                }
+            });
+
+            #[cfg(feature = "network")]
+            ui.collapsing("DLNA renderer", |ui| {
+                let mut config = self.config.borrow_mut();
+                let dlna = &mut config.dlna;
+                let mut changed = ui
+                    .checkbox(
+                        &mut dlna.renderer,
+                        "Let apps on the network play on this player",
+                    )
+                    .on_hover_text("Applies after a restart")
+                    .changed();
+                ui.horizontal(|ui| {
+                    ui.label("Name");
+                    changed |= ui
+                        .add(
+                            egui::TextEdit::singleline(&mut dlna.name)
+                                .hint_text("wgpu-media-player on this computer"),
+                        )
+                        .changed();
+                });
+
+                if changed {
+                    config.save();
+                }
+            });
+
+            ui.collapsing("Network cache", |ui| {
+                let mut config = self.config.borrow_mut();
+                let cache = &mut config.cache;
+                let mut changed = ui
+                    .checkbox(&mut cache.enabled, "Cache http streams on disk")
+                    .changed();
+                let response = ui.add_enabled(
+                    cache.enabled,
+                    egui::DragValue::new(&mut cache.max_size_mb)
+                        .clamp_range(16..=65_536)
+                        .speed(16.0)
+                        .suffix(" MB")
+                        .prefix("Size limit: "),
+                );
+                changed |= response.changed() || response.drag_released();
+
+                let mut location = cache
+                    .location
+                    .as_ref()
+                    .map(|path| path.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                ui.horizontal(|ui| {
+                    ui.label("Location");
+                    if ui
+                        .add(
+                            egui::TextEdit::singleline(&mut location)
+                                .hint_text(cache.directory().to_string_lossy().into_owned()),
+                        )
+                        .changed()
+                    {
+                        cache.location =
+                            (!location.trim().is_empty()).then(|| PathBuf::from(location.trim()));
+                        changed = true;
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    ui.weak("Applied when a stream is opened");
+                    if ui.button("Clear cache").clicked() {
+                        clear_cache(&cache.directory());
+                    }
+                });
+
+                if changed && !response.dragged() {
+                    config.save();
+                }
+            });
+
+            ui.collapsing("RTSP", |ui| {
+                let mut config = self.config.borrow_mut();
+                let rtsp = &mut config.rtsp;
+                let mut changed = ui
+                    .add(
+                        egui::DragValue::new(&mut rtsp.latency_ms)
+                            .clamp_range(0..=10_000)
+                            .speed(10.0)
+                            .suffix(" ms")
+                            .prefix("Latency: "),
+                    )
+                    .changed();
+                changed |= ui
+                    .checkbox(&mut rtsp.low_latency, "Low latency (IP cameras)")
+                    .changed();
+                changed |= ui.checkbox(&mut rtsp.force_tcp, "Force TCP").changed();
+                ui.weak("Applied when a stream is opened");
+
+                if changed {
+                    config.save();
+                }
+            });
+
+            ui.collapsing("SRT and RTMP", |ui| {
+                let mut config = self.config.borrow_mut();
+                let ingest = &mut config.ingest;
+                let mut changed = ui
+                    .add(
+                        egui::DragValue::new(&mut ingest.srt_latency_ms)
+                            .clamp_range(20..=8000)
+                            .speed(5.0)
+                            .suffix(" ms")
+                            .prefix("SRT latency: "),
+                    )
+                    .on_hover_text("Time to send lost packets again, the sender's may be larger")
+                    .changed();
+                ui.horizontal(|ui| {
+                    ui.label("SRT passphrase");
+                    changed |= ui
+                        .add(
+                            egui::TextEdit::singleline(&mut ingest.srt_passphrase)
+                                .password(true)
+                                .hint_text("10 to 79 characters"),
+                        )
+                        .changed();
+                });
+                changed |= ui
+                    .add(
+                        egui::DragValue::new(&mut ingest.rtmp_timeout_secs)
+                            .clamp_range(1..=120)
+                            .suffix(" s")
+                            .prefix("RTMP timeout: "),
+                    )
+                    .changed();
+                ui.weak(
+                    "Applied when a stream is opened, parameters in the uri take precedence. \
+                     srt://:9000?mode=listener waits for a sender.",
+                );
+
+                if changed {
+                    config.save();
+                }
+            });
+
+            ui.collapsing("Proxy", |ui| {
+                let mut config = self.config.borrow_mut();
+                let proxy = &mut config.proxy;
+                let mut changed = false;
+                egui::ComboBox::from_label("Mode")
+                    .selected_text(proxy.mode.label())
+                    .show_ui(ui, |ui| {
+                        for mode in ProxyMode::ALL {
+                            changed |= ui
+                                .selectable_value(&mut proxy.mode, mode, mode.label())
+                                .changed();
+                        }
+                    });
+                if proxy.mode == ProxyMode::Manual {
+                    changed |= ui
+                        .add(
+                            egui::TextEdit::singleline(&mut proxy.url)
+                                .hint_text("http://host:port or socks5://host:port"),
+                        )
+                        .changed();
+                }
+                if proxy.mode != ProxyMode::Direct {
+                    egui::Grid::new("proxy_login")
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            ui.label("Username");
+                            changed |= ui.text_edit_singleline(&mut proxy.username).changed();
+                            ui.end_row();
+                            ui.label("Password");
+                            changed |= ui
+                                .add(egui::TextEdit::singleline(&mut proxy.password).password(true))
+                                .changed();
+                            ui.end_row();
+                        });
+                }
+                if self.session_proxy.is_some() {
+                    ui.weak("The proxy of --proxy is used until the player is closed");
+                } else {
+                    ui.weak("Applied when a stream is opened");
+                }
+
+                if changed {
+                    config.save();
+                }
+            });
+
+            if !self.config.borrow().credentials.is_empty() {
+                ui.collapsing("Saved logins", |ui| {
+                    let mut forgotten = None;
+                    let origins: Vec<String> =
+                        self.config.borrow().credentials.keys().cloned().collect();
+                    for origin in origins {
+                        ui.horizontal(|ui| {
+                            ui.label(origin.as_str());
+                            if ui.small_button("Forget").clicked() {
+                                forgotten = Some(origin.clone());
+                            }
+                        });
+                    }
+                    if let Some(origin) = forgotten {
+                        self.config.borrow_mut().credentials.remove(&origin);
+                        self.session_credentials.remove(&origin);
+                        self.config.borrow().save();
+                    }
+                });
+            }
+
+            if self
+                .playlist
+                .current()
+                .is_some_and(|item| item.uri.starts_with("http"))
+            {
+                ui.collapsing("Request headers", |ui| {
+                    ui.add(
+                        egui::TextEdit::multiline(&mut self.http_headers_text)
+                            .hint_text("Name: value")
+                            .desired_rows(3),
+                    );
+                    egui::Grid::new("http_options")
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            ui.label("User-agent");
+                            ui.add(
+                                egui::TextEdit::singleline(&mut self.http_options.user_agent)
+                                    .hint_text("GStreamer souphttpsrc"),
+                            );
+                            ui.end_row();
+                            ui.label("Referer");
+                            ui.text_edit_singleline(&mut self.http_options.referer);
+                            ui.end_row();
+                            ui.label("Cookie file");
+                            ui.add(
+                                egui::TextEdit::singleline(&mut self.cookie_file_text)
+                                    .hint_text("cookies.txt"),
+                            );
+                            ui.end_row();
+                        });
+                    ui.horizontal(|ui| {
+                        ui.weak("Also sent when fetching HLS encryption keys");
+                        if ui.button("Apply and reload").clicked() {
+                            self.apply_http_options();
+                        }
+                    });
+                });
+            }
+
+            ui.collapsing("Languages", |ui| {
+                let mut changed = false;
+                egui::Grid::new("languages").num_columns(2).show(ui, |ui| {
+                    ui.label("Audio");
+                    changed |= ui
+                        .add(
+                            egui::TextEdit::singleline(&mut self.audio_languages_text)
+                                .hint_text("ja, en"),
+                        )
+                        .changed();
+                    ui.end_row();
+                    ui.label("Subtitles");
+                    changed |= ui
+                        .add(
+                            egui::TextEdit::singleline(&mut self.subtitle_languages_text)
+                                .hint_text("en"),
+                        )
+                        .changed();
+                    ui.end_row();
+                });
+                ui.weak("Most wanted first, picks the streams of files with several");
+                if changed {
+                    let languages = LanguagePreferences {
+                        audio: languages::parse_list(&self.audio_languages_text),
+                        subtitles: languages::parse_list(&self.subtitle_languages_text),
+                    };
+                    self.config.borrow_mut().languages = languages.clone();
+                    self.config.borrow().save();
+                    self.send_command(DecoderCommand::SetLanguagePreferences(languages));
+                }
+            });
+
+            ui.collapsing("Closed captions", |ui| {
+                let mut captions = self.config.borrow().captions;
+                let binding = Action::ToggleCaptions
+                    .key_binding()
+                    .map(|binding| binding.to_string())
+                    .unwrap_or_default();
+                ui.checkbox(&mut captions.enabled, "Show closed captions")
+                    .on_hover_text(binding);
+                egui::ComboBox::from_label("Channel")
+                    .selected_text(captions.channel.label())
+                    .show_ui(ui, |ui| {
+                        for channel in CaptionChannel::ALL {
+                            ui.selectable_value(&mut captions.channel, channel, channel.label());
+                        }
+                    })
+                    .response
+                    .on_hover_text("CEA-608 and CEA-708 captions of TV recordings and DVDs");
+                if captions != self.config.borrow().captions {
+                    self.set_captions(captions);
+                }
+            });
+
+            ui.collapsing("Subtitle style", |ui| {
+                let mut style = self.config.borrow().subtitle_style.clone();
+                egui::Grid::new("subtitle_style")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Font");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut style.font_family).hint_text("Sans"),
+                        );
+                        ui.end_row();
+                        ui.label("Size");
+                        ui.add(egui::Slider::new(&mut style.font_size, 8..=48))
+                            .on_hover_text("Points on a 640 pixel wide video");
+                        ui.end_row();
+                        ui.label("Text color");
+                        ui.color_edit_button_srgb(&mut style.text_color);
+                        ui.end_row();
+                        ui.checkbox(&mut style.outline, "Outline");
+                        ui.add_enabled_ui(style.outline, |ui| {
+                            ui.color_edit_button_srgb(&mut style.outline_color)
+                        });
+                        ui.end_row();
+                        ui.checkbox(&mut style.background, "Background box");
+                        ui.end_row();
+                        ui.label("Position");
+                        ui.add(egui::Slider::new(&mut style.position, 0.0..=0.9))
+                            .on_hover_text("Distance from the bottom of the video");
+                        ui.end_row();
+                    });
+                if ui.button("Reset").clicked() {
+                    style = SubtitleStyle::default();
+                }
+                ui.weak("Applies to text subtitles, ASS subtitles keep their own styles");
+                if style != self.config.borrow().subtitle_style {
+                    self.set_subtitle_style(style);
+                }
+            });
+
+            let mut encoding_override = None;
+            if let Some(subtitle) = &self.subtitle {
+                ui.separator();
+                ui.label(format!(
+                    "Subtitle: {}",
+                    subtitle
+                        .path
+                        .file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                ));
+                let selected_text = if subtitle.encoding_overridden {
+                    subtitle.encoding.name().to_string()
+                } else {
+                    format!("{} (detected)", subtitle.encoding.name())
+                };
+                egui::ComboBox::from_label("Subtitle encoding")
+                    .selected_text(selected_text)
+                    .show_ui(ui, |ui| {
+                        for &encoding in subtitles::ENCODINGS {
+                            if ui
+                                .selectable_label(encoding == subtitle.encoding, encoding.name())
+                                .clicked()
+                            {
+                                encoding_override = Some((subtitle.path.clone(), encoding));
+                            }
+                        }
+                    });
+            }
+            if self.subtitle.is_some() {
+                ui.horizontal(|ui| {
+                    let mut subtitle_delay_ms = self.subtitle_delay_ms();
+                    if ui.button("-").clicked() {
+                        self.set_subtitle_delay(subtitle_delay_ms - SUBTITLE_DELAY_STEP_MS, true);
+                    }
+                    let response = ui.add(
+                        egui::DragValue::new(&mut subtitle_delay_ms)
+                            .speed(5.0)
+                            .suffix(" ms")
+                            .prefix("Subtitle delay: "),
+                    );
+                    if response.changed() || response.drag_released() {
+                        self.set_subtitle_delay(subtitle_delay_ms, !response.dragged());
+                    }
+                    if ui.button("+").clicked() {
+                        self.set_subtitle_delay(subtitle_delay_ms + SUBTITLE_DELAY_STEP_MS, true);
+                    }
+                });
+            }
+            if let Some((path, encoding)) = encoding_override {
+                self.load_subtitle(&path, Some(encoding));
+            }
+
+            ui.separator();
+            ui.label("Second subtitle")
+                .on_hover_text("Shown at the top, e.g. the original language over a translation");
+            let mut load = None;
+            let mut remove = false;
+            if let Some(subtitle) = &self.second_subtitle {
+                ui.horizontal(|ui| {
+                    ui.label(
+                        subtitle
+                            .file
+                            .path
+                            .file_name()
+                            .unwrap_or_default()
+                            .to_string_lossy(),
+                    );
+                    remove = ui.button("Remove").clicked();
+                });
+                ui.horizontal(|ui| {
+                    let mut delay_ms = self.second_subtitle_delay_ms();
+                    if ui.button("-").clicked() {
+                        self.set_second_subtitle_delay(delay_ms - SUBTITLE_DELAY_STEP_MS, true);
+                    }
+                    let response = ui.add(
+                        egui::DragValue::new(&mut delay_ms)
+                            .speed(5.0)
+                            .suffix(" ms")
+                            .prefix("Delay: "),
+                    );
+                    if response.changed() || response.drag_released() {
+                        self.set_second_subtitle_delay(delay_ms, !response.dragged());
+                    }
+                    if ui.button("+").clicked() {
+                        self.set_second_subtitle_delay(delay_ms + SUBTITLE_DELAY_STEP_MS, true);
+                    }
+                });
+            } else {
+                ui.weak("Drop two subtitle files at once, or load the second one here");
+            }
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.second_subtitle_path)
+                        .hint_text(".srt, .vtt or .ass"),
+                );
+                let path = self.second_subtitle_path.trim();
+                if ui
+                    .add_enabled(!path.is_empty(), egui::Button::new("Load"))
+                    .clicked()
+                {
+                    load = Some(PathBuf::from(path));
+                }
+            });
+            if remove {
+                self.second_subtitle = None;
+                self.update_media_profile(|profile| profile.second_subtitle = None);
+            }
+            if let Some(path) = load {
+                self.load_second_subtitle(&path, None);
+            }
+        });
+    }
+
+    pub fn handle_window_event(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::ModifiersChanged(state) => {
+                self.input.modifiers.alt = state.alt();
+                self.input.modifiers.ctrl = state.ctrl();
+                self.input.modifiers.shift = state.shift();
+                self.input.modifiers.mac_cmd = cfg!(target_os = "macos") && state.logo();
+                self.input.modifiers.command = if cfg!(target_os = "macos") {
+                    state.logo()
+                } else {
+                    state.ctrl()
+                };
+            }
+            WindowEvent::KeyboardInput { input, .. } if input.state == ElementState::Pressed => {
+                let Some(keycode) = input.virtual_keycode else {
+                    return;
+                };
+
+                // the palette is an egui window, it handles its own input
+                if self.command_palette.is_open() {
+                    return;
+                }
+
+                if self.cover_flow.is_open() {
+                    match keycode {
+                        VirtualKeyCode::Left => self.cover_flow.select_previous(),
+                        VirtualKeyCode::Right => self.cover_flow.select_next(&self.playlist),
+                        VirtualKeyCode::Return => {
+                            self.play(self.cover_flow.selected());
+                            self.cover_flow.close();
+                        }
+                        VirtualKeyCode::Escape | VirtualKeyCode::P => self.cover_flow.close(),
+                        _ => {}
+                    }
+                    return;
+                }
+
+                let key_binding = KeyBinding {
+                    key: keycode,
+                    command: self.input.modifiers.command,
+                    shift: self.input.modifiers.shift,
+                };
+                if let Some(action) = Action::from_key_binding(key_binding) {
+                    self.execute(action);
+                }
+            }
+            WindowEvent::DroppedFile(path) => self.dropped_files.push(path.clone()),
+            WindowEvent::Resized(size) => self.input.window_size = *size,
+            WindowEvent::CursorMoved { position, .. } => {
+                self.input.cursor = Some(*position);
+                self.drag_video(*position);
+            }
+            WindowEvent::CursorLeft { .. } => self.input.cursor = None,
+            WindowEvent::MouseInput { state, button, .. } => {
+                self.handle_mouse_button(*state, *button)
+            }
+            WindowEvent::MouseWheel { delta, .. } => self.handle_mouse_wheel(*delta),
+            _ => {}
+        }
+    }
+
+    /// Double click for fullscreen, middle click to mute and dragging the video to scrub
+    fn handle_mouse_button(&mut self, state: ElementState, button: MouseButton) {
+        match (button, state) {
+            (MouseButton::Left, ElementState::Released) => {
+                let scrubbing = self.input.drag.take().is_some_and(|drag| drag.scrubbing);
+                if scrubbing {
+                    if let Some(command) = self.controls.finish_scrub() {
+                        self.send_command(command);
+                    }
+                }
+            }
+            // the drags and clicks of egui's own widgets
+            _ if self.input.pointer_over_ui => {}
+            (MouseButton::Left, ElementState::Pressed) => {
+                let Some(cursor) = self.input.cursor else {
+                    return;
+                };
+                let double_click = self.input.last_click.is_some_and(|(time, position)| {
+                    time.elapsed() < DOUBLE_CLICK_INTERVAL
+                        && (position.x - cursor.x).abs() < DRAG_THRESHOLD
+                        && (position.y - cursor.y).abs() < DRAG_THRESHOLD
+                });
+                if double_click {
+                    self.input.last_click = None;
+                    self.execute(Action::ToggleFullscreen);
+                    return;
+                }
+                self.input.last_click = Some((Instant::now(), cursor));
+                self.input.drag = Some(PointerDrag {
+                    start_x: cursor.x,
+                    start_y: cursor.y,
+                    start_position: self.controls.shown_position(),
+                    start_view: self.sphere_view(),
+                    scrubbing: false,
+                });
+            }
+            (MouseButton::Middle, ElementState::Pressed) => self.execute(Action::ToggleMute),
+            _ => {}
+        }
+    }
+
+    /// Scrubs relative to where the drag started, `DRAG_SCRUB_RANGE` over the window width.
+    /// 360° video is turned instead, the point under the cursor follows it.
+    fn drag_video(&mut self, cursor: PhysicalPosition<f64>) {
+        let Some(drag) = self.input.drag.as_mut() else {
+            return;
+        };
+        if let Some(start_view) = drag.start_view {
+            let height = self.input.window_size.height.max(1) as f64;
+            let degrees_per_pixel = (start_view.fov as f64 / height) as f32;
+            let (dx, dy) = (cursor.x - drag.start_x, cursor.y - drag.start_y);
+            self.sphere_view.yaw = (start_view.yaw - dx as f32 * degrees_per_pixel) % 360.0;
+            self.sphere_view.pitch =
+                (start_view.pitch + dy as f32 * degrees_per_pixel).clamp(-90.0, 90.0);
+            if dx.abs() >= DRAG_THRESHOLD || dy.abs() >= DRAG_THRESHOLD {
+                // not a click that started a double click
+                self.input.last_click = None;
+            }
+            return;
+        }
+        let Some(duration) = self.controls.duration() else {
+            return;
+        };
+        let distance = cursor.x - drag.start_x;
+        drag.scrubbing |= distance.abs() >= DRAG_THRESHOLD;
+        if !drag.scrubbing || self.input.window_size.width == 0 {
+            return;
+        }
+
+        let offset =
+            DRAG_SCRUB_RANGE.as_secs_f64() * distance / self.input.window_size.width as f64;
+        let position =
+            (drag.start_position.as_secs_f64() + offset).clamp(0.0, duration.as_secs_f64());
+        // not a click that started a double click
+        self.input.last_click = None;
+        if let Some(command) = self.controls.scrub(Duration::from_secs_f64(position)) {
+            self.send_command(command);
+        }
+    }
+
+    /// Changes the volume, seeks while shift is held or zooms while ctrl is held
+    fn handle_mouse_wheel(&mut self, delta: MouseScrollDelta) {
+        if self.input.pointer_over_ui {
+            return;
+        }
+        let (x, y) = match delta {
+            MouseScrollDelta::LineDelta(x, y) => (x as f64, y as f64),
+            MouseScrollDelta::PixelDelta(position) => (
+                position.x / PIXELS_PER_WHEEL_STEP,
+                position.y / PIXELS_PER_WHEEL_STEP,
+            ),
+        };
+
+        if self.input.modifiers.ctrl && self.sphere_view().is_some() {
+            self.sphere_view.fov = (self.sphere_view.fov - y as f32 * FOV_STEP)
+                .clamp(SphereView::MIN_FOV, SphereView::MAX_FOV);
+        } else if self.input.modifiers.ctrl && y != 0.0 {
+            self.set_zoom(self.zoom() * ZOOM_STEP.powf(y as f32));
+        } else if self.input.modifiers.shift {
+            // some platforms turn the wheel into horizontal scrolling while shift is held
+            let steps = if y != 0.0 { y } else { x };
+            let position =
+                self.controls.position().as_secs_f64() + steps * WHEEL_SEEK_STEP.as_secs_f64();
+            let max = self
+                .controls
+                .duration()
+                .map_or(f64::MAX, |duration| duration.as_secs_f64());
+            let command = self
+                .controls
+                .seek(Duration::from_secs_f64(position.clamp(0.0, max)));
+            self.send_command(command);
+        } else if y != 0.0 {
+            self.set_volume(self.volume() + y * VOLUME_STEP);
+        }
+    }
+}
+
+pub fn format_url(url: &str) -> String {
+    if url.contains("://") {
+        url.to_string()
+    } else if cfg!(target_os = "windows") {
+        format!("file:///{}", url.replace('\\', "/"))
+    } else {
+        format!("file://{}", url)
+    }
+}
+
+/// The still images directly inside the folder
+fn folder_images(folder: &Path) -> Vec<PathBuf> {
+    match std::fs::read_dir(folder) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| cover_art::is_image(path))
+            .collect(),
+        Err(err) => {
+            tracing::warn!("Failed to read {}: {}", folder.display(), err);
+            Vec::new()
+        }
+    }
+}
+
+/// Removes download buffers that were left behind, e.g. by a crash
+#[cfg(feature = "ui")]
+fn clear_cache(directory: &Path) {
+    let Ok(entries) = std::fs::read_dir(directory) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        if entry
+            .file_name()
+            .to_string_lossy()
+            .starts_with(CACHE_FILE_PREFIX)
+        {
+            if let Err(err) = std::fs::remove_file(entry.path()) {
+                tracing::warn!("Failed to remove {:?}: {}", entry.path(), err);
+            }
+        }
+    }
+}
+
+/// The zones as a frame around a small rectangle, in the order `Ambilight` produces them
+#[cfg(feature = "ui")]
+fn ambilight_preview_ui(ui: &mut egui::Ui, settings: &AmbilightSettings, colors: &[[u8; 3]]) {
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(160.0, 90.0), egui::Sense::hover());
+    let painter = ui.painter();
+    painter.rect_filled(rect, 0.0, egui::Color32::BLACK);
+
+    let horizontal = settings.horizontal_zones as usize;
+    let vertical = settings.vertical_zones as usize;
+    // colors of different settings until the next frame is read back
+    if colors.len() != 2 * (horizontal + vertical) {
+        return;
+    }
+    let depth = egui::vec2(rect.width(), rect.height()) * settings.depth;
+    let zone_width = rect.width() / horizontal as f32;
+    let zone_height = rect.height() / vertical as f32;
+    let zone_rect = |index: usize| {
+        if index < horizontal {
+            let x = rect.left() + index as f32 * zone_width;
+            egui::Rect::from_min_size(egui::pos2(x, rect.top()), egui::vec2(zone_width, depth.y))
+        } else if index < horizontal + vertical {
+            let y = rect.top() + (index - horizontal) as f32 * zone_height;
+            egui::Rect::from_min_size(
+                egui::pos2(rect.right() - depth.x, y),
+                egui::vec2(depth.x, zone_height),
+            )
+        } else if index < 2 * horizontal + vertical {
+            let x = rect.right() - (index - horizontal - vertical + 1) as f32 * zone_width;
+            egui::Rect::from_min_size(
+                egui::pos2(x, rect.bottom() - depth.y),
+                egui::vec2(zone_width, depth.y),
+            )
+        } else {
+            let y = rect.bottom() - (index - 2 * horizontal - vertical + 1) as f32 * zone_height;
+            egui::Rect::from_min_size(egui::pos2(rect.left(), y), egui::vec2(depth.x, zone_height))
+        }
+    };
+    for (index, [r, g, b]) in colors.iter().copied().enumerate() {
+        painter.rect_filled(zone_rect(index), 0.0, egui::Color32::from_rgb(r, g, b));
+    }
+}
+
+#[cfg(feature = "ui")]
+fn present_mode_label(present_mode: wgpu::PresentMode) -> &'static str {
+    match present_mode {
+        wgpu::PresentMode::AutoVsync => "Auto (VSync)",
+        wgpu::PresentMode::AutoNoVsync => "Auto (no VSync)",
+        wgpu::PresentMode::Fifo => "Fifo (VSync)",
+        wgpu::PresentMode::FifoRelaxed => "Fifo relaxed (adaptive VSync)",
+        wgpu::PresentMode::Immediate => "Immediate (tearing, lowest latency)",
+        wgpu::PresentMode::Mailbox => "Mailbox (low latency, no tearing)",
+    }
 }
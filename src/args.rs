@@ -0,0 +1,85 @@
+use std::path::Path;
+
+use crate::{
+    app::format_url, custom_pipeline::PipelineDescription, disc, media_decoder::STDIN_URI,
+};
+
+/// `wgpu-gstreamer [--log-level <filter>] [--enqueue] [--new-window] [--gpu <name or index>]
+/// [--backend <vulkan|metal|dx12|dx11|gl>] [--list-gpus] [--pipeline <gst-launch description>]
+/// [--proxy <url|direct>] [files, urls or - for stdin...]`
+#[derive(Debug, Default)]
+pub struct Args {
+    pub log_level: Option<String>,
+    /// Add the files to the playlist without interrupting what is playing
+    pub enqueue: bool,
+    /// Open a window of its own even when the player is already running
+    pub new_window: bool,
+    /// Overrides the GPU of the settings, part of its name or its index in `--list-gpus`
+    pub gpu: Option<String>,
+    /// Overrides the graphics API of the settings, `gl` forces the OpenGL fallback
+    pub backend: Option<String>,
+    /// Print the GPUs and exit
+    pub list_gpus: bool,
+    /// Overrides the proxy of the settings until the player is closed
+    pub proxy: Option<String>,
+    /// Played instead of the files, it is never passed on to a running player
+    pub pipeline: Option<PipelineDescription>,
+    /// The files and urls to play, as uris
+    pub uris: Vec<String>,
+}
+
+impl Args {
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Self {
+        let mut parsed = Self::default();
+        while let Some(arg) = args.next() {
+            if let Some(level) = arg.strip_prefix("--log-level=") {
+                parsed.log_level = Some(level.to_string());
+            } else if arg == "--log-level" {
+                parsed.log_level = args.next();
+            } else if arg == "--enqueue" {
+                parsed.enqueue = true;
+            } else if arg == "--new-window" {
+                parsed.new_window = true;
+            } else if let Some(gpu) = arg.strip_prefix("--gpu=") {
+                parsed.gpu = Some(gpu.to_string());
+            } else if arg == "--gpu" {
+                parsed.gpu = args.next();
+            } else if let Some(backend) = arg.strip_prefix("--backend=") {
+                parsed.backend = Some(backend.to_string());
+            } else if arg == "--backend" {
+                parsed.backend = args.next();
+            } else if let Some(description) = arg.strip_prefix("--pipeline=") {
+                parsed.pipeline = Some(PipelineDescription::new(description));
+            } else if arg == "--pipeline" {
+                parsed.pipeline = args
+                    .next()
+                    .map(|description| PipelineDescription::new(&description));
+            } else if let Some(proxy) = arg.strip_prefix("--proxy=") {
+                parsed.proxy = Some(proxy.to_string());
+            } else if arg == "--proxy" {
+                parsed.proxy = args.next();
+            } else if arg == "--list-gpus" {
+                parsed.list_gpus = true;
+            } else if arg == "-" {
+                parsed.uris.push(STDIN_URI.to_string());
+            } else if arg.contains("://") {
+                parsed.uris.push(arg);
+            } else {
+                // another instance may be running in a different directory
+                let path = std::env::current_dir()
+                    .map(|dir| dir.join(&arg))
+                    .unwrap_or_else(|_| Path::new(&arg).to_path_buf());
+                let uri =
+                    disc::folder_uri(&path).unwrap_or_else(|| format_url(&path.to_string_lossy()));
+                parsed.uris.push(uri);
+            }
+        }
+        parsed
+    }
+
+    /// The running player can't read the standard input of this launch, and it only plays the
+    /// pipelines typed into it
+    pub fn needs_own_instance(&self) -> bool {
+        self.pipeline.is_some() || self.uris.iter().any(|uri| uri == STDIN_URI)
+    }
+}
@@ -0,0 +1,105 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use anyhow::{anyhow, Error};
+use gst::prelude::*;
+
+use crate::jobs::{self, unique_path, JobContext};
+
+/// Writes audio stream `index` of `uri` to `path`, a .flac file. AAC and ALAC are copied into
+/// an .m4a file next to it instead, FLAC is copied as well and other codecs are encoded to it.
+pub fn extract(uri: &str, index: usize, path: &Path, context: &JobContext) -> Result<(), Error> {
+    gst::init()?;
+    let pipeline = gst::Pipeline::new(Some("audio-extract"));
+    let source = gst::ElementFactory::make("urisourcebin")
+        .property("uri", uri)
+        .build()?;
+    let parsebin = gst::ElementFactory::make("parsebin").build()?;
+    pipeline.add_many(&[&source, &parsebin])?;
+    source.connect_pad_added({
+        let parsebin = parsebin.clone();
+        move |_, pad| {
+            if let Some(sink) = parsebin.static_pad("sink").filter(|sink| !sink.is_linked()) {
+                if let Err(err) = pad.link(&sink) {
+                    tracing::warn!("Failed to link {}: {:?}", pad.name(), err);
+                }
+            }
+        }
+    });
+
+    let audio_streams = AtomicUsize::new(0);
+    let output: Arc<Mutex<Option<PathBuf>>> = Arc::default();
+    let pipeline_weak = pipeline.downgrade();
+    let (flac_path, chosen) = (path.to_path_buf(), output.clone());
+    parsebin.connect_pad_added(move |parsebin, pad| {
+        let Some(pipeline) = pipeline_weak.upgrade() else {
+            return;
+        };
+        let caps = pad.current_caps().unwrap_or_else(|| pad.query_caps(None));
+        let Some(structure) = caps.structure(0) else {
+            return jobs::discard(&pipeline, pad);
+        };
+        if !structure.name().starts_with("audio/")
+            || audio_streams.fetch_add(1, Ordering::Relaxed) != index
+        {
+            return jobs::discard(&pipeline, pad);
+        }
+
+        // mpegversion 1 is MP3, 2 and 4 are AAC
+        let is_aac = structure.name() == "audio/mpeg"
+            && structure
+                .get::<i32>("mpegversion")
+                .is_ok_and(|version| version != 1);
+        let (extension, description) = match structure.name().as_str() {
+            _ if is_aac => ("m4a", "queue ! aacparse ! mp4mux"),
+            "audio/x-alac" => ("m4a", "queue ! mp4mux"),
+            "audio/x-flac" => ("flac", "queue ! flacparse"),
+            _ => ("flac", "queue ! decodebin ! audioconvert ! flacenc"),
+        };
+        let mut path = flac_path.with_extension(extension);
+        if path != flac_path && path.exists() {
+            let name = path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned();
+            path = unique_path(path.parent().unwrap_or(Path::new(".")), &name, extension);
+        }
+        tracing::info!("Extracting {} to {}", caps, path.display());
+        *chosen.lock().unwrap() = Some(path.clone());
+        let linked = gst::parse_bin_from_description(description, true)
+            .map_err(Error::from)
+            .and_then(|branch| {
+                let sink = gst::ElementFactory::make("filesink")
+                    .property("location", path.to_string_lossy().as_ref())
+                    .build()?;
+                pipeline.add_many(&[branch.upcast_ref::<gst::Element>(), &sink])?;
+                branch.link(&sink)?;
+                branch.sync_state_with_parent()?;
+                sink.sync_state_with_parent()?;
+                pad.link(&branch.static_pad("sink").unwrap())?;
+                Ok(())
+            });
+        if let Err(err) = linked {
+            gst::element_error!(
+                parsebin,
+                gst::CoreError::MissingPlugin,
+                ["Failed to write {}: {:#}", caps, err]
+            );
+        }
+    });
+
+    let result = jobs::run_pipeline(&pipeline, (gst::ClockTime::ZERO, None), None, context);
+    // the extension is only known once the stream was found
+    match output.lock().unwrap().take() {
+        Some(path) => context.set_path(path),
+        None if result.is_ok() => return Err(anyhow!("There is no audio stream {}", index + 1)),
+        None => {}
+    }
+    result
+}
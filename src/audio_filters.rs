@@ -0,0 +1,201 @@
+use anyhow::Error;
+use gst::{glib, prelude::*};
+
+use crate::config::{AudioSettings, Normalization};
+
+/// Threshold of the night mode compressor at full strength, as a fraction of full scale
+const NIGHT_MODE_MIN_THRESHOLD: f32 = 0.1;
+const NIGHT_MODE_MAX_RATIO: f32 = 8.0;
+/// The range that carries most of the intelligibility of speech
+const DIALOGUE_CENTER_HZ: f64 = 2000.0;
+const DIALOGUE_BANDWIDTH_HZ: f64 = 2500.0;
+const DIALOGUE_GAIN_DB: f64 = 6.0;
+
+/// Limits of the normalization gain, so silence or a single loud scene doesn't run away with it
+const NORMALIZATION_MAX_GAIN_DB: f64 = 12.0;
+/// How far the gain moves per loudness measurement, which ebur128level sends every second
+const NORMALIZATION_MAX_STEP_DB: f64 = 1.0;
+
+/// Center frequencies of the bands of equalizer-10bands
+#[cfg(feature = "ui")]
+pub const EQUALIZER_FREQUENCIES: [&str; 10] = [
+    "30", "60", "120", "240", "480", "950", "1.9k", "3.8k", "7.5k", "15k",
+];
+/// The range equalizer-10bands accepts
+pub const EQUALIZER_MIN_DB: f64 = -24.0;
+pub const EQUALIZER_MAX_DB: f64 = 12.0;
+/// Built in presets, the ones the user saves are stored in the config
+#[cfg(feature = "ui")]
+pub const EQUALIZER_PRESETS: &[(&str, [f64; 10])] = &[
+    ("Flat", [0.0; 10]),
+    (
+        "Bass boost",
+        [6.0, 5.0, 4.0, 2.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    ),
+    (
+        "Treble boost",
+        [0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 2.0, 4.0, 5.0, 6.0],
+    ),
+    (
+        "Vocal",
+        [-2.0, -2.0, -1.0, 1.0, 3.0, 3.0, 3.0, 1.0, 0.0, -1.0],
+    ),
+    (
+        "Loudness",
+        [5.0, 4.0, 2.0, 0.0, -1.0, -1.0, 0.0, 2.0, 4.0, 5.0],
+    ),
+];
+
+/// The bin playbin runs decoded audio through before it reaches our audio sink. All filters
+/// stay in the chain and are set to pass through when disabled, so they can be toggled live.
+pub struct AudioFilters {
+    bin: gst::Bin,
+    /// ebur128level comes from gst-plugins-rs, normalization is unavailable without it
+    loudness_meter: Option<gst::Element>,
+    normalization_gain: gst::Element,
+    normalization_gain_db: f64,
+    equalizer: gst::Element,
+    dialogue_equalizer: gst::Element,
+    compressor: gst::Element,
+    makeup_gain: gst::Element,
+}
+
+impl AudioFilters {
+    pub fn new() -> Result<Self, Error> {
+        let convert_in = gst::ElementFactory::make("audioconvert").build()?;
+        let loudness_meter = gst::ElementFactory::make("ebur128level")
+            .property_from_str("mode", "global")
+            .build()
+            .map_err(|err| tracing::warn!("Loudness normalization is unavailable: {}", err))
+            .ok();
+        let normalization_gain = gst::ElementFactory::make("volume").build()?;
+        let equalizer = gst::ElementFactory::make("equalizer-10bands").build()?;
+        let dialogue_equalizer = gst::ElementFactory::make("equalizer-nbands")
+            .property("num-bands", 1u32)
+            .build()?;
+        let band = dialogue_band(&dialogue_equalizer);
+        band.set_property("freq", DIALOGUE_CENTER_HZ);
+        band.set_property("bandwidth", DIALOGUE_BANDWIDTH_HZ);
+        let compressor = gst::ElementFactory::make("audiodynamic")
+            .property_from_str("mode", "compressor")
+            .property_from_str("characteristics", "soft-knee")
+            .build()?;
+        let makeup_gain = gst::ElementFactory::make("volume").build()?;
+        let convert_out = gst::ElementFactory::make("audioconvert").build()?;
+
+        let bin = gst::Bin::new(Some("audio-filters"));
+        // measure before anything changes the loudness, and boost dialogue before the
+        // compressor, so it catches the peaks that creates
+        let elements: Vec<&gst::Element> = [&convert_in]
+            .into_iter()
+            .chain(&loudness_meter)
+            .chain([
+                &normalization_gain,
+                &equalizer,
+                &dialogue_equalizer,
+                &compressor,
+                &makeup_gain,
+                &convert_out,
+            ])
+            .collect();
+        bin.add_many(&elements)?;
+        gst::Element::link_many(&elements)?;
+
+        let sink_pad =
+            gst::GhostPad::with_target(Some("sink"), &convert_in.static_pad("sink").unwrap())?;
+        let src_pad =
+            gst::GhostPad::with_target(Some("src"), &convert_out.static_pad("src").unwrap())?;
+        bin.add_pad(&sink_pad)?;
+        bin.add_pad(&src_pad)?;
+
+        Ok(Self {
+            bin,
+            loudness_meter,
+            normalization_gain,
+            normalization_gain_db: 0.0,
+            equalizer,
+            dialogue_equalizer,
+            compressor,
+            makeup_gain,
+        })
+    }
+
+    pub fn element(&self) -> &gst::Element {
+        self.bin.upcast_ref()
+    }
+
+    pub fn apply(&mut self, settings: &AudioSettings) {
+        for (band, &gain) in settings.equalizer_gains_db.iter().enumerate() {
+            let gain = if settings.equalizer {
+                gain.clamp(EQUALIZER_MIN_DB, EQUALIZER_MAX_DB)
+            } else {
+                0.0
+            };
+            self.equalizer.set_property(&format!("band{}", band), gain);
+        }
+
+        let dialogue_gain = if settings.dialogue_boost {
+            DIALOGUE_GAIN_DB
+        } else {
+            0.0
+        };
+        dialogue_band(&self.dialogue_equalizer).set_property("gain", dialogue_gain);
+
+        if settings.normalization == Normalization::Off || self.loudness_meter.is_none() {
+            self.set_normalization_gain(0.0);
+        }
+
+        let night_mode = settings.night_mode || settings.normalization == Normalization::Night;
+        let (threshold, ratio) = if night_mode {
+            let strength = settings.night_mode_strength.clamp(0.0, 1.0);
+            (
+                1.0 - (1.0 - NIGHT_MODE_MIN_THRESHOLD) * strength,
+                1.0 + (NIGHT_MODE_MAX_RATIO - 1.0) * strength,
+            )
+        } else {
+            (1.0, 1.0)
+        };
+        self.compressor.set_property("threshold", threshold);
+        self.compressor.set_property("ratio", ratio);
+
+        // bring compressed peaks back up to full scale, which lifts everything below the threshold
+        let compressed_peak = threshold + (1.0 - threshold) / ratio;
+        self.makeup_gain
+            .set_property("volume", (1.0 / compressed_peak) as f64);
+    }
+
+    /// Handles the loudness measurements of ebur128level, moving the normalization gain towards
+    /// the target a step at a time so it doesn't pump
+    pub fn update_loudness(&mut self, settings: &AudioSettings, structure: &gst::StructureRef) {
+        if settings.normalization == Normalization::Off || structure.name() != "ebur128-level" {
+            return;
+        }
+        // -inf until enough audio has been measured
+        let Ok(loudness) = structure.get::<f64>("global-loudness") else {
+            return;
+        };
+        if !loudness.is_finite() {
+            return;
+        }
+
+        let target = (settings.normalization_target_lufs - loudness)
+            .clamp(-NORMALIZATION_MAX_GAIN_DB, NORMALIZATION_MAX_GAIN_DB);
+        let step = (target - self.normalization_gain_db)
+            .clamp(-NORMALIZATION_MAX_STEP_DB, NORMALIZATION_MAX_STEP_DB);
+        self.set_normalization_gain(self.normalization_gain_db + step);
+    }
+
+    fn set_normalization_gain(&mut self, gain_db: f64) {
+        self.normalization_gain_db = gain_db;
+        self.normalization_gain
+            .set_property("volume", 10f64.powf(gain_db / 20.0));
+    }
+}
+
+/// The single band of the equalizer-nbands element, its bands are child objects
+fn dialogue_band(equalizer: &gst::Element) -> glib::Object {
+    equalizer
+        .dynamic_cast_ref::<gst::ChildProxy>()
+        .and_then(|proxy| proxy.child_by_index(0))
+        .expect("equalizer-nbands has a band")
+}
@@ -0,0 +1,135 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use byte_slice_cast::AsSliceOf;
+use cpal::{traits::StreamTrait, Stream};
+use gst::prelude::*;
+use ringbuf::{HeapConsumer, HeapRb};
+
+use crate::{
+    drift::{DriftCompensation, DriftEstimator, DriftResampler},
+    error::PlayerError,
+};
+
+/// Plays the decoded audio on the default cpal device, resampled to follow the pipeline clock.
+/// Created for every media that is played.
+pub struct AudioOutput {
+    sink: gst_app::AppSink,
+    // keeps playing until the output is dropped
+    _stream: Stream,
+    channels: i32,
+    sample_rate: i32,
+    /// Samples in the ring buffer that haven't been played yet
+    buffered_samples: Arc<AtomicUsize>,
+    drift_compensation: Arc<DriftCompensation>,
+    drift_estimator: DriftEstimator,
+}
+
+impl AudioOutput {
+    pub fn new() -> Result<Self, PlayerError> {
+        let (mut audio_producer, audio_consumer) = HeapRb::new(50 * 1024 * 1024).split();
+        let drift_compensation = Arc::new(DriftCompensation::default());
+        let (channels, sample_rate, stream) =
+            setup_audio_stream(audio_consumer, drift_compensation.clone())?;
+        stream
+            .play()
+            .map_err(|err| PlayerError::AudioDevice(err.to_string()))?;
+
+        let sink = gst_app::AppSink::builder()
+            .caps(
+                &gst::Caps::builder("audio/x-raw")
+                    .field("format", "F32LE")
+                    .field("rate", sample_rate)
+                    .field("channels", channels)
+                    .build(),
+            )
+            .build();
+
+        let buffered_samples = Arc::new(AtomicUsize::new(0));
+        let sink_buffered_samples = buffered_samples.clone();
+        sink.set_callbacks(
+            gst_app::AppSinkCallbacks::builder()
+                .new_sample(move |appsink| {
+                    let sample = appsink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
+                    let buffer = sample.buffer().ok_or(gst::FlowError::Error)?;
+                    let map = buffer.map_readable().map_err(|_| gst::FlowError::Error)?;
+                    let samples = map
+                        .as_slice_of::<f32>()
+                        .map_err(|_| gst::FlowError::NotNegotiated)?;
+                    audio_producer.push_slice(samples);
+                    sink_buffered_samples.store(audio_producer.len(), Ordering::Relaxed);
+                    Ok(gst::FlowSuccess::Ok)
+                })
+                .build(),
+        );
+
+        Ok(Self {
+            sink,
+            _stream: stream,
+            channels,
+            sample_rate,
+            buffered_samples,
+            drift_compensation,
+            drift_estimator: DriftEstimator::new(sample_rate),
+        })
+    }
+
+    /// The audio sink for playbin
+    pub fn sink(&self) -> &gst::Element {
+        self.sink.upcast_ref()
+    }
+
+    /// Audio that has been decoded but not played yet
+    pub fn buffered_ms(&self) -> f64 {
+        let buffered_samples = self.buffered_samples.load(Ordering::Relaxed);
+        buffered_samples as f64 / (self.channels * self.sample_rate) as f64 * 1000.0
+    }
+
+    /// Adjusts the resampling to the trend of the buffer level, returns the drift of the device
+    /// against the pipeline clock in parts per million
+    pub fn update_drift(&mut self, clock_seconds: f64) -> Option<f64> {
+        self.drift_estimator
+            .update(clock_seconds, &self.drift_compensation)
+    }
+}
+
+fn setup_audio_stream(
+    mut audio_consumer: HeapConsumer<f32>,
+    drift_compensation: Arc<DriftCompensation>,
+) -> Result<(i32, i32, Stream), PlayerError> {
+    use cpal::traits::{DeviceTrait, HostTrait};
+
+    let host = cpal::default_host();
+    let device = host
+        .default_output_device()
+        .ok_or_else(|| PlayerError::AudioDevice("no output device available".to_string()))?;
+
+    let mut supported_configs_range = device
+        .supported_output_configs()
+        .map_err(|err| PlayerError::AudioDevice(err.to_string()))?;
+
+    let config = supported_configs_range
+        .next()
+        .ok_or_else(|| {
+            PlayerError::AudioDevice("the output device has no supported configs".to_string())
+        })?
+        .with_max_sample_rate();
+    let mut resampler = DriftResampler::new(config.channels() as usize);
+    let channels = config.channels() as i32;
+    let sample_rate = config.sample_rate().0 as i32;
+
+    let stream = device
+        .build_output_stream(
+            &config.into(),
+            move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+                resampler.fill(data, &mut audio_consumer, &drift_compensation);
+            },
+            move |err| tracing::error!("CPAL error: {:?}", err),
+            None,
+        )
+        .map_err(|err| PlayerError::AudioDevice(err.to_string()))?;
+
+    Ok((channels, sample_rate, stream))
+}
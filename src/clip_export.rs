@@ -0,0 +1,160 @@
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use anyhow::Error;
+use gst::prelude::*;
+
+use crate::jobs::{self, JobContext};
+
+/// Writes the part of `uri` between `start` and `end` to a Matroska file. The streams are
+/// copied when the container can hold them, the clip then starts at the keyframe before
+/// `start`. Otherwise they are encoded again, to H.264 and Opus.
+pub fn export(
+    uri: &str,
+    start: Duration,
+    end: Duration,
+    path: &Path,
+    context: &JobContext,
+) -> Result<(), Error> {
+    gst::init()?;
+    let range = (
+        gst::ClockTime::from_nseconds(start.as_nanos() as u64),
+        Some(gst::ClockTime::from_nseconds(end.as_nanos() as u64)),
+    );
+
+    let unsupported = Arc::new(AtomicBool::new(false));
+    let pipeline = stream_copy_pipeline(uri, path, unsupported.clone())?;
+    let copied = jobs::run_pipeline(
+        &pipeline,
+        range,
+        Some(gst::SeekFlags::KEY_UNIT | gst::SeekFlags::SNAP_BEFORE),
+        context,
+    );
+    match copied {
+        Err(err) if unsupported.load(Ordering::Relaxed) && !context.is_cancelled() => {
+            tracing::info!(
+                "Encoding the clip again, the streams can't be copied: {:#}",
+                err
+            );
+            let pipeline = encode_pipeline(uri, path)?;
+            jobs::run_pipeline(&pipeline, range, Some(gst::SeekFlags::ACCURATE), context)
+        }
+        result => result,
+    }
+}
+
+/// Links the streams parsebin finds straight to the muxer. Streams other than audio and
+/// video are dropped, `unsupported` is set when the muxer can't take one of those.
+fn stream_copy_pipeline(
+    uri: &str,
+    path: &Path,
+    unsupported: Arc<AtomicBool>,
+) -> Result<gst::Pipeline, Error> {
+    let pipeline = gst::Pipeline::new(Some("clip-export"));
+    let source = gst::ElementFactory::make("urisourcebin")
+        .property("uri", uri)
+        .build()?;
+    let parsebin = gst::ElementFactory::make("parsebin").build()?;
+    let (mux, sink) = mux_to_file(path)?;
+    pipeline.add_many(&[&source, &parsebin, &mux, &sink])?;
+    mux.link(&sink)?;
+
+    link_pads_to(&source, &parsebin);
+    let pipeline_weak = pipeline.downgrade();
+    parsebin.connect_pad_added(move |parsebin, pad| {
+        let Some(pipeline) = pipeline_weak.upgrade() else {
+            return;
+        };
+        let caps = pad.current_caps().unwrap_or_else(|| pad.query_caps(None));
+        let is_media = caps.structure(0).is_some_and(|structure| {
+            structure.name().starts_with("video/") || structure.name().starts_with("audio/")
+        });
+        let linked = mux
+            .compatible_pad(pad, None)
+            .is_some_and(|mux_pad| pad.link(&mux_pad).is_ok());
+        if linked {
+            return;
+        }
+        if is_media {
+            unsupported.store(true, Ordering::Relaxed);
+            gst::element_error!(
+                parsebin,
+                gst::StreamError::Format,
+                ["Matroska can't hold {}", caps]
+            );
+        }
+        jobs::discard(&pipeline, pad);
+    });
+    Ok(pipeline)
+}
+
+/// Decodes everything and encodes the audio and video again
+fn encode_pipeline(uri: &str, path: &Path) -> Result<gst::Pipeline, Error> {
+    let pipeline = gst::Pipeline::new(Some("clip-encode"));
+    let decodebin = gst::ElementFactory::make("uridecodebin")
+        .property("uri", uri)
+        .build()?;
+    let (mux, sink) = mux_to_file(path)?;
+    pipeline.add_many(&[&decodebin, &mux, &sink])?;
+    mux.link(&sink)?;
+
+    let pipeline_weak = pipeline.downgrade();
+    decodebin.connect_pad_added(move |decodebin, pad| {
+        let Some(pipeline) = pipeline_weak.upgrade() else {
+            return;
+        };
+        let caps = pad.current_caps().unwrap_or_else(|| pad.query_caps(None));
+        let name = caps
+            .structure(0)
+            .map(|structure| structure.name().to_string())
+            .unwrap_or_default();
+        let description = match name.as_str() {
+            "video/x-raw" => "queue ! videoconvert ! x264enc speed-preset=veryfast ! h264parse",
+            "audio/x-raw" => "queue ! audioconvert ! audioresample ! opusenc",
+            _ => return jobs::discard(&pipeline, pad),
+        };
+        let linked = gst::parse_bin_from_description(description, true)
+            .map_err(Error::from)
+            .and_then(|branch| {
+                pipeline.add(&branch)?;
+                branch.sync_state_with_parent()?;
+                pad.link(&branch.static_pad("sink").unwrap())?;
+                branch.link(&mux)?;
+                Ok(())
+            });
+        if let Err(err) = linked {
+            gst::element_error!(
+                decodebin,
+                gst::CoreError::MissingPlugin,
+                ["Failed to encode {}: {}", name, err]
+            );
+        }
+    });
+    Ok(pipeline)
+}
+
+fn mux_to_file(path: &Path) -> Result<(gst::Element, gst::Element), Error> {
+    let mux = gst::ElementFactory::make("matroskamux").build()?;
+    let sink = gst::ElementFactory::make("filesink")
+        .property("location", path.to_string_lossy().as_ref())
+        .build()?;
+    Ok((mux, sink))
+}
+
+/// urisourcebin adds its pads once it knows what the uri holds
+fn link_pads_to(source: &gst::Element, next: &gst::Element) {
+    let next = next.clone();
+    source.connect_pad_added(move |_, pad| {
+        if let Some(sink) = next.static_pad("sink").filter(|sink| !sink.is_linked()) {
+            if let Err(err) = pad.link(&sink) {
+                tracing::warn!("Failed to link {}: {:?}", pad.name(), err);
+            }
+        }
+    });
+}
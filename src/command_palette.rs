@@ -0,0 +1,122 @@
+#[cfg(feature = "ui")]
+use crate::commands::Action;
+
+/// Searchable list of every action, opened with Ctrl+Shift+P
+#[derive(Default)]
+pub struct CommandPalette {
+    open: bool,
+    query: String,
+    selected: usize,
+}
+
+impl CommandPalette {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn open(&mut self) {
+        self.open = true;
+        self.query.clear();
+        self.selected = 0;
+    }
+
+    /// Returns the action the user picked
+    #[cfg(feature = "ui")]
+    pub fn ui(&mut self, ctx: &egui::Context) -> Option<Action> {
+        if !self.open {
+            return None;
+        }
+
+        let mut matches: Vec<(i32, Action)> = Action::ALL
+            .iter()
+            .filter_map(|&action| fuzzy_score(&self.query, action.label()).map(|s| (s, action)))
+            .collect();
+        matches.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        self.selected = self.selected.min(matches.len().saturating_sub(1));
+
+        let (escape, enter, up, down) = ctx.input(|i| {
+            (
+                i.key_pressed(egui::Key::Escape),
+                i.key_pressed(egui::Key::Enter),
+                i.key_pressed(egui::Key::ArrowUp),
+                i.key_pressed(egui::Key::ArrowDown),
+            )
+        });
+        if up {
+            self.selected = self.selected.saturating_sub(1);
+        }
+        if down && self.selected + 1 < matches.len() {
+            self.selected += 1;
+        }
+
+        let mut picked = None;
+        egui::Window::new("Command palette")
+            .title_bar(false)
+            .anchor(egui::Align2::CENTER_TOP, [0.0, 48.0])
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.set_width(420.0);
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.query)
+                        .hint_text("Type a command")
+                        .desired_width(f32::INFINITY),
+                )
+                .request_focus();
+
+                for (index, (_, action)) in matches.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        if ui
+                            .selectable_label(index == self.selected, action.label())
+                            .clicked()
+                        {
+                            picked = Some(*action);
+                        }
+                        if let Some(key_binding) = action.key_binding() {
+                            ui.with_layout(
+                                egui::Layout::right_to_left(egui::Align::Center),
+                                |ui| {
+                                    ui.weak(key_binding.to_string());
+                                },
+                            );
+                        }
+                    });
+                }
+            });
+
+        if enter {
+            picked = matches.get(self.selected).map(|(_, action)| *action);
+        }
+        if escape || picked.is_some() {
+            self.open = false;
+        }
+
+        picked
+    }
+}
+
+/// Scores how well `query` matches `text` as a case insensitive subsequence, consecutive and
+/// word start matches score higher. Returns None when it doesn't match at all.
+#[cfg(feature = "ui")]
+fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous_match: Option<usize> = None;
+
+    for query_char in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let index = position + text[position..].iter().position(|&c| c == query_char)?;
+
+        score += 1;
+        if previous_match.is_some_and(|previous| previous + 1 == index) {
+            score += 5;
+        }
+        if index == 0 || text[index - 1] == ' ' {
+            score += 3;
+        }
+
+        previous_match = Some(index);
+        position = index + 1;
+    }
+
+    Some(score)
+}
@@ -0,0 +1,271 @@
+use std::fmt;
+
+use winit::event::VirtualKeyCode;
+
+/// Every action the player can perform from the keyboard or the command palette
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    OpenCommandPalette,
+    TogglePause,
+    StepFrameForward,
+    StepFrameBackward,
+    ToggleReversePlayback,
+    PasteFromClipboard,
+    TogglePlaylistOverlay,
+    ToggleStatistics,
+    ToggleLogConsole,
+    ToggleTimeOsd,
+    ToggleColorLut,
+    ToggleDeband,
+    ToggleAudioOnly,
+    CycleScalingMode,
+    ToggleProjector,
+    TogglePictureInPicture,
+    ToggleLoopFile,
+    MarkAbLoopPoint,
+    PreviousItem,
+    NextItem,
+    IncreaseAudioDelay,
+    DecreaseAudioDelay,
+    IncreaseSubtitleDelay,
+    DecreaseSubtitleDelay,
+    ToggleFullscreen,
+    IncreaseVolume,
+    DecreaseVolume,
+    ToggleMute,
+    SnapWindowHalfSize,
+    SnapWindowVideoSize,
+    SnapWindowDoubleSize,
+    FullscreenOnNextMonitor,
+    NewWindow,
+    ToggleSlideshow,
+    PlayTestSource,
+    OpenDiscMenu,
+    ToggleRecording,
+    SetClipIn,
+    SetClipOut,
+    ExportClip,
+    ExportFrames,
+    ExtractAudio,
+    TranscodeItem,
+    ToggleCaptions,
+    CycleAspectRatio,
+    CycleAudioStream,
+}
+
+impl Action {
+    pub const ALL: &'static [Action] = &[
+        Action::OpenCommandPalette,
+        Action::TogglePause,
+        Action::StepFrameForward,
+        Action::StepFrameBackward,
+        Action::ToggleReversePlayback,
+        Action::PasteFromClipboard,
+        Action::TogglePlaylistOverlay,
+        Action::ToggleStatistics,
+        Action::ToggleLogConsole,
+        Action::ToggleTimeOsd,
+        Action::ToggleColorLut,
+        Action::ToggleDeband,
+        Action::ToggleAudioOnly,
+        Action::CycleScalingMode,
+        Action::ToggleProjector,
+        Action::TogglePictureInPicture,
+        Action::ToggleLoopFile,
+        Action::MarkAbLoopPoint,
+        Action::PreviousItem,
+        Action::NextItem,
+        Action::IncreaseAudioDelay,
+        Action::DecreaseAudioDelay,
+        Action::IncreaseSubtitleDelay,
+        Action::DecreaseSubtitleDelay,
+        Action::ToggleFullscreen,
+        Action::IncreaseVolume,
+        Action::DecreaseVolume,
+        Action::ToggleMute,
+        Action::SnapWindowHalfSize,
+        Action::SnapWindowVideoSize,
+        Action::SnapWindowDoubleSize,
+        Action::FullscreenOnNextMonitor,
+        Action::NewWindow,
+        Action::ToggleSlideshow,
+        Action::PlayTestSource,
+        Action::OpenDiscMenu,
+        Action::ToggleRecording,
+        Action::SetClipIn,
+        Action::SetClipOut,
+        Action::ExportClip,
+        Action::ExportFrames,
+        Action::ExtractAudio,
+        Action::TranscodeItem,
+        Action::ToggleCaptions,
+        Action::CycleAspectRatio,
+        Action::CycleAudioStream,
+    ];
+
+    #[cfg(feature = "ui")]
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::OpenCommandPalette => "Open command palette",
+            Action::TogglePause => "Play / pause",
+            Action::StepFrameForward => "Next frame",
+            Action::StepFrameBackward => "Previous frame",
+            Action::ToggleReversePlayback => "Toggle playing backwards",
+            Action::PasteFromClipboard => "Play path or url from clipboard",
+            Action::TogglePlaylistOverlay => "Toggle playlist overlay",
+            Action::ToggleStatistics => "Toggle statistics",
+            Action::ToggleLogConsole => "Toggle log console",
+            Action::ToggleTimeOsd => "Toggle time in the corner",
+            Action::ToggleColorLut => "Toggle color grading LUT",
+            Action::ToggleDeband => "Toggle debanding",
+            Action::ToggleAudioOnly => "Toggle audio only mode",
+            Action::CycleScalingMode => "Cycle scaling mode",
+            Action::ToggleProjector => "Toggle projector window",
+            Action::TogglePictureInPicture => "Detach / re-attach picture-in-picture",
+            Action::ToggleLoopFile => "Toggle looping the file",
+            Action::MarkAbLoopPoint => "Set A-B loop point / clear A-B loop",
+            Action::PreviousItem => "Previous playlist item (restarts after 3 seconds)",
+            Action::NextItem => "Next playlist item",
+            Action::IncreaseAudioDelay => "Increase audio output delay",
+            Action::DecreaseAudioDelay => "Decrease audio output delay",
+            Action::IncreaseSubtitleDelay => "Delay subtitles",
+            Action::DecreaseSubtitleDelay => "Show subtitles earlier",
+            Action::ToggleFullscreen => "Toggle fullscreen",
+            Action::IncreaseVolume => "Increase volume",
+            Action::DecreaseVolume => "Decrease volume",
+            Action::ToggleMute => "Mute / unmute",
+            Action::SnapWindowHalfSize => "Resize the window to half the video size",
+            Action::SnapWindowVideoSize => "Resize the window to the video size (1:1 pixels)",
+            Action::SnapWindowDoubleSize => "Resize the window to double the video size",
+            Action::FullscreenOnNextMonitor => "Fullscreen on the next monitor",
+            Action::NewWindow => "Open a new player window",
+            Action::ToggleSlideshow => "Start / stop the slideshow",
+            Action::PlayTestSource => "Play the test pattern and tone",
+            Action::OpenDiscMenu => "Open the DVD / Blu-ray menu",
+            Action::ToggleRecording => "Record the stream to a file",
+            Action::SetClipIn => "Set the start of the clip",
+            Action::SetClipOut => "Set the end of the clip",
+            Action::ExportClip => "Export the clip",
+            Action::ExportFrames => "Export the frames of the clip as images",
+            Action::ExtractAudio => "Extract the audio to a file",
+            Action::TranscodeItem => "Convert to the preset in the settings",
+            Action::ToggleCaptions => "Toggle closed captions",
+            Action::CycleAspectRatio => "Cycle the aspect ratio",
+            Action::CycleAudioStream => "Cycle the audio track",
+        }
+    }
+
+    pub fn key_binding(&self) -> Option<KeyBinding> {
+        match self {
+            Action::OpenCommandPalette => Some(KeyBinding::command_shift(VirtualKeyCode::P)),
+            Action::TogglePause => Some(KeyBinding::key(VirtualKeyCode::Space)),
+            Action::StepFrameForward => Some(KeyBinding::key(VirtualKeyCode::Period)),
+            Action::StepFrameBackward => Some(KeyBinding::key(VirtualKeyCode::Comma)),
+            Action::ToggleReversePlayback => Some(KeyBinding::key(VirtualKeyCode::R)),
+            Action::PasteFromClipboard => Some(KeyBinding::command(VirtualKeyCode::V)),
+            Action::TogglePlaylistOverlay => Some(KeyBinding::key(VirtualKeyCode::P)),
+            Action::ToggleStatistics => Some(KeyBinding::key(VirtualKeyCode::I)),
+            Action::ToggleLogConsole => Some(KeyBinding::key(VirtualKeyCode::Grave)),
+            Action::ToggleTimeOsd => Some(KeyBinding::key(VirtualKeyCode::O)),
+            Action::ToggleColorLut => Some(KeyBinding::key(VirtualKeyCode::C)),
+            Action::ToggleDeband => Some(KeyBinding::key(VirtualKeyCode::B)),
+            Action::ToggleAudioOnly => Some(KeyBinding::key(VirtualKeyCode::A)),
+            Action::CycleScalingMode => Some(KeyBinding::key(VirtualKeyCode::S)),
+            Action::ToggleProjector => Some(KeyBinding::command(VirtualKeyCode::P)),
+            Action::TogglePictureInPicture => Some(KeyBinding::command(VirtualKeyCode::I)),
+            Action::ToggleLoopFile => Some(KeyBinding::shift(VirtualKeyCode::L)),
+            Action::MarkAbLoopPoint => Some(KeyBinding::key(VirtualKeyCode::L)),
+            Action::PreviousItem => Some(KeyBinding::key(VirtualKeyCode::PageUp)),
+            Action::NextItem => Some(KeyBinding::key(VirtualKeyCode::PageDown)),
+            Action::IncreaseAudioDelay => Some(KeyBinding::command(VirtualKeyCode::Equals)),
+            Action::DecreaseAudioDelay => Some(KeyBinding::command(VirtualKeyCode::Minus)),
+            Action::IncreaseSubtitleDelay => Some(KeyBinding::key(VirtualKeyCode::X)),
+            Action::DecreaseSubtitleDelay => Some(KeyBinding::key(VirtualKeyCode::Z)),
+            Action::ToggleFullscreen => Some(KeyBinding::key(VirtualKeyCode::F)),
+            Action::IncreaseVolume => Some(KeyBinding::key(VirtualKeyCode::Up)),
+            Action::DecreaseVolume => Some(KeyBinding::key(VirtualKeyCode::Down)),
+            Action::ToggleMute => Some(KeyBinding::key(VirtualKeyCode::M)),
+            Action::SnapWindowHalfSize => Some(KeyBinding::key(VirtualKeyCode::Key0)),
+            Action::SnapWindowVideoSize => Some(KeyBinding::key(VirtualKeyCode::Key1)),
+            Action::SnapWindowDoubleSize => Some(KeyBinding::key(VirtualKeyCode::Key2)),
+            Action::FullscreenOnNextMonitor => Some(KeyBinding::shift(VirtualKeyCode::F)),
+            Action::NewWindow => Some(KeyBinding::command(VirtualKeyCode::N)),
+            Action::ToggleSlideshow => Some(KeyBinding::shift(VirtualKeyCode::S)),
+            Action::PlayTestSource => Some(KeyBinding::shift(VirtualKeyCode::T)),
+            Action::OpenDiscMenu => Some(KeyBinding::shift(VirtualKeyCode::M)),
+            Action::ToggleRecording => Some(KeyBinding::shift(VirtualKeyCode::R)),
+            Action::SetClipIn => Some(KeyBinding::key(VirtualKeyCode::LBracket)),
+            Action::SetClipOut => Some(KeyBinding::key(VirtualKeyCode::RBracket)),
+            Action::ExportClip => Some(KeyBinding::command(VirtualKeyCode::E)),
+            Action::ExportFrames => Some(KeyBinding::command_shift(VirtualKeyCode::E)),
+            Action::ExtractAudio => Some(KeyBinding::command_shift(VirtualKeyCode::A)),
+            Action::TranscodeItem => Some(KeyBinding::command(VirtualKeyCode::T)),
+            Action::ToggleCaptions => Some(KeyBinding::shift(VirtualKeyCode::C)),
+            Action::CycleAspectRatio => Some(KeyBinding::shift(VirtualKeyCode::A)),
+            Action::CycleAudioStream => Some(KeyBinding::key(VirtualKeyCode::D)),
+        }
+    }
+
+    pub fn from_key_binding(key_binding: KeyBinding) -> Option<Action> {
+        Action::ALL
+            .iter()
+            .copied()
+            .find(|action| action.key_binding() == Some(key_binding))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyBinding {
+    pub key: VirtualKeyCode,
+    /// Ctrl, or Cmd on macos
+    pub command: bool,
+    pub shift: bool,
+}
+
+impl KeyBinding {
+    pub fn key(key: VirtualKeyCode) -> Self {
+        Self {
+            key,
+            command: false,
+            shift: false,
+        }
+    }
+
+    pub fn shift(key: VirtualKeyCode) -> Self {
+        Self {
+            shift: true,
+            ..Self::key(key)
+        }
+    }
+
+    pub fn command(key: VirtualKeyCode) -> Self {
+        Self {
+            command: true,
+            ..Self::key(key)
+        }
+    }
+
+    pub fn command_shift(key: VirtualKeyCode) -> Self {
+        Self {
+            shift: true,
+            ..Self::command(key)
+        }
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.command {
+            let command = if cfg!(target_os = "macos") {
+                "Cmd"
+            } else {
+                "Ctrl"
+            };
+            write!(f, "{}+", command)?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+        write!(f, "{:?}", self.key)
+    }
+}
@@ -0,0 +1,166 @@
+use winit::dpi::PhysicalSize;
+
+use crate::media_decoder::DecoderCommand;
+
+/// Width of the grab area of the wipe divider, in points
+#[cfg(feature = "ui")]
+const DIVIDER_HANDLE_WIDTH: f32 = 12.0;
+
+/// How the second video of an A/B comparison is shown next to the first
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CompareMode {
+    /// Each video letterboxed in its own half of the window
+    #[cfg(feature = "ui")]
+    Split,
+    /// Both videos cover the whole window, B shows right of a draggable divider
+    #[default]
+    Wipe,
+}
+
+impl CompareMode {
+    #[cfg(feature = "ui")]
+    pub const ALL: [CompareMode; 2] = [CompareMode::Split, CompareMode::Wipe];
+
+    #[cfg(feature = "ui")]
+    pub fn label(&self) -> &'static str {
+        match self {
+            CompareMode::Split => "Side by side",
+            CompareMode::Wipe => "Wipe",
+        }
+    }
+}
+
+/// Pixels of the window a video is drawn to, x, y, width and height
+pub type PixelRect = [u32; 4];
+
+/// Where one of the two videos is drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompareArea {
+    /// The video is scaled to fit this
+    pub viewport: PixelRect,
+    /// Only this part of the viewport is drawn, None when nothing is visible
+    pub scissor: Option<PixelRect>,
+}
+
+impl CompareArea {
+    /// The whole window
+    pub fn full(size: PhysicalSize<u32>) -> Self {
+        let rect = [0, 0, size.width, size.height];
+        Self {
+            viewport: rect,
+            scissor: Some(rect).filter(|rect| rect[2] > 0 && rect[3] > 0),
+        }
+    }
+
+    pub fn size(&self) -> PhysicalSize<u32> {
+        PhysicalSize::new(self.viewport[2], self.viewport[3])
+    }
+}
+
+/// A second file played on the clock of the current one, see `DecoderCommand::FollowClock`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Compare {
+    pub uri: String,
+    pub mode: CompareMode,
+    /// Fraction of the window width left of the wipe divider
+    pub divider: f32,
+}
+
+impl Compare {
+    #[cfg(feature = "ui")]
+    pub fn new(uri: String) -> Self {
+        Self {
+            uri,
+            mode: CompareMode::default(),
+            divider: 0.5,
+        }
+    }
+
+    /// The areas of A and B in a window of `size`
+    pub fn areas(&self, size: PhysicalSize<u32>) -> [CompareArea; 2] {
+        let full = [0, 0, size.width, size.height];
+        match self.mode {
+            #[cfg(feature = "ui")]
+            CompareMode::Split => {
+                let left = size.width / 2;
+                let a = [0, 0, left, size.height];
+                let b = [left, 0, size.width - left, size.height];
+                [
+                    CompareArea {
+                        viewport: a,
+                        scissor: Some(a).filter(|rect| rect[2] > 0),
+                    },
+                    CompareArea {
+                        viewport: b,
+                        scissor: Some(b).filter(|rect| rect[2] > 0),
+                    },
+                ]
+            }
+            CompareMode::Wipe => {
+                let x = ((size.width as f32 * self.divider.clamp(0.0, 1.0)).round() as u32)
+                    .min(size.width);
+                [
+                    CompareArea {
+                        viewport: full,
+                        scissor: Some([0, 0, x, size.height]).filter(|rect| rect[2] > 0),
+                    },
+                    CompareArea {
+                        viewport: full,
+                        scissor: Some([x, 0, size.width - x, size.height])
+                            .filter(|rect| rect[2] > 0),
+                    },
+                ]
+            }
+        }
+    }
+
+    /// The line between A and B, dragged to wipe between them
+    #[cfg(feature = "ui")]
+    pub fn divider_ui(&mut self, ctx: &egui::Context) {
+        if self.mode != CompareMode::Wipe {
+            return;
+        }
+        let screen = ctx.screen_rect();
+        let x = screen.left() + screen.width() * self.divider;
+        egui::Area::new("compare_divider")
+            .fixed_pos([x - DIVIDER_HANDLE_WIDTH / 2.0, screen.top()])
+            .order(egui::Order::Background)
+            .show(ctx, |ui| {
+                let (rect, response) = ui.allocate_exact_size(
+                    egui::vec2(DIVIDER_HANDLE_WIDTH, screen.height()),
+                    egui::Sense::drag(),
+                );
+                if let Some(pos) = response
+                    .interact_pointer_pos()
+                    .filter(|_| response.dragged())
+                {
+                    self.divider = ((pos.x - screen.left()) / screen.width()).clamp(0.0, 1.0);
+                }
+                let visuals = ui.style().interact(&response);
+                ui.painter().vline(
+                    rect.center().x,
+                    rect.top()..=rect.bottom(),
+                    visuals.fg_stroke,
+                );
+                response.on_hover_cursor(egui::CursorIcon::ResizeHorizontal);
+            });
+    }
+}
+
+/// The same command for the B player, for the ones that move both videos in lockstep
+pub fn mirror(command: &DecoderCommand) -> Option<DecoderCommand> {
+    Some(match command {
+        DecoderCommand::Seek(position) => DecoderCommand::Seek(*position),
+        DecoderCommand::SeekExact(position) => DecoderCommand::SeekExact(*position),
+        DecoderCommand::Scrub(position) => DecoderCommand::Scrub(*position),
+        DecoderCommand::SetPaused(paused) => DecoderCommand::SetPaused(*paused),
+        DecoderCommand::SetSeekMode(seek_mode) => DecoderCommand::SetSeekMode(*seek_mode),
+        DecoderCommand::SetRate(rate) => DecoderCommand::SetRate(*rate),
+        DecoderCommand::StepFrame { backward } => DecoderCommand::StepFrame {
+            backward: *backward,
+        },
+        DecoderCommand::SetLoopFile(loop_file) => DecoderCommand::SetLoopFile(*loop_file),
+        DecoderCommand::SetAbLoop(points) => DecoderCommand::SetAbLoop(*points),
+        _ => return None,
+    })
+}
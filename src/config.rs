@@ -0,0 +1,1330 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::Error;
+use gst::glib;
+use serde::{Deserialize, Serialize};
+
+use crate::media_decoder::STDIN_URI;
+
+/// How many entries the recent files list keeps
+const MAX_RECENT: usize = 20;
+/// Bytes at the start and the end of a file that go into its hash, see `file_hash`
+const HASHED_CHUNK_SIZE: u64 = 64 * 1024;
+/// Items that played for less than this start from the beginning again
+const MIN_RESUME_POSITION: Duration = Duration::from_secs(10);
+/// Items that played this far are finished, they start from the beginning again
+const FINISHED_FRACTION: f64 = 0.95;
+
+/// A uri that was played, most recent first in `Config::recent`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecentItem {
+    pub uri: String,
+    pub title: String,
+    /// How far it played the last time
+    #[serde(default)]
+    pub position_ms: u64,
+    /// None for live streams, which aren't resumed
+    #[serde(default)]
+    pub duration_ms: Option<u64>,
+}
+
+impl RecentItem {
+    /// Where playback continues the next time it is played, None to start from the beginning
+    pub fn resume_position(&self) -> Option<Duration> {
+        let duration_ms = self.duration_ms?;
+        let position = Duration::from_millis(self.position_ms);
+        let finished = self.position_ms as f64 >= duration_ms as f64 * FINISHED_FRACTION;
+        (position >= MIN_RESUME_POSITION && !finished).then_some(position)
+    }
+
+    /// How much of it was played, from 0 to 1
+    #[cfg(feature = "ui")]
+    pub fn progress(&self) -> f32 {
+        match self.duration_ms {
+            Some(duration_ms) if duration_ms > 0 => {
+                (self.position_ms as f64 / duration_ms as f64).min(1.0) as f32
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+/// What the player showed when it was closed, see `Config::restore_session`
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    pub uris: Vec<String>,
+    /// Index into `uris` of the item that was playing
+    pub current: Option<usize>,
+    /// Position in the item that was playing
+    pub position_ms: u64,
+    pub paused: bool,
+    pub window: Option<WindowGeometry>,
+}
+
+/// Position and size of the window in physical pixels, the size excludes the decorations
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WindowGeometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub maximized: bool,
+}
+
+/// Settings that are remembered for a specific media uri
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MediaProfile {
+    /// Positive values delay the video, negative values delay the audio
+    pub av_offset_ms: i64,
+    #[serde(flatten)]
+    pub http: HttpOptions,
+    /// Positive values show the subtitles later, for external subtitle files that are out of sync
+    pub subtitle_delay_ms: i64,
+    /// The same for the second subtitle file, shown above the first one
+    pub second_subtitle_delay_ms: i64,
+    /// The audio stream that was picked, counting from 0
+    pub audio_stream: Option<usize>,
+    /// The external subtitle file that was loaded, and the encoding it was decoded with when
+    /// that was picked by hand
+    pub subtitle: Option<PathBuf>,
+    pub subtitle_encoding: Option<String>,
+    pub second_subtitle: Option<PathBuf>,
+    /// Replaces the aspect ratio of the video
+    pub aspect_ratio: Option<AspectRatio>,
+    /// Scales the fitted video, None is 1
+    pub zoom: Option<f32>,
+    /// Replaces the projection read from the metadata of the file
+    pub projection: Option<Projection>,
+    /// Replaces the stereo layout read from the metadata of the file
+    pub stereo_layout: Option<StereoLayout>,
+}
+
+/// Display aspect ratios for videos whose own is wrong, like anamorphic rips without the flag
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AspectRatio {
+    Square,
+    Standard,
+    Widescreen,
+    Flat,
+    Scope,
+}
+
+impl AspectRatio {
+    pub const ALL: [AspectRatio; 5] = [
+        AspectRatio::Square,
+        AspectRatio::Standard,
+        AspectRatio::Widescreen,
+        AspectRatio::Flat,
+        AspectRatio::Scope,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            AspectRatio::Square => "1:1",
+            AspectRatio::Standard => "4:3",
+            AspectRatio::Widescreen => "16:9",
+            AspectRatio::Flat => "1.85:1",
+            AspectRatio::Scope => "2.39:1",
+        }
+    }
+
+    /// Width divided by height
+    pub fn value(&self) -> f32 {
+        match self {
+            AspectRatio::Square => 1.0,
+            AspectRatio::Standard => 4.0 / 3.0,
+            AspectRatio::Widescreen => 16.0 / 9.0,
+            AspectRatio::Flat => 1.85,
+            AspectRatio::Scope => 2.39,
+        }
+    }
+}
+
+/// How the frames map to what the camera saw, 360° video is shown through a virtual camera
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Projection {
+    #[default]
+    Flat,
+    /// Longitude on the x axis and latitude on the y axis, covering the whole sphere
+    Equirectangular,
+}
+
+impl Projection {
+    pub const ALL: [Projection; 2] = [Projection::Flat, Projection::Equirectangular];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Projection::Flat => "Flat",
+            Projection::Equirectangular => "360° equirectangular",
+        }
+    }
+}
+
+/// Where the two eyes of 3D video are in the frame, has to match stereo_layout in shader.wgsl.
+/// The left eye is in the left or top half.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StereoLayout {
+    #[default]
+    Mono,
+    SideBySide,
+    TopBottom,
+}
+
+impl StereoLayout {
+    pub const ALL: [StereoLayout; 3] = [
+        StereoLayout::Mono,
+        StereoLayout::SideBySide,
+        StereoLayout::TopBottom,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            StereoLayout::Mono => "2D",
+            StereoLayout::SideBySide => "Side by side",
+            StereoLayout::TopBottom => "Top and bottom",
+        }
+    }
+}
+
+/// What is shown of 3D video, has to match stereo_output in shader.wgsl
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StereoOutput {
+    #[default]
+    LeftEye,
+    RightEye,
+    /// Red for the left eye and cyan for the right one, for red/cyan glasses
+    Anaglyph,
+}
+
+#[cfg(feature = "ui")]
+impl StereoOutput {
+    pub const ALL: [StereoOutput; 3] = [
+        StereoOutput::LeftEye,
+        StereoOutput::RightEye,
+        StereoOutput::Anaglyph,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            StereoOutput::LeftEye => "Left eye",
+            StereoOutput::RightEye => "Right eye",
+            StereoOutput::Anaglyph => "Red/cyan anaglyph",
+        }
+    }
+}
+
+/// How the http sources request a uri, kept in its `MediaProfile`
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpOptions {
+    /// Sent with every http request for this uri, including the AES-128 key requests of HLS
+    #[serde(rename = "http_headers")]
+    pub headers: BTreeMap<String, String>,
+    /// Replaces the user-agent of gstreamer when not empty
+    pub user_agent: String,
+    /// Sent as the Referer header unless the headers have one
+    pub referer: String,
+    /// Netscape cookies.txt, as exported by browser extensions and yt-dlp
+    pub cookie_file: Option<PathBuf>,
+}
+
+/// How rtsp:// sources are set up, applied whenever a stream is opened
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RtspSettings {
+    /// Size of the jitter buffer
+    pub latency_ms: u32,
+    /// Only use RTP over TCP, for cameras behind firewalls that drop UDP
+    pub force_tcp: bool,
+    /// Shrinks the jitter buffer and drops late packets, for watching IP cameras live
+    pub low_latency: bool,
+}
+
+impl Default for RtspSettings {
+    fn default() -> Self {
+        // rtspsrc's own default
+        Self {
+            latency_ms: 2000,
+            force_tcp: false,
+            low_latency: false,
+        }
+    }
+}
+
+/// How srt:// and rtmp:// contribution feeds are received, applied whenever a stream is opened.
+/// Parameters in the uri, like srt://host:port?latency=200, take precedence.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IngestSettings {
+    /// How long SRT waits for lost packets to be sent again, the larger latency of both ends is
+    /// used
+    pub srt_latency_ms: u32,
+    /// Decrypts SRT feeds that are sent encrypted, empty for unencrypted ones
+    pub srt_passphrase: String,
+    /// How long an RTMP feed may stall before it counts as lost and is reconnected
+    pub rtmp_timeout_secs: u32,
+}
+
+impl Default for IngestSettings {
+    fn default() -> Self {
+        // srtsrc's own latency, rtmp2src gives up after 5 seconds
+        Self {
+            srt_latency_ms: 125,
+            srt_passphrase: String::new(),
+            rtmp_timeout_secs: 5,
+        }
+    }
+}
+
+/// Where the http sources get the proxy they connect through
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProxyMode {
+    /// The http_proxy, https_proxy, all_proxy and no_proxy environment variables
+    #[default]
+    Environment,
+    Manual,
+    /// Connects directly even when the environment sets a proxy
+    Direct,
+}
+
+#[cfg(feature = "ui")]
+impl ProxyMode {
+    pub const ALL: [ProxyMode; 3] = [ProxyMode::Environment, ProxyMode::Manual, ProxyMode::Direct];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ProxyMode::Environment => "From the environment",
+            ProxyMode::Manual => "Manual",
+            ProxyMode::Direct => "No proxy",
+        }
+    }
+}
+
+/// The proxy of the http sources, applied whenever a stream is opened
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProxySettings {
+    pub mode: ProxyMode,
+    /// http://host:port or socks5://host:port, used in the manual mode
+    pub url: String,
+    /// Sent when the proxy asks for authentication, empty when it doesn't
+    pub username: String,
+    pub password: String,
+}
+
+impl ProxySettings {
+    /// These settings with the proxy of `--proxy <url>`, `direct` turns the proxy off
+    pub fn with_arg(&self, arg: &str) -> Self {
+        if arg == "direct" {
+            Self {
+                mode: ProxyMode::Direct,
+                ..self.clone()
+            }
+        } else {
+            Self {
+                mode: ProxyMode::Manual,
+                url: arg.to_string(),
+                ..self.clone()
+            }
+        }
+    }
+}
+
+/// What is sent to a server that asks for authentication
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Credentials {
+    /// For basic and digest authentication, negotiated by the source
+    pub username: String,
+    pub password: String,
+    /// Sent as `Authorization: Bearer <token>`, empty when the server takes a username
+    pub token: String,
+}
+
+/// The scheme, host and port of a uri, logins are saved per server
+pub fn origin(uri: &str) -> Option<String> {
+    let (scheme, rest) = uri.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    // a login in the uri itself isn't part of the server
+    let host = authority.rsplit('@').next()?;
+    (!host.is_empty()).then(|| format!("{}://{}", scheme, host).to_lowercase())
+}
+
+/// Download buffer for progressive http streams, so they can be seeked back without
+/// downloading them again
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheSettings {
+    pub enabled: bool,
+    pub max_size_mb: u64,
+    /// Directory for the temporary files, the user's cache directory when not set
+    pub location: Option<PathBuf>,
+}
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_size_mb: 512,
+            location: None,
+        }
+    }
+}
+
+/// How images advance while the slideshow runs
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SlideshowSettings {
+    /// Seconds each image is shown
+    pub interval_secs: f32,
+    /// Fade from one image to the next, 0 switches right away
+    pub crossfade_ms: u32,
+}
+
+impl Default for SlideshowSettings {
+    fn default() -> Self {
+        Self {
+            interval_secs: 5.0,
+            crossfade_ms: 500,
+        }
+    }
+}
+
+/// CEA-608 and CEA-708 captions carried in the video stream, as broadcasts and DVDs have them
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CaptionSettings {
+    pub enabled: bool,
+    pub channel: CaptionChannel,
+}
+
+/// The captions to show. CEA-608 has CC1 in the first field and CC3 in the second, CEA-708
+/// numbers its services with the primary language as service 1.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CaptionChannel {
+    #[default]
+    Cc1,
+    Cc3,
+    Service1,
+    Service2,
+    Service3,
+    Service4,
+    Service5,
+    Service6,
+}
+
+impl CaptionChannel {
+    #[cfg(feature = "ui")]
+    pub const ALL: [CaptionChannel; 8] = [
+        CaptionChannel::Cc1,
+        CaptionChannel::Cc3,
+        CaptionChannel::Service1,
+        CaptionChannel::Service2,
+        CaptionChannel::Service3,
+        CaptionChannel::Service4,
+        CaptionChannel::Service5,
+        CaptionChannel::Service6,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            CaptionChannel::Cc1 => "CC1 (608)",
+            CaptionChannel::Cc3 => "CC3 (608)",
+            CaptionChannel::Service1 => "Service 1 (708)",
+            CaptionChannel::Service2 => "Service 2 (708)",
+            CaptionChannel::Service3 => "Service 3 (708)",
+            CaptionChannel::Service4 => "Service 4 (708)",
+            CaptionChannel::Service5 => "Service 5 (708)",
+            CaptionChannel::Service6 => "Service 6 (708)",
+        }
+    }
+
+    /// The CEA-608 field, 0 or 1, None for the CEA-708 services
+    pub fn field(&self) -> Option<i32> {
+        match self {
+            CaptionChannel::Cc1 => Some(0),
+            CaptionChannel::Cc3 => Some(1),
+            _ => None,
+        }
+    }
+
+    /// The CEA-708 service number, None for the CEA-608 channels
+    pub fn service(&self) -> Option<i32> {
+        match self {
+            CaptionChannel::Cc1 | CaptionChannel::Cc3 => None,
+            service => Some(*service as i32 - CaptionChannel::Service1 as i32 + 1),
+        }
+    }
+}
+
+/// Languages to pick the audio and subtitle streams of files with several by, most wanted
+/// first. Streams picked by hand for a file win over these.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LanguagePreferences {
+    /// ISO 639 codes like "ja" or "jpn"
+    pub audio: Vec<String>,
+    pub subtitles: Vec<String>,
+}
+
+/// How text subtitles look. ASS subtitles keep the styles of the file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SubtitleStyle {
+    pub font_family: String,
+    /// Points on a 640 pixel wide video, the text scales with the video
+    pub font_size: u32,
+    /// srgb
+    pub text_color: [u8; 3],
+    pub outline: bool,
+    /// srgb
+    pub outline_color: [u8; 3],
+    /// Darkens a box behind the text
+    pub background: bool,
+    /// Distance of the text from the bottom of the video, 0 to 1 of its height
+    pub position: f32,
+}
+
+impl Default for SubtitleStyle {
+    fn default() -> Self {
+        Self {
+            font_family: "Sans".to_string(),
+            font_size: 18,
+            text_color: [255, 255, 255],
+            outline: true,
+            outline_color: [0, 0, 0],
+            background: false,
+            position: 0.05,
+        }
+    }
+}
+
+impl SubtitleStyle {
+    /// The pango font description textoverlay takes
+    pub fn font_desc(&self) -> String {
+        format!("{} {}", self.font_family.trim(), self.font_size)
+    }
+}
+
+/// The image format of exported frames
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FrameFormat {
+    /// Lossless, for analysis
+    #[default]
+    Png,
+    Jpeg,
+}
+
+impl FrameFormat {
+    #[cfg(feature = "ui")]
+    pub const ALL: [FrameFormat; 2] = [FrameFormat::Png, FrameFormat::Jpeg];
+
+    #[cfg(feature = "ui")]
+    pub fn label(&self) -> &'static str {
+        match self {
+            FrameFormat::Png => "PNG",
+            FrameFormat::Jpeg => "JPEG",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            FrameFormat::Png => "png",
+            FrameFormat::Jpeg => "jpg",
+        }
+    }
+}
+
+/// What playlist items are converted to, see transcode.rs
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TranscodePreset {
+    /// Plays nearly everywhere
+    #[default]
+    H264Mp4,
+    /// Without patented codecs, for the web
+    Vp9Webm,
+}
+
+impl TranscodePreset {
+    #[cfg(feature = "ui")]
+    pub const ALL: [TranscodePreset; 2] = [TranscodePreset::H264Mp4, TranscodePreset::Vp9Webm];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TranscodePreset::H264Mp4 => "H.264 and AAC in MP4",
+            TranscodePreset::Vp9Webm => "VP9 and Opus in WebM",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            TranscodePreset::H264Mp4 => "mp4",
+            TranscodePreset::Vp9Webm => "webm",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FrameExportSettings {
+    pub format: FrameFormat,
+    /// 0 to 100, for JPEG
+    pub jpeg_quality: u8,
+}
+
+impl Default for FrameExportSettings {
+    fn default() -> Self {
+        Self {
+            format: FrameFormat::default(),
+            jpeg_quality: 90,
+        }
+    }
+}
+
+impl CacheSettings {
+    #[cfg(any(feature = "ui", feature = "network"))]
+    pub fn directory(&self) -> PathBuf {
+        self.location.clone().unwrap_or_else(|| {
+            dirs::cache_dir()
+                .unwrap_or_else(std::env::temp_dir)
+                .join("wgpu-media-player")
+        })
+    }
+}
+
+/// Evens out the loudness between files and programs, see `AudioFilters::update_loudness`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Normalization {
+    #[default]
+    Off,
+    Standard,
+    /// Also compresses the dynamic range like night mode, for listening at a low volume
+    Night,
+}
+
+#[cfg(feature = "ui")]
+impl Normalization {
+    pub const ALL: [Normalization; 3] = [
+        Normalization::Off,
+        Normalization::Standard,
+        Normalization::Night,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Normalization::Off => "Off",
+            Normalization::Standard => "EBU R128",
+            Normalization::Night => "EBU R128 + night mode",
+        }
+    }
+}
+
+/// How seeks from hotkeys, clicks on the seek bar and remotes land, jumps of less than
+/// `SHORT_SEEK` are always frame exact. Releasing a drag of the seek bar always lands exactly,
+/// the drag has already previewed it with keyframes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SeekMode {
+    /// Decodes from the previous keyframe up to the exact position, slower
+    Accurate,
+    /// Jumps to the nearest keyframe, instant
+    #[default]
+    Fast,
+}
+
+#[cfg(feature = "ui")]
+impl SeekMode {
+    pub const ALL: [SeekMode; 2] = [SeekMode::Accurate, SeekMode::Fast];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SeekMode::Accurate => "Accurate (frame exact)",
+            SeekMode::Fast => "Fast (keyframes)",
+        }
+    }
+}
+
+/// Noise added when the video is quantized to the 8 bits of the window, hides the banding of
+/// 10 bit video and of the color processing
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Dither {
+    Off,
+    /// A fixed 8x8 Bayer pattern, invisible at normal viewing distances
+    #[default]
+    Ordered,
+    /// Noise that changes every frame, averages out over time
+    Temporal,
+}
+
+#[cfg(feature = "ui")]
+impl Dither {
+    pub const ALL: [Dither; 3] = [Dither::Off, Dither::Ordered, Dither::Temporal];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Dither::Off => "Off",
+            Dither::Ordered => "Ordered",
+            Dither::Temporal => "Temporal",
+        }
+    }
+}
+
+/// Which format the window surface is created with, applies after a restart
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SurfaceFormat {
+    /// An srgb format when the surface supports one, the hardware encodes the output
+    #[default]
+    Srgb,
+    /// A format without the srgb encoding, the shader encodes instead. For drivers whose srgb
+    /// surfaces look wrong.
+    Linear,
+}
+
+#[cfg(feature = "ui")]
+impl SurfaceFormat {
+    pub const ALL: [SurfaceFormat; 2] = [SurfaceFormat::Srgb, SurfaceFormat::Linear];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SurfaceFormat::Srgb => "sRGB (when available)",
+            SurfaceFormat::Linear => "Non-sRGB (encoded in the shader)",
+        }
+    }
+}
+
+/// Graphics API wgpu runs on
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GpuBackend {
+    /// The best one the platform has
+    #[default]
+    Auto,
+    Vulkan,
+    Metal,
+    Dx12,
+    Dx11,
+    /// OpenGL, the fallback for old GPUs and broken drivers
+    Gl,
+}
+
+impl GpuBackend {
+    #[cfg(feature = "ui")]
+    pub const ALL: [GpuBackend; 6] = [
+        GpuBackend::Auto,
+        GpuBackend::Vulkan,
+        GpuBackend::Metal,
+        GpuBackend::Dx12,
+        GpuBackend::Dx11,
+        GpuBackend::Gl,
+    ];
+
+    #[cfg(feature = "ui")]
+    pub fn label(&self) -> &'static str {
+        match self {
+            GpuBackend::Auto => "Automatic",
+            GpuBackend::Vulkan => "Vulkan",
+            GpuBackend::Metal => "Metal",
+            GpuBackend::Dx12 => "DirectX 12",
+            GpuBackend::Dx11 => "DirectX 11",
+            GpuBackend::Gl => "OpenGL",
+        }
+    }
+
+    /// The names `--backend` accepts
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "auto" => Some(GpuBackend::Auto),
+            "vulkan" => Some(GpuBackend::Vulkan),
+            "metal" => Some(GpuBackend::Metal),
+            "dx12" => Some(GpuBackend::Dx12),
+            "dx11" => Some(GpuBackend::Dx11),
+            "gl" | "opengl" => Some(GpuBackend::Gl),
+            _ => None,
+        }
+    }
+}
+
+/// Which GPU renders the video
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GpuSettings {
+    pub backend: GpuBackend,
+    /// Part of the adapter name, None picks the high performance GPU
+    pub adapter: Option<String>,
+}
+
+/// How the playback position is shown
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimeDisplay {
+    /// Show the time left instead of the time played
+    pub remaining: bool,
+    /// Also show the time in the top right corner, e.g. while the controls are covered
+    pub osd: bool,
+}
+
+/// A .wgsl filter written by the user, see `UserShader`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserShaderSettings {
+    pub path: PathBuf,
+    pub enabled: bool,
+}
+
+/// Filters that run on the video before it is drawn, in the order of the fields, see
+/// `Postprocess`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PostprocessSettings {
+    pub denoise: bool,
+    pub deband: bool,
+    /// Each iteration smooths over a larger area with a lower threshold
+    pub deband_iterations: u32,
+    /// Differences below this are smoothed, in steps of 8 bit video
+    pub deband_threshold: f32,
+    /// Distance of the pixels that are averaged, in pixels
+    pub deband_range: f32,
+    /// Noise added to hide the remaining steps, in steps of 8 bit video
+    pub deband_grain: f32,
+    /// Edge adaptive upscaling to the size the video is shown at, before the scaling mode applies
+    pub upscale: bool,
+    pub sharpen: bool,
+    /// From 0 (subtle) to 1 (strong)
+    pub sharpen_strength: f32,
+    /// Run after the built in filters, in this order
+    pub user_shaders: Vec<UserShaderSettings>,
+}
+
+impl Default for PostprocessSettings {
+    fn default() -> Self {
+        Self {
+            denoise: false,
+            deband: false,
+            // mpv's defaults
+            deband_iterations: 1,
+            deband_threshold: 3.0,
+            deband_range: 16.0,
+            deband_grain: 2.0,
+            upscale: false,
+            sharpen: false,
+            sharpen_strength: 0.5,
+            user_shaders: Vec::new(),
+        }
+    }
+}
+
+/// What fills the area around the video
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LetterboxMode {
+    #[default]
+    Black,
+    Color,
+    /// A blurred copy of the video stretched over the window
+    Ambient,
+    Image,
+}
+
+#[cfg(feature = "ui")]
+impl LetterboxMode {
+    pub const ALL: [LetterboxMode; 4] = [
+        LetterboxMode::Black,
+        LetterboxMode::Color,
+        LetterboxMode::Ambient,
+        LetterboxMode::Image,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            LetterboxMode::Black => "Black",
+            LetterboxMode::Color => "Solid color",
+            LetterboxMode::Ambient => "Ambient (blurred video)",
+            LetterboxMode::Image => "Image",
+        }
+    }
+}
+
+/// The background around the video, see `Letterbox`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LetterboxSettings {
+    pub mode: LetterboxMode,
+    /// srgb
+    pub color: [u8; 3],
+    /// How much darker the ambient background is than the video, 0 to 1
+    pub ambient_dim: f32,
+    /// .png or .jpg stretched over the window for `LetterboxMode::Image`
+    pub image: Option<PathBuf>,
+}
+
+impl Default for LetterboxSettings {
+    fn default() -> Self {
+        Self {
+            mode: LetterboxMode::default(),
+            color: [24, 24, 24],
+            ambient_dim: 0.5,
+            image: None,
+        }
+    }
+}
+
+/// Average colors along the edges of the video for LED strips, see `Ambilight`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AmbilightSettings {
+    pub enabled: bool,
+    /// Zones along the top and the bottom edge
+    pub horizontal_zones: u32,
+    /// Zones along the left and the right edge
+    pub vertical_zones: u32,
+    /// How far the zones reach into the frame, as a fraction of its size
+    pub depth: f32,
+    /// host:port the colors are sent to with the WLED realtime protocol (DRGB)
+    pub udp_target: Option<String>,
+}
+
+impl Default for AmbilightSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            horizontal_zones: 16,
+            vertical_zones: 9,
+            depth: 0.1,
+            udp_target: None,
+        }
+    }
+}
+
+/// Filters on the audio path, see `AudioFilters`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioSettings {
+    /// Compresses the dynamic range so loud scenes don't drown out dialogue
+    pub night_mode: bool,
+    /// From 0 (barely noticeable) to 1 (everything about as loud)
+    pub night_mode_strength: f32,
+    /// Emphasizes the speech frequencies for hard to hear dialogue
+    pub dialogue_boost: bool,
+    /// Latency of the audio output, e.g. bluetooth headphones, the video is delayed to match
+    pub output_delay_ms: i64,
+    pub equalizer: bool,
+    /// Gains of the ten equalizer bands in dB, see `EQUALIZER_FREQUENCIES`
+    pub equalizer_gains_db: [f64; 10],
+    pub normalization: Normalization,
+    /// Integrated loudness the gain is adjusted to
+    pub normalization_target_lufs: f64,
+    /// Linear, from 0 to 1
+    pub volume: f64,
+    pub muted: bool,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            night_mode: false,
+            night_mode_strength: 0.5,
+            dialogue_boost: false,
+            output_delay_ms: 0,
+            equalizer: false,
+            equalizer_gains_db: [0.0; 10],
+            normalization: Normalization::Off,
+            // EBU R128's broadcast target
+            normalization_target_lufs: -23.0,
+            volume: 1.0,
+            muted: false,
+        }
+    }
+}
+
+/// What is shown in place of the video for files without a video stream
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Visualizer {
+    Off,
+    #[default]
+    Spectrum,
+    Waveform,
+    Synaescope,
+    Goom,
+}
+
+impl Visualizer {
+    #[cfg(feature = "ui")]
+    pub const ALL: [Visualizer; 5] = [
+        Visualizer::Off,
+        Visualizer::Spectrum,
+        Visualizer::Waveform,
+        Visualizer::Synaescope,
+        Visualizer::Goom,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Visualizer::Off => "Off",
+            Visualizer::Spectrum => "Spectrum",
+            Visualizer::Waveform => "Waveform",
+            Visualizer::Synaescope => "Synaescope",
+            Visualizer::Goom => "Goom",
+        }
+    }
+
+    /// The gstreamer element that renders it
+    pub fn factory_name(&self) -> Option<&'static str> {
+        match self {
+            Visualizer::Off => None,
+            Visualizer::Spectrum => Some("spectrascope"),
+            Visualizer::Waveform => Some("wavescope"),
+            Visualizer::Synaescope => Some("synaescope"),
+            Visualizer::Goom => Some("goom"),
+        }
+    }
+}
+
+/// Picture of the built in test source, the names of the videotestsrc patterns
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TestPattern {
+    #[default]
+    Smpte,
+    /// Moving, for judging frame pacing
+    Ball,
+    /// Fine rings, for judging the scaling
+    ZonePlate,
+    /// A smooth ramp, for judging the dithering
+    Gradient,
+    Snow,
+}
+
+impl TestPattern {
+    #[cfg(feature = "ui")]
+    pub const ALL: [TestPattern; 5] = [
+        TestPattern::Smpte,
+        TestPattern::Ball,
+        TestPattern::ZonePlate,
+        TestPattern::Gradient,
+        TestPattern::Snow,
+    ];
+
+    #[cfg(feature = "ui")]
+    pub fn label(&self) -> &'static str {
+        match self {
+            TestPattern::Smpte => "Color bars",
+            TestPattern::Ball => "Moving ball",
+            TestPattern::ZonePlate => "Zone plate",
+            TestPattern::Gradient => "Gradient",
+            TestPattern::Snow => "Noise",
+        }
+    }
+
+    /// The value of the videotestsrc pattern property
+    pub fn nick(&self) -> &'static str {
+        match self {
+            TestPattern::Smpte => "smpte",
+            TestPattern::Ball => "ball",
+            TestPattern::ZonePlate => "zone-plate",
+            TestPattern::Gradient => "gradient",
+            TestPattern::Snow => "snow",
+        }
+    }
+}
+
+/// Frame rates the test source can run at, as fractions
+#[cfg(feature = "ui")]
+pub const TEST_FRAME_RATES: [(u32, u32); 7] = [
+    (24000, 1001),
+    (24, 1),
+    (25, 1),
+    (30000, 1001),
+    (30, 1),
+    (50, 1),
+    (60, 1),
+];
+
+/// Plays without media files, for checking the rendering, the frame pacing and the sync
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TestSourceSettings {
+    pub pattern: TestPattern,
+    /// One of `TEST_FRAME_RATES`
+    pub frame_rate: (u32, u32),
+}
+
+impl Default for TestSourceSettings {
+    fn default() -> Self {
+        Self {
+            pattern: TestPattern::default(),
+            frame_rate: (30, 1),
+        }
+    }
+}
+
+/// Lets phones and other DLNA control points on the network play on the player, see
+/// `DlnaRenderer`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DlnaSettings {
+    /// Announces the player as a media renderer, applies after a restart
+    pub renderer: bool,
+    /// Shown by control points, the host name when empty
+    pub name: String,
+    /// Control points recognize the renderer by it after a restart, made when it first starts
+    pub uuid: Option<String>,
+}
+
+/// Everything that is persisted between runs, stored as json in the user's config directory
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Keyed by the hash of local files and by the uri of everything else
+    pub media: HashMap<String, MediaProfile>,
+    /// The keys of `media` of the uris that were identified, see `identify_media`
+    #[serde(skip)]
+    media_keys: HashMap<String, String>,
+    pub rtsp: RtspSettings,
+    pub ingest: IngestSettings,
+    pub proxy: ProxySettings,
+    /// Logins that were saved when a server asked for one, by `origin`
+    pub credentials: BTreeMap<String, Credentials>,
+    pub audio: AudioSettings,
+    pub postprocess: PostprocessSettings,
+    /// .cube file applied for color grading
+    pub color_lut: Option<PathBuf>,
+    pub letterbox: LetterboxSettings,
+    pub ambilight: AmbilightSettings,
+    pub dither: Dither,
+    /// How 3D video is shown on a 2D display
+    pub stereo_output: StereoOutput,
+    pub surface_format: SurfaceFormat,
+    /// Shows HDR video without tone mapping on a float surface, applies after a restart
+    pub hdr_output: bool,
+    pub gpu: GpuSettings,
+    /// Equalizer presets saved by the user, next to the built in `EQUALIZER_PRESETS`
+    pub equalizer_presets: BTreeMap<String, [f64; 10]>,
+    pub cache: CacheSettings,
+    /// Where recordings and exports are saved, the videos folder when not set
+    pub output_folder: Option<PathBuf>,
+    pub frame_export: FrameExportSettings,
+    pub transcode_preset: TranscodePreset,
+    pub dlna: DlnaSettings,
+    pub slideshow: SlideshowSettings,
+    pub test_source: TestSourceSettings,
+    pub visualizer: Visualizer,
+    pub captions: CaptionSettings,
+    pub subtitle_style: SubtitleStyle,
+    pub languages: LanguagePreferences,
+    pub seek_mode: SeekMode,
+    pub time_display: TimeDisplay,
+    /// Lets the display blank and the system sleep while video plays
+    pub allow_sleep: bool,
+    /// Shows an icon with playback controls in the system tray, applies after a restart
+    pub tray_icon: bool,
+    /// Hides the minimized window, the tray icon brings it back
+    pub minimize_to_tray: bool,
+    /// Closing the window hides it instead and the audio keeps playing, like minimizing to the
+    /// tray
+    pub close_to_tray: bool,
+    /// Opens a window per launch instead of passing the files to the running player
+    pub multiple_instances: bool,
+    /// Files passed on by a later launch are added to the playlist instead of played right away
+    pub enqueue_opened_files: bool,
+    /// Reopens the playlist and the window of the last session when started without files
+    pub restore_session: bool,
+    /// Saved when the last window closes while `restore_session` is set
+    pub session: Option<Session>,
+    pub recent: Vec<RecentItem>,
+    /// Scanned for the media library, including their subfolders
+    pub library_folders: Vec<PathBuf>,
+}
+
+impl Config {
+    fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("wgpu-media-player").join("config.json"))
+    }
+
+    /// Loads the config, falls back to the defaults when there is none or it can't be read
+    pub fn load() -> Self {
+        let Some(path) = Self::path() else {
+            return Self::default();
+        };
+
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|err| {
+                tracing::warn!("Failed to parse config {:?}: {}", path, err);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self) {
+        if let Err(err) = self.try_save() {
+            tracing::warn!("Failed to save config: {}", err);
+        }
+    }
+
+    fn try_save(&self) -> Result<(), Error> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        // a crash or a full disk halfway through the write leaves the previous config intact
+        let temporary = path.with_extension("json.tmp");
+        create_private(&temporary)?.write_all(serde_json::to_string_pretty(self)?.as_bytes())?;
+        std::fs::rename(temporary, path)?;
+
+        Ok(())
+    }
+
+    /// Moves the uri to the top of the recent files, keeping the title it had when there is no
+    /// new one
+    pub fn add_recent(&mut self, uri: &str, title: Option<&str>) {
+        // the stream is gone once it has been read
+        if uri == STDIN_URI {
+            return;
+        }
+        let previous = self
+            .recent
+            .iter()
+            .position(|item| item.uri == uri)
+            .map(|index| self.recent.remove(index));
+        // played again, it continues where it was left
+        let (position_ms, duration_ms) = previous.as_ref().map_or((0, None), |previous| {
+            (previous.position_ms, previous.duration_ms)
+        });
+        let title = match (title, previous) {
+            (Some(title), _) => title.to_string(),
+            (None, Some(previous)) => previous.title,
+            (None, None) => uri.to_string(),
+        };
+        self.recent.insert(
+            0,
+            RecentItem {
+                uri: uri.to_string(),
+                title,
+                position_ms,
+                duration_ms,
+            },
+        );
+        self.recent.truncate(MAX_RECENT);
+    }
+
+    /// Remembers how far the recent item played. It is only kept in memory until the config is
+    /// saved, which playing is too frequent for.
+    pub fn set_recent_position(
+        &mut self,
+        uri: &str,
+        position: Duration,
+        duration: Option<Duration>,
+    ) {
+        if let Some(item) = self.recent.iter_mut().find(|item| item.uri == uri) {
+            item.position_ms = position.as_millis() as u64;
+            item.duration_ms = duration.map(|duration| duration.as_millis() as u64);
+        }
+    }
+
+    /// Where the item continues, see `RecentItem::resume_position`
+    pub fn resume_position(&self, uri: &str) -> Option<Duration> {
+        self.recent
+            .iter()
+            .find(|item| item.uri == uri)
+            .and_then(RecentItem::resume_position)
+    }
+
+    /// The recent items that were left before their end, most recent first
+    #[cfg(feature = "ui")]
+    pub fn continue_watching(&self) -> impl Iterator<Item = &RecentItem> {
+        self.recent
+            .iter()
+            .filter(|item| item.resume_position().is_some())
+    }
+
+    pub fn output_directory(&self) -> PathBuf {
+        self.output_folder.clone().unwrap_or_else(|| {
+            dirs::video_dir()
+                .or_else(dirs::home_dir)
+                .unwrap_or_else(std::env::temp_dir)
+        })
+    }
+
+    /// Keys the profile of a local file by its content, so it is found again after the file was
+    /// renamed or moved. Profiles stored under the uri before are moved to the hash.
+    pub fn identify_media(&mut self, uri: &str) {
+        if self.media_keys.contains_key(uri) || uri.contains('#') {
+            return;
+        }
+        let Ok((path, _)) = glib::filename_from_uri(uri) else {
+            return;
+        };
+        let key = match file_hash(&path) {
+            Ok(hash) => format!("hash:{:016x}", hash),
+            Err(err) => {
+                tracing::debug!("Failed to hash {}: {}", path.display(), err);
+                return;
+            }
+        };
+        if let Some(profile) = self.media.remove(uri) {
+            self.media.entry(key.clone()).or_insert(profile);
+        }
+        self.media_keys.insert(uri.to_string(), key);
+    }
+
+    fn media_key<'a>(&'a self, uri: &'a str) -> &'a str {
+        self.media_keys.get(uri).map_or(uri, String::as_str)
+    }
+
+    pub fn media_profile(&self, uri: &str) -> MediaProfile {
+        self.media
+            .get(self.media_key(uri))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Stores the profile, default profiles are removed to keep the file small
+    pub fn set_media_profile(&mut self, uri: &str, profile: MediaProfile) {
+        let key = self.media_key(uri).to_string();
+        if profile == MediaProfile::default() {
+            self.media.remove(&key);
+        } else {
+            self.media.insert(key, profile);
+        }
+    }
+}
+
+/// The config holds the passwords of the saved logins, so on unix only the user can read it
+fn create_private(path: &Path) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let file = options.open(path)?;
+    // the mode only applies to new files, one left behind by an older version keeps its own
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+    Ok(file)
+}
+
+/// Identifies a file by its size and the sum of the 64 bit words at its start and end, like
+/// OpenSubtitles does. Cheap for big files and the same wherever the file is.
+fn file_hash(path: &Path) -> io::Result<u64> {
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+    let mut hash = size;
+    for start in [0, size.saturating_sub(HASHED_CHUNK_SIZE)] {
+        file.seek(SeekFrom::Start(start))?;
+        let mut chunk = Vec::new();
+        (&mut file)
+            .take(HASHED_CHUNK_SIZE)
+            .read_to_end(&mut chunk)?;
+        for word in chunk.chunks(8) {
+            let mut bytes = [0; 8];
+            bytes[..word.len()].copy_from_slice(word);
+            hash = hash.wrapping_add(u64::from_le_bytes(bytes));
+        }
+    }
+    Ok(hash)
+}
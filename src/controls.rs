@@ -0,0 +1,338 @@
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "ui")]
+use crate::config::TimeDisplay;
+use crate::media_decoder::{DecoderCommand, MediaTags};
+
+/// Time between the keyframe seeks sent while dragging the seek bar
+const SCRUB_INTERVAL: Duration = Duration::from_millis(50);
+/// How long the volume stays on screen after it changed
+#[cfg(feature = "ui")]
+const VOLUME_OSD_DURATION: Duration = Duration::from_secs(1);
+
+/// The bar at the bottom of the window with the seek bar
+#[derive(Default)]
+pub struct ControlBar {
+    position: Duration,
+    duration: Option<Duration>,
+    /// Percentage while the pipeline is stalled to fill its buffers
+    buffering: Option<i32>,
+    /// Position the seek bar is being dragged to, in seconds
+    drag_position: Option<f64>,
+    /// When the last preview seek was sent while dragging
+    last_scrub: Option<Instant>,
+    /// Downloaded parts of a network stream as fractions of the whole
+    buffered_ranges: Vec<(f32, f32)>,
+    /// The A and B points of the A-B loop, shown as markers on the seek bar
+    loop_points: (Option<Duration>, Option<Duration>),
+    /// Attempt and maximum attempts while a dropped stream is being reconnected
+    reconnecting: Option<(u32, u32)>,
+    /// Title, artist and album, shown above the seek bar
+    tags: MediaTags,
+    /// Volume and mute state, and when they last changed
+    volume_osd: Option<(f64, bool, Instant)>,
+}
+
+impl ControlBar {
+    pub fn reset(&mut self) {
+        *self = Self {
+            volume_osd: self.volume_osd.take(),
+            ..Self::default()
+        };
+    }
+
+    pub fn position(&self) -> Duration {
+        self.position
+    }
+
+    /// Position the seek bar shows, including a scrub that hasn't finished yet
+    pub fn shown_position(&self) -> Duration {
+        self.drag_position
+            .map_or(self.position, Duration::from_secs_f64)
+    }
+
+    pub fn duration(&self) -> Option<Duration> {
+        self.duration
+    }
+
+    /// Moves the seek bar right away instead of waiting for the decoder to report the position
+    pub fn seek(&mut self, position: Duration) -> DecoderCommand {
+        self.position = position;
+        DecoderCommand::Seek(position)
+    }
+
+    /// Previews the position with keyframe seeks, for dragging the seek bar or the video. The
+    /// real seek follows with `finish_scrub`.
+    pub fn scrub(&mut self, position: Duration) -> Option<DecoderCommand> {
+        let position = position.as_secs_f64();
+        let moved = self.drag_position != Some(position);
+        let due = self
+            .last_scrub
+            .is_none_or(|last_scrub| last_scrub.elapsed() >= SCRUB_INTERVAL);
+        self.drag_position = Some(position);
+        (moved && due).then(|| {
+            self.last_scrub = Some(Instant::now());
+            DecoderCommand::Scrub(Duration::from_secs_f64(position))
+        })
+    }
+
+    /// Seeks frame exact to where the scrub stopped, whatever the seek mode, None when nothing
+    /// was scrubbed
+    pub fn finish_scrub(&mut self) -> Option<DecoderCommand> {
+        self.last_scrub = None;
+        let position = Duration::from_secs_f64(self.drag_position.take()?);
+        self.position = position;
+        Some(DecoderCommand::SeekExact(position))
+    }
+
+    /// Shows the volume for a moment
+    pub fn show_volume(&mut self, volume: f64, muted: bool) {
+        self.volume_osd = Some((volume, muted, Instant::now()));
+    }
+
+    pub fn set_loop_points(&mut self, a: Option<Duration>, b: Option<Duration>) {
+        self.loop_points = (a, b);
+    }
+
+    pub fn set_position(&mut self, position: Duration, duration: Option<Duration>) {
+        self.position = position;
+        self.duration = duration;
+        // the decoder only reports positions while it is playing again
+        self.reconnecting = None;
+    }
+
+    pub fn set_buffered_ranges(&mut self, ranges: Vec<(f32, f32)>) {
+        self.buffered_ranges = ranges;
+    }
+
+    pub fn set_buffering(&mut self, percent: i32) {
+        self.buffering = (percent < 100).then_some(percent);
+    }
+
+    pub fn tags(&self) -> &MediaTags {
+        &self.tags
+    }
+
+    pub fn set_tags(&mut self, tags: MediaTags) {
+        self.tags = tags;
+    }
+
+    pub fn set_reconnecting(&mut self, attempt: Option<(u32, u32)>) {
+        self.reconnecting = attempt;
+    }
+
+    /// `1:23 / 45:00`, or `-43:37 / 45:00` for the remaining time. Live streams only have the
+    /// position.
+    #[cfg(feature = "ui")]
+    fn time_label(&self, position: Duration, time_display: &TimeDisplay) -> String {
+        match self.duration {
+            Some(duration) if time_display.remaining => format!(
+                "-{} / {}",
+                format_time(duration.saturating_sub(position)),
+                format_time(duration)
+            ),
+            Some(duration) => format!("{} / {}", format_time(position), format_time(duration)),
+            None => format_time(position),
+        }
+    }
+
+    #[cfg(feature = "ui")]
+    pub fn ui(
+        &mut self,
+        ctx: &egui::Context,
+        time_display: &mut TimeDisplay,
+    ) -> Option<DecoderCommand> {
+        let mut command = None;
+
+        egui::TopBottomPanel::bottom("controls").show(ctx, |ui| {
+            if let Some(title) = &self.tags.title {
+                ui.horizontal(|ui| {
+                    ui.strong(title);
+                    let tags = [&self.tags.artist, &self.tags.album, &self.tags.station];
+                    for tag in tags.into_iter().flatten() {
+                        ui.label("·");
+                        ui.label(tag);
+                    }
+                });
+            }
+
+            let duration = self.duration.unwrap_or_default().as_secs_f64();
+            let mut position = self.drag_position.unwrap_or(self.position.as_secs_f64());
+
+            let time_label = self.time_label(Duration::from_secs_f64(position), time_display);
+            let response = ui
+                .horizontal(|ui| {
+                    let label = ui
+                        .add(
+                            egui::Label::new(egui::RichText::new(time_label).monospace())
+                                .sense(egui::Sense::click()),
+                        )
+                        .on_hover_text("Click to switch between the time played and left");
+                    if label.clicked() {
+                        time_display.remaining = !time_display.remaining;
+                    }
+
+                    ui.spacing_mut().slider_width = ui.available_width();
+                    ui.add_enabled(
+                        self.buffering.is_none() && duration > 0.0,
+                        egui::Slider::new(&mut position, 0.0..=duration.max(f64::EPSILON))
+                            .show_value(false),
+                    )
+                })
+                .inner;
+
+            paint_buffered_ranges(ui, &response, &self.buffered_ranges, position / duration);
+            for point in [self.loop_points.0, self.loop_points.1]
+                .into_iter()
+                .flatten()
+            {
+                paint_marker(ui, &response, point.as_secs_f64() / duration);
+            }
+
+            if response.dragged() {
+                command = self.scrub(Duration::from_secs_f64(position));
+            } else if response.drag_released() {
+                self.drag_position = Some(position);
+                command = self.finish_scrub();
+            } else if response.changed() {
+                self.drag_position = None;
+                self.last_scrub = None;
+                command = Some(self.seek(Duration::from_secs_f64(position)));
+            }
+        });
+
+        if time_display.osd {
+            let position = self.shown_position();
+            egui::Area::new("time_osd")
+                .anchor(egui::Align2::RIGHT_TOP, [-8.0, 8.0])
+                .interactable(false)
+                .show(ctx, |ui| {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.monospace(self.time_label(position, time_display));
+                    });
+                });
+        }
+
+        if let Some((volume, muted, shown_at)) = self.volume_osd {
+            if shown_at.elapsed() < VOLUME_OSD_DURATION {
+                egui::Area::new("volume_osd")
+                    .anchor(egui::Align2::CENTER_TOP, [0.0, 8.0])
+                    .interactable(false)
+                    .show(ctx, |ui| {
+                        egui::Frame::popup(ui.style()).show(ui, |ui| {
+                            if muted {
+                                ui.label("Muted");
+                            } else {
+                                ui.label(format!("Volume {:.0}%", volume * 100.0));
+                            }
+                        });
+                    });
+                // hidden again even when nothing else redraws
+                ctx.request_repaint_after(VOLUME_OSD_DURATION);
+            } else {
+                self.volume_osd = None;
+            }
+        }
+
+        if let Some(percent) = self.buffering {
+            egui::Area::new("buffering")
+                .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                .interactable(false)
+                .show(ctx, |ui| {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label(format!("Buffering {}%", percent));
+                        });
+                    });
+                });
+        }
+
+        if let Some((attempt, max_attempts)) = self.reconnecting {
+            egui::Area::new("reconnecting")
+                .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                .show(ctx, |ui| {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label(format!(
+                                "Connection lost, reconnecting (attempt {}/{})",
+                                attempt, max_attempts
+                            ));
+                            if ui.button("Cancel").clicked() {
+                                self.reconnecting = None;
+                                command = Some(DecoderCommand::CancelReconnect);
+                            }
+                        });
+                    });
+                });
+        }
+
+        command
+    }
+}
+
+/// Paints the downloaded parts that haven't been played yet on top of the seek bar rail
+#[cfg(feature = "ui")]
+fn paint_buffered_ranges(
+    ui: &egui::Ui,
+    response: &egui::Response,
+    ranges: &[(f32, f32)],
+    played: f64,
+) {
+    if ranges.is_empty() || !played.is_finite() {
+        return;
+    }
+
+    // matches the rail egui draws for a horizontal slider
+    let rect = response.rect;
+    let rail_radius = (rect.height() / 4.0).max(2.0);
+    let played_x = rect.left() + rect.width() * played as f32;
+
+    for &(start, stop) in ranges {
+        let left = (rect.left() + rect.width() * start).max(played_x);
+        let right = rect.left() + rect.width() * stop;
+        if right <= left {
+            continue;
+        }
+
+        ui.painter().rect_filled(
+            egui::Rect::from_min_max(
+                egui::pos2(left, rect.center().y - rail_radius),
+                egui::pos2(right, rect.center().y + rail_radius),
+            ),
+            rail_radius,
+            egui::Color32::from_white_alpha(40),
+        );
+    }
+}
+
+/// Draws a tick through the seek bar at the given fraction of the media
+#[cfg(feature = "ui")]
+fn paint_marker(ui: &egui::Ui, response: &egui::Response, fraction: f64) {
+    if !fraction.is_finite() {
+        return;
+    }
+
+    let rect = response.rect;
+    let x = rect.left() + rect.width() * fraction.clamp(0.0, 1.0) as f32;
+    ui.painter().line_segment(
+        [egui::pos2(x, rect.top()), egui::pos2(x, rect.bottom())],
+        egui::Stroke::new(2.0, egui::Color32::YELLOW),
+    );
+}
+
+/// Formats as m:ss, or h:mm:ss for anything longer than an hour
+pub fn format_time(time: Duration) -> String {
+    let seconds = time.as_secs();
+    if seconds >= 3600 {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
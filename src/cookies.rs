@@ -0,0 +1,81 @@
+use std::{path::Path, time::SystemTime};
+
+use anyhow::{Context, Error};
+use url::Url;
+
+/// A line of a Netscape cookies.txt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cookie {
+    /// Without the leading dot
+    pub domain: String,
+    pub include_subdomains: bool,
+    pub path: String,
+    pub secure: bool,
+    /// Seconds since the epoch, 0 for session cookies
+    pub expires: u64,
+    pub name: String,
+    pub value: String,
+}
+
+impl Cookie {
+    /// Whether a browser would send the cookie with a request for `url`
+    pub fn matches(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        let host = host.to_lowercase();
+        let domain_matches = host == self.domain
+            || (self.include_subdomains && host.ends_with(&format!(".{}", self.domain)));
+        let path_matches = url.path().starts_with(&self.path);
+        let expired = self.expires != 0
+            && SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .is_ok_and(|now| now.as_secs() >= self.expires);
+        domain_matches && path_matches && (!self.secure || url.scheme() == "https") && !expired
+    }
+}
+
+pub fn load(path: &Path) -> Result<Vec<Cookie>, Error> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read the cookie file {}", path.display()))?;
+    Ok(parse(&text))
+}
+
+/// Skips the lines that aren't cookies instead of failing, the files are often edited by hand
+pub fn parse(text: &str) -> Vec<Cookie> {
+    text.lines()
+        .filter_map(|line| {
+            // curl and yt-dlp write http-only cookies as comments with this prefix
+            let line = line.strip_prefix("#HttpOnly_").unwrap_or(line);
+            if line.starts_with('#') {
+                return None;
+            }
+            let fields: Vec<&str> = line.trim_end_matches('\r').split('\t').collect();
+            let [domain, include_subdomains, path, secure, expires, name, value] = fields[..]
+            else {
+                return None;
+            };
+            Some(Cookie {
+                domain: domain.trim_start_matches('.').to_lowercase(),
+                include_subdomains: include_subdomains.eq_ignore_ascii_case("TRUE"),
+                path: path.to_string(),
+                secure: secure.eq_ignore_ascii_case("TRUE"),
+                expires: expires.parse().unwrap_or(0),
+                name: name.to_string(),
+                value: value.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// The name=value pairs of the cookies for `uri`, what souphttpsrc takes as its cookies
+pub fn for_uri(cookies: &[Cookie], uri: &str) -> Vec<String> {
+    let Ok(url) = Url::parse(uri) else {
+        return Vec::new();
+    };
+    cookies
+        .iter()
+        .filter(|cookie| cookie.matches(&url))
+        .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+        .collect()
+}
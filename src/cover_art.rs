@@ -0,0 +1,88 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Error};
+
+/// Embedded cover art of a music file or another image, decoded to RGBA
+#[derive(Debug)]
+pub struct CoverArt {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+/// Picks the front cover, or the preview image of files that only have that
+pub fn find_image(tags: &gst::TagListRef) -> Option<gst::Sample> {
+    tags.get::<gst::tags::Image>()
+        .map(|value| value.get())
+        .or_else(|| {
+            tags.get::<gst::tags::PreviewImage>()
+                .map(|value| value.get())
+        })
+}
+
+/// Media type of the image files `load` can decode
+fn image_type(path: &Path) -> Option<&'static str> {
+    match path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_lowercase)
+        .as_deref()
+    {
+        Some("png") => Some("image/png"),
+        Some("jpg" | "jpeg") => Some("image/jpeg"),
+        Some("webp") => Some("image/webp"),
+        Some("gif") => Some("image/gif"),
+        _ => None,
+    }
+}
+
+/// Whether the path is an image, by its extension
+pub fn is_image(path: &Path) -> bool {
+    image_type(path).is_some()
+}
+
+/// Whether the uri is a local image, which is shown instead of played unless it is animated
+pub fn is_image_uri(uri: &str) -> bool {
+    uri.starts_with("file://") && is_image(Path::new(uri))
+}
+
+/// Decodes the first frame of a .png, .jpg, .webp or .gif file
+pub fn load(path: &Path) -> Result<CoverArt, Error> {
+    let Some(media_type) = image_type(path) else {
+        return Err(anyhow!(
+            "{} isn't a .png, .jpg, .webp or .gif",
+            path.display()
+        ));
+    };
+    let data = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let sample = gst::Sample::builder()
+        .buffer(&gst::Buffer::from_slice(data))
+        .caps(&gst::Caps::builder(media_type).build())
+        .build();
+    decode(&sample).with_context(|| format!("Failed to decode {}", path.display()))
+}
+
+/// Decodes the jpeg or png of an image tag
+pub fn decode(sample: &gst::Sample) -> Result<CoverArt, Error> {
+    let caps = gst::Caps::builder("video/x-raw")
+        .field("format", "RGBA")
+        .field("pixel-aspect-ratio", gst::Fraction::new(1, 1))
+        .build();
+    let sample = gst_video::convert_sample(sample, &caps, gst::ClockTime::from_seconds(5))?;
+
+    let info = gst_video::VideoInfo::from_caps(
+        sample
+            .caps()
+            .ok_or_else(|| anyhow!("sample without caps"))?,
+    )?;
+    let buffer = sample
+        .buffer()
+        .ok_or_else(|| anyhow!("sample without buffer"))?;
+    let map = buffer.map_readable()?;
+
+    Ok(CoverArt {
+        width: info.width(),
+        height: info.height(),
+        data: map.as_slice().to_vec(),
+    })
+}
@@ -0,0 +1,146 @@
+#[cfg(feature = "ui")]
+use std::collections::HashMap;
+
+#[cfg(feature = "ui")]
+use egui::{pos2, vec2, Align2, Color32, FontId, Rect};
+
+use crate::playlist::Playlist;
+
+/// How many items are drawn on each side of the selected one
+#[cfg(feature = "ui")]
+const VISIBLE_NEIGHBOURS: usize = 4;
+
+/// Fullscreen overlay that shows the playlist as large thumbnails, meant to be navigated from the couch
+#[derive(Default)]
+pub struct CoverFlow {
+    open: bool,
+    selected: usize,
+}
+
+impl CoverFlow {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn toggle(&mut self, playlist: &Playlist) {
+        self.open = !self.open;
+        if self.open {
+            self.selected = playlist.current_index().unwrap_or(0);
+        }
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn select_next(&mut self, playlist: &Playlist) {
+        if self.selected + 1 < playlist.len() {
+            self.selected += 1;
+        }
+    }
+
+    #[cfg(feature = "ui")]
+    pub fn ui(
+        &self,
+        ctx: &egui::Context,
+        playlist: &Playlist,
+        thumbnails: &HashMap<String, egui::TextureHandle>,
+    ) {
+        if !self.open {
+            return;
+        }
+
+        let painter = ctx.layer_painter(egui::LayerId::new(
+            egui::Order::Foreground,
+            egui::Id::new("cover_flow"),
+        ));
+        let screen = ctx.screen_rect();
+        painter.rect_filled(screen, 0.0, Color32::from_black_alpha(220));
+
+        if playlist.is_empty() {
+            painter.text(
+                screen.center(),
+                Align2::CENTER_CENTER,
+                "The playlist is empty, drop some files to add them",
+                FontId::proportional(24.0),
+                Color32::WHITE,
+            );
+            return;
+        }
+
+        let cover_height = screen.height() * 0.4;
+        let first = self.selected.saturating_sub(VISIBLE_NEIGHBOURS);
+        let last = (self.selected + VISIBLE_NEIGHBOURS).min(playlist.len() - 1);
+
+        // paint from the outside in, so the selected cover ends up on top
+        let mut visible: Vec<usize> = (first..=last).collect();
+        visible.sort_by_key(|index| std::cmp::Reverse(index.abs_diff(self.selected)));
+
+        for index in visible {
+            let item = &playlist.items()[index];
+            let offset = index as f32 - self.selected as f32;
+            let is_selected = index == self.selected;
+
+            let height = if is_selected {
+                cover_height
+            } else {
+                cover_height * 0.6
+            };
+            let aspect_ratio = thumbnails
+                .get(&item.uri)
+                .map(|texture| texture.aspect_ratio())
+                .unwrap_or(16.0 / 9.0);
+            let center = pos2(
+                screen.center().x + offset * cover_height * 1.1,
+                screen.center().y,
+            );
+            let rect = Rect::from_center_size(center, vec2(height * aspect_ratio, height));
+
+            let tint = if is_selected {
+                Color32::WHITE
+            } else {
+                Color32::from_gray(110)
+            };
+            match thumbnails.get(&item.uri) {
+                Some(texture) => painter.image(
+                    texture.id(),
+                    rect,
+                    Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0)),
+                    tint,
+                ),
+                None => painter.rect_filled(rect, 4.0, Color32::from_gray(40)),
+            }
+
+            if playlist.current_index() == Some(index) {
+                painter.rect_stroke(rect, 0.0, egui::Stroke::new(3.0, Color32::LIGHT_BLUE));
+            }
+        }
+
+        let selected = &playlist.items()[self.selected];
+        painter.text(
+            pos2(
+                screen.center().x,
+                screen.center().y + cover_height / 2.0 + 24.0,
+            ),
+            Align2::CENTER_TOP,
+            &selected.title,
+            FontId::proportional(28.0),
+            Color32::WHITE,
+        );
+        painter.text(
+            pos2(screen.center().x, screen.bottom() - 24.0),
+            Align2::CENTER_BOTTOM,
+            "← → browse    Enter play    Esc close",
+            FontId::proportional(16.0),
+            Color32::GRAY,
+        );
+    }
+}
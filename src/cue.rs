@@ -0,0 +1,333 @@
+use std::{path::Path, time::Duration};
+
+use crate::playlist_file::{resolve, PlaylistEntry};
+
+/// Appended to the uri of the audio file for each track. It isn't the `#t=` of media fragments,
+/// which a url may already carry for the server.
+const TRACK_FRAGMENT: &str = "#cue-track=";
+/// CUE times are minutes, seconds and frames of a CD
+const FRAMES_PER_SECOND: u64 = 75;
+
+/// Part of a single file album that one playlist item plays
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrackRange {
+    pub start: Duration,
+    /// None for the last track, which plays to the end of the file
+    pub end: Option<Duration>,
+}
+
+impl TrackRange {
+    pub fn contains(&self, position: Duration) -> bool {
+        position >= self.start && self.end.is_none_or(|end| position < end)
+    }
+}
+
+/// The uri of a track of the file
+pub fn track_uri(file_uri: &str, range: TrackRange) -> String {
+    match range.end {
+        Some(end) => format!(
+            "{}{}{:.3},{:.3}",
+            file_uri,
+            TRACK_FRAGMENT,
+            range.start.as_secs_f64(),
+            end.as_secs_f64()
+        ),
+        None => format!(
+            "{}{}{:.3}",
+            file_uri,
+            TRACK_FRAGMENT,
+            range.start.as_secs_f64()
+        ),
+    }
+}
+
+/// The uri of the file and the range of the track, for uris made by `track_uri`
+pub fn split_track_uri(uri: &str) -> (&str, Option<TrackRange>) {
+    let Some((file_uri, fragment)) = uri.rsplit_once(TRACK_FRAGMENT) else {
+        return (uri, None);
+    };
+    let seconds = |text: &str| {
+        text.parse::<f64>()
+            .ok()
+            .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+    };
+    let range = match fragment.split_once(',') {
+        Some((start, end)) => seconds(start)
+            .zip(seconds(end))
+            .map(|(start, end)| TrackRange {
+                start,
+                end: Some(end),
+            }),
+        None => seconds(fragment).map(|start| TrackRange { start, end: None }),
+    };
+    match range {
+        Some(range) => (file_uri, Some(range)),
+        None => (uri, None),
+    }
+}
+
+#[derive(Debug, Default)]
+struct CueTrack {
+    file: Option<String>,
+    title: Option<String>,
+    performer: Option<String>,
+    start: Option<Duration>,
+}
+
+/// The tracks of a CUE sheet as entries of the audio files it refers to
+pub fn parse(text: &str, folder: &Path) -> Vec<PlaylistEntry> {
+    let mut file = None;
+    let mut tracks: Vec<CueTrack> = Vec::new();
+    for line in text.lines() {
+        let line = line.trim();
+        let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        match command.to_uppercase().as_str() {
+            "FILE" => {
+                // FILE "name" WAVE, the type is left out of unquoted names too
+                let name = match rest.trim().strip_prefix('"') {
+                    Some(_) => quoted(rest),
+                    None => rest
+                        .trim()
+                        .rsplit_once(char::is_whitespace)
+                        .map_or(rest.trim(), |(name, _)| name.trim())
+                        .to_string(),
+                };
+                file = resolve(folder, &name);
+            }
+            "TRACK" => tracks.push(CueTrack {
+                file: file.clone(),
+                ..Default::default()
+            }),
+            "TITLE" => {
+                if let Some(track) = tracks.last_mut() {
+                    track.title = Some(quoted(rest));
+                }
+            }
+            "PERFORMER" => {
+                if let Some(track) = tracks.last_mut() {
+                    track.performer = Some(quoted(rest));
+                }
+            }
+            // INDEX 01 is where the track starts, 00 is the pregap before it
+            "INDEX" => {
+                let mut parts = rest.split_whitespace();
+                if let (Some("01"), Some(time), Some(track)) =
+                    (parts.next(), parts.next(), tracks.last_mut())
+                {
+                    track.start = parse_time(time);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut entries = Vec::new();
+    for (index, track) in tracks.iter().enumerate() {
+        let (Some(file), Some(start)) = (&track.file, track.start) else {
+            continue;
+        };
+        // a track ends where the next one of the same file starts
+        let end = tracks
+            .get(index + 1)
+            .filter(|next| next.file.as_ref() == Some(file))
+            .and_then(|next| next.start);
+        let title = match (&track.performer, &track.title) {
+            (Some(performer), Some(title)) => Some(format!("{} - {}", performer, title)),
+            (None, Some(title)) => Some(title.clone()),
+            _ => None,
+        };
+        entries.push(PlaylistEntry {
+            uri: track_uri(file, TrackRange { start, end }),
+            title,
+        });
+    }
+    entries
+}
+
+/// The argument without its quotes
+fn quoted(rest: &str) -> String {
+    let rest = rest.trim();
+    match rest.strip_prefix('"') {
+        Some(quoted) => quoted.split('"').next().unwrap_or_default().to_string(),
+        None => rest.to_string(),
+    }
+}
+
+/// mm:ss:ff
+fn parse_time(time: &str) -> Option<Duration> {
+    let mut parts = time.split(':').map(|part| part.parse::<u64>().ok());
+    let (Some(Some(minutes)), Some(Some(seconds)), Some(Some(frames))) =
+        (parts.next(), parts.next(), parts.next())
+    else {
+        return None;
+    };
+    let millis = minutes
+        .checked_mul(60)?
+        .checked_add(seconds)?
+        .checked_mul(1000)?
+        .checked_add(frames.checked_mul(1000)? / FRAMES_PER_SECOND)?;
+    Some(Duration::from_millis(millis))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folder() -> &'static Path {
+        Path::new(if cfg!(windows) { r"C:\music" } else { "/music" })
+    }
+
+    fn file_uri(name: &str) -> String {
+        crate::app::format_url(&folder().join(name).to_string_lossy())
+    }
+
+    const SHEET: &str = r#"REM GENRE Rock
+PERFORMER "Band"
+TITLE "Album"
+FILE "album.flac" WAVE
+  TRACK 01 AUDIO
+    TITLE "First"
+    PERFORMER "Band"
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    TITLE "Second"
+    INDEX 00 03:58:00
+    INDEX 01 04:00:00
+FILE bonus.wav WAVE
+  TRACK 03 AUDIO
+    INDEX 01 00:00:00
+"#;
+
+    #[test]
+    fn parses_tracks() {
+        let entries = parse(SHEET, folder());
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].title.as_deref(), Some("Band - First"));
+        assert_eq!(entries[1].title.as_deref(), Some("Second"));
+        assert_eq!(entries[2].title, None);
+
+        let (uri, first) = split_track_uri(&entries[0].uri);
+        assert_eq!(uri, file_uri("album.flac"));
+        assert_eq!(
+            first,
+            Some(TrackRange {
+                start: Duration::ZERO,
+                end: Some(Duration::from_secs(240)),
+            })
+        );
+        let (uri, second) = split_track_uri(&entries[1].uri);
+        assert_eq!(uri, file_uri("album.flac"));
+        assert_eq!(
+            second,
+            Some(TrackRange {
+                start: Duration::from_secs(240),
+                end: None,
+            })
+        );
+        // the next file starts over
+        let (uri, third) = split_track_uri(&entries[2].uri);
+        assert_eq!(uri, file_uri("bonus.wav"));
+        assert_eq!(third.map(|range| range.end), Some(None));
+    }
+
+    #[test]
+    fn keeps_absolute_paths_and_urls() {
+        let absolute = folder().join("other").join("a.flac");
+        let text = format!(
+            "FILE \"{}\" WAVE\nTRACK 01 AUDIO\nINDEX 01 00:00:00\n\
+             FILE \"http://host/b.mp3\" MP3\nTRACK 02 AUDIO\nINDEX 01 00:00:00\n",
+            absolute.display()
+        );
+        let entries = parse(&text, Path::new("/elsewhere"));
+        assert_eq!(
+            split_track_uri(&entries[0].uri).0,
+            crate::app::format_url(&absolute.to_string_lossy())
+        );
+        assert_eq!(split_track_uri(&entries[1].uri).0, "http://host/b.mp3");
+    }
+
+    #[test]
+    fn skips_malformed_tracks() {
+        let text = "TRACK 01 AUDIO\nINDEX 01 00:00:00\n\
+                    FILE a.wav WAVE\nTRACK 02 AUDIO\nINDEX 01 00:xx:00\n\
+                    TRACK 03 AUDIO\nINDEX 00 00:01:00\n\
+                    TRACK 04 AUDIO\nINDEX 01 99999999999999999999:00:00\n\
+                    TRACK 05 AUDIO\nINDEX 01 307445734561825860:00:00\n\
+                    garbage\n\
+                    TRACK 06 AUDIO\nINDEX 01 01:00:00\n";
+        let entries = parse(text, folder());
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            split_track_uri(&entries[0].uri).1,
+            Some(TrackRange {
+                start: Duration::from_secs(60),
+                end: None,
+            })
+        );
+    }
+
+    #[test]
+    fn parses_frames() {
+        assert_eq!(parse_time("01:02:37"), Some(Duration::from_millis(62_493)));
+        assert_eq!(parse_time("100:00:00"), Some(Duration::from_secs(6000)));
+        assert_eq!(parse_time("01:02"), None);
+        assert_eq!(parse_time(&format!("{}:00:00", u64::MAX)), None);
+    }
+
+    #[test]
+    fn refuses_pipelines() {
+        let text = "FILE \"pipeline:filesrc location=/etc/passwd ! fakesink\" WAVE\n\
+                    TRACK 01 AUDIO\nINDEX 01 00:00:00\n";
+        assert!(parse(text, folder()).is_empty());
+    }
+
+    #[test]
+    fn splits_track_uris() {
+        let range = TrackRange {
+            start: Duration::from_millis(1500),
+            end: Some(Duration::from_secs(3)),
+        };
+        let uri = track_uri("file:///a.flac", range);
+        assert_eq!(split_track_uri(&uri), ("file:///a.flac", Some(range)));
+        assert_eq!(
+            split_track_uri("file:///a.flac#cue-track=2"),
+            (
+                "file:///a.flac",
+                Some(TrackRange {
+                    start: Duration::from_secs(2),
+                    end: None,
+                })
+            )
+        );
+    }
+
+    #[test]
+    fn leaves_other_uris_alone() {
+        for uri in [
+            "file:///a.flac",
+            "http://host/video.mp4#t=10,20",
+            "http://host/video.mp4#t=1e30",
+            "file:///a.flac#cue-track=",
+            "file:///a.flac#cue-track=x",
+            "file:///a.flac#cue-track=-1",
+            "file:///a.flac#cue-track=NaN",
+            "file:///a.flac#cue-track=1e30",
+            "file:///a.flac#cue-track=0,1e300",
+        ] {
+            assert_eq!(split_track_uri(uri), (uri, None), "{}", uri);
+        }
+    }
+
+    #[test]
+    fn contains_positions_of_its_range() {
+        let range = TrackRange {
+            start: Duration::from_secs(10),
+            end: Some(Duration::from_secs(20)),
+        };
+        assert!(!range.contains(Duration::from_secs(9)));
+        assert!(range.contains(Duration::from_secs(10)));
+        assert!(!range.contains(Duration::from_secs(20)));
+        let last = TrackRange { end: None, ..range };
+        assert!(last.contains(Duration::from_secs(1_000_000)));
+    }
+}
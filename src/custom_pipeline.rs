@@ -0,0 +1,136 @@
+use anyhow::{anyhow, Context, Error};
+use gst::prelude::*;
+
+use crate::config::TestSourceSettings;
+
+/// Name of the appsink the video frames are taken from
+pub const VIDEO_SINK: &str = "video";
+/// Name of the appsink that is replaced by the audio output
+pub const AUDIO_SINK: &str = "audio";
+/// Shown as the placeholder of the description field
+#[cfg(feature = "ui")]
+pub const EXAMPLE: &str =
+    "videotestsrc ! videoconvert ! appsink name=video audiotestsrc ! audioconvert ! audioresample ! appsink name=audio";
+
+/// A gst-launch description the user typed in, with `--pipeline` or in the settings. It can
+/// read and write any file, so it is never made from a uri, which playlist files and remote
+/// control points hand over as well.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PipelineDescription(String);
+
+impl PipelineDescription {
+    pub fn new(description: &str) -> Self {
+        Self(description.trim().to_string())
+    }
+
+    /// The test pattern over a tick every second, played by `Action::PlayTestSource`
+    pub fn test_source(settings: &TestSourceSettings) -> Self {
+        let (numerator, denominator) = settings.frame_rate;
+        Self(format!(
+            "videotestsrc pattern={} ! video/x-raw,framerate={}/{} ! videoconvert ! appsink name={} \
+             audiotestsrc wave=ticks ! audioconvert ! audioresample ! appsink name={}",
+            settings.pattern.nick(),
+            numerator,
+            denominator,
+            VIDEO_SINK,
+            AUDIO_SINK
+        ))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Earlier versions played the pipeline of a `pipeline:` uri. Playlist files, remote control
+/// points and later launches could hand those over, so they are refused.
+pub fn is_pipeline_uri(uri: &str) -> bool {
+    uri.trim_start()
+        .get(.."pipeline:".len())
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case("pipeline:"))
+}
+
+/// A pipeline of the user, with its sinks wired into the player
+pub struct CustomPipeline {
+    pub pipeline: gst::Pipeline,
+    /// Its appsink named `video`, the caller sets the caps and callbacks
+    pub video_sink: Option<gst_app::AppSink>,
+    /// In front of the audio output, when there is an appsink named `audio`
+    pub volume: Option<gst::Element>,
+}
+
+impl CustomPipeline {
+    /// Parses the description and swaps its `audio` appsink for `audio_sink`. The appsinks have
+    /// to follow an element with always pads, like videoconvert and audioconvert, so they are
+    /// linked right away.
+    pub fn parse(description: &str, audio_sink: &gst::Element) -> Result<Self, Error> {
+        let pipeline = gst::parse_launch_full(description, None, gst::ParseFlags::FATAL_ERRORS)
+            .context("Failed to parse the pipeline")?
+            .downcast::<gst::Pipeline>()
+            .map_err(|_| anyhow!("The pipeline needs at least a source and an appsink"))?;
+
+        let video_sink =
+            match pipeline.by_name(VIDEO_SINK) {
+                Some(element) => Some(element.downcast::<gst_app::AppSink>().map_err(|_| {
+                    anyhow!("The element named {} has to be an appsink", VIDEO_SINK)
+                })?),
+                None => None,
+            };
+
+        let volume = match pipeline.by_name(AUDIO_SINK) {
+            Some(appsink) => Some(replace_audio_sink(&pipeline, &appsink, audio_sink)?),
+            None => None,
+        };
+
+        if video_sink.is_none() && volume.is_none() {
+            return Err(anyhow!(
+                "The pipeline has no appsink named {} or {}",
+                VIDEO_SINK,
+                AUDIO_SINK
+            ));
+        }
+
+        Ok(Self {
+            pipeline,
+            video_sink,
+            volume,
+        })
+    }
+}
+
+/// Links whatever feeds `appsink` to a volume element and `audio_sink` instead
+fn replace_audio_sink(
+    pipeline: &gst::Pipeline,
+    appsink: &gst::Element,
+    audio_sink: &gst::Element,
+) -> Result<gst::Element, Error> {
+    if appsink
+        .factory()
+        .is_none_or(|factory| factory.name() != "appsink")
+    {
+        return Err(anyhow!(
+            "The element named {} has to be an appsink",
+            AUDIO_SINK
+        ));
+    }
+    let sink_pad = appsink
+        .static_pad("sink")
+        .ok_or_else(|| anyhow!("appsink without a sink pad"))?;
+    let upstream = sink_pad.peer().ok_or_else(|| {
+        anyhow!(
+            "The {} appsink isn't linked, put an audioconvert in front of it",
+            AUDIO_SINK
+        )
+    })?;
+    upstream.unlink(&sink_pad)?;
+    pipeline.remove(appsink)?;
+
+    let volume = gst::ElementFactory::make("volume").build()?;
+    pipeline.add_many(&[&volume, audio_sink])?;
+    let volume_sink = volume
+        .static_pad("sink")
+        .ok_or_else(|| anyhow!("volume without a sink pad"))?;
+    upstream.link(&volume_sink)?;
+    volume.link(audio_sink)?;
+    Ok(volume)
+}
@@ -0,0 +1,193 @@
+use std::path::Path;
+
+use gst::{glib::translate::IntoGlib, prelude::*};
+use gst_video::NavigationCommand;
+
+const DVD_PREFIX: &str = "dvd://";
+const BLURAY_PREFIX: &str = "bluray://";
+
+/// Whether the uri is a DVD or Blu-ray folder, made by `folder_uri`
+pub fn is_disc_uri(uri: &str) -> bool {
+    uri.starts_with(DVD_PREFIX) || uri.starts_with(BLURAY_PREFIX)
+}
+
+/// The dvd:// or bluray:// uri of a folder that holds a VIDEO_TS or BDMV folder, or is one
+pub fn folder_uri(path: &Path) -> Option<String> {
+    let root = match path.file_name().and_then(|name| name.to_str()) {
+        Some(name)
+            if name.eq_ignore_ascii_case("VIDEO_TS") || name.eq_ignore_ascii_case("BDMV") =>
+        {
+            path.parent()?
+        }
+        _ => path,
+    };
+    if root.join("VIDEO_TS").is_dir() {
+        Some(format!("{}{}", DVD_PREFIX, root.display()))
+    } else if root.join("BDMV").is_dir() {
+        Some(format!("{}{}", BLURAY_PREFIX, root.display()))
+    } else {
+        None
+    }
+}
+
+/// The uri for playbin and the folder its source reads from. The sources take the title in
+/// their uri and the folder as their device.
+pub fn source(uri: &str) -> Option<(&'static str, String)> {
+    if let Some(folder) = uri.strip_prefix(DVD_PREFIX) {
+        Some((DVD_PREFIX, folder.to_string()))
+    } else {
+        uri.strip_prefix(BLURAY_PREFIX)
+            .map(|folder| (BLURAY_PREFIX, folder.to_string()))
+    }
+}
+
+/// A menu of the disc, the ui has a button for each
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiscMenu {
+    Root,
+    #[cfg(feature = "ui")]
+    Title,
+    #[cfg(feature = "ui")]
+    Chapter,
+    #[cfg(feature = "ui")]
+    Audio,
+    #[cfg(feature = "ui")]
+    Subtitle,
+    #[cfg(feature = "ui")]
+    Angle,
+}
+
+#[cfg(feature = "ui")]
+impl DiscMenu {
+    pub const ALL: [DiscMenu; 6] = [
+        DiscMenu::Root,
+        DiscMenu::Title,
+        DiscMenu::Chapter,
+        DiscMenu::Audio,
+        DiscMenu::Subtitle,
+        DiscMenu::Angle,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DiscMenu::Root => "Menu",
+            DiscMenu::Title => "Titles",
+            DiscMenu::Chapter => "Chapters",
+            DiscMenu::Audio => "Audio",
+            DiscMenu::Subtitle => "Subtitles",
+            DiscMenu::Angle => "Angles",
+        }
+    }
+}
+
+/// Moves around the menus of a disc, passed on to the source as navigation commands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiscNavigation {
+    Menu(DiscMenu),
+    #[cfg(feature = "ui")]
+    Left,
+    #[cfg(feature = "ui")]
+    Right,
+    #[cfg(feature = "ui")]
+    Up,
+    #[cfg(feature = "ui")]
+    Down,
+    /// Presses the highlighted button
+    #[cfg(feature = "ui")]
+    Activate,
+    #[cfg(feature = "ui")]
+    PreviousAngle,
+    #[cfg(feature = "ui")]
+    NextAngle,
+}
+
+impl DiscNavigation {
+    /// The DVD menus of gstnavigation.h are numbered menu commands
+    fn command(self) -> NavigationCommand {
+        match self {
+            DiscNavigation::Menu(DiscMenu::Root) => NavigationCommand::Menu3,
+            #[cfg(feature = "ui")]
+            DiscNavigation::Menu(DiscMenu::Title) => NavigationCommand::Menu2,
+            #[cfg(feature = "ui")]
+            DiscNavigation::Menu(DiscMenu::Chapter) => NavigationCommand::Menu7,
+            #[cfg(feature = "ui")]
+            DiscNavigation::Menu(DiscMenu::Audio) => NavigationCommand::Menu5,
+            #[cfg(feature = "ui")]
+            DiscNavigation::Menu(DiscMenu::Subtitle) => NavigationCommand::Menu4,
+            #[cfg(feature = "ui")]
+            DiscNavigation::Menu(DiscMenu::Angle) => NavigationCommand::Menu6,
+            #[cfg(feature = "ui")]
+            DiscNavigation::Left => NavigationCommand::Left,
+            #[cfg(feature = "ui")]
+            DiscNavigation::Right => NavigationCommand::Right,
+            #[cfg(feature = "ui")]
+            DiscNavigation::Up => NavigationCommand::Up,
+            #[cfg(feature = "ui")]
+            DiscNavigation::Down => NavigationCommand::Down,
+            #[cfg(feature = "ui")]
+            DiscNavigation::Activate => NavigationCommand::Activate,
+            #[cfg(feature = "ui")]
+            DiscNavigation::PreviousAngle => NavigationCommand::PrevAngle,
+            #[cfg(feature = "ui")]
+            DiscNavigation::NextAngle => NavigationCommand::NextAngle,
+        }
+    }
+}
+
+/// Sends the command upstream to the disc source
+pub fn navigate(pipeline: &gst::Element, navigation: DiscNavigation) {
+    let structure = gst::Structure::builder("application/x-gst-navigation")
+        .field("event", "command")
+        .field("command-code", navigation.command().into_glib() as u32)
+        .build();
+    if !pipeline.send_event(gst::event::Navigation::new(structure)) {
+        tracing::debug!("The disc source ignored {:?}", navigation);
+    }
+}
+
+/// Where playback is on the disc, the numbers start at 0
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DiscPosition {
+    pub title: Option<u32>,
+    pub titles: Option<u32>,
+    pub chapter: Option<u32>,
+    pub chapters: Option<u32>,
+}
+
+/// Asks the source in the title and chapter formats it registers
+pub fn query_position(pipeline: &gst::Element) -> DiscPosition {
+    let query = |nick: &str, duration: bool| {
+        let format = gst::Format::by_nick(nick);
+        if format == gst::Format::Undefined {
+            return None;
+        }
+        let value = if duration {
+            pipeline.query_duration_generic(format)
+        } else {
+            pipeline.query_position_generic(format)
+        }?;
+        u32::try_from(value.value()).ok()
+    };
+    DiscPosition {
+        title: query("title", false),
+        titles: query("title", true),
+        chapter: query("chapter", false),
+        chapters: query("chapter", true),
+    }
+}
+
+/// Jumps to the start of a title or a chapter of the current title
+#[cfg(feature = "ui")]
+pub fn seek(pipeline: &gst::Element, nick: &str, index: u32) {
+    let format = gst::Format::by_nick(nick);
+    if format == gst::Format::Undefined {
+        tracing::warn!("The disc source doesn't know {}s", nick);
+        return;
+    }
+    if let Err(err) = pipeline.seek_simple(
+        gst::SeekFlags::FLUSH,
+        gst::GenericFormattedValue::new(format, index as i64),
+    ) {
+        tracing::warn!("Failed to go to {} {}: {}", nick, index + 1, err);
+    }
+}
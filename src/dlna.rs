@@ -0,0 +1,304 @@
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hasher},
+    net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{anyhow, Context, Error};
+
+/// SSDP announcements and searches go to this multicast group
+pub const SSDP_GROUP: Ipv4Addr = Ipv4Addr::new(239, 255, 255, 250);
+pub const SSDP_PORT: u16 = 1900;
+
+/// The SERVER header of SSDP and HTTP responses, OS/version UPnP/1.0 product/version
+pub fn server_header() -> String {
+    format!(
+        "{}/1.0 UPnP/1.0 wgpu-media-player/{}",
+        std::env::consts::OS,
+        env!("CARGO_PKG_VERSION")
+    )
+}
+
+/// A random version 4 uuid, for the renderer and its event subscriptions
+pub fn new_uuid() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let mut bytes = [0u8; 16];
+    for chunk in bytes.chunks_mut(8) {
+        // every RandomState is seeded differently
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(nanos);
+        chunk.copy_from_slice(&hasher.finish().to_le_bytes());
+    }
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+/// The address of this machine that `peer` reaches, so the urls we hand out work for it
+pub fn local_address(peer: SocketAddr) -> Option<IpAddr> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+    // connecting a UDP socket sends nothing, it only picks the route
+    socket.connect(peer).ok()?;
+    socket.local_addr().ok().map(|address| address.ip())
+}
+
+/// The request or status line and the headers of an SSDP message, the names in lowercase
+pub fn parse_ssdp(message: &str) -> Option<(&str, HashMap<String, &str>)> {
+    let mut lines = message.lines();
+    let start_line = lines.next()?.trim();
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_lowercase(), value.trim()))
+        .collect();
+    Some((start_line, headers))
+}
+
+pub fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+pub fn soap_envelope(body: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\
+         <s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" \
+         s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\">\
+         <s:Body>{}</s:Body></s:Envelope>",
+        body
+    )
+}
+
+/// An action call or response of a service, the arguments in the order of its description
+pub fn soap_action(service: &str, action: &str, arguments: &[(&str, String)]) -> String {
+    let mut xml = format!("<u:{} xmlns:u=\"{}\">", action, service);
+    for (name, value) in arguments {
+        xml.push_str(&format!("<{0}>{1}</{0}>", name, escape_xml(value)));
+    }
+    xml.push_str(&format!("</u:{}>", action));
+    xml
+}
+
+/// The action and arguments of a call, or the arguments of a response, in a SOAP envelope
+pub fn parse_soap(xml: &str) -> Result<(String, HashMap<String, String>), Error> {
+    let document = roxmltree::Document::parse(xml).context("Invalid SOAP message")?;
+    let action = document
+        .descendants()
+        .find(|node| node.tag_name().name() == "Body")
+        .and_then(|body| body.children().find(|node| node.is_element()))
+        .ok_or_else(|| anyhow!("The SOAP message has no body"))?;
+    let arguments = action
+        .children()
+        .filter(|node| node.is_element())
+        .map(|node| {
+            (
+                node.tag_name().name().to_string(),
+                node.text().unwrap_or_default().to_string(),
+            )
+        })
+        .collect();
+    Ok((action.tag_name().name().to_string(), arguments))
+}
+
+/// An item or container of DIDL-Lite metadata, which describes the media of both servers and
+/// renderers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DidlObject {
+    pub id: String,
+    pub title: Option<String>,
+    /// Items are played, containers hold more objects
+    pub container: bool,
+    /// The first resource of an item
+    pub uri: Option<String>,
+}
+
+pub fn parse_didl(xml: &str) -> Result<Vec<DidlObject>, Error> {
+    let document = roxmltree::Document::parse(xml).context("Invalid DIDL-Lite metadata")?;
+    let child_text = |node: roxmltree::Node, name: &str| {
+        node.children()
+            .find(|child| child.tag_name().name() == name)
+            .and_then(|child| child.text())
+            .map(|text| text.trim().to_string())
+            .filter(|text| !text.is_empty())
+    };
+    Ok(document
+        .root_element()
+        .children()
+        .filter(|node| matches!(node.tag_name().name(), "item" | "container"))
+        .map(|node| DidlObject {
+            id: node.attribute("id").unwrap_or_default().to_string(),
+            title: child_text(node, "title"),
+            container: node.tag_name().name() == "container",
+            uri: child_text(node, "res"),
+        })
+        .collect())
+}
+
+/// DIDL-Lite of a single video item, what renderers report as the metadata of their track
+pub fn didl_item(title: &str, uri: &str) -> String {
+    format!(
+        "<DIDL-Lite xmlns=\"urn:schemas-upnp-org:metadata-1-0/DIDL-Lite/\" \
+         xmlns:dc=\"http://purl.org/dc/elements/1.1/\" \
+         xmlns:upnp=\"urn:schemas-upnp-org:metadata-1-0/upnp/\">\
+         <item id=\"0\" parentID=\"-1\" restricted=\"1\">\
+         <dc:title>{}</dc:title>\
+         <upnp:class>object.item.videoItem</upnp:class>\
+         <res protocolInfo=\"http-get:*:*:*\">{}</res>\
+         </item></DIDL-Lite>",
+        escape_xml(title),
+        escape_xml(uri)
+    )
+}
+
+/// H:MM:SS, the time format of AVTransport
+pub fn format_time(time: Duration) -> String {
+    let seconds = time.as_secs();
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// H+:MM:SS with optional fractions of a second
+pub fn parse_time(time: &str) -> Option<Duration> {
+    let mut parts = time.trim().split(':');
+    let (Some(hours), Some(minutes), Some(seconds), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return None;
+    };
+    let hours = hours.parse::<u64>().ok()?;
+    let minutes = minutes.parse::<u64>().ok()?;
+    let seconds = Duration::try_from_secs_f64(seconds.parse::<f64>().ok()?).ok()?;
+    let whole = hours
+        .checked_mul(3600)?
+        .checked_add(minutes.checked_mul(60)?)?;
+    Duration::from_secs(whole).checked_add(seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_times() {
+        assert_eq!(parse_time("0:00:00"), Some(Duration::ZERO));
+        assert_eq!(parse_time(" 1:02:03 "), Some(Duration::from_secs(3723)));
+        assert_eq!(
+            parse_time("100:00:00.5"),
+            Some(Duration::from_millis(360_000_500))
+        );
+        assert_eq!(parse_time("0:00"), None);
+        assert_eq!(parse_time("0:00:00:00"), None);
+        assert_eq!(parse_time("a:00:00"), None);
+        assert_eq!(parse_time("0:00:-1"), None);
+        assert_eq!(parse_time("0:00:NaN"), None);
+        assert_eq!(parse_time("0:00:inf"), None);
+    }
+
+    #[test]
+    fn rejects_times_out_of_range() {
+        assert_eq!(parse_time("0:0:1e300"), None);
+        assert_eq!(parse_time(&format!("{}:00:00", u64::MAX)), None);
+        assert_eq!(parse_time(&format!("0:{}:00", u64::MAX)), None);
+        assert_eq!(parse_time(&format!("{}:59:00", u64::MAX / 3600)), None);
+    }
+
+    #[test]
+    fn formats_what_it_parses() {
+        let time = Duration::from_secs(36_000 + 59 * 60 + 7);
+        assert_eq!(format_time(time), "10:59:07");
+        assert_eq!(parse_time(&format_time(time)), Some(time));
+    }
+
+    #[test]
+    fn parses_soap_actions() {
+        let body = soap_action(
+            "urn:schemas-upnp-org:service:AVTransport:1",
+            "Seek",
+            &[
+                ("InstanceID", "0".to_string()),
+                ("Target", "0:01:00".to_string()),
+            ],
+        );
+        let (action, arguments) = parse_soap(&soap_envelope(&body)).unwrap();
+        assert_eq!(action, "Seek");
+        assert_eq!(arguments.len(), 2);
+        assert_eq!(arguments["InstanceID"], "0");
+        assert_eq!(arguments["Target"], "0:01:00");
+    }
+
+    #[test]
+    fn parses_escaped_and_empty_arguments() {
+        let body = soap_action(
+            "urn:schemas-upnp-org:service:AVTransport:1",
+            "SetAVTransportURI",
+            &[
+                ("CurrentURI", "http://host/a?b=1&c=<2>".to_string()),
+                ("CurrentURIMetaData", String::new()),
+            ],
+        );
+        let (_, arguments) = parse_soap(&soap_envelope(&body)).unwrap();
+        assert_eq!(arguments["CurrentURI"], "http://host/a?b=1&c=<2>");
+        assert_eq!(arguments["CurrentURIMetaData"], "");
+    }
+
+    #[test]
+    fn rejects_malformed_soap() {
+        assert!(parse_soap("").is_err());
+        assert!(parse_soap("<s:Envelope").is_err());
+        assert!(parse_soap(&soap_envelope("")).is_err());
+    }
+
+    #[test]
+    fn parses_didl() {
+        let xml = "<DIDL-Lite xmlns=\"urn:schemas-upnp-org:metadata-1-0/DIDL-Lite/\" \
+                   xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\
+                   <container id=\"1\"><dc:title>Movies</dc:title></container>\
+                   <item id=\"2\"><dc:title> </dc:title><res> http://host/a.mp4 </res></item>\
+                   <desc id=\"3\"/>\
+                   </DIDL-Lite>";
+        assert_eq!(
+            parse_didl(xml).unwrap(),
+            vec![
+                DidlObject {
+                    id: "1".to_string(),
+                    title: Some("Movies".to_string()),
+                    container: true,
+                    uri: None,
+                },
+                DidlObject {
+                    id: "2".to_string(),
+                    title: None,
+                    container: false,
+                    uri: Some("http://host/a.mp4".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_its_own_items() {
+        let objects = parse_didl(&didl_item("A & B", "http://host/a.mp4?x=1&y=2")).unwrap();
+        assert_eq!(objects.len(), 1);
+        assert_eq!(objects[0].title.as_deref(), Some("A & B"));
+        assert_eq!(objects[0].uri.as_deref(), Some("http://host/a.mp4?x=1&y=2"));
+        assert!(parse_didl("<DIDL-Lite>").is_err());
+    }
+}
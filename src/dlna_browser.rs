@@ -0,0 +1,386 @@
+use std::{
+    collections::HashSet,
+    net::{Ipv4Addr, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Error};
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use url::Url;
+
+use crate::{
+    dlna::{self, SSDP_GROUP, SSDP_PORT},
+    playlist_file::PlaylistEntry,
+};
+
+const MEDIA_SERVER: &str = "urn:schemas-upnp-org:device:MediaServer:1";
+const CONTENT_DIRECTORY: &str = "urn:schemas-upnp-org:service:ContentDirectory:1";
+/// Servers answer a search within its MX seconds
+const SEARCH_TIME: Duration = Duration::from_secs(3);
+/// Folders are listed in pages, servers may cap the page size anyway
+const PAGE_SIZE: u32 = 200;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A DLNA media server on the network, like a NAS or Plex
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaServer {
+    pub name: String,
+    /// Of its ContentDirectory service
+    control_url: String,
+}
+
+/// A folder or a playable item on a media server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BrowseEntry {
+    Folder { id: String, title: String },
+    Item(PlaylistEntry),
+}
+
+enum BrowserEvent {
+    Found(MediaServer),
+    SearchFinished,
+    /// The entries of the folder that was opened with the same generation
+    Listed(u64, Result<Vec<BrowseEntry>, String>),
+}
+
+/// Finds media servers and lists their folders, the searches and requests run on background
+/// threads
+pub struct MediaServerBrowser {
+    servers: Vec<MediaServer>,
+    searching: bool,
+    /// Index into `servers` of the one being browsed
+    server: Option<usize>,
+    /// The folders opened on the server, id and title, the last one is listed
+    path: Vec<(String, String)>,
+    entries: Vec<BrowseEntry>,
+    loading: bool,
+    error: Option<String>,
+    /// Tells the listing of the current folder from ones that were left before they arrived
+    generation: u64,
+    sender: Sender<BrowserEvent>,
+    receiver: Receiver<BrowserEvent>,
+}
+
+impl Default for MediaServerBrowser {
+    fn default() -> Self {
+        let (sender, receiver) = unbounded();
+        Self {
+            servers: Vec::new(),
+            searching: false,
+            server: None,
+            path: Vec::new(),
+            entries: Vec::new(),
+            loading: false,
+            error: None,
+            generation: 0,
+            sender,
+            receiver,
+        }
+    }
+}
+
+impl MediaServerBrowser {
+    pub fn search(&mut self) {
+        if self.searching {
+            return;
+        }
+        self.searching = true;
+        let sender = self.sender.clone();
+        std::thread::spawn(move || {
+            if let Err(err) = discover(&sender) {
+                tracing::warn!("Failed to search for media servers: {:#}", err);
+            }
+            sender.send(BrowserEvent::SearchFinished).ok();
+        });
+    }
+
+    /// Lists a folder of the current server, "0" is the root
+    fn open(&mut self, id: String, title: String) {
+        let Some(server) = self.server.and_then(|index| self.servers.get(index)) else {
+            return;
+        };
+        self.path.push((id.clone(), title));
+        self.entries.clear();
+        self.error = None;
+        self.loading = true;
+        self.generation += 1;
+        let generation = self.generation;
+        let control_url = server.control_url.clone();
+        let sender = self.sender.clone();
+        std::thread::spawn(move || {
+            let entries = browse(&control_url, &id).map_err(|err| format!("{:#}", err));
+            sender.send(BrowserEvent::Listed(generation, entries)).ok();
+        });
+    }
+
+    fn select_server(&mut self, index: usize) {
+        self.server = Some(index);
+        self.path.clear();
+        let name = self.servers[index].name.clone();
+        self.open("0".to_string(), name);
+    }
+
+    /// Goes back to a folder of the path
+    fn go_up(&mut self, depth: usize) {
+        if let Some((id, title)) = self.path.get(depth).cloned() {
+            self.path.truncate(depth);
+            self.open(id, title);
+        }
+    }
+
+    fn receive(&mut self) {
+        for event in self.receiver.try_iter() {
+            match event {
+                BrowserEvent::Found(server) => {
+                    if !self.servers.contains(&server) {
+                        self.servers.push(server);
+                    }
+                }
+                BrowserEvent::SearchFinished => self.searching = false,
+                BrowserEvent::Listed(generation, entries) if generation == self.generation => {
+                    self.loading = false;
+                    match entries {
+                        Ok(entries) => self.entries = entries,
+                        Err(err) => self.error = Some(err),
+                    }
+                }
+                BrowserEvent::Listed(..) => {}
+            }
+        }
+    }
+
+    /// Returns the items to play, a single one or all of the folder
+    #[cfg(feature = "ui")]
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> Option<Vec<PlaylistEntry>> {
+        self.receive();
+        let mut play = None;
+
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(!self.searching, egui::Button::new("Search the network"))
+                .clicked()
+            {
+                self.search();
+            }
+            if self.searching {
+                ui.spinner();
+            }
+        });
+        if self.servers.is_empty() && !self.searching {
+            ui.weak("No media servers found yet");
+        }
+        let mut selected = None;
+        for (index, server) in self.servers.iter().enumerate() {
+            if ui
+                .selectable_label(self.server == Some(index), &server.name)
+                .clicked()
+            {
+                selected = Some(index);
+            }
+        }
+        if let Some(index) = selected {
+            self.select_server(index);
+        }
+        self.server?;
+
+        ui.separator();
+        let mut up = None;
+        ui.horizontal_wrapped(|ui| {
+            for (depth, (_, title)) in self.path.iter().enumerate() {
+                if depth > 0 {
+                    ui.label("›");
+                }
+                if ui.link(title).clicked() {
+                    up = Some(depth);
+                }
+            }
+        });
+        if let Some(depth) = up {
+            self.go_up(depth);
+        }
+        if self.loading {
+            ui.spinner();
+        }
+        if let Some(error) = &self.error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+
+        let items: Vec<PlaylistEntry> = self
+            .entries
+            .iter()
+            .filter_map(|entry| match entry {
+                BrowseEntry::Item(item) => Some(item.clone()),
+                BrowseEntry::Folder { .. } => None,
+            })
+            .collect();
+        if items.len() > 1 && ui.button("Play all").clicked() {
+            play = Some(items);
+        }
+
+        let mut opened = None;
+        egui::ScrollArea::vertical()
+            .max_height(300.0)
+            .show(ui, |ui| {
+                for entry in &self.entries {
+                    match entry {
+                        BrowseEntry::Folder { id, title } => {
+                            if ui.button(format!("🗀 {}", title)).clicked() {
+                                opened = Some((id.clone(), title.clone()));
+                            }
+                        }
+                        BrowseEntry::Item(item) => {
+                            let title = item.title.as_deref().unwrap_or(&item.uri);
+                            if ui.selectable_label(false, title).clicked() {
+                                play = Some(vec![item.clone()]);
+                            }
+                        }
+                    }
+                }
+            });
+        if let Some((id, title)) = opened {
+            self.open(id, title);
+        }
+        play
+    }
+}
+
+/// Sends an SSDP search and reports each server that answers until `SEARCH_TIME` is up
+fn discover(sender: &Sender<BrowserEvent>) -> Result<(), Error> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    socket.set_multicast_ttl_v4(2)?;
+    let message = format!(
+        "M-SEARCH * HTTP/1.1\r\nHOST: {}:{}\r\nMAN: \"ssdp:discover\"\r\nMX: {}\r\nST: {}\r\n\r\n",
+        SSDP_GROUP,
+        SSDP_PORT,
+        SEARCH_TIME.as_secs() - 1,
+        MEDIA_SERVER
+    );
+    socket.send_to(message.as_bytes(), (SSDP_GROUP, SSDP_PORT))?;
+
+    let started = Instant::now();
+    let mut locations = HashSet::new();
+    let mut buffer = [0; 4096];
+    while let Some(left) = SEARCH_TIME.checked_sub(started.elapsed()) {
+        socket.set_read_timeout(Some(left.max(Duration::from_millis(1))))?;
+        let Ok((length, _)) = socket.recv_from(&mut buffer) else {
+            break;
+        };
+        let message = String::from_utf8_lossy(&buffer[..length]);
+        let Some(location) = dlna::parse_ssdp(&message)
+            .and_then(|(_, headers)| headers.get("location").map(|location| location.to_string()))
+        else {
+            continue;
+        };
+        if !locations.insert(location.clone()) {
+            continue;
+        }
+        match describe(&location) {
+            Ok(server) => {
+                sender.send(BrowserEvent::Found(server)).ok();
+            }
+            Err(err) => tracing::debug!("Skipping the media server at {}: {:#}", location, err),
+        }
+    }
+    Ok(())
+}
+
+/// Reads the name and the ContentDirectory of a server from its device description
+fn describe(location: &str) -> Result<MediaServer, Error> {
+    let description = ureq::get(location)
+        .timeout(REQUEST_TIMEOUT)
+        .call()?
+        .into_string()?;
+    let document = roxmltree::Document::parse(&description)?;
+    let text = |node: roxmltree::Node, name: &str| {
+        node.children()
+            .find(|child| child.tag_name().name() == name)
+            .and_then(|child| child.text())
+            .map(|text| text.trim().to_string())
+    };
+    let device = document
+        .descendants()
+        .find(|node| node.tag_name().name() == "device")
+        .ok_or_else(|| anyhow!("No device in the description"))?;
+    let control_url = document
+        .descendants()
+        .filter(|node| node.tag_name().name() == "service")
+        .find(|service| {
+            text(*service, "serviceType").is_some_and(|kind| {
+                kind.starts_with("urn:schemas-upnp-org:service:ContentDirectory:")
+            })
+        })
+        .and_then(|service| text(service, "controlURL"))
+        .ok_or_else(|| anyhow!("The server has no ContentDirectory"))?;
+    // relative to URLBase in UPnP 1.0 descriptions, and to the description otherwise
+    let base = text(document.root_element(), "URLBase")
+        .filter(|base| !base.is_empty())
+        .unwrap_or_else(|| location.to_string());
+    let control_url = Url::parse(&base)?.join(&control_url)?.to_string();
+
+    Ok(MediaServer {
+        name: text(device, "friendlyName").unwrap_or_else(|| location.to_string()),
+        control_url,
+    })
+}
+
+/// Lists the folders and items of a folder, page by page
+fn browse(control_url: &str, id: &str) -> Result<Vec<BrowseEntry>, Error> {
+    let mut entries = Vec::new();
+    let mut start = 0;
+    loop {
+        let arguments = [
+            ("ObjectID", id.to_string()),
+            ("BrowseFlag", "BrowseDirectChildren".to_string()),
+            ("Filter", "*".to_string()),
+            ("StartingIndex", start.to_string()),
+            ("RequestedCount", PAGE_SIZE.to_string()),
+            ("SortCriteria", String::new()),
+        ];
+        let body = dlna::soap_envelope(&dlna::soap_action(CONTENT_DIRECTORY, "Browse", &arguments));
+        let response = ureq::post(control_url)
+            .timeout(REQUEST_TIMEOUT)
+            .set("Content-Type", "text/xml; charset=\"utf-8\"")
+            .set("SOAPAction", &format!("\"{}#Browse\"", CONTENT_DIRECTORY))
+            .send_string(&body);
+        let response = match response {
+            Ok(response) => response.into_string()?,
+            Err(ureq::Error::Status(code, _)) => {
+                return Err(anyhow!("The server couldn't list the folder ({})", code))
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let (_, outputs) = dlna::parse_soap(&response)?;
+        let result = outputs
+            .get("Result")
+            .ok_or_else(|| anyhow!("The server sent no listing"))?;
+        let returned = outputs
+            .get("NumberReturned")
+            .and_then(|number| number.parse::<u32>().ok())
+            .unwrap_or(0);
+        let total = outputs
+            .get("TotalMatches")
+            .and_then(|number| number.parse::<u32>().ok())
+            .unwrap_or(0);
+
+        for object in dlna::parse_didl(result).context("Unreadable listing")? {
+            let title = object.title.unwrap_or_else(|| object.id.clone());
+            if object.container {
+                entries.push(BrowseEntry::Folder {
+                    id: object.id,
+                    title,
+                });
+            } else if let Some(uri) = object.uri {
+                entries.push(BrowseEntry::Item(PlaylistEntry {
+                    uri,
+                    title: Some(title),
+                }));
+            }
+        }
+
+        start += returned;
+        // servers that don't know the total report 0
+        if returned == 0 || (total != 0 && start >= total) {
+            return Ok(entries);
+        }
+    }
+}
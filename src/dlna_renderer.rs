@@ -0,0 +1,1021 @@
+use std::{
+    collections::HashMap,
+    io::Read,
+    net::{Ipv4Addr, SocketAddr, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Error};
+use socket2::{Domain, Protocol, Socket, Type};
+use tiny_http::{Header, Request, Response, Server};
+
+use crate::{
+    custom_pipeline,
+    dlna::{self, SSDP_GROUP, SSDP_PORT},
+};
+
+const MEDIA_RENDERER: &str = "urn:schemas-upnp-org:device:MediaRenderer:1";
+/// How long control points keep the announcement, it is repeated well before that
+const MAX_AGE_SECS: u64 = 1800;
+const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(600);
+/// How often the state is compared with what subscribers were last told
+const EVENT_INTERVAL: Duration = Duration::from_secs(1);
+const SUBSCRIPTION_TIMEOUT_SECS: u64 = 1800;
+/// SOAP requests are a few hundred bytes, the metadata of SetAVTransportURI a few kilobytes
+const MAX_SOAP_BODY: u64 = 64 * 1024;
+/// What playbin can play from a url, control points check this before they send something
+const SINK_PROTOCOL_INFO: &[&str] = &[
+    "http-get:*:video/mp4:*",
+    "http-get:*:video/x-matroska:*",
+    "http-get:*:video/webm:*",
+    "http-get:*:video/mpeg:*",
+    "http-get:*:video/mp2t:*",
+    "http-get:*:video/quicktime:*",
+    "http-get:*:video/x-msvideo:*",
+    "http-get:*:video/x-ms-wmv:*",
+    "http-get:*:audio/mpeg:*",
+    "http-get:*:audio/mp4:*",
+    "http-get:*:audio/flac:*",
+    "http-get:*:audio/x-flac:*",
+    "http-get:*:audio/ogg:*",
+    "http-get:*:audio/wav:*",
+    "http-get:*:audio/L16:*",
+    "http-get:*:image/jpeg:*",
+    "http-get:*:image/png:*",
+    "http-get:*:image/gif:*",
+    "http-get:*:application/vnd.apple.mpegurl:*",
+];
+
+/// What a control point on the network asks the player to do
+#[derive(Debug, Clone, PartialEq)]
+pub enum RendererCommand {
+    /// Loads the uri paused, control points send Play next
+    Open {
+        uri: String,
+        title: Option<String>,
+    },
+    Play,
+    Pause,
+    /// Pauses at the start
+    Stop,
+    Seek(Duration),
+    Next,
+    Previous,
+    /// From 0 to 1
+    SetVolume(f64),
+    SetMuted(bool),
+}
+
+/// What the player that had focus last is doing, reported to control points
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RendererState {
+    pub uri: Option<String>,
+    pub title: Option<String>,
+    pub paused: bool,
+    pub position: Duration,
+    pub duration: Option<Duration>,
+    pub volume: f64,
+    pub muted: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Service {
+    AvTransport,
+    RenderingControl,
+    ConnectionManager,
+}
+
+impl Service {
+    const ALL: [Service; 3] = [
+        Service::AvTransport,
+        Service::RenderingControl,
+        Service::ConnectionManager,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Service::AvTransport => "AVTransport",
+            Service::RenderingControl => "RenderingControl",
+            Service::ConnectionManager => "ConnectionManager",
+        }
+    }
+
+    fn urn(&self) -> String {
+        format!("urn:schemas-upnp-org:service:{}:1", self.name())
+    }
+
+    fn from_path(path: &str) -> Option<(Service, &str)> {
+        let (name, rest) = path.trim_start_matches('/').split_once('/')?;
+        Service::ALL
+            .into_iter()
+            .find(|service| service.name() == name)
+            .map(|service| (service, rest))
+    }
+}
+
+struct Subscription {
+    sid: String,
+    service: Service,
+    callback: String,
+    /// Counts the events sent to the subscriber
+    seq: u32,
+    expires: Instant,
+}
+
+struct Shared {
+    state: RendererState,
+    /// Set by Stop and SetAVTransportURI until Play, the player itself only knows paused
+    stopped: bool,
+    subscriptions: Vec<Subscription>,
+}
+
+/// Announces the player as a DLNA media renderer, so phones and other control points can send
+/// it urls to play and control playback. The commands are passed to `on_command`.
+pub struct DlnaRenderer {
+    uuid: String,
+    shared: Arc<Mutex<Shared>>,
+    server: Arc<Server>,
+    shutdown: Arc<AtomicBool>,
+}
+
+impl DlnaRenderer {
+    /// Shown under `name`, `uuid` tells control points that it is the same renderer after a
+    /// restart
+    pub fn start(
+        name: &str,
+        uuid: &str,
+        on_command: impl Fn(RendererCommand) + Send + 'static,
+    ) -> Result<Self, Error> {
+        let server = Arc::new(
+            Server::http((Ipv4Addr::UNSPECIFIED, 0))
+                .map_err(|err| anyhow!("Failed to start the DLNA renderer: {}", err))?,
+        );
+        let port = server
+            .server_addr()
+            .to_ip()
+            .ok_or_else(|| anyhow!("The DLNA renderer isn't listening on a TCP port"))?
+            .port();
+        let ssdp_socket = ssdp_socket().context("Failed to listen for SSDP searches")?;
+
+        let renderer = Self {
+            uuid: uuid.to_string(),
+            shared: Arc::new(Mutex::new(Shared {
+                state: RendererState::default(),
+                stopped: true,
+                subscriptions: Vec::new(),
+            })),
+            server,
+            shutdown: Arc::new(AtomicBool::new(false)),
+        };
+
+        let description = device_description(name, uuid);
+        let server = renderer.server.clone();
+        let shared = renderer.shared.clone();
+        std::thread::spawn(move || {
+            for request in server.incoming_requests() {
+                let response = handle_request(request, &description, &shared, &on_command);
+                if let Err(err) = response {
+                    tracing::debug!("DLNA request failed: {:#}", err);
+                }
+            }
+        });
+
+        let uuid = renderer.uuid.clone();
+        let shutdown = renderer.shutdown.clone();
+        std::thread::spawn(move || answer_searches(&ssdp_socket, &uuid, port, &shutdown));
+
+        let shared = renderer.shared.clone();
+        let shutdown = renderer.shutdown.clone();
+        std::thread::spawn(move || send_events(&shared, &shutdown));
+
+        tracing::info!("DLNA renderer \"{}\" listening on port {}", name, port);
+        Ok(renderer)
+    }
+
+    pub fn set_state(&self, state: RendererState) {
+        let mut shared = lock(&self.shared);
+        // playing on from the player clears a stop of the control point
+        if !state.paused {
+            shared.stopped = false;
+        }
+        shared.state = state;
+    }
+}
+
+impl Drop for DlnaRenderer {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+        self.server.unblock();
+        let Ok(socket) = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)) else {
+            return;
+        };
+        for (nt, usn) in notification_types(&self.uuid) {
+            let message = format!(
+                "NOTIFY * HTTP/1.1\r\nHOST: {}:{}\r\nNT: {}\r\nNTS: ssdp:byebye\r\nUSN: {}\r\n\r\n",
+                SSDP_GROUP, SSDP_PORT, nt, usn
+            );
+            socket
+                .send_to(message.as_bytes(), (SSDP_GROUP, SSDP_PORT))
+                .ok();
+        }
+    }
+}
+
+/// Joins the SSDP group on port 1900, shared with other UPnP programs on the machine
+fn ssdp_socket() -> Result<UdpSocket, Error> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    socket.bind(&SocketAddr::from((Ipv4Addr::UNSPECIFIED, SSDP_PORT)).into())?;
+    let socket = UdpSocket::from(socket);
+    socket.join_multicast_v4(&SSDP_GROUP, &Ipv4Addr::UNSPECIFIED)?;
+    socket.set_multicast_ttl_v4(2)?;
+    // wakes up to announce again and to notice the shutdown
+    socket.set_read_timeout(Some(Duration::from_secs(1)))?;
+    Ok(socket)
+}
+
+/// The NT and USN of every announcement: the root device, the device itself, its type and its
+/// services
+fn notification_types(uuid: &str) -> Vec<(String, String)> {
+    let device = format!("uuid:{}", uuid);
+    let mut types = vec![
+        (
+            "upnp:rootdevice".to_string(),
+            format!("{}::upnp:rootdevice", device),
+        ),
+        (device.clone(), device.clone()),
+        (
+            MEDIA_RENDERER.to_string(),
+            format!("{}::{}", device, MEDIA_RENDERER),
+        ),
+    ];
+    for service in Service::ALL {
+        types.push((service.urn(), format!("{}::{}", device, service.urn())));
+    }
+    types
+}
+
+fn answer_searches(socket: &UdpSocket, uuid: &str, port: u16, shutdown: &AtomicBool) {
+    let location = |peer: SocketAddr| {
+        dlna::local_address(peer)
+            .map(|address| format!("http://{}:{}/description.xml", address, port))
+    };
+    let types = notification_types(uuid);
+    let server = dlna::server_header();
+    let mut announced_at: Option<Instant> = None;
+    let mut buffer = [0; 4096];
+    while !shutdown.load(Ordering::Relaxed) {
+        if announced_at.is_none_or(|at| at.elapsed() > ANNOUNCE_INTERVAL) {
+            announced_at = Some(Instant::now());
+            let group = SocketAddr::from((SSDP_GROUP, SSDP_PORT));
+            if let Some(location) = location(group) {
+                for (nt, usn) in &types {
+                    let message = format!(
+                        "NOTIFY * HTTP/1.1\r\nHOST: {}\r\nCACHE-CONTROL: max-age={}\r\n\
+                         LOCATION: {}\r\nNT: {}\r\nNTS: ssdp:alive\r\nSERVER: {}\r\nUSN: {}\r\n\r\n",
+                        group, MAX_AGE_SECS, location, nt, server, usn
+                    );
+                    socket.send_to(message.as_bytes(), group).ok();
+                }
+            }
+        }
+
+        let (length, peer) = match socket.recv_from(&mut buffer) {
+            Ok(received) => received,
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) =>
+            {
+                continue
+            }
+            Err(err) => {
+                tracing::warn!("Stopped answering SSDP searches: {}", err);
+                return;
+            }
+        };
+        let message = String::from_utf8_lossy(&buffer[..length]);
+        let Some((start_line, headers)) = dlna::parse_ssdp(&message) else {
+            continue;
+        };
+        if !start_line.starts_with("M-SEARCH") {
+            continue;
+        }
+        let Some(&target) = headers.get("st") else {
+            continue;
+        };
+        let Some(location) = location(peer) else {
+            continue;
+        };
+        for (nt, usn) in &types {
+            if target != "ssdp:all" && target != nt.as_str() {
+                continue;
+            }
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age={}\r\nEXT:\r\nLOCATION: {}\r\n\
+                 SERVER: {}\r\nST: {}\r\nUSN: {}\r\n\r\n",
+                MAX_AGE_SECS, location, server, nt, usn
+            );
+            socket.send_to(response.as_bytes(), peer).ok();
+        }
+    }
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).expect("invalid header")
+}
+
+fn xml_response(xml: String) -> Response<std::io::Cursor<Vec<u8>>> {
+    Response::from_string(xml).with_header(header("Content-Type", "text/xml; charset=\"utf-8\""))
+}
+
+fn handle_request(
+    mut request: Request,
+    description: &str,
+    shared: &Mutex<Shared>,
+    on_command: &impl Fn(RendererCommand),
+) -> Result<(), Error> {
+    let method = request.method().as_str().to_uppercase();
+    let path = request.url().to_string();
+    if method == "GET" && path == "/description.xml" {
+        request.respond(xml_response(description.to_string()))?;
+        return Ok(());
+    }
+    let Some((service, endpoint)) = Service::from_path(&path) else {
+        request.respond(Response::empty(404))?;
+        return Ok(());
+    };
+
+    match (method.as_str(), endpoint) {
+        ("GET", "scpd.xml") => request.respond(xml_response(scpd(service)))?,
+        ("POST", "control") => {
+            let mut body = String::new();
+            Read::take(request.as_reader(), MAX_SOAP_BODY).read_to_string(&mut body)?;
+            let response = match dlna::parse_soap(&body) {
+                Ok((action, arguments)) => {
+                    control(service, &action, &arguments, shared, on_command).map(|outputs| {
+                        dlna::soap_action(&service.urn(), &format!("{}Response", action), &outputs)
+                    })
+                }
+                Err(err) => Err(SoapFault::invalid_args(&format!("{:#}", err))),
+            };
+            match response {
+                Ok(body) => request.respond(xml_response(dlna::soap_envelope(&body)))?,
+                Err(fault) => {
+                    tracing::debug!("DLNA {} action failed: {}", service.name(), fault.1);
+                    request.respond(xml_response(fault.envelope()).with_status_code(500))?
+                }
+            }
+        }
+        ("SUBSCRIBE", "event") => subscribe(request, service, shared)?,
+        ("UNSUBSCRIBE", "event") => {
+            let sid = header_value(&request, "SID");
+            lock(shared)
+                .subscriptions
+                .retain(|subscription| Some(subscription.sid.as_str()) != sid.as_deref());
+            request.respond(Response::empty(200))?;
+        }
+        _ => request.respond(Response::empty(405))?,
+    }
+    Ok(())
+}
+
+fn header_value(request: &Request, name: &'static str) -> Option<String> {
+    request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv(name))
+        .map(|header| header.value.as_str().trim().to_string())
+}
+
+/// New subscriptions get the whole state right away, renewals only extend the timeout
+fn subscribe(request: Request, service: Service, shared: &Mutex<Shared>) -> Result<(), Error> {
+    let expires = Instant::now() + Duration::from_secs(SUBSCRIPTION_TIMEOUT_SECS);
+    if let Some(sid) = header_value(&request, "SID") {
+        let renewed = lock(shared)
+            .subscriptions
+            .iter_mut()
+            .find(|subscription| subscription.sid == sid)
+            .map(|subscription| subscription.expires = expires)
+            .is_some();
+        let response = if renewed {
+            subscription_response(&sid)
+        } else {
+            Response::empty(412)
+        };
+        request.respond(response)?;
+        return Ok(());
+    }
+
+    // <http://host/path>, possibly several of them
+    let callback = header_value(&request, "CALLBACK").and_then(|callback| {
+        callback
+            .trim_start_matches('<')
+            .split('>')
+            .next()
+            .filter(|url| url.starts_with("http://"))
+            .map(str::to_string)
+    });
+    let Some(callback) = callback else {
+        request.respond(Response::empty(412))?;
+        return Ok(());
+    };
+    let sid = format!("uuid:{}", dlna::new_uuid());
+    request.respond(subscription_response(&sid))?;
+
+    let body = event_body(service, &lock(shared));
+    notify(&callback, &sid, 0, &body);
+    lock(shared).subscriptions.push(Subscription {
+        sid,
+        service,
+        callback,
+        seq: 1,
+        expires,
+    });
+    Ok(())
+}
+
+fn subscription_response(sid: &str) -> Response<std::io::Empty> {
+    Response::empty(200)
+        .with_header(header("SID", sid))
+        .with_header(header(
+            "TIMEOUT",
+            &format!("Second-{}", SUBSCRIPTION_TIMEOUT_SECS),
+        ))
+        .with_header(header("SERVER", &dlna::server_header()))
+}
+
+/// Tells the subscribers of AVTransport and RenderingControl when their state changes
+fn send_events(shared: &Mutex<Shared>, shutdown: &AtomicBool) {
+    let mut sent: HashMap<Service, String> = HashMap::new();
+    while !shutdown.load(Ordering::Relaxed) {
+        std::thread::sleep(EVENT_INTERVAL);
+        let mut notifications = Vec::new();
+        {
+            let mut shared = lock(shared);
+            let now = Instant::now();
+            shared
+                .subscriptions
+                .retain(|subscription| subscription.expires > now);
+            for service in [Service::AvTransport, Service::RenderingControl] {
+                let body = event_body(service, &shared);
+                if sent.get(&service) == Some(&body) {
+                    continue;
+                }
+                for subscription in &mut shared.subscriptions {
+                    if subscription.service == service {
+                        notifications.push((
+                            subscription.callback.clone(),
+                            subscription.sid.clone(),
+                            subscription.seq,
+                            body.clone(),
+                        ));
+                        subscription.seq = subscription.seq.checked_add(1).unwrap_or(1);
+                    }
+                }
+                sent.insert(service, body);
+            }
+        }
+        for (callback, sid, seq, body) in notifications {
+            notify(&callback, &sid, seq, &body);
+        }
+    }
+}
+
+fn notify(callback: &str, sid: &str, seq: u32, body: &str) {
+    let result = ureq::request("NOTIFY", callback)
+        .timeout(Duration::from_secs(5))
+        .set("Content-Type", "text/xml; charset=\"utf-8\"")
+        .set("NT", "upnp:event")
+        .set("NTS", "upnp:propchange")
+        .set("SID", sid)
+        .set("SEQ", &seq.to_string())
+        .send_string(body);
+    if let Err(err) = result {
+        tracing::debug!("Failed to send a DLNA event to {}: {}", callback, err);
+    }
+}
+
+fn transport_state(shared: &Shared) -> &'static str {
+    match (&shared.state.uri, shared.stopped, shared.state.paused) {
+        (None, _, _) => "NO_MEDIA_PRESENT",
+        (Some(_), true, _) => "STOPPED",
+        (Some(_), false, true) => "PAUSED_PLAYBACK",
+        (Some(_), false, false) => "PLAYING",
+    }
+}
+
+fn volume_percent(state: &RendererState) -> String {
+    ((state.volume * 100.0).round() as u32).to_string()
+}
+
+fn track_metadata(state: &RendererState) -> String {
+    match (&state.uri, &state.title) {
+        (Some(uri), title) => dlna::didl_item(title.as_deref().unwrap_or(uri), uri),
+        (None, _) => String::new(),
+    }
+}
+
+/// A propertyset with the LastChange of the service, or all variables of ConnectionManager
+fn event_body(service: Service, shared: &Shared) -> String {
+    let state = &shared.state;
+    let property = |name: &str, value: &str| {
+        format!(
+            "<e:property><{0}>{1}</{0}></e:property>",
+            name,
+            dlna::escape_xml(value)
+        )
+    };
+    let properties = match service {
+        Service::AvTransport => {
+            let uri = state.uri.clone().unwrap_or_default();
+            let duration = dlna::format_time(state.duration.unwrap_or_default());
+            let metadata = track_metadata(state);
+            let variables = [
+                ("TransportState", transport_state(shared)),
+                ("TransportStatus", "OK"),
+                ("TransportPlaySpeed", "1"),
+                ("NumberOfTracks", if uri.is_empty() { "0" } else { "1" }),
+                ("CurrentTrack", if uri.is_empty() { "0" } else { "1" }),
+                ("AVTransportURI", uri.as_str()),
+                ("AVTransportURIMetaData", metadata.as_str()),
+                ("CurrentTrackURI", uri.as_str()),
+                ("CurrentTrackMetaData", metadata.as_str()),
+                ("CurrentTrackDuration", duration.as_str()),
+                ("CurrentMediaDuration", duration.as_str()),
+                (
+                    "CurrentTransportActions",
+                    "Play,Pause,Stop,Seek,Next,Previous",
+                ),
+            ];
+            let last_change = last_change("urn:schemas-upnp-org:metadata-1-0/AVT/", &variables);
+            property("LastChange", &last_change)
+        }
+        Service::RenderingControl => {
+            let volume = volume_percent(state);
+            let variables = [
+                ("Volume", volume.as_str()),
+                ("Mute", if state.muted { "1" } else { "0" }),
+            ];
+            let last_change = last_change("urn:schemas-upnp-org:metadata-1-0/RCS/", &variables);
+            property("LastChange", &last_change)
+        }
+        Service::ConnectionManager => [
+            property("SourceProtocolInfo", ""),
+            property("SinkProtocolInfo", &SINK_PROTOCOL_INFO.join(",")),
+            property("CurrentConnectionIDs", "0"),
+        ]
+        .concat(),
+    };
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\
+         <e:propertyset xmlns:e=\"urn:schemas-upnp-org:event-1-0\">{}</e:propertyset>",
+        properties
+    )
+}
+
+fn last_change(namespace: &str, variables: &[(&str, &str)]) -> String {
+    let mut xml = format!("<Event xmlns=\"{}\"><InstanceID val=\"0\">", namespace);
+    for (name, value) in variables {
+        let channel = if namespace.ends_with("/RCS/") {
+            " channel=\"Master\""
+        } else {
+            ""
+        };
+        xml.push_str(&format!(
+            "<{}{} val=\"{}\"/>",
+            name,
+            channel,
+            dlna::escape_xml(value)
+        ));
+    }
+    xml.push_str("</InstanceID></Event>");
+    xml
+}
+
+/// The UPnP error code and description of a failed action
+struct SoapFault(u32, String);
+
+impl SoapFault {
+    fn invalid_action() -> Self {
+        Self(401, "Invalid Action".to_string())
+    }
+
+    fn invalid_args(description: &str) -> Self {
+        Self(402, format!("Invalid Args: {}", description))
+    }
+
+    fn envelope(&self) -> String {
+        dlna::soap_envelope(&format!(
+            "<s:Fault><faultcode>s:Client</faultcode><faultstring>UPnPError</faultstring>\
+             <detail><UPnPError xmlns=\"urn:schemas-upnp-org:control-1-0\">\
+             <errorCode>{}</errorCode><errorDescription>{}</errorDescription>\
+             </UPnPError></detail></s:Fault>",
+            self.0,
+            dlna::escape_xml(&self.1)
+        ))
+    }
+}
+
+/// Every field of `Shared` is valid on its own, so a panic while it was locked leaves nothing
+/// half done that would be worth taking the UI thread down for
+fn lock(shared: &Mutex<Shared>) -> MutexGuard<'_, Shared> {
+    shared.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Runs an action and returns its output arguments, there is a single instance and channel
+fn control(
+    service: Service,
+    action: &str,
+    arguments: &HashMap<String, String>,
+    shared: &Mutex<Shared>,
+    on_command: &impl Fn(RendererCommand),
+) -> Result<Vec<(&'static str, String)>, SoapFault> {
+    let argument = |name: &str| {
+        arguments
+            .get(name)
+            .map(String::as_str)
+            .ok_or_else(|| SoapFault::invalid_args(name))
+    };
+    let mut shared = lock(shared);
+    let state = shared.state.clone();
+    let uri = state.uri.clone().unwrap_or_default();
+    let duration = dlna::format_time(state.duration.unwrap_or_default());
+    let position = dlna::format_time(state.position);
+
+    let outputs = match (service, action) {
+        (Service::AvTransport, "SetAVTransportURI") => {
+            let uri = argument("CurrentURI")?.trim().to_string();
+            // only the user plays pipelines, see `PipelineDescription`
+            if custom_pipeline::is_pipeline_uri(&uri) {
+                return Err(SoapFault::invalid_args("CurrentURI"));
+            }
+            let title = arguments
+                .get("CurrentURIMetaData")
+                .filter(|metadata| !metadata.trim().is_empty())
+                .and_then(|metadata| dlna::parse_didl(metadata).ok())
+                .and_then(|objects| objects.into_iter().next())
+                .and_then(|object| object.title);
+            on_command(RendererCommand::Open { uri, title });
+            shared.stopped = true;
+            vec![]
+        }
+        (Service::AvTransport, "Play") => {
+            on_command(RendererCommand::Play);
+            shared.stopped = false;
+            vec![]
+        }
+        (Service::AvTransport, "Pause") => {
+            on_command(RendererCommand::Pause);
+            vec![]
+        }
+        (Service::AvTransport, "Stop") => {
+            on_command(RendererCommand::Stop);
+            shared.stopped = true;
+            vec![]
+        }
+        (Service::AvTransport, "Seek") => {
+            let unit = argument("Unit")?;
+            if unit != "REL_TIME" && unit != "ABS_TIME" {
+                return Err(SoapFault(710, "Seek mode not supported".to_string()));
+            }
+            let target = dlna::parse_time(argument("Target")?)
+                .ok_or_else(|| SoapFault(711, "Illegal seek target".to_string()))?;
+            on_command(RendererCommand::Seek(target));
+            vec![]
+        }
+        (Service::AvTransport, "Next") => {
+            on_command(RendererCommand::Next);
+            vec![]
+        }
+        (Service::AvTransport, "Previous") => {
+            on_command(RendererCommand::Previous);
+            vec![]
+        }
+        (Service::AvTransport, "GetTransportInfo") => vec![
+            (
+                "CurrentTransportState",
+                transport_state(&shared).to_string(),
+            ),
+            ("CurrentTransportStatus", "OK".to_string()),
+            ("CurrentSpeed", "1".to_string()),
+        ],
+        (Service::AvTransport, "GetPositionInfo") => vec![
+            ("Track", if uri.is_empty() { "0" } else { "1" }.to_string()),
+            ("TrackDuration", duration),
+            ("TrackMetaData", track_metadata(&state)),
+            ("TrackURI", uri),
+            ("RelTime", position.clone()),
+            ("AbsTime", position),
+            ("RelCount", i32::MAX.to_string()),
+            ("AbsCount", i32::MAX.to_string()),
+        ],
+        (Service::AvTransport, "GetMediaInfo") => vec![
+            (
+                "NrTracks",
+                if uri.is_empty() { "0" } else { "1" }.to_string(),
+            ),
+            ("MediaDuration", duration),
+            ("CurrentURI", uri),
+            ("CurrentURIMetaData", track_metadata(&state)),
+            ("NextURI", String::new()),
+            ("NextURIMetaData", String::new()),
+            ("PlayMedium", "NETWORK".to_string()),
+            ("RecordMedium", "NOT_IMPLEMENTED".to_string()),
+            ("WriteStatus", "NOT_IMPLEMENTED".to_string()),
+        ],
+        (Service::AvTransport, "GetTransportSettings") => vec![
+            ("PlayMode", "NORMAL".to_string()),
+            ("RecQualityMode", "NOT_IMPLEMENTED".to_string()),
+        ],
+        (Service::AvTransport, "GetDeviceCapabilities") => vec![
+            ("PlayMedia", "NETWORK".to_string()),
+            ("RecMedia", "NOT_IMPLEMENTED".to_string()),
+            ("RecQualityModes", "NOT_IMPLEMENTED".to_string()),
+        ],
+        (Service::AvTransport, "GetCurrentTransportActions") => {
+            vec![("Actions", "Play,Pause,Stop,Seek,Next,Previous".to_string())]
+        }
+        (Service::RenderingControl, "GetVolume") => {
+            vec![("CurrentVolume", volume_percent(&state))]
+        }
+        (Service::RenderingControl, "SetVolume") => {
+            let volume = argument("DesiredVolume")?
+                .parse::<u32>()
+                .map_err(|_| SoapFault::invalid_args("DesiredVolume"))?;
+            on_command(RendererCommand::SetVolume(volume.min(100) as f64 / 100.0));
+            vec![]
+        }
+        (Service::RenderingControl, "GetMute") => vec![(
+            "CurrentMute",
+            if state.muted { "1" } else { "0" }.to_string(),
+        )],
+        (Service::RenderingControl, "SetMute") => {
+            let muted = matches!(argument("DesiredMute")?, "1" | "true" | "yes");
+            on_command(RendererCommand::SetMuted(muted));
+            vec![]
+        }
+        (Service::ConnectionManager, "GetProtocolInfo") => vec![
+            ("Source", String::new()),
+            ("Sink", SINK_PROTOCOL_INFO.join(",")),
+        ],
+        (Service::ConnectionManager, "GetCurrentConnectionIDs") => {
+            vec![("ConnectionIDs", "0".to_string())]
+        }
+        (Service::ConnectionManager, "GetCurrentConnectionInfo") => vec![
+            ("RcsID", "0".to_string()),
+            ("AVTransportID", "0".to_string()),
+            ("ProtocolInfo", String::new()),
+            ("PeerConnectionManager", String::new()),
+            ("PeerConnectionID", "-1".to_string()),
+            ("Direction", "Input".to_string()),
+            ("Status", "OK".to_string()),
+        ],
+        _ => return Err(SoapFault::invalid_action()),
+    };
+    Ok(outputs)
+}
+
+fn device_description(name: &str, uuid: &str) -> String {
+    let services: String = Service::ALL
+        .iter()
+        .map(|service| {
+            format!(
+                "<service><serviceType>{0}</serviceType>\
+                 <serviceId>urn:upnp-org:serviceId:{1}</serviceId>\
+                 <SCPDURL>/{1}/scpd.xml</SCPDURL>\
+                 <controlURL>/{1}/control</controlURL>\
+                 <eventSubURL>/{1}/event</eventSubURL></service>",
+                service.urn(),
+                service.name()
+            )
+        })
+        .collect();
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\
+         <root xmlns=\"urn:schemas-upnp-org:device-1-0\" \
+         xmlns:dlna=\"urn:schemas-dlna-org:device-1-0\">\
+         <specVersion><major>1</major><minor>0</minor></specVersion>\
+         <device><deviceType>{}</deviceType>\
+         <friendlyName>{}</friendlyName>\
+         <manufacturer>wgpu-media-player</manufacturer>\
+         <modelName>wgpu-media-player</modelName>\
+         <modelNumber>{}</modelNumber>\
+         <UDN>uuid:{}</UDN>\
+         <dlna:X_DLNADOC>DMR-1.50</dlna:X_DLNADOC>\
+         <serviceList>{}</serviceList></device></root>",
+        MEDIA_RENDERER,
+        dlna::escape_xml(name),
+        env!("CARGO_PKG_VERSION"),
+        uuid,
+        services
+    )
+}
+
+/// Arguments of an action: name, whether it is an output, related state variable
+type Argument = (&'static str, bool, &'static str);
+
+/// The actions and state variables of a service, which control points read before calling it
+fn scpd(service: Service) -> String {
+    const INSTANCE: Argument = ("InstanceID", false, "A_ARG_TYPE_InstanceID");
+    const CHANNEL: Argument = ("Channel", false, "A_ARG_TYPE_Channel");
+    let actions: &[(&str, &[Argument])] = match service {
+        Service::AvTransport => &[
+            (
+                "SetAVTransportURI",
+                &[
+                    INSTANCE,
+                    ("CurrentURI", false, "AVTransportURI"),
+                    ("CurrentURIMetaData", false, "AVTransportURIMetaData"),
+                ],
+            ),
+            ("Play", &[INSTANCE, ("Speed", false, "TransportPlaySpeed")]),
+            ("Pause", &[INSTANCE]),
+            ("Stop", &[INSTANCE]),
+            (
+                "Seek",
+                &[
+                    INSTANCE,
+                    ("Unit", false, "A_ARG_TYPE_SeekMode"),
+                    ("Target", false, "A_ARG_TYPE_SeekTarget"),
+                ],
+            ),
+            ("Next", &[INSTANCE]),
+            ("Previous", &[INSTANCE]),
+            (
+                "GetTransportInfo",
+                &[
+                    INSTANCE,
+                    ("CurrentTransportState", true, "TransportState"),
+                    ("CurrentTransportStatus", true, "TransportStatus"),
+                    ("CurrentSpeed", true, "TransportPlaySpeed"),
+                ],
+            ),
+            (
+                "GetPositionInfo",
+                &[
+                    INSTANCE,
+                    ("Track", true, "CurrentTrack"),
+                    ("TrackDuration", true, "CurrentTrackDuration"),
+                    ("TrackMetaData", true, "CurrentTrackMetaData"),
+                    ("TrackURI", true, "CurrentTrackURI"),
+                    ("RelTime", true, "RelativeTimePosition"),
+                    ("AbsTime", true, "AbsoluteTimePosition"),
+                    ("RelCount", true, "RelativeCounterPosition"),
+                    ("AbsCount", true, "AbsoluteCounterPosition"),
+                ],
+            ),
+            (
+                "GetMediaInfo",
+                &[
+                    INSTANCE,
+                    ("NrTracks", true, "NumberOfTracks"),
+                    ("MediaDuration", true, "CurrentMediaDuration"),
+                    ("CurrentURI", true, "AVTransportURI"),
+                    ("CurrentURIMetaData", true, "AVTransportURIMetaData"),
+                    ("NextURI", true, "NextAVTransportURI"),
+                    ("NextURIMetaData", true, "NextAVTransportURIMetaData"),
+                    ("PlayMedium", true, "PlaybackStorageMedium"),
+                    ("RecordMedium", true, "RecordStorageMedium"),
+                    ("WriteStatus", true, "RecordMediumWriteStatus"),
+                ],
+            ),
+            (
+                "GetTransportSettings",
+                &[
+                    INSTANCE,
+                    ("PlayMode", true, "CurrentPlayMode"),
+                    ("RecQualityMode", true, "CurrentRecordQualityMode"),
+                ],
+            ),
+            (
+                "GetDeviceCapabilities",
+                &[
+                    INSTANCE,
+                    ("PlayMedia", true, "PossiblePlaybackStorageMedia"),
+                    ("RecMedia", true, "PossibleRecordStorageMedia"),
+                    ("RecQualityModes", true, "PossibleRecordQualityModes"),
+                ],
+            ),
+            (
+                "GetCurrentTransportActions",
+                &[INSTANCE, ("Actions", true, "CurrentTransportActions")],
+            ),
+        ],
+        Service::RenderingControl => &[
+            (
+                "GetVolume",
+                &[INSTANCE, CHANNEL, ("CurrentVolume", true, "Volume")],
+            ),
+            (
+                "SetVolume",
+                &[INSTANCE, CHANNEL, ("DesiredVolume", false, "Volume")],
+            ),
+            (
+                "GetMute",
+                &[INSTANCE, CHANNEL, ("CurrentMute", true, "Mute")],
+            ),
+            (
+                "SetMute",
+                &[INSTANCE, CHANNEL, ("DesiredMute", false, "Mute")],
+            ),
+        ],
+        Service::ConnectionManager => &[
+            (
+                "GetProtocolInfo",
+                &[
+                    ("Source", true, "SourceProtocolInfo"),
+                    ("Sink", true, "SinkProtocolInfo"),
+                ],
+            ),
+            (
+                "GetCurrentConnectionIDs",
+                &[("ConnectionIDs", true, "CurrentConnectionIDs")],
+            ),
+            (
+                "GetCurrentConnectionInfo",
+                &[
+                    ("ConnectionID", false, "A_ARG_TYPE_ConnectionID"),
+                    ("RcsID", true, "A_ARG_TYPE_RcsID"),
+                    ("AVTransportID", true, "A_ARG_TYPE_AVTransportID"),
+                    ("ProtocolInfo", true, "A_ARG_TYPE_ProtocolInfo"),
+                    (
+                        "PeerConnectionManager",
+                        true,
+                        "A_ARG_TYPE_ConnectionManager",
+                    ),
+                    ("PeerConnectionID", true, "A_ARG_TYPE_ConnectionID"),
+                    ("Direction", true, "A_ARG_TYPE_Direction"),
+                    ("Status", true, "A_ARG_TYPE_ConnectionStatus"),
+                ],
+            ),
+        ],
+    };
+
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\
+         <scpd xmlns=\"urn:schemas-upnp-org:service-1-0\">\
+         <specVersion><major>1</major><minor>0</minor></specVersion><actionList>",
+    );
+    // every variable an argument relates to, typed by its name
+    let mut variables = Vec::new();
+    for (action, arguments) in actions {
+        xml.push_str(&format!("<action><name>{}</name><argumentList>", action));
+        for (name, output, variable) in arguments.iter() {
+            xml.push_str(&format!(
+                "<argument><name>{}</name><direction>{}</direction>\
+                 <relatedStateVariable>{}</relatedStateVariable></argument>",
+                name,
+                if *output { "out" } else { "in" },
+                variable
+            ));
+            if !variables.contains(variable) {
+                variables.push(*variable);
+            }
+        }
+        xml.push_str("</argumentList></action>");
+    }
+    xml.push_str("</actionList><serviceStateTable>");
+    if service != Service::ConnectionManager {
+        xml.push_str(
+            "<stateVariable sendEvents=\"yes\"><name>LastChange</name>\
+             <dataType>string</dataType></stateVariable>",
+        );
+    }
+    for variable in variables {
+        let data_type = match variable {
+            "A_ARG_TYPE_InstanceID" | "CurrentTrack" | "NumberOfTracks" => "ui4",
+            "RelativeCounterPosition"
+            | "AbsoluteCounterPosition"
+            | "A_ARG_TYPE_ConnectionID"
+            | "A_ARG_TYPE_RcsID"
+            | "A_ARG_TYPE_AVTransportID" => "i4",
+            "Volume" => "ui2",
+            "Mute" => "boolean",
+            _ => "string",
+        };
+        // ConnectionManager has no LastChange, its variables are evented themselves
+        let send_events = matches!(
+            variable,
+            "SourceProtocolInfo" | "SinkProtocolInfo" | "CurrentConnectionIDs"
+        );
+        xml.push_str(&format!(
+            "<stateVariable sendEvents=\"{}\"><name>{}</name><dataType>{}</dataType>{}</stateVariable>",
+            if send_events { "yes" } else { "no" },
+            variable,
+            data_type,
+            if variable == "Volume" {
+                "<allowedValueRange><minimum>0</minimum><maximum>100</maximum><step>1</step></allowedValueRange>"
+            } else {
+                ""
+            }
+        ));
+    }
+    xml.push_str("</serviceStateTable></scpd>");
+    xml
+}
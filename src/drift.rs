@@ -0,0 +1,197 @@
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+use ringbuf::HeapConsumer;
+
+/// How far the audio may be sped up or slowed down, 0.1% is well below an audible pitch change
+const MAX_CORRECTION: f64 = 0.001;
+/// Seconds of pipeline clock time to measure before a correction, shorter windows are
+/// dominated by the jitter of the audio callbacks
+const MIN_MEASURE_SECONDS: f64 = 10.0;
+/// A bigger change of the buffer level between two updates is a seek or an underrun, not drift
+const MAX_LEVEL_JUMP_SECONDS: f64 = 0.1;
+
+/// Shared between the audio callback, which counts the frames the device consumed, and the
+/// decoder, which compares that against the pipeline clock and sets the resampling step
+pub struct DriftCompensation {
+    frames_played: AtomicU64,
+    /// Frames left in the ring buffer after the last audio callback
+    buffered_frames: AtomicU64,
+    /// f32 bits of the number of input frames consumed per output frame
+    step: AtomicU32,
+}
+
+impl Default for DriftCompensation {
+    fn default() -> Self {
+        Self {
+            frames_played: AtomicU64::new(0),
+            buffered_frames: AtomicU64::new(0),
+            step: AtomicU32::new(1.0f32.to_bits()),
+        }
+    }
+}
+
+impl DriftCompensation {
+    pub fn frames_played(&self) -> u64 {
+        self.frames_played.load(Ordering::Relaxed)
+    }
+
+    pub fn buffered_frames(&self) -> u64 {
+        self.buffered_frames.load(Ordering::Relaxed)
+    }
+
+    fn step(&self) -> f64 {
+        f32::from_bits(self.step.load(Ordering::Relaxed)) as f64
+    }
+
+    fn set_step(&self, step: f64) {
+        self.step.store((step as f32).to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// Keeps the ring buffer level stable by following its trend with the resampling step, and
+/// measures the real rate of the audio device against the pipeline clock for the statistics
+pub struct DriftEstimator {
+    nominal_rate: f64,
+    /// Pipeline clock time in seconds and the frames played at that time
+    start: Option<(f64, u64)>,
+    /// Pipeline clock time in seconds and the buffered frames, since the last correction
+    fill_levels: Vec<(f64, f64)>,
+}
+
+impl DriftEstimator {
+    pub fn new(sample_rate: i32) -> Self {
+        Self {
+            nominal_rate: sample_rate as f64,
+            start: None,
+            fill_levels: Vec::new(),
+        }
+    }
+
+    /// Updates the resampling step, returns the measured drift in parts per million
+    pub fn update(&mut self, clock_seconds: f64, compensation: &DriftCompensation) -> Option<f64> {
+        self.follow_fill_level(clock_seconds, compensation);
+
+        let frames_played = compensation.frames_played();
+        let Some((start_seconds, start_frames)) = self.start else {
+            // wait for the stream to actually run before starting to measure
+            if frames_played > 0 {
+                self.start = Some((clock_seconds, frames_played));
+            }
+            return None;
+        };
+
+        let elapsed = clock_seconds - start_seconds;
+        if elapsed < MIN_MEASURE_SECONDS {
+            return None;
+        }
+
+        let device_rate = (frames_played - start_frames) as f64 / elapsed;
+        Some((device_rate / self.nominal_rate - 1.0) * 1_000_000.0)
+    }
+
+    /// Adds the current buffer level and corrects the step once there is a long enough trend
+    fn follow_fill_level(&mut self, clock_seconds: f64, compensation: &DriftCompensation) {
+        let buffered = compensation.buffered_frames() as f64;
+        let interrupted = self
+            .fill_levels
+            .last()
+            .is_some_and(|&(last_seconds, last)| {
+                // paused, or a seek flushed the pipeline
+                clock_seconds <= last_seconds
+                    || (buffered - last).abs() > self.nominal_rate * MAX_LEVEL_JUMP_SECONDS
+            });
+        if interrupted || buffered == 0.0 {
+            self.fill_levels.clear();
+        }
+        if buffered == 0.0 {
+            return;
+        }
+
+        self.fill_levels.push((clock_seconds, buffered));
+        let start_seconds = self.fill_levels[0].0;
+        if clock_seconds - start_seconds < MIN_MEASURE_SECONDS {
+            return;
+        }
+
+        // a filling buffer means the device runs slow, so it has to consume more input frames
+        // per output frame, a draining one the opposite
+        let slope = fill_slope(&self.fill_levels);
+        let step = (compensation.step() + slope / self.nominal_rate)
+            .clamp(1.0 - MAX_CORRECTION, 1.0 + MAX_CORRECTION);
+        compensation.set_step(step);
+        self.fill_levels.clear();
+    }
+}
+
+/// Least squares slope of the buffer level in frames per second
+fn fill_slope(levels: &[(f64, f64)]) -> f64 {
+    let count = levels.len() as f64;
+    let mean_seconds = levels.iter().map(|(seconds, _)| seconds).sum::<f64>() / count;
+    let mean_frames = levels.iter().map(|(_, frames)| frames).sum::<f64>() / count;
+    let (covariance, variance) =
+        levels
+            .iter()
+            .fold((0.0, 0.0), |(covariance, variance), (seconds, frames)| {
+                let dx = seconds - mean_seconds;
+                (covariance + dx * (frames - mean_frames), variance + dx * dx)
+            });
+    if variance > 0.0 {
+        covariance / variance
+    } else {
+        0.0
+    }
+}
+
+/// Linear interpolating resampler that runs in the audio callback
+pub struct DriftResampler {
+    channels: usize,
+    /// Position between the previous and the current frame
+    position: f64,
+    previous: Vec<f32>,
+    current: Vec<f32>,
+}
+
+impl DriftResampler {
+    pub fn new(channels: usize) -> Self {
+        Self {
+            channels,
+            position: 0.0,
+            previous: vec![0.0; channels],
+            current: vec![0.0; channels],
+        }
+    }
+
+    pub fn fill(
+        &mut self,
+        output: &mut [f32],
+        consumer: &mut HeapConsumer<f32>,
+        compensation: &DriftCompensation,
+    ) {
+        let step = compensation.step();
+
+        for frame in output.chunks_exact_mut(self.channels) {
+            while self.position >= 1.0 {
+                std::mem::swap(&mut self.previous, &mut self.current);
+                // play silence when the buffer ran dry
+                let read = consumer.pop_slice(&mut self.current);
+                self.current[read..].fill(0.0);
+                self.position -= 1.0;
+            }
+
+            let position = self.position as f32;
+            for ((sample, previous), current) in
+                frame.iter_mut().zip(&self.previous).zip(&self.current)
+            {
+                *sample = previous + (current - previous) * position;
+            }
+            self.position += step;
+        }
+
+        compensation
+            .frames_played
+            .fetch_add((output.len() / self.channels) as u64, Ordering::Relaxed);
+        compensation
+            .buffered_frames
+            .store((consumer.len() / self.channels) as u64, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,159 @@
+use std::{fmt, path::PathBuf, sync::Arc};
+
+use gst::{glib, prelude::*};
+
+/// Everything that can go wrong while playing, returned by the decoder and the renderer and
+/// reported to the ui with `PlayerEvent::Error`
+#[derive(Debug, Clone)]
+pub enum PlayerError {
+    /// gstreamer or the pipeline couldn't be set up
+    Init(String),
+    /// Setting up or changing the pipeline failed, shared so the error stays cloneable
+    Pipeline(Arc<anyhow::Error>),
+    /// The pipeline didn't change its state, the details are posted on the bus where the decode
+    /// errors are picked up
+    StateChange(gst::StateChangeError),
+    /// An element of the pipeline failed while playing, e.g. for a missing file or a corrupt
+    /// stream
+    Decode {
+        element: Option<String>,
+        message: String,
+    },
+    /// There is no usable audio output device
+    AudioDevice(String),
+    /// The gpu can't display the video
+    Gpu(String),
+    /// The media, or one of its streams, can't be played by this build, e.g. because a plugin
+    /// is missing
+    UnsupportedFormat(String),
+    /// A server wants a login or refused the one that was sent
+    Unauthorized(String),
+    /// A user shader doesn't compile, the previous version of it keeps running
+    Shader { path: PathBuf, message: String },
+}
+
+impl PlayerError {
+    /// Sorts an error posted on the bus into decoding problems and missing plugins
+    pub fn from_bus(err: &gst::message::Error) -> Self {
+        let error = err.error();
+        let unsupported = error.matches(gst::CoreError::MissingPlugin)
+            || error.matches(gst::StreamError::CodecNotFound)
+            || error.matches(gst::StreamError::TypeNotFound)
+            || error.matches(gst::StreamError::WrongType);
+        if unsupported {
+            PlayerError::UnsupportedFormat(error.to_string())
+        } else if error.matches(gst::ResourceError::NotAuthorized) {
+            PlayerError::Unauthorized(error.to_string())
+        } else {
+            PlayerError::Decode {
+                element: err.src().map(|src| src.name().to_string()),
+                message: error.to_string(),
+            }
+        }
+    }
+}
+
+/// Whether reconnecting can fix an error posted on the bus: timeouts, dropped connections and
+/// server errors. A missing resource, a host name that doesn't resolve or a refused login fail
+/// the same way again.
+pub fn is_transient(err: &gst::message::Error) -> bool {
+    let error = err.error();
+    if let Some(status) = err.debug().as_deref().and_then(http_status) {
+        // timed out, too many requests or a server error
+        return status == 408 || status == 429 || status >= 500;
+    }
+    // souphttpsrc and rtspsrc post NotFound for a 404 and for a name that doesn't resolve
+    error.matches(gst::ResourceError::OpenRead)
+        || error.matches(gst::ResourceError::Read)
+        || error.matches(gst::ResourceError::Busy)
+        || error.matches(gst::ResourceError::Failed)
+        || error.matches(gst::StreamError::Failed)
+}
+
+/// The status code souphttpsrc puts into the debug text of a failed request, as in
+/// "Gone (410), URL: https://..."
+fn http_status(debug: &str) -> Option<u16> {
+    let (response, _) = debug.split_once("), URL: ")?;
+    let (_, status) = response.rsplit_once('(')?;
+    status.parse().ok()
+}
+
+impl fmt::Display for PlayerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlayerError::Init(message) => write!(f, "Failed to set up playback: {}", message),
+            PlayerError::Pipeline(err) => write!(f, "Failed to set up playback: {}", err),
+            PlayerError::StateChange(_) => write!(f, "The pipeline failed to change its state"),
+            PlayerError::Decode {
+                element: Some(element),
+                message,
+            } => write!(f, "{} ({})", message, element),
+            PlayerError::Decode {
+                element: None,
+                message,
+            } => write!(f, "{}", message),
+            PlayerError::AudioDevice(message) => write!(f, "Audio output failed: {}", message),
+            PlayerError::Gpu(message) => write!(f, "Can't display the video: {}", message),
+            PlayerError::UnsupportedFormat(message) => write!(f, "Unsupported media: {}", message),
+            PlayerError::Unauthorized(message) => write!(f, "Not authorized: {}", message),
+            PlayerError::Shader { path, message } => {
+                write!(f, "Shader {} failed: {}", path.display(), message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PlayerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PlayerError::Pipeline(err) => Some(err.as_ref().as_ref()),
+            PlayerError::StateChange(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<glib::Error> for PlayerError {
+    fn from(err: glib::Error) -> Self {
+        PlayerError::Init(err.to_string())
+    }
+}
+
+impl From<glib::BoolError> for PlayerError {
+    fn from(err: glib::BoolError) -> Self {
+        PlayerError::Init(err.to_string())
+    }
+}
+
+impl From<gst::StateChangeError> for PlayerError {
+    fn from(err: gst::StateChangeError) -> Self {
+        PlayerError::StateChange(err)
+    }
+}
+
+/// A `PlayerError` that went through anyhow keeps its variant
+impl From<anyhow::Error> for PlayerError {
+    fn from(err: anyhow::Error) -> Self {
+        err.downcast()
+            .unwrap_or_else(|err| PlayerError::Pipeline(Arc::new(err)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn http_status_from_debug() {
+        assert_eq!(
+            http_status("Gone (410), URL: https://example.com/live.m3u8, Redirect to: (NULL)"),
+            Some(410)
+        );
+        assert_eq!(
+            http_status("Service Unavailable (503), URL: http://example.com/a (b).ts"),
+            Some(503)
+        );
+        assert_eq!(http_status("Could not resolve server name."), None);
+        assert_eq!(http_status("Unknown (abc), URL: http://example.com"), None);
+    }
+}
@@ -0,0 +1,89 @@
+use std::{path::Path, time::Duration};
+
+use anyhow::{Context, Error};
+use gst::prelude::*;
+
+use crate::{
+    config::{FrameExportSettings, FrameFormat},
+    jobs::{self, JobContext},
+};
+
+/// Writes every frame of `uri` between `start` and `end` to `folder`, at the size of the video,
+/// as frame-000001.png and so on
+pub fn export(
+    uri: &str,
+    start: Duration,
+    end: Duration,
+    folder: &Path,
+    settings: FrameExportSettings,
+    context: &JobContext,
+) -> Result<(), Error> {
+    gst::init()?;
+    std::fs::create_dir_all(folder)
+        .with_context(|| format!("Failed to create {}", folder.display()))?;
+
+    let pipeline = gst::Pipeline::new(Some("frame-export"));
+    let decodebin = gst::ElementFactory::make("uridecodebin")
+        .property("uri", uri)
+        .build()?;
+    pipeline.add(&decodebin)?;
+
+    // multifilesink formats the location like printf
+    let location = Path::new(&folder.to_string_lossy().replace('%', "%%"))
+        .join(format!("frame-%06d.{}", settings.format.extension()))
+        .to_string_lossy()
+        .into_owned();
+    let encoder = match settings.format {
+        FrameFormat::Png => "pngenc".to_string(),
+        FrameFormat::Jpeg => format!("jpegenc quality={}", settings.jpeg_quality.min(100)),
+    };
+    let description = format!(
+        "queue ! videoconvert ! {} ! multifilesink index=1 post-messages=false",
+        encoder
+    );
+
+    let pipeline_weak = pipeline.downgrade();
+    decodebin.connect_pad_added(move |decodebin, pad| {
+        let Some(pipeline) = pipeline_weak.upgrade() else {
+            return;
+        };
+        let caps = pad.current_caps().unwrap_or_else(|| pad.query_caps(None));
+        let is_video = caps
+            .structure(0)
+            .is_some_and(|structure| structure.name() == "video/x-raw");
+        // only the first video stream is exported
+        let has_video = pipeline.by_name("frames").is_some();
+        if !is_video || has_video {
+            return jobs::discard(&pipeline, pad);
+        }
+        let linked = gst::parse_bin_from_description(&description, true)
+            .map_err(Error::from)
+            .and_then(|branch| {
+                branch.set_property("name", "frames");
+                let sink = branch
+                    .iterate_sinks()
+                    .next()
+                    .ok()
+                    .flatten()
+                    .context("The branch has no sink")?;
+                sink.set_property("location", &location);
+                pipeline.add(&branch)?;
+                branch.sync_state_with_parent()?;
+                pad.link(&branch.static_pad("sink").unwrap())?;
+                Ok(())
+            });
+        if let Err(err) = linked {
+            gst::element_error!(
+                decodebin,
+                gst::CoreError::MissingPlugin,
+                ["Failed to encode the frames: {:#}", err]
+            );
+        }
+    });
+
+    let range = (
+        gst::ClockTime::from_nseconds(start.as_nanos() as u64),
+        Some(gst::ClockTime::from_nseconds(end.as_nanos() as u64)),
+    );
+    jobs::run_pipeline(&pipeline, range, Some(gst::SeekFlags::ACCURATE), context)
+}
@@ -0,0 +1,105 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+/// Layout of the pixels of a frame, both use 4 bytes per pixel
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    #[default]
+    Rgba8,
+    /// 10 bits per color and 2 bits of alpha, little endian with red in the lowest bits
+    Rgb10a2,
+}
+
+/// How the values of a frame map to light, HDR video uses BT.2020 primaries
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Transfer {
+    #[default]
+    Sdr,
+    /// HDR10, absolute luminance up to 10000 nits
+    Pq,
+    /// Hybrid log-gamma, relative to the peak of the display
+    Hlg,
+}
+
+/// A decoded frame, rows start `stride` bytes apart and may be padded at the end
+#[derive(Debug)]
+pub struct Frame {
+    pub data: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    pub stride: u32,
+    pub format: PixelFormat,
+    pub transfer: Transfer,
+    /// Drawn over the frame, the same bitmaps are shared by every frame they are shown on
+    pub subtitles: Option<Arc<SubtitleBitmaps>>,
+}
+
+/// Image based subtitles like PGS, DVB and VobSub, as the subtitle overlay of gstreamer
+/// attached them to a frame
+#[derive(Debug)]
+pub struct SubtitleBitmaps {
+    /// Changes whenever the overlay shows something else
+    pub seqnum: u32,
+    pub bitmaps: Vec<SubtitleBitmap>,
+}
+
+#[derive(Debug)]
+pub struct SubtitleBitmap {
+    /// Where the bitmap is drawn, in pixels of the video
+    pub x: i32,
+    pub y: i32,
+    pub render_width: u32,
+    pub render_height: u32,
+    pub width: u32,
+    pub height: u32,
+    /// RGBA without premultiplied alpha, rows are `4 * width` bytes
+    pub data: Vec<u8>,
+}
+
+/// Decoded frames waiting to be uploaded. When rendering stalls the oldest frames are dropped,
+/// so neither memory nor latency grows and the decoder never blocks on the renderer.
+pub struct FrameQueue {
+    frames: Mutex<VecDeque<Frame>>,
+    capacity: usize,
+    dropped: AtomicU64,
+}
+
+impl FrameQueue {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            frames: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Adds a decoded frame, dropping the oldest one when the queue is full
+    pub fn push(&self, frame: Frame) {
+        let mut frames = self.frames.lock().unwrap();
+        if frames.len() >= self.capacity {
+            frames.pop_front();
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        frames.push_back(frame);
+    }
+
+    /// Takes the newest frame, the older ones were never shown and count as dropped
+    pub fn take_latest(&self) -> Option<Frame> {
+        let mut frames = self.frames.lock().unwrap();
+        let latest = frames.pop_back();
+        self.dropped
+            .fetch_add(frames.len() as u64, Ordering::Relaxed);
+        frames.clear();
+        latest
+    }
+
+    /// Frames that were dropped before they could be uploaded, since the queue was created
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
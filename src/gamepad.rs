@@ -0,0 +1,70 @@
+use gilrs::{Axis, Button, EventType, Gilrs};
+
+/// How far the left stick has to be pushed to move to the next item
+const STICK_THRESHOLD: f32 = 0.5;
+
+/// The buttons of a gamepad the player listens to, by what they mean rather than where they
+/// are, so the d-pad and the left stick both move the selection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GamepadButton {
+    Left,
+    Right,
+    /// A on Xbox layouts, cross on PlayStation ones
+    Confirm,
+    /// B on Xbox layouts, circle on PlayStation ones
+    Back,
+    Menu,
+}
+
+/// Reads the gamepads on a background thread and calls `on_button` for every press. There is
+/// nothing to do when the platform has no gamepad support, e.g. without udev.
+pub fn listen(on_button: impl Fn(GamepadButton) + Send + 'static) {
+    std::thread::spawn(move || {
+        let mut gilrs = match Gilrs::new() {
+            Ok(gilrs) => gilrs,
+            Err(err) => {
+                tracing::debug!("Not listening to gamepads: {}", err);
+                return;
+            }
+        };
+        // the stick moves one item each time it leaves the middle
+        let mut stick_direction = 0;
+        loop {
+            let Some(event) = gilrs.next_event_blocking(None) else {
+                continue;
+            };
+            let button = match event.event {
+                EventType::ButtonPressed(button, _) | EventType::ButtonRepeated(button, _) => {
+                    match button {
+                        Button::DPadLeft => Some(GamepadButton::Left),
+                        Button::DPadRight => Some(GamepadButton::Right),
+                        Button::South => Some(GamepadButton::Confirm),
+                        Button::East => Some(GamepadButton::Back),
+                        Button::Start | Button::Mode => Some(GamepadButton::Menu),
+                        _ => None,
+                    }
+                }
+                EventType::AxisChanged(Axis::LeftStickX, value, _) => {
+                    let direction = if value <= -STICK_THRESHOLD {
+                        -1
+                    } else if value >= STICK_THRESHOLD {
+                        1
+                    } else {
+                        0
+                    };
+                    let moved = direction != stick_direction;
+                    stick_direction = direction;
+                    match direction {
+                        -1 if moved => Some(GamepadButton::Left),
+                        1 if moved => Some(GamepadButton::Right),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+            if let Some(button) = button {
+                on_button(button);
+            }
+        }
+    });
+}
@@ -0,0 +1,193 @@
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Context, Poll, Wake, Waker},
+    thread::Thread,
+};
+
+use anyhow::{anyhow, Error};
+
+use crate::config::{GpuBackend, GpuSettings};
+
+/// The GPU every window of the process draws with
+pub struct Gpu {
+    pub instance: wgpu::Instance,
+    pub adapter: wgpu::Adapter,
+    pub device: Arc<wgpu::Device>,
+    pub queue: wgpu::Queue,
+    /// What the settings can pick from, see `adapter_names`
+    pub adapter_names: Vec<String>,
+    pub adapter_label: String,
+    /// What the GPU was picked by, including `--gpu` and `--backend`
+    pub settings: GpuSettings,
+    /// Set by the error handler of the device, see `is_lost`
+    lost: Arc<AtomicBool>,
+}
+
+impl Gpu {
+    /// Opens the device of the adapter `settings` ask for, see `request_adapter`
+    pub async fn new(
+        instance: wgpu::Instance,
+        surface: &wgpu::Surface,
+        settings: GpuSettings,
+    ) -> Result<Self, Error> {
+        let adapter = request_adapter(&instance, surface, &settings)
+            .await
+            .ok_or_else(|| anyhow!("there is no GPU that can draw to the window"))?;
+        tracing::info!("Rendering on {}", adapter_label(&adapter.get_info()));
+
+        // Create the logical device and command queue
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: None,
+                    features: wgpu::Features::empty(),
+                    // Make sure we use the texture resolution limits from the adapter, so we can support images the size of the swapchain.
+                    limits: wgpu::Limits::default(),
+                },
+                None,
+            )
+            .await?;
+        // wgpu panics on errors by default, a failed allocation shouldn't end playback. wgpu
+        // has no callback for a lost device, it shows up as errors caused by the loss.
+        let lost = Arc::new(AtomicBool::new(false));
+        {
+            let lost = lost.clone();
+            device.on_uncaptured_error(Box::new(move |err| {
+                tracing::error!("GPU error: {}", err);
+                if is_device_lost(&err) {
+                    lost.store(true, Ordering::Relaxed);
+                }
+            }));
+        }
+
+        Ok(Self {
+            adapter_names: adapter_names(&instance, settings.backend),
+            adapter_label: adapter_label(&adapter.get_info()),
+            instance,
+            adapter,
+            device: Arc::new(device),
+            queue,
+            settings,
+            lost,
+        })
+    }
+
+    /// The driver was reset, crashed or the GPU was unplugged, nothing can be drawn with the
+    /// device anymore. The windows have to be moved to a new one, see `PlayerWindow::set_gpu`.
+    pub fn is_lost(&self) -> bool {
+        self.lost.load(Ordering::Relaxed)
+    }
+}
+
+/// Whether the error, or one of its causes, is the `DeviceError::Lost` of wgpu-core
+fn is_device_lost(err: &wgpu::Error) -> bool {
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(err);
+    while let Some(error) = source {
+        if error.to_string().contains("device is lost") {
+            return true;
+        }
+        source = error.source();
+    }
+    false
+}
+
+pub fn instance(backend: GpuBackend) -> wgpu::Instance {
+    wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: backends(backend),
+        ..Default::default()
+    })
+}
+
+pub fn backends(backend: GpuBackend) -> wgpu::Backends {
+    match backend {
+        GpuBackend::Auto => wgpu::Backends::all(),
+        GpuBackend::Vulkan => wgpu::Backends::VULKAN,
+        GpuBackend::Metal => wgpu::Backends::METAL,
+        GpuBackend::Dx12 => wgpu::Backends::DX12,
+        GpuBackend::Dx11 => wgpu::Backends::DX11,
+        GpuBackend::Gl => wgpu::Backends::GL,
+    }
+}
+
+/// Name, API and kind of the adapter, as shown by the settings and `--list-gpus`
+pub fn adapter_label(info: &wgpu::AdapterInfo) -> String {
+    format!("{} ({:?}, {:?})", info.name, info.backend, info.device_type)
+}
+
+/// The adapter names the settings can pick from, each GPU once even when several APIs have it
+pub fn adapter_names(instance: &wgpu::Instance, backend: GpuBackend) -> Vec<String> {
+    let mut names: Vec<_> = instance
+        .enumerate_adapters(backends(backend))
+        .map(|adapter| adapter.get_info().name)
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// The adapter `settings` ask for, by index in `--list-gpus` or by part of its name. Falls back
+/// to the high performance GPU when there is no such adapter or it can't draw to `surface`,
+/// which is what happens on laptops that switch GPUs per application.
+pub async fn request_adapter(
+    instance: &wgpu::Instance,
+    surface: &wgpu::Surface,
+    settings: &GpuSettings,
+) -> Option<wgpu::Adapter> {
+    if let Some(wanted) = &settings.adapter {
+        let wanted_name = wanted.to_lowercase();
+        let adapter = instance
+            .enumerate_adapters(backends(settings.backend))
+            .enumerate()
+            .find(|(index, adapter)| {
+                index.to_string() == *wanted
+                    || adapter
+                        .get_info()
+                        .name
+                        .to_lowercase()
+                        .contains(&wanted_name)
+            })
+            .map(|(_, adapter)| adapter);
+        match adapter {
+            Some(adapter) if adapter.is_surface_supported(surface) => return Some(adapter),
+            Some(adapter) => tracing::warn!(
+                "{} can't draw to the window, picking another GPU",
+                adapter_label(&adapter.get_info())
+            ),
+            None => tracing::warn!("There is no GPU matching \"{}\"", wanted),
+        }
+    }
+
+    instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            force_fallback_adapter: false,
+            // Request an adapter which can render to our surface
+            compatible_surface: Some(surface),
+        })
+        .await
+}
+
+/// Runs a future of wgpu to completion on the event loop, they are ready right away on native
+/// backends. The tokio runtime of `main` can't be entered again from the event loop.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    struct Unpark(Thread);
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+    let mut context = Context::from_waker(&waker);
+    let mut future = Box::pin(future);
+    loop {
+        match future.as_mut().poll(&mut context) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => std::thread::park(),
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use std::time::{Duration, Instant};
+
+use wgpu::util::DeviceExt;
+use winit::dpi::PhysicalSize;
+
+const BLOCK_SIZE: u32 = 8;
+const WORKGROUP_SIZE: u32 = 8;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct InterpolationParams {
+    blocks_x: u32,
+    blocks_y: u32,
+    t: f32,
+    _padding: f32,
+}
+
+/// Experimental motion compensated frame interpolation, see interpolation.wgsl.
+///
+/// Keeps a copy of the previous video frame, estimates per block motion whenever a new frame
+/// arrives and warps both frames to the current point in time on every redraw. This shows
+/// video one frame late, in exchange for presenting e.g. 24fps content at the display rate.
+pub struct FrameInterpolator {
+    video_size: PhysicalSize<u32>,
+    blocks: (u32, u32),
+    previous_frame: wgpu::Texture,
+    output_frame: wgpu::Texture,
+    output_view: wgpu::TextureView,
+    params_buffer: wgpu::Buffer,
+    motion_pipeline: wgpu::ComputePipeline,
+    warp_pipeline: wgpu::ComputePipeline,
+    motion_bind_group: wgpu::BindGroup,
+    warp_bind_group: wgpu::BindGroup,
+    motion_outdated: bool,
+    last_frame: Instant,
+    frame_interval: Duration,
+}
+
+impl FrameInterpolator {
+    pub fn new(
+        device: &wgpu::Device,
+        video_texture: &wgpu::Texture,
+        video_size: PhysicalSize<u32>,
+    ) -> Self {
+        let extent = wgpu::Extent3d {
+            width: video_size.width,
+            height: video_size.height,
+            depth_or_array_layers: 1,
+        };
+        let blocks = (
+            video_size.width.div_ceil(BLOCK_SIZE),
+            video_size.height.div_ceil(BLOCK_SIZE),
+        );
+
+        // same format as the video texture so it can be copied, read back without the srgb decode
+        let video_format = video_texture.format();
+        let encoded_format = video_format.remove_srgb_suffix();
+        let previous_frame = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Previous Frame"),
+            size: extent,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: video_format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: if encoded_format != video_format {
+                std::slice::from_ref(&encoded_format)
+            } else {
+                &[]
+            },
+        });
+        // storage textures can't be srgb, so write the encoded values and sample through an srgb view
+        let output_frame = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Interpolated Frame"),
+            size: extent,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING,
+            view_formats: &[wgpu::TextureFormat::Rgba8UnormSrgb],
+        });
+
+        let unorm_view = |texture: &wgpu::Texture| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                format: Some(texture.format().remove_srgb_suffix()),
+                ..Default::default()
+            })
+        };
+        let previous_view = unorm_view(&previous_frame);
+        let current_view = unorm_view(video_texture);
+        let output_storage_view = unorm_view(&output_frame);
+        let output_view = output_frame.create_view(&wgpu::TextureViewDescriptor {
+            format: Some(wgpu::TextureFormat::Rgba8UnormSrgb),
+            ..Default::default()
+        });
+
+        let motion_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Motion Buffer"),
+            size: (blocks.0 * blocks.1) as u64 * std::mem::size_of::<[f32; 2]>() as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Interpolation Params Buffer"),
+            contents: bytemuck::cast_slice(&[InterpolationParams {
+                blocks_x: blocks.0,
+                blocks_y: blocks.1,
+                t: 1.0,
+                _padding: 0.0,
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Interpolation Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("interpolation.wgsl").into()),
+        });
+        let motion_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Motion Estimation Pipeline"),
+            layout: None,
+            module: &shader,
+            entry_point: "estimate_motion",
+        });
+        let warp_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Warp Pipeline"),
+            layout: None,
+            module: &shader,
+            entry_point: "warp",
+        });
+
+        let motion_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Motion Estimation Bind Group"),
+            layout: &motion_pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&previous_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&current_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: motion_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: params_buffer.as_entire_binding(),
+                },
+            ],
+        });
+        let warp_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Warp Bind Group"),
+            layout: &warp_pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&previous_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&current_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: motion_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(&output_storage_view),
+                },
+            ],
+        });
+
+        Self {
+            video_size,
+            blocks,
+            previous_frame,
+            output_frame,
+            output_view,
+            params_buffer,
+            motion_pipeline,
+            warp_pipeline,
+            motion_bind_group,
+            warp_bind_group,
+            motion_outdated: false,
+            last_frame: Instant::now(),
+            // 24fps until we have measured the actual rate
+            frame_interval: Duration::from_micros(41_667),
+        }
+    }
+
+    pub fn output_view(&self) -> &wgpu::TextureView {
+        &self.output_view
+    }
+
+    /// Rgba8Unorm holding the srgb encoded interpolated frame
+    pub fn output_texture(&self) -> &wgpu::Texture {
+        &self.output_frame
+    }
+
+    /// Has to be called before the new frame is written into the video texture
+    pub fn before_new_frame(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        video_texture: &wgpu::Texture,
+    ) {
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Previous Frame Copy"),
+        });
+        encoder.copy_texture_to_texture(
+            video_texture.as_image_copy(),
+            self.previous_frame.as_image_copy(),
+            wgpu::Extent3d {
+                width: self.video_size.width,
+                height: self.video_size.height,
+                depth_or_array_layers: 1,
+            },
+        );
+        // submitted now, so it runs before the upload of the new frame that write_texture queues
+        queue.submit(Some(encoder.finish()));
+
+        let now = Instant::now();
+        let interval = now.duration_since(self.last_frame);
+        // ignore pauses and seeks, they would make the interpolation crawl
+        if interval < Duration::from_millis(250) {
+            self.frame_interval = self.frame_interval.mul_f32(0.9) + interval.mul_f32(0.1);
+        }
+        self.last_frame = now;
+        self.motion_outdated = true;
+    }
+
+    /// Records the passes that write the interpolated frame for the current point in time
+    pub fn interpolate(&mut self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder) {
+        if self.motion_outdated {
+            self.motion_outdated = false;
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Motion Estimation Pass"),
+            });
+            pass.set_pipeline(&self.motion_pipeline);
+            pass.set_bind_group(0, &self.motion_bind_group, &[]);
+            pass.dispatch_workgroups(
+                self.blocks.0.div_ceil(WORKGROUP_SIZE),
+                self.blocks.1.div_ceil(WORKGROUP_SIZE),
+                1,
+            );
+        }
+
+        let t = (self.last_frame.elapsed().as_secs_f32() / self.frame_interval.as_secs_f32())
+            .clamp(0.0, 1.0);
+        queue.write_buffer(
+            &self.params_buffer,
+            0,
+            bytemuck::cast_slice(&[InterpolationParams {
+                blocks_x: self.blocks.0,
+                blocks_y: self.blocks.1,
+                t,
+                _padding: 0.0,
+            }]),
+        );
+
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Warp Pass"),
+        });
+        pass.set_pipeline(&self.warp_pipeline);
+        pass.set_bind_group(0, &self.warp_bind_group, &[]);
+        pass.dispatch_workgroups(
+            self.video_size.width.div_ceil(WORKGROUP_SIZE),
+            self.video_size.height.div_ceil(WORKGROUP_SIZE),
+            1,
+        );
+    }
+}
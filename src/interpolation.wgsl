@@ -0,0 +1,83 @@
+// Block matching motion estimation between the previous and current video frame, followed by a
+// warp that synthesizes the frame at time `t` between them.
+
+struct Params {
+    blocks_x: u32,
+    blocks_y: u32,
+    // 0 is the previous frame, 1 the current one
+    t: f32,
+    _padding: f32,
+}
+
+@group(0) @binding(0)
+var previous_frame: texture_2d<f32>;
+@group(0) @binding(1)
+var current_frame: texture_2d<f32>;
+@group(0) @binding(2)
+var<storage, read_write> motion: array<vec2<f32>>;
+@group(0) @binding(3)
+var<uniform> params: Params;
+@group(0) @binding(4)
+var output_frame: texture_storage_2d<rgba8unorm, write>;
+
+fn load_previous(coords: vec2<i32>) -> vec4<f32> {
+    let size = vec2<i32>(textureDimensions(previous_frame));
+    return textureLoad(previous_frame, clamp(coords, vec2<i32>(0), size - 1), 0);
+}
+
+fn load_current(coords: vec2<i32>) -> vec4<f32> {
+    let size = vec2<i32>(textureDimensions(current_frame));
+    return textureLoad(current_frame, clamp(coords, vec2<i32>(0), size - 1), 0);
+}
+
+fn luma(color: vec4<f32>) -> f32 {
+    return dot(color.rgb, vec3<f32>(0.299, 0.587, 0.114));
+}
+
+// one invocation per 8x8 block, searches +-12 pixels in steps of 2 on a subsampled block
+@compute @workgroup_size(8, 8)
+fn estimate_motion(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= params.blocks_x || id.y >= params.blocks_y {
+        return;
+    }
+
+    let origin = vec2<i32>(id.xy) * 8;
+    var best = vec2<i32>(0);
+    var best_cost = 1000000.0;
+    for (var dy = -12; dy <= 12; dy += 2) {
+        for (var dx = -12; dx <= 12; dx += 2) {
+            var cost = 0.0;
+            for (var y = 0; y < 8; y += 2) {
+                for (var x = 0; x < 8; x += 2) {
+                    let coords = origin + vec2<i32>(x, y);
+                    cost += abs(luma(load_previous(coords)) - luma(load_current(coords + vec2<i32>(dx, dy))));
+                }
+            }
+            // prefer short vectors, so flat areas don't jitter around
+            cost += 0.002 * f32(abs(dx) + abs(dy));
+
+            if cost < best_cost {
+                best_cost = cost;
+                best = vec2<i32>(dx, dy);
+            }
+        }
+    }
+
+    motion[id.y * params.blocks_x + id.x] = vec2<f32>(best);
+}
+
+@compute @workgroup_size(8, 8)
+fn warp(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = vec2<u32>(textureDimensions(output_frame));
+    if id.x >= size.x || id.y >= size.y {
+        return;
+    }
+
+    let block = min(id.xy / 8u, vec2<u32>(params.blocks_x - 1u, params.blocks_y - 1u));
+    let motion_vector = motion[block.y * params.blocks_x + block.x];
+    let position = vec2<f32>(id.xy);
+
+    let from_previous = load_previous(vec2<i32>(round(position - params.t * motion_vector)));
+    let from_current = load_current(vec2<i32>(round(position + (1.0 - params.t) * motion_vector)));
+    textureStore(output_frame, vec2<i32>(id.xy), mix(from_previous, from_current, params.t));
+}
@@ -0,0 +1,430 @@
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use anyhow::{anyhow, Error};
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use gst::prelude::*;
+
+/// How often a running pipeline reports its progress
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+/// How long a pipeline may take to preroll before seeking to its range
+const PREROLL_TIMEOUT: gst::ClockTime = gst::ClockTime::from_seconds(10);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JobState {
+    /// Waiting for the queued jobs before it, see `Jobs::enqueue`
+    Queued,
+    /// How far the job got, from 0 to 1
+    Running(f32),
+    Done,
+    Failed(String),
+    Cancelled,
+}
+
+/// A file or folder that is written in the background, e.g. an exported clip
+pub struct Job {
+    id: u64,
+    pub label: String,
+    pub path: PathBuf,
+    pub state: JobState,
+    /// Runs after the other queued jobs instead of right away
+    queued: bool,
+    cancel: Arc<AtomicBool>,
+}
+
+enum Update {
+    Progress(u64, f32),
+    Path(u64, PathBuf),
+    Finished(u64, JobState),
+}
+
+/// Handed to the work of a job, to report progress and notice that it was cancelled
+pub struct JobContext {
+    id: u64,
+    sender: Sender<Update>,
+    cancel: Arc<AtomicBool>,
+    path: Mutex<PathBuf>,
+}
+
+impl JobContext {
+    pub fn set_progress(&self, progress: f32) {
+        self.sender
+            .send(Update::Progress(self.id, progress.clamp(0.0, 1.0)))
+            .ok();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+
+    /// For work that only knows where it writes to once it has started, e.g. because the
+    /// extension depends on the codec
+    pub fn set_path(&self, path: PathBuf) {
+        *self.path.lock().unwrap() = path.clone();
+        self.sender.send(Update::Path(self.id, path)).ok();
+    }
+}
+
+type Work = Box<dyn FnOnce(&JobContext) -> Result<(), Error> + Send>;
+
+/// The background jobs of a player window, each runs on a thread of its own
+pub struct Jobs {
+    jobs: Vec<Job>,
+    /// Work of the jobs that run one after the other, see `enqueue`
+    queue: VecDeque<(JobContext, Work)>,
+    sender: Sender<Update>,
+    receiver: Receiver<Update>,
+    next_id: u64,
+}
+
+impl Default for Jobs {
+    fn default() -> Self {
+        let (sender, receiver) = unbounded();
+        Self {
+            jobs: Vec::new(),
+            queue: VecDeque::new(),
+            sender,
+            receiver,
+            next_id: 0,
+        }
+    }
+}
+
+impl Jobs {
+    /// Runs `work` on a thread right away, the file or folder at `path` is removed when the job
+    /// fails or is cancelled
+    pub fn spawn(
+        &mut self,
+        label: String,
+        path: PathBuf,
+        work: impl FnOnce(&JobContext) -> Result<(), Error> + Send + 'static,
+    ) {
+        let (mut job, context) = self.create(label, path, false);
+        job.state = start(context, Box::new(work));
+        self.jobs.push(job);
+    }
+
+    /// Like `spawn`, but the job waits until the queued jobs before it ended. For work that
+    /// takes all the cpu it gets, like transcoding.
+    pub fn enqueue(
+        &mut self,
+        label: String,
+        path: PathBuf,
+        work: impl FnOnce(&JobContext) -> Result<(), Error> + Send + 'static,
+    ) {
+        let (job, context) = self.create(label, path, true);
+        self.jobs.push(job);
+        self.queue.push_back((context, Box::new(work)));
+        self.start_queued();
+    }
+
+    fn create(&mut self, label: String, path: PathBuf, queued: bool) -> (Job, JobContext) {
+        let id = self.next_id;
+        self.next_id += 1;
+        let cancel = Arc::new(AtomicBool::new(false));
+        let context = JobContext {
+            id,
+            sender: self.sender.clone(),
+            cancel: cancel.clone(),
+            path: Mutex::new(path.clone()),
+        };
+        let job = Job {
+            id,
+            label,
+            path,
+            state: JobState::Queued,
+            queued,
+            cancel,
+        };
+        (job, context)
+    }
+
+    /// Like `unique_path`, but also numbered when a job that hasn't ended writes to the path,
+    /// the files of queued jobs don't exist yet
+    pub fn unused_path(&self, directory: &Path, name: &str, extension: &str) -> PathBuf {
+        numbered_path(directory, name, extension, |path| {
+            path.exists()
+                || self.jobs.iter().any(|job| {
+                    job.path == path && matches!(job.state, JobState::Running(_) | JobState::Queued)
+                })
+        })
+    }
+
+    /// Starts the next queued job once the one before it ended
+    fn start_queued(&mut self) {
+        for job in &mut self.jobs {
+            if job.state == JobState::Queued && job.cancel.load(Ordering::Relaxed) {
+                job.state = JobState::Cancelled;
+            }
+        }
+        self.queue.retain(|(context, _)| !context.is_cancelled());
+
+        let running = |job: &Job| job.queued && matches!(job.state, JobState::Running(_));
+        while !self.jobs.iter().any(running) {
+            let Some((context, work)) = self.queue.pop_front() else {
+                return;
+            };
+            let id = context.id;
+            let state = start(context, work);
+            if let Some(job) = self.jobs.iter_mut().find(|job| job.id == id) {
+                job.state = state;
+            }
+        }
+    }
+
+    /// Applies what the threads reported, returns the jobs that ended since the last call
+    pub fn poll(&mut self) -> Vec<&Job> {
+        let mut ended = Vec::new();
+        for update in self.receiver.try_iter() {
+            let (id, state) = match update {
+                Update::Progress(id, progress) => (id, JobState::Running(progress)),
+                Update::Path(id, path) => {
+                    if let Some(job) = self.jobs.iter_mut().find(|job| job.id == id) {
+                        job.path = path;
+                    }
+                    continue;
+                }
+                Update::Finished(id, state) => {
+                    ended.push(id);
+                    (id, state)
+                }
+            };
+            if let Some(job) = self.jobs.iter_mut().find(|job| job.id == id) {
+                // progress that was sent just before the end arrives after it
+                if !matches!(job.state, JobState::Running(_)) {
+                    continue;
+                }
+                job.state = state;
+            }
+        }
+        self.start_queued();
+        self.jobs
+            .iter()
+            .filter(|job| ended.contains(&job.id))
+            .collect()
+    }
+
+    /// The running jobs with a progress bar and a cancel button, the ended ones until they're
+    /// cleared
+    #[cfg(feature = "ui")]
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        if self.jobs.is_empty() {
+            ui.weak("Nothing is being exported");
+            return;
+        }
+        for job in &self.jobs {
+            ui.horizontal(|ui| {
+                ui.label(&job.label)
+                    .on_hover_text(job.path.display().to_string());
+                match &job.state {
+                    JobState::Running(progress) => {
+                        ui.add(
+                            egui::ProgressBar::new(*progress)
+                                .show_percentage()
+                                .desired_width(120.0),
+                        );
+                        if ui.small_button("✖").on_hover_text("Cancel").clicked() {
+                            job.cancel.store(true, Ordering::Relaxed);
+                        }
+                    }
+                    JobState::Queued => {
+                        ui.weak("Queued");
+                        if ui.small_button("✖").on_hover_text("Cancel").clicked() {
+                            job.cancel.store(true, Ordering::Relaxed);
+                        }
+                    }
+                    JobState::Done => {
+                        ui.weak("Done");
+                    }
+                    JobState::Failed(err) => {
+                        ui.colored_label(ui.visuals().error_fg_color, format!("Failed: {}", err));
+                    }
+                    JobState::Cancelled => {
+                        ui.weak("Cancelled");
+                    }
+                }
+            });
+        }
+        let active = |job: &&Job| matches!(job.state, JobState::Running(_) | JobState::Queued);
+        let active_count = self.jobs.iter().filter(active).count();
+        if active_count > 0 {
+            ui.ctx().request_repaint_after(PROGRESS_INTERVAL);
+        }
+        if self.jobs.len() > active_count && ui.button("Clear finished").clicked() {
+            self.jobs.retain(|job| active(&job));
+        }
+    }
+}
+
+/// Runs the work of a job on a thread of its own
+fn start(context: JobContext, work: Work) -> JobState {
+    let id = context.id;
+    let spawned = std::thread::Builder::new()
+        .name(format!("job {}", id))
+        .spawn(move || {
+            let state = match work(&context) {
+                Ok(()) => JobState::Done,
+                Err(_) if context.is_cancelled() => JobState::Cancelled,
+                Err(err) => JobState::Failed(format!("{:#}", err)),
+            };
+            let job_path = context.path.lock().unwrap().clone();
+            match &state {
+                JobState::Done => tracing::info!("Wrote {}", job_path.display()),
+                JobState::Failed(err) => {
+                    tracing::warn!("Failed to write {}: {}", job_path.display(), err)
+                }
+                _ => {}
+            }
+            if state != JobState::Done {
+                let _ = if job_path.is_dir() {
+                    std::fs::remove_dir_all(&job_path)
+                } else {
+                    std::fs::remove_file(&job_path)
+                };
+            }
+            context.sender.send(Update::Finished(id, state)).ok();
+        });
+    match spawned {
+        Ok(_) => JobState::Running(0.0),
+        Err(err) => JobState::Failed(err.to_string()),
+    }
+}
+
+/// Plays `pipeline` as fast as it can until it ends, reporting the position within `range`
+/// as the progress. When `seek_flags` are given the pipeline is first prerolled and seeked to
+/// the range.
+pub fn run_pipeline(
+    pipeline: &gst::Pipeline,
+    range: (gst::ClockTime, Option<gst::ClockTime>),
+    seek_flags: Option<gst::SeekFlags>,
+    context: &JobContext,
+) -> Result<(), Error> {
+    let result = run_pipeline_inner(pipeline, range, seek_flags, context);
+    let _ = pipeline.set_state(gst::State::Null);
+    result
+}
+
+fn run_pipeline_inner(
+    pipeline: &gst::Pipeline,
+    (start, end): (gst::ClockTime, Option<gst::ClockTime>),
+    seek_flags: Option<gst::SeekFlags>,
+    context: &JobContext,
+) -> Result<(), Error> {
+    let bus = pipeline
+        .bus()
+        .ok_or_else(|| anyhow!("The pipeline has no bus"))?;
+    if let Some(flags) = seek_flags {
+        pipeline.set_state(gst::State::Paused)?;
+        let (result, ..) = pipeline.state(PREROLL_TIMEOUT);
+        if result.is_err() {
+            return Err(bus_error(&bus).unwrap_or_else(|| anyhow!("Failed to open the media")));
+        }
+        pipeline.seek(
+            1.0,
+            flags | gst::SeekFlags::FLUSH,
+            gst::SeekType::Set,
+            Some(start),
+            if end.is_some() {
+                gst::SeekType::Set
+            } else {
+                gst::SeekType::None
+            },
+            end,
+        )?;
+    }
+    pipeline.set_state(gst::State::Playing)?;
+
+    loop {
+        if context.is_cancelled() {
+            return Err(anyhow!("Cancelled"));
+        }
+
+        if let Some(msg) = bus.timed_pop(gst::ClockTime::from_nseconds(
+            PROGRESS_INTERVAL.as_nanos() as u64,
+        )) {
+            match msg.view() {
+                gst::MessageView::Eos(..) => return Ok(()),
+                gst::MessageView::Error(err) => {
+                    return Err(anyhow!("{} ({:?})", err.error(), err.debug()))
+                }
+                _ => {}
+            }
+        }
+
+        let end = end.or_else(|| pipeline.query_duration::<gst::ClockTime>());
+        if let (Some(position), Some(end)) = (pipeline.query_position::<gst::ClockTime>(), end) {
+            let length = end.saturating_sub(start).nseconds().max(1);
+            context.set_progress(position.saturating_sub(start).nseconds() as f32 / length as f32);
+        }
+    }
+}
+
+fn bus_error(bus: &gst::Bus) -> Option<Error> {
+    let msg = bus.pop_filtered(&[gst::MessageType::Error])?;
+    match msg.view() {
+        gst::MessageView::Error(err) => Some(anyhow!("{} ({:?})", err.error(), err.debug())),
+        _ => None,
+    }
+}
+
+/// Streams that aren't exported still need somewhere to go, an unlinked pad stops the pipeline
+pub fn discard(pipeline: &gst::Pipeline, pad: &gst::Pad) {
+    let result = gst::ElementFactory::make("fakesink")
+        .property("sync", false)
+        .property("async", false)
+        .build()
+        .map_err(Error::from)
+        .and_then(|sink| {
+            pipeline.add(&sink)?;
+            sink.sync_state_with_parent()?;
+            pad.link(&sink.static_pad("sink").unwrap())?;
+            Ok(())
+        });
+    if let Err(err) = result {
+        tracing::warn!("Failed to discard {}: {:#}", pad.name(), err);
+    }
+}
+
+/// `<directory>/<name>.<extension>`, numbered when that file exists. Folders have no
+/// extension. Characters that aren't allowed in file names on some platform are replaced.
+pub fn unique_path(directory: &Path, name: &str, extension: &str) -> PathBuf {
+    numbered_path(directory, name, extension, |path| path.exists())
+}
+
+fn numbered_path(
+    directory: &Path,
+    name: &str,
+    extension: &str,
+    is_taken: impl Fn(&Path) -> bool,
+) -> PathBuf {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let name = match name.trim() {
+        "" => "recording",
+        name => name,
+    };
+    let extension = match extension {
+        "" => String::new(),
+        extension => format!(".{}", extension),
+    };
+    (1..)
+        .map(|number| match number {
+            1 => directory.join(format!("{}{}", name, extension)),
+            _ => directory.join(format!("{} ({}){}", name, number, extension)),
+        })
+        .find(|path| !is_taken(path))
+        .unwrap()
+}
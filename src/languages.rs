@@ -0,0 +1,73 @@
+/// ISO 639-2 codes of common languages with their ISO 639-1 code, demuxers report either and
+/// people type either
+const ISO_639_2: &[(&str, &str)] = &[
+    ("ara", "ar"),
+    ("chi", "zh"),
+    ("zho", "zh"),
+    ("cze", "cs"),
+    ("ces", "cs"),
+    ("dan", "da"),
+    ("dut", "nl"),
+    ("nld", "nl"),
+    ("eng", "en"),
+    ("fin", "fi"),
+    ("fre", "fr"),
+    ("fra", "fr"),
+    ("ger", "de"),
+    ("deu", "de"),
+    ("gre", "el"),
+    ("ell", "el"),
+    ("heb", "he"),
+    ("hin", "hi"),
+    ("hun", "hu"),
+    ("ita", "it"),
+    ("jpn", "ja"),
+    ("kor", "ko"),
+    ("nor", "no"),
+    ("pol", "pl"),
+    ("por", "pt"),
+    ("rus", "ru"),
+    ("spa", "es"),
+    ("swe", "sv"),
+    ("tha", "th"),
+    ("tur", "tr"),
+    ("ukr", "uk"),
+    ("vie", "vi"),
+];
+
+/// Splits a list like "ja, en" that was typed in the settings
+#[cfg(feature = "ui")]
+pub fn parse_list(text: &str) -> Vec<String> {
+    text.split(',')
+        .map(str::trim)
+        .filter(|language| !language.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// The stream whose language comes first in `preferences`, None when no stream matches.
+/// `languages` holds the language tag of every stream, if it has one.
+pub fn best_match(languages: &[Option<String>], preferences: &[String]) -> Option<usize> {
+    preferences
+        .iter()
+        .map(|preference| normalize(preference))
+        .filter(|preference| !preference.is_empty())
+        .find_map(|preference| {
+            languages.iter().position(|language| {
+                language
+                    .as_deref()
+                    .is_some_and(|language| normalize(language) == preference)
+            })
+        })
+}
+
+/// Lowercase ISO 639-1 where it is known, regions like the US of en-US are dropped
+fn normalize(code: &str) -> String {
+    let code = code.trim().to_lowercase();
+    let language = code.split(['-', '_']).next().unwrap_or_default();
+    ISO_639_2
+        .iter()
+        .find(|(iso_639_2, _)| *iso_639_2 == language)
+        .map_or(language, |(_, iso_639_1)| iso_639_1)
+        .to_string()
+}
@@ -0,0 +1,386 @@
+use std::{num::NonZeroU32, sync::Arc};
+
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    config::{LetterboxMode, LetterboxSettings},
+    cover_art::CoverArt,
+    renderer::HDR_SURFACE_FORMAT,
+};
+
+/// Size of the copy of the video behind ambient letterboxing, small enough that stretching it
+/// over the window blurs it. Has to match fs_downsample in letterbox.wgsl.
+const AMBIENT_SIZE: (u32, u32) = (32, 18);
+const AMBIENT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// Has to match Params in letterbox.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct LetterboxParams {
+    color: [f32; 4],
+    uv_scale: [f32; 2],
+    mode: u32,
+    encode_srgb: u32,
+    dim: f32,
+    decode_source: u32,
+    // uniform buffers need to be 16 byte aligned
+    _padding: [u32; 2],
+}
+
+/// Fills the area around the video with a color, a blurred copy of the video or an image. Drawn
+/// in a pass of its own that clears the window before the video is drawn on top.
+pub struct Letterbox {
+    settings: LetterboxSettings,
+    /// The surface isn't srgb, so the shader encodes the output
+    encode_srgb: bool,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    params_buffer: wgpu::Buffer,
+    downsample_pipeline: wgpu::RenderPipeline,
+    background_pipeline: wgpu::RenderPipeline,
+    ambient_view: wgpu::TextureView,
+    ambient_bind_group: wgpu::BindGroup,
+    image: Option<Arc<CoverArt>>,
+    image_bind_group: Option<wgpu::BindGroup>,
+}
+
+impl Letterbox {
+    pub fn new(device: &wgpu::Device, surface_format: wgpu::TextureFormat) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("letterbox_bind_group_layout"),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Letterbox Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Letterbox Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("letterbox.wgsl").into()),
+        });
+        let create_pipeline = |label, entry_point, format| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_fullscreen",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point,
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+        };
+        let downsample_pipeline = create_pipeline(
+            "Ambient Downsample Pipeline",
+            "fs_downsample",
+            AMBIENT_FORMAT,
+        );
+        let background_pipeline =
+            create_pipeline("Letterbox Pipeline", "fs_background", surface_format);
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Letterbox Params Buffer"),
+            size: std::mem::size_of::<LetterboxParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let ambient_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Ambient Texture"),
+            size: wgpu::Extent3d {
+                width: AMBIENT_SIZE.0,
+                height: AMBIENT_SIZE.1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: AMBIENT_FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let ambient_view = ambient_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self {
+            settings: LetterboxSettings::default(),
+            encode_srgb: !surface_format.describe().srgb && surface_format != HDR_SURFACE_FORMAT,
+            ambient_bind_group: Self::create_bind_group(
+                device,
+                &bind_group_layout,
+                &ambient_view,
+                &sampler,
+                &params_buffer,
+            ),
+            bind_group_layout,
+            sampler,
+            params_buffer,
+            downsample_pipeline,
+            background_pipeline,
+            ambient_view,
+            image: None,
+            image_bind_group: None,
+        }
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        view: &wgpu::TextureView,
+        sampler: &wgpu::Sampler,
+        params_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: params_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("letterbox_bind_group"),
+        })
+    }
+
+    pub fn set_settings(&mut self, settings: &LetterboxSettings) {
+        if settings != &self.settings {
+            self.settings = settings.clone();
+        }
+    }
+
+    /// Uploads the image for `LetterboxMode::Image`, compared by pointer like the LUT
+    pub fn set_image(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        image: Option<&Arc<CoverArt>>,
+    ) {
+        let unchanged = match (image, &self.image) {
+            (Some(new), Some(current)) => Arc::ptr_eq(new, current),
+            (None, None) => true,
+            _ => false,
+        };
+        if unchanged {
+            return;
+        }
+        self.image = image.cloned();
+        self.image_bind_group = None;
+
+        let Some(image) = image else {
+            return;
+        };
+        let max_dimension = device.limits().max_texture_dimension_2d;
+        if image.width == 0
+            || image.height == 0
+            || image.width > max_dimension
+            || image.height > max_dimension
+        {
+            tracing::warn!(
+                "The letterbox image is {}x{}, at most {}x{} is supported",
+                image.width,
+                image.height,
+                max_dimension,
+                max_dimension
+            );
+            return;
+        }
+
+        let size = wgpu::Extent3d {
+            width: image.width,
+            height: image.height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Letterbox Image"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            texture.as_image_copy(),
+            &image.data,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(4 * image.width),
+                rows_per_image: NonZeroU32::new(image.height),
+            },
+            size,
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        self.image_bind_group = Some(Self::create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &view,
+            &self.sampler,
+            &self.params_buffer,
+        ));
+    }
+
+    /// Writes the parameters and, for ambient mode, shrinks the current frame. `decode_source`
+    /// is set when `source` holds encoded values the sampler doesn't decode.
+    #[allow(clippy::too_many_arguments)]
+    pub fn prepare(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        source: &wgpu::TextureView,
+        decode_source: bool,
+        window_size: PhysicalSize<u32>,
+        video_size: PhysicalSize<u32>,
+    ) {
+        let content_size = match (self.settings.mode, &self.image) {
+            (LetterboxMode::Image, Some(image)) => PhysicalSize::new(image.width, image.height),
+            _ => video_size,
+        };
+        let srgb_decode = |value: u8| {
+            let encoded = value as f32 / 255.0;
+            if encoded <= 0.04045 {
+                encoded / 12.92
+            } else {
+                ((encoded + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        let [r, g, b] = self.settings.color;
+        queue.write_buffer(
+            &self.params_buffer,
+            0,
+            bytemuck::cast_slice(&[LetterboxParams {
+                color: [srgb_decode(r), srgb_decode(g), srgb_decode(b), 1.0],
+                uv_scale: cover_scale(window_size, content_size),
+                mode: self.settings.mode as u32,
+                encode_srgb: self.encode_srgb as u32,
+                dim: self.settings.ambient_dim.clamp(0.0, 1.0),
+                decode_source: decode_source as u32,
+                _padding: [0; 2],
+            }]),
+        );
+
+        if self.settings.mode != LetterboxMode::Ambient {
+            return;
+        }
+        // the source changes with the interpolation and postprocess settings, so the bind group
+        // is made for every frame, it's tiny
+        let bind_group = Self::create_bind_group(
+            device,
+            &self.bind_group_layout,
+            source,
+            &self.sampler,
+            &self.params_buffer,
+        );
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Ambient Downsample Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.ambient_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(&self.downsample_pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    /// Clears `view` and fills it with the background, the video is drawn on top afterwards
+    pub fn draw(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let bind_group = match self.settings.mode {
+            LetterboxMode::Black => None,
+            // the color mode doesn't sample, but the layout needs some texture
+            LetterboxMode::Color | LetterboxMode::Ambient => Some(&self.ambient_bind_group),
+            // stays black until an image is loaded
+            LetterboxMode::Image => self.image_bind_group.as_ref(),
+        };
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Letterbox Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        if let Some(bind_group) = bind_group {
+            render_pass.set_pipeline(&self.background_pipeline);
+            render_pass.set_bind_group(0, bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+    }
+}
+
+/// Scales texture coordinates so content of `content_size` covers a window of `window_size`,
+/// cropping the sides that don't fit
+fn cover_scale(window_size: PhysicalSize<u32>, content_size: PhysicalSize<u32>) -> [f32; 2] {
+    let window_aspect = window_size.width.max(1) as f32 / window_size.height.max(1) as f32;
+    let content_aspect = content_size.width.max(1) as f32 / content_size.height.max(1) as f32;
+    if window_aspect > content_aspect {
+        [1.0, content_aspect / window_aspect]
+    } else {
+        [window_aspect / content_aspect, 1.0]
+    }
+}
@@ -0,0 +1,103 @@
+// Fills the area around the video, see letterbox.rs
+
+struct Params {
+    // linear
+    color: vec4<f32>,
+    // fits the source over the whole target, cropping what doesn't fit
+    uv_scale: vec2<f32>,
+    // has to match LetterboxMode in config.rs
+    mode: u32,
+    // the surface isn't srgb, so the output has to be encoded here
+    encode_srgb: u32,
+    dim: f32,
+    // the video texture holds encoded values the sampler doesn't decode
+    decode_source: u32,
+}
+
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+@group(0) @binding(1)
+var source_sampler: sampler;
+@group(0) @binding(2)
+var<uniform> params: Params;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// a single triangle covering the target
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+fn srgb_encode(linear: vec3<f32>) -> vec3<f32> {
+    let low = linear * 12.92;
+    let high = 1.055 * pow(linear, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, linear <= vec3<f32>(0.0031308));
+}
+
+fn srgb_decode(encoded: vec3<f32>) -> vec3<f32> {
+    let low = encoded / 12.92;
+    let high = pow((encoded + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, encoded <= vec3<f32>(0.04045));
+}
+
+fn fit(uv: vec2<f32>) -> vec2<f32> {
+    return 0.5 + (uv - 0.5) * params.uv_scale;
+}
+
+// Shrinks the video into the small ambient texture, every pixel averages a 4x4 grid of taps
+// over the part of the video it covers
+@fragment
+fn fs_downsample(in: VertexOutput) -> @location(0) vec4<f32> {
+    // has to match AMBIENT_SIZE in letterbox.rs
+    let footprint = 1.0 / vec2<f32>(32.0, 18.0);
+    var sum = vec3<f32>(0.0);
+    for (var y = 0; y < 4; y += 1) {
+        for (var x = 0; x < 4; x += 1) {
+            let offset = ((vec2<f32>(f32(x), f32(y)) + 0.5) / 4.0 - 0.5) * footprint;
+            sum += textureSampleLevel(source, source_sampler, in.uv + offset, 0.0).rgb;
+        }
+    }
+    var color = sum / 16.0;
+    if params.decode_source == 1u {
+        color = srgb_decode(color);
+    }
+    return vec4<f32>(color, 1.0);
+}
+
+@fragment
+fn fs_background(in: VertexOutput) -> @location(0) vec4<f32> {
+    var color = params.color.rgb;
+    switch params.mode {
+        // ambient, a tent filter on top of the downsampling finishes the blur
+        case 2u: {
+            let texel = 1.0 / vec2<f32>(textureDimensions(source));
+            let uv = fit(in.uv);
+            var sum = vec3<f32>(0.0);
+            for (var y = -1; y <= 1; y += 1) {
+                for (var x = -1; x <= 1; x += 1) {
+                    let weight = f32((2 - abs(x)) * (2 - abs(y)));
+                    let offset = vec2<f32>(f32(x), f32(y)) * texel;
+                    sum += textureSampleLevel(source, source_sampler, uv + offset, 0.0).rgb * weight;
+                }
+            }
+            color = sum / 16.0 * (1.0 - params.dim);
+        }
+        // image
+        case 3u: {
+            color = textureSampleLevel(source, source_sampler, fit(in.uv), 0.0).rgb;
+        }
+        default: {}
+    }
+    if params.encode_srgb == 1u {
+        color = srgb_encode(color);
+    }
+    return vec4<f32>(color, 1.0);
+}
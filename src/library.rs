@@ -0,0 +1,358 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Error};
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use gst::glib;
+use image::ImageEncoder;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    playlist::natural_cmp,
+    thumbnailer::{self, Thumbnail},
+};
+
+/// Files that are picked up by a scan, by their extension
+const MEDIA_EXTENSIONS: &[&str] = &[
+    "3gp", "aac", "avi", "flac", "flv", "m2ts", "m4a", "m4v", "mka", "mkv", "mov", "mp3", "mp4",
+    "mpeg", "mpg", "ogg", "ogv", "opus", "ts", "wav", "webm", "wma", "wmv",
+];
+/// How long the discoverer may take to read the duration and tags of a file
+const DISCOVER_TIMEOUT: gst::ClockTime = gst::ClockTime::from_seconds(10);
+/// Playing this far into an item marks it as watched
+const WATCHED_FRACTION: f64 = 0.9;
+
+/// A file found in one of `Config::library_folders`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LibraryItem {
+    pub uri: String,
+    /// The title tag, or the file name without its extension
+    pub title: String,
+    pub duration: Option<Duration>,
+    pub watched: bool,
+    /// Modification time of the file when it was scanned, it is read again once this changes
+    modified: u64,
+}
+
+enum ScanEvent {
+    Updated(LibraryItem),
+    Removed(String),
+    Finished,
+}
+
+/// The media in the library folders, stored in a sled database next to the config so the grid
+/// shows up right away and only new or changed files are read again
+pub struct Library {
+    db: sled::Db,
+    /// Sorted by title
+    items: Vec<LibraryItem>,
+    scan_sender: Sender<ScanEvent>,
+    scan_receiver: Receiver<ScanEvent>,
+    /// Set to stop the running scan
+    scan_cancel: Option<Arc<AtomicBool>>,
+    /// Goes up whenever `poll` takes in a changed item, the windows compare it with the one
+    /// their thumbnails were loaded at
+    generation: u64,
+}
+
+impl Library {
+    fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("wgpu-media-player").join("library"))
+    }
+
+    pub fn open() -> Result<Self, Error> {
+        let path = Self::path().context("There is no config folder for the library")?;
+        let db = sled::open(&path)
+            .with_context(|| format!("Failed to open the library at {}", path.display()))?;
+        let mut items: Vec<LibraryItem> = db
+            .open_tree("items")?
+            .iter()
+            .values()
+            .filter_map(|value| {
+                let value = value.ok()?;
+                serde_json::from_slice(&value)
+                    .map_err(|err| tracing::warn!("Skipping a library item: {}", err))
+                    .ok()
+            })
+            .collect();
+        items.sort_by(|a, b| natural_cmp(&a.title, &b.title));
+        let (scan_sender, scan_receiver) = unbounded();
+
+        Ok(Self {
+            db,
+            items,
+            scan_sender,
+            scan_receiver,
+            scan_cancel: None,
+            generation: 0,
+        })
+    }
+
+    pub fn items(&self) -> &[LibraryItem] {
+        &self.items
+    }
+
+    pub fn is_scanning(&self) -> bool {
+        self.scan_cancel.is_some()
+    }
+
+    /// Looks through `folders` in the background, files that are gone or outside of them are
+    /// removed. A scan that is still running is stopped first.
+    pub fn scan(&mut self, folders: Vec<PathBuf>) {
+        if let Some(cancel) = self.scan_cancel.take() {
+            cancel.store(true, Ordering::Relaxed);
+        }
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.scan_cancel = Some(cancel.clone());
+        let (db, sender) = (self.db.clone(), self.scan_sender.clone());
+        std::thread::spawn(move || {
+            if let Err(err) = scan(&db, &folders, &cancel, &sender) {
+                tracing::warn!("Failed to scan the library: {:#}", err);
+            }
+            if !cancel.load(Ordering::Relaxed) {
+                sender.send(ScanEvent::Finished).ok();
+            }
+        });
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Takes in what the scan found so far
+    pub fn poll(&mut self) {
+        let mut changed = false;
+        for event in self.scan_receiver.try_iter() {
+            match event {
+                ScanEvent::Updated(item) => {
+                    changed = true;
+                    self.items.retain(|existing| existing.uri != item.uri);
+                    let index = self.items.partition_point(|existing| {
+                        natural_cmp(&existing.title, &item.title).is_lt()
+                    });
+                    self.items.insert(index, item);
+                }
+                ScanEvent::Removed(uri) => {
+                    changed = true;
+                    self.items.retain(|item| item.uri != uri);
+                }
+                ScanEvent::Finished => self.scan_cancel = None,
+            }
+        }
+        if changed {
+            self.generation += 1;
+        }
+    }
+
+    /// The thumbnail stored by the scan, None for audio and files it couldn't be made for
+    pub fn thumbnail(&self, uri: &str) -> Option<Thumbnail> {
+        let png = self.db.open_tree("thumbnails").ok()?.get(uri).ok()??;
+        let image = image::load_from_memory_with_format(&png, image::ImageFormat::Png)
+            .map_err(|err| tracing::warn!("Failed to decode the thumbnail of {}: {}", uri, err))
+            .ok()?
+            .to_rgba8();
+        Some(Thumbnail {
+            uri: uri.to_string(),
+            width: image.width(),
+            height: image.height(),
+            data: image.into_raw(),
+        })
+    }
+
+    pub fn set_watched(&mut self, uri: &str, watched: bool) {
+        let Some(item) = self.items.iter_mut().find(|item| item.uri == uri) else {
+            return;
+        };
+        if item.watched == watched {
+            return;
+        }
+        item.watched = watched;
+        let stored = serde_json::to_vec(item)
+            .map_err(Error::from)
+            .and_then(|value| Ok(self.db.open_tree("items")?.insert(uri, value)?));
+        if let Err(err) = stored {
+            tracing::warn!("Failed to store the watched state of {}: {}", uri, err);
+        }
+    }
+
+    /// Marks the item as watched once playback got near its end
+    pub fn update_position(&mut self, uri: &str, position: Duration, duration: Option<Duration>) {
+        let Some(duration) = duration.filter(|duration| !duration.is_zero()) else {
+            return;
+        };
+        if position.as_secs_f64() >= duration.as_secs_f64() * WATCHED_FRACTION {
+            self.set_watched(uri, true);
+        }
+    }
+}
+
+impl Drop for Library {
+    fn drop(&mut self) {
+        if let Some(cancel) = self.scan_cancel.take() {
+            cancel.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+fn scan(
+    db: &sled::Db,
+    folders: &[PathBuf],
+    cancel: &AtomicBool,
+    sender: &Sender<ScanEvent>,
+) -> Result<(), Error> {
+    gst::init()?;
+    let discoverer = gst_pbutils::Discoverer::new(DISCOVER_TIMEOUT)?;
+    let items = db.open_tree("items")?;
+    let thumbnails = db.open_tree("thumbnails")?;
+
+    let mut paths = Vec::new();
+    for folder in folders {
+        find_media(folder, &mut paths);
+    }
+    let mut found = HashSet::new();
+    for path in paths {
+        if cancel.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let uri = match glib::filename_to_uri(&path, None) {
+            Ok(uri) => uri.to_string(),
+            Err(err) => {
+                tracing::debug!("Skipping {}: {}", path.display(), err);
+                continue;
+            }
+        };
+        found.insert(uri.clone());
+        let modified = std::fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |modified| modified.as_secs());
+        let existing: Option<LibraryItem> = items
+            .get(&uri)?
+            .and_then(|value| serde_json::from_slice(&value).ok());
+        if existing.as_ref().map(|item| item.modified) == Some(modified) {
+            continue;
+        }
+
+        let (item, has_video) = discover(&discoverer, &path, uri.clone(), modified);
+        let item = LibraryItem {
+            watched: existing.is_some_and(|existing| existing.watched),
+            ..item
+        };
+        if has_video {
+            match thumbnailer::create_thumbnail(&uri).and_then(|thumbnail| encode_png(&thumbnail)) {
+                Ok(png) => {
+                    thumbnails.insert(&uri, png)?;
+                }
+                Err(err) => tracing::debug!("Failed to create a thumbnail for {}: {}", uri, err),
+            }
+        }
+        items.insert(&uri, serde_json::to_vec(&item)?)?;
+        sender.send(ScanEvent::Updated(item)).ok();
+    }
+
+    // the folders of a newer scan may differ
+    if cancel.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    for key in items.iter().keys() {
+        let key = key?;
+        let uri = String::from_utf8_lossy(&key).into_owned();
+        if !found.contains(&uri) {
+            items.remove(&key)?;
+            thumbnails.remove(&key)?;
+            sender.send(ScanEvent::Removed(uri)).ok();
+        }
+    }
+    db.flush()?;
+    Ok(())
+}
+
+/// Adds the media files in `folder` and its subfolders to `paths`
+fn find_media(folder: &Path, paths: &mut Vec<PathBuf>) {
+    let entries = match std::fs::read_dir(folder) {
+        Ok(entries) => entries,
+        Err(err) => {
+            tracing::warn!("Failed to read {}: {}", folder.display(), err);
+            return;
+        }
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        // hidden folders hold caches and thumbnails of other programs
+        let hidden = entry.file_name().to_string_lossy().starts_with('.');
+        match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() && !hidden => find_media(&path, paths),
+            Ok(file_type) if file_type.is_file() && is_media(&path) => paths.push(path),
+            _ => {}
+        }
+    }
+}
+
+fn is_media(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| MEDIA_EXTENSIONS.contains(&extension.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Reads the title and duration, files the discoverer can't read are still listed by their
+/// name. Also returns whether there is a video stream to make a thumbnail of.
+fn discover(
+    discoverer: &gst_pbutils::Discoverer,
+    path: &Path,
+    uri: String,
+    modified: u64,
+) -> (LibraryItem, bool) {
+    let file_name = path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    let mut item = LibraryItem {
+        uri,
+        title: file_name,
+        duration: None,
+        watched: false,
+        modified,
+    };
+    let info = match discoverer.discover_uri(&item.uri) {
+        Ok(info) => info,
+        Err(err) => {
+            tracing::debug!("Failed to read {}: {}", item.uri, err);
+            return (item, false);
+        }
+    };
+    if let Some(title) = info
+        .tags()
+        .and_then(|tags| {
+            tags.get::<gst::tags::Title>()
+                .map(|title| title.get().to_string())
+        })
+        .filter(|title| !title.trim().is_empty())
+    {
+        item.title = title;
+    }
+    item.duration = info
+        .duration()
+        .map(|duration| Duration::from_nanos(duration.nseconds()));
+    (item, !info.video_streams().is_empty())
+}
+
+fn encode_png(thumbnail: &Thumbnail) -> Result<Vec<u8>, Error> {
+    let mut png = Vec::new();
+    image::codecs::png::PngEncoder::new(&mut png).write_image(
+        &thumbnail.data,
+        thumbnail.width,
+        thumbnail.height,
+        image::ColorType::Rgba8,
+    )?;
+    Ok(png)
+}
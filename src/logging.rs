@@ -0,0 +1,124 @@
+use std::{
+    collections::VecDeque,
+    io,
+    sync::{Arc, Mutex},
+};
+
+use tracing_subscriber::{fmt::format::FmtSpan, prelude::*, EnvFilter};
+
+/// Lines kept for the log console, older ones are dropped
+const CONSOLE_LINES: usize = 1000;
+/// Used when neither `--log-level` nor `RUST_LOG` is given
+const DEFAULT_FILTER: &str = "info";
+
+/// The most recent log lines, shared between the subscriber and the log console
+#[derive(Clone, Default)]
+pub struct LogBuffer(Arc<Mutex<VecDeque<String>>>);
+
+impl LogBuffer {
+    fn push(&self, text: &str) {
+        let mut lines = self.0.lock().unwrap();
+        for line in text.lines() {
+            if lines.len() == CONSOLE_LINES {
+                lines.pop_front();
+            }
+            lines.push_back(line.to_string());
+        }
+    }
+}
+
+struct ConsoleWriter(LogBuffer);
+
+impl io::Write for ConsoleWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.push(&String::from_utf8_lossy(buf));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Logs to stdout and the log console. The filter is a level like `debug` or a `RUST_LOG` style
+/// directive, `trace` also logs how long every decode, upload and render took.
+pub fn init(filter: Option<&str>) -> LogBuffer {
+    let mut invalid_filter = None;
+    let env_filter = match filter {
+        Some(filter) => EnvFilter::try_new(filter).unwrap_or_else(|err| {
+            invalid_filter = Some((filter.to_string(), err));
+            EnvFilter::new(DEFAULT_FILTER)
+        }),
+        None => {
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER))
+        }
+    };
+
+    let buffer = LogBuffer::default();
+    let console_buffer = buffer.clone();
+    tracing_subscriber::registry()
+        .with(env_filter)
+        .with(tracing_subscriber::fmt::layer().with_span_events(FmtSpan::CLOSE))
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_target(false)
+                .with_span_events(FmtSpan::CLOSE)
+                .with_writer(move || ConsoleWriter(console_buffer.clone())),
+        )
+        .init();
+
+    if let Some((filter, err)) = invalid_filter {
+        tracing::warn!(
+            "Invalid log level {:?}, using {}: {}",
+            filter,
+            DEFAULT_FILTER,
+            err
+        );
+    }
+
+    buffer
+}
+
+/// On screen view of the log, for diagnosing issues without a terminal
+#[cfg(feature = "ui")]
+pub struct LogConsole {
+    open: bool,
+    buffer: LogBuffer,
+}
+
+#[cfg(feature = "ui")]
+impl LogConsole {
+    pub fn new(buffer: LogBuffer) -> Self {
+        Self {
+            open: false,
+            buffer,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+    }
+
+    pub fn ui(&mut self, ctx: &egui::Context) {
+        let mut open = self.open;
+        egui::Window::new("Log")
+            .open(&mut open)
+            .default_size([720.0, 320.0])
+            .show(ctx, |ui| {
+                if ui.button("Clear").clicked() {
+                    self.buffer.0.lock().unwrap().clear();
+                }
+                ui.separator();
+                egui::ScrollArea::both()
+                    .stick_to_bottom(true)
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        for line in self.buffer.0.lock().unwrap().iter() {
+                            ui.monospace(line);
+                        }
+                    });
+            });
+        self.open = open;
+    }
+}
@@ -0,0 +1,186 @@
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context, Error};
+
+/// Largest LUT_3D_SIZE the spec allows
+const MAX_SIZE: u32 = 256;
+
+/// A 3D color lookup table from a .cube file, as used for color grading.
+///
+/// The entries are applied to the srgb encoded video, red changes fastest, then green, then
+/// blue.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CubeLut {
+    pub title: Option<String>,
+    pub size: u32,
+    pub domain_min: [f32; 3],
+    pub domain_max: [f32; 3],
+    pub entries: Vec<[f32; 3]>,
+}
+
+impl CubeLut {
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Invalid LUT {}", path.display()))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let mut title = None;
+        let mut size = None;
+        let mut domain_min = [0.0; 3];
+        let mut domain_max = [1.0; 3];
+        let mut entries = Vec::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let line_error = || format!("line {}", index + 1);
+
+            let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            match keyword {
+                "TITLE" => title = Some(rest.trim().trim_matches('"').to_string()),
+                "LUT_3D_SIZE" => {
+                    let value: u32 = rest.trim().parse().with_context(line_error)?;
+                    if !(2..=MAX_SIZE).contains(&value) {
+                        bail!(
+                            "{}: LUT_3D_SIZE has to be between 2 and {}",
+                            line_error(),
+                            MAX_SIZE
+                        );
+                    }
+                    size = Some(value);
+                }
+                "LUT_1D_SIZE" => bail!("1D LUTs aren't supported"),
+                "DOMAIN_MIN" => domain_min = parse_triple(rest).with_context(line_error)?,
+                "DOMAIN_MAX" => domain_max = parse_triple(rest).with_context(line_error)?,
+                // other keywords, e.g. LUT_3D_INPUT_RANGE of some tools, don't change the table
+                _ if keyword.starts_with(|c: char| c.is_ascii_alphabetic()) => {}
+                _ => entries.push(parse_triple(line).with_context(line_error)?),
+            }
+        }
+
+        let size = size.ok_or_else(|| anyhow!("LUT_3D_SIZE is missing"))?;
+        let expected = (size * size * size) as usize;
+        if entries.len() != expected {
+            bail!(
+                "expected {} entries for a size of {}, found {}",
+                expected,
+                size,
+                entries.len()
+            );
+        }
+        if (0..3).any(|channel| domain_max[channel] <= domain_min[channel]) {
+            bail!("DOMAIN_MAX has to be larger than DOMAIN_MIN");
+        }
+
+        Ok(Self {
+            title,
+            size,
+            domain_min,
+            domain_max,
+            entries,
+        })
+    }
+
+    /// The entries packed as Rgb10a2Unorm texels, precise enough for grading without needing
+    /// filterable float textures
+    pub fn rgb10a2_texels(&self) -> Vec<u32> {
+        let channel = |value: f32| (value.clamp(0.0, 1.0) * 1023.0).round() as u32;
+        self.entries
+            .iter()
+            .map(|&[r, g, b]| channel(r) | channel(g) << 10 | channel(b) << 20 | 3 << 30)
+            .collect()
+    }
+}
+
+fn parse_triple(text: &str) -> Result<[f32; 3], Error> {
+    let mut values = text.split_whitespace();
+    let mut next = || {
+        let value: f32 = values
+            .next()
+            .ok_or_else(|| anyhow!("expected three numbers"))?
+            .parse()?;
+        // `inf`, `NaN` and numbers too large for an f32 parse as well
+        if !value.is_finite() {
+            bail!("{} isn't a finite number", value);
+        }
+        Ok::<_, Error>(value)
+    };
+    Ok([next()?, next()?, next()?])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The identity table of size 2 after the given header lines
+    fn cube(header: &str) -> String {
+        let mut text = header.to_string();
+        for b in 0..2 {
+            for g in 0..2 {
+                for r in 0..2 {
+                    text.push_str(&format!("{} {} {}\n", r, g, b));
+                }
+            }
+        }
+        text
+    }
+
+    #[test]
+    fn parses_a_table() {
+        let lut = CubeLut::parse(&cube(
+            "# made by hand\nTITLE \"Identity\"\n\nLUT_3D_SIZE 2\nDOMAIN_MIN 0 0 0\n\
+             DOMAIN_MAX 1.0 1.0 1.0\nLUT_3D_INPUT_RANGE 0 1\n",
+        ))
+        .unwrap();
+        assert_eq!(lut.title.as_deref(), Some("Identity"));
+        assert_eq!(lut.size, 2);
+        assert_eq!(lut.entries.len(), 8);
+        assert_eq!(lut.entries[1], [1.0, 0.0, 0.0]);
+        assert_eq!(lut.entries[2], [0.0, 1.0, 0.0]);
+        assert_eq!(lut.entries[7], [1.0, 1.0, 1.0]);
+        let texels = lut.rgb10a2_texels();
+        assert_eq!(texels[0], 3 << 30);
+        assert_eq!(texels[7], 1023 | 1023 << 10 | 1023 << 20 | 3 << 30);
+    }
+
+    #[test]
+    fn rejects_malformed_tables() {
+        // the size is missing, out of range or doesn't fit a u32
+        assert!(CubeLut::parse(&cube("")).is_err());
+        assert!(CubeLut::parse(&cube("LUT_3D_SIZE 1\n")).is_err());
+        assert!(CubeLut::parse(&cube("LUT_3D_SIZE 257\n")).is_err());
+        assert!(CubeLut::parse(&cube("LUT_3D_SIZE 4294967296\n")).is_err());
+        assert!(CubeLut::parse(&cube("LUT_3D_SIZE -2\n")).is_err());
+        assert!(CubeLut::parse(&cube("LUT_3D_SIZE two\n")).is_err());
+        assert!(CubeLut::parse(&cube("LUT_1D_SIZE 2\n")).is_err());
+        // too few or too many entries
+        assert!(CubeLut::parse("LUT_3D_SIZE 2\n0 0 0\n").is_err());
+        assert!(CubeLut::parse(&cube("LUT_3D_SIZE 2\n0 0 0\n")).is_err());
+        // an entry that isn't three numbers
+        assert!(CubeLut::parse(&cube("LUT_3D_SIZE 2\n0 0\n")).is_err());
+        assert!(CubeLut::parse(&cube("LUT_3D_SIZE 2\n0 0 x\n")).is_err());
+        // an empty domain
+        assert!(CubeLut::parse(&cube("LUT_3D_SIZE 2\nDOMAIN_MAX 1 0 1\n")).is_err());
+        assert!(CubeLut::parse(&cube("LUT_3D_SIZE 2\nDOMAIN_MIN 0 0\n")).is_err());
+    }
+
+    #[test]
+    fn rejects_numbers_that_arent_finite() {
+        let with_entry = |entry: &str| {
+            let text = cube("LUT_3D_SIZE 2\n");
+            let (_, rest) = text.split_once('\n').unwrap();
+            let (_, rest) = rest.split_once('\n').unwrap();
+            format!("LUT_3D_SIZE 2\n{}\n{}", entry, rest)
+        };
+        assert!(CubeLut::parse(&with_entry("0 0 0")).is_ok());
+        assert!(CubeLut::parse(&with_entry("1e39 0 0")).is_err());
+        assert!(CubeLut::parse(&with_entry("0 inf 0")).is_err());
+        assert!(CubeLut::parse(&with_entry("0 0 NaN")).is_err());
+        assert!(CubeLut::parse(&cube("LUT_3D_SIZE 2\nDOMAIN_MAX 1 1 1e39\n")).is_err());
+        assert!(CubeLut::parse(&cube("LUT_3D_SIZE 2\nDOMAIN_MIN NaN 0 0\n")).is_err());
+    }
+}
@@ -1,315 +1,550 @@
 extern crate gstreamer as gst;
 extern crate gstreamer_app as gst_app;
+extern crate gstreamer_pbutils as gst_pbutils;
 extern crate gstreamer_video as gst_video;
 
-use crossbeam_channel::bounded;
-use egui::FontDefinitions;
-use egui_wgpu_backend::{RenderPass, ScreenDescriptor};
-use egui_winit_platform::{Platform, PlatformDescriptor};
-use gst_video::VideoInfo;
-use media_decoder::MediaDecoder;
-use renderer::{VideoRenderer, INDICES};
+use anyhow::{anyhow, Error};
+use args::Args;
+#[cfg(feature = "ui")]
+use commands::Action;
+use config::{GpuBackend, GpuSettings};
+#[cfg(feature = "network")]
+use dlna_renderer::{DlnaRenderer, RendererCommand};
+#[cfg(feature = "ui")]
+use gamepad::GamepadButton;
+use gpu::Gpu;
+#[cfg(feature = "library")]
+use library::Library;
+use media_decoder::PlayerEvent;
+use media_keys::MediaKey;
+#[cfg(feature = "ui")]
+use media_keys::MediaKeys;
+use player_window::{DecoderId, PlayerWindow};
+use single_instance::HandOff;
+#[cfg(feature = "ui")]
+use sleep_inhibitor::SleepInhibitor;
+#[cfg(feature = "ui")]
+use tray::{Tray, TrayCommand, TrayState};
 
 use std::{
+    cell::RefCell,
+    rc::Rc,
     sync::{Arc, Mutex},
-    time::Instant,
-    u8,
 };
-use tokio::sync::oneshot;
 use winit::{
-    dpi::PhysicalSize,
+    dpi::{PhysicalPosition, PhysicalSize},
     event::{Event, WindowEvent},
     event_loop::{ControlFlow, EventLoopBuilder},
+    window::WindowId,
 };
 
+mod adaptive;
+mod ambilight;
+#[cfg(feature = "ui")]
+mod animation;
 mod app;
+mod args;
+mod audio_extract;
+mod audio_filters;
+#[cfg(feature = "audio")]
+mod audio_output;
+mod clip_export;
+mod command_palette;
+mod commands;
+mod compare;
+mod config;
+mod controls;
+#[cfg(feature = "network")]
+mod cookies;
+mod cover_art;
+mod cover_flow;
+mod cue;
+mod custom_pipeline;
+mod disc;
+#[cfg(feature = "network")]
+mod dlna;
+#[cfg(all(feature = "ui", feature = "network"))]
+mod dlna_browser;
+#[cfg(feature = "network")]
+mod dlna_renderer;
+#[cfg(feature = "audio")]
+mod drift;
+mod error;
+mod frame_export;
+mod frame_queue;
+#[cfg(feature = "ui")]
+mod gamepad;
+mod gpu;
+mod interpolation;
+mod jobs;
+mod languages;
+mod letterbox;
+#[cfg(feature = "library")]
+mod library;
+mod logging;
+mod lut;
 mod media_decoder;
+mod media_keys;
+#[cfg(feature = "ndi")]
+mod ndi;
+mod player_window;
+mod playlist;
+mod playlist_file;
+mod postprocess;
+#[cfg(feature = "network")]
+mod proxy;
+mod recorder;
 mod renderer;
+mod single_instance;
+#[cfg(feature = "ui")]
+mod sleep_inhibitor;
+mod spherical;
+mod stats;
+mod subtitle_overlay;
+mod subtitles;
+#[cfg(windows)]
+mod taskbar;
 mod texture;
-
+#[cfg(feature = "ui")]
+mod thumbnailer;
+mod toasts;
+mod transcode;
+#[cfg(feature = "ui")]
+mod tray;
+mod user_shader;
+mod video_window;
+
+/// Events of a decoder are tagged with the window of its player, see `PlayerWindow`
 #[derive(Debug)]
-enum UserEvent {
-    NewFrameReady(Vec<u8>),
-    RequestRedraw,
+pub enum UserEvent {
+    /// Frames are waiting in the frame queue
+    NewFrameReady(DecoderId),
+    VideoSizeChanged(DecoderId, PhysicalSize<u32>),
+    Player(DecoderId, PlayerEvent),
+    HandOff(HandOff),
+    /// From the taskbar buttons of a window, or None for the media keys
+    MediaKey(Option<WindowId>, MediaKey),
+    #[cfg(feature = "ui")]
+    Tray(TrayCommand),
+    #[cfg(feature = "ui")]
+    Gamepad(GamepadButton),
+    /// From a DLNA control point on the network
+    #[cfg(feature = "network")]
+    Renderer(RendererCommand),
 }
 
-struct ExampleRepaintSignal(std::sync::Mutex<winit::event_loop::EventLoopProxy<UserEvent>>);
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let args = Args::parse(std::env::args().skip(1));
+    #[cfg(feature = "ui")]
+    let log_buffer = logging::init(args.log_level.as_deref());
+    #[cfg(not(feature = "ui"))]
+    logging::init(args.log_level.as_deref());
+
+    let saved_config = config::Config::load();
+    let single_instance =
+        !args.new_window && !args.needs_own_instance() && !saved_config.multiple_instances;
+    if single_instance && !args.uris.is_empty() {
+        let hand_off = HandOff {
+            uris: args.uris.clone(),
+            enqueue: args.enqueue,
+        };
+        if single_instance::hand_off(&hand_off) {
+            tracing::info!("Passed the files on to the running player");
+            return;
+        }
+    }
 
-impl epi::backend::RepaintSignal for ExampleRepaintSignal {
-    fn request_repaint(&self) {
-        self.0
-            .lock()
-            .unwrap()
-            .send_event(UserEvent::RequestRedraw)
-            .ok();
+    let mut gpu_settings = saved_config.gpu.clone();
+    if let Some(backend) = &args.backend {
+        match GpuBackend::parse(backend) {
+            Some(backend) => gpu_settings.backend = backend,
+            None => tracing::warn!("Unknown backend {}", backend),
+        }
+    }
+    if args.gpu.is_some() {
+        gpu_settings.adapter = args.gpu.clone();
     }
-}
 
-#[tokio::main(flavor = "current_thread")]
-async fn main() {
-    let event_loop = EventLoopBuilder::<UserEvent>::with_user_event().build();
-    let window = winit::window::WindowBuilder::new()
-        .with_inner_size(winit::dpi::LogicalSize::new(1280, 720))
-        .with_title("wgpu-media-player")
-        .build(&event_loop)
-        .unwrap();
+    let instance = gpu::instance(gpu_settings.backend);
+    if args.list_gpus {
+        for (index, adapter) in instance
+            .enumerate_adapters(gpu::backends(gpu_settings.backend))
+            .enumerate()
+        {
+            println!("{}: {}", index, gpu::adapter_label(&adapter.get_info()));
+        }
+        return;
+    }
 
-    let size = window.inner_size();
+    // files to open replace the last session
+    let session = saved_config
+        .restore_session
+        .then(|| saved_config.session.clone())
+        .flatten()
+        .filter(|_| args.uris.is_empty());
 
-    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-        backends: wgpu::Backends::all(),
-        ..Default::default()
-    });
+    let event_loop = EventLoopBuilder::<UserEvent>::with_user_event().build();
+    let mut window_builder = winit::window::WindowBuilder::new()
+        .with_inner_size(winit::dpi::LogicalSize::new(1280, 720))
+        .with_title("wgpu-media-player");
+    if let Some(geometry) = session.as_ref().and_then(|session| session.window) {
+        window_builder = window_builder
+            .with_inner_size(PhysicalSize::new(geometry.width, geometry.height))
+            .with_maximized(geometry.maximized);
+        // the monitor it was on may have been unplugged since
+        let on_screen = event_loop.available_monitors().any(|monitor| {
+            let (position, size) = (monitor.position(), monitor.size());
+            (position.x..position.x + size.width as i32).contains(&geometry.x)
+                && (position.y..position.y + size.height as i32).contains(&geometry.y)
+        });
+        if on_screen {
+            window_builder =
+                window_builder.with_position(PhysicalPosition::new(geometry.x, geometry.y));
+        }
+    }
+    let window = window_builder.build(&event_loop).unwrap();
 
     let surface = unsafe { instance.create_surface(&window) }.unwrap();
-    let adapter = instance
-        .request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::HighPerformance,
-            force_fallback_adapter: false,
-            // Request an adapter which can render to our surface
-            compatible_surface: Some(&surface),
-        })
+    let mut gpu = Gpu::new(instance, &surface, gpu_settings)
         .await
-        .expect("Failed to find an appropriate adapter");
-
-    // Create the logical device and command queue
-    let (device, queue) = adapter
-        .request_device(
-            &wgpu::DeviceDescriptor {
-                label: None,
-                features: wgpu::Features::empty(),
-                // Make sure we use the texture resolution limits from the adapter, so we can support images the size of the swapchain.
-                limits: wgpu::Limits::default(),
-            },
-            None,
-        )
-        .await
-        .expect("Failed to create device");
-
-    let swapchain_capabilities = surface.get_capabilities(&adapter);
-    let swapchain_format = swapchain_capabilities.formats[0];
-
-    let config = wgpu::SurfaceConfiguration {
-        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-        format: swapchain_format,
-        width: size.width,
-        height: size.height,
-        present_mode: wgpu::PresentMode::Fifo,
-        alpha_mode: wgpu::CompositeAlphaMode::Auto,
-        view_formats: [swapchain_format].to_vec(),
-    };
-
-    surface.configure(&device, &config);
-
-    // setup egui
-    let mut platform = Platform::new(PlatformDescriptor {
-        physical_width: size.width,
-        physical_height: size.height,
-        scale_factor: window.scale_factor(),
-        font_definitions: FontDefinitions::default(),
-        style: Default::default(),
-    });
-
-    let mut egui_rpass = RenderPass::new(&device, swapchain_format, 1);
-    let mut demo_app = egui_demo_lib::DemoWindows::default();
-
+        .expect("Failed to set up the GPU");
+
+    // every window changes and saves the same settings
+    let config = Rc::new(RefCell::new(saved_config));
+    // sled locks the database, so the windows share it. Only new and changed files are read
+    // again, the stored items are shown meanwhile.
+    #[cfg(feature = "library")]
+    let library = Library::open()
+        .map_err(|err| tracing::warn!("{:#}", err))
+        .ok()
+        .map(|mut library| {
+            let folders = config.borrow().library_folders.clone();
+            if !folders.is_empty() {
+                library.scan(folders);
+            }
+            Rc::new(RefCell::new(library))
+        });
     let repaint_proxy = Arc::new(Mutex::new(event_loop.create_proxy()));
-    let (video_size_sender, video_size_receiver) = oneshot::channel::<PhysicalSize<u32>>();
-    let (load_file_sender, load_file_receiver) = oneshot::channel::<String>();
-
-    std::thread::spawn(move || {
-        let path = load_file_receiver.blocking_recv().unwrap();
-
-        let (video_frame_sender, video_frame_receiver) = bounded::<Vec<u8>>(1);
-        let (video_info_sender, video_info_receiver) = bounded::<VideoInfo>(1);
+    let mut first_player = PlayerWindow::new(
+        window,
+        surface,
+        &gpu,
+        config.clone(),
+        #[cfg(feature = "library")]
+        library.clone(),
+        #[cfg(feature = "ui")]
+        log_buffer.clone(),
+        repaint_proxy.clone(),
+    );
+    // only this launch uses the proxy of the command line, it isn't saved
+    let session_proxy = args
+        .proxy
+        .as_deref()
+        .map(|arg| config.borrow().proxy.with_arg(arg));
+    if let Some(proxy) = &session_proxy {
+        first_player.app_mut().set_session_proxy(proxy.clone());
+    }
+    match session {
+        Some(session) => first_player.app_mut().restore_session(session),
+        None => first_player.app_mut().open(args.uris, args.enqueue),
+    }
+    if let Some(pipeline) = args.pipeline {
+        first_player.app_mut().play_pipeline(pipeline);
+    }
 
-        std::thread::spawn(move || loop {
-            let frame = video_frame_receiver.recv().unwrap();
+    if single_instance {
+        let repaint_proxy = repaint_proxy.clone();
+        let listening = single_instance::listen(move |hand_off| {
             repaint_proxy
                 .lock()
                 .unwrap()
-                .send_event(UserEvent::NewFrameReady(frame))
-                .unwrap();
+                .send_event(UserEvent::HandOff(hand_off))
+                .ok();
         });
+        if let Err(err) = listening {
+            tracing::debug!("Not accepting files from other launches: {}", err);
+        }
+    }
 
-        std::thread::spawn(move || {
-            let info = video_info_receiver.recv().unwrap();
-            video_size_sender
-                .send(PhysicalSize {
-                    width: info.width(),
-                    height: info.height(),
-                })
-                .unwrap();
-        });
-
-        MediaDecoder::new(&path, video_info_sender, video_frame_sender).unwrap();
-
-        // while let Ok(frame) = video_frame_receiver.recv() {
-        //     repaint_proxy
-        //         .lock()
-        //         .unwrap()
-        //         .send_event(UserEvent::NewFrameReady(frame))
-        //         .unwrap();
-        // }
-        // media_decoder.start();
-    });
-
-    let device = Arc::new(device);
-    let config = Arc::new(Mutex::new(config));
-    let renderer = Arc::new(Mutex::new(None));
+    #[cfg(feature = "ui")]
+    let mut media_keys = {
+        let repaint_proxy = repaint_proxy.clone();
+        MediaKeys::new(first_player.window(), move |key| {
+            repaint_proxy
+                .lock()
+                .unwrap()
+                .send_event(UserEvent::MediaKey(None, key))
+                .ok();
+        })
+        .map_err(|err| tracing::warn!("{:#}", err))
+        .ok()
+    };
 
+    #[cfg(feature = "ui")]
     {
-        let device = device.clone();
-        let config = config.clone();
-        let renderer = renderer.clone();
-        let window_inner_size = window.inner_size();
-        std::thread::spawn(move || {
-            let size = video_size_receiver
-                .blocking_recv()
-                .expect("Failed to get initial video size");
-            *renderer.lock().unwrap() = Some(VideoRenderer::new(
-                window_inner_size,
-                size,
-                device,
-                config.lock().unwrap().clone(),
-            ));
+        let repaint_proxy = repaint_proxy.clone();
+        gamepad::listen(move |button| {
+            repaint_proxy
+                .lock()
+                .unwrap()
+                .send_event(UserEvent::Gamepad(button))
+                .ok();
         });
     }
-    let mut app = app::App::new();
-    app.set_on_load_file_request(move |path| {
-        load_file_sender.send(path).unwrap();
-    });
-
-    let start_time = Instant::now();
-    event_loop.run(move |event, _, control_flow| {
-        // Have the closure take ownership of the resources.
-        // `event_loop.run` never returns, therefore we must do this to ensure
-        // the resources are properly cleaned up.
-        platform.handle_event(&event);
-        let _ = (&instance, &adapter);
 
+    #[cfg(feature = "ui")]
+    let mut tray = first_player
+        .app()
+        .tray_icon()
+        .then(|| {
+            let repaint_proxy = repaint_proxy.clone();
+            Tray::new(move |command| {
+                repaint_proxy
+                    .lock()
+                    .unwrap()
+                    .send_event(UserEvent::Tray(command))
+                    .ok();
+            })
+            .map_err(|err| tracing::warn!("Failed to create the tray icon: {:#}", err))
+            .ok()
+        })
+        .flatten();
+
+    #[cfg(feature = "network")]
+    let mut dlna_renderer =
+        first_player
+            .app_mut()
+            .dlna_renderer_identity()
+            .and_then(|(name, uuid)| {
+                let repaint_proxy = repaint_proxy.clone();
+                DlnaRenderer::start(&name, &uuid, move |command| {
+                    repaint_proxy
+                        .lock()
+                        .unwrap()
+                        .send_event(UserEvent::Renderer(command))
+                        .ok();
+                })
+                .map_err(|err| tracing::warn!("{:#}", err))
+                .ok()
+            });
+
+    #[cfg(feature = "ui")]
+    let mut sleep_inhibitor = SleepInhibitor::default();
+    // the media keys, the tray, DLNA control points and files passed on by other launches go to
+    // the player that had focus last
+    let mut focused = first_player.id();
+    let mut players = vec![first_player];
+
+    event_loop.run(move |event, target, control_flow| {
         *control_flow = ControlFlow::Wait;
 
-        match event {
-            Event::WindowEvent { event, .. } => {
-                if matches!(event, WindowEvent::CloseRequested | WindowEvent::Destroyed) {
-                    *control_flow = ControlFlow::Exit;
+        match &event {
+            Event::WindowEvent { window_id, .. } | Event::RedrawRequested(window_id) => {
+                let Some(index) = players.iter().position(|player| player.owns(*window_id)) else {
+                    // left over events of a window that was just closed
+                    return;
+                };
+                if let Event::WindowEvent {
+                    event: WindowEvent::Focused(true),
+                    ..
+                } = event
+                {
+                    focused = players[index].id();
+                }
+                #[cfg(feature = "ui")]
+                let tray_available = tray.is_some();
+                #[cfg(not(feature = "ui"))]
+                let tray_available = false;
+                if !players[index].handle_event(&gpu, &event, tray_available) {
+                    let mut player = players.remove(index);
+                    // the last window that closes leaves the session behind
+                    if players.is_empty() {
+                        player.save_session();
+                    }
+                    player.shut_down();
+                    if players.is_empty() {
+                        *control_flow = ControlFlow::Exit;
+                    } else if focused == *window_id {
+                        focused = players[0].id();
+                    }
                 }
+                return;
+            }
+            _ => {}
+        }
 
-                if let WindowEvent::Resized(size) = &event {
-                    config.lock().unwrap().width = size.width;
-                    config.lock().unwrap().height = size.height;
-                    surface.configure(&device, &config.lock().unwrap());
+        let focused_player = players.iter().position(|player| player.id() == focused);
+        match event {
+            Event::MainEventsCleared => {
+                let gpu_settings = players
+                    .iter_mut()
+                    .find_map(|player| player.app_mut().take_gpu_settings());
+                if let Some(settings) = gpu_settings {
+                    match move_to_gpu(&mut players, settings) {
+                        Ok(new_gpu) => gpu = new_gpu,
+                        Err(err) => tracing::error!("Failed to switch the GPU: {:#}", err),
+                    }
+                } else if gpu.is_lost() {
+                    tracing::warn!("The GPU device was lost, creating it again");
+                    match move_to_gpu(&mut players, gpu.settings.clone()) {
+                        Ok(new_gpu) => gpu = new_gpu,
+                        Err(err) => tracing::error!("Failed to recreate the GPU device: {:#}", err),
+                    }
+                }
 
-                    if let Some(renderer) = renderer.lock().unwrap().as_mut() {
-                        renderer.handle_resize(&device, *size);
+                let mut new_windows = Vec::new();
+                for player in &mut players {
+                    player.update(target, &gpu);
+                    new_windows.extend(player.app_mut().take_new_window());
+                }
+                for uris in new_windows {
+                    match PlayerWindow::open(
+                        target,
+                        &gpu,
+                        config.clone(),
+                        #[cfg(feature = "library")]
+                        library.clone(),
+                        #[cfg(feature = "ui")]
+                        log_buffer.clone(),
+                        repaint_proxy.clone(),
+                    ) {
+                        Ok(mut player) => {
+                            if let Some(proxy) = &session_proxy {
+                                player.app_mut().set_session_proxy(proxy.clone());
+                            }
+                            player.app_mut().open(uris, false);
+                            players.push(player);
+                        }
+                        Err(err) => tracing::warn!("Failed to open a new window: {:#}", err),
                     }
+                }
 
-                    // On macos the window needs to be redrawn manually after resizing
-                    window.request_redraw();
-                } else if let WindowEvent::ScaleFactorChanged {
-                    new_inner_size: size,
-                    ..
-                } = &event
+                #[cfg(feature = "ui")]
+                sleep_inhibitor
+                    .set_inhibited(players.iter().any(|player| player.app().inhibit_sleep()));
+                // the media keys, the tray and the DLNA renderer report on the focused player
+                #[cfg(any(feature = "ui", feature = "network"))]
                 {
-                    config.lock().unwrap().width = size.width;
-                    config.lock().unwrap().height = size.height;
-                    surface.configure(&device, &config.lock().unwrap());
-
-                    if let Some(renderer) = renderer.lock().unwrap().as_mut() {
-                        renderer.handle_resize(&device, **size);
+                    let Some(player) = focused_player.map(|index| &players[index]) else {
+                        return;
+                    };
+                    let app = player.app();
+                    #[cfg(feature = "ui")]
+                    if let Some(media_keys) = media_keys.as_mut() {
+                        media_keys.set_playing(app.now_playing(), app.paused());
+                    }
+                    #[cfg(feature = "network")]
+                    if let Some(renderer) = dlna_renderer.as_ref() {
+                        renderer.set_state(app.renderer_state());
+                    }
+                    #[cfg(feature = "ui")]
+                    if let Some(tray) = tray.as_mut() {
+                        tray.set_state(TrayState {
+                            title: app.now_playing().map(|(title, _)| title.to_string()),
+                            paused: app.paused(),
+                            muted: app.muted(),
+                        });
                     }
-
-                    // On macos the window needs to be redrawn manually after resizing
-                    window.request_redraw();
                 }
-
-                app.handle_window_event(&event);
             }
-            Event::MainEventsCleared | Event::UserEvent(UserEvent::RequestRedraw) => {
-                window.request_redraw();
+            Event::UserEvent(UserEvent::NewFrameReady(id)) => {
+                if let Some(player) = players.iter_mut().find(|player| player.id() == id.window) {
+                    player.new_frame_ready(&gpu, id.compare);
+                }
             }
-            Event::RedrawRequested(_) => {
-                platform.update_time(start_time.elapsed().as_secs_f64());
-
-                let frame = surface
-                    .get_current_texture()
-                    .expect("Failed to acquire next swap chain texture");
-                let view = frame
-                    .texture
-                    .create_view(&wgpu::TextureViewDescriptor::default());
-                let mut encoder =
-                    device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-
-                {
-                    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                        label: None,
-                        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                            view: &view,
-                            resolve_target: None,
-                            ops: wgpu::Operations {
-                                load: wgpu::LoadOp::Load,
-                                store: true,
-                            },
-                        })],
-                        depth_stencil_attachment: None,
-                    });
-
-                    if let Some(renderer) = renderer.lock().unwrap().as_mut() {
-                        // im not going to bother -> https://github.com/gfx-rs/wgpu/issues/1453
-                        render_pass.set_pipeline(&renderer.render_pipeline);
-                        render_pass.set_bind_group(0, &renderer.bind_group, &[]);
-                        render_pass.set_vertex_buffer(0, renderer.vertex_buffer.slice(..));
-                        render_pass.set_index_buffer(
-                            renderer.index_buffer.slice(..),
-                            wgpu::IndexFormat::Uint16,
-                        );
-                        render_pass.draw_indexed(0..INDICES.len() as u32, 0, 0..1);
-                    }
+            Event::UserEvent(UserEvent::VideoSizeChanged(id, size)) => {
+                if let Some(player) = players.iter_mut().find(|player| player.id() == id.window) {
+                    player.set_video_size(&gpu, id.compare, size);
                 }
-
-                // Begin to draw the UI frame.
-                platform.begin_frame();
-
-                // Draw the demo application.
-                demo_app.ui(&platform.context());
-
-                let full_output = platform.end_frame(Some(&window));
-                let paint_jobs = platform.context().tessellate(full_output.shapes);
-
-                // Upload all resources for the GPU.
-                let width = config.lock().unwrap().width;
-                let height = config.lock().unwrap().height;
-                let screen_descriptor = ScreenDescriptor {
-                    physical_width: width,
-                    physical_height: height,
-                    scale_factor: window.scale_factor() as f32,
+            }
+            Event::UserEvent(UserEvent::Player(id, event)) => {
+                if let Some(player) = players.iter_mut().find(|player| player.id() == id.window) {
+                    player.handle_player_event(&gpu, id.compare, event);
+                }
+            }
+            Event::UserEvent(UserEvent::HandOff(hand_off)) => {
+                if let Some(index) = focused_player {
+                    players[index]
+                        .app_mut()
+                        .open(hand_off.uris, hand_off.enqueue);
+                    players[index].window().focus_window();
+                }
+            }
+            Event::UserEvent(UserEvent::MediaKey(id, key)) => {
+                let index = id
+                    .and_then(|id| players.iter().position(|player| player.id() == id))
+                    .or(focused_player);
+                match (index, key) {
+                    (Some(index), MediaKey::Raise) => players[index].show(),
+                    (Some(index), key) => players[index].app_mut().handle_media_key(key),
+                    (None, _) => {}
+                }
+            }
+            #[cfg(feature = "ui")]
+            Event::UserEvent(UserEvent::Gamepad(button)) => {
+                if let Some(index) = focused_player {
+                    players[index].app_mut().handle_gamepad_button(button);
+                }
+            }
+            #[cfg(feature = "network")]
+            Event::UserEvent(UserEvent::Renderer(command)) => {
+                if let Some(index) = focused_player {
+                    players[index].app_mut().handle_renderer_command(command);
+                }
+            }
+            #[cfg(feature = "ui")]
+            Event::UserEvent(UserEvent::Tray(TrayCommand::Quit)) => {
+                *control_flow = ControlFlow::Exit;
+            }
+            #[cfg(feature = "ui")]
+            Event::UserEvent(UserEvent::Tray(command)) => {
+                let Some(player) = focused_player.map(|index| &mut players[index]) else {
+                    return;
                 };
-                let tdelta: egui::TexturesDelta = full_output.textures_delta;
-                egui_rpass
-                    .add_textures(&device, &queue, &tdelta)
-                    .expect("add texture ok");
-                egui_rpass.update_buffers(&device, &queue, &paint_jobs, &screen_descriptor);
-
-                // Record all render passes.
-                egui_rpass
-                    .execute(&mut encoder, &view, &paint_jobs, &screen_descriptor, None)
-                    .unwrap();
-
-                queue.submit(Some(encoder.finish()));
-                frame.present();
-
-                egui_rpass
-                    .remove_textures(tdelta)
-                    .expect("remove texture ok");
+                match command {
+                    TrayCommand::TogglePause => player.app_mut().execute(Action::TogglePause),
+                    TrayCommand::Previous => player.app_mut().execute(Action::PreviousItem),
+                    TrayCommand::Next => player.app_mut().execute(Action::NextItem),
+                    TrayCommand::ToggleMute => player.app_mut().execute(Action::ToggleMute),
+                    TrayCommand::ShowWindow => player.show(),
+                    TrayCommand::HideWindow => player.hide(),
+                    // handled above, it ends every player
+                    TrayCommand::Quit => {}
+                }
             }
-            Event::UserEvent(UserEvent::NewFrameReady(data)) => {
-                if let Some(renderer) = renderer.lock().unwrap().as_mut() {
-                    renderer.new_frame(&queue, &data);
+            Event::LoopDestroyed => {
+                // control points forget the renderer right away instead of when it times out
+                #[cfg(feature = "network")]
+                drop(dlna_renderer.take());
+                // quit from the tray with windows open
+                if let Some(index) = focused_player {
+                    players[index].save_session();
+                }
+                // stop the pipelines and the audio outputs before the process exits
+                for player in players.drain(..) {
+                    player.shut_down();
                 }
-                window.request_redraw();
             }
             _ => {}
         }
     });
 }
+
+/// Opens the GPU of `settings` and moves every window over to it, they keep using the current
+/// one when it can't be opened
+fn move_to_gpu(players: &mut [PlayerWindow], settings: GpuSettings) -> Result<Gpu, Error> {
+    let instance = gpu::instance(settings.backend);
+    let surfaces = players
+        .iter()
+        .map(|player| unsafe { instance.create_surface(player.window()) })
+        .collect::<Result<Vec<_>, _>>()?;
+    let Some(first_surface) = surfaces.first() else {
+        return Err(anyhow!("there is no window to draw to"));
+    };
+    let gpu = gpu::block_on(Gpu::new(instance, first_surface, settings))?;
+    for (player, surface) in players.iter_mut().zip(surfaces) {
+        player.set_gpu(&gpu, surface);
+    }
+    Ok(gpu)
+}
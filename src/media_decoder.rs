@@ -1,167 +1,2356 @@
+#[cfg(feature = "ui")]
+use std::path::Path;
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
 use anyhow::Error;
-use byte_slice_cast::AsSliceOf;
-use cpal::{traits::StreamTrait, Stream};
-use crossbeam_channel::Sender;
-use gst::prelude::*;
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender, TrySendError};
+use gst::{glib, prelude::*};
 use gstreamer_video::VideoInfo;
-use ringbuf::{HeapConsumer, HeapRb};
 
-pub struct MediaDecoder;
+#[cfg(feature = "ui")]
+use crate::animation::{self, Animation};
+#[cfg(feature = "audio")]
+use crate::audio_output::AudioOutput;
+#[cfg(feature = "ndi")]
+use crate::ndi;
+use crate::{
+    adaptive::{self, Variant},
+    audio_filters::AudioFilters,
+    config::{
+        AudioSettings, CacheSettings, CaptionChannel, CaptionSettings, Credentials, HttpOptions,
+        IngestSettings, LanguagePreferences, ProxySettings, RtspSettings, SeekMode, SubtitleStyle,
+        Visualizer,
+    },
+    cover_art::{self, CoverArt},
+    custom_pipeline::{CustomPipeline, PipelineDescription},
+    disc::{self, DiscNavigation, DiscPosition},
+    error::{self, PlayerError},
+    frame_queue::{Frame, FrameQueue, PixelFormat, SubtitleBitmap, SubtitleBitmaps, Transfer},
+    languages,
+    recorder::StreamRecorder,
+    spherical::{self, VideoLayout},
+    stats::DecoderStats,
+};
+#[cfg(feature = "network")]
+use crate::{cookies, proxy};
+
+const STATS_INTERVAL: Duration = Duration::from_millis(500);
+/// Seeks that jump less than this are frame exact regardless of the seek mode
+pub const SHORT_SEEK: Duration = Duration::from_secs(30);
+const POSITION_INTERVAL: Duration = Duration::from_millis(200);
+/// `-` on the command line, playbin reads it with fdsrc and decodebin like any other source
+pub const STDIN_URI: &str = "fd://0";
+/// Prefix of the download buffer files, used to find leftovers when clearing the cache
+#[cfg(any(feature = "ui", feature = "network"))]
+pub const CACHE_FILE_PREFIX: &str = "download-";
+/// Resolution the visualizers render at
+const VISUALIZER_WIDTH: i32 = 1280;
+const VISUALIZER_HEIGHT: i32 = 720;
+/// Uri prefixes of contribution feeds, the sources that read them and where to get those
+#[cfg(feature = "network")]
+const INGEST_SOURCES: [(&str, &[&str], &str); 3] = [
+    ("srt://", &["srtsrc"], "the srt plugin of gst-plugins-bad"),
+    (
+        "rtmp://",
+        &["rtmp2src", "rtmpsrc"],
+        "the rtmp2 plugin of gst-plugins-bad",
+    ),
+    (
+        "rtmps://",
+        &["rtmp2src"],
+        "the rtmp2 plugin of gst-plugins-bad",
+    ),
+];
+/// Jitter buffer size used by the rtsp low latency mode
+#[cfg(feature = "network")]
+const RTSP_LOW_LATENCY_MS: u32 = 50;
+
+const MAX_RECONNECT_ATTEMPTS: u32 = 6;
+/// Doubled after every failed attempt
+const RECONNECT_BASE_DELAY: Duration = Duration::from_millis(500);
+/// Playing this long without errors starts the attempts over
+const RECONNECT_RESET: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+pub enum DecoderCommand {
+    /// Stops whatever is playing and starts playing the given uri
+    Load(String),
+    /// Stops whatever is playing and starts a pipeline of the user, see `CustomPipeline`
+    PlayPipeline(PipelineDescription),
+    /// Drops the video branch of playbin so only audio is decoded
+    SetAudioOnly(bool),
+    /// Shown instead of the video when the media only has audio
+    SetVisualizer(Visualizer),
+    /// Uri of an external, UTF-8 encoded subtitle file for the current media
+    SetSubtitle(String),
+    /// Shows the closed captions of the video stream
+    SetCaptions(CaptionSettings),
+    /// Changes how text subtitles look, applies to the current file as well
+    SetSubtitleStyle(SubtitleStyle),
+    /// Picks the audio and subtitle streams of the uris that are loaded after this
+    SetLanguagePreferences(LanguagePreferences),
+    /// Where the uri that is loaded next starts playing, instead of the beginning
+    SetStartPosition(Duration),
+    /// Frame exact or keyframe seek, depending on the seek mode and the distance
+    Seek(Duration),
+    /// Frame exact seek whatever the seek mode, where a drag of the seek bar was released
+    SeekExact(Duration),
+    /// Fast seek to the nearest keyframe, sent continuously while the seek bar is dragged
+    Scrub(Duration),
+    SetPaused(bool),
+    SetSeekMode(SeekMode),
+    /// Playback speed of the current media, negative values play backwards
+    SetRate(f64),
+    /// Pauses and shows the next frame, or the previous one when `backward` is set
+    StepFrame {
+        backward: bool,
+    },
+    /// Positive values delay the video, negative values delay the audio. Added on top of the
+    /// output delay of the audio settings.
+    SetAvOffset(i64),
+    /// Used for the rtsp:// streams that are loaded after this
+    SetRtspSettings(RtspSettings),
+    /// Used for the srt:// and rtmp:// streams that are loaded after this
+    SetIngestSettings(IngestSettings),
+    /// The proxy of the http streams that are loaded after this
+    SetProxySettings(ProxySettings),
+    /// The login for the server of the uri that is loaded next, if it asks for one
+    SetCredentials(Option<Credentials>),
+    /// Caps the bitrate of HLS/DASH streams in bits per second, None lets the demuxer decide
+    #[cfg(feature = "ui")]
+    SetMaxBitrate(Option<u64>),
+    SetAudioSettings(AudioSettings),
+    /// Used for the http streams that are loaded after this
+    SetCacheSettings(CacheSettings),
+    /// Start over instead of stopping at the end, kept for the files loaded after this
+    SetLoopFile(bool),
+    /// Keeps playing between the two positions of the current media
+    SetAbLoop(Option<(Duration, Duration)>),
+    /// Extra headers, user-agent and cookies of the http requests for the uri that is loaded
+    /// next
+    SetHttpOptions(HttpOptions),
+    /// Shifts the subtitles, positive values show them later
+    SetSubtitleDelay(i64),
+    /// Plays audio stream `index` of the current media, once the media has that many
+    SetAudioStream(usize),
+    /// Copies the streams of the current media into a Matroska file from the next keyframe on
+    StartRecording(PathBuf),
+    StopRecording,
+    /// Stops trying to reconnect to a dropped stream
+    #[cfg(feature = "ui")]
+    CancelReconnect,
+    /// Runs the pipeline on the clock of another player, so both show the same running time.
+    /// None lets the pipeline pick its own clock again for the uri that is loaded next.
+    FollowClock(Option<SharedClock>),
+    /// Moves around the menus of a DVD or Blu-ray
+    Navigate(DiscNavigation),
+    /// Jumps to a title of the disc, counting from 0
+    #[cfg(feature = "ui")]
+    SelectTitle(u32),
+    /// Jumps to a chapter of the current title of the disc, counting from 0
+    #[cfg(feature = "ui")]
+    SelectChapter(u32),
+    /// Tears down the pipeline and the audio output and makes `run` return
+    Shutdown,
+}
+
+/// Things the decoder reports back to the ui
+#[derive(Debug)]
+pub enum PlayerEvent {
+    Stats(DecoderStats),
+    /// The pipeline is stalled until its buffers are filled up to 100%
+    Buffering(i32),
+    /// `generation` counts the uris that were loaded, the positions of the previous one can
+    /// still be queued after the next `DecoderCommand::Load`
+    Position {
+        generation: u64,
+        position: Duration,
+        duration: Option<Duration>,
+    },
+    /// Downloaded parts of a network stream, as fractions of the whole media
+    BufferedRanges(Vec<(f32, f32)>),
+    /// Quality levels of an HLS/DASH stream, highest bandwidth first
+    Variants(Vec<Variant>),
+    /// Index into the variants of the one that is currently playing
+    ActiveVariant(Option<usize>),
+    /// A network stream dropped and is about to be reconnected
+    Reconnecting {
+        attempt: u32,
+        max_attempts: u32,
+    },
+    /// Every reconnect attempt failed
+    ReconnectFailed,
+    Tags(MediaTags),
+    /// Something went wrong, playback stopped unless the stream is being reconnected
+    Error(PlayerError),
+    /// Shown instead of the video, for music files without a video stream
+    CoverArt(CoverArt),
+    /// A still image was loaded, it is shown in place of the video until the next uri
+    Image(CoverArt),
+    /// The pipeline started playing on this clock, see `DecoderCommand::FollowClock`
+    Clock(SharedClock),
+    /// The title and chapter of a DVD or Blu-ray, sent with the position
+    Disc(DiscPosition),
+    /// The file the current media is being recorded to, None once the recording is saved
+    Recording(Option<PathBuf>),
+    /// Index of the audio stream that is playing, among the audio streams of the media
+    AudioStream(Option<usize>),
+    /// How many audio streams the media has
+    AudioStreams(usize),
+    /// How many video streams the media has, sent once it plays. Music has none, the
+    /// visualizer or the cover art is shown instead.
+    VideoStreams(usize),
+    /// How the frames of the new uri are laid out, sent when it starts playing
+    Layout(VideoLayout),
+    /// The source refused to seek back to the start of the loop, the A-B loop and looping the
+    /// file were turned off
+    LoopCleared,
+}
+
+/// A clock and the base time a pipeline runs on, the running time is the clock time minus the
+/// base time
+#[derive(Debug, Clone)]
+pub struct SharedClock {
+    pub clock: gst::Clock,
+    pub base_time: gst::ClockTime,
+}
+
+/// The tags of the current media that are worth showing
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MediaTags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    /// Name of an internet radio station, its title is the song that is playing right now
+    pub station: Option<String>,
+}
+
+impl MediaTags {
+    /// Keeps the tags that were already known, the streams of a file each send their own.
+    /// SHOUTcast and Icecast streams send the StreamTitle of their ICY metadata as the title
+    /// whenever the song changes, and their icy-name header as the organization.
+    fn merge(&mut self, tags: &gst::TagListRef, is_network_stream: bool) {
+        if let Some(title) = tags.get::<gst::tags::Title>() {
+            self.title = Some(title.get().to_string());
+        }
+        if let Some(artist) = tags.get::<gst::tags::Artist>() {
+            self.artist = Some(artist.get().to_string());
+        }
+        if let Some(album) = tags.get::<gst::tags::Album>() {
+            self.album = Some(album.get().to_string());
+        }
+        // the record label in files
+        if let Some(station) = tags
+            .get::<gst::tags::Organization>()
+            .filter(|_| is_network_stream)
+        {
+            self.station = Some(station.get().to_string());
+        }
+    }
+}
+
+/// What the decoder plays, see `DecoderCommand::Load` and `DecoderCommand::PlayPipeline`
+enum Media {
+    Uri(String),
+    Pipeline(PipelineDescription),
+}
+
+impl Media {
+    /// For the log
+    fn name(&self) -> &str {
+        match self {
+            Media::Uri(uri) => uri,
+            Media::Pipeline(pipeline) => pipeline.as_str(),
+        }
+    }
+}
+
+/// Why `MediaDecoder::play` returned
+enum PlaybackEnd {
+    /// Reached the end, or failed in a way reconnecting won't fix
+    Finished,
+    /// Another uri or pipeline was loaded in the meantime
+    Load(Media),
+    /// A network stream failed, with the position to resume at
+    NetworkError(Option<gst::ClockTime>),
+    Shutdown,
+}
+
+/// What ended the wait before reconnecting
+enum ReconnectWait {
+    Elapsed,
+    #[cfg(feature = "ui")]
+    Cancelled,
+    Load(Media),
+    Closed,
+}
+
+/// Puts a pipeline back to Null however playback ends, also when it fails halfway through.
+/// Dropped in another state it keeps its streaming threads, devices and connections.
+struct StopOnDrop(gst::Element);
+
+impl Drop for StopOnDrop {
+    fn drop(&mut self) {
+        if let Err(err) = self.0.set_state(gst::State::Null) {
+            tracing::warn!("Failed to stop the pipeline: {}", err);
+        }
+    }
+}
+
+/// Updated from the appsink callbacks, read when sending stats
+#[derive(Default)]
+struct FrameCounters {
+    decoded_frames: AtomicU64,
+    /// Nanoseconds, u64::MAX when no frame has been decoded yet
+    last_video_pts: AtomicU64,
+}
+
+/// Passes the samples of the video appsink on to the renderer
+struct FrameForwarder {
+    video_info_sender: Sender<VideoInfo>,
+    frame_queue: Arc<FrameQueue>,
+    /// Wakes up the event loop, holds at most one pending notification
+    frame_ready_sender: Sender<()>,
+    counters: Arc<FrameCounters>,
+    /// Size of the last `VideoInfo` sent, a new one is sent whenever the caps change size
+    sent_size: Mutex<Option<(u32, u32)>>,
+    /// The last subtitle bitmaps, converted once for all the frames they are shown on
+    subtitles: Mutex<Option<Arc<SubtitleBitmaps>>>,
+}
+
+impl FrameForwarder {
+    /// `decoded` is false for prerolled samples, which are handed out again once playing
+    fn forward(
+        &self,
+        sample: &gst::Sample,
+        decoded: bool,
+    ) -> Result<gst::FlowSuccess, gst::FlowError> {
+        let info = sample
+            .caps()
+            .and_then(|caps| gst_video::VideoInfo::from_caps(caps).ok())
+            .ok_or(gst::FlowError::NotNegotiated)?;
+        {
+            let mut sent_size = self.sent_size.lock().unwrap();
+            let size = (info.width(), info.height());
+            if *sent_size != Some(size) {
+                self.video_info_sender
+                    .send(info.clone())
+                    .map_err(|_| gst::FlowError::Flushing)?;
+                *sent_size = Some(size);
+            }
+        }
+
+        let buffer = sample.buffer().ok_or(gst::FlowError::Error)?;
+        if decoded {
+            self.counters.decoded_frames.fetch_add(1, Ordering::Relaxed);
+        }
+        if let Some(pts) = buffer.pts() {
+            self.counters
+                .last_video_pts
+                .store(pts.nseconds(), Ordering::Relaxed);
+        }
+
+        // decoders pad the rows, the video meta of the buffer has the real stride and offset
+        let frame = gst_video::VideoFrameRef::from_buffer_ref_readable(buffer, &info)
+            .map_err(|_| gst::FlowError::Error)?;
+        let data = frame.plane_data(0).map_err(|_| gst::FlowError::Error)?;
+        self.frame_queue.push(Frame {
+            data: data.to_vec(),
+            width: info.width(),
+            height: info.height(),
+            stride: frame.plane_stride()[0] as u32,
+            format: match info.format() {
+                gst_video::VideoFormat::Rgb10a2Le => PixelFormat::Rgb10a2,
+                _ => PixelFormat::Rgba8,
+            },
+            // videoconvert keeps the transfer function, the shader decodes it
+            transfer: match info.colorimetry().transfer() {
+                gst_video::VideoTransferFunction::Smpte2084 => Transfer::Pq,
+                gst_video::VideoTransferFunction::AribStdB67 => Transfer::Hlg,
+                _ => Transfer::Sdr,
+            },
+            subtitles: self.subtitles(buffer),
+        });
+        // when it is full the event loop hasn't picked up the previous notification yet
+        if let Err(TrySendError::Disconnected(_)) = self.frame_ready_sender.try_send(()) {
+            return Err(gst::FlowError::Flushing);
+        }
+        Ok(gst::FlowSuccess::Ok)
+    }
+
+    /// The subtitles the overlay attached to `buffer` instead of blending them into it
+    fn subtitles(&self, buffer: &gst::BufferRef) -> Option<Arc<SubtitleBitmaps>> {
+        let meta = buffer.meta::<gst_video::VideoOverlayCompositionMeta>()?;
+        let composition = meta.overlay();
+        let mut subtitles = self.subtitles.lock().unwrap();
+        if let Some(bitmaps) = subtitles
+            .as_ref()
+            .filter(|bitmaps| bitmaps.seqnum == composition.seqnum())
+        {
+            return Some(bitmaps.clone());
+        }
+        let bitmaps = Arc::new(SubtitleBitmaps {
+            seqnum: composition.seqnum(),
+            bitmaps: (0..composition.n_rectangles())
+                .filter_map(|index| composition.rectangle(index).ok())
+                .filter_map(|rectangle| subtitle_bitmap(&rectangle))
+                .collect(),
+        });
+        *subtitles = Some(bitmaps.clone());
+        Some(bitmaps)
+    }
+}
+
+/// Copies the pixels of `rectangle` into RGBA rows without padding
+fn subtitle_bitmap(rectangle: &gst_video::VideoOverlayRectangleRef) -> Option<SubtitleBitmap> {
+    let (x, y, render_width, render_height) = rectangle.render_rectangle();
+    let pixels = rectangle.pixels_unscaled_argb(gst_video::VideoOverlayFormatFlags::empty());
+    let meta = pixels.meta::<gst_video::VideoMeta>()?;
+    let (width, height) = (meta.width(), meta.height());
+    let (offset, stride) = (meta.offset()[0], meta.stride()[0] as usize);
+    let map = pixels.map_readable().ok()?;
+    let row_size = 4 * width as usize;
+
+    let mut data = Vec::with_capacity(row_size * height as usize);
+    for row in map
+        .get(offset..)?
+        .chunks(stride)
+        .take(height as usize)
+        .filter_map(|row| row.get(..row_size))
+    {
+        for pixel in row.chunks_exact(4) {
+            // the overlay format is BGRA in memory on little endian machines, ARGB otherwise
+            let [b, g, r, a] = if cfg!(target_endian = "little") {
+                [pixel[0], pixel[1], pixel[2], pixel[3]]
+            } else {
+                [pixel[3], pixel[2], pixel[1], pixel[0]]
+            };
+            data.extend_from_slice(&[r, g, b, a]);
+        }
+    }
+    if data.len() != row_size * height as usize {
+        return None;
+    }
+    Some(SubtitleBitmap {
+        x,
+        y,
+        render_width,
+        render_height,
+        width,
+        height,
+        data,
+    })
+}
+
+pub struct MediaDecoder {
+    video_info_sender: Sender<VideoInfo>,
+    frame_queue: Arc<FrameQueue>,
+    frame_ready_sender: Sender<()>,
+    command_receiver: Receiver<DecoderCommand>,
+    player_event_sender: Sender<PlayerEvent>,
+    audio_only: bool,
+    visualizer: Visualizer,
+    captions: CaptionSettings,
+    /// Shared with the handler that styles the text overlays playbin creates
+    subtitle_style: Arc<Mutex<SubtitleStyle>>,
+    languages: LanguagePreferences,
+    seek_mode: SeekMode,
+    rtsp_settings: RtspSettings,
+    ingest_settings: IngestSettings,
+    proxy_settings: ProxySettings,
+    credentials: Option<Credentials>,
+    audio_settings: AudioSettings,
+    cache_settings: CacheSettings,
+    loop_file: bool,
+    http_options: HttpOptions,
+    /// When the last reconnect attempt started. It can be cancelled until the attempts start
+    /// over, even when the stream briefly played again in between, see `RECONNECT_RESET`.
+    reconnected_at: Option<Instant>,
+    followed_clock: Option<SharedClock>,
+    /// Taken by the next uri that is loaded
+    start_position: Option<gst::ClockTime>,
+    /// How many uris were loaded, sent with the position
+    generation: u64,
+}
 
 impl MediaDecoder {
     pub fn new(
-        path_or_url: &str,
         video_info_sender: Sender<VideoInfo>,
-        new_frame_sender: Sender<Vec<u8>>,
-    ) -> Result<Self, Error> {
+        frame_queue: Arc<FrameQueue>,
+        frame_ready_sender: Sender<()>,
+        command_receiver: Receiver<DecoderCommand>,
+        player_event_sender: Sender<PlayerEvent>,
+    ) -> Self {
+        Self {
+            video_info_sender,
+            frame_queue,
+            frame_ready_sender,
+            command_receiver,
+            player_event_sender,
+            audio_only: false,
+            visualizer: Visualizer::default(),
+            captions: CaptionSettings::default(),
+            subtitle_style: Arc::default(),
+            languages: LanguagePreferences::default(),
+            seek_mode: SeekMode::default(),
+            rtsp_settings: RtspSettings::default(),
+            ingest_settings: IngestSettings::default(),
+            proxy_settings: ProxySettings::default(),
+            credentials: None,
+            audio_settings: AudioSettings::default(),
+            cache_settings: CacheSettings::default(),
+            loop_file: false,
+            http_options: HttpOptions::default(),
+            reconnected_at: None,
+            followed_clock: None,
+            start_position: None,
+            generation: 0,
+        }
+    }
+
+    /// Plays every uri that gets loaded until the command channel is closed
+    pub fn run(mut self) -> Result<(), PlayerError> {
         gst::init()?;
 
-        let (mut audio_producer, audio_consumer) = HeapRb::new(50 * 1024 * 1024).split();
-        let (channels, sample_rate, audio_stream) = setup_audio_stream(audio_consumer);
-        audio_stream.play().unwrap();
+        let mut next_media = None;
+        loop {
+            let media = match next_media.take() {
+                Some(media) => media,
+                None => match self.command_receiver.recv() {
+                    Ok(DecoderCommand::Shutdown) | Err(_) => return Ok(()),
+                    Ok(command) => match self.handle_idle_command(command) {
+                        Some(media) => media,
+                        None => continue,
+                    },
+                },
+            };
+            self.generation += 1;
 
-        let videosink = gst_app::AppSink::builder()
-            .caps(
-                &gst::Caps::builder("video/x-raw")
-                    .field("format", "RGBA")
-                    .build(),
-            )
-            .build();
+            let mut attempt = 0;
+            let mut start_position = self.start_position.take();
+            self.reconnected_at = None;
+            loop {
+                let started = Instant::now();
+                let played = match &media {
+                    Media::Uri(uri) => self.play(uri, start_position),
+                    Media::Pipeline(pipeline) => self.play_pipeline(pipeline.as_str()),
+                };
+                let end = match played {
+                    Ok(end) => end,
+                    Err(err) => {
+                        self.report(err);
+                        break;
+                    }
+                };
+                let position = match end {
+                    PlaybackEnd::Finished => break,
+                    PlaybackEnd::Load(media) => {
+                        next_media = Some(media);
+                        break;
+                    }
+                    PlaybackEnd::NetworkError(position) => position,
+                    PlaybackEnd::Shutdown => return Ok(()),
+                };
 
-        let mut has_sent_info = false;
+                // the stream played fine for a while, so this is a new outage
+                if started.elapsed() >= RECONNECT_RESET {
+                    attempt = 0;
+                }
+                if attempt >= MAX_RECONNECT_ATTEMPTS {
+                    tracing::warn!("Giving up on {} after {} attempts", media.name(), attempt);
+                    self.player_event_sender
+                        .send(PlayerEvent::ReconnectFailed)
+                        .ok();
+                    break;
+                }
 
-        videosink.set_callbacks(
-            gst_app::AppSinkCallbacks::builder()
-                .new_sample(move |appsink| {
-                    let sample = appsink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
+                let delay = RECONNECT_BASE_DELAY * 2u32.pow(attempt);
+                attempt += 1;
+                tracing::info!(
+                    "Reconnecting to {} in {:?} (attempt {}/{})",
+                    media.name(),
+                    delay,
+                    attempt,
+                    MAX_RECONNECT_ATTEMPTS
+                );
+                self.player_event_sender
+                    .send(PlayerEvent::Reconnecting {
+                        attempt,
+                        max_attempts: MAX_RECONNECT_ATTEMPTS,
+                    })
+                    .ok();
 
-                    if !has_sent_info {
-                        let info = gst_video::VideoInfo::from_caps(sample.caps().unwrap()).unwrap();
-                        video_info_sender.send(info).unwrap();
-                        has_sent_info = true;
+                match self.wait_for_reconnect(delay) {
+                    ReconnectWait::Elapsed => {
+                        start_position = position;
+                        self.reconnected_at = Some(Instant::now());
+                    }
+                    #[cfg(feature = "ui")]
+                    ReconnectWait::Cancelled => break,
+                    ReconnectWait::Load(media) => {
+                        next_media = Some(media);
+                        break;
                     }
+                    ReconnectWait::Closed => return Ok(()),
+                }
+            }
+        }
+    }
 
-                    let buffer = sample.buffer().unwrap();
-                    let map = buffer.map_readable().unwrap();
-                    let data = map.as_slice();
+    /// Nothing is playing, so just remember the settings for the next file. Returns the uri or
+    /// pipeline when the command loads one.
+    fn handle_idle_command(&mut self, command: DecoderCommand) -> Option<Media> {
+        match command {
+            DecoderCommand::Load(uri) => return Some(Media::Uri(uri)),
+            DecoderCommand::PlayPipeline(pipeline) => return Some(Media::Pipeline(pipeline)),
+            DecoderCommand::SetAudioOnly(audio_only) => self.audio_only = audio_only,
+            DecoderCommand::SetVisualizer(visualizer) => self.visualizer = visualizer,
+            DecoderCommand::SetCaptions(captions) => self.captions = captions,
+            DecoderCommand::SetSubtitleStyle(style) => *self.subtitle_style.lock().unwrap() = style,
+            DecoderCommand::SetLanguagePreferences(languages) => self.languages = languages,
+            DecoderCommand::SetSeekMode(seek_mode) => self.seek_mode = seek_mode,
+            DecoderCommand::SetRtspSettings(settings) => self.rtsp_settings = settings,
+            DecoderCommand::SetIngestSettings(settings) => self.ingest_settings = settings,
+            DecoderCommand::SetProxySettings(settings) => self.proxy_settings = settings,
+            DecoderCommand::SetCredentials(credentials) => self.credentials = credentials,
+            DecoderCommand::SetAudioSettings(settings) => self.audio_settings = settings,
+            DecoderCommand::SetCacheSettings(settings) => self.cache_settings = settings,
+            DecoderCommand::SetLoopFile(loop_file) => self.loop_file = loop_file,
+            DecoderCommand::SetHttpOptions(options) => self.http_options = options,
+            DecoderCommand::FollowClock(clock) => self.followed_clock = clock,
+            DecoderCommand::SetStartPosition(position) => {
+                self.start_position =
+                    Some(gst::ClockTime::from_nseconds(position.as_nanos() as u64))
+            }
+            // subtitles and seeks belong to the media that just ended
+            DecoderCommand::SetSubtitle(_)
+            | DecoderCommand::Seek(_)
+            | DecoderCommand::SeekExact(_)
+            | DecoderCommand::Scrub(_)
+            | DecoderCommand::SetPaused(_)
+            | DecoderCommand::SetRate(_)
+            | DecoderCommand::StepFrame { .. }
+            | DecoderCommand::SetAvOffset(_)
+            | DecoderCommand::SetSubtitleDelay(_)
+            | DecoderCommand::SetAudioStream(_)
+            | DecoderCommand::SetAbLoop(_)
+            | DecoderCommand::Navigate(_)
+            | DecoderCommand::StartRecording(_)
+            | DecoderCommand::StopRecording => {}
+            #[cfg(feature = "ui")]
+            DecoderCommand::SetMaxBitrate(_)
+            | DecoderCommand::SelectTitle(_)
+            | DecoderCommand::SelectChapter(_)
+            | DecoderCommand::CancelReconnect => {}
+            // handled by the callers, they have to stop waiting for commands
+            DecoderCommand::Shutdown => {}
+        }
 
-                    new_frame_sender.send(data.to_vec()).unwrap();
-                    Ok(gst::FlowSuccess::Ok)
-                })
-                .build(),
-        );
+        None
+    }
 
-        let audiosink = gst_app::AppSink::builder()
-            .caps(
-                &gst::Caps::builder("audio/x-raw")
-                    .field("format", "F32LE")
-                    .field("rate", sample_rate)
-                    .field("channels", channels)
-                    .build(),
-            )
-            .build();
+    /// Seeks for a command of the user. Sources that can't seek, like the standard input or
+    /// live streams, refuse it and keep playing where they are, so that isn't fatal. Returns
+    /// whether the seek was accepted.
+    fn seek(
+        &self,
+        pipeline: &gst::Element,
+        position: gst::ClockTime,
+        rate: f64,
+        flags: gst::SeekFlags,
+    ) -> bool {
+        match seek_at_rate(pipeline, position, rate, flags) {
+            Ok(()) => true,
+            Err(err) => {
+                self.report(PlayerError::Decode {
+                    element: None,
+                    message: format!("Can't seek in this stream: {:#}", err),
+                });
+                false
+            }
+        }
+    }
 
-        audiosink.set_callbacks(
-            gst_app::AppSinkCallbacks::builder()
-                .new_sample(move |appsink| {
-                    let sample = appsink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
-                    let buffer = sample.buffer().unwrap();
-                    let map = buffer.map_readable().unwrap();
-                    let samples = map.as_slice_of::<f32>().unwrap();
-                    audio_producer.push_slice(samples);
-                    Ok(gst::FlowSuccess::Ok)
-                })
-                .build(),
-        );
+    /// Turns off the loops after the source refused to seek back, they would only fail again
+    fn clear_loops(&mut self) {
+        self.loop_file = false;
+        self.player_event_sender.send(PlayerEvent::LoopCleared).ok();
+    }
+
+    /// Prints the error and shows it in the ui
+    fn report(&self, error: PlayerError) {
+        tracing::error!("{}", error);
+        self.player_event_sender
+            .send(PlayerEvent::Error(error))
+            .ok();
+    }
+
+    /// Sleeps until it is time to reconnect, returns early when the user cancels, another uri
+    /// is loaded or the command channel is closed
+    fn wait_for_reconnect(&mut self, delay: Duration) -> ReconnectWait {
+        let deadline = Instant::now() + delay;
+        loop {
+            match self.command_receiver.recv_deadline(deadline) {
+                #[cfg(feature = "ui")]
+                Ok(DecoderCommand::CancelReconnect) => return ReconnectWait::Cancelled,
+                Ok(DecoderCommand::Shutdown) => return ReconnectWait::Closed,
+                Ok(command) => {
+                    if let Some(media) = self.handle_idle_command(command) {
+                        return ReconnectWait::Load(media);
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => return ReconnectWait::Elapsed,
+                Err(RecvTimeoutError::Disconnected) => return ReconnectWait::Closed,
+            }
+        }
+    }
+
+    /// Blocks until the media has ended or failed, optionally starting at the given position
+    fn play(
+        &mut self,
+        path_or_url: &str,
+        start_position: Option<gst::ClockTime>,
+    ) -> Result<PlaybackEnd, PlayerError> {
+        #[cfg(not(feature = "network"))]
+        if !is_local(path_or_url) {
+            return Err(PlayerError::UnsupportedFormat(format!(
+                "{} is a network stream, but this build has no network support",
+                path_or_url
+            )));
+        }
+
+        if cover_art::is_image_uri(path_or_url) {
+            return self.play_image(path_or_url);
+        }
+        #[cfg(feature = "ndi")]
+        if let Some(name) = ndi::source_name(path_or_url) {
+            if !ndi::is_available() {
+                return Err(PlayerError::UnsupportedFormat(
+                    "No gstreamer plugin receives NDI, install the ndi plugin of gst-plugins-rs \
+                     and the NDI runtime"
+                        .to_string(),
+                ));
+            }
+            return self.play_pipeline(&ndi::pipeline_description(name));
+        }
+
+        #[cfg(feature = "audio")]
+        let mut audio_output = self.open_audio_output();
+        #[cfg(feature = "audio")]
+        let mut clock_drift_ppm = None;
+
+        let videosink = gst_app::AppSink::builder().build();
+        let counters = self.forward_frames(&videosink);
 
         // audio_pipeline.add_many(&[&audio_convert, &audio_resample, audiosink.upcast_ref()])?;
         // gst::Element::link_many(&[&audio_convert, &audio_resample, audiosink.upcast_ref()])?;
 
+        let mut audio_filters = AudioFilters::new()?;
+        audio_filters.apply(&self.audio_settings);
+
+        #[cfg(feature = "network")]
+        if let Some((scheme, sources, plugin)) = INGEST_SOURCES
+            .iter()
+            .find(|(scheme, _, _)| path_or_url.starts_with(scheme))
+        {
+            if !sources
+                .iter()
+                .any(|name| gst::ElementFactory::find(name).is_some())
+            {
+                return Err(PlayerError::UnsupportedFormat(format!(
+                    "No gstreamer plugin reads {} uris, install {}",
+                    scheme, plugin
+                )));
+            }
+        }
+
+        // the disc sources take the folder as their device
+        let disc_source = disc::source(path_or_url);
+        if let Some((scheme, _)) = disc_source {
+            if gst::Element::make_from_uri(gst::URIType::Src, scheme, None).is_err() {
+                return Err(PlayerError::UnsupportedFormat(format!(
+                    "No gstreamer plugin reads {} uris, install resindvd for DVDs or a libbluray \
+                     plugin for Blu-rays",
+                    scheme
+                )));
+            }
+        }
+
         let pipeline = gst::ElementFactory::make("playbin")
-            .property("uri", path_or_url)
-            .property("video-sink", &videosink)
-            .property("audio-sink", &audiosink)
+            .property(
+                "uri",
+                disc_source
+                    .as_ref()
+                    .map_or(path_or_url, |(scheme, _)| *scheme),
+            )
+            .property("video-sink", &video_sink_bin(&videosink)?)
+            .property("audio-filter", audio_filters.element())
+            .property("volume", self.audio_settings.volume.clamp(0.0, 1.0))
+            .property("mute", self.audio_settings.muted)
             .build()?;
+        let _stop = StopOnDrop(pipeline.clone());
+        // the video sink bin converts, see video_sink_bin
+        set_playbin_flag(&pipeline, "native-video", true);
+        // without our own output gstreamer picks an audio sink itself
+        #[cfg(feature = "audio")]
+        if let Some(audio_output) = &audio_output {
+            pipeline.set_property("audio-sink", audio_output.sink());
+        }
 
-        let target_state = gst::State::Playing;
+        let mut caption_filter = caption_overlay(self.captions);
+        pipeline.set_property("video-filter", caption_filter.as_ref());
+        style_subtitles(&pipeline, &self.subtitle_style);
+
+        #[cfg(feature = "network")]
+        self.setup_network_sources(&pipeline);
+        let recorder = StreamRecorder::attach(&pipeline);
+        if let Some((_, folder)) = disc_source.clone() {
+            pipeline.connect("source-setup", false, move |values| {
+                let source = values[1].get::<gst::Element>().unwrap();
+                if source.has_property("device", None) {
+                    source.set_property("device", &folder);
+                }
+                None
+            });
+        }
+
+        let mut target_state = gst::State::Playing;
+        let mut rate = 1.0;
+
+        set_visualizer(&pipeline, self.visualizer, self.audio_only);
+        if self.audio_only {
+            set_audio_only(&pipeline, true)?;
+        }
+
+        #[cfg(feature = "network")]
+        if self.cache_settings.enabled && path_or_url.starts_with("http") {
+            if let Err(err) = setup_download_cache(&pipeline, &self.cache_settings) {
+                tracing::warn!("Failed to set up the download cache: {}", err);
+            }
+        }
+
+        if let Some(shared) = self.followed_clock.as_ref() {
+            follow_clock(&pipeline, shared);
+        }
+
+        if let Some(position) = start_position {
+            // seeking only works once the pipeline has prerolled
+            pipeline.set_state(gst::State::Paused)?;
+            let _ = pipeline.state(gst::ClockTime::from_seconds(10));
+            if let Err(err) =
+                pipeline.seek_simple(gst::SeekFlags::FLUSH | gst::SeekFlags::KEY_UNIT, position)
+            {
+                tracing::warn!("Failed to resume at {}: {}", position, err);
+            }
+        }
 
         pipeline.set_state(gst::State::Playing)?;
 
-        let bus = pipeline.bus().unwrap();
-        for msg in bus.iter_timed(gst::ClockTime::NONE) {
+        let is_network_stream = !is_local(path_or_url);
+        // the projection and stereo layout don't reach the appsink, they are read from the file
+        let layout = glib::filename_from_uri(path_or_url)
+            .map_or(VideoLayout::default(), |(path, _)| spherical::detect(&path));
+        self.player_event_sender
+            .send(PlayerEvent::Layout(layout))
+            .ok();
+        #[cfg(not(feature = "network"))]
+        let variants: Vec<Variant> = Vec::new();
+        #[cfg(feature = "network")]
+        let mut variants: Vec<Variant> = Vec::new();
+        #[cfg(feature = "network")]
+        let variant_receiver =
+            adaptive::is_adaptive_uri(path_or_url).then(|| self.fetch_variants(path_or_url));
+        let mut active_variant = None;
+        let mut audio_stream = None;
+        let mut audio_streams = 0;
+        let mut video_streams = None;
+        // the streams are only known once the media is demuxed
+        let mut wanted_audio_stream = None;
+        // once per file, unless a stream was picked by hand before
+        let mut pick_audio_by_language = !self.languages.audio.is_empty();
+        let mut pick_subtitles_by_language = !self.languages.subtitles.is_empty();
+        let mut end = PlaybackEnd::Finished;
+        // resuming happens at the live edge for streams without a duration
+        let mut resume_position = None;
+        let mut is_live = false;
+        let mut bitrate = None;
+        let mut tags = MediaTags::default();
+        let mut cover_art = None;
+        // replaces the visualizer once it is known that there is no video
+        let mut showing_cover_art = false;
+        let mut ab_loop: Option<(gst::ClockTime, gst::ClockTime)> = None;
+        let mut av_offset_ms = 0;
+        set_av_offset(
+            &pipeline,
+            av_offset_ms + self.audio_settings.output_delay_ms,
+        );
+        let mut last_stats = Instant::now();
+        let mut last_position = Instant::now();
+        let bus = pipeline
+            .bus()
+            .ok_or_else(|| PlayerError::Init("playbin has no bus".to_string()))?;
+        'playback: loop {
+            if last_position.elapsed() >= POSITION_INTERVAL {
+                last_position = Instant::now();
+                if let Some(position) = pipeline.query_position::<gst::ClockTime>() {
+                    let duration = pipeline.query_duration::<gst::ClockTime>();
+                    resume_position = duration.map(|_| position);
+                    is_live = is_network_stream && duration.is_none();
+                    let n_video = usize::try_from(pipeline.property::<i32>("n-video")).unwrap_or(0);
+                    if video_streams != Some(n_video) {
+                        video_streams = Some(n_video);
+                        self.player_event_sender
+                            .send(PlayerEvent::VideoStreams(n_video))
+                            .ok();
+                    }
+                    if let Some(art) = cover_art.take() {
+                        if pipeline.property::<i32>("n-video") == 0 {
+                            showing_cover_art = true;
+                            set_visualizer(&pipeline, Visualizer::Off, self.audio_only);
+                            relink_branches(&pipeline)?;
+                            self.player_event_sender
+                                .send(PlayerEvent::CoverArt(art))
+                                .ok();
+                        }
+                    }
+                    self.player_event_sender
+                        .send(PlayerEvent::Position {
+                            generation: self.generation,
+                            position: position.into(),
+                            duration: duration.map(Into::into),
+                        })
+                        .ok();
+                }
+
+                if disc_source.is_some() {
+                    self.player_event_sender
+                        .send(PlayerEvent::Disc(disc::query_position(&pipeline)))
+                        .ok();
+                }
+
+                if is_network_stream {
+                    self.player_event_sender
+                        .send(PlayerEvent::BufferedRanges(query_buffered_ranges(
+                            &pipeline,
+                        )))
+                        .ok();
+                }
+
+                let n_audio = usize::try_from(pipeline.property::<i32>("n-audio")).unwrap_or(0);
+                if n_audio != audio_streams {
+                    audio_streams = n_audio;
+                    self.player_event_sender
+                        .send(PlayerEvent::AudioStreams(n_audio))
+                        .ok();
+                }
+                if pick_audio_by_language && n_audio > 1 {
+                    pick_audio_by_language = false;
+                    let languages = stream_languages(&pipeline, "get-audio-tags", n_audio);
+                    if let Some(index) = languages::best_match(&languages, &self.languages.audio) {
+                        tracing::info!("Playing audio stream {} for its language", index);
+                        wanted_audio_stream = Some(index);
+                    }
+                }
+                let n_text = usize::try_from(pipeline.property::<i32>("n-text")).unwrap_or(0);
+                if pick_subtitles_by_language && n_text > 1 {
+                    pick_subtitles_by_language = false;
+                    let languages = stream_languages(&pipeline, "get-text-tags", n_text);
+                    if let Some(index) =
+                        languages::best_match(&languages, &self.languages.subtitles)
+                    {
+                        tracing::info!("Showing subtitle stream {} for its language", index);
+                        pipeline.set_property("current-text", index as i32);
+                    }
+                }
+                if let Some(index) = wanted_audio_stream.filter(|index| *index < n_audio) {
+                    pipeline.set_property("current-audio", index as i32);
+                    wanted_audio_stream = None;
+                }
+                let current_audio = usize::try_from(pipeline.property::<i32>("current-audio")).ok();
+                if current_audio != audio_stream {
+                    audio_stream = current_audio;
+                    self.player_event_sender
+                        .send(PlayerEvent::AudioStream(current_audio))
+                        .ok();
+                }
+
+                #[cfg(feature = "network")]
+                if let Some(fetched) = variant_receiver
+                    .as_ref()
+                    .and_then(|receiver| receiver.try_recv().ok())
+                    .filter(|fetched| !fetched.is_empty())
+                {
+                    variants = fetched;
+                    self.player_event_sender
+                        .send(PlayerEvent::Variants(variants.clone()))
+                        .ok();
+                }
+                if !variants.is_empty() {
+                    let variant = find_active_variant(&videosink, &variants, bitrate);
+                    if variant != active_variant {
+                        active_variant = variant;
+                        self.player_event_sender
+                            .send(PlayerEvent::ActiveVariant(variant))
+                            .ok();
+                    }
+                }
+            }
+
+            if last_stats.elapsed() >= STATS_INTERVAL {
+                last_stats = Instant::now();
+                let mut stats = collect_stats(&pipeline, &counters, bitrate);
+                stats.queue_dropped_frames = self.frame_queue.dropped();
+                #[cfg(feature = "audio")]
+                if let Some(audio_output) = audio_output.as_mut() {
+                    if let Some(time) = pipeline.clock().and_then(|clock| clock.time()) {
+                        let seconds = time.nseconds() as f64 / 1_000_000_000.0;
+                        clock_drift_ppm = audio_output.update_drift(seconds).or(clock_drift_ppm);
+                    }
+                    stats.audio_buffer_ms = audio_output.buffered_ms();
+                    stats.clock_drift_ppm = clock_drift_ppm;
+                }
+                self.player_event_sender
+                    .send(PlayerEvent::Stats(stats))
+                    .ok();
+            }
+
+            for command in self.command_receiver.try_iter() {
+                match command {
+                    // another track of the same CUE sheet, see `cue::split_track_uri`
+                    DecoderCommand::Load(uri)
+                        if uri == path_or_url && self.start_position.is_some() =>
+                    {
+                        self.generation += 1;
+                        let position = self.start_position.take().unwrap_or_default();
+                        rate = 1.0;
+                        self.seek(&pipeline, position, rate, gst::SeekFlags::ACCURATE);
+                        target_state = gst::State::Playing;
+                        pipeline.set_state(target_state)?;
+                    }
+                    DecoderCommand::Load(uri) => {
+                        end = PlaybackEnd::Load(Media::Uri(uri));
+                        break 'playback;
+                    }
+                    DecoderCommand::PlayPipeline(pipeline) => {
+                        end = PlaybackEnd::Load(Media::Pipeline(pipeline));
+                        break 'playback;
+                    }
+                    DecoderCommand::Shutdown => {
+                        end = PlaybackEnd::Shutdown;
+                        break 'playback;
+                    }
+                    DecoderCommand::SetAudioOnly(audio_only) => {
+                        self.audio_only = audio_only;
+                        if !showing_cover_art {
+                            set_visualizer(&pipeline, self.visualizer, audio_only);
+                        }
+                        set_audio_only(&pipeline, audio_only)?;
+                    }
+                    DecoderCommand::SetVisualizer(visualizer) => {
+                        self.visualizer = visualizer;
+                        if !showing_cover_art {
+                            set_visualizer(&pipeline, visualizer, self.audio_only);
+                            relink_branches(&pipeline)?;
+                        }
+                    }
+                    DecoderCommand::SetSubtitle(uri) => set_subtitle(&pipeline, &uri)?,
+                    DecoderCommand::SetCaptions(captions) => {
+                        let toggled = captions.enabled != self.captions.enabled;
+                        self.captions = captions;
+                        if toggled {
+                            caption_filter = caption_overlay(captions);
+                            reload_at_position(&pipeline, |pipeline| {
+                                pipeline.set_property("video-filter", caption_filter.as_ref())
+                            })?;
+                        } else if let Some(overlay) = caption_filter.as_ref() {
+                            set_caption_channel(overlay, captions.channel);
+                        }
+                    }
+                    DecoderCommand::SetSubtitleStyle(style) => {
+                        apply_subtitle_style(&pipeline, &style);
+                        *self.subtitle_style.lock().unwrap() = style;
+                    }
+                    DecoderCommand::Seek(position) => {
+                        let position = gst::ClockTime::from_nseconds(position.as_nanos() as u64);
+                        let is_short =
+                            pipeline
+                                .query_position::<gst::ClockTime>()
+                                .is_some_and(|current| {
+                                    current.nseconds().abs_diff(position.nseconds())
+                                        < SHORT_SEEK.as_nanos() as u64
+                                });
+                        let flags = if self.seek_mode == SeekMode::Accurate || is_short {
+                            gst::SeekFlags::ACCURATE
+                        } else {
+                            gst::SeekFlags::KEY_UNIT | gst::SeekFlags::SNAP_NEAREST
+                        };
+                        self.seek(&pipeline, position, rate, flags);
+                    }
+                    DecoderCommand::SeekExact(position) => {
+                        let position = gst::ClockTime::from_nseconds(position.as_nanos() as u64);
+                        self.seek(&pipeline, position, rate, gst::SeekFlags::ACCURATE);
+                    }
+                    DecoderCommand::SetSeekMode(seek_mode) => self.seek_mode = seek_mode,
+                    DecoderCommand::SetStartPosition(position) => {
+                        self.start_position =
+                            Some(gst::ClockTime::from_nseconds(position.as_nanos() as u64));
+                    }
+                    DecoderCommand::Scrub(position) => {
+                        // a drag sends a scrub per frame, so failures are only logged, the
+                        // release shows the error
+                        let position = gst::ClockTime::from_nseconds(position.as_nanos() as u64);
+                        let flags = gst::SeekFlags::KEY_UNIT | gst::SeekFlags::SNAP_NEAREST;
+                        if let Err(err) = seek_at_rate(&pipeline, position, rate, flags) {
+                            tracing::warn!("Failed to scrub to {}: {:#}", position, err);
+                        }
+                    }
+                    DecoderCommand::SetPaused(paused) => {
+                        target_state = if paused {
+                            gst::State::Paused
+                        } else {
+                            gst::State::Playing
+                        };
+                        pipeline.set_state(target_state)?;
+                    }
+                    DecoderCommand::SetRate(new_rate) => {
+                        rate = new_rate;
+                        if let Some(position) = pipeline.query_position::<gst::ClockTime>() {
+                            self.seek(&pipeline, position, rate, gst::SeekFlags::ACCURATE);
+                        }
+                    }
+                    DecoderCommand::StepFrame { backward } => {
+                        target_state = gst::State::Paused;
+                        pipeline.set_state(target_state)?;
+                        // steps go in the direction of the playback, so turn around first
+                        if (rate < 0.0) != backward {
+                            rate = -rate;
+                            if let Some(position) = pipeline.query_position::<gst::ClockTime>() {
+                                self.seek(&pipeline, position, rate, gst::SeekFlags::ACCURATE);
+                            }
+                        }
+                        videosink.send_event(gst::event::Step::new(
+                            gst::format::Buffers::ONE,
+                            1.0,
+                            true,
+                            false,
+                        ));
+                    }
+                    DecoderCommand::SetAvOffset(offset_ms) => {
+                        av_offset_ms = offset_ms;
+                        set_av_offset(
+                            &pipeline,
+                            av_offset_ms + self.audio_settings.output_delay_ms,
+                        );
+                    }
+                    DecoderCommand::SetSubtitleDelay(delay_ms) => {
+                        pipeline.set_property("text-offset", delay_ms * 1_000_000);
+                    }
+                    DecoderCommand::SetAudioStream(index) => {
+                        wanted_audio_stream = Some(index);
+                        pick_audio_by_language = false;
+                    }
+                    DecoderCommand::SetLanguagePreferences(languages) => self.languages = languages,
+                    // the source of the current stream has already been set up
+                    DecoderCommand::SetRtspSettings(settings) => self.rtsp_settings = settings,
+                    DecoderCommand::SetIngestSettings(settings) => self.ingest_settings = settings,
+                    DecoderCommand::SetProxySettings(settings) => self.proxy_settings = settings,
+                    DecoderCommand::SetCredentials(credentials) => self.credentials = credentials,
+                    #[cfg(feature = "ui")]
+                    DecoderCommand::SetMaxBitrate(bitrate) => set_max_bitrate(&pipeline, bitrate),
+                    DecoderCommand::SetAudioSettings(settings) => {
+                        self.audio_settings = settings;
+                        audio_filters.apply(&settings);
+                        pipeline.set_property("volume", settings.volume.clamp(0.0, 1.0));
+                        pipeline.set_property("mute", settings.muted);
+                        set_av_offset(&pipeline, av_offset_ms + settings.output_delay_ms);
+                    }
+                    DecoderCommand::SetCacheSettings(settings) => self.cache_settings = settings,
+                    DecoderCommand::SetLoopFile(loop_file) => self.loop_file = loop_file,
+                    // the sources of the current media have already been set up
+                    DecoderCommand::SetHttpOptions(options) => self.http_options = options,
+                    #[cfg(feature = "ui")]
+                    DecoderCommand::CancelReconnect => {
+                        if self
+                            .reconnected_at
+                            .is_some_and(|at| at.elapsed() < RECONNECT_RESET)
+                        {
+                            break 'playback;
+                        }
+                    }
+                    DecoderCommand::FollowClock(clock) => {
+                        if let Some(shared) = clock.as_ref() {
+                            // the new base time is picked up on the way back to playing
+                            if target_state >= gst::State::Playing {
+                                pipeline.set_state(gst::State::Paused)?;
+                                follow_clock(&pipeline, shared);
+                                pipeline.set_state(gst::State::Playing)?;
+                            } else {
+                                follow_clock(&pipeline, shared);
+                            }
+                        }
+                        self.followed_clock = clock;
+                    }
+                    DecoderCommand::SetAbLoop(points) => {
+                        ab_loop = points.map(|(a, b)| {
+                            (
+                                gst::ClockTime::from_nseconds(a.as_nanos() as u64),
+                                gst::ClockTime::from_nseconds(b.as_nanos() as u64),
+                            )
+                        });
+                    }
+                    DecoderCommand::Navigate(navigation) => disc::navigate(&pipeline, navigation),
+                    #[cfg(feature = "ui")]
+                    DecoderCommand::SelectTitle(title) => disc::seek(&pipeline, "title", title),
+                    #[cfg(feature = "ui")]
+                    DecoderCommand::SelectChapter(chapter) => {
+                        disc::seek(&pipeline, "chapter", chapter)
+                    }
+                    DecoderCommand::StartRecording(path) => match recorder.start(&path) {
+                        Ok(()) => {
+                            self.player_event_sender
+                                .send(PlayerEvent::Recording(Some(path)))
+                                .ok();
+                        }
+                        Err(err) => self.report(PlayerError::Decode {
+                            element: None,
+                            message: format!("Failed to start recording: {:#}", err),
+                        }),
+                    },
+                    DecoderCommand::StopRecording => {
+                        if recorder.stop().is_some() {
+                            self.player_event_sender
+                                .send(PlayerEvent::Recording(None))
+                                .ok();
+                        }
+                    }
+                }
+            }
+
+            if let Some(err) = recorder.take_error() {
+                self.report(PlayerError::Decode {
+                    element: None,
+                    message: format!("Recording failed: {:#}", err),
+                });
+                self.player_event_sender
+                    .send(PlayerEvent::Recording(None))
+                    .ok();
+            }
+
+            if let Some((a, b)) = ab_loop {
+                if pipeline
+                    .query_position::<gst::ClockTime>()
+                    .is_some_and(|position| position >= b)
+                    && !self.seek(&pipeline, a, rate, gst::SeekFlags::ACCURATE)
+                {
+                    ab_loop = None;
+                    self.clear_loops();
+                }
+            }
+
+            let Some(msg) = bus.timed_pop(gst::ClockTime::from_mseconds(50)) else {
+                continue;
+            };
+
             use gst::MessageView;
             match msg.view() {
                 MessageView::Eos(..) => {
+                    // reached the start while playing backwards
+                    if rate < 0.0 {
+                        target_state = gst::State::Paused;
+                        pipeline.set_state(target_state)?;
+                        continue;
+                    }
+
+                    // b can be right at the end, in which case it never gets reached
+                    let loop_start = match ab_loop {
+                        Some((a, _)) => Some(a),
+                        None => self.loop_file.then_some(gst::ClockTime::ZERO),
+                    };
+                    if let Some(position) = loop_start {
+                        if self.seek(&pipeline, position, rate, gst::SeekFlags::ACCURATE) {
+                            continue;
+                        }
+                        self.clear_loops();
+                    }
+
+                    // live streams don't end, the connection dropped
+                    if is_live {
+                        tracing::warn!("Live stream {} ended unexpectedly", path_or_url);
+                        end = PlaybackEnd::NetworkError(None);
+                        break;
+                    }
+
                     pipeline.set_state(gst::State::Paused)?;
-                    println!("received eos");
+                    tracing::debug!("received eos");
                     // An EndOfStream event was sent to the pipeline, so exit
                     break;
                 }
                 MessageView::Error(err) => {
-                    println!(
+                    tracing::warn!(
                         "Error from {:?}: {} ({:?})",
                         err.src().map(|s| s.path_string()),
                         err.error(),
                         err.debug()
                     );
+                    if is_decryption_error(err) {
+                        // reconnecting gets the same key again
+                        self.report(PlayerError::Decode {
+                            element: err.src().map(|src| src.name().to_string()),
+                            message: format!(
+                                "Failed to decrypt {}, the key may be missing or wrong",
+                                path_or_url
+                            ),
+                        });
+                        break;
+                    }
+                    if is_network_stream && error::is_transient(err) {
+                        end = PlaybackEnd::NetworkError(resume_position);
+                    } else {
+                        self.report(PlayerError::from_bus(err));
+                    }
                     break;
                 }
                 MessageView::Buffering(msg) => {
                     let percent = msg.percent();
+                    self.player_event_sender
+                        .send(PlayerEvent::Buffering(percent))
+                        .ok();
                     if percent < 100 && target_state >= gst::State::Paused {
-                        println!("Buffering {}%", percent);
+                        tracing::debug!("Buffering {}%", percent);
                         pipeline.set_state(gst::State::Paused)?;
                     } else if target_state >= gst::State::Playing {
                         pipeline.set_state(gst::State::Playing)?;
                     } else if target_state >= gst::State::Paused {
-                        println!("Buffering complete");
+                        tracing::debug!("Buffering complete");
                     }
                 }
-                MessageView::ClockLost(_) => {
-                    if target_state >= gst::State::Playing {
-                        pipeline.set_state(gst::State::Paused)?;
-                        pipeline.set_state(gst::State::Playing)?;
+                MessageView::Tag(tag) => {
+                    let tag_list = tag.tags();
+                    if let Some(value) = tag_list.get::<gst::tags::Bitrate>() {
+                        bitrate = Some(value.get());
+                    } else if let Some(value) = tag_list.get::<gst::tags::NominalBitrate>() {
+                        bitrate = Some(value.get());
+                    }
+
+                    let previous_tags = tags.clone();
+                    tags.merge(&tag_list, is_network_stream);
+                    if tags != previous_tags {
+                        self.player_event_sender
+                            .send(PlayerEvent::Tags(tags.clone()))
+                            .ok();
+                    }
+
+                    if cover_art.is_none() && !showing_cover_art {
+                        if let Some(image) = cover_art::find_image(&tag_list) {
+                            match cover_art::decode(&image) {
+                                Ok(art) => cover_art = Some(art),
+                                Err(err) => {
+                                    tracing::warn!("Failed to decode the cover art: {}", err)
+                                }
+                            }
+                        }
+                    }
+                }
+                MessageView::Element(element) => {
+                    if let Some(structure) = element.structure() {
+                        audio_filters.update_loudness(&self.audio_settings, structure);
+                    }
+                }
+                // a followed pipeline keeps the base time it was given
+                MessageView::StateChanged(change)
+                    if self.followed_clock.is_none()
+                        && change.current() == gst::State::Playing
+                        && msg.src() == Some(pipeline.upcast_ref()) =>
+                {
+                    if let (Some(clock), Some(base_time)) = (pipeline.clock(), pipeline.base_time())
+                    {
+                        self.player_event_sender
+                            .send(PlayerEvent::Clock(SharedClock { clock, base_time }))
+                            .ok();
+                    }
+                }
+                MessageView::ClockLost(_) if target_state >= gst::State::Playing => {
+                    pipeline.set_state(gst::State::Paused)?;
+                    pipeline.set_state(gst::State::Playing)?;
+                }
+                _ => (),
+            }
+        }
+
+        if recorder.stop().is_some() {
+            self.player_event_sender
+                .send(PlayerEvent::Recording(None))
+                .ok();
+        }
+
+        Ok(end)
+    }
+
+    /// Sets up the appsink to pass its frames on to the renderer
+    fn forward_frames(&self, videosink: &gst_app::AppSink) -> Arc<FrameCounters> {
+        // 10 bit video stays 10 bit, 8 bit video prefers the first format
+        let formats = gst::Structure::builder("video/x-raw")
+            .field("format", gst::List::new(["RGBA", "RGB10A2_LE"]))
+            .build();
+        videosink.set_caps(Some(
+            &gst::Caps::builder_full()
+                .structure_with_features(
+                    formats.clone(),
+                    gst::CapsFeatures::new([
+                        gst_video::CAPS_FEATURE_META_GST_VIDEO_OVERLAY_COMPOSITION,
+                    ]),
+                )
+                .structure(formats)
+                .build(),
+        ));
+
+        let counters = Arc::new(FrameCounters {
+            last_video_pts: AtomicU64::new(u64::MAX),
+            ..Default::default()
+        });
+        let frames = Arc::new(FrameForwarder {
+            video_info_sender: self.video_info_sender.clone(),
+            frame_queue: self.frame_queue.clone(),
+            frame_ready_sender: self.frame_ready_sender.clone(),
+            counters: counters.clone(),
+            sent_size: Mutex::new(None),
+            subtitles: Mutex::new(None),
+        });
+        let preroll_frames = frames.clone();
+
+        videosink.set_callbacks(
+            gst_app::AppSinkCallbacks::builder()
+                .new_sample(move |appsink| {
+                    let _span = tracing::trace_span!("decode").entered();
+                    let sample = appsink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
+                    frames.forward(&sample, true)
+                })
+                // while paused, frame steps and seeks only show up as prerolled samples
+                .new_preroll(move |appsink| {
+                    let sample = appsink.pull_preroll().map_err(|_| gst::FlowError::Eos)?;
+                    preroll_frames.forward(&sample, false)
+                })
+                .build(),
+        );
+        counters
+    }
+
+    /// Plays a gst-launch description of the user, see `CustomPipeline`. Only the basics work,
+    /// everything that relies on playbin like subtitles and visualizers is left out.
+    fn play_pipeline(&mut self, description: &str) -> Result<PlaybackEnd, PlayerError> {
+        #[cfg(feature = "audio")]
+        let audio_output = self.open_audio_output();
+        #[cfg(feature = "audio")]
+        let audio_sink = match &audio_output {
+            Some(audio_output) => audio_output.sink().clone(),
+            None => gst::ElementFactory::make("autoaudiosink").build()?,
+        };
+        #[cfg(not(feature = "audio"))]
+        let audio_sink = gst::ElementFactory::make("autoaudiosink").build()?;
+
+        let custom = CustomPipeline::parse(description, &audio_sink)
+            .map_err(|err| PlayerError::Init(format!("{:#}", err)))?;
+        let pipeline = custom.pipeline;
+        let _stop = StopOnDrop(pipeline.clone().upcast());
+        if let Some(videosink) = &custom.video_sink {
+            self.forward_frames(videosink);
+        }
+        let set_volume = |settings: &AudioSettings| {
+            if let Some(volume) = &custom.volume {
+                volume.set_property("volume", settings.volume.clamp(0.0, 1.0));
+                volume.set_property("mute", settings.muted);
+            }
+        };
+        set_volume(&self.audio_settings);
+        if let Some(shared) = self.followed_clock.as_ref() {
+            follow_clock(pipeline.upcast_ref(), shared);
+        }
+        pipeline.set_state(gst::State::Playing)?;
+
+        let bus = pipeline
+            .bus()
+            .ok_or_else(|| PlayerError::Init("the pipeline has no bus".to_string()))?;
+        let mut end = PlaybackEnd::Finished;
+        let mut last_position = Instant::now();
+        // a clone, the settings are handled by methods that borrow all of self
+        let command_receiver = self.command_receiver.clone();
+        'playback: loop {
+            if last_position.elapsed() >= POSITION_INTERVAL {
+                last_position = Instant::now();
+                if let Some(position) = pipeline.query_position::<gst::ClockTime>() {
+                    self.player_event_sender
+                        .send(PlayerEvent::Position {
+                            generation: self.generation,
+                            position: position.into(),
+                            duration: pipeline.query_duration::<gst::ClockTime>().map(Into::into),
+                        })
+                        .ok();
+                }
+            }
+
+            for command in command_receiver.try_iter() {
+                match command {
+                    DecoderCommand::Load(uri) => {
+                        end = PlaybackEnd::Load(Media::Uri(uri));
+                        break 'playback;
+                    }
+                    DecoderCommand::PlayPipeline(pipeline) => {
+                        end = PlaybackEnd::Load(Media::Pipeline(pipeline));
+                        break 'playback;
+                    }
+                    DecoderCommand::Shutdown => {
+                        end = PlaybackEnd::Shutdown;
+                        break 'playback;
+                    }
+                    DecoderCommand::SetPaused(paused) => {
+                        pipeline.set_state(if paused {
+                            gst::State::Paused
+                        } else {
+                            gst::State::Playing
+                        })?;
+                    }
+                    DecoderCommand::Seek(position)
+                    | DecoderCommand::SeekExact(position)
+                    | DecoderCommand::Scrub(position) => {
+                        let position = gst::ClockTime::from_nseconds(position.as_nanos() as u64);
+                        // live sources can't seek, that is fine
+                        if let Err(err) = pipeline
+                            .seek_simple(gst::SeekFlags::FLUSH | gst::SeekFlags::KEY_UNIT, position)
+                        {
+                            tracing::debug!("Failed to seek the custom pipeline: {}", err);
+                        }
+                    }
+                    DecoderCommand::SetAudioSettings(settings) => {
+                        self.audio_settings = settings;
+                        set_volume(&settings);
+                    }
+                    command => {
+                        self.handle_idle_command(command);
+                    }
+                }
+            }
+
+            let Some(msg) = bus.timed_pop(gst::ClockTime::from_mseconds(50)) else {
+                continue;
+            };
+
+            use gst::MessageView;
+            match msg.view() {
+                MessageView::Eos(..) => {
+                    if self.loop_file
+                        && pipeline
+                            .seek_simple(gst::SeekFlags::FLUSH, gst::ClockTime::ZERO)
+                            .is_ok()
+                    {
+                        continue;
+                    }
+                    break;
+                }
+                MessageView::Error(err) => {
+                    self.report(PlayerError::from_bus(err));
+                    break;
+                }
+                MessageView::StateChanged(change)
+                    if self.followed_clock.is_none()
+                        && change.current() == gst::State::Playing
+                        && msg.src() == Some(pipeline.upcast_ref()) =>
+                {
+                    if let (Some(clock), Some(base_time)) = (pipeline.clock(), pipeline.base_time())
+                    {
+                        self.player_event_sender
+                            .send(PlayerEvent::Clock(SharedClock { clock, base_time }))
+                            .ok();
                     }
                 }
                 _ => (),
             }
         }
 
-        pipeline.set_state(gstreamer::State::Null)?;
+        Ok(end)
+    }
 
-        Ok(Self)
+    /// Without a usable audio device the media plays on without sound, gstreamer's own sink
+    /// falls back to discarding the audio
+    #[cfg(feature = "audio")]
+    fn open_audio_output(&self) -> Option<AudioOutput> {
+        AudioOutput::new().map_err(|err| self.report(err)).ok()
+    }
+
+    /// Images don't need a pipeline, they are decoded once and stay up until the next uri
+    fn play_image(&mut self, uri: &str) -> Result<PlaybackEnd, PlayerError> {
+        let decode_error = |err: Error| PlayerError::Decode {
+            element: None,
+            message: format!("{:#}", err),
+        };
+        let (path, _) = glib::filename_from_uri(uri).map_err(|err| decode_error(err.into()))?;
+        // without the ui only the first frame is shown
+        #[cfg(feature = "ui")]
+        if animation::is_animated(&path) {
+            return self.play_animation(&path).map_err(decode_error);
+        }
+        let image = cover_art::load(&path).map_err(decode_error)?;
+        self.player_event_sender
+            .send(PlayerEvent::Image(image))
+            .ok();
+        Ok(PlaybackEnd::Finished)
+    }
+
+    /// Shows the frames of a GIF, APNG or animated WebP like a video that loops, until another
+    /// uri is loaded
+    #[cfg(feature = "ui")]
+    fn play_animation(&mut self, path: &Path) -> Result<PlaybackEnd, Error> {
+        let mut animation = Animation::open(path)?;
+        let mut sent_size = None;
+        let mut paused = false;
+        // None once the last frame stays up
+        let mut next_frame_at = Some(Instant::now());
+        loop {
+            let timeout = match next_frame_at.filter(|_| !paused) {
+                Some(next_frame_at) => next_frame_at.saturating_duration_since(Instant::now()),
+                None => POSITION_INTERVAL,
+            };
+            let frame = match self.command_receiver.recv_timeout(timeout) {
+                Ok(DecoderCommand::Load(uri)) => return Ok(PlaybackEnd::Load(Media::Uri(uri))),
+                Ok(DecoderCommand::PlayPipeline(pipeline)) => {
+                    return Ok(PlaybackEnd::Load(Media::Pipeline(pipeline)))
+                }
+                Ok(DecoderCommand::Shutdown) | Err(RecvTimeoutError::Disconnected) => {
+                    return Ok(PlaybackEnd::Shutdown)
+                }
+                Ok(DecoderCommand::SetPaused(paused_now)) => {
+                    paused = paused_now;
+                    if !paused {
+                        next_frame_at = next_frame_at.map(|_| Instant::now());
+                    }
+                    continue;
+                }
+                Ok(
+                    DecoderCommand::Seek(position)
+                    | DecoderCommand::SeekExact(position)
+                    | DecoderCommand::Scrub(position),
+                ) => animation.seek(position)?,
+                Ok(DecoderCommand::StepFrame { backward: false }) => {
+                    paused = true;
+                    animation.next_frame()?
+                }
+                Ok(command) => {
+                    self.handle_idle_command(command);
+                    continue;
+                }
+                Err(RecvTimeoutError::Timeout) if paused || next_frame_at.is_none() => continue,
+                Err(RecvTimeoutError::Timeout) => animation.next_frame()?,
+            };
+
+            let Some((image, delay)) = frame else {
+                next_frame_at = None;
+                continue;
+            };
+            next_frame_at = Some(Instant::now() + delay);
+            let size = image.dimensions();
+            if sent_size != Some(size) {
+                let info =
+                    VideoInfo::builder(gst_video::VideoFormat::Rgba, size.0, size.1).build()?;
+                if self.video_info_sender.send(info).is_err() {
+                    return Ok(PlaybackEnd::Shutdown);
+                }
+                sent_size = Some(size);
+            }
+            self.frame_queue.push(Frame {
+                data: image.into_raw(),
+                width: size.0,
+                height: size.1,
+                stride: size.0 * 4,
+                format: PixelFormat::Rgba8,
+                transfer: Transfer::Sdr,
+                subtitles: None,
+            });
+            if let Err(TrySendError::Disconnected(_)) = self.frame_ready_sender.try_send(()) {
+                return Ok(PlaybackEnd::Shutdown);
+            }
+            self.player_event_sender
+                .send(PlayerEvent::Position {
+                    generation: self.generation,
+                    position: animation.position(),
+                    duration: animation.duration(),
+                })
+                .ok();
+        }
+    }
+
+    /// Reads the manifest a second time for the quality menu, without holding up the playback.
+    /// The variants arrive on the returned channel, nothing does when it fails.
+    #[cfg(feature = "network")]
+    fn fetch_variants(&self, uri: &str) -> Receiver<Vec<Variant>> {
+        let (sender, receiver) = crossbeam_channel::bounded(1);
+        let uri = uri.to_string();
+        let http_sources = self.http_sources(&uri);
+        std::thread::spawn(move || {
+            let configure = |source: &gst::Element| {
+                if is_http_source(source) {
+                    http_sources.configure_connection(source);
+                }
+                http_sources.configure_request(source);
+            };
+            match adaptive::fetch_variants(&uri, configure) {
+                Ok(variants) => {
+                    sender.send(variants).ok();
+                }
+                Err(err) => tracing::warn!("Failed to read the variants of {}: {}", uri, err),
+            }
+        });
+        receiver
+    }
+
+    /// The http options, the login and the proxy for the http sources of `uri`
+    #[cfg(feature = "network")]
+    fn http_sources(&self, uri: &str) -> HttpSources {
+        let cookies = match &self.http_options.cookie_file {
+            Some(path) => cookies::load(path).unwrap_or_else(|err| {
+                tracing::warn!("{:#}", err);
+                Vec::new()
+            }),
+            None => Vec::new(),
+        };
+        HttpSources {
+            headers: headers_structure(&self.http_options, self.credentials.as_ref()),
+            user_agent: Some(self.http_options.user_agent.clone())
+                .filter(|agent| !agent.is_empty()),
+            cookies,
+            proxy: proxy::for_uri(&self.proxy_settings, uri),
+            proxy_settings: self.proxy_settings.clone(),
+            credentials: self.credentials.clone(),
+        }
+    }
+
+    /// Passes the rtsp, srt and rtmp settings, the http options and the proxy to the sources
+    /// playbin creates
+    #[cfg(feature = "network")]
+    fn setup_network_sources(&self, pipeline: &gst::Element) {
+        let rtsp_settings = self.rtsp_settings;
+        let ingest_settings = self.ingest_settings.clone();
+        let uri = pipeline
+            .property::<Option<String>>("uri")
+            .unwrap_or_default();
+        let http_sources = Arc::new(self.http_sources(&uri));
+        let uri_cookies = cookies::for_uri(&http_sources.cookies, &uri);
+        // hlsdemux and dashdemux create the http sources of the segments themselves, those are
+        // only seen when they're added
+        {
+            let http_sources = http_sources.clone();
+            pipeline.connect("deep-element-added", false, move |values| {
+                let element = values[2].get::<gst::Element>().unwrap();
+                if is_http_source(&element) {
+                    http_sources.configure_connection(&element);
+                }
+                None
+            });
+        }
+        if http_sources.headers.is_some() || !uri_cookies.is_empty() {
+            // hlsdemux fetches playlists and keys with http sources of its own, those only
+            // see the headers and cookies through this context
+            let mut context = gst::Context::new("http-headers", true);
+            let structure = context.get_mut().unwrap().structure_mut();
+            if let Some(headers) = &http_sources.headers {
+                structure.set("request-headers", headers.clone());
+            }
+            if !uri_cookies.is_empty() {
+                structure.set("cookies", uri_cookies);
+            }
+            pipeline.set_context(&context);
+        }
+
+        pipeline.connect("source-setup", false, move |values| {
+            let source = values[1].get::<gst::Element>().unwrap();
+            match source.factory().map(|f| f.name()).as_deref() {
+                Some("rtspsrc") => {
+                    configure_rtsp_source(&source, rtsp_settings);
+                    if let Some(credentials) = &http_sources.credentials {
+                        configure_login(&source, credentials);
+                    }
+                }
+                Some("srtsrc") => configure_srt_source(&source, &ingest_settings),
+                // rtmpsrc is the older librtmp based one
+                Some("rtmp2src" | "rtmpsrc") => configure_rtmp_source(&source, &ingest_settings),
+                _ => {}
+            }
+            http_sources.configure_request(&source);
+            None
+        });
     }
 }
 
-fn setup_audio_stream(mut audio_consumer: HeapConsumer<f32>) -> (i32, i32, Stream) {
-    use cpal::traits::{DeviceTrait, HostTrait};
+/// What the http sources of a file get, those of playbin and the demuxers as well as the one
+/// that reads the manifest for the variants
+#[cfg(feature = "network")]
+struct HttpSources {
+    /// The extra headers, the referer and the bearer token
+    headers: Option<gst::Structure>,
+    user_agent: Option<String>,
+    cookies: Vec<cookies::Cookie>,
+    proxy: Option<String>,
+    proxy_settings: ProxySettings,
+    credentials: Option<Credentials>,
+}
 
-    let host = cpal::default_host();
-    let device = host
-        .default_output_device()
-        .expect("no output device available");
+#[cfg(feature = "network")]
+impl HttpSources {
+    /// The proxy and the login, for souphttpsrc and curlhttpsrc
+    fn configure_connection(&self, source: &gst::Element) {
+        configure_http_proxy(source, self.proxy.as_deref(), &self.proxy_settings);
+        if let Some(credentials) = &self.credentials {
+            configure_login(source, credentials);
+        }
+    }
 
-    let mut supported_configs_range = device
-        .supported_output_configs()
-        .expect("error while querying configs");
+    /// The headers, the user agent and the cookies, for any source that takes them
+    fn configure_request(&self, source: &gst::Element) {
+        if let Some(headers) = &self.headers {
+            if source.has_property("extra-headers", None) {
+                source.set_property("extra-headers", headers);
+            }
+        }
+        if let Some(user_agent) = &self.user_agent {
+            if source.has_property("user-agent", None) {
+                source.set_property("user-agent", user_agent);
+            }
+        }
+        if !self.cookies.is_empty() && source.has_property("cookies", None) {
+            // redirects and segments on other hosts get the cookies of their own domain
+            let location = source
+                .property::<Option<String>>("location")
+                .unwrap_or_default();
+            let source_cookies = cookies::for_uri(&self.cookies, &location);
+            if !source_cookies.is_empty() {
+                source.set_property("cookies", source_cookies);
+            }
+        }
+    }
+}
 
-    let config = supported_configs_range
-        .find(|_| true)
-        .unwrap()
-        .with_max_sample_rate();
+#[cfg(feature = "network")]
+fn is_http_source(element: &gst::Element) -> bool {
+    matches!(
+        element.factory().map(|f| f.name()).as_deref(),
+        Some("souphttpsrc" | "curlhttpsrc")
+    )
+}
 
-    (
-        config.channels() as i32,
-        config.sample_rate().0 as i32,
-        device
-            .build_output_stream(
-                &config.into(),
-                move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
-                    audio_consumer.pop_slice(data);
-                },
-                move |err| println!("CPAL error: {:?}", err),
-                None,
+fn query_buffered_ranges(pipeline: &gst::Element) -> Vec<(f32, f32)> {
+    // GST_FORMAT_PERCENT_MAX
+    const PERCENT_MAX: f32 = 1_000_000.0;
+
+    let mut query = gst::query::Buffering::new(gst::Format::Percent);
+    if !pipeline.query(&mut query) {
+        return Vec::new();
+    }
+
+    query
+        .ranges()
+        .into_iter()
+        .map(|(start, stop)| {
+            (
+                start.value() as f32 / PERCENT_MAX,
+                stop.value() as f32 / PERCENT_MAX,
             )
-            .unwrap(),
-    )
+        })
+        .collect()
+}
+
+fn collect_stats(
+    pipeline: &gst::Element,
+    counters: &FrameCounters,
+    bitrate: Option<u32>,
+) -> DecoderStats {
+    let last_video_pts = counters.last_video_pts.load(Ordering::Relaxed);
+    let av_offset_ms = pipeline
+        .query_position::<gst::ClockTime>()
+        .filter(|_| last_video_pts != u64::MAX)
+        .map(|position| (last_video_pts as f64 - position.nseconds() as f64) / 1_000_000.0);
+
+    let video_decoder = find_decoder(pipeline, "Video");
+    let video_format = video_decoder
+        .as_ref()
+        .and_then(|decoder| decoder.static_pad("src"))
+        .and_then(|pad| pad.current_caps())
+        .and_then(|caps| VideoInfo::from_caps(&caps).ok())
+        .map(|info| {
+            format!(
+                "{}x{} {}",
+                info.width(),
+                info.height(),
+                info.format().to_str()
+            )
+        });
+
+    DecoderStats {
+        decoded_frames: counters.decoded_frames.load(Ordering::Relaxed),
+        av_offset_ms,
+        audio_buffer_ms: 0.0,
+        video_decoder: video_decoder.and_then(|decoder| decoder.factory().map(|f| f.name().into())),
+        audio_decoder: find_decoder(pipeline, "Audio")
+            .and_then(|decoder| decoder.factory().map(|f| f.name().into())),
+        video_format,
+        bitrate,
+        clock_drift_ppm: None,
+        queue_dropped_frames: 0,
+    }
+}
+
+/// Looks for the decoder playbin autoplugged for the given media klass, "Video" or "Audio"
+fn find_decoder(pipeline: &gst::Element, media_klass: &str) -> Option<gst::Element> {
+    find_element_by_klass(pipeline, &["Decoder", media_klass])
+}
+
+/// Finds the first element in the pipeline whose klass contains all the given parts
+fn find_element_by_klass(pipeline: &gst::Element, parts: &[&str]) -> Option<gst::Element> {
+    let bin = pipeline.downcast_ref::<gst::Bin>()?;
+    bin.iterate_recurse().into_iter().flatten().find(|element| {
+        element
+            .factory()
+            .and_then(|factory| {
+                factory
+                    .metadata(gst::ELEMENT_METADATA_KLASS)
+                    .map(String::from)
+            })
+            .map(|klass| parts.iter().all(|part| klass.contains(part)))
+            .unwrap_or(false)
+    })
+}
+
+/// The adaptive demuxers pick the best variant that fits in their connection speed, so pinning
+/// it to the bandwidth of a variant selects that variant and 0 brings back automatic selection
+#[cfg(feature = "ui")]
+fn set_max_bitrate(pipeline: &gst::Element, bitrate: Option<u64>) {
+    let kbps = bitrate.map_or(0, |bitrate| bitrate.div_ceil(1000));
+
+    // used for demuxers that are created later on, e.g. after a seek
+    pipeline.set_property("connection-speed", kbps);
+    if let Some(demuxer) = find_element_by_klass(pipeline, &["Demuxer", "Adaptive"]) {
+        if demuxer.has_property("connection-speed", None) {
+            demuxer.set_property("connection-speed", kbps.min(u32::MAX as u64) as u32);
+        }
+    }
+}
+
+/// Converts the video for `videosink`, instead of playbin doing it before the sink. That way the
+/// allocation query of the subtitle overlay reaches the sink, which asks for the bitmaps of
+/// PGS, DVB and VobSub subtitles as `VideoOverlayCompositionMeta`. The renderer draws them over
+/// the video instead of the overlay blending them into the frames.
+fn video_sink_bin(videosink: &gst_app::AppSink) -> Result<gst::Element, Error> {
+    let bin = gst::Bin::new(Some("video-sink"));
+    let convert = gst::ElementFactory::make("videoconvert").build()?;
+    bin.add_many(&[&convert, videosink.upcast_ref()])?;
+    convert.link(videosink)?;
+
+    let ghost_pad = gst::GhostPad::with_target(Some("sink"), &convert.static_pad("sink").unwrap())?;
+    // videoconvert copies the meta from its input to its output buffers
+    ghost_pad.add_probe(
+        gst::PadProbeType::QUERY_DOWNSTREAM | gst::PadProbeType::PUSH,
+        |_, info| {
+            if let Some(gst::PadProbeData::Query(query)) = info.data.as_mut() {
+                if let gst::QueryViewMut::Allocation(allocation) = query.view_mut() {
+                    allocation.add_allocation_meta::<gst_video::VideoOverlayCompositionMeta>(None);
+                }
+            }
+            gst::PadProbeReturn::Ok
+        },
+    );
+    bin.add_pad(&ghost_pad)?;
+    Ok(bin.upcast())
+}
+
+/// Matches the resolution that reaches the video sink and the bitrate against the variants
+fn find_active_variant(
+    videosink: &gst_app::AppSink,
+    variants: &[Variant],
+    bitrate: Option<u32>,
+) -> Option<usize> {
+    let caps = videosink.static_pad("sink")?.current_caps()?;
+    let height = VideoInfo::from_caps(&caps).ok()?.height();
+    adaptive::active_variant(variants, height, bitrate)
+}
+
+/// The extra headers, the referer and the bearer token of the login, None when there are none
+#[cfg(feature = "network")]
+fn headers_structure(
+    options: &HttpOptions,
+    credentials: Option<&Credentials>,
+) -> Option<gst::Structure> {
+    let mut structure = gst::Structure::new_empty("extra-headers");
+    for (name, value) in &options.headers {
+        structure.set(name.as_str(), value);
+    }
+    let has_referer = options
+        .headers
+        .keys()
+        .any(|name| name.eq_ignore_ascii_case("referer"));
+    if !options.referer.is_empty() && !has_referer {
+        structure.set("Referer", &options.referer);
+    }
+    if let Some(credentials) = credentials.filter(|credentials| !credentials.token.is_empty()) {
+        structure.set(
+            "Authorization",
+            format!("Bearer {}", credentials.token.trim()),
+        );
+    }
+    (structure.n_fields() > 0).then_some(structure)
+}
+
+/// hlsdemux posts `DecryptNokey` when the key can't be downloaded and `Decrypt` when a segment
+/// doesn't decrypt with it
+fn is_decryption_error(err: &gst::message::Error) -> bool {
+    let error = err.error();
+    error.matches(gst::StreamError::Decrypt) || error.matches(gst::StreamError::DecryptNokey)
+}
+
+/// Flushing seek that keeps the playback rate, when playing backwards the segment runs from the
+/// start up to the position. Flushing also drops the frames queued in the old direction so the
+/// next frame shown is the one at the position.
+fn seek_at_rate(
+    pipeline: &gst::Element,
+    position: gst::ClockTime,
+    rate: f64,
+    flags: gst::SeekFlags,
+) -> Result<(), Error> {
+    let flags = flags | gst::SeekFlags::FLUSH;
+    if rate >= 0.0 {
+        pipeline.seek(
+            rate,
+            flags,
+            gst::SeekType::Set,
+            Some(position),
+            gst::SeekType::None,
+            gst::ClockTime::NONE,
+        )?;
+    } else {
+        pipeline.seek(
+            rate,
+            flags,
+            gst::SeekType::Set,
+            Some(gst::ClockTime::ZERO),
+            gst::SeekType::Set,
+            Some(position),
+        )?;
+    }
+    Ok(())
+}
+
+/// Stops the pipeline from picking its own clock and base time when it starts playing
+fn follow_clock(pipeline: &gst::Element, shared: &SharedClock) {
+    let Some(pipeline) = pipeline.downcast_ref::<gst::Pipeline>() else {
+        return;
+    };
+    pipeline.use_clock(Some(&shared.clock));
+    pipeline.set_start_time(gst::ClockTime::NONE);
+    pipeline.set_base_time(shared.base_time);
+}
+
+fn set_av_offset(pipeline: &gst::Element, offset_ms: i64) {
+    pipeline.set_property("av-offset", offset_ms * 1_000_000);
+}
+
+fn set_playbin_flag(pipeline: &gst::Element, nick: &str, enabled: bool) {
+    let flags = pipeline.property_value("flags");
+    let flags_class = glib::FlagsClass::new(flags.type_()).unwrap();
+    let flags_builder = flags_class.builder_with_value(flags).unwrap();
+    let flags = if enabled {
+        flags_builder.set_by_nick(nick)
+    } else {
+        flags_builder.unset_by_nick(nick)
+    }
+    .build()
+    .unwrap();
+    pipeline.set_property_from_value("flags", &flags);
+}
+
+fn set_audio_only(pipeline: &gst::Element, audio_only: bool) -> Result<(), Error> {
+    set_playbin_flag(pipeline, "video", !audio_only);
+    relink_branches(pipeline)
+}
+
+/// playbin only (un)links its branches after the flags change on a flushing seek
+fn relink_branches(pipeline: &gst::Element) -> Result<(), Error> {
+    if let Some(position) = pipeline.query_position::<gst::ClockTime>() {
+        pipeline.seek_simple(gst::SeekFlags::FLUSH | gst::SeekFlags::KEY_UNIT, position)?;
+    }
+
+    Ok(())
+}
+
+/// playbin only renders the visualizer when the media has no video stream
+fn set_visualizer(pipeline: &gst::Element, visualizer: Visualizer, audio_only: bool) {
+    let element = match visualizer.factory_name().map(visualizer_bin).transpose() {
+        Ok(element) => element,
+        Err(err) => {
+            tracing::warn!(
+                "Failed to create the {} visualizer: {}",
+                visualizer.label(),
+                err
+            );
+            None
+        }
+    };
+
+    // the visualizer would undo the power savings of audio only mode
+    set_playbin_flag(pipeline, "vis", element.is_some() && !audio_only);
+    if let Some(element) = element {
+        pipeline.set_property("vis-plugin", element);
+    }
+}
+
+/// The visualizer followed by a capsfilter, they render at a tiny size by default
+fn visualizer_bin(factory_name: &str) -> Result<gst::Element, Error> {
+    let visualizer = gst::ElementFactory::make(factory_name).build()?;
+    let capsfilter = gst::ElementFactory::make("capsfilter")
+        .property(
+            "caps",
+            gst::Caps::builder("video/x-raw")
+                .field("width", VISUALIZER_WIDTH)
+                .field("height", VISUALIZER_HEIGHT)
+                .build(),
+        )
+        .build()?;
+
+    let bin = gst::Bin::new(None);
+    bin.add_many(&[&visualizer, &capsfilter])?;
+    visualizer.link(&capsfilter)?;
+    let sink_pad =
+        gst::GhostPad::with_target(Some("sink"), &visualizer.static_pad("sink").unwrap())?;
+    let src_pad = gst::GhostPad::with_target(Some("src"), &capsfilter.static_pad("src").unwrap())?;
+    bin.add_pad(&sink_pad)?;
+    bin.add_pad(&src_pad)?;
+
+    Ok(bin.upcast())
+}
+
+/// Makes playbin download progressive streams into a size limited ring buffer file
+#[cfg(feature = "network")]
+fn setup_download_cache(pipeline: &gst::Element, settings: &CacheSettings) -> Result<(), Error> {
+    let directory = settings.directory();
+    std::fs::create_dir_all(&directory)?;
+    let template = directory.join(format!("{}XXXXXX", CACHE_FILE_PREFIX));
+    let template = template.to_string_lossy().into_owned();
+
+    set_playbin_flag(pipeline, "download", true);
+    pipeline.set_property("ring-buffer-max-size", settings.max_size_mb * 1024 * 1024);
+
+    // uridecodebin puts the file in the temp directory, move it once its queue2 shows up
+    let bin = pipeline.downcast_ref::<gst::Bin>().unwrap();
+    bin.connect_deep_element_added(move |_, _, element| {
+        if element.factory().is_some_and(|f| f.name() == "queue2") {
+            element.set_property("temp-template", &template);
+            element.set_property("temp-remove", true);
+        }
+    });
+
+    Ok(())
+}
+
+/// playbin only picks up a new suburi when going to PAUSED, so restart it at the current position
+fn set_subtitle(pipeline: &gst::Element, subtitle_uri: &str) -> Result<(), Error> {
+    reload_at_position(pipeline, |pipeline| {
+        pipeline.set_property("suburi", subtitle_uri);
+        pipeline.set_property("subtitle-encoding", "UTF-8");
+    })
+}
+
+/// Changes properties of playbin that only apply when it goes to PAUSED, then resumes playing at
+/// the current position
+fn reload_at_position(
+    pipeline: &gst::Element,
+    configure: impl FnOnce(&gst::Element),
+) -> Result<(), Error> {
+    let position = pipeline.query_position::<gst::ClockTime>();
+
+    pipeline.set_state(gst::State::Ready)?;
+    configure(pipeline);
+    pipeline.set_state(gst::State::Paused)?;
+    let _ = pipeline.state(gst::ClockTime::from_seconds(5));
+
+    if let Some(position) = position {
+        pipeline.seek_simple(gst::SeekFlags::FLUSH | gst::SeekFlags::KEY_UNIT, position)?;
+    }
+    pipeline.set_state(gst::State::Playing)?;
+
+    Ok(())
+}
+
+/// The language tag of every stream of a kind, `signal` is get-audio-tags or get-text-tags
+fn stream_languages(pipeline: &gst::Element, signal: &str, count: usize) -> Vec<Option<String>> {
+    (0..count as i32)
+        .map(|index| {
+            pipeline
+                .emit_by_name::<Option<gst::TagList>>(signal, &[&index])
+                .and_then(|tags| {
+                    tags.get::<gst::tags::LanguageCode>()
+                        .map(|language| language.get().to_string())
+                })
+        })
+        .collect()
+}
+
+/// Styles text subtitles with `style`, including the text overlays playbin creates later on
+fn style_subtitles(pipeline: &gst::Element, style: &Arc<Mutex<SubtitleStyle>>) {
+    apply_subtitle_style(pipeline, &style.lock().unwrap());
+    let bin = pipeline.downcast_ref::<gst::Bin>().unwrap();
+    let style = style.clone();
+    bin.connect_deep_element_added(move |_, _, element| {
+        if element.factory().is_some_and(|f| f.name() == "textoverlay") {
+            style_text_overlay(element, &style.lock().unwrap());
+        }
+    });
+}
+
+/// playbin hands the font to its subtitle overlay, the rest is set on the textoverlay it renders
+/// plain text subtitles with
+fn apply_subtitle_style(pipeline: &gst::Element, style: &SubtitleStyle) {
+    pipeline.set_property("subtitle-font-desc", style.font_desc());
+    let Some(bin) = pipeline.downcast_ref::<gst::Bin>() else {
+        return;
+    };
+    for overlay in bin
+        .iterate_recurse()
+        .into_iter()
+        .flatten()
+        .filter(|element| element.factory().is_some_and(|f| f.name() == "textoverlay"))
+    {
+        style_text_overlay(&overlay, style);
+    }
+}
+
+fn style_text_overlay(overlay: &gst::Element, style: &SubtitleStyle) {
+    // textoverlay takes colors as ARGB
+    let argb = |[r, g, b]: [u8; 3]| 0xFF00_0000 | u32::from_be_bytes([0, r, g, b]);
+    overlay.set_property("font-desc", style.font_desc());
+    overlay.set_property("color", argb(style.text_color));
+    overlay.set_property("draw-outline", style.outline);
+    overlay.set_property("outline-color", argb(style.outline_color));
+    overlay.set_property("shaded-background", style.background);
+    // positions the text so that ypos 1 puts its bottom on the bottom of the video
+    overlay.set_property_from_str("valignment", "position");
+    overlay.set_property("ypos", 1.0 - style.position.clamp(0.0, 1.0) as f64);
+}
+
+/// cc708overlay draws the captions carried in the video stream, playbin runs it as its video
+/// filter. It is left out while captions are off since it can't pass 10 bit video through.
+fn caption_overlay(captions: CaptionSettings) -> Option<gst::Element> {
+    if !captions.enabled {
+        return None;
+    }
+    match gst::ElementFactory::make("cc708overlay").build() {
+        Ok(overlay) => {
+            set_caption_channel(&overlay, captions.channel);
+            Some(overlay)
+        }
+        Err(err) => {
+            tracing::warn!(
+                "Closed captions need cc708overlay from gst-plugins-bad: {}",
+                err
+            );
+            None
+        }
+    }
+}
+
+/// Older versions of cc708overlay only know the CEA-708 services
+fn set_caption_channel(overlay: &gst::Element, channel: CaptionChannel) {
+    if overlay.has_property("cc-field", None) {
+        // -1 decodes whichever CEA-608 field has captions
+        overlay.set_property("cc-field", channel.field().unwrap_or(-1));
+    }
+    if let Some(service) = channel.service() {
+        overlay.set_property("service-number", service);
+    }
+}
+
+#[cfg(feature = "network")]
+fn configure_rtsp_source(source: &gst::Element, settings: RtspSettings) {
+    let latency_ms = if settings.low_latency {
+        settings.latency_ms.min(RTSP_LOW_LATENCY_MS)
+    } else {
+        settings.latency_ms
+    };
+    source.set_property("latency", latency_ms);
+    // late packets would otherwise hold up everything behind them
+    source.set_property("drop-on-latency", settings.low_latency);
+
+    if settings.force_tcp {
+        source.set_property_from_str("protocols", "tcp");
+    }
+}
+
+/// Leaves the parameters in the query of the uri, like srt://host:port?passphrase=..., alone
+#[cfg(feature = "network")]
+fn configure_srt_source(source: &gst::Element, settings: &IngestSettings) {
+    let uri = source.property::<Option<String>>("uri").unwrap_or_default();
+    let in_uri = |key: &str| {
+        uri.split_once('?').is_some_and(|(_, query)| {
+            query
+                .split('&')
+                .any(|parameter| parameter.split('=').next() == Some(key))
+        })
+    };
+    if !in_uri("latency") {
+        source.set_property(
+            "latency",
+            settings.srt_latency_ms.min(i32::MAX as u32) as i32,
+        );
+    }
+    if !settings.srt_passphrase.is_empty() && !in_uri("passphrase") {
+        source.set_property("passphrase", &settings.srt_passphrase);
+    }
+}
+
+/// An empty proxy also drops the one souphttpsrc takes from http_proxy by itself
+#[cfg(feature = "network")]
+fn configure_http_proxy(source: &gst::Element, proxy: Option<&str>, settings: &ProxySettings) {
+    source.set_property("proxy", proxy.unwrap_or_default());
+    if proxy.is_none() || settings.username.is_empty() {
+        return;
+    }
+    // souphttpsrc and curlhttpsrc name the user differently
+    for user_property in ["proxy-id", "proxy-user"] {
+        if source.has_property(user_property, None) {
+            source.set_property(user_property, &settings.username);
+        }
+    }
+    source.set_property("proxy-pw", &settings.password);
+}
+
+/// Basic and digest logins, tokens are sent with the headers
+#[cfg(feature = "network")]
+fn configure_login(source: &gst::Element, credentials: &Credentials) {
+    if credentials.username.is_empty() {
+        return;
+    }
+    // souphttpsrc and rtspsrc, curlhttpsrc
+    for (user_property, password_property) in [("user-id", "user-pw"), ("user", "passwd")] {
+        if source.has_property(user_property, None) {
+            source.set_property(user_property, &credentials.username);
+            source.set_property(password_property, &credentials.password);
+        }
+    }
+}
+
+#[cfg(feature = "network")]
+fn configure_rtmp_source(source: &gst::Element, settings: &IngestSettings) {
+    // a gint on rtmpsrc and a guint on rtmp2src
+    source.set_property_from_str("timeout", &settings.rtmp_timeout_secs.to_string());
+}
+
+/// Files, discs, the standard input and NDI sources, everything else goes through the network
+/// code
+fn is_local(uri: &str) -> bool {
+    #[cfg(feature = "ndi")]
+    if ndi::source_name(uri).is_some() {
+        return true;
+    }
+    uri.starts_with("file://") || uri == STDIN_URI || disc::is_disc_uri(uri)
 }
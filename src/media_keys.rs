@@ -0,0 +1,114 @@
+#[cfg(feature = "ui")]
+use anyhow::{anyhow, Error};
+#[cfg(feature = "ui")]
+use souvlaki::{MediaControlEvent, MediaControls, MediaMetadata, MediaPlayback, PlatformConfig};
+#[cfg(feature = "ui")]
+use winit::window::Window;
+
+/// A hardware media key, or the same button in the media overlay of the OS
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaKey {
+    Play,
+    Pause,
+    PlayPause,
+    Next,
+    Previous,
+    Stop,
+    /// Asks for the window, e.g. from the player entry of the media overlay
+    Raise,
+}
+
+/// Title and artist of what is playing, and whether it is paused
+#[cfg(feature = "ui")]
+type PlaybackState = (Option<(String, Option<String>)>, bool);
+
+/// Receives the media keys even while the window isn't focused, through MPRIS on Linux, the
+/// system media transport controls on Windows and the now playing center on macOS. Those only
+/// route the keys to players that report what they are playing, see `set_playing`.
+#[cfg(feature = "ui")]
+pub struct MediaKeys {
+    controls: MediaControls,
+    /// Last reported title, artist and paused state, so the OS is only told about changes
+    reported: Option<PlaybackState>,
+}
+
+#[cfg(feature = "ui")]
+impl MediaKeys {
+    /// `on_key` is called from a background thread
+    pub fn new(window: &Window, on_key: impl Fn(MediaKey) + Send + 'static) -> Result<Self, Error> {
+        let config = PlatformConfig {
+            dbus_name: "wgpu_media_player",
+            display_name: "wgpu media player",
+            hwnd: hwnd(window),
+        };
+        let mut controls = MediaControls::new(config)
+            .map_err(|err| anyhow!("Failed to register for media keys: {:?}", err))?;
+        controls
+            .attach(move |event| {
+                let key = match event {
+                    MediaControlEvent::Play => MediaKey::Play,
+                    MediaControlEvent::Pause => MediaKey::Pause,
+                    MediaControlEvent::Toggle => MediaKey::PlayPause,
+                    MediaControlEvent::Next => MediaKey::Next,
+                    MediaControlEvent::Previous => MediaKey::Previous,
+                    MediaControlEvent::Stop => MediaKey::Stop,
+                    MediaControlEvent::Raise => MediaKey::Raise,
+                    _ => return,
+                };
+                on_key(key);
+            })
+            .map_err(|err| anyhow!("Failed to listen for media keys: {:?}", err))?;
+
+        Ok(Self {
+            controls,
+            reported: None,
+        })
+    }
+
+    /// Tells the OS the title and artist of what is playing, None when nothing is
+    pub fn set_playing(&mut self, now_playing: Option<(&str, Option<&str>)>, paused: bool) {
+        let state = (
+            now_playing.map(|(title, artist)| (title.to_string(), artist.map(str::to_string))),
+            paused,
+        );
+        if self.reported.as_ref() == Some(&state) {
+            return;
+        }
+
+        let playback = match (now_playing, paused) {
+            (None, _) => MediaPlayback::Stopped,
+            (Some(_), true) => MediaPlayback::Paused { progress: None },
+            (Some(_), false) => MediaPlayback::Playing { progress: None },
+        };
+        if let Err(err) = self.controls.set_metadata(MediaMetadata {
+            title: now_playing.map(|(title, _)| title),
+            artist: now_playing.and_then(|(_, artist)| artist),
+            ..Default::default()
+        }) {
+            tracing::debug!("Failed to report the title for media keys: {:?}", err);
+        }
+        if let Err(err) = self.controls.set_playback(playback) {
+            tracing::debug!(
+                "Failed to report the playback state for media keys: {:?}",
+                err
+            );
+        }
+        self.reported = Some(state);
+    }
+}
+
+/// The system media transport controls belong to a window on Windows
+#[cfg(all(feature = "ui", windows))]
+fn hwnd(window: &Window) -> Option<*mut std::ffi::c_void> {
+    use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
+
+    match window.raw_window_handle() {
+        RawWindowHandle::Win32(handle) => Some(handle.hwnd),
+        _ => None,
+    }
+}
+
+#[cfg(all(feature = "ui", not(windows)))]
+fn hwnd(_window: &Window) -> Option<*mut std::ffi::c_void> {
+    None
+}
@@ -0,0 +1,86 @@
+use gst::prelude::*;
+
+use crate::custom_pipeline::{AUDIO_SINK, VIDEO_SINK};
+
+const URI_PREFIX: &str = "ndi://";
+
+/// The uri of an NDI source, by its name like "HOST (Camera 1)"
+pub fn uri(name: &str) -> String {
+    format!("{}{}", URI_PREFIX, name)
+}
+
+pub fn source_name(uri: &str) -> Option<&str> {
+    uri.strip_prefix(URI_PREFIX)
+}
+
+/// Whether the NDI plugin of gst-plugins-rs is installed, it needs the NDI runtime as well
+pub fn is_available() -> bool {
+    gst::ElementFactory::find("ndisrc").is_some()
+}
+
+/// Receives the source and splits it into the appsinks `CustomPipeline` wires into the player
+pub fn pipeline_description(name: &str) -> String {
+    format!(
+        "ndisrc ndi-name=\"{}\" ! ndisrcdemux name=demux \
+         demux.video ! queue ! videoconvert ! appsink name={} \
+         demux.audio ! queue ! audioconvert ! audioresample ! appsink name={}",
+        name.replace('\\', "\\\\").replace('"', "\\\""),
+        VIDEO_SINK,
+        AUDIO_SINK
+    )
+}
+
+/// NDI senders on the network, found by the device provider of the NDI plugin once `start` is
+/// called
+#[derive(Default)]
+pub struct NdiSources {
+    /// The running provider, or why it couldn't be started
+    provider: Option<Result<gst::DeviceProvider, String>>,
+}
+
+impl NdiSources {
+    /// Starts looking for sources, does nothing after the first call
+    pub fn start(&mut self) -> Result<(), &str> {
+        let provider = self.provider.get_or_insert_with(|| {
+            gst::init().map_err(|err| err.to_string())?;
+            let provider =
+                gst::DeviceProviderFactory::by_name("ndideviceprovider").ok_or_else(|| {
+                    "Install the ndi plugin of gst-plugins-rs and the NDI runtime to find sources"
+                        .to_string()
+                })?;
+            provider
+                .start()
+                .map_err(|err| format!("Failed to look for NDI sources: {}", err))?;
+            Ok(provider)
+        });
+        provider.as_ref().map(|_| ()).map_err(String::as_str)
+    }
+
+    /// The names of the sources found so far, sorted
+    pub fn names(&self) -> Vec<String> {
+        let Some(Ok(provider)) = &self.provider else {
+            return Vec::new();
+        };
+        let mut names: Vec<String> = provider
+            .devices()
+            .iter()
+            .map(|device| {
+                device
+                    .properties()
+                    .and_then(|properties| properties.get::<String>("ndi-name").ok())
+                    .unwrap_or_else(|| device.display_name().to_string())
+            })
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+}
+
+impl Drop for NdiSources {
+    fn drop(&mut self) {
+        if let Some(Ok(provider)) = &self.provider {
+            provider.stop();
+        }
+    }
+}
@@ -0,0 +1,1151 @@
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    sync::{Arc, Mutex},
+    thread::JoinHandle,
+    time::Instant,
+};
+
+use anyhow::{anyhow, Error};
+use crossbeam_channel::{bounded, unbounded, Sender};
+#[cfg(feature = "ui")]
+use egui::FontDefinitions;
+#[cfg(feature = "ui")]
+use egui_wgpu_backend::{RenderPass, ScreenDescriptor};
+#[cfg(feature = "ui")]
+use egui_winit_platform::{Platform, PlatformDescriptor};
+use gst_video::VideoInfo;
+use winit::{
+    dpi::PhysicalSize,
+    event::{ElementState, Event, VirtualKeyCode, WindowEvent},
+    event_loop::{EventLoopProxy, EventLoopWindowTarget},
+    window::{Fullscreen, Window, WindowId},
+};
+
+#[cfg(feature = "library")]
+use crate::library::Library;
+#[cfg(feature = "ui")]
+use crate::logging::LogBuffer;
+use crate::{
+    app::App,
+    compare::CompareArea,
+    config::{Config, WindowGeometry},
+    cover_art::CoverArt,
+    error::PlayerError,
+    frame_queue::{Frame, FrameQueue, PixelFormat, Transfer},
+    gpu::Gpu,
+    media_decoder::{DecoderCommand, MediaDecoder, PlayerEvent},
+    renderer::{select_surface_format, DrawTarget, VideoRenderer, INDICES},
+    video_window::VideoWindow,
+    UserEvent,
+};
+
+/// Decoded frames that may wait for the renderer, more means the oldest are dropped
+const FRAME_QUEUE_CAPACITY: usize = 3;
+
+/// Which decoder of which player an event comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecoderId {
+    pub window: WindowId,
+    /// The decoder of the file the current one is compared with, see `Compare`
+    pub compare: bool,
+}
+
+/// A decoder thread and the threads forwarding what it sends to the event loop
+struct Decoder {
+    frame_queue: Arc<FrameQueue>,
+    shutdown_sender: Sender<DecoderCommand>,
+    thread: JoinHandle<()>,
+    forwarding_threads: Vec<JoinHandle<()>>,
+}
+
+impl Decoder {
+    /// Also returns the sender its commands go to
+    fn spawn(
+        id: DecoderId,
+        repaint_proxy: &Arc<Mutex<EventLoopProxy<UserEvent>>>,
+    ) -> (Self, Sender<DecoderCommand>) {
+        let (decoder_command_sender, decoder_command_receiver) = unbounded::<DecoderCommand>();
+        let frame_queue = Arc::new(FrameQueue::new(FRAME_QUEUE_CAPACITY));
+        let (frame_ready_sender, frame_ready_receiver) = bounded::<()>(1);
+        let (video_info_sender, video_info_receiver) = bounded::<VideoInfo>(1);
+        let (player_event_sender, player_event_receiver) = unbounded::<PlayerEvent>();
+
+        // forward everything the decoder sends to the event loop until the decoder is gone
+        let mut forwarding_threads = Vec::new();
+        {
+            let repaint_proxy = repaint_proxy.clone();
+            forwarding_threads.push(std::thread::spawn(move || {
+                for () in frame_ready_receiver {
+                    let event = UserEvent::NewFrameReady(id);
+                    if repaint_proxy.lock().unwrap().send_event(event).is_err() {
+                        break;
+                    }
+                }
+            }));
+        }
+
+        {
+            let repaint_proxy = repaint_proxy.clone();
+            forwarding_threads.push(std::thread::spawn(move || {
+                for event in player_event_receiver {
+                    let event = UserEvent::Player(id, event);
+                    if repaint_proxy.lock().unwrap().send_event(event).is_err() {
+                        break;
+                    }
+                }
+            }));
+        }
+
+        {
+            let repaint_proxy = repaint_proxy.clone();
+            forwarding_threads.push(std::thread::spawn(move || {
+                for info in video_info_receiver {
+                    let size = PhysicalSize::new(info.width(), info.height());
+                    let event = UserEvent::VideoSizeChanged(id, size);
+                    if repaint_proxy.lock().unwrap().send_event(event).is_err() {
+                        break;
+                    }
+                }
+            }));
+        }
+
+        let thread = {
+            let frame_queue = frame_queue.clone();
+            std::thread::spawn(move || {
+                MediaDecoder::new(
+                    video_info_sender,
+                    frame_queue,
+                    frame_ready_sender,
+                    decoder_command_receiver,
+                    player_event_sender,
+                )
+                .run()
+                .unwrap_or_else(|err| tracing::error!("Decoder stopped: {}", err));
+            })
+        };
+
+        let decoder = Self {
+            frame_queue,
+            shutdown_sender: decoder_command_sender.clone(),
+            thread,
+            forwarding_threads,
+        };
+        (decoder, decoder_command_sender)
+    }
+
+    /// Stops the pipeline and the audio output, the forwarding threads end once the decoder
+    /// dropped its senders
+    fn shut_down(self) {
+        self.shutdown_sender.send(DecoderCommand::Shutdown).ok();
+        if self.thread.join().is_err() {
+            tracing::error!("The decoder thread panicked");
+        }
+        for thread in self.forwarding_threads {
+            thread.join().ok();
+        }
+    }
+}
+
+/// A window with its own player: decoder, renderer, ui and the projector and
+/// picture-in-picture windows it opened. Events for it are tagged with the id of its window.
+pub struct PlayerWindow {
+    // declared before the window so it is dropped first
+    surface: wgpu::Surface,
+    config: wgpu::SurfaceConfiguration,
+    window: Window,
+    #[cfg(feature = "ui")]
+    platform: Platform,
+    #[cfg(feature = "ui")]
+    egui_rpass: RenderPass,
+    #[cfg(feature = "ui")]
+    start_time: Instant,
+    renderer: Option<VideoRenderer>,
+    app: App,
+    decoder: Decoder,
+    /// Running while the app compares the current file with another one
+    compare_decoder: Option<Decoder>,
+    compare_renderer: Option<VideoRenderer>,
+    repaint_proxy: Arc<Mutex<EventLoopProxy<UserEvent>>>,
+    projector: Option<VideoWindow>,
+    picture_in_picture: Option<VideoWindow>,
+    #[cfg(windows)]
+    taskbar: Option<crate::taskbar::Taskbar>,
+    /// Reported once until a frame can be acquired again
+    surface_out_of_memory: bool,
+    /// Last applied by us, the window manager can change it as well
+    fullscreen: bool,
+    fullscreen_monitor: Option<String>,
+    /// The window is hidden while minimized or closed to the tray, audio keeps playing
+    hidden_to_tray: bool,
+    /// The renderer shows a still image, see `PlayerEvent::Image`
+    showing_image: bool,
+    /// The previous image and when it started fading into the current one
+    fading_out: Option<(VideoRenderer, Instant)>,
+    /// Last set, follows the song of internet radio
+    title: String,
+}
+
+impl PlayerWindow {
+    /// Opens another window next to the ones that are open
+    pub fn open(
+        target: &EventLoopWindowTarget<UserEvent>,
+        gpu: &Gpu,
+        saved_config: Rc<RefCell<Config>>,
+        #[cfg(feature = "library")] library: Option<Rc<RefCell<Library>>>,
+        #[cfg(feature = "ui")] log_buffer: LogBuffer,
+        repaint_proxy: Arc<Mutex<EventLoopProxy<UserEvent>>>,
+    ) -> Result<Self, Error> {
+        let window = winit::window::WindowBuilder::new()
+            .with_inner_size(winit::dpi::LogicalSize::new(1280, 720))
+            .with_title("wgpu-media-player")
+            .build(target)?;
+        let surface = unsafe { gpu.instance.create_surface(&window) }?;
+        if !gpu.adapter.is_surface_supported(&surface) {
+            return Err(anyhow!("the GPU can't draw to the new window"));
+        }
+        Ok(Self::new(
+            window,
+            surface,
+            gpu,
+            saved_config,
+            #[cfg(feature = "library")]
+            library,
+            #[cfg(feature = "ui")]
+            log_buffer,
+            repaint_proxy,
+        ))
+    }
+
+    /// `surface` belongs to `window` and can be drawn to by `gpu`
+    pub fn new(
+        window: Window,
+        surface: wgpu::Surface,
+        gpu: &Gpu,
+        saved_config: Rc<RefCell<Config>>,
+        #[cfg(feature = "library")] library: Option<Rc<RefCell<Library>>>,
+        #[cfg(feature = "ui")] log_buffer: LogBuffer,
+        repaint_proxy: Arc<Mutex<EventLoopProxy<UserEvent>>>,
+    ) -> Self {
+        let size = window.inner_size();
+        let capabilities = surface.get_capabilities(&gpu.adapter);
+        let format = select_surface_format(
+            &capabilities.formats,
+            saved_config.borrow().surface_format,
+            saved_config.borrow().hdr_output,
+        );
+        tracing::info!("Surface format {:?}", format);
+
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: size.width,
+            height: size.height,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: [format].to_vec(),
+        };
+        surface.configure(&gpu.device, &config);
+
+        let id = window.id();
+        let (decoder, decoder_command_sender) = Decoder::spawn(
+            DecoderId {
+                window: id,
+                compare: false,
+            },
+            &repaint_proxy,
+        );
+
+        let mut app = App::new(
+            #[cfg(feature = "ui")]
+            log_buffer,
+            saved_config,
+            #[cfg(feature = "library")]
+            library,
+        );
+        app.set_supported_present_modes(capabilities.present_modes);
+        app.set_surface_format(format);
+        app.set_window_size(size);
+        report_monitors(&mut app, &window);
+        app.set_adapters(gpu.adapter_names.clone(), gpu.adapter_label.clone());
+        app.set_on_command(move |command| {
+            decoder_command_sender.send(command).ok();
+        });
+
+        #[cfg(windows)]
+        let taskbar = {
+            let repaint_proxy = repaint_proxy.clone();
+            crate::taskbar::Taskbar::new(&window, move |key| {
+                repaint_proxy
+                    .lock()
+                    .unwrap()
+                    .send_event(UserEvent::MediaKey(Some(id), key))
+                    .ok();
+            })
+            .map_err(|err| tracing::warn!("Failed to set up the taskbar button: {:#}", err))
+            .ok()
+        };
+
+        Self {
+            #[cfg(feature = "ui")]
+            platform: new_platform(&window),
+            #[cfg(feature = "ui")]
+            egui_rpass: RenderPass::new(&gpu.device, format, 1),
+            #[cfg(feature = "ui")]
+            start_time: Instant::now(),
+            surface,
+            config,
+            window,
+            renderer: None,
+            app,
+            decoder,
+            compare_decoder: None,
+            compare_renderer: None,
+            repaint_proxy,
+            projector: None,
+            picture_in_picture: None,
+            #[cfg(windows)]
+            taskbar,
+            surface_out_of_memory: false,
+            fullscreen: false,
+            fullscreen_monitor: None,
+            hidden_to_tray: false,
+            showing_image: false,
+            fading_out: None,
+            title: "wgpu-media-player".to_string(),
+        }
+    }
+
+    pub fn id(&self) -> WindowId {
+        self.window.id()
+    }
+
+    pub fn window(&self) -> &Window {
+        &self.window
+    }
+
+    #[cfg(any(feature = "ui", feature = "network"))]
+    pub fn app(&self) -> &App {
+        &self.app
+    }
+
+    pub fn app_mut(&mut self) -> &mut App {
+        &mut self.app
+    }
+
+    /// Whether `window_id` is the window of this player or one of the windows it opened
+    pub fn owns(&self, window_id: WindowId) -> bool {
+        window_id == self.window.id()
+            || Some(window_id) == self.projector.as_ref().map(VideoWindow::window_id)
+            || Some(window_id) == self.picture_in_picture.as_ref().map(VideoWindow::window_id)
+    }
+
+    /// Moves the window over to a new GPU, `surface` belongs to the window and was created by
+    /// the instance of `gpu`. The video shows up again with the next frame, the renderers and
+    /// the textures of the ui start over on the new device.
+    pub fn set_gpu(&mut self, gpu: &Gpu, surface: wgpu::Surface) {
+        if !gpu.adapter.is_surface_supported(&surface) {
+            tracing::warn!("{} can't draw to a window", gpu.adapter_label);
+        }
+        // the swapchain of the old surface has to be gone before the new one is configured
+        self.surface = surface;
+        let capabilities = self.surface.get_capabilities(&gpu.adapter);
+        let (surface_format, hdr_output) = self.app.surface_settings();
+        let format = select_surface_format(&capabilities.formats, surface_format, hdr_output);
+        tracing::info!("Surface format {:?}", format);
+        self.config.format = format;
+        self.config.view_formats = vec![format];
+        self.surface.configure(&gpu.device, &self.config);
+        self.surface_out_of_memory = false;
+        self.app
+            .set_supported_present_modes(capabilities.present_modes);
+        self.app.set_surface_format(format);
+        self.app
+            .set_adapters(gpu.adapter_names.clone(), gpu.adapter_label.clone());
+
+        #[cfg(feature = "ui")]
+        {
+            self.platform = new_platform(&self.window);
+            self.egui_rpass = RenderPass::new(&gpu.device, format, 1);
+            self.app.forget_textures();
+        }
+
+        let window_size = self.window.inner_size();
+        let recreate = |renderer: Option<VideoRenderer>| {
+            let video_size = renderer?.video_size();
+            VideoRenderer::new(
+                window_size,
+                video_size,
+                gpu.device.clone(),
+                self.config.clone(),
+            )
+            .map_err(|err| tracing::error!("{}", err))
+            .ok()
+        };
+        self.renderer = recreate(self.renderer.take());
+        self.compare_renderer = recreate(self.compare_renderer.take());
+        self.fading_out = None;
+        // opened again on the new GPU by `update`
+        self.projector = None;
+        self.picture_in_picture = None;
+        self.request_redraw();
+    }
+
+    /// Brings the window back from the tray
+    pub fn show(&mut self) {
+        self.window.set_visible(true);
+        self.window.set_minimized(false);
+        self.window.focus_window();
+        self.hidden_to_tray = false;
+        self.app.set_background(false);
+    }
+
+    /// Keeps playing the audio without the window, until it is shown again
+    pub fn hide(&mut self) {
+        self.window.set_visible(false);
+        self.hidden_to_tray = true;
+        self.app.set_background(true);
+    }
+
+    pub fn request_redraw(&self) {
+        self.window.request_redraw();
+        if let Some(projector) = self.projector.as_ref() {
+            projector.request_redraw();
+        }
+        if let Some(picture_in_picture) = self.picture_in_picture.as_ref() {
+            picture_in_picture.request_redraw();
+        }
+    }
+
+    /// Window events and redraws of the windows it `owns`. Returns false once its window was
+    /// closed, the player should be shut down then. The window can only be minimized to the
+    /// tray when `tray_available`.
+    pub fn handle_event(
+        &mut self,
+        gpu: &Gpu,
+        event: &Event<UserEvent>,
+        tray_available: bool,
+    ) -> bool {
+        let window_id = match event {
+            Event::WindowEvent { window_id, .. } | Event::RedrawRequested(window_id) => {
+                Some(*window_id)
+            }
+            _ => None,
+        };
+        let is_projector_event =
+            window_id.is_some() && window_id == self.projector.as_ref().map(VideoWindow::window_id);
+        let is_picture_in_picture_event = window_id.is_some()
+            && window_id == self.picture_in_picture.as_ref().map(VideoWindow::window_id);
+        // egui only lives in the main window
+        #[cfg(feature = "ui")]
+        if window_id == Some(self.window.id()) {
+            self.platform.handle_event(event);
+        }
+
+        match event {
+            Event::WindowEvent { event, .. } if is_projector_event => match event {
+                WindowEvent::CloseRequested => self.app.set_projector(false),
+                WindowEvent::Resized(size) => {
+                    if let Some(projector) = self.projector.as_mut() {
+                        projector.resize(&gpu.device, *size);
+                    }
+                }
+                WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                    if let Some(projector) = self.projector.as_mut() {
+                        projector.resize(&gpu.device, **new_inner_size);
+                    }
+                }
+                WindowEvent::KeyboardInput { input, .. }
+                    if input.state == ElementState::Pressed
+                        && input.virtual_keycode == Some(VirtualKeyCode::Escape) =>
+                {
+                    self.app.set_projector(false)
+                }
+                // keep the shortcuts working while the projector has focus
+                _ => self.app.handle_window_event(event),
+            },
+            Event::RedrawRequested(_) if is_projector_event => {
+                if let Some(projector) = self.projector.as_ref() {
+                    projector.render(
+                        &gpu.device,
+                        &gpu.queue,
+                        self.renderer.as_ref().filter(|_| !self.app.audio_only()),
+                    );
+                }
+            }
+            Event::WindowEvent { event, .. } if is_picture_in_picture_event => {
+                match event {
+                    // closing it puts the video back into the main window
+                    WindowEvent::CloseRequested => self.app.set_picture_in_picture(false),
+                    WindowEvent::Resized(size) => {
+                        if let Some(picture_in_picture) = self.picture_in_picture.as_mut() {
+                            picture_in_picture.resize(&gpu.device, *size);
+                        }
+                    }
+                    WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                        if let Some(picture_in_picture) = self.picture_in_picture.as_mut() {
+                            picture_in_picture.resize(&gpu.device, **new_inner_size);
+                        }
+                    }
+                    // the mouse gestures are laid out for the main window, only the shortcuts
+                    // apply
+                    WindowEvent::KeyboardInput { .. } | WindowEvent::ModifiersChanged(_) => {
+                        self.app.handle_window_event(event)
+                    }
+                    _ => {}
+                }
+            }
+            Event::RedrawRequested(_) if is_picture_in_picture_event => {
+                if let Some(picture_in_picture) = self.picture_in_picture.as_ref() {
+                    picture_in_picture.render(
+                        &gpu.device,
+                        &gpu.queue,
+                        self.renderer.as_ref().filter(|_| !self.app.audio_only()),
+                    );
+                }
+            }
+            // left over events of a projector or picture-in-picture window that was just closed
+            Event::WindowEvent { window_id, .. } | Event::RedrawRequested(window_id)
+                if *window_id != self.window.id() => {}
+            Event::WindowEvent { event, .. } => {
+                if let WindowEvent::CloseRequested = event {
+                    if tray_available && self.app.close_to_tray() {
+                        self.hide();
+                        return true;
+                    }
+                }
+                if matches!(event, WindowEvent::CloseRequested | WindowEvent::Destroyed) {
+                    return false;
+                }
+                self.handle_window_event(gpu, event, tray_available);
+            }
+            Event::RedrawRequested(_) => self.redraw(gpu),
+            _ => {}
+        }
+        true
+    }
+
+    fn handle_window_event(&mut self, gpu: &Gpu, event: &WindowEvent, tray_available: bool) {
+        let new_size = match event {
+            WindowEvent::Resized(size) => Some(*size),
+            WindowEvent::ScaleFactorChanged { new_inner_size, .. } => Some(**new_inner_size),
+            _ => None,
+        };
+        // minimized windows have no pixels to configure the surface with
+        if let Some(size) = new_size.filter(|size| size.width > 0 && size.height > 0) {
+            self.config.width = size.width;
+            self.config.height = size.height;
+            self.surface.configure(&gpu.device, &self.config);
+
+            if let Some(renderer) = self.renderer.as_mut() {
+                renderer.handle_resize(size);
+            }
+
+            // On macos the window needs to be redrawn manually after resizing
+            self.window.request_redraw();
+
+            // e.g. left with the controls of the window manager
+            if self.window.fullscreen().is_some() != self.fullscreen {
+                self.fullscreen = !self.fullscreen;
+                self.app.set_fullscreen(self.fullscreen);
+            }
+        }
+
+        if let WindowEvent::Resized(_) = event {
+            if tray_available
+                && self.app.minimize_to_tray()
+                && self.window.is_minimized() == Some(true)
+            {
+                self.hide();
+            }
+        }
+
+        if let WindowEvent::Moved(_) = event {
+            report_monitors(&mut self.app, &self.window);
+        }
+
+        self.app.handle_window_event(event);
+
+        // without video we only redraw when the ui needs it
+        if self.app.audio_only() {
+            self.window.request_redraw();
+        }
+    }
+
+    /// Applies what the app changed, once per event loop iteration
+    pub fn update(&mut self, target: &EventLoopWindowTarget<UserEvent>, gpu: &Gpu) {
+        let app = &mut self.app;
+        let window = &self.window;
+        app.finish_drop();
+        app.advance_slideshow();
+        app.poll_jobs();
+        if app.fullscreen() != self.fullscreen
+            || (self.fullscreen && app.fullscreen_monitor() != self.fullscreen_monitor.as_deref())
+        {
+            self.fullscreen = app.fullscreen();
+            self.fullscreen_monitor = app.fullscreen_monitor().map(str::to_string);
+            // None goes fullscreen on the monitor showing the window
+            let monitor = self.fullscreen_monitor.as_ref().and_then(|name| {
+                window
+                    .available_monitors()
+                    .find(|monitor| monitor.name().as_ref() == Some(name))
+            });
+            window.set_fullscreen(self.fullscreen.then_some(Fullscreen::Borderless(monitor)));
+        }
+        let title = app.window_title();
+        if title != self.title {
+            window.set_title(&title);
+            self.title = title;
+        }
+        if let Some(scale) = app.take_window_scale() {
+            if let Some(renderer) = self.renderer.as_ref() {
+                window.set_maximized(false);
+                window.set_inner_size(snapped_window_size(window, renderer.video_size(), scale));
+            }
+        }
+        #[cfg(windows)]
+        if let Some(taskbar) = self.taskbar.as_mut() {
+            taskbar.set_state(app.progress(), app.paused());
+        }
+
+        if app.projector() != self.projector.is_some() {
+            self.projector = app
+                .projector()
+                .then(|| {
+                    VideoWindow::open_projector(
+                        target,
+                        window,
+                        &gpu.instance,
+                        &gpu.adapter,
+                        &gpu.device,
+                        self.config.format,
+                    )
+                })
+                .transpose()
+                .unwrap_or_else(|err| {
+                    tracing::warn!("Failed to open the projector: {}", err);
+                    app.set_projector(false);
+                    None
+                });
+        }
+        if app.picture_in_picture() != self.picture_in_picture.is_some() {
+            self.picture_in_picture = app
+                .picture_in_picture()
+                .then(|| {
+                    VideoWindow::open_picture_in_picture(
+                        target,
+                        window,
+                        &gpu.instance,
+                        &gpu.adapter,
+                        &gpu.device,
+                        self.config.format,
+                    )
+                })
+                .transpose()
+                .unwrap_or_else(|err| {
+                    tracing::warn!("Failed to open picture-in-picture: {}", err);
+                    app.set_picture_in_picture(false);
+                    None
+                });
+        }
+        if app.compare().is_some() != self.compare_decoder.is_some() {
+            match self.compare_decoder.take() {
+                Some(decoder) => {
+                    decoder.shut_down();
+                    self.compare_renderer = None;
+                }
+                None => {
+                    let id = DecoderId {
+                        window: window.id(),
+                        compare: true,
+                    };
+                    let (decoder, command_sender) = Decoder::spawn(id, &self.repaint_proxy);
+                    app.set_on_compare_command(move |command| {
+                        command_sender.send(command).ok();
+                    });
+                    self.compare_decoder = Some(decoder);
+                }
+            }
+        }
+
+        if !self.app.audio_only() {
+            if !self.hidden_to_tray {
+                self.window.request_redraw();
+            }
+            if let Some(projector) = self.projector.as_ref() {
+                projector.request_redraw();
+            }
+            if let Some(picture_in_picture) = self.picture_in_picture.as_ref() {
+                picture_in_picture.request_redraw();
+            }
+        }
+    }
+
+    fn redraw(&mut self, gpu: &Gpu) {
+        let _span = tracing::trace_span!("render").entered();
+        let device = &gpu.device;
+        let queue = &gpu.queue;
+
+        #[cfg(feature = "ui")]
+        self.platform
+            .update_time(self.start_time.elapsed().as_secs_f64());
+
+        if let Some(renderer) = self.renderer.as_mut() {
+            let app = &mut self.app;
+            renderer.set_scaling_mode(app.scaling_mode());
+            renderer.set_dither(app.dither());
+            renderer.set_sphere_view(app.sphere_view());
+            renderer.set_stereo(app.stereo_layout(), app.stereo_output());
+            renderer.set_aspect_ratio(app.aspect_ratio());
+            renderer.set_zoom(app.zoom());
+            renderer.set_frame_interpolation(device, app.frame_interpolation());
+            renderer.set_postprocess(&app.postprocess());
+            renderer.set_lut(device, queue, app.lut());
+            renderer.set_letterbox(device, queue, &app.letterbox(), app.letterbox_image());
+            renderer.set_ambilight(&app.ambilight());
+            if let Some(colors) = renderer.take_ambilight_colors(device) {
+                app.set_ambilight_colors(colors);
+            }
+        }
+        // both sides of a comparison go through the same processing
+        if let Some(renderer) = self.compare_renderer.as_mut() {
+            let app = &self.app;
+            renderer.set_scaling_mode(app.scaling_mode());
+            renderer.set_dither(app.dither());
+            renderer.set_sphere_view(app.sphere_view());
+            renderer.set_stereo(app.stereo_layout(), app.stereo_output());
+            renderer.set_aspect_ratio(app.aspect_ratio());
+            renderer.set_zoom(app.zoom());
+            renderer.set_frame_interpolation(device, app.frame_interpolation());
+            renderer.set_postprocess(&app.postprocess());
+            renderer.set_lut(device, queue, app.lut());
+        }
+
+        let present_mode = self.app.present_mode();
+        if self.config.present_mode != present_mode {
+            self.config.present_mode = present_mode;
+            self.surface.configure(device, &self.config);
+        }
+
+        let frame = match self.surface.get_current_texture() {
+            Ok(frame) => {
+                self.surface_out_of_memory = false;
+                frame
+            }
+            // e.g. mid resize or after the display slept, the next redraw can continue
+            Err(wgpu::SurfaceError::Outdated) => {
+                self.surface.configure(device, &self.config);
+                self.window.request_redraw();
+                return;
+            }
+            Err(wgpu::SurfaceError::Lost) => {
+                tracing::warn!("The surface was lost, creating it again");
+                match unsafe { gpu.instance.create_surface(&self.window) } {
+                    Ok(new_surface) => self.surface = new_surface,
+                    Err(err) => tracing::error!("Failed to create the surface: {}", err),
+                }
+                self.surface.configure(device, &self.config);
+                self.window.request_redraw();
+                return;
+            }
+            Err(wgpu::SurfaceError::Timeout) => {
+                tracing::debug!("Timed out acquiring the next frame");
+                self.window.request_redraw();
+                return;
+            }
+            // keep trying, the memory may be freed by other applications
+            Err(wgpu::SurfaceError::OutOfMemory) => {
+                if !self.surface_out_of_memory {
+                    self.surface_out_of_memory = true;
+                    tracing::error!("Out of GPU memory for the next frame");
+                    self.app
+                        .handle_player_event(PlayerEvent::Error(PlayerError::Gpu(
+                            "out of GPU memory".to_string(),
+                        )));
+                }
+                self.surface.configure(device, &self.config);
+                return;
+            }
+        };
+        let view = frame
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+        let window_size = PhysicalSize::new(self.config.width, self.config.height);
+        // the compared file shows up once its first frame arrived
+        let areas = self
+            .app
+            .compare()
+            .filter(|_| self.compare_renderer.is_some())
+            .map(|compare| compare.areas(window_size));
+        if let Some(renderer) = self.renderer.as_mut() {
+            if !self.app.audio_only() {
+                let size = areas.map_or(window_size, |[area, _]| area.size());
+                if renderer.window_size() != size {
+                    renderer.handle_resize(size);
+                }
+                renderer.prepare(device, queue, &mut encoder);
+                for err in renderer.take_postprocess_errors() {
+                    self.app.handle_player_event(PlayerEvent::Error(err));
+                }
+                renderer.draw_letterbox(&mut encoder, &view);
+            }
+        }
+        let crossfade = self.app.crossfade().as_secs_f32();
+        let fade = self
+            .fading_out
+            .as_ref()
+            .map(|(_, started)| started.elapsed().as_secs_f32() / crossfade.max(f32::EPSILON));
+        if fade.is_some_and(|fade| fade >= 1.0) {
+            self.fading_out = None;
+        }
+        if let Some((renderer, _)) = self.fading_out.as_mut() {
+            if !self.app.audio_only() {
+                if renderer.window_size() != window_size {
+                    renderer.handle_resize(window_size);
+                }
+                renderer.prepare(device, queue, &mut encoder);
+            }
+        }
+        if let (Some(renderer), Some([_, area])) = (self.compare_renderer.as_mut(), areas) {
+            if !self.app.audio_only() {
+                if renderer.window_size() != area.size() {
+                    renderer.handle_resize(area.size());
+                }
+                renderer.prepare(device, queue, &mut encoder);
+                // the same shaders as the current file, which reports their errors
+                renderer.take_postprocess_errors();
+            }
+        }
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+
+            if let Some(renderer) = self
+                .renderer
+                .as_ref()
+                .filter(|_| !self.app.audio_only() && !self.app.picture_in_picture())
+            {
+                let full = CompareArea::full(window_size);
+                draw_video(
+                    &mut render_pass,
+                    renderer,
+                    areas.map_or(full, |[area, _]| area),
+                    1.0,
+                );
+                if let (Some(renderer), Some([_, area])) = (self.compare_renderer.as_ref(), areas) {
+                    draw_video(&mut render_pass, renderer, area, 1.0);
+                }
+                if let (Some((renderer, _)), Some(fade)) = (self.fading_out.as_ref(), fade) {
+                    draw_video(&mut render_pass, renderer, full, 1.0 - fade as f64);
+                }
+                self.app.frame_rendered();
+            }
+        }
+
+        #[cfg(feature = "ui")]
+        let tdelta = {
+            // Begin to draw the UI frame.
+            self.platform.begin_frame();
+
+            // Draw the player ui.
+            self.app.ui(&self.platform.context());
+
+            let full_output = self.platform.end_frame(Some(&self.window));
+            let paint_jobs = self.platform.context().tessellate(full_output.shapes);
+
+            // Upload all resources for the GPU.
+            let screen_descriptor = ScreenDescriptor {
+                physical_width: self.config.width,
+                physical_height: self.config.height,
+                scale_factor: self.window.scale_factor() as f32,
+            };
+            let tdelta: egui::TexturesDelta = full_output.textures_delta;
+            self.egui_rpass
+                .add_textures(device, queue, &tdelta)
+                .expect("add texture ok");
+            self.egui_rpass
+                .update_buffers(device, queue, &paint_jobs, &screen_descriptor);
+
+            // Record all render passes.
+            self.egui_rpass
+                .execute(&mut encoder, &view, &paint_jobs, &screen_descriptor, None)
+                .unwrap();
+
+            tdelta
+        };
+
+        queue.submit(Some(encoder.finish()));
+        for renderer in self.renderer.iter().chain(&self.compare_renderer) {
+            renderer.submitted();
+        }
+        frame.present();
+
+        #[cfg(feature = "ui")]
+        self.egui_rpass
+            .remove_textures(tdelta)
+            .expect("remove texture ok");
+    }
+
+    /// Uploads the newest decoded frame of the current or the compared file
+    pub fn new_frame_ready(&mut self, gpu: &Gpu, compare: bool) {
+        let (decoder, renderer) = if compare {
+            (self.compare_decoder.as_ref(), &mut self.compare_renderer)
+        } else {
+            (Some(&self.decoder), &mut self.renderer)
+        };
+        // left over frames of a comparison that just ended
+        let Some(frame) = decoder.and_then(|decoder| decoder.frame_queue.take_latest()) else {
+            return;
+        };
+        if let Some(renderer) = renderer.as_mut() {
+            // the frame can arrive before the size change it belongs to
+            let frame_size = PhysicalSize::new(frame.width, frame.height);
+            if let Err(err) = renderer.set_video_format(&gpu.device, frame_size, frame.format) {
+                tracing::error!("{}", err);
+                return;
+            }
+            renderer.new_frame(&gpu.device, &gpu.queue, &frame);
+            if !compare {
+                self.app.frame_received();
+            }
+        }
+        self.request_redraw();
+    }
+
+    pub fn set_video_size(&mut self, gpu: &Gpu, compare: bool, size: PhysicalSize<u32>) {
+        if compare && self.compare_decoder.is_none() {
+            return;
+        }
+        let renderer = if compare {
+            self.compare_renderer.take()
+        } else {
+            self.renderer.take()
+        };
+        let new_renderer = match renderer {
+            // keep the renderer, playback continues at the new resolution
+            Some(mut current) => current.set_video_size(&gpu.device, size).map(|_| current),
+            None => VideoRenderer::new(
+                self.window.inner_size(),
+                size,
+                gpu.device.clone(),
+                self.config.clone(),
+            ),
+        };
+        let new_renderer = match new_renderer {
+            Ok(new_renderer) => Some(new_renderer),
+            Err(err) => {
+                tracing::error!("{}", err);
+                self.handle_player_event(gpu, compare, PlayerEvent::Error(err));
+                None
+            }
+        };
+        if compare {
+            self.compare_renderer = new_renderer;
+        } else {
+            self.renderer = new_renderer;
+            self.showing_image = false;
+            self.fading_out = None;
+        }
+    }
+
+    pub fn handle_player_event(&mut self, gpu: &Gpu, compare: bool, event: PlayerEvent) {
+        if compare {
+            self.app.handle_compare_event(event);
+            return;
+        }
+        match event {
+            PlayerEvent::CoverArt(art) => {
+                self.showing_image = false;
+                self.show_cover_art(gpu, art);
+            }
+            PlayerEvent::Image(image) => self.show_image(gpu, image),
+            event => self.app.handle_player_event(event),
+        }
+    }
+
+    /// Shows a still image, fading over from the previous one
+    fn show_image(&mut self, gpu: &Gpu, image: CoverArt) {
+        let crossfade = !self.app.crossfade().is_zero();
+        let previous = self
+            .renderer
+            .take()
+            .filter(|_| self.showing_image && crossfade);
+        self.show_cover_art(gpu, image);
+        self.showing_image = true;
+        self.fading_out = previous.map(|renderer| (renderer, Instant::now()));
+    }
+
+    /// Shows the cover of audio files in place of the video
+    fn show_cover_art(&mut self, gpu: &Gpu, art: CoverArt) {
+        let cover_renderer = VideoRenderer::new(
+            self.window.inner_size(),
+            PhysicalSize::new(art.width, art.height),
+            gpu.device.clone(),
+            self.config.clone(),
+        );
+        let mut cover_renderer = match cover_renderer {
+            Ok(cover_renderer) => cover_renderer,
+            Err(err) => {
+                tracing::error!("{}", err);
+                self.app.handle_player_event(PlayerEvent::Error(err));
+                return;
+            }
+        };
+        cover_renderer.new_frame(
+            &gpu.device,
+            &gpu.queue,
+            &Frame {
+                stride: 4 * art.width,
+                width: art.width,
+                height: art.height,
+                data: art.data,
+                format: PixelFormat::Rgba8,
+                transfer: Transfer::Sdr,
+                subtitles: None,
+            },
+        );
+        self.renderer = Some(cover_renderer);
+        self.request_redraw();
+    }
+
+    /// Remembers what is playing and where the window is for the next start
+    pub fn save_session(&mut self) {
+        let window = &self.window;
+        let size = window.inner_size();
+        let geometry = match window.outer_position() {
+            // fullscreen and minimized windows don't have the size to restore
+            Ok(position)
+                if window.fullscreen().is_none()
+                    && window.is_minimized() != Some(true)
+                    && size.width > 0
+                    && size.height > 0 =>
+            {
+                Some(WindowGeometry {
+                    x: position.x,
+                    y: position.y,
+                    width: size.width,
+                    height: size.height,
+                    maximized: window.is_maximized(),
+                })
+            }
+            _ => None,
+        };
+        self.app.save_session(geometry);
+    }
+
+    /// Stops the pipelines and the audio outputs
+    pub fn shut_down(self) {
+        self.decoder.shut_down();
+        if let Some(decoder) = self.compare_decoder {
+            decoder.shut_down();
+        }
+    }
+}
+
+/// Draws the video quad and its bitmap subtitles into `area` of the window, blended over what
+/// is there below an `opacity` of 1
+fn draw_video<'a>(
+    render_pass: &mut wgpu::RenderPass<'a>,
+    renderer: &'a VideoRenderer,
+    area: CompareArea,
+    opacity: f64,
+) {
+    let Some([x, y, width, height]) = area.scissor else {
+        return;
+    };
+    let [viewport_x, viewport_y, viewport_width, viewport_height] = area.viewport;
+    render_pass.set_viewport(
+        viewport_x as f32,
+        viewport_y as f32,
+        viewport_width as f32,
+        viewport_height as f32,
+        0.0,
+        1.0,
+    );
+    render_pass.set_scissor_rect(x, y, width, height);
+    // im not going to bother -> https://github.com/gfx-rs/wgpu/issues/1453
+    if opacity < 1.0 {
+        render_pass.set_pipeline(renderer.pipeline(true));
+        render_pass.set_blend_constant(wgpu::Color {
+            r: opacity,
+            g: opacity,
+            b: opacity,
+            a: opacity,
+        });
+    } else {
+        render_pass.set_pipeline(renderer.pipeline(false));
+    }
+    render_pass.set_bind_group(0, renderer.current_bind_group(), &[]);
+    render_pass.set_bind_group(1, &renderer.lut_bind_group, &[]);
+    render_pass.set_bind_group(
+        2,
+        &renderer.transform_bind_group,
+        &[renderer.transform_offset(DrawTarget::Window)],
+    );
+    render_pass.set_vertex_buffer(0, renderer.vertex_buffer.slice(..));
+    render_pass.set_index_buffer(renderer.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+    render_pass.draw_indexed(0..INDICES.len() as u32, 0, 0..1);
+    renderer.draw_subtitles(render_pass, DrawTarget::Window);
+}
+
+/// `scale` times the video size, shrunk to fit on the monitor of the window with its decorations.
+/// winit doesn't know the work area, so taskbars and docks may still overlap it.
+fn snapped_window_size(
+    window: &Window,
+    video_size: PhysicalSize<u32>,
+    scale: f64,
+) -> PhysicalSize<u32> {
+    let width = video_size.width as f64 * scale;
+    let height = video_size.height as f64 * scale;
+    let fit = window.current_monitor().map_or(1.0, |monitor| {
+        let (inner, outer) = (window.inner_size(), window.outer_size());
+        let decorations_width = outer.width.saturating_sub(inner.width) as f64;
+        let decorations_height = outer.height.saturating_sub(inner.height) as f64;
+        let available_width = monitor.size().width as f64 - decorations_width;
+        let available_height = monitor.size().height as f64 - decorations_height;
+        (available_width / width)
+            .min(available_height / height)
+            .min(1.0)
+    });
+    PhysicalSize::new(
+        (width * fit).round().max(1.0) as u32,
+        (height * fit).round().max(1.0) as u32,
+    )
+}
+
+#[cfg(feature = "ui")]
+fn new_platform(window: &Window) -> Platform {
+    let size = window.inner_size();
+    Platform::new(PlatformDescriptor {
+        physical_width: size.width,
+        physical_height: size.height,
+        scale_factor: window.scale_factor(),
+        font_definitions: FontDefinitions::default(),
+        style: Default::default(),
+    })
+}
+
+/// Monitors can be plugged in at any time, they are enumerated again when the window moves
+fn report_monitors(app: &mut App, window: &Window) {
+    app.set_monitors(
+        window
+            .available_monitors()
+            .filter_map(|monitor| monitor.name())
+            .collect(),
+        window.current_monitor().and_then(|monitor| monitor.name()),
+    );
+}
@@ -0,0 +1,129 @@
+use std::{cmp::Ordering, iter::Peekable, str::Chars};
+
+use crate::{cue, media_decoder::STDIN_URI};
+
+pub struct PlaylistItem {
+    pub uri: String,
+    pub title: String,
+    /// Applied every time the item is played, see `MediaProfile::av_offset_ms`
+    pub av_offset_ms: i64,
+}
+
+impl PlaylistItem {
+    pub fn new(uri: String) -> Self {
+        let title = if uri == STDIN_URI {
+            "Standard input".to_string()
+        } else {
+            // the tracks of a CUE sheet get their titles from it
+            let (file_uri, _) = cue::split_track_uri(&uri);
+            file_uri
+                .trim_end_matches('/')
+                .rsplit('/')
+                .next()
+                .unwrap_or(file_uri)
+                .replace("%20", " ")
+        };
+
+        Self {
+            uri,
+            title,
+            av_offset_ms: 0,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Playlist {
+    items: Vec<PlaylistItem>,
+    current: Option<usize>,
+}
+
+impl Playlist {
+    pub fn items(&self) -> &[PlaylistItem] {
+        &self.items
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    #[cfg(feature = "ui")]
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn current_index(&self) -> Option<usize> {
+        self.current
+    }
+
+    pub fn current(&self) -> Option<&PlaylistItem> {
+        self.current.and_then(|index| self.items.get(index))
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut PlaylistItem> {
+        self.items.get_mut(index)
+    }
+
+    pub fn current_mut(&mut self) -> Option<&mut PlaylistItem> {
+        self.current.and_then(|index| self.items.get_mut(index))
+    }
+
+    /// Adds an item to the end of the playlist and returns its index
+    pub fn push(&mut self, item: PlaylistItem) -> usize {
+        self.items.push(item);
+        self.items.len() - 1
+    }
+
+    /// Makes the item at `index` the current one, returns it when the index is valid
+    pub fn select(&mut self, index: usize) -> Option<&PlaylistItem> {
+        if index >= self.items.len() {
+            return None;
+        }
+
+        self.current = Some(index);
+        self.items.get(index)
+    }
+
+    /// No item is current while something outside the playlist plays
+    pub fn deselect(&mut self) {
+        self.current = None;
+    }
+}
+
+/// Compares names the way people count, so `Episode 2` comes before `Episode 10`
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x = take_number(&mut a);
+                let y = take_number(&mut b);
+                // without leading zeros the longer number is the larger one
+                let ordering = x.len().cmp(&y.len()).then_with(|| x.cmp(&y));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+fn take_number(chars: &mut Peekable<Chars>) -> String {
+    let mut number = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+        number.push(c);
+    }
+    number.trim_start_matches('0').to_string()
+}
@@ -0,0 +1,351 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Error};
+use gst::glib;
+
+use crate::{app::format_url, cue, custom_pipeline, subtitles};
+
+/// An entry of a playlist file, with the title the file gives it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlaylistEntry {
+    pub uri: String,
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    M3u,
+    Pls,
+    Xspf,
+    Cue,
+}
+
+fn format(path: &Path) -> Option<Format> {
+    match path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_lowercase)
+        .as_deref()
+    {
+        Some("m3u" | "m3u8") => Some(Format::M3u),
+        Some("pls") => Some(Format::Pls),
+        Some("xspf") => Some(Format::Xspf),
+        Some("cue") => Some(Format::Cue),
+        _ => None,
+    }
+}
+
+/// The entries of the local playlist file the uri points to, None for everything else
+pub fn read_uri(uri: &str) -> Result<Option<Vec<PlaylistEntry>>, Error> {
+    if !uri.starts_with("file://") || format(Path::new(uri)).is_none() {
+        return Ok(None);
+    }
+    // `format_url` doesn't escape the path, uris from elsewhere may be escaped
+    let unescaped = uri.trim_start_matches("file://");
+    let unescaped = if cfg!(target_os = "windows") {
+        unescaped.trim_start_matches('/')
+    } else {
+        unescaped
+    };
+    let path = if Path::new(unescaped).exists() {
+        PathBuf::from(unescaped)
+    } else {
+        glib::filename_from_uri(uri)?.0
+    };
+    read(&path)
+}
+
+/// The entries of a playlist file or the tracks of a CUE sheet, relative paths are resolved against its folder. None when it
+/// isn't a playlist file, or an .m3u8 that is an HLS stream, which playbin plays itself.
+pub fn read(path: &Path) -> Result<Option<Vec<PlaylistEntry>>, Error> {
+    let Some(format) = format(path) else {
+        return Ok(None);
+    };
+    let bytes =
+        std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    // .m3u8 is UTF-8, the others are whatever the program that wrote them used
+    let (text, _, _) = subtitles::detect_encoding(&bytes).decode(&bytes);
+    let folder = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let entries = match format {
+        Format::M3u => {
+            if text
+                .lines()
+                .any(|line| line.trim_start().starts_with("#EXT-X-"))
+            {
+                return Ok(None);
+            }
+            parse_m3u(&text, folder)
+        }
+        Format::Pls => parse_pls(&text, folder),
+        Format::Xspf => parse_xspf(&text, folder),
+        Format::Cue => cue::parse(&text, folder),
+    };
+    Ok(Some(entries))
+}
+
+fn parse_m3u(text: &str, folder: &Path) -> Vec<PlaylistEntry> {
+    let mut entries = Vec::new();
+    let mut title = None;
+    for line in text.lines().map(str::trim) {
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            // #EXTINF:<seconds>,<title>
+            title = info
+                .split_once(',')
+                .map(|(_, title)| title.trim().to_string())
+                .filter(|title| !title.is_empty());
+        } else if !line.is_empty() && !line.starts_with('#') {
+            let title = title.take();
+            if let Some(uri) = resolve(folder, line) {
+                entries.push(PlaylistEntry { uri, title });
+            }
+        }
+    }
+    entries
+}
+
+/// An ini file with File1=, Title1= and so on, in any order
+fn parse_pls(text: &str, folder: &Path) -> Vec<PlaylistEntry> {
+    let mut files = BTreeMap::new();
+    let mut titles = BTreeMap::new();
+    for line in text.lines() {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_lowercase();
+        let value = value.trim();
+        if let Some(number) = key
+            .strip_prefix("file")
+            .and_then(|number| number.parse::<u32>().ok())
+        {
+            if let Some(uri) = resolve(folder, value) {
+                files.insert(number, uri);
+            }
+        } else if let Some(number) = key
+            .strip_prefix("title")
+            .and_then(|number| number.parse::<u32>().ok())
+        {
+            titles.insert(number, value.to_string());
+        }
+    }
+    files
+        .into_iter()
+        .map(|(number, uri)| PlaylistEntry {
+            uri,
+            title: titles.remove(&number).filter(|title| !title.is_empty()),
+        })
+        .collect()
+}
+
+/// Only the location and title of each track, the rest of the XML is skipped
+fn parse_xspf(text: &str, folder: &Path) -> Vec<PlaylistEntry> {
+    let mut entries = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("<track>") {
+        rest = &rest[start + "<track>".len()..];
+        let end = rest.find("</track>").unwrap_or(rest.len());
+        let track = &rest[..end];
+        rest = &rest[end..];
+
+        let Some(location) = tag_text(track, "location") else {
+            continue;
+        };
+        let location = unescape_xml(location);
+        // locations are uris, relative ones are percent encoded paths
+        let uri = if location.contains("://") {
+            resolve(folder, &location)
+        } else {
+            let path = glib::uri_unescape_string(location.as_str(), None::<&str>)
+                .map_or(location.clone(), |path| path.to_string());
+            resolve(folder, &path)
+        };
+        let Some(uri) = uri else {
+            continue;
+        };
+        entries.push(PlaylistEntry {
+            uri,
+            title: tag_text(track, "title")
+                .map(unescape_xml)
+                .filter(|title| !title.is_empty()),
+        });
+    }
+    entries
+}
+
+fn tag_text<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    let start = xml.find(&open)? + open.len();
+    let end = start + xml[start..].find(&close)?;
+    Some(xml[start..end].trim())
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Urls stay as they are, paths are made absolute against the folder of the playlist file.
+/// None for a `pipeline:` uri, see `custom_pipeline::is_pipeline_uri`.
+pub fn resolve(folder: &Path, location: &str) -> Option<String> {
+    if custom_pipeline::is_pipeline_uri(location) {
+        tracing::warn!("Skipping the pipeline {} of a playlist file", location);
+        return None;
+    }
+    if location.contains("://") {
+        return Some(location.to_string());
+    }
+    let path = Path::new(location);
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        folder.join(path)
+    };
+    Some(format_url(&path.to_string_lossy()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folder() -> &'static Path {
+        Path::new(if cfg!(windows) { r"C:\music" } else { "/music" })
+    }
+
+    fn file_uri(name: &str) -> String {
+        format_url(&folder().join(name).to_string_lossy())
+    }
+
+    fn entry(uri: &str, title: Option<&str>) -> PlaylistEntry {
+        PlaylistEntry {
+            uri: uri.to_string(),
+            title: title.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn resolves_locations() {
+        assert_eq!(
+            resolve(folder(), "https://example.com/a.mp3").as_deref(),
+            Some("https://example.com/a.mp3")
+        );
+        assert_eq!(resolve(folder(), "a.mp3"), Some(file_uri("a.mp3")));
+        let absolute = if cfg!(windows) {
+            r"D:\other\b.mp3"
+        } else {
+            "/other/b.mp3"
+        };
+        assert_eq!(resolve(folder(), absolute), Some(format_url(absolute)));
+        assert_eq!(resolve(folder(), "pipeline:videotestsrc ! fakesink"), None);
+        assert_eq!(resolve(folder(), "PIPELINE:filesrc ! fakesink"), None);
+    }
+
+    #[test]
+    fn parses_m3u() {
+        let text = "#EXTM3U\n\
+                    #EXTINF:123, First \n\
+                    first.mp3\n\
+                    \n\
+                    # a comment\n\
+                    #EXTINF:-1,\n\
+                    http://radio.example.com/stream\n\
+                    second.mp3\n\
+                    #EXTINF:5,Pipeline\n\
+                    pipeline:videotestsrc ! fakesink\n\
+                    third.mp3\n";
+        assert_eq!(
+            parse_m3u(text, folder()),
+            vec![
+                entry(&file_uri("first.mp3"), Some("First")),
+                entry("http://radio.example.com/stream", None),
+                entry(&file_uri("second.mp3"), None),
+                entry(&file_uri("third.mp3"), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_pls() {
+        let text = "[playlist]\n\
+                    Title2=Second\n\
+                    File2=http://example.com/2.mp3\n\
+                    FILE1 = one.mp3\n\
+                    Title1=\n\
+                    Filex=skipped.mp3\n\
+                    File3=pipeline:videotestsrc ! fakesink\n\
+                    Title3=Pipeline\n\
+                    not a key\n\
+                    NumberOfEntries=3\n";
+        assert_eq!(
+            parse_pls(text, folder()),
+            vec![
+                entry(&file_uri("one.mp3"), None),
+                entry("http://example.com/2.mp3", Some("Second")),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_xspf() {
+        let text = r#"<?xml version="1.0" encoding="UTF-8"?>
+<playlist version="1" xmlns="http://xspf.org/ns/0/">
+  <trackList>
+    <track>
+      <location>my%20song.mp3</location>
+      <title>Tom &amp; Jerry</title>
+    </track>
+    <track>
+      <title>No location</title>
+    </track>
+    <track>
+      <location>http://example.com/a.mp3?x=1&amp;y=2</location>
+    </track>
+    <track>
+      <location>pipeline:videotestsrc ! fakesink</location>
+    </track>
+    <track>
+      <location>unterminated.mp3</location>
+  </trackList>
+</playlist>"#;
+        assert_eq!(
+            parse_xspf(text, folder()),
+            vec![
+                entry(&file_uri("my song.mp3"), Some("Tom & Jerry")),
+                entry("http://example.com/a.mp3?x=1&y=2", None),
+                entry(&file_uri("unterminated.mp3"), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_hls_playlists_to_playbin() {
+        let folder = std::env::temp_dir().join(format!("playlist-test-{}", std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+        let hls = folder.join("stream.m3u8");
+        std::fs::write(
+            &hls,
+            "#EXTM3U\n#EXT-X-VERSION:3\n#EXTINF:10,\nsegment0.ts\n",
+        )
+        .unwrap();
+        let list = folder.join("list.m3u");
+        std::fs::write(&list, "one.mp3\n").unwrap();
+        let not_a_playlist = folder.join("video.mp4");
+
+        assert_eq!(read(&hls).unwrap(), None);
+        assert_eq!(
+            read(&list).unwrap(),
+            Some(vec![entry(
+                &format_url(&folder.join("one.mp3").to_string_lossy()),
+                None
+            )])
+        );
+        assert_eq!(read(&not_a_playlist).unwrap(), None);
+        std::fs::remove_dir_all(&folder).ok();
+    }
+}
@@ -0,0 +1,472 @@
+use std::time::{Duration, Instant};
+
+use wgpu::util::DeviceExt;
+use winit::dpi::PhysicalSize;
+
+use crate::{config::PostprocessSettings, error::PlayerError, user_shader::UserShader};
+
+const WORKGROUP_SIZE: u32 = 8;
+/// How often the files of the user shaders are checked for changes
+const RELOAD_INTERVAL: Duration = Duration::from_millis(500);
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct PostprocessParams {
+    sharpen_strength: f32,
+    deband_threshold: f32,
+    deband_range: f32,
+    deband_grain: f32,
+    deband_iterations: u32,
+    _padding: [u32; 3],
+}
+
+impl PostprocessParams {
+    fn new(settings: &PostprocessSettings) -> Self {
+        Self {
+            sharpen_strength: settings.sharpen_strength.clamp(0.0, 1.0),
+            // a threshold of 0 would divide by 0
+            deband_threshold: settings.deband_threshold.max(0.1) / 255.0,
+            deband_range: settings.deband_range.max(1.0),
+            deband_grain: settings.deband_grain.max(0.0) / 255.0,
+            deband_iterations: settings.deband_iterations.clamp(1, 4),
+            _padding: [0; 3],
+        }
+    }
+}
+
+/// Sizes of the textures a stage reads and writes, the `FrameInfo` of user shaders
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct FrameInfo {
+    input_size: [f32; 2],
+    output_size: [f32; 2],
+    time: f32,
+    _padding: [f32; 3],
+}
+
+/// One filter of the chain, implemented in postprocess.wgsl
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PostprocessPass {
+    Denoise,
+    Deband,
+    Upscale,
+    Sharpen,
+}
+
+impl PostprocessPass {
+    /// In the order they run, sharpening comes last so it doesn't amplify noise or scaling
+    /// artifacts
+    pub const ALL: [PostprocessPass; 4] = [
+        PostprocessPass::Denoise,
+        PostprocessPass::Deband,
+        PostprocessPass::Upscale,
+        PostprocessPass::Sharpen,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PostprocessPass::Denoise => "Denoise",
+            PostprocessPass::Deband => "Deband",
+            PostprocessPass::Upscale => "Upscale",
+            PostprocessPass::Sharpen => "Sharpen",
+        }
+    }
+
+    fn entry_point(&self) -> &'static str {
+        match self {
+            PostprocessPass::Denoise => "denoise",
+            PostprocessPass::Deband => "deband",
+            PostprocessPass::Upscale => "upscale",
+            PostprocessPass::Sharpen => "sharpen",
+        }
+    }
+
+    fn enabled(&self, settings: &PostprocessSettings) -> bool {
+        match self {
+            PostprocessPass::Denoise => settings.denoise,
+            PostprocessPass::Deband => settings.deband,
+            PostprocessPass::Upscale => settings.upscale,
+            PostprocessPass::Sharpen => settings.sharpen,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StageKind {
+    Builtin(PostprocessPass),
+    /// Index into `Postprocess::user_shaders`
+    User(usize),
+}
+
+/// An enabled pass, writing its own texture that the next one reads
+struct Stage {
+    kind: StageKind,
+    input_size: PhysicalSize<u32>,
+    size: PhysicalSize<u32>,
+    frame_info_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+/// Runs the enabled filters of `PostprocessSettings` on the video texture before it is drawn,
+/// followed by the enabled user shaders.
+///
+/// All passes work on the video resolution except for the upscale, which writes the size the
+/// video is shown at so the passes after it run on the final pixels.
+pub struct Postprocess {
+    settings: PostprocessSettings,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    /// Indexed by `PostprocessPass`
+    pipelines: [wgpu::ComputePipeline; 4],
+    params_buffer: wgpu::Buffer,
+    sampler: wgpu::Sampler,
+    /// The enabled user shaders, in the order they run
+    user_shaders: Vec<UserShader>,
+    /// Compile errors of the user shaders that weren't reported yet
+    errors: Vec<PlayerError>,
+    last_reload_check: Instant,
+    start: Instant,
+    stages: Vec<Stage>,
+    /// The srgb view of the last pass, None when no pass is enabled
+    output_view: Option<wgpu::TextureView>,
+}
+
+impl Postprocess {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Postprocess Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: wgpu::TextureFormat::Rgba8Unorm,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Postprocess Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Postprocess Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("postprocess.wgsl").into()),
+        });
+        let pipelines = PostprocessPass::ALL.map(|pass| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(pass.label()),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: pass.entry_point(),
+            })
+        });
+
+        let settings = PostprocessSettings::default();
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Postprocess Params Buffer"),
+            contents: bytemuck::cast_slice(&[PostprocessParams::new(&settings)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            settings,
+            bind_group_layout,
+            pipeline_layout,
+            pipelines,
+            params_buffer,
+            sampler,
+            user_shaders: Vec::new(),
+            errors: Vec::new(),
+            last_reload_check: Instant::now(),
+            start: Instant::now(),
+            stages: Vec::new(),
+            output_view: None,
+        }
+    }
+
+    pub fn output_view(&self) -> Option<&wgpu::TextureView> {
+        self.output_view.as_ref()
+    }
+
+    pub fn settings(&self) -> &PostprocessSettings {
+        &self.settings
+    }
+
+    /// Returns whether passes were turned on or off, which needs a `rebuild`
+    pub fn set_settings(&mut self, settings: &PostprocessSettings) -> bool {
+        let passes_changed = PostprocessPass::ALL
+            .iter()
+            .any(|pass| pass.enabled(settings) != pass.enabled(&self.settings))
+            || settings.user_shaders != self.settings.user_shaders;
+        self.settings = settings.clone();
+        passes_changed
+    }
+
+    /// Errors of user shaders that failed to load since the last call
+    pub fn take_errors(&mut self) -> Vec<PlayerError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// Compiles the user shaders whose file changed, returns whether one of them was replaced
+    pub fn reload_changed_shaders(&mut self, device: &wgpu::Device) -> bool {
+        if self.user_shaders.is_empty() || self.last_reload_check.elapsed() < RELOAD_INTERVAL {
+            return false;
+        }
+        self.last_reload_check = Instant::now();
+        self.reload_user_shaders(device)
+    }
+
+    fn reload_user_shaders(&mut self, device: &wgpu::Device) -> bool {
+        let mut reloaded = false;
+        for shader in &mut self.user_shaders {
+            match shader.reload_if_changed(device, &self.pipeline_layout) {
+                Ok(changed) => reloaded |= changed,
+                Err(err) => {
+                    tracing::warn!("{}", err);
+                    self.errors.push(err);
+                }
+            }
+        }
+        reloaded
+    }
+
+    /// Sets up the textures of the enabled passes, `source` is read through a view without the
+    /// srgb decode
+    /// and `display_size` is what the upscale pass and display sized user shaders scale to
+    pub fn rebuild(
+        &mut self,
+        device: &wgpu::Device,
+        source: &wgpu::Texture,
+        video_size: PhysicalSize<u32>,
+        display_size: PhysicalSize<u32>,
+    ) {
+        // keep the shaders that stay enabled, so they aren't compiled again
+        let mut previous = std::mem::take(&mut self.user_shaders);
+        for settings in self
+            .settings
+            .user_shaders
+            .iter()
+            .filter(|shader| shader.enabled)
+        {
+            let shader = match previous
+                .iter()
+                .position(|shader| shader.path() == settings.path)
+            {
+                Some(index) => previous.swap_remove(index),
+                None => UserShader::new(settings.path.clone()),
+            };
+            self.user_shaders.push(shader);
+        }
+        self.reload_user_shaders(device);
+
+        let max_dimension = device.limits().max_texture_dimension_2d;
+        let display_size = PhysicalSize::new(
+            display_size.width.min(max_dimension),
+            display_size.height.min(max_dimension),
+        );
+        let builtin = PostprocessPass::ALL
+            .into_iter()
+            .filter(|pass| pass.enabled(&self.settings))
+            .map(StageKind::Builtin);
+        let user = self
+            .user_shaders
+            .iter()
+            .enumerate()
+            .filter(|(_, shader)| shader.pipeline().is_some())
+            .map(|(index, _)| StageKind::User(index));
+        let kinds: Vec<_> = builtin.chain(user).collect();
+
+        self.stages.clear();
+        let mut input_view = source.create_view(&wgpu::TextureViewDescriptor {
+            format: Some(source.format().remove_srgb_suffix()),
+            ..Default::default()
+        });
+        let mut size = video_size;
+        let mut output = None;
+        for kind in kinds {
+            let output_size = match kind {
+                // only scales up, downscaling is left to the scaling mode
+                StageKind::Builtin(PostprocessPass::Upscale)
+                    if display_size.width <= size.width || display_size.height <= size.height =>
+                {
+                    continue
+                }
+                StageKind::Builtin(PostprocessPass::Upscale) => display_size,
+                StageKind::User(index) if self.user_shaders[index].display_output() => display_size,
+                _ => size,
+            };
+
+            // storage textures can't be srgb, so write the encoded values and sample through an
+            // srgb view
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("Postprocess Output"),
+                size: wgpu::Extent3d {
+                    width: output_size.width,
+                    height: output_size.height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING,
+                view_formats: &[wgpu::TextureFormat::Rgba8UnormSrgb],
+            });
+            let storage_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let frame_info_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Postprocess Frame Info Buffer"),
+                size: std::mem::size_of::<FrameInfo>() as u64,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Postprocess Bind Group"),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&input_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&storage_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: self.params_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: frame_info_buffer.as_entire_binding(),
+                    },
+                ],
+            });
+
+            self.stages.push(Stage {
+                kind,
+                input_size: size,
+                size: output_size,
+                frame_info_buffer,
+                bind_group,
+            });
+            input_view = storage_view;
+            size = output_size;
+            output = Some(texture);
+        }
+
+        self.output_view = output.map(|texture| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                format: Some(wgpu::TextureFormat::Rgba8UnormSrgb),
+                ..Default::default()
+            })
+        });
+    }
+
+    /// Records the passes, they run on every redraw so they also apply to interpolated frames
+    pub fn run(&self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder) {
+        if self.stages.is_empty() {
+            return;
+        }
+
+        queue.write_buffer(
+            &self.params_buffer,
+            0,
+            bytemuck::cast_slice(&[PostprocessParams::new(&self.settings)]),
+        );
+        let time = self.start.elapsed().as_secs_f32();
+        for stage in &self.stages {
+            let (label, pipeline, workgroup_size) = match stage.kind {
+                StageKind::Builtin(pass) => (
+                    pass.label(),
+                    &self.pipelines[pass as usize],
+                    [WORKGROUP_SIZE, WORKGROUP_SIZE, 1],
+                ),
+                StageKind::User(index) => {
+                    let shader = &self.user_shaders[index];
+                    let Some(pipeline) = shader.pipeline() else {
+                        continue;
+                    };
+                    ("User Shader", pipeline, shader.workgroup_size())
+                }
+            };
+            queue.write_buffer(
+                &stage.frame_info_buffer,
+                0,
+                bytemuck::cast_slice(&[FrameInfo {
+                    input_size: [
+                        stage.input_size.width as f32,
+                        stage.input_size.height as f32,
+                    ],
+                    output_size: [stage.size.width as f32, stage.size.height as f32],
+                    time,
+                    _padding: [0.0; 3],
+                }]),
+            );
+
+            let mut pass =
+                encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some(label) });
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, &stage.bind_group, &[]);
+            pass.dispatch_workgroups(
+                stage.size.width.div_ceil(workgroup_size[0]),
+                stage.size.height.div_ceil(workgroup_size[1]),
+                1,
+            );
+        }
+    }
+}
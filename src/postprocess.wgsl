@@ -0,0 +1,194 @@
+// Filters that run on the video before it is drawn, see postprocess.rs. Like most video filters
+// they work on the srgb encoded values, the output is sampled through an srgb view again.
+
+struct Params {
+    // 0 is subtle, 1 strong
+    sharpen_strength: f32,
+    // in 0-1 units
+    deband_threshold: f32,
+    // in pixels
+    deband_range: f32,
+    deband_grain: f32,
+    deband_iterations: u32,
+}
+
+// the FrameInfo of user_shader_prelude.wgsl
+struct FrameInfo {
+    input_size: vec2<f32>,
+    output_size: vec2<f32>,
+    time: f32,
+}
+
+@group(0) @binding(0)
+var input_frame: texture_2d<f32>;
+@group(0) @binding(1)
+var output_frame: texture_storage_2d<rgba8unorm, write>;
+@group(0) @binding(2)
+var<uniform> params: Params;
+@group(0) @binding(4)
+var<uniform> frame: FrameInfo;
+
+fn load(coords: vec2<i32>) -> vec4<f32> {
+    let size = vec2<i32>(textureDimensions(input_frame));
+    return textureLoad(input_frame, clamp(coords, vec2<i32>(0), size - 1), 0);
+}
+
+fn luma(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.299, 0.587, 0.114));
+}
+
+fn random(position: vec2<f32>) -> f32 {
+    return fract(sin(dot(position, vec2<f32>(12.9898, 78.233))) * 43758.5453);
+}
+
+fn outside_output(id: vec3<u32>) -> bool {
+    let size = textureDimensions(output_frame);
+    return id.x >= u32(size.x) || id.y >= u32(size.y);
+}
+
+// 5x5 bilateral filter, smooths noise but leaves edges alone
+@compute @workgroup_size(8, 8)
+fn denoise(@builtin(global_invocation_id) id: vec3<u32>) {
+    if outside_output(id) {
+        return;
+    }
+    let coords = vec2<i32>(id.xy);
+    let center = load(coords);
+
+    var sum = vec3<f32>(0.0);
+    var weight_sum = 0.0;
+    for (var y = -2; y <= 2; y += 1) {
+        for (var x = -2; x <= 2; x += 1) {
+            let sample = load(coords + vec2<i32>(x, y)).rgb;
+            let difference = sample - center.rgb;
+            let spatial = f32(x * x + y * y) / (2.0 * 1.5 * 1.5);
+            let range = dot(difference, difference) / (2.0 * 0.08 * 0.08);
+            let weight = exp(-spatial - range);
+            sum += sample * weight;
+            weight_sum += weight;
+        }
+    }
+    textureStore(output_frame, coords, vec4<f32>(sum / weight_sum, center.a));
+}
+
+// mpv style debanding: every iteration averages four pixels around the pixel and takes that
+// average where it differs less than the threshold from the pixel, which only happens in
+// gradients. Later iterations look further with a lower threshold. The grain on top changes
+// every frame, so what is left of the steps is dithered away.
+@compute @workgroup_size(8, 8)
+fn deband(@builtin(global_invocation_id) id: vec3<u32>) {
+    if outside_output(id) {
+        return;
+    }
+    let coords = vec2<i32>(id.xy);
+    let center = load(coords);
+    let seed = vec2<f32>(coords) + fract(frame.time * vec2<f32>(12.345, 67.89)) * 100.0;
+
+    var color = center.rgb;
+    for (var i = 1u; i <= params.deband_iterations; i += 1u) {
+        let iteration = f32(i);
+        let angle = random(seed + vec2<f32>(iteration * 0.37)) * 6.2831853;
+        let distance = random(seed + vec2<f32>(iteration * 0.59)) * params.deband_range * iteration;
+        let offset = vec2<i32>(round(vec2<f32>(cos(angle), sin(angle)) * distance));
+        let average = (load(coords + offset).rgb
+            + load(coords - offset).rgb
+            + load(coords + vec2<i32>(-offset.y, offset.x)).rgb
+            + load(coords + vec2<i32>(offset.y, -offset.x)).rgb) * 0.25;
+
+        // fades instead of switching at the threshold, so no new edges appear
+        let difference = abs(color - average);
+        let threshold = params.deband_threshold / iteration;
+        let keep = clamp(difference / threshold, vec3<f32>(0.0), vec3<f32>(1.0));
+        color = mix(average, color, keep);
+    }
+
+    let noise = vec3<f32>(
+        random(seed + vec2<f32>(0.71)),
+        random(seed + vec2<f32>(1.13)),
+        random(seed + vec2<f32>(1.79))
+    );
+    color += (noise - vec3<f32>(0.5)) * params.deband_grain;
+    textureStore(output_frame, coords, vec4<f32>(color, center.a));
+}
+
+// the windowed lanczos2 approximation of FSR, takes the squared distance
+fn lanczos2(distance2: f32) -> f32 {
+    let x = min(distance2, 4.0);
+    let base = 2.0 / 5.0 * x - 1.0;
+    let window = 1.0 / 4.0 * x - 1.0;
+    return (25.0 / 16.0 * base * base - (25.0 / 16.0 - 1.0)) * (window * window);
+}
+
+// edge adaptive upscaling in the spirit of FSR's EASU: a 4x4 lanczos kernel that is stretched
+// along edges, clamped to the nearest pixels to avoid ringing
+@compute @workgroup_size(8, 8)
+fn upscale(@builtin(global_invocation_id) id: vec3<u32>) {
+    if outside_output(id) {
+        return;
+    }
+    let input_size = vec2<f32>(textureDimensions(input_frame));
+    let output_size = vec2<f32>(textureDimensions(output_frame));
+    // relative to the centers of the input pixels
+    let position = (vec2<f32>(id.xy) + 0.5) * input_size / output_size - 0.5;
+    let base = floor(position);
+    let fraction = position - base;
+    let origin = vec2<i32>(base);
+
+    let l00 = luma(load(origin).rgb);
+    let l10 = luma(load(origin + vec2<i32>(1, 0)).rgb);
+    let l01 = luma(load(origin + vec2<i32>(0, 1)).rgb);
+    let l11 = luma(load(origin + vec2<i32>(1, 1)).rgb);
+    let gradient = vec2<f32>(l10 - l00 + l11 - l01, l01 - l00 + l11 - l10);
+    let edge = length(gradient);
+    var across = vec2<f32>(1.0, 0.0);
+    if edge > 1.0 / 1024.0 {
+        across = gradient / edge;
+    }
+    let along = vec2<f32>(-across.y, across.x);
+    // the stronger the edge, the longer the kernel along it
+    let stretch = 1.0 + clamp(edge * 4.0, 0.0, 1.0);
+
+    var sum = vec3<f32>(0.0);
+    var weight_sum = 0.0;
+    var low = vec3<f32>(1.0);
+    var high = vec3<f32>(0.0);
+    for (var y = -1; y <= 2; y += 1) {
+        for (var x = -1; x <= 2; x += 1) {
+            let sample = load(origin + vec2<i32>(x, y)).rgb;
+            let offset = vec2<f32>(f32(x), f32(y)) - fraction;
+            let distance = vec2<f32>(dot(offset, across), dot(offset, along) / stretch);
+            let weight = lanczos2(dot(distance, distance));
+            sum += sample * weight;
+            weight_sum += weight;
+            if x >= 0 && x <= 1 && y >= 0 && y <= 1 {
+                low = min(low, sample);
+                high = max(high, sample);
+            }
+        }
+    }
+    let color = clamp(sum / weight_sum, low, high);
+    textureStore(output_frame, vec2<i32>(id.xy), vec4<f32>(color, 1.0));
+}
+
+// contrast adaptive sharpening, sharpens less where the contrast is already high to avoid halos
+@compute @workgroup_size(8, 8)
+fn sharpen(@builtin(global_invocation_id) id: vec3<u32>) {
+    if outside_output(id) {
+        return;
+    }
+    let coords = vec2<i32>(id.xy);
+    let center = load(coords);
+    let north = load(coords + vec2<i32>(0, -1)).rgb;
+    let south = load(coords + vec2<i32>(0, 1)).rgb;
+    let west = load(coords + vec2<i32>(-1, 0)).rgb;
+    let east = load(coords + vec2<i32>(1, 0)).rgb;
+
+    let low = min(center.rgb, min(min(north, south), min(west, east)));
+    let high = max(center.rgb, max(max(north, south), max(west, east)));
+    let headroom = min(low, vec3<f32>(1.0) - high) / max(high, vec3<f32>(1.0 / 256.0));
+    let amount = sqrt(clamp(headroom, vec3<f32>(0.0), vec3<f32>(1.0)));
+    let weight = amount * (-1.0 / mix(8.0, 5.0, params.sharpen_strength));
+    let color = (center.rgb + (north + south + west + east) * weight) / (1.0 + 4.0 * weight);
+    let clamped = clamp(color, vec3<f32>(0.0), vec3<f32>(1.0));
+    textureStore(output_frame, coords, vec4<f32>(clamped, center.a));
+}
@@ -0,0 +1,42 @@
+use url::Url;
+
+use crate::config::{ProxyMode, ProxySettings};
+
+/// The url of the proxy for `uri`, None to connect directly
+pub fn for_uri(settings: &ProxySettings, uri: &str) -> Option<String> {
+    match settings.mode {
+        ProxyMode::Environment => from_environment(uri),
+        ProxyMode::Manual => Some(settings.url.trim().to_string()).filter(|url| !url.is_empty()),
+        ProxyMode::Direct => None,
+    }
+}
+
+/// The lowercase names take precedence, like in curl
+fn env_var(name: &str) -> Option<String> {
+    [name.to_string(), name.to_uppercase()]
+        .iter()
+        .find_map(|name| std::env::var(name).ok())
+        .filter(|value| !value.trim().is_empty())
+}
+
+fn from_environment(uri: &str) -> Option<String> {
+    let url = Url::parse(uri).ok()?;
+    let host = url.host_str()?.to_lowercase();
+    if env_var("no_proxy").is_some_and(|no_proxy| bypasses(&no_proxy, &host)) {
+        return None;
+    }
+    let scheme_var = match url.scheme() {
+        "https" => "https_proxy",
+        _ => "http_proxy",
+    };
+    env_var(scheme_var).or_else(|| env_var("all_proxy"))
+}
+
+/// no_proxy lists hosts and domains separated by commas, `*` matches every host
+fn bypasses(no_proxy: &str, host: &str) -> bool {
+    no_proxy
+        .split(',')
+        .map(|entry| entry.trim().trim_start_matches('.').to_lowercase())
+        .filter(|entry| !entry.is_empty())
+        .any(|entry| entry == "*" || host == entry || host.ends_with(&format!(".{}", entry)))
+}
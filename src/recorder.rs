@@ -0,0 +1,259 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{anyhow, Context, Error};
+use gst::{glib::translate::IntoGlib, prelude::*};
+
+/// How long the muxer gets to write its index when a recording stops
+const FINISH_TIMEOUT: gst::ClockTime = gst::ClockTime::from_seconds(5);
+
+/// Copies the demuxed streams of a playing pipeline into a Matroska file, so a stream can be
+/// saved without decoding or downloading it a second time
+pub struct StreamRecorder {
+    /// Source pads of the demuxers, every stream that can be recorded
+    pads: Arc<Mutex<Vec<gst::Pad>>>,
+    recording: Arc<Mutex<Option<Recording>>>,
+}
+
+impl StreamRecorder {
+    /// Watches the demuxers that are added to `pipeline` for streams
+    pub fn attach(pipeline: &gst::Element) -> Self {
+        let pads: Arc<Mutex<Vec<gst::Pad>>> = Arc::default();
+        let recording: Arc<Mutex<Option<Recording>>> = Arc::default();
+
+        let (added_pads, added_recording) = (pads.clone(), recording.clone());
+        pipeline.connect("deep-element-added", false, move |values| {
+            let element = values[2].get::<gst::Element>().unwrap();
+            let klass = element
+                .factory()
+                .and_then(|factory| factory.metadata("klass").map(str::to_string))
+                .unwrap_or_default();
+            // hlsdemux and dashdemux output fragments of a container, demuxed again after them
+            if !klass.contains("Demuxer") || klass.contains("Adaptive") {
+                return None;
+            }
+
+            let (pads, recording) = (added_pads.clone(), added_recording.clone());
+            element.connect_pad_added(move |_, pad| {
+                if pad.direction() != gst::PadDirection::Src {
+                    return;
+                }
+                pads.lock().unwrap().push(pad.clone());
+                let recording = recording.clone();
+                pad.add_probe(gst::PadProbeType::BUFFER, move |pad, info| {
+                    if let Some(gst::PadProbeData::Buffer(buffer)) = &info.data {
+                        if let Some(recording) = recording.lock().unwrap().as_mut() {
+                            recording.push(pad, buffer);
+                        }
+                    }
+                    gst::PadProbeReturn::Ok
+                });
+            });
+            let pads = added_pads.clone();
+            element.connect_pad_removed(move |_, pad| {
+                pads.lock().unwrap().retain(|known| known != pad);
+            });
+            None
+        });
+
+        Self { pads, recording }
+    }
+
+    /// Records the streams from the next keyframe on, replaces a recording that is running
+    pub fn start(&self, path: &Path) -> Result<(), Error> {
+        self.stop();
+        let pads = self.pads.lock().unwrap().clone();
+        let recording = Recording::new(&pads, path)?;
+        *self.recording.lock().unwrap() = Some(recording);
+        Ok(())
+    }
+
+    /// Finishes the file, returns where it was saved
+    pub fn stop(&self) -> Option<PathBuf> {
+        let recording = self.recording.lock().unwrap().take()?;
+        Some(recording.finish())
+    }
+
+    /// Stops a recording that failed, e.g. because the disk is full, and returns why
+    pub fn take_error(&self) -> Option<Error> {
+        let message = {
+            let recording = self.recording.lock().unwrap();
+            recording
+                .as_ref()?
+                .pipeline
+                .bus()?
+                .pop_filtered(&[gst::MessageType::Error])?
+        };
+        let gst::MessageView::Error(err) = message.view() else {
+            return None;
+        };
+        let error = anyhow!("{} ({:?})", err.error(), err.debug());
+        // the streaming threads wait on the lock, so it isn't held while finishing
+        self.stop();
+        Some(error)
+    }
+}
+
+impl Drop for StreamRecorder {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+struct Recording {
+    pipeline: gst::Pipeline,
+    path: PathBuf,
+    /// The demuxer pad every stream comes from and the source it's pushed into
+    streams: Vec<(gst::Pad, gst_app::AppSrc)>,
+    video: Option<gst::Pad>,
+    /// Timestamp of the first recorded buffer, the recording starts at 0
+    base: Option<gst::ClockTime>,
+}
+
+impl Recording {
+    fn new(pads: &[gst::Pad], path: &Path) -> Result<Self, Error> {
+        let pipeline = gst::Pipeline::new(Some("recorder"));
+        let mux = gst::ElementFactory::make("matroskamux").build()?;
+        let sink = gst::ElementFactory::make("filesink")
+            .property("location", path.to_string_lossy().as_ref())
+            .build()?;
+        pipeline.add_many(&[&mux, &sink])?;
+        mux.link(&sink)?;
+
+        let mut streams = Vec::new();
+        let mut video = None;
+        for pad in pads {
+            let Some(caps) = pad.current_caps() else {
+                continue;
+            };
+            match add_stream(&pipeline, &mux, &caps) {
+                Ok(source) => {
+                    let is_video = caps
+                        .structure(0)
+                        .is_some_and(|structure| structure.name().starts_with("video/"));
+                    if is_video && video.is_none() {
+                        video = Some(pad.clone());
+                    }
+                    streams.push((pad.clone(), source));
+                }
+                Err(err) => tracing::warn!("Not recording {}: {:#}", caps, err),
+            }
+        }
+        if streams.is_empty() {
+            return Err(anyhow!("There are no streams that can be recorded"));
+        }
+
+        pipeline
+            .set_state(gst::State::Playing)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        tracing::info!("Recording {} streams to {}", streams.len(), path.display());
+        Ok(Self {
+            pipeline,
+            path: path.to_path_buf(),
+            streams,
+            video,
+            base: None,
+        })
+    }
+
+    fn push(&mut self, pad: &gst::Pad, buffer: &gst::Buffer) {
+        let Some((_, source)) = self.streams.iter().find(|(known, _)| known == pad) else {
+            return;
+        };
+        let base = match self.base {
+            Some(base) => base,
+            None => {
+                // players can only start decoding the file at a keyframe
+                let is_keyframe = !buffer.flags().contains(gst::BufferFlags::DELTA_UNIT);
+                if self.video.as_ref().is_some_and(|video| video != pad) || !is_keyframe {
+                    return;
+                }
+                let Some(base) = buffer.dts_or_pts() else {
+                    return;
+                };
+                *self.base.insert(base)
+            }
+        };
+
+        // whatever was demuxed just before the first keyframe is dropped
+        let Some(pts) = buffer.pts().and_then(|pts| pts.checked_sub(base)) else {
+            return;
+        };
+        let dts = buffer.dts().and_then(|dts| dts.checked_sub(base));
+        let mut buffer = buffer.copy();
+        let buffer_mut = buffer.make_mut();
+        buffer_mut.set_pts(pts);
+        buffer_mut.set_dts(dts);
+        if let Err(err) = source.push_buffer(buffer) {
+            tracing::debug!("Recording dropped a buffer: {}", err);
+        }
+    }
+
+    fn finish(self) -> PathBuf {
+        for (_, source) in &self.streams {
+            let _ = source.end_of_stream();
+        }
+        // the muxer writes the duration and the index once every stream has ended
+        if let Some(bus) = self.pipeline.bus() {
+            bus.timed_pop_filtered(
+                FINISH_TIMEOUT,
+                &[gst::MessageType::Eos, gst::MessageType::Error],
+            );
+        }
+        let _ = self.pipeline.set_state(gst::State::Null);
+        tracing::info!("Saved the recording {}", self.path.display());
+        self.path
+    }
+}
+
+/// An appsrc for a stream with `caps`, linked to the muxer through a parser when the stream
+/// needs one
+fn add_stream(
+    pipeline: &gst::Pipeline,
+    mux: &gst::Element,
+    caps: &gst::Caps,
+) -> Result<gst_app::AppSrc, Error> {
+    let source = gst_app::AppSrc::builder()
+        .caps(caps)
+        .format(gst::Format::Time)
+        .build();
+    let mut chain = vec![source.clone().upcast::<gst::Element>()];
+    chain.extend(parser_for(caps));
+    for element in &chain {
+        pipeline.add(element)?;
+    }
+
+    let linked = gst::Element::link_many(&chain.iter().collect::<Vec<_>>())
+        .and_then(|_| chain.last().unwrap().link(mux));
+    if let Err(err) = linked {
+        for element in &chain {
+            let _ = pipeline.remove(element);
+        }
+        return Err(err).context("Matroska can't hold the stream");
+    }
+    Ok(source)
+}
+
+/// Demuxers don't always output what the muxer takes, tsdemux gives H.264 as a byte-stream
+/// without alignment for example. Raw streams are taken as they are.
+fn parser_for(caps: &gst::Caps) -> Option<gst::Element> {
+    let is_raw = caps
+        .structure(0)
+        .is_none_or(|structure| structure.name().ends_with("/x-raw"));
+    if is_raw {
+        return None;
+    }
+    let mut factories: Vec<gst::ElementFactory> = gst::ElementFactory::factories_with_type(
+        gst::ElementFactoryType::PARSER,
+        gst::Rank::Marginal,
+    )
+    .into_iter()
+    .filter(|factory| factory.can_sink_all_caps(caps))
+    .collect();
+    factories.sort_by_key(|factory| std::cmp::Reverse(factory.rank().into_glib()));
+    factories
+        .first()
+        .and_then(|factory| factory.create().build().ok())
+}
@@ -3,18 +3,231 @@ use std::{num::NonZeroU32, sync::Arc};
 use wgpu::util::DeviceExt;
 use winit::dpi::PhysicalSize;
 
-use crate::texture::Texture;
+use crate::{
+    ambilight::Ambilight,
+    config::{
+        AmbilightSettings, Dither, LetterboxSettings, PostprocessSettings, StereoLayout,
+        StereoOutput, SurfaceFormat,
+    },
+    cover_art::CoverArt,
+    error::PlayerError,
+    frame_queue::{Frame, PixelFormat, Transfer},
+    interpolation::FrameInterpolator,
+    letterbox::Letterbox,
+    lut::CubeLut,
+    postprocess::Postprocess,
+    subtitle_overlay::SubtitleOverlay,
+    texture::Texture,
+};
 
 pub const INDICES: &[u16] = &[0, 1, 2, 3, 4, 5];
+/// Shown as scRGB by Windows and as extended range by macOS
+pub const HDR_SURFACE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// Kernel used to scale the video texture to the window, implemented in shader.wgsl
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ScalingMode {
+    Nearest,
+    /// Nearest neighbour at the largest whole multiple of the video size that fits
+    IntegerScale,
+    #[default]
+    Bilinear,
+    Bicubic,
+    Lanczos3,
+}
+
+impl ScalingMode {
+    pub const ALL: [ScalingMode; 5] = [
+        ScalingMode::Nearest,
+        ScalingMode::IntegerScale,
+        ScalingMode::Bilinear,
+        ScalingMode::Bicubic,
+        ScalingMode::Lanczos3,
+    ];
+
+    #[cfg(feature = "ui")]
+    pub fn label(&self) -> &'static str {
+        match self {
+            ScalingMode::Nearest => "Nearest neighbour",
+            ScalingMode::IntegerScale => "Integer scale",
+            ScalingMode::Bilinear => "Bilinear",
+            ScalingMode::Bicubic => "Bicubic",
+            ScalingMode::Lanczos3 => "Lanczos3",
+        }
+    }
+}
+
+/// Settings of the fragment shader, has to match Draw in shader.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct DrawUniform {
+    scaling_mode: u32,
+    dither: u32,
+    /// Counts the redraws, moves the temporal dither pattern
+    frame: u32,
+    /// 1 when the texture holds encoded values the sampler doesn't decode
+    encoded_texture: u32,
+    /// 1 when the surface expects srgb encoded values instead of encoding them itself
+    encode_srgb: u32,
+    /// `Transfer` of the video
+    transfer: u32,
+    /// 1 when the surface is scRGB, linear with 1.0 at 80 nits
+    hdr_output: u32,
+    stereo_layout: u32,
+    stereo_output: u32,
+    // uniform buffers need to be 16 byte aligned
+    _padding: [u32; 2],
+}
+
+/// Scales the unit quad to where the video lands on a surface, has to match Transform in
+/// shader.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct TransformUniform {
+    scale: [f32; 2],
+    offset: [f32; 2],
+    /// Yaw, pitch, tan of half the vertical field of view and aspect ratio, for 360° video
+    view: [f32; 4],
+}
+
+/// Where the camera inside a 360° video looks, in degrees
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SphereView {
+    /// Positive turns right, 0 is the center of the frame
+    pub yaw: f32,
+    /// Positive looks up, -90 to 90
+    pub pitch: f32,
+    /// Vertical field of view
+    pub fov: f32,
+}
+
+impl SphereView {
+    pub const MIN_FOV: f32 = 30.0;
+    pub const MAX_FOV: f32 = 120.0;
+}
+
+impl Default for SphereView {
+    fn default() -> Self {
+        Self {
+            yaw: 0.0,
+            pitch: 0.0,
+            fov: 90.0,
+        }
+    }
+}
+
+/// The surfaces the video is drawn on, each has its own slot in the transform buffer since
+/// their sizes differ
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrawTarget {
+    Window,
+    Projector,
+    PictureInPicture,
+}
+
+impl DrawTarget {
+    const COUNT: u64 = 3;
+}
+
+/// Picks the surface format `preference` asks for, falls back to the first one the surface
+/// supports. Float formats are only used for `hdr`, they expect scRGB values.
+pub fn select_surface_format(
+    formats: &[wgpu::TextureFormat],
+    preference: SurfaceFormat,
+    hdr: bool,
+) -> wgpu::TextureFormat {
+    if hdr {
+        if formats.contains(&HDR_SURFACE_FORMAT) {
+            return HDR_SURFACE_FORMAT;
+        }
+        tracing::warn!("The surface doesn't support HDR output, falling back to SDR");
+    }
+    let srgb = preference == SurfaceFormat::Srgb;
+    formats
+        .iter()
+        .copied()
+        .filter(|format| format.add_srgb_suffix() != format.remove_srgb_suffix())
+        .find(|format| format.describe().srgb == srgb)
+        .unwrap_or(formats[0])
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct LutUniform {
+    domain_min: [f32; 4],
+    domain_max: [f32; 4],
+    /// 0 when no LUT is applied
+    size: f32,
+    _padding: [f32; 3],
+}
+
+impl LutUniform {
+    fn new(lut: Option<&CubeLut>) -> Self {
+        let extend = |[r, g, b]: [f32; 3]| [r, g, b, 0.0];
+        Self {
+            domain_min: extend(lut.map_or([0.0; 3], |lut| lut.domain_min)),
+            domain_max: extend(lut.map_or([1.0; 3], |lut| lut.domain_max)),
+            size: lut.map_or(0.0, |lut| lut.size as f32),
+            _padding: [0.0; 3],
+        }
+    }
+}
 
 pub struct VideoRenderer {
     window_size: PhysicalSize<u32>,
     video_size: PhysicalSize<u32>,
-    pub render_pipeline: wgpu::RenderPipeline,
+    video_format: PixelFormat,
+    scaling_mode: ScalingMode,
+    dither: Dither,
+    frame_count: u32,
+    /// The surface isn't srgb, so the shader encodes the output
+    encode_srgb: bool,
+    hdr_output: bool,
+    transfer: Transfer,
+    render_pipeline: wgpu::RenderPipeline,
+    /// Blends the video over what was drawn before, with the blend constant as its opacity
+    crossfade_pipeline: wgpu::RenderPipeline,
+    /// Draw 360° video through `sphere_view` instead, see fs_sphere in shader.wgsl
+    sphere_pipeline: wgpu::RenderPipeline,
+    sphere_crossfade_pipeline: wgpu::RenderPipeline,
+    /// Set while the video is equirectangular
+    sphere_view: Option<SphereView>,
+    stereo_layout: StereoLayout,
+    stereo_output: StereoOutput,
+    /// Width divided by height the video is shown at instead of its own
+    aspect_ratio: Option<f32>,
+    /// Scales the fitted video, parts that end up outside the surface are cut off
+    zoom: f32,
     pub bind_group: wgpu::BindGroup,
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
+    draw_buffer: wgpu::Buffer,
     texture: Texture,
+    /// Frames are copied from here into the texture, its rows are padded to
+    /// `COPY_BYTES_PER_ROW_ALIGNMENT`
+    upload_buffer: wgpu::Buffer,
+    /// Reused for repacking frames whose stride isn't aligned
+    padded_rows: Vec<u8>,
+    bind_group_layout: wgpu::BindGroupLayout,
+    /// Set while frame interpolation is enabled, together with the bind group sampling its output
+    interpolation: Option<(FrameInterpolator, wgpu::BindGroup)>,
+    postprocess: Postprocess,
+    /// Samples the output of the postprocess chain, None when no pass is enabled
+    postprocess_bind_group: Option<wgpu::BindGroup>,
+    /// Set when the postprocess textures have to be recreated before the next draw
+    postprocess_outdated: bool,
+    /// Slots of `transform_stride` bytes, indexed by `DrawTarget`
+    transform_buffer: wgpu::Buffer,
+    transform_stride: u64,
+    /// Group 2 of the render pipeline, bound with `transform_offset`
+    pub transform_bind_group: wgpu::BindGroup,
+    /// Group 1 of the render pipeline, the color grading LUT
+    pub lut_bind_group: wgpu::BindGroup,
+    lut_bind_group_layout: wgpu::BindGroupLayout,
+    lut: Option<Arc<CubeLut>>,
+    letterbox: Letterbox,
+    ambilight: Ambilight,
+    subtitle_overlay: SubtitleOverlay,
 }
 
 impl VideoRenderer {
@@ -23,7 +236,9 @@ impl VideoRenderer {
         video_size: PhysicalSize<u32>,
         device: Arc<wgpu::Device>,
         config: wgpu::SurfaceConfiguration,
-    ) -> Self {
+    ) -> Result<Self, PlayerError> {
+        check_video_size(&device, video_size)?;
+
         let texture_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 entries: &[
@@ -45,44 +260,128 @@ impl VideoRenderer {
                         ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                         count: None,
                     },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
                 ],
                 label: Some("texture_bind_group_layout"),
             });
 
+        let lut_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D3,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+                label: Some("lut_bind_group_layout"),
+            });
+
+        let transform_size = std::mem::size_of::<TransformUniform>() as u64;
+        let transform_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    // fs_sphere reads the view
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: wgpu::BufferSize::new(transform_size),
+                    },
+                    count: None,
+                }],
+                label: Some("transform_bind_group_layout"),
+            });
+
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[&texture_bind_group_layout],
+                bind_group_layouts: &[
+                    &texture_bind_group_layout,
+                    &lut_bind_group_layout,
+                    &transform_bind_group_layout,
+                ],
                 push_constant_ranges: &[],
             });
 
-        let texture_to_render = Texture::new(
-            &device,
-            (video_size.width, video_size.height),
-            Some("Video"),
-        )
-        .unwrap();
+        let video_format = PixelFormat::default();
+        let (texture_to_render, upload_buffer) =
+            create_video_texture(&device, video_size, video_format)?;
 
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &texture_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&texture_to_render.view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&texture_to_render.sampler),
-                },
-            ],
-            label: Some("diffuse_bind_group"),
+        let scaling_mode = ScalingMode::default();
+        // written before every draw, see prepare
+        let draw_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Draw Buffer"),
+            contents: bytemuck::cast_slice(&[DrawUniform::default()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
+        let bind_group = VideoRenderer::create_bind_group(
+            &device,
+            &texture_bind_group_layout,
+            &texture_to_render.view,
+            &texture_to_render.sampler,
+            &draw_buffer,
+        );
+
+        // the quad covers the whole surface, the transform scales it down to the video
         let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(&VideoRenderer::get_vertices(window_size, video_size)),
+            contents: bytemuck::cast_slice(&QUAD),
             usage: wgpu::BufferUsages::VERTEX,
         });
+        // every slot has to start at an offset the device can bind
+        let transform_stride =
+            transform_size.max(device.limits().min_uniform_buffer_offset_alignment.into());
+        let transform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Transform Buffer"),
+            size: transform_stride * DrawTarget::COUNT,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let transform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &transform_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &transform_buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(transform_size),
+                }),
+            }],
+            label: Some("transform_bind_group"),
+        });
         let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Index Buffer"),
             contents: bytemuck::cast_slice(INDICES),
@@ -94,94 +393,665 @@ impl VideoRenderer {
             source: wgpu::ShaderSource::Wgsl(include_str!("shader.wgsl").into()),
         });
 
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[Vertex::desc()],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState {
-                        color: wgpu::BlendComponent::REPLACE,
-                        alpha: wgpu::BlendComponent::REPLACE,
-                    }),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                // Setting this to anything other than Fill requires Features::POLYGON_MODE_LINE
-                // or Features::POLYGON_MODE_POINT
-                polygon_mode: wgpu::PolygonMode::Fill,
-                // Requires Features::DEPTH_CLIP_CONTROL
-                unclipped_depth: false,
-                // Requires Features::CONSERVATIVE_RASTERIZATION
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
+        let create_pipeline = |label: &str, entry_point: &str, blend: wgpu::BlendState| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[Vertex::desc()],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point,
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: config.format,
+                        blend: Some(blend),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: Some(wgpu::Face::Back),
+                    // Setting this to anything other than Fill requires Features::POLYGON_MODE_LINE
+                    // or Features::POLYGON_MODE_POINT
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    // Requires Features::DEPTH_CLIP_CONTROL
+                    unclipped_depth: false,
+                    // Requires Features::CONSERVATIVE_RASTERIZATION
+                    conservative: false,
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                // If the pipeline will be used with a multiview render pass, this
+                // indicates how many array layers the attachments will have.
+                multiview: None,
+            })
+        };
+        let replace = wgpu::BlendState {
+            color: wgpu::BlendComponent::REPLACE,
+            alpha: wgpu::BlendComponent::REPLACE,
+        };
+        let crossfade = wgpu::BlendState {
+            color: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::Constant,
+                dst_factor: wgpu::BlendFactor::OneMinusConstant,
+                operation: wgpu::BlendOperation::Add,
             },
-            // If the pipeline will be used with a multiview render pass, this
-            // indicates how many array layers the attachments will have.
-            multiview: None,
-        });
+            alpha: wgpu::BlendComponent::REPLACE,
+        };
+        let render_pipeline = create_pipeline("Render Pipeline", "fs_main", replace);
+        let crossfade_pipeline = create_pipeline("Crossfade Pipeline", "fs_main", crossfade);
+        let sphere_pipeline = create_pipeline("Sphere Pipeline", "fs_sphere", replace);
+        let sphere_crossfade_pipeline =
+            create_pipeline("Sphere Crossfade Pipeline", "fs_sphere", crossfade);
 
-        Self {
+        // the layout needs a texture even without a LUT, the shader skips it then
+        let lut_bind_group = VideoRenderer::create_lut_bind_group(
+            &device,
+            &lut_bind_group_layout,
+            &VideoRenderer::create_lut_texture(&device, 1),
+            LutUniform::new(None),
+        );
+
+        Ok(Self {
             window_size,
             video_size,
+            video_format,
+            scaling_mode,
+            dither: Dither::default(),
+            frame_count: 0,
+            encode_srgb: !config.format.describe().srgb && config.format != HDR_SURFACE_FORMAT,
+            hdr_output: config.format == HDR_SURFACE_FORMAT,
+            transfer: Transfer::default(),
             bind_group,
             index_buffer,
             render_pipeline,
+            crossfade_pipeline,
+            sphere_pipeline,
+            sphere_crossfade_pipeline,
+            sphere_view: None,
+            stereo_layout: StereoLayout::default(),
+            stereo_output: StereoOutput::default(),
+            aspect_ratio: None,
+            zoom: 1.0,
             vertex_buffer,
+            draw_buffer,
             texture: texture_to_render,
+            upload_buffer,
+            padded_rows: Vec::new(),
+            bind_group_layout: texture_bind_group_layout,
+            interpolation: None,
+            postprocess: Postprocess::new(&device),
+            postprocess_bind_group: None,
+            postprocess_outdated: false,
+            transform_buffer,
+            transform_stride,
+            transform_bind_group,
+            lut_bind_group,
+            lut_bind_group_layout,
+            lut: None,
+            letterbox: Letterbox::new(&device, config.format),
+            ambilight: Ambilight::new(&device),
+            subtitle_overlay: SubtitleOverlay::new(
+                &device,
+                config.format,
+                &transform_bind_group_layout,
+            ),
+        })
+    }
+
+    pub fn video_size(&self) -> PhysicalSize<u32> {
+        self.video_size
+    }
+
+    /// Switches to a new video resolution without recreating the pipeline, for streams that
+    /// change their caps while playing
+    pub fn set_video_size(
+        &mut self,
+        device: &wgpu::Device,
+        video_size: PhysicalSize<u32>,
+    ) -> Result<(), PlayerError> {
+        self.set_video_format(device, video_size, self.video_format)
+    }
+
+    /// Like `set_video_size`, also switches between 8 and 10 bit textures
+    pub fn set_video_format(
+        &mut self,
+        device: &wgpu::Device,
+        video_size: PhysicalSize<u32>,
+        video_format: PixelFormat,
+    ) -> Result<(), PlayerError> {
+        if video_size == self.video_size && video_format == self.video_format {
+            return Ok(());
         }
+        check_video_size(device, video_size)?;
+
+        let (texture, upload_buffer) = create_video_texture(device, video_size, video_format)?;
+        self.bind_group = VideoRenderer::create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &texture.view,
+            &texture.sampler,
+            &self.draw_buffer,
+        );
+        self.texture = texture;
+        self.upload_buffer = upload_buffer;
+        self.video_size = video_size;
+        self.video_format = video_format;
+
+        // the interpolator keeps its own textures at the old size and format
+        let interpolate = self.interpolation.take().is_some();
+        self.set_frame_interpolation(device, interpolate);
+        self.postprocess_outdated = true;
+        Ok(())
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        view: &wgpu::TextureView,
+        sampler: &wgpu::Sampler,
+        draw_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: draw_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("diffuse_bind_group"),
+        })
     }
 
-    pub fn new_frame(&self, queue: &wgpu::Queue, data: &[u8]) {
-        queue.write_texture(
-            wgpu::ImageCopyTexture {
-                aspect: wgpu::TextureAspect::All,
-                texture: &self.texture.texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
+    /// Applies the LUT to everything drawn from now on, None turns color grading off
+    pub fn set_lut(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        lut: Option<&Arc<CubeLut>>,
+    ) {
+        let unchanged = match (lut, &self.lut) {
+            (Some(lut), Some(current)) => Arc::ptr_eq(lut, current),
+            (None, None) => true,
+            _ => false,
+        };
+        if unchanged {
+            return;
+        }
+        self.lut = lut.cloned();
+
+        let texture = VideoRenderer::create_lut_texture(device, lut.map_or(1, |lut| lut.size));
+        if let Some(lut) = lut {
+            queue.write_texture(
+                texture.as_image_copy(),
+                bytemuck::cast_slice(&lut.rgb10a2_texels()),
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(4 * lut.size),
+                    rows_per_image: NonZeroU32::new(lut.size),
+                },
+                wgpu::Extent3d {
+                    width: lut.size,
+                    height: lut.size,
+                    depth_or_array_layers: lut.size,
+                },
+            );
+        }
+        self.lut_bind_group = VideoRenderer::create_lut_bind_group(
+            device,
+            &self.lut_bind_group_layout,
+            &texture,
+            LutUniform::new(lut.map(|lut| lut.as_ref())),
+        );
+    }
+
+    fn create_lut_texture(device: &wgpu::Device, size: u32) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("LUT"),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: size,
             },
-            data,
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: NonZeroU32::new(4 * self.video_size.width),
-                rows_per_image: NonZeroU32::new(self.video_size.height),
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format: wgpu::TextureFormat::Rgb10a2Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        })
+    }
+
+    fn create_lut_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        texture: &wgpu::Texture,
+        uniform: LutUniform,
+    ) -> wgpu::BindGroup {
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("LUT Buffer"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("lut_bind_group"),
+        })
+    }
+
+    /// The bind group to draw with, the interpolated frame when interpolation is enabled
+    pub fn current_bind_group(&self) -> &wgpu::BindGroup {
+        if let Some(bind_group) = &self.postprocess_bind_group {
+            return bind_group;
+        }
+        match &self.interpolation {
+            Some((_, bind_group)) => bind_group,
+            None => &self.bind_group,
+        }
+    }
+
+    /// The view `current_bind_group` samples
+    fn current_view(&self) -> &wgpu::TextureView {
+        if let Some(view) = self.postprocess.output_view() {
+            return view;
+        }
+        match &self.interpolation {
+            Some((interpolator, _)) => interpolator.output_view(),
+            None => &self.texture.view,
+        }
+    }
+
+    pub fn set_letterbox(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        settings: &LetterboxSettings,
+        image: Option<&Arc<CoverArt>>,
+    ) {
+        self.letterbox.set_settings(settings);
+        self.letterbox.set_image(device, queue, image);
+    }
+
+    /// Clears the window and fills it with the letterbox background, before the video is drawn
+    pub fn draw_letterbox(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        self.letterbox.draw(encoder, view);
+    }
+
+    pub fn set_ambilight(&mut self, settings: &AmbilightSettings) {
+        self.ambilight.set_settings(settings);
+    }
+
+    /// Call after submitting the encoder passed to `prepare`
+    pub fn submitted(&self) {
+        self.ambilight.submitted();
+    }
+
+    /// The edge colors of a recent frame, see `Ambilight`
+    pub fn take_ambilight_colors(&self, device: &wgpu::Device) -> Option<Vec<[u8; 3]>> {
+        self.ambilight.take_colors(device)
+    }
+
+    pub fn set_postprocess(&mut self, settings: &PostprocessSettings) {
+        if settings != self.postprocess.settings() {
+            self.postprocess_outdated |= self.postprocess.set_settings(settings);
+        }
+    }
+
+    /// User shaders that failed to compile since the last call
+    pub fn take_postprocess_errors(&mut self) -> Vec<PlayerError> {
+        self.postprocess.take_errors()
+    }
+
+    /// Points the postprocess chain at the current frame, the interpolated one when enabled
+    fn rebuild_postprocess(&mut self, device: &wgpu::Device) {
+        let source = match &self.interpolation {
+            Some((interpolator, _)) => interpolator.output_texture(),
+            None => &self.texture.texture,
+        };
+        let (width, height) =
+            VideoRenderer::video_extent(self.window_size, self.video_size, self.scaling_mode);
+        let display_size = PhysicalSize::new(
+            (width * self.window_size.width as f32).round() as u32,
+            (height * self.window_size.height as f32).round() as u32,
+        );
+        self.postprocess
+            .rebuild(device, source, self.video_size, display_size);
+        self.postprocess_bind_group = self.postprocess.output_view().map(|view| {
+            VideoRenderer::create_bind_group(
+                device,
+                &self.bind_group_layout,
+                view,
+                &self.texture.sampler,
+                &self.draw_buffer,
+            )
+        });
+    }
+
+    pub fn set_frame_interpolation(&mut self, device: &wgpu::Device, enabled: bool) {
+        if enabled == self.interpolation.is_some() {
+            return;
+        }
+
+        self.interpolation = enabled.then(|| {
+            let interpolator =
+                FrameInterpolator::new(device, &self.texture.texture, self.video_size);
+            let bind_group = VideoRenderer::create_bind_group(
+                device,
+                &self.bind_group_layout,
+                interpolator.output_view(),
+                &self.texture.sampler,
+                &self.draw_buffer,
+            );
+            (interpolator, bind_group)
+        });
+        self.postprocess_outdated = true;
+    }
+
+    /// Records the work that has to happen before the video can be drawn
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+    ) {
+        if let Some((interpolator, _)) = self.interpolation.as_mut() {
+            interpolator.interpolate(queue, encoder);
+        }
+        // a user shader that was edited can also have changed its output size
+        self.postprocess_outdated |= self.postprocess.reload_changed_shaders(device);
+        if self.postprocess_outdated {
+            self.postprocess_outdated = false;
+            self.rebuild_postprocess(device);
+        }
+        self.postprocess.run(queue, encoder);
+
+        self.write_transform(queue, DrawTarget::Window, self.window_size);
+
+        if self.dither == Dither::Temporal {
+            self.frame_count = self.frame_count.wrapping_add(1);
+        }
+        // only the video texture itself can hold encoded values, 10 bit textures have no srgb
+        // format, the passes write 8 bit textures that are sampled through srgb views
+        let encoded_texture = self.interpolation.is_none()
+            && self.postprocess.output_view().is_none()
+            && !self.texture.texture.format().describe().srgb;
+        queue.write_buffer(
+            &self.draw_buffer,
+            0,
+            bytemuck::cast_slice(&[DrawUniform {
+                scaling_mode: self.scaling_mode as u32,
+                dither: self.dither as u32,
+                frame: self.frame_count,
+                encoded_texture: encoded_texture as u32,
+                encode_srgb: self.encode_srgb as u32,
+                transfer: self.transfer as u32,
+                hdr_output: self.hdr_output as u32,
+                stereo_layout: self.stereo_layout as u32,
+                stereo_output: self.stereo_output as u32,
+                _padding: [0; 2],
+            }]),
+        );
+
+        self.letterbox.prepare(
+            device,
+            queue,
+            encoder,
+            self.current_view(),
+            encoded_texture,
+            self.window_size,
+            self.video_size,
+        );
+        self.ambilight
+            .extract(device, queue, encoder, self.current_view(), encoded_texture);
+    }
+
+    /// Uploads the frame, its rows may be padded. Buffer to texture copies need rows aligned to
+    /// `COPY_BYTES_PER_ROW_ALIGNMENT`, so unless the decoder already aligned them the rows are
+    /// repacked first, which makes widths like 1366 or 854 work.
+    pub fn new_frame(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, frame: &Frame) {
+        let _span = tracing::trace_span!("upload").entered();
+
+        // frames of the previous file can still arrive right after switching
+        if frame.width != self.video_size.width
+            || frame.height != self.video_size.height
+            || frame.format != self.video_format
+        {
+            return;
+        }
+        let row_size = 4 * frame.width;
+        if frame.stride < row_size
+            || frame.data.len() < (frame.stride * (frame.height - 1) + row_size) as usize
+        {
+            return;
+        }
+        self.subtitle_overlay
+            .set_bitmaps(device, queue, frame.subtitles.as_ref(), self.video_size);
+
+        if let Some((interpolator, _)) = self.interpolation.as_mut() {
+            interpolator.before_new_frame(device, queue, &self.texture.texture);
+        }
+        self.transfer = frame.transfer;
+
+        let upload_stride = padded_row_size(frame.width);
+        let upload_size = upload_stride as usize * frame.height as usize;
+        if frame.stride == upload_stride && frame.data.len() >= upload_size {
+            queue.write_buffer(&self.upload_buffer, 0, &frame.data[..upload_size]);
+        } else {
+            let row_size = row_size as usize;
+            self.padded_rows.resize(upload_size, 0);
+            for (padded_row, row) in self
+                .padded_rows
+                .chunks_exact_mut(upload_stride as usize)
+                .zip(frame.data.chunks(frame.stride as usize))
+            {
+                padded_row[..row_size].copy_from_slice(&row[..row_size]);
+            }
+            queue.write_buffer(&self.upload_buffer, 0, &self.padded_rows);
+        }
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Video Upload"),
+        });
+        encoder.copy_buffer_to_texture(
+            wgpu::ImageCopyBuffer {
+                buffer: &self.upload_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(upload_stride),
+                    rows_per_image: NonZeroU32::new(frame.height),
+                },
             },
+            self.texture.texture.as_image_copy(),
             wgpu::Extent3d {
-                width: self.video_size.width,
-                height: self.video_size.height,
+                width: frame.width,
+                height: frame.height,
                 depth_or_array_layers: 1,
             },
         );
+        queue.submit(Some(encoder.finish()));
+    }
+
+    pub fn set_scaling_mode(&mut self, scaling_mode: ScalingMode) {
+        if self.scaling_mode == scaling_mode {
+            return;
+        }
+
+        let integer_scale_changed = (self.scaling_mode == ScalingMode::IntegerScale)
+            != (scaling_mode == ScalingMode::IntegerScale);
+        self.scaling_mode = scaling_mode;
+
+        if integer_scale_changed {
+            self.handle_resize(self.window_size);
+        }
+    }
+
+    pub fn set_dither(&mut self, dither: Dither) {
+        self.dither = dither;
     }
 
-    // resize vertex buffer, black bars etc..
-    pub fn handle_resize(&mut self, device: &wgpu::Device, size: PhysicalSize<u32>) {
+    /// Shows the video as the inside of a sphere seen through `view`, None draws it flat
+    pub fn set_sphere_view(&mut self, view: Option<SphereView>) {
+        self.sphere_view = view;
+    }
+
+    /// Shows `output` of 3D video whose eyes are laid out as `layout`
+    pub fn set_stereo(&mut self, layout: StereoLayout, output: StereoOutput) {
+        self.stereo_layout = layout;
+        self.stereo_output = output;
+    }
+
+    /// Shows the video at `aspect_ratio` instead of its own, None goes back to its own
+    pub fn set_aspect_ratio(&mut self, aspect_ratio: Option<f32>) {
+        self.aspect_ratio = aspect_ratio;
+    }
+
+    pub fn set_zoom(&mut self, zoom: f32) {
+        self.zoom = zoom;
+    }
+
+    /// The size of what is shown of the frame, one eye of full resolution 3D video. Half
+    /// resolution 3D video squeezes the eyes to the size of a 2D frame, they are stretched back.
+    fn shown_size(&self) -> PhysicalSize<u32> {
+        let PhysicalSize { width, height } = self.video_size;
+        let aspect = width as f32 / height.max(1) as f32;
+        let size = match self.stereo_layout {
+            StereoLayout::SideBySide if aspect > 2.5 => PhysicalSize::new(width / 2, height),
+            StereoLayout::TopBottom if aspect < 1.0 => PhysicalSize::new(width, height / 2),
+            _ => self.video_size,
+        };
+        match self.aspect_ratio {
+            Some(aspect_ratio) => PhysicalSize::new(
+                (size.height as f32 * aspect_ratio).round() as u32,
+                size.height,
+            ),
+            None => size,
+        }
+    }
+
+    /// The pipeline to draw the video with, `crossfade` blends it with the blend constant as
+    /// its opacity
+    pub fn pipeline(&self, crossfade: bool) -> &wgpu::RenderPipeline {
+        match (self.sphere_view.is_some(), crossfade) {
+            (false, false) => &self.render_pipeline,
+            (false, true) => &self.crossfade_pipeline,
+            (true, false) => &self.sphere_pipeline,
+            (true, true) => &self.sphere_crossfade_pipeline,
+        }
+    }
+
+    /// Fits the video into `target`, written before every draw so resizing only has to
+    /// remember the size
+    pub fn write_transform(
+        &self,
+        queue: &wgpu::Queue,
+        target: DrawTarget,
+        window_size: PhysicalSize<u32>,
+    ) {
+        let (scale, view) = match self.sphere_view {
+            // the camera sees the sphere on the whole surface
+            Some(view) => {
+                let aspect = window_size.width.max(1) as f32 / window_size.height.max(1) as f32;
+                let view = [
+                    view.yaw.to_radians(),
+                    view.pitch.to_radians(),
+                    (view.fov.to_radians() / 2.0).tan(),
+                    aspect,
+                ];
+                ([1.0, 1.0], view)
+            }
+            None => {
+                let (width, height) =
+                    VideoRenderer::video_extent(window_size, self.shown_size(), self.scaling_mode);
+                ([width * self.zoom, height * self.zoom], [0.0; 4])
+            }
+        };
+        queue.write_buffer(
+            &self.transform_buffer,
+            self.transform_offset(target) as u64,
+            bytemuck::cast_slice(&[TransformUniform {
+                scale,
+                offset: [0.0, 0.0],
+                view,
+            }]),
+        );
+    }
+
+    /// Draws the bitmap subtitles of the current frame over the video drawn for `target`
+    pub fn draw_subtitles<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        target: DrawTarget,
+    ) {
+        self.subtitle_overlay.draw(
+            render_pass,
+            &self.transform_bind_group,
+            self.transform_offset(target),
+        );
+    }
+
+    /// The dynamic offset to bind `transform_bind_group` with
+    pub fn transform_offset(&self, target: DrawTarget) -> u32 {
+        (target as u64 * self.transform_stride) as u32
+    }
+
+    /// The size the video is fitted into, the window or a part of it
+    pub fn window_size(&self) -> PhysicalSize<u32> {
+        self.window_size
+    }
+
+    pub fn handle_resize(&mut self, size: PhysicalSize<u32>) {
         self.window_size = size;
-        self.vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(&VideoRenderer::get_vertices(size, self.video_size)),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
+        // the upscale pass scales to the size the video is shown at
+        self.postprocess_outdated |= self.postprocess.settings().upscale;
     }
 
-    fn get_vertices(window_size: PhysicalSize<u32>, video_size: PhysicalSize<u32>) -> Vec<Vertex> {
+    /// Fraction of the window width and height the video covers
+    fn video_extent(
+        window_size: PhysicalSize<u32>,
+        video_size: PhysicalSize<u32>,
+        scaling_mode: ScalingMode,
+    ) -> (f32, f32) {
         let screen_width = window_size.width as f32;
         let screen_height = window_size.height as f32;
 
@@ -194,41 +1064,47 @@ impl VideoRenderer {
             vertex_height = 1.0;
         }
 
-        let top_left: [f32; 3] = [-vertex_width, vertex_height, 0.0];
-        let bottom_left: [f32; 3] = [-vertex_width, -vertex_height, 0.0];
-        let top_right: [f32; 3] = [vertex_width, vertex_height, 0.0];
-        let bottom_right: [f32; 3] = [vertex_width, -vertex_height, 0.0];
+        // only snap when the video fits at least once, otherwise fall back to fitting it
+        let integer_scale = (window_size.width / video_size.width.max(1))
+            .min(window_size.height / video_size.height.max(1));
+        if scaling_mode == ScalingMode::IntegerScale && integer_scale >= 1 {
+            vertex_width = (video_size.width * integer_scale) as f32 / screen_width;
+            vertex_height = (video_size.height * integer_scale) as f32 / screen_height;
+        }
 
-        vec![
-            Vertex {
-                position: top_left,
-                tex_coords: [0.0, 0.0],
-            },
-            Vertex {
-                position: bottom_left,
-                tex_coords: [0.0, 1.0],
-            },
-            Vertex {
-                position: bottom_right,
-                tex_coords: [1.0, 1.0],
-            },
-            // second triangle
-            Vertex {
-                position: top_left,
-                tex_coords: [0.0, 0.0],
-            },
-            Vertex {
-                position: bottom_right,
-                tex_coords: [1.0, 1.0],
-            },
-            Vertex {
-                position: top_right,
-                tex_coords: [1.0, 0.0],
-            },
-        ]
+        (vertex_width, vertex_height)
     }
 }
 
+/// Two triangles covering the surface
+const QUAD: [Vertex; 6] = [
+    Vertex {
+        position: [-1.0, 1.0, 0.0],
+        tex_coords: [0.0, 0.0],
+    },
+    Vertex {
+        position: [-1.0, -1.0, 0.0],
+        tex_coords: [0.0, 1.0],
+    },
+    Vertex {
+        position: [1.0, -1.0, 0.0],
+        tex_coords: [1.0, 1.0],
+    },
+    // second triangle
+    Vertex {
+        position: [-1.0, 1.0, 0.0],
+        tex_coords: [0.0, 0.0],
+    },
+    Vertex {
+        position: [1.0, -1.0, 0.0],
+        tex_coords: [1.0, 1.0],
+    },
+    Vertex {
+        position: [1.0, 1.0, 0.0],
+        tex_coords: [1.0, 0.0],
+    },
+];
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 struct Vertex {
@@ -257,3 +1133,56 @@ impl Vertex {
         }
     }
 }
+
+/// Bytes per row of the upload buffer, 4 bytes per pixel rounded up to wgpu's copy alignment
+fn padded_row_size(width: u32) -> u32 {
+    let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    (4 * width).div_ceil(alignment) * alignment
+}
+
+fn check_video_size(
+    device: &wgpu::Device,
+    video_size: PhysicalSize<u32>,
+) -> Result<(), PlayerError> {
+    if video_size.width == 0 || video_size.height == 0 {
+        return Err(PlayerError::UnsupportedFormat(format!(
+            "the video has no pixels ({}x{})",
+            video_size.width, video_size.height
+        )));
+    }
+    let max_dimension = device.limits().max_texture_dimension_2d;
+    if video_size.width > max_dimension || video_size.height > max_dimension {
+        return Err(PlayerError::Gpu(format!(
+            "the video is {}x{}, but textures can't be larger than {}x{}",
+            video_size.width, video_size.height, max_dimension, max_dimension
+        )));
+    }
+    Ok(())
+}
+
+/// The texture frames are copied into and the buffer they are staged in on the way
+fn create_video_texture(
+    device: &wgpu::Device,
+    video_size: PhysicalSize<u32>,
+    video_format: PixelFormat,
+) -> Result<(Texture, wgpu::Buffer), PlayerError> {
+    let format = match video_format {
+        PixelFormat::Rgba8 => wgpu::TextureFormat::Rgba8UnormSrgb,
+        // there is no srgb variant, the shader decodes it
+        PixelFormat::Rgb10a2 => wgpu::TextureFormat::Rgb10a2Unorm,
+    };
+    let texture = Texture::new(
+        device,
+        (video_size.width, video_size.height),
+        format,
+        Some("Video"),
+    )
+    .map_err(|err| PlayerError::Gpu(err.to_string()))?;
+    let upload_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Video Upload Buffer"),
+        size: (padded_row_size(video_size.width) * video_size.height) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    Ok((texture, upload_buffer))
+}
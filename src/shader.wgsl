@@ -8,13 +8,26 @@ struct VertexOutput {
     @location(0) tex_coords: vec2<f32>,
 }
 
+// fits the quad, which covers the whole surface, to the video
+struct Transform {
+    scale: vec2<f32>,
+    offset: vec2<f32>,
+    // yaw and pitch in radians, tan of half the vertical field of view and the aspect ratio
+    // of the surface, only used by fs_sphere
+    view: vec4<f32>,
+}
+
+@group(2) @binding(0)
+var<uniform> transform: Transform;
+
 @vertex
 fn vs_main(
     model: VertexInput,
 ) -> VertexOutput {
     var out: VertexOutput;
     out.tex_coords = model.tex_coords;
-    out.clip_position = vec4<f32>(model.position, 1.0);
+    let position = model.position.xy * transform.scale + transform.offset;
+    out.clip_position = vec4<f32>(position, model.position.z, 1.0);
     return out;
 }
  
@@ -25,8 +38,362 @@ var t_diffuse: texture_2d<f32>;
 @group(0)@binding(1)
 var s_diffuse: sampler;
 
+struct Draw {
+    // has to match ScalingMode in renderer.rs
+    scaling_mode: u32,
+    // has to match Dither in config.rs
+    dither: u32,
+    frame: u32,
+    // 10 bit textures hold the encoded values, they aren't decoded by the sampler
+    encoded_texture: u32,
+    // the surface isn't srgb, so the output has to be encoded here
+    encode_srgb: u32,
+    // has to match Transfer in frame_queue.rs
+    transfer: u32,
+    // the surface is scRGB, linear with 1.0 at 80 nits
+    hdr_output: u32,
+    // has to match StereoLayout in config.rs
+    stereo_layout: u32,
+    // has to match StereoOutput in config.rs
+    stereo_output: u32,
+}
+
+@group(0) @binding(2)
+var<uniform> draw: Draw;
+
+struct Lut {
+    domain_min: vec4<f32>,
+    domain_max: vec4<f32>,
+    // 0 when no LUT is applied
+    size: f32,
+}
+
+@group(1) @binding(0)
+var lut_texture: texture_3d<f32>;
+@group(1) @binding(1)
+var lut_sampler: sampler;
+@group(1) @binding(2)
+var<uniform> lut: Lut;
+
+fn load_texel(coords: vec2<i32>) -> vec4<f32> {
+    let size = vec2<i32>(textureDimensions(t_diffuse));
+    return textureLoad(t_diffuse, clamp(coords, vec2<i32>(0), size - 1), 0);
+}
+
+fn sample_nearest(tex_coords: vec2<f32>) -> vec4<f32> {
+    let size = vec2<f32>(textureDimensions(t_diffuse));
+    return load_texel(vec2<i32>(floor(tex_coords * size)));
+}
+
+// Catmull-Rom, a = -0.5
+fn cubic_weight(x: f32) -> f32 {
+    let ax = abs(x);
+    if ax < 1.0 {
+        return 1.5 * ax * ax * ax - 2.5 * ax * ax + 1.0;
+    }
+    if ax < 2.0 {
+        return -0.5 * ax * ax * ax + 2.5 * ax * ax - 4.0 * ax + 2.0;
+    }
+    return 0.0;
+}
+
+fn lanczos3_weight(x: f32) -> f32 {
+    if abs(x) < 0.0001 {
+        return 1.0;
+    }
+    if abs(x) >= 3.0 {
+        return 0.0;
+    }
+    let px = 3.14159265359 * x;
+    return 3.0 * sin(px) * sin(px / 3.0) / (px * px);
+}
+
+fn sample_bicubic(tex_coords: vec2<f32>) -> vec4<f32> {
+    let size = vec2<f32>(textureDimensions(t_diffuse));
+    let position = tex_coords * size - 0.5;
+    let base = floor(position);
+    let fraction = position - base;
+
+    var color = vec4<f32>(0.0);
+    var total = 0.0;
+    for (var y = -1; y <= 2; y += 1) {
+        let wy = cubic_weight(f32(y) - fraction.y);
+        for (var x = -1; x <= 2; x += 1) {
+            let weight = cubic_weight(f32(x) - fraction.x) * wy;
+            color += load_texel(vec2<i32>(base) + vec2<i32>(x, y)) * weight;
+            total += weight;
+        }
+    }
+    return clamp(color / total, vec4<f32>(0.0), vec4<f32>(1.0));
+}
+
+fn sample_lanczos3(tex_coords: vec2<f32>) -> vec4<f32> {
+    let size = vec2<f32>(textureDimensions(t_diffuse));
+    let position = tex_coords * size - 0.5;
+    let base = floor(position);
+    let fraction = position - base;
+
+    var color = vec4<f32>(0.0);
+    var total = 0.0;
+    for (var y = -2; y <= 3; y += 1) {
+        let wy = lanczos3_weight(f32(y) - fraction.y);
+        for (var x = -2; x <= 3; x += 1) {
+            let weight = lanczos3_weight(f32(x) - fraction.x) * wy;
+            color += load_texel(vec2<i32>(base) + vec2<i32>(x, y)) * weight;
+            total += weight;
+        }
+    }
+    return clamp(color / total, vec4<f32>(0.0), vec4<f32>(1.0));
+}
+
+fn srgb_encode(linear: vec3<f32>) -> vec3<f32> {
+    let low = linear * 12.92;
+    let high = 1.055 * pow(linear, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, linear <= vec3<f32>(0.0031308));
+}
+
+fn srgb_decode(encoded: vec3<f32>) -> vec3<f32> {
+    let low = encoded / 12.92;
+    let high = pow((encoded + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, encoded <= vec3<f32>(0.04045));
+}
+
+// .cube LUTs are made for the encoded values, the texture sample gives linear ones
+fn apply_lut(color: vec4<f32>) -> vec4<f32> {
+    if lut.size == 0.0 {
+        return color;
+    }
+    let encoded = srgb_encode(clamp(color.rgb, vec3<f32>(0.0), vec3<f32>(1.0)));
+    let normalized = clamp(
+        (encoded - lut.domain_min.xyz) / (lut.domain_max.xyz - lut.domain_min.xyz),
+        vec3<f32>(0.0),
+        vec3<f32>(1.0)
+    );
+    // 0 and 1 are the centers of the outer texels
+    let coords = (normalized * (lut.size - 1.0) + 0.5) / lut.size;
+    let graded = textureSampleLevel(lut_texture, lut_sampler, coords, 0.0).rgb;
+    return vec4<f32>(srgb_decode(graded), color.a);
+}
+
+// 8x8 Bayer matrix, the bits of x ^ y and y interleaved and reversed
+fn bayer(position: vec2<f32>) -> f32 {
+    let y = u32(position.y) & 7u;
+    let a = (u32(position.x) & 7u) ^ y;
+    let value = ((y & 1u) << 5u) | ((a & 1u) << 4u) | ((y & 2u) << 2u) | ((a & 2u) << 1u)
+        | ((y & 4u) >> 1u) | ((a & 4u) >> 2u);
+    return (f32(value) + 0.5) / 64.0;
+}
+
+// interleaved gradient noise, shifted every frame
+fn temporal_noise(position: vec2<f32>) -> f32 {
+    let shifted = position + 5.588238 * f32(draw.frame % 64u);
+    return fract(52.9829189 * fract(dot(shifted, vec2<f32>(0.06711056, 0.00583715))));
+}
+
+// The surface stores 8 bit srgb encoded values. With dithering the color is rounded to those
+// levels here, up or down depending on the threshold, so the fraction that would be lost turns
+// into a fine pattern instead of bands.
+fn output(color: vec4<f32>, position: vec2<f32>) -> vec4<f32> {
+    if draw.dither == 0u && draw.encode_srgb == 0u {
+        return color;
+    }
+    var encoded = srgb_encode(clamp(color.rgb, vec3<f32>(0.0), vec3<f32>(1.0)));
+    var threshold = -1.0;
+    switch draw.dither {
+        case 1u: {
+            threshold = bayer(position);
+        }
+        case 2u: {
+            threshold = temporal_noise(position);
+        }
+        default: {}
+    }
+    if threshold >= 0.0 {
+        encoded = min(floor(encoded * 255.0 + threshold), vec3<f32>(255.0)) / 255.0;
+    }
+    if draw.encode_srgb == 1u {
+        return vec4<f32>(encoded, color.a);
+    }
+    return vec4<f32>(srgb_decode(encoded), color.a);
+}
+
+// BT.2408 reference white, SDR white sits here in HDR
+const REFERENCE_WHITE_NITS: f32 = 203.0;
+// HDR video rarely goes brighter, the SDR tone mapping keeps detail up to here
+const MASTERING_PEAK_NITS: f32 = 1000.0;
+const SCRGB_NITS: f32 = 80.0;
+
+// SMPTE ST 2084, to nits
+fn pq_decode(encoded: vec3<f32>) -> vec3<f32> {
+    let m1 = 0.1593017578125;
+    let m2 = 78.84375;
+    let c1 = 0.8359375;
+    let c2 = 18.8515625;
+    let c3 = 18.6875;
+    let p = pow(clamp(encoded, vec3<f32>(0.0), vec3<f32>(1.0)), vec3<f32>(1.0 / m2));
+    let linear = pow(max(p - c1, vec3<f32>(0.0)) / (c2 - c3 * p), vec3<f32>(1.0 / m1));
+    return linear * 10000.0;
+}
+
+fn bt2020_luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2627, 0.678, 0.0593));
+}
+
+// ARIB STD-B67 with the OOTF of a 1000 nits display, to nits
+fn hlg_decode(encoded: vec3<f32>) -> vec3<f32> {
+    let a = 0.17883277;
+    let b = 0.28466892;
+    let c = 0.55991073;
+    let e = clamp(encoded, vec3<f32>(0.0), vec3<f32>(1.0));
+    let low = e * e / 3.0;
+    let high = (exp((e - c) / a) + b) / 12.0;
+    let scene = select(high, low, e <= vec3<f32>(0.5));
+    return MASTERING_PEAK_NITS * pow(max(bt2020_luminance(scene), 1e-6), 0.2) * scene;
+}
+
+fn bt2020_to_bt709(color: vec3<f32>) -> vec3<f32> {
+    return vec3<f32>(
+        dot(color, vec3<f32>(1.6605, -0.5876, -0.0728)),
+        dot(color, vec3<f32>(-0.1246, 1.1329, -0.0083)),
+        dot(color, vec3<f32>(-0.0182, -0.1006, 1.1187))
+    );
+}
+
+// extended Reinhard on the luminance, 1.0 is reference white and the mastering peak maps to 1.0
+fn tone_map(color: vec3<f32>) -> vec3<f32> {
+    let white = MASTERING_PEAK_NITS / REFERENCE_WHITE_NITS;
+    let luminance = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+    if luminance <= 0.0 {
+        return vec3<f32>(0.0);
+    }
+    let mapped = luminance * (1.0 + luminance / (white * white)) / (1.0 + luminance);
+    return clamp(color * (mapped / luminance), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// HDR video in BT.709 nits, the LUT and the scaling are already applied
+fn output_hdr(nits: vec3<f32>, alpha: f32, position: vec2<f32>) -> vec4<f32> {
+    if draw.hdr_output == 1u {
+        // negative values are the colors outside of BT.709, scRGB keeps them
+        return vec4<f32>(nits / SCRGB_NITS, alpha);
+    }
+    return output(vec4<f32>(tone_map(nits / REFERENCE_WHITE_NITS), alpha), position);
+}
+
+fn sample_video(tex_coords: vec2<f32>) -> vec4<f32> {
+    let bilinear = textureSample(t_diffuse, s_diffuse, tex_coords);
+
+    var color: vec4<f32>;
+    switch draw.scaling_mode {
+        // nearest and integer scale
+        case 0u, 1u: {
+            color = sample_nearest(tex_coords);
+        }
+        // bicubic
+        case 3u: {
+            color = sample_bicubic(tex_coords);
+        }
+        // lanczos3
+        case 4u: {
+            color = sample_lanczos3(tex_coords);
+        }
+        default: {
+            color = bilinear;
+        }
+    }
+    return color;
+}
+
+// maps `tex_coords` into the half of a 3D frame that holds `eye`, 0 is the left one
+fn eye_coords(tex_coords: vec2<f32>, eye: u32) -> vec2<f32> {
+    // side by side
+    if draw.stereo_layout == 1u {
+        return vec2<f32>((tex_coords.x + f32(eye)) * 0.5, tex_coords.y);
+    }
+    // top and bottom
+    if draw.stereo_layout == 2u {
+        return vec2<f32>(tex_coords.x, (tex_coords.y + f32(eye)) * 0.5);
+    }
+    return tex_coords;
+}
+
+// one eye of 3D video, or the red of the left eye and the green and blue of the right one
+fn sample_stereo(tex_coords: vec2<f32>) -> vec4<f32> {
+    if draw.stereo_layout == 0u {
+        return sample_video(tex_coords);
+    }
+    if draw.stereo_output == 2u {
+        let left = sample_video(eye_coords(tex_coords, 0u));
+        let right = sample_video(eye_coords(tex_coords, 1u));
+        return vec4<f32>(left.r, right.g, right.b, left.a);
+    }
+    return sample_video(eye_coords(tex_coords, draw.stereo_output));
+}
+
+// samples the video at `tex_coords` and converts it for the surface
+fn shade(tex_coords: vec2<f32>, position: vec2<f32>) -> vec4<f32> {
+    var color = sample_stereo(tex_coords);
+    if draw.transfer != 0u {
+        // the output of the passes is decoded as srgb by the sampler, undo that first
+        var encoded = color.rgb;
+        if draw.encoded_texture == 0u {
+            encoded = srgb_encode(color.rgb);
+        }
+        var nits: vec3<f32>;
+        if draw.transfer == 1u {
+            nits = pq_decode(encoded);
+        } else {
+            nits = hlg_decode(encoded);
+        }
+        // LUTs are made for SDR, so they are skipped here
+        return output_hdr(bt2020_to_bt709(nits), color.a, position);
+    }
+
+    if draw.encoded_texture == 1u {
+        color = vec4<f32>(srgb_decode(color.rgb), color.a);
+    }
+    color = apply_lut(color);
+    if draw.hdr_output == 1u {
+        // SDR white at 80 nits, the same as the ui drawn on top
+        return color;
+    }
+    return output(color, position);
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    return shade(in.tex_coords, in.clip_position.xy);
+}
+
+// 360° video, the frame is an equirectangular projection of the sphere around the camera.
+// The quad covers the surface and every pixel looks at the point of the sphere in its direction.
+@fragment
+fn fs_sphere(in: VertexOutput) -> @location(0) vec4<f32> {
+    let yaw = transform.view.x;
+    let pitch = transform.view.y;
+    let tan_half_fov = transform.view.z;
+    let aspect = transform.view.w;
+
+    let screen = vec2<f32>(in.tex_coords.x * 2.0 - 1.0, 1.0 - in.tex_coords.y * 2.0);
+    var ray = normalize(vec3<f32>(screen.x * tan_half_fov * aspect, screen.y * tan_half_fov, -1.0));
+    // looking up, around the x axis
+    ray = vec3<f32>(
+        ray.x,
+        ray.y * cos(pitch) - ray.z * sin(pitch),
+        ray.y * sin(pitch) + ray.z * cos(pitch)
+    );
+    // looking right, around the y axis
+    ray = vec3<f32>(
+        ray.x * cos(yaw) - ray.z * sin(yaw),
+        ray.y,
+        ray.x * sin(yaw) + ray.z * cos(yaw)
+    );
+
+    // the center of the frame is straight ahead, the top is straight up
+    let longitude = atan2(ray.x, -ray.z);
+    let latitude = asin(clamp(ray.y, -1.0, 1.0));
+    let tex_coords = vec2<f32>(
+        longitude / (2.0 * 3.14159265359) + 0.5,
+        0.5 - latitude / 3.14159265359
+    );
+    return shade(tex_coords, in.clip_position.xy);
 }
- 
\ No newline at end of file
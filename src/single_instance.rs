@@ -0,0 +1,173 @@
+use std::{io::Write, time::Duration};
+
+use anyhow::Error;
+
+use crate::custom_pipeline;
+
+/// Later instances send their files right away, this only keeps one that hangs from blocking
+/// the others
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+/// Far more than the files of a command line, anything longer isn't from another instance
+const MAX_MESSAGE_LEN: u64 = 1024 * 1024;
+
+/// In a folder only the user can enter, so other users can't pass on files or take the socket
+/// over. The runtime dir is made like that, the fallback in the cache dir is restricted here.
+#[cfg(unix)]
+fn socket_path() -> Result<std::path::PathBuf, Error> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    if let Some(dir) = dirs::runtime_dir() {
+        return Ok(dir.join("wgpu-media-player.sock"));
+    }
+    let dir = dirs::cache_dir()
+        .ok_or_else(|| anyhow::anyhow!("there is no folder for the socket"))?
+        .join("wgpu-media-player");
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(&dir)?;
+    // the mode only applies when the folder is new
+    std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700))?;
+    Ok(dir.join("instance.sock"))
+}
+
+#[cfg(windows)]
+const PIPE_NAME: &str = r"\\.\pipe\wgpu-media-player";
+
+/// Files a later instance was started with, passed on to the running one
+#[derive(Debug, Clone)]
+pub struct HandOff {
+    pub uris: Vec<String>,
+    pub enqueue: bool,
+}
+
+impl HandOff {
+    /// `play` or `enqueue` on the first line, then one uri per line
+    fn encode(&self) -> String {
+        let mut text = String::from(if self.enqueue { "enqueue\n" } else { "play\n" });
+        for uri in &self.uris {
+            text.push_str(uri);
+            text.push('\n');
+        }
+        text
+    }
+
+    fn decode(text: &str) -> Option<Self> {
+        let mut lines = text.lines();
+        let enqueue = match lines.next()? {
+            "play" => false,
+            "enqueue" => true,
+            _ => return None,
+        };
+        Some(Self {
+            uris: lines
+                .filter(|line| !line.is_empty() && !custom_pipeline::is_pipeline_uri(line))
+                .map(str::to_string)
+                .collect(),
+            enqueue,
+        })
+    }
+}
+
+/// Passes the files to the instance that is already running, returns false when there is none
+pub fn hand_off(hand_off: &HandOff) -> bool {
+    #[cfg(unix)]
+    let connection = match socket_path() {
+        Ok(path) => std::os::unix::net::UnixStream::connect(path),
+        Err(_) => return false,
+    };
+    #[cfg(windows)]
+    let connection = std::fs::OpenOptions::new().write(true).open(PIPE_NAME);
+
+    match connection {
+        Ok(mut connection) => connection.write_all(hand_off.encode().as_bytes()).is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Makes this the running instance, `on_hand_off` is called from a background thread for the
+/// files of every later instance
+#[cfg(unix)]
+pub fn listen(on_hand_off: impl Fn(HandOff) + Send + 'static) -> Result<(), Error> {
+    use std::{
+        io::Read,
+        os::unix::net::{UnixListener, UnixStream},
+    };
+
+    let path = socket_path()?;
+    let listener = match UnixListener::bind(&path) {
+        Ok(listener) => listener,
+        Err(err) if err.kind() == std::io::ErrorKind::AddrInUse => {
+            if UnixStream::connect(&path).is_ok() {
+                return Err(err.into());
+            }
+            // left behind by an instance that crashed
+            std::fs::remove_file(&path)?;
+            UnixListener::bind(&path)?
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    std::thread::spawn(move || {
+        for connection in listener.incoming().flatten() {
+            if connection.set_read_timeout(Some(READ_TIMEOUT)).is_err() {
+                continue;
+            }
+            let mut text = String::new();
+            let read = connection
+                .take(MAX_MESSAGE_LEN + 1)
+                .read_to_string(&mut text);
+            if read.is_ok() && text.len() as u64 <= MAX_MESSAGE_LEN {
+                if let Some(hand_off) = HandOff::decode(&text) {
+                    on_hand_off(hand_off);
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(windows)]
+pub fn listen(on_hand_off: impl Fn(HandOff) + Send + 'static) -> Result<(), Error> {
+    use tokio::{io::AsyncReadExt, net::windows::named_pipe::ServerOptions};
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_io()
+        .enable_time()
+        .build()?;
+    let mut server = {
+        let _guard = runtime.enter();
+        // fails when another instance owns the pipe
+        ServerOptions::new()
+            .first_pipe_instance(true)
+            .create(PIPE_NAME)?
+    };
+
+    std::thread::spawn(move || {
+        runtime.block_on(async move {
+            loop {
+                if server.connect().await.is_err() {
+                    return;
+                }
+                let connection = match ServerOptions::new().create(PIPE_NAME) {
+                    Ok(next_server) => std::mem::replace(&mut server, next_server),
+                    Err(_) => return,
+                };
+                let mut text = String::new();
+                let read = tokio::time::timeout(
+                    READ_TIMEOUT,
+                    connection
+                        .take(MAX_MESSAGE_LEN + 1)
+                        .read_to_string(&mut text),
+                )
+                .await;
+                if matches!(read, Ok(Ok(_))) && text.len() as u64 <= MAX_MESSAGE_LEN {
+                    if let Some(hand_off) = HandOff::decode(&text) {
+                        on_hand_off(hand_off);
+                    }
+                }
+            }
+        });
+    });
+    Ok(())
+}
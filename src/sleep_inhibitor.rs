@@ -0,0 +1,166 @@
+/// Keeps the display from blanking and the system from sleeping while video plays
+#[derive(Default)]
+pub struct SleepInhibitor {
+    /// Released when dropped
+    inhibition: Option<platform::Inhibition>,
+    /// Set once inhibiting failed, so it isn't retried and logged for every frame. Cleared when
+    /// playback stops, the next one tries again.
+    failed: bool,
+}
+
+impl SleepInhibitor {
+    pub fn set_inhibited(&mut self, inhibited: bool) {
+        if !inhibited {
+            self.failed = false;
+            if self.inhibition.take().is_some() {
+                tracing::debug!("Allowing the display to sleep");
+            }
+            return;
+        }
+        if self.inhibition.is_some() || self.failed {
+            return;
+        }
+
+        match platform::Inhibition::new() {
+            Ok(inhibition) => {
+                tracing::debug!("Keeping the display awake");
+                self.inhibition = Some(inhibition);
+            }
+            Err(err) => {
+                tracing::warn!("Failed to keep the display awake: {:#}", err);
+                self.failed = true;
+            }
+        }
+    }
+}
+
+/// org.freedesktop.ScreenSaver, implemented by the desktop environment. The inhibition also ends
+/// when the connection closes, e.g. after a crash.
+#[cfg(all(unix, not(target_os = "macos")))]
+mod platform {
+    use anyhow::{Context, Error};
+    use zbus::blocking::Connection;
+
+    const SCREEN_SAVER: &str = "org.freedesktop.ScreenSaver";
+    const SCREEN_SAVER_PATH: &str = "/org/freedesktop/ScreenSaver";
+
+    pub struct Inhibition {
+        connection: Connection,
+        cookie: u32,
+    }
+
+    impl Inhibition {
+        pub fn new() -> Result<Self, Error> {
+            let connection =
+                Connection::session().context("Failed to connect to the session bus")?;
+            let reply = connection
+                .call_method(
+                    Some(SCREEN_SAVER),
+                    SCREEN_SAVER_PATH,
+                    Some(SCREEN_SAVER),
+                    "Inhibit",
+                    &("wgpu-media-player", "Playing video"),
+                )
+                .context("org.freedesktop.ScreenSaver.Inhibit failed")?;
+            let cookie = reply.body()?;
+            Ok(Self { connection, cookie })
+        }
+    }
+
+    impl Drop for Inhibition {
+        fn drop(&mut self) {
+            if let Err(err) = self.connection.call_method(
+                Some(SCREEN_SAVER),
+                SCREEN_SAVER_PATH,
+                Some(SCREEN_SAVER),
+                "UnInhibit",
+                &(self.cookie,),
+            ) {
+                tracing::warn!("org.freedesktop.ScreenSaver.UnInhibit failed: {}", err);
+            }
+        }
+    }
+}
+
+/// SetThreadExecutionState, which applies to the calling thread. That is the event loop, which
+/// runs for as long as the player does.
+#[cfg(windows)]
+mod platform {
+    use anyhow::{bail, Error};
+    use windows_sys::Win32::System::Power::{
+        SetThreadExecutionState, ES_CONTINUOUS, ES_DISPLAY_REQUIRED, ES_SYSTEM_REQUIRED,
+    };
+
+    pub struct Inhibition;
+
+    impl Inhibition {
+        pub fn new() -> Result<Self, Error> {
+            let previous = unsafe {
+                SetThreadExecutionState(ES_CONTINUOUS | ES_DISPLAY_REQUIRED | ES_SYSTEM_REQUIRED)
+            };
+            if previous == 0 {
+                bail!("SetThreadExecutionState failed");
+            }
+            Ok(Self)
+        }
+    }
+
+    impl Drop for Inhibition {
+        fn drop(&mut self) {
+            unsafe { SetThreadExecutionState(ES_CONTINUOUS) };
+        }
+    }
+}
+
+/// An IOKit power assertion, released by the system when the process exits
+#[cfg(target_os = "macos")]
+mod platform {
+    use anyhow::{bail, Error};
+    use core_foundation::{
+        base::TCFType,
+        string::{CFString, CFStringRef},
+    };
+
+    const ASSERTION_LEVEL_ON: u32 = 255;
+
+    #[link(name = "IOKit", kind = "framework")]
+    extern "C" {
+        fn IOPMAssertionCreateWithName(
+            assertion_type: CFStringRef,
+            level: u32,
+            name: CFStringRef,
+            id: *mut u32,
+        ) -> i32;
+        fn IOPMAssertionRelease(id: u32) -> i32;
+    }
+
+    pub struct Inhibition {
+        id: u32,
+    }
+
+    impl Inhibition {
+        pub fn new() -> Result<Self, Error> {
+            let assertion_type = CFString::from_static_string("PreventUserIdleDisplaySleep");
+            let name = CFString::from_static_string("Playing video");
+            let mut id = 0;
+            let result = unsafe {
+                IOPMAssertionCreateWithName(
+                    assertion_type.as_concrete_TypeRef(),
+                    ASSERTION_LEVEL_ON,
+                    name.as_concrete_TypeRef(),
+                    &mut id,
+                )
+            };
+            if result != 0 {
+                bail!("IOPMAssertionCreateWithName failed with {:#x}", result);
+            }
+            Ok(Self { id })
+        }
+    }
+
+    impl Drop for Inhibition {
+        fn drop(&mut self) {
+            unsafe { IOPMAssertionRelease(self.id) };
+        }
+    }
+}
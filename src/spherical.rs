@@ -0,0 +1,192 @@
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use crate::config::{Projection, StereoLayout};
+
+/// The moov box of a long video holds the index of every sample, more isn't searched
+const MAX_SEARCHED_BYTES: u64 = 32 * 1024 * 1024;
+/// Matroska puts the track headers at the start of the file
+const MAX_MATROSKA_HEADER_BYTES: u64 = 1024 * 1024;
+const MATROSKA_MAGIC: [u8; 4] = [0x1A, 0x45, 0xDF, 0xA3];
+/// The uuid box of version 1 of the spherical video metadata, followed by XML
+const SPHERICAL_V1_UUID: [u8; 16] = [
+    0xFF, 0xCC, 0x82, 0x63, 0xF8, 0x55, 0x4A, 0x93, 0x88, 0x14, 0x58, 0x7A, 0x02, 0x52, 0x1F, 0xDD,
+];
+
+/// How the frames of a file are laid out, as its metadata declares
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct VideoLayout {
+    pub projection: Projection,
+    pub stereo: StereoLayout,
+}
+
+/// The layout the spherical video metadata or the Matroska StereoMode of the file at `path`
+/// declares. Layouts that can't be shown are left flat and mono.
+pub fn detect(path: &Path) -> VideoLayout {
+    match read_layout(path) {
+        Ok(layout) => layout,
+        Err(err) => {
+            tracing::debug!("Failed to read the layout of {}: {}", path.display(), err);
+            VideoLayout::default()
+        }
+    }
+}
+
+fn read_layout(path: &Path) -> io::Result<VideoLayout> {
+    let mut file = File::open(path)?;
+    let mut header = [0; 8];
+    file.read_exact(&mut header)?;
+    file.seek(SeekFrom::Start(0))?;
+
+    if header[..4] == MATROSKA_MAGIC {
+        let mut head = Vec::new();
+        file.take(MAX_MATROSKA_HEADER_BYTES)
+            .read_to_end(&mut head)?;
+        return Ok(VideoLayout {
+            projection: matroska_projection(&head),
+            stereo: matroska_stereo(&head),
+        });
+    }
+    if !matches!(
+        &header[4..],
+        b"ftyp" | b"moov" | b"free" | b"wide" | b"mdat"
+    ) {
+        return Ok(VideoLayout::default());
+    }
+    Ok(match find_mp4_box(&mut file, b"moov")? {
+        Some(moov) => VideoLayout {
+            projection: mp4_projection(&moov),
+            stereo: mp4_stereo(&moov),
+        },
+        None => VideoLayout::default(),
+    })
+}
+
+/// Reads the content of the top level box `name`
+fn find_mp4_box(file: &mut File, name: &[u8; 4]) -> io::Result<Option<Vec<u8>>> {
+    let file_size = file.metadata()?.len();
+    let mut position = 0;
+    while position + 8 <= file_size {
+        file.seek(SeekFrom::Start(position))?;
+        let mut header = [0; 8];
+        file.read_exact(&mut header)?;
+        let mut header_size = 8;
+        let size = match u32::from_be_bytes(header[..4].try_into().unwrap()) {
+            // the box continues to the end of the file
+            0 => file_size - position,
+            // the size follows as 64 bits
+            1 => {
+                let mut large_size = [0; 8];
+                file.read_exact(&mut large_size)?;
+                header_size = 16;
+                u64::from_be_bytes(large_size)
+            }
+            size => size as u64,
+        };
+        if size < header_size {
+            return Ok(None);
+        }
+        if &header[4..] == name {
+            let mut content = Vec::new();
+            file.take((size - header_size).min(MAX_SEARCHED_BYTES))
+                .read_to_end(&mut content)?;
+            return Ok(Some(content));
+        }
+        position += size;
+    }
+    Ok(None)
+}
+
+/// Version 2 puts a proj box in an sv3d box of the video sample entry, its first child names
+/// the projection. Version 1 is XML in a uuid box of the track, it only knows equirectangular.
+fn mp4_projection(moov: &[u8]) -> Projection {
+    if let Some(sv3d) = find(moov, b"sv3d") {
+        let boxes = &moov[sv3d..];
+        let projection = [b"equi", b"cbmp", b"mshp"]
+            .into_iter()
+            .filter_map(|name| find(boxes, name).map(|index| (index, name)))
+            .min();
+        return match projection {
+            Some((_, b"equi")) => Projection::Equirectangular,
+            Some((_, name)) => {
+                tracing::info!(
+                    "The {} projection isn't supported",
+                    String::from_utf8_lossy(name)
+                );
+                Projection::Flat
+            }
+            None => Projection::Flat,
+        };
+    }
+    if let Some(uuid) = find(moov, &SPHERICAL_V1_UUID) {
+        let xml = String::from_utf8_lossy(&moov[uuid..moov.len().min(uuid + 4096)]);
+        if xml.contains("<GSpherical:Spherical>true") {
+            return Projection::Equirectangular;
+        }
+    }
+    Projection::Flat
+}
+
+/// The st3d box of version 2, a full box with the mode in its first byte, or the StereoMode of
+/// the XML of version 1
+fn mp4_stereo(moov: &[u8]) -> StereoLayout {
+    if let Some(st3d) = find(moov, b"st3d") {
+        return match moov.get(st3d + 8).copied() {
+            Some(1) => StereoLayout::TopBottom,
+            Some(2) => StereoLayout::SideBySide,
+            _ => StereoLayout::Mono,
+        };
+    }
+    if let Some(uuid) = find(moov, &SPHERICAL_V1_UUID) {
+        let xml = String::from_utf8_lossy(&moov[uuid..moov.len().min(uuid + 4096)]);
+        if xml.contains("<GSpherical:StereoMode>top-bottom") {
+            return StereoLayout::TopBottom;
+        }
+        if xml.contains("<GSpherical:StereoMode>left-right") {
+            return StereoLayout::SideBySide;
+        }
+    }
+    StereoLayout::Mono
+}
+
+/// StereoMode of the video track. The modes with the right eye first are shown with the eyes
+/// swapped, the anaglyph and interleaved ones as they are.
+fn matroska_stereo(head: &[u8]) -> StereoLayout {
+    match find(head, &[0x53, 0xB8, 0x81]).and_then(|index| head.get(index + 3).copied()) {
+        Some(1 | 11) => StereoLayout::SideBySide,
+        Some(2 | 3) => StereoLayout::TopBottom,
+        Some(0) | None => StereoLayout::Mono,
+        Some(stereo_mode) => {
+            tracing::info!("The Matroska stereo mode {} isn't supported", stereo_mode);
+            StereoLayout::Mono
+        }
+    }
+}
+
+/// ProjectionType in the Projection element of the video track, 1 is equirectangular
+fn matroska_projection(head: &[u8]) -> Projection {
+    // the id, a size of one byte and the value
+    match find(head, &[0x76, 0x71, 0x81])
+        .and_then(|index| head.get(index + 3))
+        .copied()
+    {
+        Some(1) => Projection::Equirectangular,
+        Some(0) | None => Projection::Flat,
+        Some(projection_type) => {
+            tracing::info!(
+                "The Matroska projection type {} isn't supported",
+                projection_type
+            );
+            Projection::Flat
+        }
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
@@ -0,0 +1,163 @@
+use std::time::{Duration, Instant};
+
+/// Snapshot of the decoder side, sent a couple of times per second
+#[derive(Debug, Default, Clone)]
+pub struct DecoderStats {
+    pub decoded_frames: u64,
+    /// Timestamp of the last decoded video frame minus the playback position
+    pub av_offset_ms: Option<f64>,
+    pub audio_buffer_ms: f64,
+    pub video_decoder: Option<String>,
+    pub audio_decoder: Option<String>,
+    pub video_format: Option<String>,
+    pub bitrate: Option<u32>,
+    /// How much faster the audio device runs than the pipeline clock
+    pub clock_drift_ppm: Option<f64>,
+    /// Frames the frame queue dropped because the renderer didn't keep up
+    pub queue_dropped_frames: u64,
+}
+
+const FPS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Collects playback statistics and draws them as an on screen panel
+pub struct StatsOverlay {
+    open: bool,
+    decoder: DecoderStats,
+    decoded_fps: f64,
+    last_decoder_sample: Option<(Instant, u64)>,
+    rendered_fps: f64,
+    dropped_frames: u64,
+    frame_pending: bool,
+    interval_start: Instant,
+    interval_rendered_frames: u64,
+}
+
+impl Default for StatsOverlay {
+    fn default() -> Self {
+        Self {
+            open: false,
+            decoder: DecoderStats::default(),
+            decoded_fps: 0.0,
+            last_decoder_sample: None,
+            rendered_fps: 0.0,
+            dropped_frames: 0,
+            frame_pending: false,
+            interval_start: Instant::now(),
+            interval_rendered_frames: 0,
+        }
+    }
+}
+
+impl StatsOverlay {
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+    }
+
+    pub fn set_decoder_stats(&mut self, stats: DecoderStats) {
+        let now = Instant::now();
+        if let Some((last_time, last_frames)) = self.last_decoder_sample {
+            let elapsed = now.duration_since(last_time).as_secs_f64();
+            if elapsed > 0.0 && stats.decoded_frames >= last_frames {
+                self.decoded_fps = (stats.decoded_frames - last_frames) as f64 / elapsed;
+            }
+        }
+        self.last_decoder_sample = Some((now, stats.decoded_frames));
+        self.decoder = stats;
+    }
+
+    /// A new frame arrived, when the previous one never made it to the screen it counts as dropped
+    pub fn frame_received(&mut self) {
+        if self.frame_pending {
+            self.dropped_frames += 1;
+        }
+        self.frame_pending = true;
+    }
+
+    pub fn frame_rendered(&mut self) {
+        if !self.frame_pending {
+            return;
+        }
+
+        self.frame_pending = false;
+        self.interval_rendered_frames += 1;
+
+        let elapsed = self.interval_start.elapsed();
+        if elapsed >= FPS_INTERVAL {
+            self.rendered_fps = self.interval_rendered_frames as f64 / elapsed.as_secs_f64();
+            self.interval_rendered_frames = 0;
+            self.interval_start = Instant::now();
+        }
+    }
+
+    #[cfg(feature = "ui")]
+    pub fn ui(&self, ctx: &egui::Context) {
+        if !self.open {
+            return;
+        }
+
+        fn or_unknown(value: &Option<String>) -> &str {
+            value.as_deref().unwrap_or("unknown")
+        }
+
+        egui::Window::new("Statistics")
+            .anchor(egui::Align2::RIGHT_TOP, [-8.0, 8.0])
+            .resizable(false)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("stats_grid").num_columns(2).show(ui, |ui| {
+                    ui.label("Decoded fps");
+                    ui.monospace(format!("{:.2}", self.decoded_fps));
+                    ui.end_row();
+
+                    ui.label("Rendered fps");
+                    ui.monospace(format!("{:.2}", self.rendered_fps));
+                    ui.end_row();
+
+                    ui.label("Dropped frames");
+                    ui.monospace(self.dropped_frames.to_string());
+                    ui.end_row();
+
+                    ui.label("Dropped in queue");
+                    ui.monospace(self.decoder.queue_dropped_frames.to_string());
+                    ui.end_row();
+
+                    ui.label("A/V offset");
+                    ui.monospace(match self.decoder.av_offset_ms {
+                        Some(offset) => format!("{:+.1} ms", offset),
+                        None => "unknown".to_string(),
+                    });
+                    ui.end_row();
+
+                    ui.label("Audio buffer");
+                    ui.monospace(format!("{:.0} ms", self.decoder.audio_buffer_ms));
+                    ui.end_row();
+
+                    ui.label("Audio clock drift");
+                    ui.monospace(match self.decoder.clock_drift_ppm {
+                        Some(ppm) => format!("{:+.0} ppm", ppm),
+                        None => "measuring".to_string(),
+                    });
+                    ui.end_row();
+
+                    ui.label("Video decoder");
+                    ui.monospace(or_unknown(&self.decoder.video_decoder));
+                    ui.end_row();
+
+                    ui.label("Audio decoder");
+                    ui.monospace(or_unknown(&self.decoder.audio_decoder));
+                    ui.end_row();
+
+                    ui.label("Video format");
+                    ui.monospace(or_unknown(&self.decoder.video_format));
+                    ui.end_row();
+
+                    ui.label("Bitrate");
+                    ui.monospace(match self.decoder.bitrate {
+                        Some(bitrate) => format!("{:.0} kbit/s", bitrate as f64 / 1000.0),
+                        None => "unknown".to_string(),
+                    });
+                    ui.end_row();
+                });
+            });
+    }
+}
@@ -0,0 +1,243 @@
+use std::{num::NonZeroU32, sync::Arc};
+
+use wgpu::util::DeviceExt;
+use winit::dpi::PhysicalSize;
+
+use crate::{frame_queue::SubtitleBitmaps, renderer::HDR_SURFACE_FORMAT};
+
+/// Has to match Params in subtitle_overlay.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct OverlayParams {
+    rect: [f32; 4],
+    output: u32,
+    // uniform buffers need to be 16 byte aligned
+    _padding: [u32; 3],
+}
+
+/// Draws the bitmaps of PGS, DVB and VobSub subtitles over the video, with the transform the
+/// video was drawn with so they land on the same spot of the frame
+pub struct SubtitleOverlay {
+    /// Value of `OverlayParams::output` for the surface format
+    output: u32,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    pipeline: wgpu::RenderPipeline,
+    bitmaps: Option<Arc<SubtitleBitmaps>>,
+    /// One per bitmap, each with its texture and params
+    bind_groups: Vec<wgpu::BindGroup>,
+}
+
+impl SubtitleOverlay {
+    pub fn new(
+        device: &wgpu::Device,
+        surface_format: wgpu::TextureFormat,
+        transform_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("subtitle_overlay_bind_group_layout"),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Subtitle Overlay Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout, transform_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Subtitle Overlay Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("subtitle_overlay.wgsl").into()),
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Subtitle Overlay Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let output = if surface_format == HDR_SURFACE_FORMAT {
+            2
+        } else if !surface_format.describe().srgb {
+            1
+        } else {
+            0
+        };
+        Self {
+            output,
+            bind_group_layout,
+            sampler,
+            pipeline,
+            bitmaps: None,
+            bind_groups: Vec::new(),
+        }
+    }
+
+    /// Uploads the bitmaps of the current frame, only when they differ from the previous ones.
+    /// Their positions are relative to a frame of `video_size`.
+    pub fn set_bitmaps(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bitmaps: Option<&Arc<SubtitleBitmaps>>,
+        video_size: PhysicalSize<u32>,
+    ) {
+        let unchanged = match (bitmaps, &self.bitmaps) {
+            (Some(new), Some(current)) => new.seqnum == current.seqnum,
+            (None, None) => true,
+            _ => false,
+        };
+        if unchanged {
+            return;
+        }
+        self.bitmaps = bitmaps.cloned();
+        self.bind_groups.clear();
+
+        let Some(bitmaps) = bitmaps else {
+            return;
+        };
+        let max_dimension = device.limits().max_texture_dimension_2d;
+        for bitmap in &bitmaps.bitmaps {
+            if bitmap.width == 0
+                || bitmap.height == 0
+                || bitmap.width > max_dimension
+                || bitmap.height > max_dimension
+            {
+                continue;
+            }
+            let size = wgpu::Extent3d {
+                width: bitmap.width,
+                height: bitmap.height,
+                depth_or_array_layers: 1,
+            };
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("Subtitle Bitmap"),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            });
+            queue.write_texture(
+                texture.as_image_copy(),
+                &bitmap.data,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(4 * bitmap.width),
+                    rows_per_image: NonZeroU32::new(bitmap.height),
+                },
+                size,
+            );
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+            let video_width = video_size.width.max(1) as f32;
+            let video_height = video_size.height.max(1) as f32;
+            let params = OverlayParams {
+                rect: [
+                    bitmap.x as f32 / video_width,
+                    bitmap.y as f32 / video_height,
+                    bitmap.render_width as f32 / video_width,
+                    bitmap.render_height as f32 / video_height,
+                ],
+                output: self.output,
+                _padding: [0; 3],
+            };
+            let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Subtitle Params Buffer"),
+                contents: bytemuck::cast_slice(&[params]),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+            self.bind_groups
+                .push(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &self.bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&view),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&self.sampler),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: params_buffer.as_entire_binding(),
+                        },
+                    ],
+                    label: Some("subtitle_overlay_bind_group"),
+                }));
+        }
+    }
+
+    /// Draws the bitmaps, `transform` and `transform_offset` are the ones the video was drawn
+    /// with
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        transform: &'a wgpu::BindGroup,
+        transform_offset: u32,
+    ) {
+        if self.bind_groups.is_empty() {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(1, transform, &[transform_offset]);
+        for bind_group in &self.bind_groups {
+            render_pass.set_bind_group(0, bind_group, &[]);
+            render_pass.draw(0..4, 0..1);
+        }
+    }
+}
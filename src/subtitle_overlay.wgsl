@@ -0,0 +1,60 @@
+// Draws subtitle bitmaps over the video, see subtitle_overlay.rs
+
+struct Params {
+    // x, y, width and height of the bitmap as fractions of the video
+    rect: vec4<f32>,
+    // 0 writes linear values, 1 encodes them as srgb, 2 scales them to scRGB
+    output: u32,
+}
+
+// the transform the video was drawn with, see Transform in shader.wgsl
+struct Transform {
+    scale: vec2<f32>,
+    offset: vec2<f32>,
+    view: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var bitmap: texture_2d<f32>;
+@group(0) @binding(1)
+var bitmap_sampler: sampler;
+@group(0) @binding(2)
+var<uniform> params: Params;
+@group(1) @binding(0)
+var<uniform> transform: Transform;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// a triangle strip covering the rectangle of the bitmap
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32(index & 1u), f32(index >> 1u));
+    let video_uv = params.rect.xy + uv * params.rect.zw;
+    let position = vec2<f32>(video_uv.x * 2.0 - 1.0, 1.0 - video_uv.y * 2.0);
+    var out: VertexOutput;
+    out.position = vec4<f32>(position * transform.scale + transform.offset, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+fn srgb_encode(linear: vec3<f32>) -> vec3<f32> {
+    let low = linear * 12.92;
+    let high = 1.055 * pow(linear, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, linear <= vec3<f32>(0.0031308));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let sampled = textureSample(bitmap, bitmap_sampler, in.uv);
+    var color = sampled.rgb;
+    if params.output == 1u {
+        color = srgb_encode(color);
+    } else if params.output == 2u {
+        // subtitles are as bright as SDR white, 203 nits
+        color = color * 203.0 / 80.0;
+    }
+    return vec4<f32>(color, sampled.a);
+}
@@ -0,0 +1,370 @@
+#[cfg(feature = "ui")]
+use std::time::Duration;
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+#[cfg(feature = "ui")]
+use anyhow::anyhow;
+use anyhow::Error;
+use chardetng::EncodingDetector;
+use encoding_rs::Encoding;
+
+const SUBTITLE_EXTENSIONS: &[&str] = &["srt", "ass", "ssa", "sub", "vtt", "txt"];
+/// Enough of a .sub file to tell text subtitles from VobSub
+const SUB_SNIFF_LENGTH: u64 = 4096;
+
+/// Numbers the copies in the temp folder apart, see `load`
+static CONVERTED_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// Encodings offered in the manual override, covers the usual suspects of legacy subtitle files
+#[cfg(feature = "ui")]
+pub const ENCODINGS: &[&Encoding] = &[
+    encoding_rs::UTF_8,
+    encoding_rs::WINDOWS_1250,
+    encoding_rs::WINDOWS_1251,
+    encoding_rs::WINDOWS_1252,
+    encoding_rs::WINDOWS_1253,
+    encoding_rs::WINDOWS_1254,
+    encoding_rs::WINDOWS_1255,
+    encoding_rs::WINDOWS_1256,
+    encoding_rs::WINDOWS_1257,
+    encoding_rs::ISO_8859_2,
+    encoding_rs::KOI8_R,
+    encoding_rs::GBK,
+    encoding_rs::GB18030,
+    encoding_rs::BIG5,
+    encoding_rs::SHIFT_JIS,
+    encoding_rs::EUC_JP,
+    encoding_rs::EUC_KR,
+];
+
+pub struct SubtitleFile {
+    pub path: PathBuf,
+    /// The encoding the file was decoded with
+    pub encoding: &'static Encoding,
+    /// Whether `encoding` was picked by the user instead of detected
+    pub encoding_overridden: bool,
+    /// UTF-8 copy of the file that gets handed to playbin
+    pub converted_path: PathBuf,
+}
+
+/// A subtitle file the player draws itself, above the one playbin renders
+#[cfg(feature = "ui")]
+pub struct SecondSubtitle {
+    pub file: SubtitleFile,
+    /// Sorted by start, drawn by the ui
+    pub cues: Vec<Cue>,
+}
+
+/// A line of a subtitle file and when it is shown
+#[cfg(feature = "ui")]
+#[derive(Debug, Clone)]
+pub struct Cue {
+    pub start: Duration,
+    pub end: Duration,
+    pub text: String,
+}
+
+pub fn is_subtitle_file(path: &Path) -> bool {
+    let Some(extension) = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_lowercase)
+    else {
+        return false;
+    };
+    // .sub is also the binary half of VobSub, which has to be played along with its .idx
+    if extension == "sub" {
+        return is_text_sub(path);
+    }
+    SUBTITLE_EXTENSIONS.contains(&extension.as_str())
+}
+
+/// Whether the .sub file is MicroDVD or SubViewer
+fn is_text_sub(path: &Path) -> bool {
+    let mut head = Vec::new();
+    let read = std::fs::File::open(path)
+        .and_then(|file| file.take(SUB_SNIFF_LENGTH).read_to_end(&mut head));
+    if read.is_err() || head.contains(&0) {
+        return false;
+    }
+    let head = String::from_utf8_lossy(&head);
+    let Some(first_line) = head
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+    else {
+        return false;
+    };
+    is_microdvd_line(first_line)
+        || first_line.eq_ignore_ascii_case("[INFORMATION]")
+        || is_subviewer_times(first_line)
+}
+
+/// `{start frame}{end frame}text`, the end may be left out
+fn is_microdvd_line(line: &str) -> bool {
+    let frame = |text: &str, allow_empty: bool| -> Option<usize> {
+        let end = text.strip_prefix('{')?.find('}')? + 1;
+        let number = &text[1..end];
+        (number.chars().all(|c| c.is_ascii_digit()) && (allow_empty || !number.is_empty()))
+            .then_some(end + 1)
+    };
+    frame(line, false).is_some_and(|end| frame(&line[end..], true).is_some())
+}
+
+/// `hh:mm:ss.cc,hh:mm:ss.cc`, the line before the text of each SubViewer cue
+fn is_subviewer_times(line: &str) -> bool {
+    let is_time = |time: &str| {
+        let bytes = time.as_bytes();
+        bytes.len() == 11
+            && bytes.iter().enumerate().all(|(index, byte)| match index {
+                2 | 5 => *byte == b':',
+                8 => *byte == b'.',
+                _ => byte.is_ascii_digit(),
+            })
+    };
+    line.split_once(',')
+        .is_some_and(|(start, end)| is_time(start) && is_time(end))
+}
+
+pub fn detect_encoding(bytes: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return encoding;
+    }
+
+    if std::str::from_utf8(bytes).is_ok() {
+        return encoding_rs::UTF_8;
+    }
+
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    detector.guess(None, true)
+}
+
+/// Converts the subtitle file to UTF-8, using `encoding` when given or a detected one otherwise
+pub fn load(path: &Path, encoding: Option<&'static Encoding>) -> Result<SubtitleFile, Error> {
+    let bytes = std::fs::read(path)?;
+    let encoding_overridden = encoding.is_some();
+    let encoding = encoding.unwrap_or_else(|| detect_encoding(&bytes));
+    let (text, _, _) = encoding.decode(&bytes);
+
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or("srt");
+    let name = format!("{:x}.{}", hasher.finish(), extension);
+    let converted_path = match dirs::cache_dir() {
+        Some(cache) => {
+            let folder = cache.join("wgpu-media-player").join("subtitles");
+            std::fs::create_dir_all(&folder)?;
+            let converted_path = folder.join(name);
+            std::fs::write(&converted_path, text.as_bytes())?;
+            converted_path
+        }
+        // others can write to the temp folder, so the copy has to be a new file instead of
+        // following whatever is already there
+        None => {
+            let converted_path = std::env::temp_dir().join(format!(
+                "wgpu-media-player-{}-{}-{}",
+                std::process::id(),
+                CONVERTED_COUNT.fetch_add(1, Ordering::Relaxed),
+                name
+            ));
+            std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&converted_path)?
+                .write_all(text.as_bytes())?;
+            converted_path
+        }
+    };
+
+    Ok(SubtitleFile {
+        path: path.to_path_buf(),
+        encoding,
+        encoding_overridden,
+        converted_path,
+    })
+}
+
+/// Converts the subtitle file like `load` and reads its cues
+#[cfg(feature = "ui")]
+pub fn load_second(
+    path: &Path,
+    encoding: Option<&'static Encoding>,
+) -> Result<SecondSubtitle, Error> {
+    let file = load(path, encoding)?;
+    let cues = parse_cues(&std::fs::read_to_string(&file.converted_path)?);
+    if cues.is_empty() {
+        return Err(anyhow!("No SRT, WebVTT or ASS subtitles found"));
+    }
+    Ok(SecondSubtitle { file, cues })
+}
+
+/// The text of the cues shown at `position`, one line each
+#[cfg(feature = "ui")]
+pub fn text_at(cues: &[Cue], position: Duration) -> String {
+    // cues can overlap, so the ones that started before may still be shown
+    let started = cues.partition_point(|cue| cue.start <= position);
+    cues[..started]
+        .iter()
+        .filter(|cue| position < cue.end)
+        .map(|cue| cue.text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Reads SRT, WebVTT and ASS/SSA subtitles, formatting is dropped
+#[cfg(feature = "ui")]
+fn parse_cues(text: &str) -> Vec<Cue> {
+    let mut cues = if text.contains("[Events]") {
+        parse_ass(text)
+    } else {
+        parse_srt(text)
+    };
+    cues.sort_by_key(|cue| cue.start);
+    cues
+}
+
+/// SRT and WebVTT, a line with the times followed by the lines of text up to an empty line
+#[cfg(feature = "ui")]
+fn parse_srt(text: &str) -> Vec<Cue> {
+    let mut cues = Vec::new();
+    let mut lines = text.lines();
+    while let Some(line) = lines.next() {
+        let Some((start, end)) = line.split_once("-->") else {
+            continue;
+        };
+        // WebVTT puts cue settings after the end time
+        let end = end.split_whitespace().next().unwrap_or_default();
+        let (Some(start), Some(end)) = (parse_time(start), parse_time(end)) else {
+            continue;
+        };
+        let text = lines
+            .by_ref()
+            .take_while(|line| !line.trim().is_empty())
+            .map(strip_tags)
+            .collect::<Vec<_>>()
+            .join("\n");
+        cues.push(Cue { start, end, text });
+    }
+    cues
+}
+
+/// The Dialogue lines of the [Events] section, in the order of its Format line
+#[cfg(feature = "ui")]
+fn parse_ass(text: &str) -> Vec<Cue> {
+    let mut fields = vec!["Layer", "Start", "End", "Style", "Name", "Text"];
+    let mut cues = Vec::new();
+    for line in text.lines() {
+        if let Some(format) = line.strip_prefix("Format:") {
+            fields = format.split(',').map(str::trim).collect();
+            continue;
+        }
+        let Some(dialogue) = line.strip_prefix("Dialogue:") else {
+            continue;
+        };
+        // the text is the last field and can contain commas itself
+        let values: Vec<_> = dialogue.splitn(fields.len(), ',').collect();
+        let field = |name| {
+            fields
+                .iter()
+                .position(|field| *field == name)
+                .and_then(|index| values.get(index))
+        };
+        let (Some(start), Some(end), Some(text)) = (
+            field("Start").and_then(|time| parse_time(time)),
+            field("End").and_then(|time| parse_time(time)),
+            field("Text"),
+        ) else {
+            continue;
+        };
+        let text = strip_tags(&text.replace("\\N", "\n").replace("\\n", "\n"));
+        cues.push(Cue { start, end, text });
+    }
+    cues
+}
+
+/// `hours:minutes:seconds.fraction` with optional hours, SRT separates the fraction with a
+/// comma
+#[cfg(feature = "ui")]
+fn parse_time(time: &str) -> Option<Duration> {
+    let time = time.trim().replace(',', ".");
+    let mut parts = time.rsplit(':');
+    let seconds: f64 = parts.next()?.parse().ok()?;
+    let minutes: u64 = parts.next()?.parse().ok()?;
+    let hours: u64 = parts.next().map_or(Some(0), |hours| hours.parse().ok())?;
+    // casting saturates, so garbage can't overflow
+    let millis = (seconds * 1000.0).round() as u64;
+    Some(Duration::from_secs(hours * 3600 + minutes * 60) + Duration::from_millis(millis))
+}
+
+/// Drops the <i> tags of SRT and WebVTT and the {\an8} overrides of ASS
+#[cfg(feature = "ui")]
+fn strip_tags(line: &str) -> String {
+    let mut text = String::with_capacity(line.len());
+    let mut closing = None;
+    for character in line.chars() {
+        match (closing, character) {
+            (None, '<') => closing = Some('>'),
+            (None, '{') => closing = Some('}'),
+            (Some(end), _) if character == end => closing = None,
+            (None, _) => text.push(character),
+            _ => {}
+        }
+    }
+    text.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_text_sub_lines() {
+        assert!(is_microdvd_line("{100}{200}Hello|World"));
+        assert!(is_microdvd_line("{1}{}Until the next one"));
+        assert!(!is_microdvd_line("{}{200}No start"));
+        assert!(!is_microdvd_line("{a}{200}Not a frame"));
+        assert!(!is_microdvd_line("{100}"));
+        assert!(is_subviewer_times("00:00:01.50,00:00:03.00"));
+        assert!(!is_subviewer_times("00:00:01,500 --> 00:00:03,000"));
+        assert!(!is_subviewer_times("00:00:01.50"));
+    }
+
+    #[test]
+    fn tells_vobsub_from_text_subs() {
+        let folder = std::env::temp_dir().join(format!("subtitles-test-{}", std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+        let write = |name: &str, bytes: &[u8]| {
+            let path = folder.join(name);
+            std::fs::write(&path, bytes).unwrap();
+            path
+        };
+        assert!(is_subtitle_file(&write(
+            "micro.sub",
+            b"\xef\xbb\xbf\n{0}{25}Hi\n"
+        )));
+        assert!(is_subtitle_file(&write(
+            "viewer.SUB",
+            b"[INFORMATION]\n[TITLE]x\n"
+        )));
+        assert!(!is_subtitle_file(&write(
+            "vobsub.sub",
+            b"\x00\x00\x01\xba\x44"
+        )));
+        assert!(!is_subtitle_file(&write("empty.sub", b"")));
+        assert!(!is_subtitle_file(&folder.join("missing.sub")));
+        assert!(is_subtitle_file(&folder.join("missing.SRT")));
+        assert!(!is_subtitle_file(&folder.join("video.mkv")));
+        std::fs::remove_dir_all(&folder).ok();
+    }
+}
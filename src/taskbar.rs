@@ -0,0 +1,281 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::{anyhow, Context, Error};
+use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
+use windows::{
+    w,
+    Win32::{
+        Foundation::{HWND, LPARAM, LRESULT, WPARAM},
+        System::Com::{
+            CoCreateInstance, CoInitializeEx, CLSCTX_INPROC_SERVER, COINIT_APARTMENTTHREADED,
+        },
+        UI::{
+            Shell::{
+                DefSubclassProc, ITaskbarList3, RemoveWindowSubclass, SetWindowSubclass,
+                TaskbarList, TBPF_NOPROGRESS, TBPF_NORMAL, TBPF_PAUSED, THBF_ENABLED, THBN_CLICKED,
+                THB_FLAGS, THB_ICON, THB_TOOLTIP, THUMBBUTTON,
+            },
+            WindowsAndMessaging::{
+                CreateIcon, DestroyIcon, RegisterWindowMessageW, HICON, WM_COMMAND,
+            },
+        },
+    },
+};
+use winit::window::Window;
+
+use crate::media_keys::MediaKey;
+
+const ICON_SIZE: i32 = 16;
+/// Any id will do, the window has only one subclass of ours
+const SUBCLASS_ID: usize = 1;
+/// Progress is reported in steps of this, so it isn't updated for every frame
+const PROGRESS_STEPS: u64 = 1000;
+
+/// The thumbnail toolbar, in this order
+const BUTTONS: [MediaKey; 3] = [MediaKey::Previous, MediaKey::PlayPause, MediaKey::Next];
+
+/// Shared with the window procedure
+struct Subclass {
+    on_button: Box<dyn Fn(MediaKey)>,
+    /// The message explorer sends when it (re)creates the taskbar button
+    button_created_message: u32,
+    /// Set when the button was recreated, e.g. after explorer restarted, which drops the toolbar
+    button_recreated: AtomicBool,
+}
+
+struct Icons {
+    previous: HICON,
+    play: HICON,
+    pause: HICON,
+    next: HICON,
+}
+
+/// Playback progress on the taskbar button and play/pause and skip buttons in the thumbnail
+/// that pops up above it, with ITaskbarList3
+pub struct Taskbar {
+    hwnd: HWND,
+    taskbar: ITaskbarList3,
+    subclass: Box<Subclass>,
+    icons: Icons,
+    buttons_added: bool,
+    /// Last reported progress step and paused state, so the taskbar is only told about changes
+    reported: Option<(Option<u64>, bool)>,
+}
+
+impl Taskbar {
+    /// `on_button` is called on the event loop thread, from within its window procedure
+    pub fn new(window: &Window, on_button: impl Fn(MediaKey) + 'static) -> Result<Self, Error> {
+        let RawWindowHandle::Win32(handle) = window.raw_window_handle() else {
+            return Err(anyhow!("Not a Win32 window"));
+        };
+        let hwnd = HWND(handle.hwnd as isize);
+
+        // winit already initialized COM on this thread for drag and drop, which is fine too
+        unsafe { CoInitializeEx(None, COINIT_APARTMENTTHREADED) }.ok();
+        let taskbar: ITaskbarList3 = unsafe {
+            CoCreateInstance(&TaskbarList, None, CLSCTX_INPROC_SERVER)
+                .context("Failed to create the taskbar list")?
+        };
+        unsafe { taskbar.HrInit() }.context("Failed to initialize the taskbar list")?;
+
+        let subclass = Box::new(Subclass {
+            on_button: Box::new(on_button),
+            button_created_message: unsafe { RegisterWindowMessageW(w!("TaskbarButtonCreated")) },
+            button_recreated: AtomicBool::new(false),
+        });
+        let subclassed = unsafe {
+            SetWindowSubclass(
+                hwnd,
+                Some(subclass_proc),
+                SUBCLASS_ID,
+                &*subclass as *const Subclass as usize,
+            )
+        };
+        if !subclassed.as_bool() {
+            return Err(anyhow!("Failed to subclass the window"));
+        }
+
+        let mut taskbar = Self {
+            hwnd,
+            taskbar,
+            subclass,
+            icons: Icons::new()?,
+            buttons_added: false,
+            reported: None,
+        };
+        // fails when the taskbar button doesn't exist yet, it is added once it is created
+        taskbar.add_buttons();
+        Ok(taskbar)
+    }
+
+    /// `progress` from 0 to 1, None hides the progress
+    pub fn set_state(&mut self, progress: Option<f64>, paused: bool) {
+        if self
+            .subclass
+            .button_recreated
+            .swap(false, Ordering::Relaxed)
+        {
+            self.buttons_added = false;
+            self.reported = None;
+        }
+        if !self.buttons_added {
+            self.add_buttons();
+        }
+
+        let step = progress.map(|progress| (progress * PROGRESS_STEPS as f64).round() as u64);
+        let state = (step, paused);
+        if self.reported == Some(state) {
+            return;
+        }
+
+        let result = unsafe {
+            match step {
+                None => self.taskbar.SetProgressState(self.hwnd, TBPF_NOPROGRESS),
+                Some(step) => self
+                    .taskbar
+                    .SetProgressState(self.hwnd, if paused { TBPF_PAUSED } else { TBPF_NORMAL })
+                    .and_then(|()| {
+                        self.taskbar
+                            .SetProgressValue(self.hwnd, step, PROGRESS_STEPS)
+                    }),
+            }
+        };
+        if let Err(err) = result {
+            tracing::debug!("Failed to report the progress on the taskbar: {}", err);
+        }
+        if self.buttons_added && self.reported.map(|(_, paused)| paused) != Some(paused) {
+            let buttons = self.buttons(paused);
+            if let Err(err) = unsafe { self.taskbar.ThumbBarUpdateButtons(self.hwnd, &buttons) } {
+                tracing::debug!("Failed to update the thumbnail buttons: {}", err);
+            }
+        }
+        self.reported = Some(state);
+    }
+
+    fn add_buttons(&mut self) {
+        let paused = self.reported.map_or(true, |(_, paused)| paused);
+        let buttons = self.buttons(paused);
+        match unsafe { self.taskbar.ThumbBarAddButtons(self.hwnd, &buttons) } {
+            Ok(()) => self.buttons_added = true,
+            Err(err) => tracing::debug!("Failed to add the thumbnail buttons: {}", err),
+        }
+    }
+
+    fn buttons(&self, paused: bool) -> [THUMBBUTTON; 3] {
+        BUTTONS.map(|key| {
+            let (icon, tooltip) = match key {
+                MediaKey::Previous => (self.icons.previous, "Previous"),
+                MediaKey::Next => (self.icons.next, "Next"),
+                _ if paused => (self.icons.play, "Play"),
+                _ => (self.icons.pause, "Pause"),
+            };
+            let mut button = THUMBBUTTON {
+                dwMask: THB_ICON | THB_TOOLTIP | THB_FLAGS,
+                iId: button_id(key),
+                hIcon: icon,
+                dwFlags: THBF_ENABLED,
+                ..Default::default()
+            };
+            for (dst, src) in button.szTip.iter_mut().zip(tooltip.encode_utf16()) {
+                *dst = src;
+            }
+            button
+        })
+    }
+}
+
+impl Drop for Taskbar {
+    fn drop(&mut self) {
+        unsafe {
+            RemoveWindowSubclass(self.hwnd, Some(subclass_proc), SUBCLASS_ID);
+            self.taskbar
+                .SetProgressState(self.hwnd, TBPF_NOPROGRESS)
+                .ok();
+        }
+    }
+}
+
+fn button_id(key: MediaKey) -> u32 {
+    BUTTONS.iter().position(|&button| button == key).unwrap() as u32
+}
+
+unsafe extern "system" fn subclass_proc(
+    hwnd: HWND,
+    message: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+    _id: usize,
+    data: usize,
+) -> LRESULT {
+    let subclass = &*(data as *const Subclass);
+    if message == WM_COMMAND && (wparam.0 >> 16) as u32 & 0xffff == THBN_CLICKED {
+        if let Some(&key) = BUTTONS.get(wparam.0 & 0xffff) {
+            (subclass.on_button)(key);
+            return LRESULT(0);
+        }
+    }
+    if message == subclass.button_created_message {
+        subclass.button_recreated.store(true, Ordering::Relaxed);
+    }
+    DefSubclassProc(hwnd, message, wparam, lparam)
+}
+
+impl Icons {
+    fn new() -> Result<Self, Error> {
+        Ok(Self {
+            previous: create_icon(|x, y| bar(x, 3.0) || triangle(x, y, false))?,
+            play: create_icon(|x, y| triangle(x, y, true))?,
+            pause: create_icon(|x, _| bar(x, 4.0) || bar(x, 10.0))?,
+            next: create_icon(|x, y| bar(x, 11.0) || triangle(x, y, true))?,
+        })
+    }
+}
+
+impl Drop for Icons {
+    fn drop(&mut self) {
+        for icon in [self.previous, self.play, self.pause, self.next] {
+            unsafe { DestroyIcon(icon) };
+        }
+    }
+}
+
+/// A 2 pixel wide vertical bar starting at `left`
+fn bar(x: f32, left: f32) -> bool {
+    (left..left + 2.0).contains(&x)
+}
+
+/// A triangle pointing right or left, in the middle of the icon
+fn triangle(x: f32, y: f32, right: bool) -> bool {
+    let size = ICON_SIZE as f32;
+    let x = if right { x } else { size - x };
+    x >= 5.0 && (y - size / 2.0).abs() <= (12.0 - x) * 0.8
+}
+
+/// A white icon, `inside` is called with the center of every pixel
+fn create_icon(inside: impl Fn(f32, f32) -> bool) -> Result<HICON, Error> {
+    let mut bgra = Vec::with_capacity((ICON_SIZE * ICON_SIZE * 4) as usize);
+    for y in 0..ICON_SIZE {
+        for x in 0..ICON_SIZE {
+            let alpha = if inside(x as f32 + 0.5, y as f32 + 0.5) {
+                255
+            } else {
+                0
+            };
+            bgra.extend([alpha; 4]);
+        }
+    }
+    // the alpha channel does the masking, the and mask stays empty
+    let and_mask = vec![0u8; (ICON_SIZE * ICON_SIZE / 8) as usize];
+    unsafe {
+        CreateIcon(
+            None,
+            ICON_SIZE,
+            ICON_SIZE,
+            1,
+            32,
+            and_mask.as_ptr(),
+            bgra.as_ptr(),
+        )
+    }
+    .context("Failed to create a thumbnail button icon")
+}
@@ -7,13 +7,18 @@ pub struct Texture {
 }
 
 impl Texture {
-    pub fn new(device: &wgpu::Device, dimensions: (u32, u32), label: Option<&str>) -> Result<Self> {
+    pub fn new(
+        device: &wgpu::Device,
+        dimensions: (u32, u32),
+        format: wgpu::TextureFormat,
+        label: Option<&str>,
+    ) -> Result<Self> {
         let size = wgpu::Extent3d {
             width: dimensions.0,
             height: dimensions.1,
             depth_or_array_layers: 1,
         };
-        let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+        let encoded_format = format.remove_srgb_suffix();
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             label,
             size,
@@ -21,8 +26,15 @@ impl Texture {
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-            view_formats: &[],
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::COPY_SRC,
+            // compute passes read the raw, still srgb encoded values
+            view_formats: if encoded_format != format {
+                std::slice::from_ref(&encoded_format)
+            } else {
+                &[]
+            },
         });
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
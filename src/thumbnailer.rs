@@ -0,0 +1,120 @@
+use anyhow::{anyhow, Error};
+use crossbeam_channel::{Sender, TrySendError};
+use gst::prelude::*;
+
+use crate::cue;
+
+const THUMBNAIL_WIDTH: i32 = 320;
+/// Uris waiting for their thumbnail, more are skipped until the worker catches up
+const QUEUE_LENGTH: usize = 256;
+
+pub struct Thumbnail {
+    pub uri: String,
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+/// Grabs a frame at 10% of the media on a worker thread, one file at a time, and sends the
+/// thumbnails once they are decoded. The worker stops when this is dropped.
+pub struct Thumbnailer {
+    uri_sender: Sender<String>,
+}
+
+impl Thumbnailer {
+    pub fn new(thumbnail_sender: Sender<Thumbnail>) -> Self {
+        let (uri_sender, uri_receiver) = crossbeam_channel::bounded::<String>(QUEUE_LENGTH);
+        std::thread::spawn(move || {
+            for uri in uri_receiver {
+                match create_thumbnail(cue::split_track_uri(&uri).0) {
+                    Ok(mut thumbnail) => {
+                        // tracks of a CUE sheet share the file, the playlist looks them up by
+                        // their own uri
+                        thumbnail.uri = uri;
+                        if thumbnail_sender.send(thumbnail).is_err() {
+                            return;
+                        }
+                    }
+                    Err(err) => tracing::warn!("Failed to create thumbnail for {}: {}", uri, err),
+                }
+            }
+        });
+        Self { uri_sender }
+    }
+
+    /// Queues a local file, streams would be opened a second time and devices, the standard
+    /// input and listening sockets would be taken away from the player
+    pub fn request(&self, uri: &str) {
+        if !cue::split_track_uri(uri).0.starts_with("file://") {
+            return;
+        }
+        if let Err(TrySendError::Full(uri)) = self.uri_sender.try_send(uri.to_string()) {
+            tracing::debug!("Skipping the thumbnail of {}, too many are queued", uri);
+        }
+    }
+}
+
+/// Grabs the frame right away, on the calling thread
+pub fn create_thumbnail(uri: &str) -> Result<Thumbnail, Error> {
+    gst::init()?;
+
+    let videosink = gst_app::AppSink::builder()
+        .caps(
+            &gst::Caps::builder("video/x-raw")
+                .field("format", "RGBA")
+                .field("width", THUMBNAIL_WIDTH)
+                .field("pixel-aspect-ratio", gst::Fraction::new(1, 1))
+                .build(),
+        )
+        .build();
+    let audiosink = gst::ElementFactory::make("fakesink").build()?;
+
+    let pipeline = gst::ElementFactory::make("playbin")
+        .property("uri", uri)
+        .property("video-sink", &videosink)
+        .property("audio-sink", &audiosink)
+        .build()?;
+
+    let result = (|| -> Result<Thumbnail, Error> {
+        pipeline.set_state(gst::State::Paused)?;
+        pipeline
+            .state(gst::ClockTime::from_seconds(10))
+            .0
+            .map_err(|_| anyhow!("timed out while prerolling"))?;
+
+        if let Some(duration) = pipeline.query_duration::<gst::ClockTime>() {
+            pipeline.seek_simple(
+                gst::SeekFlags::FLUSH | gst::SeekFlags::KEY_UNIT,
+                gst::ClockTime::from_nseconds(duration.nseconds() / 10),
+            )?;
+            pipeline
+                .state(gst::ClockTime::from_seconds(10))
+                .0
+                .map_err(|_| anyhow!("timed out while seeking"))?;
+        }
+
+        let sample = videosink
+            .pull_preroll()
+            .map_err(|_| anyhow!("no video frame available"))?;
+        let info = gst_video::VideoInfo::from_caps(
+            sample
+                .caps()
+                .ok_or_else(|| anyhow!("sample without caps"))?,
+        )?;
+        let buffer = sample
+            .buffer()
+            .ok_or_else(|| anyhow!("sample without buffer"))?;
+        let map = buffer.map_readable()?;
+
+        Ok(Thumbnail {
+            uri: uri.to_string(),
+            width: info.width(),
+            height: info.height(),
+            data: map.as_slice().to_vec(),
+        })
+    })();
+
+    pipeline.set_state(gst::State::Null)?;
+
+    result
+}
@@ -0,0 +1,67 @@
+#[cfg(feature = "ui")]
+use std::time::{Duration, Instant};
+
+/// How long a toast stays up when it isn't dismissed
+#[cfg(feature = "ui")]
+const TOAST_DURATION: Duration = Duration::from_secs(10);
+
+#[cfg(feature = "ui")]
+struct Toast {
+    message: String,
+    shown_at: Instant,
+}
+
+/// Non-fatal errors, stacked above the controls in the bottom right corner until they expire or are dismissed
+#[derive(Default)]
+pub struct Toasts {
+    #[cfg(feature = "ui")]
+    toasts: Vec<Toast>,
+}
+
+impl Toasts {
+    #[cfg(feature = "ui")]
+    pub fn push(&mut self, message: String) {
+        self.toasts.push(Toast {
+            message,
+            shown_at: Instant::now(),
+        });
+    }
+
+    /// Without the ui there is nowhere to show it, so it goes to the log
+    #[cfg(not(feature = "ui"))]
+    pub fn push(&mut self, message: String) {
+        tracing::warn!("{}", message);
+    }
+
+    #[cfg(feature = "ui")]
+    pub fn ui(&mut self, ctx: &egui::Context) {
+        self.toasts
+            .retain(|toast| toast.shown_at.elapsed() < TOAST_DURATION);
+        if self.toasts.is_empty() {
+            return;
+        }
+
+        let mut dismissed = None;
+        egui::Area::new("toasts")
+            // clear of the statistics window and the seek bar
+            .anchor(egui::Align2::RIGHT_BOTTOM, [-8.0, -64.0])
+            .show(ctx, |ui| {
+                ui.set_max_width(360.0);
+                for (index, toast) in self.toasts.iter().enumerate() {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.colored_label(ui.visuals().error_fg_color, "⚠");
+                            ui.label(&toast.message);
+                            if ui.small_button("✖").on_hover_text("Dismiss").clicked() {
+                                dismissed = Some(index);
+                            }
+                        });
+                    });
+                }
+            });
+
+        if let Some(index) = dismissed {
+            self.toasts.remove(index);
+        }
+    }
+}
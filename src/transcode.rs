@@ -0,0 +1,90 @@
+use std::path::Path;
+
+use anyhow::Error;
+use gst_pbutils::prelude::*;
+
+use crate::{
+    config::TranscodePreset,
+    jobs::{self, JobContext},
+};
+
+/// Converts `uri` to a file of `preset` at `path`, the first video and audio stream are kept
+pub fn transcode(
+    uri: &str,
+    preset: TranscodePreset,
+    path: &Path,
+    context: &JobContext,
+) -> Result<(), Error> {
+    gst::init()?;
+    let pipeline = gst::Pipeline::new(Some("transcode"));
+    let decodebin = gst::ElementFactory::make("uridecodebin")
+        .property("uri", uri)
+        .build()?;
+    let encodebin = gst::ElementFactory::make("encodebin")
+        .property("profile", profile(preset))
+        .build()?;
+    let sink = gst::ElementFactory::make("filesink")
+        .property("location", path.to_string_lossy().as_ref())
+        .build()?;
+    pipeline.add_many(&[&decodebin, &encodebin, &sink])?;
+    encodebin.link(&sink)?;
+
+    let pipeline_weak = pipeline.downgrade();
+    decodebin.connect_pad_added(move |decodebin, pad| {
+        let Some(pipeline) = pipeline_weak.upgrade() else {
+            return;
+        };
+        let caps = pad.current_caps().unwrap_or_else(|| pad.query_caps(None));
+        let template = match caps.structure(0).map(|structure| structure.name().as_str()) {
+            Some("video/x-raw") => "video_%u",
+            Some("audio/x-raw") => "audio_%u",
+            _ => return jobs::discard(&pipeline, pad),
+        };
+        // the profile has room for one stream of each, the others are left out
+        let Some(encoder_pad) = encodebin.request_pad_simple(template) else {
+            return jobs::discard(&pipeline, pad);
+        };
+        if let Err(err) = pad.link(&encoder_pad) {
+            gst::element_error!(
+                decodebin,
+                gst::CoreError::Negotiation,
+                ["Failed to encode {}: {:?}", caps, err]
+            );
+        }
+    });
+
+    jobs::run_pipeline(&pipeline, (gst::ClockTime::ZERO, None), None, context)
+}
+
+/// The container and codecs of `preset`, encodebin picks the encoders and muxer for them
+fn profile(preset: TranscodePreset) -> gst_pbutils::EncodingContainerProfile {
+    let (container, video, audio) = match preset {
+        TranscodePreset::H264Mp4 => (
+            gst::Caps::builder("video/quicktime")
+                .field("variant", "iso")
+                .build(),
+            gst::Caps::builder("video/x-h264").build(),
+            gst::Caps::builder("audio/mpeg")
+                .field("mpegversion", 4i32)
+                .build(),
+        ),
+        TranscodePreset::Vp9Webm => (
+            gst::Caps::builder("video/webm").build(),
+            gst::Caps::builder("video/x-vp9").build(),
+            gst::Caps::builder("audio/x-opus").build(),
+        ),
+    };
+    gst_pbutils::EncodingContainerProfile::builder(&container)
+        .name(preset.label())
+        .add_profile(
+            gst_pbutils::EncodingVideoProfile::builder(&video)
+                .presence(1)
+                .build(),
+        )
+        .add_profile(
+            gst_pbutils::EncodingAudioProfile::builder(&audio)
+                .presence(1)
+                .build(),
+        )
+        .build()
+}
@@ -0,0 +1,231 @@
+use anyhow::Error;
+use tray_icon::{
+    menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem},
+    ClickType, Icon, TrayIcon, TrayIconBuilder, TrayIconEvent,
+};
+
+const ICON_SIZE: u32 = 32;
+
+/// What the menu of the tray icon can do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrayCommand {
+    TogglePause,
+    Previous,
+    Next,
+    ToggleMute,
+    /// Brings back the window, also sent when the icon itself is clicked
+    ShowWindow,
+    /// Hides the window and keeps playing the audio
+    HideWindow,
+    Quit,
+}
+
+impl TrayCommand {
+    const ALL: [TrayCommand; 7] = [
+        TrayCommand::TogglePause,
+        TrayCommand::Previous,
+        TrayCommand::Next,
+        TrayCommand::ToggleMute,
+        TrayCommand::ShowWindow,
+        TrayCommand::HideWindow,
+        TrayCommand::Quit,
+    ];
+
+    /// Id of its menu item
+    fn id(&self) -> &'static str {
+        match self {
+            TrayCommand::TogglePause => "toggle_pause",
+            TrayCommand::Previous => "previous",
+            TrayCommand::Next => "next",
+            TrayCommand::ToggleMute => "toggle_mute",
+            TrayCommand::ShowWindow => "show_window",
+            TrayCommand::HideWindow => "hide_window",
+            TrayCommand::Quit => "quit",
+        }
+    }
+}
+
+/// Shown in the tooltip and the menu of the icon
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrayState {
+    pub title: Option<String>,
+    pub paused: bool,
+    pub muted: bool,
+}
+
+/// The icon with the items that change with the state
+struct TrayMenu {
+    icon: TrayIcon,
+    toggle_pause: MenuItem,
+    toggle_mute: MenuItem,
+}
+
+impl TrayMenu {
+    fn new() -> Result<Self, Error> {
+        let item =
+            |command: TrayCommand, text: &str| MenuItem::with_id(command.id(), text, true, None);
+        let toggle_pause = item(TrayCommand::TogglePause, "Pause");
+        let toggle_mute = item(TrayCommand::ToggleMute, "Mute");
+        let menu = Menu::with_items(&[
+            &toggle_pause,
+            &item(TrayCommand::Previous, "Previous"),
+            &item(TrayCommand::Next, "Next"),
+            &toggle_mute,
+            &PredefinedMenuItem::separator(),
+            &item(TrayCommand::ShowWindow, "Show window"),
+            &item(TrayCommand::HideWindow, "Play in background"),
+            &item(TrayCommand::Quit, "Quit"),
+        ])?;
+        let icon = TrayIconBuilder::new()
+            .with_menu(Box::new(menu))
+            .with_tooltip("wgpu media player")
+            .with_icon(Icon::from_rgba(icon_rgba(), ICON_SIZE, ICON_SIZE)?)
+            .build()?;
+
+        Ok(Self {
+            icon,
+            toggle_pause,
+            toggle_mute,
+        })
+    }
+
+    fn apply(&self, state: &TrayState) {
+        self.toggle_pause
+            .set_text(if state.paused { "Play" } else { "Pause" });
+        self.toggle_mute
+            .set_text(if state.muted { "Unmute" } else { "Mute" });
+        let tooltip = match (&state.title, state.paused) {
+            (Some(title), true) => format!("{} (paused)", title),
+            (Some(title), false) => title.clone(),
+            (None, _) => "wgpu media player".to_string(),
+        };
+        if let Err(err) = self.icon.set_tooltip(Some(tooltip)) {
+            tracing::debug!("Failed to update the tray tooltip: {}", err);
+        }
+    }
+}
+
+/// An icon in the system tray with the playback controls, so the player can be controlled while
+/// its window is hidden
+pub struct Tray {
+    /// On Linux the icon lives on a thread running the gtk main loop, which the state is sent to
+    #[cfg(target_os = "linux")]
+    state_sender: crossbeam_channel::Sender<TrayState>,
+    #[cfg(not(target_os = "linux"))]
+    menu: TrayMenu,
+    state: Option<TrayState>,
+}
+
+impl Tray {
+    /// `on_command` is called from a background thread
+    pub fn new(on_command: impl Fn(TrayCommand) + Send + 'static) -> Result<Self, Error> {
+        #[cfg(target_os = "linux")]
+        let state_sender = spawn_gtk_thread()?;
+        #[cfg(not(target_os = "linux"))]
+        let menu = TrayMenu::new()?;
+
+        // the events of the icon and its menu go to global channels, which never close
+        std::thread::spawn(move || {
+            let menu_events = MenuEvent::receiver();
+            let icon_events = TrayIconEvent::receiver();
+            loop {
+                let command = crossbeam_channel::select! {
+                    recv(menu_events) -> event => event.ok().and_then(|event| {
+                        TrayCommand::ALL
+                            .into_iter()
+                            .find(|command| event.id == command.id())
+                    }),
+                    recv(icon_events) -> event => event
+                        .ok()
+                        .filter(|event| event.click_type == ClickType::Left)
+                        .map(|_| TrayCommand::ShowWindow),
+                };
+                if let Some(command) = command {
+                    on_command(command);
+                }
+            }
+        });
+
+        Ok(Self {
+            #[cfg(target_os = "linux")]
+            state_sender,
+            #[cfg(not(target_os = "linux"))]
+            menu,
+            state: None,
+        })
+    }
+
+    pub fn set_state(&mut self, state: TrayState) {
+        if self.state.as_ref() == Some(&state) {
+            return;
+        }
+
+        #[cfg(target_os = "linux")]
+        self.state_sender.send(state.clone()).ok();
+        #[cfg(not(target_os = "linux"))]
+        self.menu.apply(&state);
+        self.state = Some(state);
+    }
+}
+
+/// gtk objects have to be created and used on the thread that initialized gtk
+#[cfg(target_os = "linux")]
+fn spawn_gtk_thread() -> Result<crossbeam_channel::Sender<TrayState>, Error> {
+    use gtk::glib;
+    use std::time::Duration;
+
+    let (state_sender, state_receiver) = crossbeam_channel::unbounded::<TrayState>();
+    let (result_sender, result_receiver) = crossbeam_channel::bounded(1);
+    std::thread::spawn(move || {
+        let menu = gtk::init()
+            .map_err(Error::from)
+            .and_then(|()| TrayMenu::new());
+        let menu = match menu {
+            Ok(menu) => {
+                result_sender.send(Ok(())).ok();
+                menu
+            }
+            Err(err) => {
+                result_sender.send(Err(err)).ok();
+                return;
+            }
+        };
+
+        glib::timeout_add_local(Duration::from_millis(100), move || {
+            if let Some(state) = state_receiver.try_iter().last() {
+                menu.apply(&state);
+            }
+            glib::Continue(true)
+        });
+        gtk::main();
+    });
+
+    result_receiver
+        .recv()
+        .map_err(|_| anyhow::anyhow!("The tray thread exited"))??;
+    Ok(state_sender)
+}
+
+/// A white play triangle on a dark circle
+fn icon_rgba() -> Vec<u8> {
+    let size = ICON_SIZE as f32;
+    let mut rgba = Vec::with_capacity((ICON_SIZE * ICON_SIZE * 4) as usize);
+    for y in 0..ICON_SIZE {
+        for x in 0..ICON_SIZE {
+            let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+            let radius = ((px - size / 2.0).powi(2) + (py - size / 2.0).powi(2)).sqrt();
+            // pointing right, centered a bit to the right to look balanced
+            let in_triangle =
+                px >= size * 0.38 && (py - size / 2.0).abs() <= (size * 0.74 - px) * 0.6;
+            let pixel = if in_triangle {
+                [255, 255, 255, 255]
+            } else if radius <= size / 2.0 {
+                [40, 40, 40, 255]
+            } else {
+                [0, 0, 0, 0]
+            };
+            rgba.extend(pixel);
+        }
+    }
+    rgba
+}
@@ -0,0 +1,149 @@
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::error::PlayerError;
+
+/// Declares what a user shader can use, put in front of the file
+const PRELUDE: &str = include_str!("user_shader_prelude.wgsl");
+/// Makes the shader write the size the video is shown at instead of the input size
+const DISPLAY_OUTPUT_DIRECTIVE: &str = "// output: display";
+const ENTRY_POINT: &str = "main";
+
+/// A filter loaded from a .wgsl file, compiled again whenever the file changes.
+///
+/// A version that doesn't compile is reported and the previous one keeps running, so a shader
+/// can be edited while watching the result.
+pub struct UserShader {
+    path: PathBuf,
+    /// Modification time of the file when it was last loaded
+    modified: Option<SystemTime>,
+    loaded: bool,
+    pipeline: Option<wgpu::ComputePipeline>,
+    workgroup_size: [u32; 3],
+    display_output: bool,
+}
+
+impl UserShader {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            modified: None,
+            loaded: false,
+            pipeline: None,
+            workgroup_size: [1; 3],
+            display_output: false,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// None until a version of the file compiled
+    pub fn pipeline(&self) -> Option<&wgpu::ComputePipeline> {
+        self.pipeline.as_ref()
+    }
+
+    pub fn workgroup_size(&self) -> [u32; 3] {
+        self.workgroup_size
+    }
+
+    pub fn display_output(&self) -> bool {
+        self.display_output
+    }
+
+    /// Compiles the file when it changed since it was last loaded, returns whether the pipeline
+    /// was replaced
+    pub fn reload_if_changed(
+        &mut self,
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+    ) -> Result<bool, PlayerError> {
+        let modified = std::fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        if self.loaded && modified == self.modified {
+            return Ok(false);
+        }
+        self.loaded = true;
+        self.modified = modified;
+
+        let source = std::fs::read_to_string(&self.path).map_err(|err| PlayerError::Shader {
+            path: self.path.clone(),
+            message: err.to_string(),
+        })?;
+        self.compile(device, layout, &source)
+            .map_err(|message| PlayerError::Shader {
+                path: self.path.clone(),
+                message,
+            })?;
+        Ok(true)
+    }
+
+    fn compile(
+        &mut self,
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        source: &str,
+    ) -> Result<(), String> {
+        let full_source = format!("{}{}", PRELUDE, source);
+        // wgpu panics on invalid shaders, so they are checked with naga first
+        let module = naga::front::wgsl::parse_str(&full_source).map_err(|err| {
+            // point at the line in the user's file, not in the prelude
+            let line = err
+                .location(&full_source)
+                .map(|location| location.line_number as usize)
+                .filter(|&line| line > PRELUDE.lines().count());
+            match line {
+                Some(line) => format!("line {}: {}", line - PRELUDE.lines().count(), err.message()),
+                None => err.message().to_string(),
+            }
+        })?;
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::empty(),
+        )
+        .validate(&module)
+        .map_err(|err| error_chain(&err.into_inner()))?;
+        let entry_point = module
+            .entry_points
+            .iter()
+            .find(|entry_point| {
+                entry_point.name == ENTRY_POINT && entry_point.stage == naga::ShaderStage::Compute
+            })
+            .ok_or_else(|| format!("there is no @compute fn {}", ENTRY_POINT))?;
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("User Shader"),
+            source: wgpu::ShaderSource::Wgsl(full_source.as_str().into()),
+        });
+        self.pipeline = Some(
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("User Shader Pipeline"),
+                layout: Some(layout),
+                module: &shader,
+                entry_point: ENTRY_POINT,
+            }),
+        );
+        self.workgroup_size = entry_point.workgroup_size;
+        self.display_output = source
+            .lines()
+            .any(|line| line.trim() == DISPLAY_OUTPUT_DIRECTIVE);
+        Ok(())
+    }
+}
+
+/// naga's errors only say which function is invalid, the reason is in their sources
+fn error_chain(err: &dyn Error) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        message.push_str(": ");
+        message.push_str(&err.to_string());
+        source = err.source();
+    }
+    message
+}
@@ -0,0 +1,24 @@
+// Put in front of every user shader, see user_shader.rs.
+//
+// A user shader is a compute shader with a `main` entry point that writes every pixel of
+// `output_frame`. Both frames hold srgb encoded values. The output has the size of the input,
+// unless the shader contains the line `// output: display`, then it has the size the video is
+// shown at, for writing scalers.
+
+struct FrameInfo {
+    input_size: vec2<f32>,
+    output_size: vec2<f32>,
+    // seconds since the player started, for animated effects
+    time: f32,
+}
+
+@group(0) @binding(0)
+var input_frame: texture_2d<f32>;
+@group(0) @binding(1)
+var output_frame: texture_storage_2d<rgba8unorm, write>;
+// linear filtering, clamped to the edges
+@group(0) @binding(3)
+var input_sampler: sampler;
+@group(0) @binding(4)
+var<uniform> frame: FrameInfo;
+
@@ -0,0 +1,216 @@
+use anyhow::{anyhow, Error};
+use winit::{
+    dpi::{LogicalSize, PhysicalPosition, PhysicalSize},
+    event_loop::EventLoopWindowTarget,
+    window::{Fullscreen, Window, WindowId, WindowLevel},
+};
+
+use crate::renderer::{DrawTarget, VideoRenderer, INDICES};
+
+/// Size of the picture-in-picture window when it opens
+const PICTURE_IN_PICTURE_SIZE: LogicalSize<f64> = LogicalSize::new(480.0, 270.0);
+/// Distance of the picture-in-picture window to the corner of the monitor
+const PICTURE_IN_PICTURE_MARGIN: f64 = 32.0;
+
+/// A window besides the main one that only shows the video, so the main window can be used for
+/// the controls. Either a projector, borderless fullscreen on another monitor while presenting,
+/// or picture-in-picture, small and on top of the other windows.
+pub struct VideoWindow {
+    // declared before the window so it is dropped first
+    surface: wgpu::Surface,
+    config: wgpu::SurfaceConfiguration,
+    window: Window,
+    draw_target: DrawTarget,
+}
+
+impl VideoWindow {
+    /// Opens on the first monitor that isn't showing the main window, or on the same one when
+    /// there is only one. The surface has to use `format` to work with the video pipeline.
+    pub fn open_projector<T>(
+        target: &EventLoopWindowTarget<T>,
+        main_window: &Window,
+        instance: &wgpu::Instance,
+        adapter: &wgpu::Adapter,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+    ) -> Result<Self, Error> {
+        let current_monitor = main_window.current_monitor();
+        let monitor = target
+            .available_monitors()
+            .find(|monitor| Some(monitor) != current_monitor.as_ref())
+            .or(current_monitor);
+
+        let window = winit::window::WindowBuilder::new()
+            .with_title("wgpu-media-player projector")
+            .with_fullscreen(Some(Fullscreen::Borderless(monitor)))
+            .build(target)?;
+
+        Self::new(
+            window,
+            DrawTarget::Projector,
+            instance,
+            adapter,
+            device,
+            format,
+        )
+    }
+
+    /// Opens in the bottom right corner of the monitor showing the main window
+    pub fn open_picture_in_picture<T>(
+        target: &EventLoopWindowTarget<T>,
+        main_window: &Window,
+        instance: &wgpu::Instance,
+        adapter: &wgpu::Adapter,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+    ) -> Result<Self, Error> {
+        let mut builder = winit::window::WindowBuilder::new()
+            .with_title("wgpu-media-player picture-in-picture")
+            .with_inner_size(PICTURE_IN_PICTURE_SIZE)
+            .with_window_level(WindowLevel::AlwaysOnTop);
+        if let Some(monitor) = main_window.current_monitor() {
+            let scale_factor = monitor.scale_factor();
+            let size: PhysicalSize<i32> = PICTURE_IN_PICTURE_SIZE.to_physical(scale_factor);
+            let margin = (PICTURE_IN_PICTURE_MARGIN * scale_factor) as i32;
+            let (origin, extent) = (monitor.position(), monitor.size());
+            builder = builder.with_position(PhysicalPosition::new(
+                origin.x + extent.width as i32 - size.width - margin,
+                origin.y + extent.height as i32 - size.height - margin,
+            ));
+        }
+        let window = builder.build(target)?;
+
+        Self::new(
+            window,
+            DrawTarget::PictureInPicture,
+            instance,
+            adapter,
+            device,
+            format,
+        )
+    }
+
+    fn new(
+        window: Window,
+        draw_target: DrawTarget,
+        instance: &wgpu::Instance,
+        adapter: &wgpu::Adapter,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+    ) -> Result<Self, Error> {
+        let surface = unsafe { instance.create_surface(&window) }?;
+        if !surface.get_capabilities(adapter).formats.contains(&format) {
+            return Err(anyhow!(
+                "the surface of the {:?} window doesn't support {:?}",
+                draw_target,
+                format
+            ));
+        }
+
+        let size = window.inner_size();
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: size.width,
+            height: size.height,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: vec![format],
+        };
+        surface.configure(device, &config);
+
+        Ok(Self {
+            surface,
+            config,
+            window,
+            draw_target,
+        })
+    }
+
+    pub fn window_id(&self) -> WindowId {
+        self.window.id()
+    }
+
+    pub fn request_redraw(&self) {
+        self.window.request_redraw();
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, size: PhysicalSize<u32>) {
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+
+        self.config.width = size.width;
+        self.config.height = size.height;
+        self.surface.configure(device, &self.config);
+    }
+
+    /// Draws the current video frame on black
+    pub fn render(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        renderer: Option<&VideoRenderer>,
+    ) {
+        let frame = match self.surface.get_current_texture() {
+            Ok(frame) => frame,
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                self.surface.configure(device, &self.config);
+                self.window.request_redraw();
+                return;
+            }
+            Err(err) => {
+                tracing::warn!(
+                    "Failed to acquire the frame of the {:?} window: {}",
+                    self.draw_target,
+                    err
+                );
+                return;
+            }
+        };
+        let view = frame
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        if let Some(renderer) = renderer {
+            renderer.write_transform(queue, self.draw_target, self.window.inner_size());
+        }
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Video Window"),
+        });
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Video Window Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+
+            if let Some(renderer) = renderer {
+                render_pass.set_pipeline(renderer.pipeline(false));
+                render_pass.set_bind_group(0, renderer.current_bind_group(), &[]);
+                render_pass.set_bind_group(1, &renderer.lut_bind_group, &[]);
+                render_pass.set_bind_group(
+                    2,
+                    &renderer.transform_bind_group,
+                    &[renderer.transform_offset(self.draw_target)],
+                );
+                render_pass.set_vertex_buffer(0, renderer.vertex_buffer.slice(..));
+                render_pass
+                    .set_index_buffer(renderer.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+                render_pass.draw_indexed(0..INDICES.len() as u32, 0, 0..1);
+                renderer.draw_subtitles(&mut render_pass, self.draw_target);
+            }
+        }
+
+        queue.submit(Some(encoder.finish()));
+        frame.present();
+    }
+}